@@ -80,6 +80,6 @@ export default [
   },
   prettier,
   {
-    ignores: ['dist/**', 'node_modules/**', 'src-tauri/**'],
+    ignores: ['dist/**', 'node_modules/**', 'src-tauri/**', 'src/bindings.ts'],
   },
 ];
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::State;
+use crate::error::AppError;
+use crate::status::AuditAction;
+use crate::DatabaseState;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// RFC 3339, UTC
+    pub timestamp: String,
+    /// OS login of whoever ran the app
+    pub user: String,
+    pub action: AuditAction,
+    pub batch_id: Option<String>,
+    pub details: Option<String>,
+}
+
+/// Criteria for `get_audit_log`; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct AuditLogFilter {
+    pub action: Option<AuditAction>,
+    pub batch_id: Option<String>,
+    pub user: Option<String>,
+    /// Inclusive lower bound, RFC 3339
+    pub from: Option<String>,
+    /// Exclusive upper bound, RFC 3339
+    pub to: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Get audit log entries, newest first
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_audit_log(
+    filter: AuditLogFilter,
+    db: State<DatabaseState>,
+) -> Result<Vec<AuditEntry>, AppError> {
+    db.0.get_audit_log(&filter)
+}
@@ -0,0 +1,59 @@
+use tauri::State;
+use crate::error::AppError;
+use crate::services::cloud_drive::{self, DeviceLogin};
+use crate::services::settings_service::SettingsService;
+use crate::services::http::{HttpClients, HttpTimeouts};
+use crate::status::AuditAction;
+use crate::DatabaseState;
+
+/// Start signing in to the drive selected in Settings
+///
+/// # Returns
+/// The code to enter at `verification_url`; pass it to `complete_cloud_login`
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn start_cloud_login(settings: State<'_, SettingsService>) -> Result<DeviceLogin, AppError> {
+    let config = settings.get().cloud_config();
+    let http = HttpClients::new(HttpTimeouts::default())?;
+
+    cloud_drive::start_device_login(http.async_client(), &config).await
+}
+
+/// Wait until the user approved the device code, then keep the sign-in
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn complete_cloud_login(
+    login: DeviceLogin,
+    settings: State<'_, SettingsService>,
+    db: State<'_, DatabaseState>,
+) -> Result<(), AppError> {
+    let config = settings.get().cloud_config();
+    let http = HttpClients::new(HttpTimeouts::default())?;
+
+    let refresh_token = cloud_drive::finish_device_login(http.async_client(), &config, &login).await?;
+    db.0.set_cloud_refresh_token(Some(&refresh_token))?;
+    db.0.record_audit(
+        AuditAction::SettingsChanged,
+        None,
+        Some(&format!("cloud drive connected: {}", config.provider.label())),
+    )
+}
+
+/// Forget the cloud drive sign-in
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn disconnect_cloud_drive(db: State<DatabaseState>) -> Result<(), AppError> {
+    db.0.set_cloud_refresh_token(None)?;
+    db.0.record_audit(AuditAction::SettingsChanged, None, Some("cloud drive disconnected"))
+}
+
+/// Whether a cloud drive sign-in is stored
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_cloud_drive_status(db: State<DatabaseState>) -> Result<bool, AppError> {
+    Ok(db.0.get_cloud_refresh_token()?.is_some())
+}
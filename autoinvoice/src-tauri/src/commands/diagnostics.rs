@@ -0,0 +1,133 @@
+use serde::Serialize;
+use specta::Type;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use crate::error::AppError;
+use crate::services::browser::{BrowserViewport, SelectorMatch, VnptBrowser};
+use crate::services::compatibility::{self, SelectorCompatibility};
+use crate::services::metrics::{self, StageMetrics};
+use crate::services::selector_config::SelectorConfig;
+use crate::status::AuditAction;
+use crate::DatabaseState;
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct PageInspection {
+    pub url: String,
+    pub selectors: Vec<SelectorMatch>,
+}
+
+/// Copy the repro bundle of a failed invoice to a user-chosen location
+///
+/// # Arguments
+/// * `invoice_id` - Invoice whose bundle should be exported
+/// * `destination` - Target directory or file path
+///
+/// # Returns
+/// * Path of the exported zip file
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn export_repro_bundle(
+    invoice_id: String,
+    destination: String,
+    db: State<DatabaseState>,
+) -> Result<String, AppError> {
+    let invoice = db
+        .0
+        .get_invoice(&invoice_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Invoice not found: {}", invoice_id)))?;
+
+    let source = invoice.repro_path.map(PathBuf::from).ok_or_else(|| {
+        AppError::ConfigError(format!("No repro bundle recorded for invoice {}", invoice.code))
+    })?;
+
+    if !source.exists() {
+        return Err(AppError::IoError(format!(
+            "Repro bundle no longer exists: {}",
+            source.display()
+        )));
+    }
+
+    let destination = Path::new(&destination);
+    let target = if destination.is_dir() {
+        destination.join(source.file_name().unwrap_or_default())
+    } else {
+        destination.to_path_buf()
+    };
+
+    std::fs::copy(&source, &target)?;
+
+    let target = target.to_string_lossy().to_string();
+    db.0.record_audit(
+        AuditAction::Export,
+        Some(&invoice.batch_id),
+        Some(&format!("Repro bundle of {}: {}", invoice.code, target)),
+    )?;
+
+    Ok(target)
+}
+
+/// Open a portal page and report which known selectors currently match
+///
+/// # Arguments
+/// * `url` - Portal lookup page to inspect
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn inspect_page(app: AppHandle, url: String) -> Result<PageInspection, AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+
+    tokio::task::spawn_blocking(move || {
+        let browser = VnptBrowser::new(true, &BrowserViewport::default())?;
+        browser.set_selector_overrides(SelectorConfig::load(&app_data_dir)?.overrides);
+        browser.navigate_to_search(&url)?;
+
+        Ok(PageInspection {
+            url: browser.current_url().unwrap_or(url),
+            selectors: browser.inspect_selectors(),
+        })
+    })
+    .await
+    .map_err(|e| AppError::BrowserError(format!("Task panicked: {}", e)))?
+}
+
+/// Compare the local selector version with the minimum published for the
+/// current portal HTML
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn check_selector_compatibility(app: AppHandle) -> Result<SelectorCompatibility, AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+
+    compatibility::check(&app_data_dir).await
+}
+
+/// Per-stage download timings recorded on this machine, to help tune
+/// timeouts and delays; empty unless metrics collection is enabled
+///
+/// # Arguments
+/// * `since` - Only include timings recorded from this RFC 3339 time
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_performance_metrics(
+    since: Option<String>,
+    db: State<DatabaseState>,
+) -> Result<Vec<StageMetrics>, AppError> {
+    let timings = db.0.get_stage_timings(since.as_deref())?;
+    Ok(metrics::summarize(&timings))
+}
+
+/// Delete all recorded stage timings
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn clear_performance_metrics(db: State<DatabaseState>) -> Result<(), AppError> {
+    db.0.clear_stage_timings()
+}
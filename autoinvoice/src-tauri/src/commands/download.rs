@@ -1,74 +1,753 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::Instrument;
 
+use crate::services::browser_pool::BrowserPool;
+use crate::services::database::Database;
+use crate::services::estimator::{self, BatchEstimate};
+use crate::services::inbox;
+use crate::services::journal::{self, BatchJournal, UnfinishedBatch};
+use crate::services::local_captcha::LocalCaptchaModel;
+use crate::commands::settings::Settings;
+use crate::services::path_policy::PathPolicy;
+use crate::services::settings_service::SettingsService;
+use crate::services::tracing_log;
+use crate::services::manual_captcha::{ManualAnswer, ManualCaptchas, PendingCaptcha};
+use crate::services::portal_profiles::PortalSlots;
+use crate::services::retry_later::{self, RetryLaterInvoice};
 use crate::services::downloader::{
-    BatchResult, DownloadConfig, DownloadOrchestrator, InvoiceDownloadRequest,
+    ActiveDownload, BatchLabel, BatchResult, DownloadConfig, DownloadOrchestrator, InvoiceDownloadRequest,
 };
 use crate::error::AppError;
+use crate::events::{self, BatchCompletedEvent, DownloadsCancelledEvent, LogEvent, LogLevel};
+use crate::status::{AuditAction, BatchStatus};
+use crate::DatabaseState;
 
-/// State to track active download orchestrators
+/// State to track active download orchestrators and the warm browser
 pub struct DownloadState {
     pub orchestrators: Arc<Mutex<HashMap<String, Arc<DownloadOrchestrator>>>>,
+    /// Batches claimed by a start that is still setting them up, not yet in
+    /// `orchestrators`
+    starting: Arc<std::sync::Mutex<HashSet<String>>>,
+    pub browser_pool: Arc<BrowserPool>,
+    /// Invoices waiting for the user to solve their captcha
+    pub manual_captchas: Arc<ManualCaptchas>,
+    /// Debug logging for batches started from now on, see `set_log_level`
+    pub debug_logging: Arc<AtomicBool>,
+    /// Invoices being downloaded per portal host, across batches
+    pub portal_slots: Arc<PortalSlots>,
 }
 
 impl Default for DownloadState {
     fn default() -> Self {
         Self {
             orchestrators: Arc::new(Mutex::new(HashMap::new())),
+            starting: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            browser_pool: Arc::new(BrowserPool::default()),
+            manual_captchas: Arc::new(ManualCaptchas::default()),
+            debug_logging: Arc::new(AtomicBool::new(false)),
+            portal_slots: Arc::new(PortalSlots::default()),
         }
     }
 }
 
-#[derive(serde::Deserialize)]
+impl DownloadState {
+    /// Reserve `batch_id` for a new run, unless it is running or another
+    /// start is setting it up; the lock is only held for the check
+    pub(crate) async fn claim(&self, batch_id: &str) -> Claim {
+        let orchestrators = self.orchestrators.lock().await;
+        if let Some(running) = orchestrators.get(batch_id) {
+            return Claim::Running(running.clone());
+        }
+        if !self.starting.lock().unwrap().insert(batch_id.to_string()) {
+            return Claim::Starting;
+        }
+        Claim::Won(StartClaim {
+            starting: self.starting.clone(),
+            batch_id: batch_id.to_string(),
+        })
+    }
+
+    /// Ids of the batches running or being started
+    pub(crate) async fn active_batch_ids(&self) -> HashSet<String> {
+        let mut ids: HashSet<String> = self.orchestrators.lock().await.keys().cloned().collect();
+        ids.extend(self.starting.lock().unwrap().iter().cloned());
+        ids
+    }
+}
+
+/// Answer to `DownloadState::claim`
+pub(crate) enum Claim {
+    /// The caller starts the batch
+    Won(StartClaim),
+    Running(Arc<DownloadOrchestrator>),
+    /// Another start has the batch and is still setting it up
+    Starting,
+}
+
+/// A batch reserved for one start; dropping it frees the batch id, so a
+/// start that fails before its orchestrator is registered can be retried
+pub(crate) struct StartClaim {
+    starting: Arc<std::sync::Mutex<HashSet<String>>>,
+    batch_id: String,
+}
+
+impl Drop for StartClaim {
+    fn drop(&mut self) {
+        self.starting.lock().unwrap().remove(&self.batch_id);
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, specta::Type)]
 pub struct StartDownloadRequest {
     pub batch_id: String,
+    /// Human-readable name, e.g. "Hóa đơn đầu vào T5"
+    #[serde(default)]
+    pub name: Option<String>,
+    /// File name of the Excel sheet the invoices came from
+    #[serde(default)]
+    pub source_file: Option<String>,
+    /// Path and SHA-256 of that sheet, from `parse_excel`
+    #[serde(default)]
+    pub source_path: Option<String>,
+    #[serde(default)]
+    pub source_hash: Option<String>,
     pub invoices: Vec<InvoiceDownloadRequest>,
-    pub config: DownloadConfig,
+    /// Per-batch changes to the saved settings
+    #[serde(default)]
+    pub overrides: DownloadOverrides,
+}
+
+impl StartDownloadRequest {
+    /// Name and source file, with blank values dropped
+    fn label(&self) -> BatchLabel {
+        let non_blank = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        BatchLabel {
+            name: non_blank(&self.name),
+            source_file: non_blank(&self.source_file),
+            source_path: non_blank(&self.source_path),
+            source_hash: non_blank(&self.source_hash),
+        }
+    }
+
+    /// The source file is later read again by `reparse_source`, so it must
+    /// be one the user picked
+    fn check_source(&self, policy: &PathPolicy) -> Result<(), AppError> {
+        match self.label().source_path {
+            Some(source_path) => policy.check_granted(&source_path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Settings a single batch may change; everything else comes from the saved settings
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct DownloadOverrides {
+    /// Portal detected in the uploaded sheet
+    pub vnpt_url: Option<String>,
+    pub download_directory: Option<String>,
+    /// Show the browser, e.g. to watch a batch that keeps failing
+    pub headless: Option<bool>,
+    /// Attempts per invoice before it is marked failed
+    pub max_retries: Option<u32>,
+    /// Record invoices needing a manual captcha as `needs-manual` instead of waiting
+    pub defer_manual_captchas: Option<bool>,
+    /// Automatic pass first, then the deferred captchas one by one
+    pub two_pass: Option<bool>,
+    /// Save what the portal served as fixtures for replaying the run offline
+    pub record_fixtures: Option<bool>,
 }
 
-/// Start downloading a batch of invoices
+/// Build the config of a batch from the saved settings and its overrides
+fn build_config(settings: &Settings, overrides: &DownloadOverrides) -> Result<DownloadConfig, AppError> {
+    let mut builder = DownloadConfig::builder(settings);
+
+    if let Some(vnpt_url) = &overrides.vnpt_url {
+        builder = builder.vnpt_url(vnpt_url);
+    }
+    if let Some(download_directory) = &overrides.download_directory {
+        builder = builder.download_directory(download_directory);
+    }
+    if let Some(headless) = overrides.headless {
+        builder = builder.headless(headless);
+    }
+    if let Some(max_retries) = overrides.max_retries {
+        builder = builder.max_retries(max_retries);
+    }
+    if let Some(defer) = overrides.defer_manual_captchas {
+        builder = builder.defer_manual_captchas(defer);
+    }
+    if let Some(two_pass) = overrides.two_pass {
+        builder = builder.two_pass(two_pass);
+    }
+    if let Some(record_fixtures) = overrides.record_fixtures {
+        builder = builder.record_fixtures(record_fixtures);
+    }
+
+    builder.build()
+}
+
+/// Acknowledgement of a batch started in the background, e.g. by
+/// `start_download`; the outcome comes with `batch:completed`
+#[derive(Debug, Clone, serde::Serialize, specta::Type)]
+pub struct BatchStarted {
+    pub batch_id: String,
+    /// False when the batch was already running or finished, e.g. for a
+    /// start retried after an IPC timeout
+    pub started: bool,
+    pub status: BatchStatus,
+}
+
+/// Start downloading a batch of invoices in the background
+///
+/// Safe to retry with the same `batch_id`: a batch already running or
+/// finished is not started again. Settings errors are returned here; the
+/// batch itself reports through its events and `get_batch_result`
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %request.batch_id))]
 pub async fn start_download(
     app: AppHandle,
     state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
+    policy: State<'_, PathPolicy>,
     request: StartDownloadRequest,
+) -> Result<BatchStarted, AppError> {
+    request.check_source(&policy)?;
+    let batch_id = request.batch_id.clone();
+    let acknowledge = |started: bool, status: BatchStatus| BatchStarted {
+        batch_id: batch_id.clone(),
+        started,
+        status,
+    };
+
+    // Claimed before spawning, so a retried start cannot run the batch twice
+    let claim = match state.claim(&batch_id).await {
+        Claim::Won(claim) => claim,
+        Claim::Running(running) => return Ok(acknowledge(false, running.activity().status)),
+        Claim::Starting => return Ok(acknowledge(false, BatchStatus::Running)),
+    };
+    if let Some(status) = finished_status(&db.0, &batch_id)? {
+        return Ok(acknowledge(false, status));
+    }
+    build_config(&app.state::<SettingsService>().get(), &request.overrides)?;
+
+    spawn_download(&app, db.0.clone(), request, claim);
+    Ok(acknowledge(true, BatchStatus::Running))
+}
+
+/// Status of a batch that already ran to its end, if it did
+fn finished_status(db: &Database, batch_id: &str) -> Result<Option<BatchStatus>, AppError> {
+    Ok(db.get_batch(batch_id)?.map(|batch| batch.status).filter(|status| {
+        matches!(
+            status,
+            BatchStatus::Completed | BatchStatus::Cancelled | BatchStatus::Failed
+        )
+    }))
+}
+
+/// Run a claimed batch in the background, logging under the request id of
+/// the calling command; the outcome is sent as `batch:completed`
+pub(crate) fn spawn_download(app: &AppHandle, db: Arc<Database>, request: StartDownloadRequest, claim: StartClaim) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(
+        async move {
+            let state = handle.state::<DownloadState>();
+            let _ = run_claimed_download(&handle, &state, db, request, claim).await;
+        }
+        .in_current_span(),
+    );
+}
+
+/// Summary of a batch and its invoices: as far as it got while it runs,
+/// final once `batch:completed` was sent
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn get_batch_result(
+    state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
+    batch_id: String,
 ) -> Result<BatchResult, AppError> {
-    let orchestrator = Arc::new(DownloadOrchestrator::new(
-        request.config,
-        request.batch_id.clone(),
-    ));
+    if let Some(running) = state.orchestrators.lock().await.get(&batch_id) {
+        return running.live_result();
+    }
+    stored_result(&db.0, &batch_id)
+}
 
-    // Store orchestrator for potential cancellation
-    {
-        let mut orchestrators = state.orchestrators.lock().await;
-        orchestrators.insert(request.batch_id.clone(), orchestrator.clone());
+/// Summary of a batch as recorded in the database
+fn stored_result(db: &Database, batch_id: &str) -> Result<BatchResult, AppError> {
+    let batch = db
+        .get_batch(batch_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Batch not found: {}", batch_id)))?;
+    let invoices = db.get_batch_invoices(&batch.id)?;
+    Ok(BatchResult::from_invoices(
+        batch.id,
+        batch.name,
+        batch.status,
+        batch.total_count,
+        invoices,
+    ))
+}
+
+/// Run a batch to its end and send `batch:completed`; a batch already
+/// running is not started twice, its state so far is returned instead
+#[tracing::instrument(skip_all, fields(batch_id = %request.batch_id))]
+pub(crate) async fn run_download(
+    app: &AppHandle,
+    state: &DownloadState,
+    db: Arc<Database>,
+    request: StartDownloadRequest,
+) -> Result<BatchResult, AppError> {
+    let claim = match state.claim(&request.batch_id).await {
+        Claim::Won(claim) => claim,
+        Claim::Running(running) => return running.live_result(),
+        Claim::Starting => {
+            return Err(AppError::ConfigError(format!(
+                "Batch {} is already being started",
+                request.batch_id
+            )));
+        }
+    };
+    run_claimed_download(app, state, db, request, claim).await
+}
+
+/// Run a batch claimed with `DownloadState::claim` to its end and send
+/// `batch:completed`
+pub(crate) async fn run_claimed_download(
+    app: &AppHandle,
+    state: &DownloadState,
+    db: Arc<Database>,
+    request: StartDownloadRequest,
+    claim: StartClaim,
+) -> Result<BatchResult, AppError> {
+    let batch_id = request.batch_id.clone();
+    let result = run_new_download(app, state, db, request, claim).await;
+    emit_batch_completed(app, batch_id, &result);
+    result
+}
+
+/// Send `batch:completed` for a batch that ran, or could not start
+pub(crate) fn emit_batch_completed(app: &AppHandle, batch_id: String, result: &Result<BatchResult, AppError>) {
+    let event = match result {
+        Ok(result) => BatchCompletedEvent {
+            batch_id,
+            batch_name: result.batch_name.clone(),
+            status: result.status,
+            total: result.total,
+            success_count: result.success_count,
+            failed_count: result.failed_count,
+            error: None,
+        },
+        Err(e) => BatchCompletedEvent {
+            batch_id,
+            batch_name: None,
+            status: BatchStatus::Failed,
+            total: 0,
+            success_count: 0,
+            failed_count: 0,
+            error: Some(e.to_string()),
+        },
+    };
+    let _ = app.emit(events::BATCH_COMPLETED, event);
+}
+
+async fn run_new_download(
+    app: &AppHandle,
+    state: &DownloadState,
+    db: Arc<Database>,
+    request: StartDownloadRequest,
+    claim: StartClaim,
+) -> Result<BatchResult, AppError> {
+    // The same batch may have run to its end since the caller checked
+    if finished_status(&db, &request.batch_id)?.is_some() {
+        return stored_result(&db, &request.batch_id);
     }
 
+    let settings = app.state::<SettingsService>().get();
+    let mut config = build_config(&settings, &request.overrides)?;
+    config.debug_logging = state.debug_logging.load(Ordering::SeqCst);
+
+    db.record_audit(
+        AuditAction::BatchStarted,
+        Some(&request.batch_id),
+        Some(&format!("{} invoices", request.invoices.len())),
+    )?;
+
+    let journal = open_journal(app, &request).map(Arc::new);
+    let local_model = if config.local_captcha_solver && !config.demo_mode {
+        // Reading and optimizing the model is slow, keep it off the runtime
+        let (handle, batch_id) = (app.clone(), request.batch_id.clone());
+        let span = tracing::Span::current();
+        tokio::task::spawn_blocking(move || span.in_scope(|| load_local_captcha_model(&handle, &batch_id)))
+            .await
+            .ok()
+            .flatten()
+    } else {
+        None
+    };
+    let mut orchestrator = DownloadOrchestrator::new(
+        config,
+        request.batch_id.clone(),
+        db,
+        state.browser_pool.clone(),
+        state.manual_captchas.clone(),
+    )?
+    .with_source(request.label())
+    .with_portal_slots(state.portal_slots.clone());
+    if let Some(journal) = &journal {
+        orchestrator = orchestrator.with_journal(journal.clone());
+    }
+    if let Some(model) = local_model {
+        orchestrator = orchestrator.with_local_captcha_model(Arc::new(model));
+    }
+    let orchestrator = Arc::new(orchestrator);
+
+    // Store orchestrator for potential cancellation; it answers for the
+    // batch from now on, in place of the claim
+    state
+        .orchestrators
+        .lock()
+        .await
+        .insert(request.batch_id.clone(), orchestrator.clone());
+    drop(claim);
+
     // Run download
-    let result = orchestrator.download_batch(&app, request.invoices).await;
+    let result = orchestrator.download_batch(app, request.invoices).await;
 
     // Remove orchestrator after completion
     {
         let mut orchestrators = state.orchestrators.lock().await;
         orchestrators.remove(&request.batch_id);
     }
+    drop(orchestrator);
+
+    // The batch ended cleanly, whatever its outcome
+    if let Some(journal) = journal.and_then(|journal| Arc::try_unwrap(journal).ok()) {
+        journal.close();
+    }
 
     result
 }
 
+/// The offline captcha model; when there is none or it does not load, the
+/// batch uses the AI provider alone and says so in its log
+fn load_local_captcha_model(app: &AppHandle, batch_id: &str) -> Option<LocalCaptchaModel> {
+    let loaded = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))
+        .and_then(|app_data_dir| {
+            let resource_dir = app.path().resource_dir().ok();
+            LocalCaptchaModel::find(&app_data_dir, resource_dir.as_deref())
+                .ok_or_else(|| AppError::ConfigError("no captcha model is installed".to_string()))
+        })
+        .and_then(|dir| LocalCaptchaModel::load(&dir));
+    let (level, message) = match &loaded {
+        Ok(model) => ("info", format!("Offline captcha model: {}", model.path().display())),
+        Err(e) => ("warn", format!("Offline captcha solver unavailable, using the AI provider: {}", e)),
+    };
+    tracing_log::trace_message(level, &message);
+    let _ = app.emit(
+        events::DOWNLOAD_LOG,
+        LogEvent {
+            batch_id: batch_id.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: level.to_string(),
+            message,
+            request_id: tracing_log::current_request_id(),
+        },
+    );
+    loaded.ok()
+}
+
+/// Journal a batch in the app data dir; without one the batch still runs,
+/// it just cannot be resumed after a crash
+fn open_journal(app: &AppHandle, request: &StartDownloadRequest) -> Option<BatchJournal> {
+    let app_data_dir = app.path().app_data_dir().ok()?;
+    let serialized = serde_json::to_string(request).ok()?;
+    BatchJournal::create(
+        &app_data_dir,
+        &request.batch_id,
+        request.label().name.as_deref(),
+        request.invoices.len() as u32,
+        &serialized,
+    )
+    .ok()
+}
+
+/// Batches that were running when the app last quit uncleanly; batches
+/// running now, whose journal is their live one, are left out
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn get_unfinished_batches(
+    app: AppHandle,
+    state: State<'_, DownloadState>,
+) -> Result<Vec<UnfinishedBatch>, AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(format!("Cannot locate app data directory: {}", e)))?;
+    let active = state.active_batch_ids().await;
+    Ok(journal::unfinished(&app_data_dir)
+        .into_iter()
+        .filter(|batch| !active.contains(&batch.batch_id))
+        .collect())
+}
+
+/// Continue an unfinished batch from its journal in the background, skipping
+/// the invoices the journal shows as done even when the database missed their
+/// final status; the outcome comes with `batch:completed`
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn resume_batch(
+    app: AppHandle,
+    state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
+    batch_id: String,
+) -> Result<BatchStarted, AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(format!("Cannot locate app data directory: {}", e)))?;
+    let claim = match state.claim(&batch_id).await {
+        Claim::Won(claim) => claim,
+        Claim::Running(running) => {
+            return Ok(BatchStarted {
+                batch_id,
+                started: false,
+                status: running.activity().status,
+            });
+        }
+        Claim::Starting => {
+            return Ok(BatchStarted {
+                batch_id,
+                started: false,
+                status: BatchStatus::Running,
+            });
+        }
+    };
+    // The journal is only dropped once the batch is ours and can start
+    let (serialized, done) = journal::read_request(&app_data_dir, &batch_id)?;
+    let mut request: StartDownloadRequest = serde_json::from_str(&serialized)
+        .map_err(|e| AppError::ConfigError(format!("Invalid journaled download request: {}", e)))?;
+    request.invoices.retain(|invoice| !done.contains(&invoice.id));
+    build_config(&app.state::<SettingsService>().get(), &request.overrides)?;
+    journal::discard(&app_data_dir, &batch_id);
+
+    spawn_download(&app, db.0.clone(), request, claim);
+    Ok(BatchStarted {
+        batch_id,
+        started: true,
+        status: BatchStatus::Running,
+    })
+}
+
+/// Forget an unfinished batch instead of resuming it; a running batch's
+/// journal is its live one and is kept
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn discard_unfinished_batch(
+    app: AppHandle,
+    state: State<'_, DownloadState>,
+    batch_id: String,
+) -> Result<(), AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(format!("Cannot locate app data directory: {}", e)))?;
+    if state.active_batch_ids().await.contains(&batch_id) {
+        return Err(AppError::ConfigError(format!(
+            "Batch {} is running and cannot be discarded",
+            batch_id
+        )));
+    }
+    journal::discard(&app_data_dir, &batch_id);
+    Ok(())
+}
+
+/// Record a batch without starting it, e.g. while offline; it stays queued
+/// until started with `start_queued_download` or automatically once online
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn queue_download(
+    state: State<DownloadState>,
+    settings: State<SettingsService>,
+    db: State<DatabaseState>,
+    policy: State<PathPolicy>,
+    request: StartDownloadRequest,
+) -> Result<(), AppError> {
+    request.check_source(&policy)?;
+    queue_request(&state, &settings.get(), &db.0, &request)
+}
+
+/// Record the batch and its invoices as queued; queueing it again adds the
+/// invoices it did not have yet
+pub fn queue_request(
+    state: &DownloadState,
+    settings: &Settings,
+    db: &Arc<Database>,
+    request: &StartDownloadRequest,
+) -> Result<(), AppError> {
+    let orchestrator = DownloadOrchestrator::new(
+        build_config(settings, &request.overrides)?,
+        request.batch_id.clone(),
+        db.clone(),
+        state.browser_pool.clone(),
+        state.manual_captchas.clone(),
+    )?
+    .with_source(request.label());
+    orchestrator.record_batch(&request.invoices)?;
+
+    let serialized = serde_json::to_string(request)
+        .map_err(|e| AppError::ConfigError(format!("Failed to serialize download request: {}", e)))?;
+    db.queue_download(&request.batch_id, &serialized)
+}
+
+/// Read the mailbox now instead of waiting for the next poll
+///
+/// # Returns
+/// Number of invoices added to the inbox batch
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn check_inbox(
+    app: AppHandle,
+    settings: State<'_, SettingsService>,
+    db: State<'_, DatabaseState>,
+) -> Result<u32, AppError> {
+    let config = settings.get().imap_config();
+    let added = inbox::poll(&app, &db.0, config).await?;
+    Ok(added as u32)
+}
+
+/// Not-found invoices waiting to be looked up again, oldest first
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_retry_later_invoices(db: State<'_, DatabaseState>) -> Result<Vec<RetryLaterInvoice>, AppError> {
+    retry_later::pending(&db.0)
+}
+
+/// Start a batch previously recorded with `queue_download` in the
+/// background, like `start_download`; the outcome comes with
+/// `batch:completed`
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn start_queued_download(
+    app: AppHandle,
+    state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
+    batch_id: String,
+) -> Result<BatchStarted, AppError> {
+    let acknowledge = |started: bool, status: BatchStatus| BatchStarted {
+        batch_id: batch_id.clone(),
+        started,
+        status,
+    };
+
+    let claim = match state.claim(&batch_id).await {
+        Claim::Won(claim) => claim,
+        Claim::Running(running) => return Ok(acknowledge(false, running.activity().status)),
+        Claim::Starting => return Ok(acknowledge(false, BatchStatus::Running)),
+    };
+    if let Some(status) = finished_status(&db.0, &batch_id)? {
+        return Ok(acknowledge(false, status));
+    }
+    let request = dequeue_request(&app, &db.0, &batch_id)?;
+
+    spawn_download(&app, db.0.clone(), request, claim);
+    Ok(acknowledge(true, BatchStatus::Running))
+}
+
+/// Start every queued batch, one after another
+pub async fn start_queued_downloads(app: &AppHandle, db: Arc<Database>) -> Result<(), AppError> {
+    let state = app.state::<DownloadState>();
+
+    for batch_id in db.get_queued_batch_ids()? {
+        let Claim::Won(claim) = state.claim(&batch_id).await else {
+            continue;
+        };
+        // A batch that cannot start stays queued and does not hold back the others
+        let request = match dequeue_request(app, &db, &batch_id) {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::warn!("Queued batch {} not started: {}", batch_id, e);
+                continue;
+            }
+        };
+        // A failed batch reports through its own events and history
+        let _ = run_claimed_download(app, &state, db.clone(), request, claim).await;
+    }
+
+    Ok(())
+}
+
+/// Request of a queued batch the caller claimed, taken off the queue once
+/// its settings build; until then the batch stays queued, to start later
+fn dequeue_request(app: &AppHandle, db: &Database, batch_id: &str) -> Result<StartDownloadRequest, AppError> {
+    let serialized = db.get_queued_download(batch_id)?.ok_or_else(|| {
+        AppError::ConfigError(format!("No queued download with batch_id: {}", batch_id))
+    })?;
+    let request: StartDownloadRequest = serde_json::from_str(&serialized)
+        .map_err(|e| AppError::ConfigError(format!("Invalid queued download request: {}", e)))?;
+
+    build_config(&app.state::<SettingsService>().get(), &request.overrides)?;
+    db.remove_queued_download(batch_id)?;
+    Ok(request)
+}
+
+/// Raise backend logging to `debug` (selector attempts, network responses)
+/// or lower it back to `info`; applies to batches started afterwards
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn set_log_level(level: LogLevel, state: State<DownloadState>) {
+    state
+        .debug_logging
+        .store(level == LogLevel::Debug, Ordering::SeqCst);
+    tracing_log::set_debug(level == LogLevel::Debug);
+}
+
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_log_level(state: State<DownloadState>) -> LogLevel {
+    if state.debug_logging.load(Ordering::SeqCst) {
+        LogLevel::Debug
+    } else {
+        LogLevel::Info
+    }
+}
+
 /// Cancel an active download batch
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
 pub async fn cancel_download(
     state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
     batch_id: String,
 ) -> Result<(), AppError> {
     let orchestrators = state.orchestrators.lock().await;
 
     if let Some(orchestrator) = orchestrators.get(&batch_id) {
         orchestrator.cancel();
-        Ok(())
+        db.0.record_audit(AuditAction::BatchCancelled, Some(&batch_id), None)
     } else {
         Err(AppError::ConfigError(format!(
             "No active download with batch_id: {}",
@@ -77,15 +756,208 @@ pub async fn cancel_download(
     }
 }
 
-/// Submit a manually solved captcha
+/// Batches running right now, oldest first, so a reloaded UI can re-attach
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn get_active_downloads(
+    state: State<'_, DownloadState>,
+) -> Result<Vec<ActiveDownload>, AppError> {
+    let orchestrators = state.orchestrators.lock().await;
+    let mut active: Vec<ActiveDownload> = orchestrators
+        .values()
+        .map(|orchestrator| orchestrator.activity())
+        .collect();
+    active.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Ok(active)
+}
+
+/// Cancel every running batch
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn cancel_all_downloads(
+    app: AppHandle,
+    state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
+) -> Result<Vec<String>, AppError> {
+    cancel_all(&app, &state, &db.0).await
+}
+
+/// Cancel every running batch and report them in one `downloads:cancelled`
+/// event, also used by the app menu
+pub async fn cancel_all(
+    app: &AppHandle,
+    state: &DownloadState,
+    db: &Database,
+) -> Result<Vec<String>, AppError> {
+    let mut batch_ids: Vec<String> = {
+        let orchestrators = state.orchestrators.lock().await;
+        orchestrators
+            .iter()
+            .map(|(batch_id, orchestrator)| {
+                orchestrator.cancel();
+                batch_id.clone()
+            })
+            .collect()
+    };
+    batch_ids.sort();
+
+    for batch_id in &batch_ids {
+        db.record_audit(AuditAction::BatchCancelled, Some(batch_id), None)?;
+    }
+    let _ = app.emit(
+        events::DOWNLOADS_CANCELLED,
+        DownloadsCancelledEvent {
+            batch_ids: batch_ids.clone(),
+        },
+    );
+
+    Ok(batch_ids)
+}
+
+/// Submit a manually solved captcha to the download waiting for it
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id, invoice_id = %invoice_id))]
 pub async fn submit_manual_captcha(
-    _state: State<'_, DownloadState>,
-    _batch_id: String,
-    _invoice_id: String,
-    _captcha_text: String,
+    state: State<'_, DownloadState>,
+    batch_id: String,
+    invoice_id: String,
+    captcha_text: String,
+) -> Result<(), AppError> {
+    let captcha_text = captcha_text.trim();
+    if captcha_text.is_empty() {
+        return Err(AppError::ConfigError("Captcha text is empty".to_string()));
+    }
+
+    state.manual_captchas.answer(
+        &batch_id,
+        &invoice_id,
+        ManualAnswer::Captcha(captcha_text.to_string()),
+    )
+}
+
+/// Skip an invoice waiting for a manual captcha; the batch moves on
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id, invoice_id = %invoice_id))]
+pub fn skip_manual_captcha(
+    state: State<DownloadState>,
+    batch_id: String,
+    invoice_id: String,
+) -> Result<(), AppError> {
+    state.manual_captchas.answer(&batch_id, &invoice_id, ManualAnswer::Skip)
+}
+
+/// Stop or resume asking for manual captchas in a running batch; while on,
+/// invoices needing one are recorded as `needs-manual` for a later pass,
+/// including those already waiting
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn set_defer_manual_captchas(
+    state: State<'_, DownloadState>,
+    batch_id: String,
+    enabled: bool,
+) -> Result<(), AppError> {
+    let orchestrators = state.orchestrators.lock().await;
+    let orchestrator = orchestrators.get(&batch_id).ok_or_else(|| {
+        AppError::ConfigError(format!("No active download with batch_id: {}", batch_id))
+    })?;
+    orchestrator.set_defer_manual_captchas(enabled);
+
+    if enabled {
+        for invoice_id in state.manual_captchas.waiting_in_batch(&batch_id) {
+            // The invoice may have stopped waiting in the meantime
+            let _ = state.manual_captchas.answer(&batch_id, &invoice_id, ManualAnswer::Defer);
+        }
+    }
+
+    Ok(())
+}
+
+/// Let a running batch go over its AI captcha budget; a batch paused on the
+/// budget resumes, one asking for manual captchas goes back to the API
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn continue_over_budget(
+    state: State<'_, DownloadState>,
+    batch_id: String,
 ) -> Result<(), AppError> {
-    // TODO: Implement manual captcha submission
-    // This would require a more complex state management to pause/resume downloads
+    let orchestrators = state.orchestrators.lock().await;
+    let orchestrator = orchestrators.get(&batch_id).ok_or_else(|| {
+        AppError::ConfigError(format!("No active download with batch_id: {}", batch_id))
+    })?;
+    orchestrator.continue_over_budget();
     Ok(())
 }
+
+/// Start or stop streaming screenshots of the tab a running batch works in,
+/// as `browser:preview` events
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn stream_browser_preview(
+    state: State<'_, DownloadState>,
+    batch_id: String,
+    enabled: bool,
+) -> Result<(), AppError> {
+    let orchestrators = state.orchestrators.lock().await;
+    let orchestrator = orchestrators.get(&batch_id).ok_or_else(|| {
+        AppError::ConfigError(format!("No active download with batch_id: {}", batch_id))
+    })?;
+
+    orchestrator.set_preview(enabled);
+    Ok(())
+}
+
+/// Invoices across all batches waiting for the user to solve their captcha,
+/// so they can be answered one after another
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_pending_captchas(state: State<DownloadState>) -> Vec<PendingCaptcha> {
+    state.manual_captchas.pending()
+}
+
+/// Predict how long a batch of `invoice_count` invoices will take and what it
+/// will cost, based on previously downloaded invoices
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn estimate_batch(
+    invoice_count: u32,
+    settings: State<SettingsService>,
+    db: State<DatabaseState>,
+) -> Result<BatchEstimate, AppError> {
+    let stats = db.0.get_invoice_stats()?;
+    let settings = settings.get();
+
+    Ok(estimator::estimate_batch(
+        &stats,
+        invoice_count,
+        settings.invoice_delay_secs,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_claim_blocks_a_second_start() {
+        let state = DownloadState::default();
+        let claim = match state.claim("batch").await {
+            Claim::Won(claim) => claim,
+            _ => panic!("a new batch is free to start"),
+        };
+        assert!(matches!(state.claim("batch").await, Claim::Starting));
+        assert!(matches!(state.claim("other").await, Claim::Won(_)));
+
+        // A start that fails before registering its orchestrator frees the batch
+        drop(claim);
+        assert!(matches!(state.claim("batch").await, Claim::Won(_)));
+    }
+}
@@ -1,14 +1,130 @@
-use crate::services::excel_parser::{parse_excel_file, ExcelParseResult};
+use serde::Serialize;
+use specta::Type;
+use std::path::Path;
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::commands::history::DownloadBatch;
+use crate::services::excel_parser::{parse_excel_file, parse_pdf_file, ExcelParseResult};
+use crate::services::demo;
+use crate::services::path_policy::PathPolicy;
+use crate::services::settings_service::SettingsService;
 use crate::error::AppError;
+use crate::DatabaseState;
+
+/// File picked in `choose_source_file`
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct SelectedFile {
+    /// Passed to `parse_excel` or `parse_pdf_for_codes` instead of the path
+    pub token: String,
+    pub file_name: String,
+}
+
+/// Let the user pick an Excel sheet or PDF to read invoices from
+///
+/// # Returns
+/// The picked file, or `None` when the dialog was cancelled
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn choose_source_file(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+) -> Result<Option<SelectedFile>, AppError> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("Excel Files", &["xlsx"])
+        .add_filter("PDF Files", &["pdf"])
+        .pick_file(move |file| {
+            let _ = tx.send(file);
+        });
+
+    let Some(file) = rx.await.ok().flatten() else {
+        return Ok(None);
+    };
+    let path = file
+        .into_path()
+        .map_err(|e| AppError::IoError(format!("Unsupported file: {}", e)))?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Ok(Some(SelectedFile {
+        token: policy.grant(path)?,
+        file_name,
+    }))
+}
 
 /// Parse an Excel file and extract invoice codes
 ///
 /// # Arguments
-/// * `file_path` - Path to the Excel file (.xlsx)
+/// * `file_token` - Token of the Excel file (.xlsx), from `choose_source_file`
 ///
 /// # Returns
 /// * `ExcelParseResult` containing invoice codes and optionally detected VNPT URL
 #[tauri::command]
-pub fn parse_excel(file_path: String) -> Result<ExcelParseResult, AppError> {
-    parse_excel_file(&file_path)
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn parse_excel(file_token: String, policy: State<PathPolicy>) -> Result<ExcelParseResult, AppError> {
+    parse_excel_file(&policy.resolve(&file_token)?.to_string_lossy())
+}
+
+/// Sample invoices to try the app with in demo mode, in place of a sheet
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn load_demo_invoices(settings: State<SettingsService>) -> Result<ExcelParseResult, AppError> {
+    // Downloaded for real, the sample codes would only be looked up in vain
+    if !settings.get().demo_mode {
+        return Err(AppError::ConfigError("Turn on demo mode in Settings first".to_string()));
+    }
+    Ok(demo::sample_invoices())
+}
+
+/// Extract lookup codes from the text of any PDF, for the same preview and
+/// download flow as an Excel file
+///
+/// # Arguments
+/// * `file_token` - Token of the PDF file, from `choose_source_file`
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn parse_pdf_for_codes(file_token: String, policy: State<PathPolicy>) -> Result<ExcelParseResult, AppError> {
+    parse_pdf_file(&policy.resolve(&file_token)?.to_string_lossy())
+}
+
+/// Parse the Excel file a batch was started from again, e.g. after fixing
+/// rows in it; compare `file_hash` with the batch's to see if it changed
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub fn reparse_source(batch_id: String, db: State<DatabaseState>) -> Result<ExcelParseResult, AppError> {
+    let batch = db
+        .0
+        .get_batch(&batch_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Batch not found: {}", batch_id)))?;
+    let source_path = batch.source_path.ok_or_else(|| {
+        AppError::ExcelError(format!("Batch {} has no recorded source file", batch_id))
+    })?;
+    if !Path::new(&source_path).exists() {
+        return Err(AppError::ExcelError(format!(
+            "Source file was moved or deleted: {}",
+            source_path
+        )));
+    }
+
+    parse_excel_file(&source_path)
+}
+
+/// Completed batches already downloaded from a file with this hash
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_batches_for_source(
+    file_hash: String,
+    db: State<DatabaseState>,
+) -> Result<Vec<DownloadBatch>, AppError> {
+    db.0.get_completed_batches_by_source(&file_hash)
 }
@@ -1,9 +1,26 @@
 use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+use std::path::Path;
 use tauri::State;
 use crate::error::AppError;
+use crate::services::anomalies::{self, InvoiceAnomaly};
+use crate::services::batch_compare::{self, BatchComparison};
+use crate::services::batch_report;
+use crate::services::disk_usage::{self, StorageReport};
+use crate::services::invoice_export::{self, CsvOptions, ExportFormat};
+use crate::services::invoice_xml::XmlInvoice;
+use crate::services::manifest::BatchManifest;
+use crate::services::pdf_metadata::InvoiceMetadata;
+use crate::services::pdf_optimize::PdfOptimization;
+use crate::services::vat_summary::{self, VatPeriod, VatSummary};
+use crate::services::xml_signature::SignatureCheck;
+use crate::services::zip_package::{self, BatchPackage};
+use crate::status::{AuditAction, BatchStatus, InvoiceStatus, Portal, SignatureStatus};
+use crate::services::settings_service::SettingsService;
 use crate::DatabaseState;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct DownloadBatch {
     pub id: String,
     pub created_at: String,
@@ -11,27 +28,62 @@ pub struct DownloadBatch {
     pub success_count: u32,
     pub failed_count: u32,
     pub download_directory: String,
+    pub status: BatchStatus,
+    /// Set on the per-portal sub-batches of a split batch
+    pub parent_id: Option<String>,
+    /// Config the batch ran with (JSON, without the API key), including
+    /// per-batch overrides
+    pub effective_config: Option<String>,
+    /// Name given when the batch was started, shown instead of the id
+    pub name: Option<String>,
+    /// File name of the Excel sheet the invoices came from
+    pub source_file: Option<String>,
+    /// Full path of that sheet, for `reparse_source`
+    pub source_path: Option<String>,
+    /// SHA-256 of the sheet when the batch was started
+    pub source_hash: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl DownloadBatch {
+    /// Name for user-facing text: the batch name, or its id when unnamed
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct HistoryInvoice {
     pub id: String,
     pub batch_id: String,
     pub code: String,
-    pub status: String,
+    pub status: InvoiceStatus,
     pub error: Option<String>,
     pub file_path: Option<String>,
     pub downloaded_at: Option<String>,
+    pub repro_path: Option<String>,
+    /// Portal that served the PDF
+    pub portal: Option<Portal>,
+    /// Object URL when the PDF was uploaded to remote storage
+    pub remote_url: Option<String>,
+    /// Signature verification of the invoice XML, when one was downloaded
+    pub signature_status: Option<SignatureStatus>,
+    /// User's note, e.g. why the invoice was ignored or where it was
+    /// obtained by hand
+    pub note: Option<String>,
 }
 
 /// Get list of download batches
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
 pub fn get_batches(db: State<DatabaseState>) -> Result<Vec<DownloadBatch>, AppError> {
     db.0.get_batches()
 }
 
 /// Get invoices for a specific batch
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
 pub fn get_batch_invoices(
     batch_id: String,
     db: State<DatabaseState>,
@@ -39,17 +91,366 @@ pub fn get_batch_invoices(
     db.0.get_batch_invoices(&batch_id)
 }
 
+/// Codes only in batch A, only in batch B, and those whose status changed,
+/// e.g. to see what a corrected spreadsheet changed
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn compare_batches(
+    batch_a: String,
+    batch_b: String,
+    db: State<DatabaseState>,
+) -> Result<BatchComparison, AppError> {
+    let invoices = |batch_id: &str| {
+        db.0.get_batch(batch_id)?
+            .ok_or_else(|| AppError::ConfigError(format!("Batch not found: {}", batch_id)))?;
+        db.0.get_batch_invoices(batch_id)
+    };
+    Ok(batch_compare::compare(&invoices(&batch_a)?, &invoices(&batch_b)?))
+}
+
+/// Parsed XML of an invoice, when it was downloaded with its XML
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn get_invoice_xml(
+    invoice_id: String,
+    db: State<DatabaseState>,
+) -> Result<Option<XmlInvoice>, AppError> {
+    db.0.get_invoice_xml(&invoice_id)
+}
+
+/// Suspicious invoices of a batch: downloaded twice, dated in the future, or
+/// with a total far outside the seller's earlier invoices
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub fn get_batch_anomalies(
+    batch_id: String,
+    db: State<DatabaseState>,
+) -> Result<Vec<InvoiceAnomaly>, AppError> {
+    let facts = db.0.get_invoice_facts(&batch_id)?;
+    Ok(anomalies::detect(&facts, chrono::Local::now().date_naive()))
+}
+
+/// Invoice values of a month (`2024-05`) or quarter (`2024-Q2`) grouped by
+/// VAT rate, as on the VAT declaration appendix. Uses the downloaded XML
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_vat_summary(period: String, db: State<DatabaseState>) -> Result<VatSummary, AppError> {
+    let range = VatPeriod::parse(&period)?;
+    let invoices = db.0.get_invoice_xml_issued_between(
+        &range.from.to_string(),
+        &range.to.to_string(),
+    )?;
+    Ok(vat_summary::summarize(&period, range, &invoices))
+}
+
+/// Number, date and amounts read from an invoice's PDF
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn get_invoice_metadata(
+    invoice_id: String,
+    db: State<DatabaseState>,
+) -> Result<Option<InvoiceMetadata>, AppError> {
+    db.0.get_invoice_metadata(&invoice_id)
+}
+
+/// Sizes before and after compressing an invoice's PDF, when it was
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn get_pdf_optimization(
+    invoice_id: String,
+    db: State<DatabaseState>,
+) -> Result<Option<PdfOptimization>, AppError> {
+    db.0.get_pdf_optimization(&invoice_id)
+}
+
+/// Signature checks of an invoice's XML, empty when it has none
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn get_invoice_signatures(
+    invoice_id: String,
+    db: State<DatabaseState>,
+) -> Result<Vec<SignatureCheck>, AppError> {
+    db.0.get_invoice_signatures(&invoice_id)
+}
+
 /// Delete a batch and all its invoices
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
 pub fn delete_batch(batch_id: String, db: State<DatabaseState>) -> Result<(), AppError> {
-    db.0.delete_batch(&batch_id)
+    db.0.delete_batch(&batch_id)?;
+    db.0.record_audit(AuditAction::BatchDeleted, Some(&batch_id), None)
 }
 
 /// Get failed invoices for a batch (for re-download)
 #[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
 pub fn get_failed_invoices(
     batch_id: String,
     db: State<DatabaseState>,
 ) -> Result<Vec<HistoryInvoice>, AppError> {
     db.0.get_failed_invoices(&batch_id)
 }
+
+/// Mark failed invoices as ignored, e.g. cancelled by the seller, so they no
+/// longer count as failed or come up for re-download
+///
+/// # Arguments
+/// * `invoice_ids` - Invoices to mark; those that did not fail are left as is
+/// * `note` - Why they are ignored, shown in history
+///
+/// # Returns
+/// * Number of invoices marked
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn ignore_invoices(
+    invoice_ids: Vec<String>,
+    note: String,
+    db: State<DatabaseState>,
+) -> Result<u32, AppError> {
+    let note = note.trim();
+    if note.is_empty() {
+        return Err(AppError::InvalidArgument {
+            argument: "note".to_string(),
+            reason: "a reason is required to ignore invoices".to_string(),
+        });
+    }
+
+    Ok(db.0.ignore_invoices(&invoice_ids, note)? as u32)
+}
+
+/// Note the user wrote on an invoice
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn get_invoice_note(
+    invoice_id: String,
+    db: State<DatabaseState>,
+) -> Result<Option<String>, AppError> {
+    db.0.get_invoice(&invoice_id)?
+        .map(|invoice| invoice.note)
+        .ok_or_else(|| AppError::ConfigError(format!("Invoice not found: {}", invoice_id)))
+}
+
+/// Write a note on an invoice, e.g. why it failed or where it was obtained
+/// by hand; an empty note removes it
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(invoice_id = %invoice_id))]
+pub fn set_invoice_note(
+    invoice_id: String,
+    note: String,
+    db: State<DatabaseState>,
+) -> Result<(), AppError> {
+    db.0.get_invoice(&invoice_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Invoice not found: {}", invoice_id)))?;
+    let note = note.trim();
+    db.0.set_invoice_note(&invoice_id, (!note.is_empty()).then_some(note))
+}
+
+/// Render a printable PDF summary of a batch for the paper archive
+///
+/// # Arguments
+/// * `batch_id` - Batch to summarize
+/// * `destination` - Target directory or file path
+///
+/// # Returns
+/// * Path of the written PDF
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub fn print_batch_summary(
+    batch_id: String,
+    destination: String,
+    db: State<DatabaseState>,
+) -> Result<String, AppError> {
+    let batch = db
+        .0
+        .get_batch(&batch_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Batch not found: {}", batch_id)))?;
+    let invoices = db.0.get_batch_invoices(&batch_id)?;
+
+    let destination = Path::new(&destination);
+    let target = if destination.is_dir() {
+        destination.join(format!("bao-cao-{}.pdf", batch_id))
+    } else {
+        destination.to_path_buf()
+    };
+
+    batch_report::write_batch_summary(&target, &batch, &invoices)?;
+
+    let target = target.to_string_lossy().to_string();
+    db.0.record_audit(
+        AuditAction::Export,
+        Some(&batch_id),
+        Some(&format!("Batch summary: {}", target)),
+    )?;
+
+    Ok(target)
+}
+
+/// Save the invoices of a batch with the given statuses as CSV or JSON, e.g.
+/// to send the codes that failed back to the supplier. The CSV layout
+/// (delimiter, decimal comma, BOM, dates) follows the settings
+///
+/// # Arguments
+/// * `batch_id` - Batch to export, including its sub-batches
+/// * `status_filter` - Statuses to include; empty for all invoices
+/// * `format` - CSV or JSON
+/// * `destination` - Target directory or file path
+///
+/// # Returns
+/// * Path of the written file
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub fn export_batch_invoices(
+    batch_id: String,
+    status_filter: Vec<InvoiceStatus>,
+    format: ExportFormat,
+    destination: String,
+    settings: State<SettingsService>,
+    db: State<DatabaseState>,
+) -> Result<String, AppError> {
+    db.0.get_batch(&batch_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Batch not found: {}", batch_id)))?;
+    let invoices = db.0.get_batch_invoices(&batch_id)?;
+    let selected = invoice_export::filter(&invoices, &status_filter);
+    let mut metadata = HashMap::new();
+    if format == ExportFormat::Csv {
+        for invoice in &selected {
+            if let Some(found) = db.0.get_invoice_metadata(&invoice.id)? {
+                metadata.insert(invoice.id.clone(), found);
+            }
+        }
+    }
+    let options = CsvOptions::from_settings(&settings.get());
+
+    let destination = Path::new(&destination);
+    let target = if destination.is_dir() {
+        destination.join(format!("hoa-don-{}.{}", batch_id, format.extension()))
+    } else {
+        destination.to_path_buf()
+    };
+
+    std::fs::write(&target, invoice_export::render(&selected, &metadata, format, &options)?)?;
+
+    let target = target.to_string_lossy().to_string();
+    db.0.record_audit(
+        AuditAction::Export,
+        Some(&batch_id),
+        Some(&format!("{} invoices: {}", selected.len(), target)),
+    )?;
+
+    Ok(target)
+}
+
+/// Zip the PDFs of a batch with its checksum manifest, e.g. to email the
+/// bundle to the accountant
+///
+/// # Arguments
+/// * `batch_id` - Batch to package, including its sub-batches
+/// * `destination` - Target directory or file path
+/// * `encrypt` - Encrypt the zip with AES-256
+/// * `password` - Password to encrypt with; when empty, a one-time password
+///   is generated and returned
+///
+/// # Returns
+/// * Path of the zip, and the generated password if any
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub fn package_batch(
+    batch_id: String,
+    destination: String,
+    encrypt: bool,
+    password: Option<String>,
+    db: State<DatabaseState>,
+) -> Result<BatchPackage, AppError> {
+    let batch = db
+        .0
+        .get_batch(&batch_id)?
+        .ok_or_else(|| AppError::ConfigError(format!("Batch not found: {}", batch_id)))?;
+    let batch_dir = Path::new(&batch.download_directory);
+    let invoices = db.0.get_batch_invoices(&batch_id)?;
+    let downloaded: Vec<(&str, &str)> = invoices
+        .iter()
+        .filter(|invoice| invoice.status == InvoiceStatus::Success)
+        .filter_map(|invoice| Some((invoice.code.as_str(), invoice.file_path.as_deref()?)))
+        .filter(|(_, path)| Path::new(path).is_file())
+        .collect();
+    if downloaded.is_empty() {
+        return Err(AppError::ConfigError(format!(
+            "Batch {} has no downloaded files to package",
+            batch_id
+        )));
+    }
+
+    let password = password.filter(|password| !password.is_empty());
+    if let Some(password) = password.as_ref().filter(|_| encrypt) {
+        zip_package::validate_password(password)?;
+    }
+    let generated_password = (encrypt && password.is_none()).then(zip_package::generate_password);
+    let zip_password = if encrypt {
+        password.as_deref().or(generated_password.as_deref())
+    } else {
+        None
+    };
+
+    let destination = Path::new(&destination);
+    let target = if destination.is_dir() {
+        destination.join(format!("hoa-don-{}.zip", batch_id))
+    } else {
+        destination.to_path_buf()
+    };
+
+    let manifest_dir = std::env::temp_dir().join(format!("autoinvoice-package-{}", uuid::Uuid::new_v4()));
+    let mut files: Vec<String> = downloaded.iter().map(|(_, path)| path.to_string()).collect();
+    let packaged = BatchManifest::build(&batch_id, batch_dir, &downloaded)
+        .and_then(|manifest| manifest.write_to(&manifest_dir))
+        .and_then(|written| {
+            files.extend(written.iter().map(|path| path.to_string_lossy().to_string()));
+            zip_package::write_zip(&target, batch_dir, &files, zip_password)
+        });
+    let _ = std::fs::remove_dir_all(&manifest_dir);
+    packaged?;
+
+    let target = target.to_string_lossy().to_string();
+    db.0.record_audit(
+        AuditAction::Export,
+        Some(&batch_id),
+        Some(&format!(
+            "Batch package{}: {}",
+            if zip_password.is_some() { " (encrypted)" } else { "" },
+            target
+        )),
+    )?;
+
+    Ok(BatchPackage {
+        path: target,
+        file_count: files.len() as u32,
+        generated_password,
+    })
+}
+
+/// Space used in the download directory per batch, month and seller, and the
+/// files no invoice record points at, which can be deleted to reclaim space
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_storage_report(
+    settings: State<SettingsService>,
+    db: State<DatabaseState>,
+) -> Result<StorageReport, AppError> {
+    let root = settings.get().download_directory;
+    disk_usage::build(Path::new(&root), &db.0.get_recorded_files()?)
+}
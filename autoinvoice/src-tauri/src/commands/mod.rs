@@ -2,8 +2,20 @@ pub mod excel;
 pub mod download;
 pub mod settings;
 pub mod history;
+pub mod diagnostics;
+pub mod audit;
+pub mod security;
+pub mod cloud;
+pub mod portal;
+pub mod onboarding;
 
 pub use excel::*;
 pub use download::*;
 pub use settings::*;
 pub use history::*;
+pub use diagnostics::*;
+pub use audit::*;
+pub use security::*;
+pub use cloud::*;
+pub use portal::*;
+pub use onboarding::*;
@@ -0,0 +1,58 @@
+use tauri::State;
+use crate::error::AppError;
+use crate::services::http::{HttpClients, HttpTimeouts};
+use crate::services::onboarding::{self, OnboardingCheck, OnboardingState, OnboardingStep};
+use crate::services::settings_service::SettingsService;
+use crate::DatabaseState;
+
+/// Steps of the first-run wizard the user got through, and the next one
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_onboarding_state(db: State<DatabaseState>) -> Result<OnboardingState, AppError> {
+    Ok(OnboardingState::new(db.0.get_onboarding_steps()?))
+}
+
+/// Run the check behind a wizard step against the saved settings, and
+/// record the step as done when it passes
+///
+/// # Arguments
+/// * `step` - Step the user is on; save the settings it asks for first
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn complete_onboarding_step(
+    step: OnboardingStep,
+    service: State<'_, SettingsService>,
+    db: State<'_, DatabaseState>,
+) -> Result<OnboardingCheck, AppError> {
+    let settings = service.get();
+    let check = match step {
+        OnboardingStep::Browser => onboarding::check_browser(),
+        OnboardingStep::DownloadFolder => onboarding::check_download_folder(&settings.download_directory),
+        OnboardingStep::ApiKey if settings.openai_api_key.trim().is_empty() => OnboardingCheck {
+            step,
+            ok: false,
+            message: Some("No OpenAI API key saved".to_string()),
+        },
+        OnboardingStep::ApiKey => {
+            let solver = settings.captcha_solver(HttpClients::new(HttpTimeouts::default())?);
+            let key_check = tokio::task::spawn_blocking(move || solver.check_key_blocking())
+                .await
+                .map_err(|e| AppError::NetworkError(format!("API key check panicked: {}", e)))?;
+            onboarding::check_api_key(&key_check)
+        }
+    };
+
+    if check.ok {
+        let completed = db.0.get_onboarding_steps()?;
+        // Stored in wizard order whatever order the steps were done in
+        let completed: Vec<OnboardingStep> = OnboardingStep::ALL
+            .iter()
+            .copied()
+            .filter(|s| *s == step || completed.contains(s))
+            .collect();
+        db.0.set_onboarding_steps(&completed)?;
+    }
+    Ok(check)
+}
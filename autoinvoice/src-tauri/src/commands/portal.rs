@@ -0,0 +1,180 @@
+use chrono::NaiveDate;
+use tauri::{AppHandle, Manager, State};
+use tracing::Instrument;
+
+use crate::commands::download::{
+    emit_batch_completed, run_claimed_download, BatchStarted, Claim, DownloadOverrides, DownloadState,
+    StartDownloadRequest,
+};
+use crate::error::AppError;
+use crate::services::browser::VnptBrowser;
+use crate::services::downloader::InvoiceDownloadRequest;
+use crate::services::excel_parser::ExcelParseResult;
+use crate::services::http::{HttpClients, HttpTimeouts};
+use crate::services::portal_account;
+use crate::services::selector_config::SelectorConfig;
+use crate::services::settings_service::SettingsService;
+use crate::status::BatchStatus;
+use crate::DatabaseState;
+
+/// Log in to the configured portal account and list the invoices issued
+/// between two dates, in the shape of a parsed Excel sheet so they go
+/// through the same preview and download flow
+///
+/// # Arguments
+/// * `from_date` - First issue date, "YYYY-MM-DD"
+/// * `to_date` - Last issue date, "YYYY-MM-DD"
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn list_portal_invoices(
+    app: AppHandle,
+    from_date: String,
+    to_date: String,
+) -> Result<ExcelParseResult, AppError> {
+    let (from, to) = parse_period(&from_date, &to_date)?;
+    fetch_invoices(&app, from, to).await
+}
+
+/// List the portal account's invoices of a period and download them all as
+/// a new batch, named after the period, in the background; the outcome comes
+/// with `batch:completed`, which also reports a listing that failed
+///
+/// # Arguments
+/// * `batch_id` - Id of the batch to create, chosen by the caller to follow its events
+/// * `from_date` - First issue date, "YYYY-MM-DD"
+/// * `to_date` - Last issue date, "YYYY-MM-DD"
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all, fields(batch_id = %batch_id))]
+pub async fn discover_invoices(
+    app: AppHandle,
+    state: State<'_, DownloadState>,
+    db: State<'_, DatabaseState>,
+    batch_id: String,
+    from_date: String,
+    to_date: String,
+) -> Result<BatchStarted, AppError> {
+    let (from, to) = parse_period(&from_date, &to_date)?;
+    // Held through the listing, so a retried call does not log in twice
+    let claim = match state.claim(&batch_id).await {
+        Claim::Won(claim) => claim,
+        Claim::Running(running) => {
+            return Ok(BatchStarted {
+                batch_id,
+                started: false,
+                status: running.activity().status,
+            });
+        }
+        Claim::Starting => {
+            return Ok(BatchStarted {
+                batch_id,
+                started: false,
+                status: BatchStatus::Running,
+            });
+        }
+    };
+    app.state::<SettingsService>().get().portal_account().validate()?;
+
+    let db = db.0.clone();
+    let handle = app.clone();
+    let acknowledged = batch_id.clone();
+    // The batch logs under the request id of this call
+    tauri::async_runtime::spawn(
+        async move {
+            let request = match list_period(&handle, batch_id.clone(), from, to).await {
+                Ok(request) => request,
+                Err(e) => return emit_batch_completed(&handle, batch_id, &Err(e)),
+            };
+            let state = handle.state::<DownloadState>();
+            // The outcome is sent as `batch:completed`
+            let _ = run_claimed_download(&handle, &state, db, request, claim).await;
+        }
+        .in_current_span(),
+    );
+    Ok(BatchStarted {
+        batch_id: acknowledged,
+        started: true,
+        status: BatchStatus::Running,
+    })
+}
+
+/// The download request of every invoice the portal lists for the period
+async fn list_period(
+    app: &AppHandle,
+    batch_id: String,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<StartDownloadRequest, AppError> {
+    let listed = fetch_invoices(app, from, to).await?;
+    if listed.invoices.is_empty() {
+        return Err(AppError::ConfigError(format!(
+            "The portal lists no invoices from {} to {}",
+            from, to
+        )));
+    }
+
+    Ok(StartDownloadRequest {
+        batch_id,
+        name: Some(listed.file_name),
+        source_file: None,
+        source_path: None,
+        source_hash: Some(listed.file_hash),
+        invoices: listed
+            .invoices
+            .into_iter()
+            .map(|invoice| InvoiceDownloadRequest {
+                id: invoice.id,
+                code: invoice.code,
+                seller_tax_code: invoice.seller_tax_code,
+                lookup_mode: invoice.lookup_mode,
+                invoice_series: invoice.invoice_series,
+                invoice_number: invoice.invoice_number,
+                portal_url: invoice.portal_url,
+            })
+            .collect(),
+        overrides: DownloadOverrides::default(),
+    })
+}
+
+/// Log in with the saved portal account and read every page of the invoice
+/// list for the period
+async fn fetch_invoices(app: &AppHandle, from: NaiveDate, to: NaiveDate) -> Result<ExcelParseResult, AppError> {
+    let settings = app.state::<SettingsService>().get();
+    let account = settings.portal_account();
+    account.validate()?;
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    let overrides = SelectorConfig::load(&app_data_dir)?.overrides;
+
+    tokio::task::spawn_blocking(move || {
+        let browser = VnptBrowser::new(true, &settings.browser_viewport())?;
+        browser.set_selector_overrides(overrides);
+        browser.set_captcha_padding(settings.captcha_padding_px);
+        let solver = settings.captcha_solver(HttpClients::new(HttpTimeouts::default())?);
+
+        portal_account::log_in(&browser, &account, &solver)?;
+        let invoices = portal_account::list_invoices(&browser, &account, from, to)?;
+        Ok::<_, AppError>(portal_account::as_parse_result(invoices, &account, from, to))
+    })
+    .await
+    .map_err(|e| AppError::BrowserError(format!("Task panicked: {}", e)))?
+}
+
+/// Issue date range, checked to be in order
+fn parse_period(from_date: &str, to_date: &str) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map_err(|_| AppError::ConfigError(format!("Invalid date '{}', expected YYYY-MM-DD", date)))
+    };
+    let (from, to) = (parse(from_date)?, parse(to_date)?);
+    if from > to {
+        return Err(AppError::ConfigError(format!(
+            "Period start {} is after its end {}",
+            from, to
+        )));
+    }
+    Ok((from, to))
+}
@@ -0,0 +1,109 @@
+use serde::Serialize;
+use specta::Type;
+use tauri::State;
+use crate::error::AppError;
+use crate::services::app_lock::{self, AppLock};
+use crate::status::AuditAction;
+use crate::DatabaseState;
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct LockStatus {
+    pub has_passcode: bool,
+    pub locked: bool,
+    /// Downloads, deletions and settings changes are refused
+    pub read_only: bool,
+}
+
+/// Whether a passcode is set and the app is currently locked
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_lock_status(
+    db: State<DatabaseState>,
+    lock: State<AppLock>,
+) -> Result<LockStatus, AppError> {
+    Ok(LockStatus {
+        has_passcode: db.0.get_passcode_hash()?.is_some(),
+        locked: lock.is_locked(),
+        read_only: lock.is_read_only(),
+    })
+}
+
+/// Unlock the app with its passcode
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn unlock_app(
+    passcode: String,
+    db: State<DatabaseState>,
+    lock: State<AppLock>,
+) -> Result<(), AppError> {
+    check_passcode(&db, &passcode)?;
+    lock.unlock();
+    Ok(())
+}
+
+/// Lock the app again; a no-op without a passcode
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn lock_app(db: State<DatabaseState>, lock: State<AppLock>) -> Result<(), AppError> {
+    if db.0.get_passcode_hash()?.is_some() {
+        lock.lock();
+    }
+    Ok(())
+}
+
+/// Set, change or remove the app passcode
+///
+/// # Arguments
+/// * `current` - Current passcode, required when one is set
+/// * `passcode` - New passcode; `None` or empty removes the lock
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn set_passcode(
+    current: Option<String>,
+    passcode: Option<String>,
+    db: State<DatabaseState>,
+) -> Result<(), AppError> {
+    check_passcode(&db, current.as_deref().unwrap_or_default())?;
+
+    let hash = match passcode.as_deref().map(str::trim) {
+        Some(passcode) if !passcode.is_empty() => Some(app_lock::hash_passcode(passcode)?),
+        _ => None,
+    };
+    db.0.set_passcode_hash(hash.as_deref())?;
+    db.0.record_audit(AuditAction::SettingsChanged, None, Some("passcode"))
+}
+
+/// Switch read-only mode; turning it off requires the passcode when one is set
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn set_read_only(
+    enabled: bool,
+    passcode: Option<String>,
+    db: State<DatabaseState>,
+    lock: State<AppLock>,
+) -> Result<(), AppError> {
+    if !enabled {
+        check_passcode(&db, passcode.as_deref().unwrap_or_default())?;
+    }
+
+    db.0.set_read_only(enabled)?;
+    lock.set_read_only(enabled);
+    db.0.record_audit(
+        AuditAction::SettingsChanged,
+        None,
+        Some(if enabled { "read-only on" } else { "read-only off" }),
+    )
+}
+
+/// Succeed when no passcode is set or `passcode` matches it
+fn check_passcode(db: &DatabaseState, passcode: &str) -> Result<(), AppError> {
+    match db.0.get_passcode_hash()? {
+        Some(hash) if !app_lock::verify_passcode(passcode, &hash) => Err(AppError::InvalidPasscode),
+        _ => Ok(()),
+    }
+}
@@ -1,23 +1,671 @@
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use specta::Type;
+use std::path::Path;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
 use crate::error::AppError;
+use crate::services::ai_budget::{self, AiKeyUsage, BudgetAction};
+use crate::services::browser::{BrowserViewport, MAX_CAPTCHA_PADDING_PX};
+use crate::services::captcha::{ApiKeyCheck, AzureOpenAiConfig, CaptchaSolver};
+use crate::services::captcha_dataset::{self, CaptchaDataset};
+use crate::services::cloud_drive::{CloudConfig, CloudProvider};
+use crate::services::config_bundle::{ConfigBundle, ConfigExport, ConfigImport};
+use crate::services::download_config::ensure_writable_dir;
+use crate::services::downloader::MAX_NOT_FOUND_VERIFY_DELAY_SECS;
+use crate::services::database::Database;
+use crate::services::dedupe::DuplicateFiles;
+use crate::services::file_naming::{FileNaming, Transliteration};
+use crate::services::pdf_fetch::PdfVariant;
+use crate::services::hooks::{self, HookScope};
+use crate::services::http::{HttpClients, HttpTimeouts};
+use crate::services::inbox::ImapConfig;
+use crate::services::local_captcha::LocalCaptchaModel;
+use crate::services::invoice_export::CsvDelimiter;
+use crate::services::ocr;
+use crate::services::pdf_optimize;
+use crate::services::portal_account::PortalAccount;
+use crate::services::portal_profiles::{self, PortalProfile};
+use crate::services::retry_later::MAX_RETRY_NOT_FOUND_HOURS;
+use crate::services::schedule::QuietHours;
+use crate::services::selector_config::SelectorConfig;
+use crate::services::settings_service::{self, SettingsService};
+use crate::services::sftp::{SftpConfig, SftpMode};
+use crate::services::storage::{S3Config, WebDavConfig};
+use crate::services::zip_package;
+use crate::status::AuditAction;
 use crate::DatabaseState;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Type)]
 pub struct Settings {
     pub openai_api_key: String,
+    /// Keys a batch switches to, in order, when the current one is rate
+    /// limited or out of quota
+    pub openai_backup_api_keys: Vec<String>,
+    /// Call an Azure OpenAI deployment instead of OpenAI; the API keys are
+    /// then keys of the Azure resource
+    pub use_azure_openai: bool,
+    pub azure_openai_endpoint: String,
+    pub azure_openai_deployment: String,
+    pub azure_openai_api_version: String,
     pub vnpt_url: String,
     pub download_directory: String,
+    /// Pause between two invoices, in seconds
+    pub invoice_delay_secs: u32,
+    /// Start of the daily quiet hours ("HH:MM"), empty when disabled
+    pub quiet_hours_start: String,
+    /// End of the daily quiet hours ("HH:MM"), empty when disabled
+    pub quiet_hours_end: String,
+    /// Retry invoices VNPT does not know on the tax-authority portal
+    pub fallback_to_tax_portal: bool,
+    /// Look an invoice the portal does not know up once more after a pause,
+    /// as same-day invoices take a while to reach the lookup
+    pub verify_not_found: bool,
+    pub not_found_verify_delay_secs: u32,
+    /// Look not-found invoices up again this many hours later, while the app
+    /// is open; 0 disables the retry-later queue
+    pub retry_not_found_hours: u32,
+    /// Start queued batches as soon as the network is back
+    pub auto_start_queued: bool,
+    /// Keep per-stage timings locally; nothing leaves the machine
+    pub collect_metrics: bool,
+    /// Also download each invoice's XML and parse it for reporting
+    pub download_xml: bool,
+    /// Keep the original PDF, the converted printable one, or both
+    pub pdf_variant: PdfVariant,
+    /// OCR PDFs without a text layer to read their number, date and amounts
+    pub ocr_scanned_pdfs: bool,
+    /// Tesseract executable; empty uses `tesseract` from PATH
+    pub tesseract_path: String,
+    /// Compress downloaded PDFs without loss
+    pub optimize_pdfs: bool,
+    /// Keep the PDF as downloaded in an `originals` folder when optimizing
+    pub keep_original_pdfs: bool,
+    /// Also linearize optimized PDFs with qpdf
+    pub linearize_pdfs: bool,
+    /// qpdf executable; empty uses `qpdf` from PATH
+    pub qpdf_path: String,
+    /// Stamp the first page of each PDF with the processing date and batch
+    pub stamp_pdfs: bool,
+    /// Estimated AI captcha spend allowed per batch and per calendar month,
+    /// in USD; 0 means no cap
+    pub ai_budget_per_batch_usd: f64,
+    pub ai_budget_per_month_usd: f64,
+    /// What a batch does once a cap is reached
+    pub ai_budget_action: BudgetAction,
+    /// Solve the next invoice's captcha on a second tab during each download
+    pub captcha_lookahead: bool,
+    /// Read captchas with the offline model first, asking the AI provider
+    /// only when it is less confident than `local_captcha_min_confidence`
+    pub local_captcha_solver: bool,
+    pub local_captcha_min_confidence: f64,
+    /// Save captchas the portal accepted, with their answers, as a dataset
+    /// for training a local solver; nothing is uploaded
+    pub collect_captcha_dataset: bool,
+    /// Dataset folder; empty uses `captcha-dataset` in the app data folder
+    pub captcha_dataset_dir: String,
+    /// Keep one Chrome profile per portal, reusing its cookies and session
+    pub persistent_browser_profiles: bool,
+    /// Download sample invoices from a simulated portal instead of the real
+    /// ones, for training; no portal or API is contacted
+    pub demo_mode: bool,
+    /// Hard-link or skip PDFs identical to one already downloaded
+    pub duplicate_files: DuplicateFiles,
+    /// Name of each PDF, e.g. `{seller}_{number}`; empty uses the lookup code
+    pub file_name_template: String,
+    /// Subfolder of the batch folder for each PDF, e.g. `{seller}`
+    pub folder_template: String,
+    /// Spelling of names from the invoice in file and folder names
+    pub file_name_transliteration: Transliteration,
+    /// CSV export layout; see `CsvOptions`
+    pub csv_delimiter: CsvDelimiter,
+    pub csv_decimal_comma: bool,
+    pub csv_bom: bool,
+    pub csv_day_first_dates: bool,
+    /// Browser window size, device scale and zoom; see `BrowserViewport`
+    pub browser_window_width: u32,
+    pub browser_window_height: u32,
+    pub browser_device_scale_factor: f64,
+    pub browser_zoom_percent: u32,
+    /// Margin captured around the captcha image, in CSS pixels
+    pub captcha_padding_px: u32,
+    /// How long a download waits for a manual captcha before skipping the invoice
+    pub manual_captcha_timeout_secs: u32,
+    /// Portal account listing every invoice of the company; empty username disables it
+    pub portal_login_url: String,
+    pub portal_invoice_list_url: String,
+    pub portal_username: String,
+    pub portal_password: String,
+    /// Queue invoices from notification emails in the mailbox below
+    pub inbox_enabled: bool,
+    pub imap_host: String,
+    pub imap_port: u16,
+    pub imap_username: String,
+    pub imap_password: String,
+    pub imap_folder: String,
+    /// Script or program run after downloads with JSON context on stdin; empty disables it
+    pub post_download_hook: String,
+    pub post_download_hook_scope: HookScope,
+    /// Upload each downloaded PDF to the S3-compatible bucket below
+    pub upload_to_s3: bool,
+    pub s3_endpoint: String,
+    pub s3_region: String,
+    pub s3_bucket: String,
+    pub s3_access_key: String,
+    pub s3_secret_key: String,
+    /// Key prefix for uploaded objects, empty for the bucket root
+    pub s3_prefix: String,
+    /// Upload finished batches to a WebDAV share such as Nextcloud
+    pub upload_to_webdav: bool,
+    pub webdav_url: String,
+    pub webdav_username: String,
+    pub webdav_password: String,
+    /// Deliver finished batches to an SFTP server
+    pub upload_to_sftp: bool,
+    pub sftp_host: String,
+    pub sftp_port: u16,
+    pub sftp_username: String,
+    /// Password, or the key passphrase when a private key is set
+    pub sftp_password: String,
+    pub sftp_private_key_path: String,
+    pub sftp_remote_dir: String,
+    pub sftp_mode: SftpMode,
+    /// Expected "SHA256:..." host key; empty pins the one seen first
+    pub sftp_host_key_fingerprint: String,
+    /// Drive finished batches are mirrored to, signed in with a device code
+    pub cloud_provider: CloudProvider,
+    pub cloud_client_id: String,
+    pub cloud_client_secret: String,
+    /// Google Drive folder id or OneDrive folder path; per-batch folders go inside
+    pub cloud_folder: String,
+}
+
+impl Settings {
+    pub fn browser_viewport(&self) -> BrowserViewport {
+        BrowserViewport {
+            width: self.browser_window_width,
+            height: self.browser_window_height,
+            device_scale_factor: self.browser_device_scale_factor,
+            zoom_percent: self.browser_zoom_percent,
+        }
+    }
+
+    pub fn portal_account(&self) -> PortalAccount {
+        PortalAccount {
+            login_url: self.portal_login_url.trim().to_string(),
+            invoice_list_url: self.portal_invoice_list_url.trim().to_string(),
+            username: self.portal_username.trim().to_string(),
+            password: self.portal_password.clone(),
+        }
+    }
+
+    pub fn imap_config(&self) -> ImapConfig {
+        ImapConfig {
+            host: self.imap_host.trim().to_string(),
+            port: self.imap_port,
+            username: self.imap_username.trim().to_string(),
+            password: self.imap_password.clone(),
+            folder: self.imap_folder.trim().to_string(),
+        }
+    }
+
+    pub fn azure_openai_config(&self) -> AzureOpenAiConfig {
+        AzureOpenAiConfig {
+            endpoint: self.azure_openai_endpoint.trim().to_string(),
+            deployment: self.azure_openai_deployment.trim().to_string(),
+            api_version: self.azure_openai_api_version.trim().to_string(),
+        }
+    }
+
+    /// Captcha solver with the keys and provider of these settings
+    pub fn captcha_solver(&self, http: HttpClients) -> CaptchaSolver {
+        let solver = CaptchaSolver::new(self.openai_api_key.clone(), http)
+            .with_backup_keys(self.openai_backup_api_keys.clone());
+        match self.use_azure_openai {
+            true => solver.with_azure(self.azure_openai_config()),
+            false => solver,
+        }
+    }
+
+    pub fn s3_config(&self) -> S3Config {
+        S3Config {
+            endpoint: self.s3_endpoint.trim().to_string(),
+            region: self.s3_region.trim().to_string(),
+            bucket: self.s3_bucket.trim().to_string(),
+            access_key: self.s3_access_key.trim().to_string(),
+            secret_key: self.s3_secret_key.trim().to_string(),
+            prefix: self.s3_prefix.trim().to_string(),
+        }
+    }
+
+    pub fn webdav_config(&self) -> WebDavConfig {
+        WebDavConfig {
+            url: self.webdav_url.trim().to_string(),
+            username: self.webdav_username.trim().to_string(),
+            password: self.webdav_password.clone(),
+        }
+    }
+
+    pub fn sftp_config(&self) -> SftpConfig {
+        SftpConfig {
+            host: self.sftp_host.trim().to_string(),
+            port: self.sftp_port,
+            username: self.sftp_username.trim().to_string(),
+            password: self.sftp_password.clone(),
+            private_key_path: self.sftp_private_key_path.trim().to_string(),
+            remote_dir: self.sftp_remote_dir.trim().to_string(),
+            mode: self.sftp_mode,
+            host_key_fingerprint: self.sftp_host_key_fingerprint.trim().to_string(),
+        }
+    }
+
+    pub fn cloud_config(&self) -> CloudConfig {
+        CloudConfig {
+            provider: self.cloud_provider,
+            client_id: self.cloud_client_id.trim().to_string(),
+            client_secret: self.cloud_client_secret.trim().to_string(),
+            folder: self.cloud_folder.trim().to_string(),
+        }
+    }
+
+    pub fn file_naming(&self) -> FileNaming {
+        FileNaming {
+            file_name_template: self.file_name_template.trim().to_string(),
+            folder_template: self.folder_template.trim().to_string(),
+            transliteration: self.file_name_transliteration,
+        }
+    }
 }
 
 /// Get application settings
 #[tauri::command]
-pub fn get_settings(db: State<DatabaseState>) -> Result<Settings, AppError> {
-    db.0.get_settings()
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_settings(settings: State<SettingsService>) -> Result<Settings, AppError> {
+    Ok(settings.get())
 }
 
 /// Save application settings
 #[tauri::command]
-pub fn save_settings(settings: Settings, db: State<DatabaseState>) -> Result<(), AppError> {
-    db.0.save_settings(&settings)
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn save_settings(
+    settings: Settings,
+    service: State<SettingsService>,
+    db: State<DatabaseState>,
+) -> Result<(), AppError> {
+    let changed = store_settings(&settings, &service, &db.0)?;
+    if changed.is_empty() {
+        return Ok(());
+    }
+    db.0.record_audit(AuditAction::SettingsChanged, None, Some(&changed.join(", ")))
+}
+
+/// Validate and save settings, whether edited or imported
+///
+/// # Returns
+/// Names of the settings that changed
+fn store_settings(settings: &Settings, service: &SettingsService, db: &Database) -> Result<Vec<String>, AppError> {
+    validate_settings(settings)?;
+
+    let previous = service.get();
+    service.save(settings)?;
+
+    // A sign-in belongs to one provider and OAuth app
+    if previous.cloud_provider != settings.cloud_provider
+        || previous.cloud_client_id.trim() != settings.cloud_client_id.trim()
+    {
+        db.set_cloud_refresh_token(None)?;
+    }
+
+    Ok(settings_service::changed_fields(&previous, settings))
+}
+
+fn validate_settings(settings: &Settings) -> Result<(), AppError> {
+    QuietHours::parse(&settings.quiet_hours_start, &settings.quiet_hours_end)?;
+    hooks::validate_hook(settings.post_download_hook.trim())?;
+    settings.browser_viewport().validate()?;
+    if settings.captcha_padding_px > MAX_CAPTCHA_PADDING_PX {
+        return Err(AppError::ConfigError(format!(
+            "Captcha padding must be at most {} pixels",
+            MAX_CAPTCHA_PADDING_PX
+        )));
+    }
+    if settings.not_found_verify_delay_secs > MAX_NOT_FOUND_VERIFY_DELAY_SECS {
+        return Err(AppError::ConfigError(format!(
+            "The not-found verification delay must be at most {} seconds",
+            MAX_NOT_FOUND_VERIFY_DELAY_SECS
+        )));
+    }
+    if settings.retry_not_found_hours > MAX_RETRY_NOT_FOUND_HOURS {
+        return Err(AppError::ConfigError(format!(
+            "The retry interval for not-found invoices must be at most {} hours",
+            MAX_RETRY_NOT_FOUND_HOURS
+        )));
+    }
+    for (argument, limit) in [
+        ("ai_budget_per_batch_usd", settings.ai_budget_per_batch_usd),
+        ("ai_budget_per_month_usd", settings.ai_budget_per_month_usd),
+    ] {
+        if !limit.is_finite() || limit < 0.0 {
+            return Err(AppError::InvalidArgument {
+                argument: argument.to_string(),
+                reason: "must be a positive amount, or 0 for no cap".to_string(),
+            });
+        }
+    }
+    if !(0.0..=1.0).contains(&settings.local_captcha_min_confidence) {
+        return Err(AppError::InvalidArgument {
+            argument: "local_captcha_min_confidence".to_string(),
+            reason: "must be between 0 and 1".to_string(),
+        });
+    }
+    settings.portal_account().validate()?;
+    if settings.use_azure_openai {
+        settings.azure_openai_config().validate()?;
+    }
+    if settings.inbox_enabled {
+        settings.imap_config().validate()?;
+    }
+    if settings.upload_to_s3 {
+        settings.s3_config().validate()?;
+    }
+    if settings.upload_to_webdav {
+        settings.webdav_config().validate()?;
+    }
+    if settings.upload_to_sftp {
+        settings.sftp_config().validate()?;
+    }
+    settings.cloud_config().validate()?;
+    settings.file_naming().validate()?;
+    if settings.ocr_scanned_pdfs {
+        ocr::check_tesseract(&settings.tesseract_path)?;
+    }
+    if settings.collect_captcha_dataset && !settings.captcha_dataset_dir.trim().is_empty() {
+        let dir = settings.captcha_dataset_dir.trim();
+        std::fs::create_dir_all(dir).map_err(|e| {
+            AppError::ConfigError(format!("Cannot create captcha dataset folder {}: {}", dir, e))
+        })?;
+    }
+    if settings.optimize_pdfs && settings.linearize_pdfs {
+        pdf_optimize::check_qpdf(&settings.qpdf_path)?;
+    }
+    Ok(())
+}
+
+/// Check an OpenAI key before a batch relies on it: lists the models it can
+/// use and times a one-token completion, telling a wrong key, an empty quota
+/// and rate limiting apart
+///
+/// # Arguments
+/// * `key` - Key to test, e.g. the one typed in Settings before saving
+/// * `azure` - Azure OpenAI deployment the key belongs to, if any
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn test_api_key(key: String, azure: Option<AzureOpenAiConfig>) -> Result<ApiKeyCheck, AppError> {
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Err(AppError::InvalidArgument {
+            argument: "key".to_string(),
+            reason: "enter an API key to test".to_string(),
+        });
+    }
+
+    let solver = CaptchaSolver::new(key, HttpClients::new(HttpTimeouts::default())?);
+    let solver = match azure {
+        Some(azure) => {
+            azure.validate()?;
+            solver.with_azure(azure)
+        }
+        None => solver,
+    };
+    tokio::task::spawn_blocking(move || solver.check_key_blocking())
+        .await
+        .map_err(|e| AppError::NetworkError(format!("API key check panicked: {}", e)))
+}
+
+/// Estimated AI captcha spend of the current month, in USD
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_ai_spend(db: State<DatabaseState>) -> Result<f64, AppError> {
+    db.0.get_ai_spend(&ai_budget::current_month())
+}
+
+/// Calls and estimated spend of each OpenAI key this month
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_ai_key_usage(db: State<DatabaseState>) -> Result<Vec<AiKeyUsage>, AppError> {
+    db.0.get_ai_key_usage(&ai_budget::current_month())
+}
+
+/// Folder of the captcha dataset and how many samples it holds
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_captcha_dataset(app: AppHandle, service: State<SettingsService>) -> Result<CaptchaDataset, AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    captcha_dataset::describe(&captcha_dataset::dataset_dir(&service.get().captcha_dataset_dir, &app_data_dir))
+}
+
+/// Folder of the offline captcha model batches would use, the user's own
+/// before the bundled one; none when no model is installed or the app was
+/// built without the `local-captcha` feature
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_local_captcha_model(app: AppHandle) -> Result<Option<String>, AppError> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    let resource_dir = app.path().resource_dir().ok();
+    Ok(LocalCaptchaModel::find(&app_data_dir, resource_dir.as_deref())
+        .map(|dir| dir.to_string_lossy().to_string()))
+}
+
+/// Politeness profiles of the portals, built-in ones included
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn get_portal_profiles(db: State<DatabaseState>) -> Result<Vec<PortalProfile>, AppError> {
+    Ok(portal_profiles::merge(db.0.get_portal_profiles()?))
+}
+
+/// Save the profile of a host, replacing a built-in one for the same host
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn save_portal_profile(profile: PortalProfile, db: State<DatabaseState>) -> Result<(), AppError> {
+    let profile = profile.validate()?;
+    db.0.save_portal_profile(&profile)?;
+    db.0.record_audit(
+        AuditAction::SettingsChanged,
+        None,
+        Some(&format!("portal_profile {}", profile.host)),
+    )
+}
+
+/// Delete the saved profile of a host
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn delete_portal_profile(host: String, db: State<DatabaseState>) -> Result<(), AppError> {
+    db.0.delete_portal_profile(&host)?;
+    db.0.record_audit(
+        AuditAction::SettingsChanged,
+        None,
+        Some(&format!("portal_profile {}", host)),
+    )
+}
+
+/// Save settings, portal profiles and selector overrides to an encrypted
+/// file, so IT can set up other machines the same way
+///
+/// # Arguments
+/// * `destination` - Target directory or file path
+/// * `password` - Password to encrypt with; when empty, a one-time password
+///   is generated and returned
+/// * `include_secrets` - Also export API keys and passwords
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub fn export_config(
+    app: AppHandle,
+    destination: String,
+    password: Option<String>,
+    include_secrets: bool,
+    service: State<SettingsService>,
+    db: State<DatabaseState>,
+) -> Result<ConfigExport, AppError> {
+    let password = password.filter(|password| !password.is_empty());
+    if let Some(password) = &password {
+        zip_package::validate_password(password)?;
+    }
+    let generated_password = password.is_none().then(zip_package::generate_password);
+
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    let bundle = ConfigBundle::new(
+        &service.get(),
+        db.0.get_portal_profiles()?,
+        SelectorConfig::load(&app_data_dir)?,
+        include_secrets,
+    )?;
+
+    let destination = Path::new(&destination);
+    let target = if destination.is_dir() {
+        destination.join("autoinvoice-config.zip")
+    } else {
+        destination.to_path_buf()
+    };
+    bundle.write(&target, password.as_deref().or(generated_password.as_deref()).unwrap_or_default())?;
+
+    let target = target.to_string_lossy().to_string();
+    db.0.record_audit(
+        AuditAction::Export,
+        None,
+        Some(&format!(
+            "Config{}: {}",
+            if include_secrets { " with secrets" } else { "" },
+            target
+        )),
+    )?;
+
+    Ok(ConfigExport {
+        path: target,
+        includes_secrets: include_secrets,
+        generated_password,
+    })
+}
+
+/// Let the user pick a file written by `export_config` and apply it: its
+/// settings, its portal profiles in place of the saved ones, and its
+/// selector overrides. Secrets the file does not carry keep their value on
+/// this machine
+///
+/// # Arguments
+/// * `password` - Password the file was encrypted with
+///
+/// # Returns
+/// What was imported, or `None` when the dialog was cancelled
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn import_config(
+    app: AppHandle,
+    password: String,
+    service: State<'_, SettingsService>,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<ConfigImport>, AppError> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("Chọn tệp cấu hình")
+        .add_filter("AutoInvoice", &["zip"])
+        .pick_file(move |file| {
+            let _ = tx.send(file);
+        });
+    let Some(file) = rx.await.ok().flatten() else {
+        return Ok(None);
+    };
+    let path = file
+        .into_path()
+        .map_err(|e| AppError::IoError(format!("Unsupported file: {}", e)))?;
+
+    let bundle = ConfigBundle::read(&path, &password)?;
+    let profiles = bundle
+        .portal_profiles
+        .iter()
+        .map(PortalProfile::validate)
+        .collect::<Result<Vec<_>, _>>()?;
+    let settings = bundle.settings_over(&service.get())?;
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+
+    let changed = store_settings(&settings, &service, &db.0)?;
+    db.0.replace_portal_profiles(&profiles)?;
+    bundle.selectors.save(&app_data_dir)?;
+    db.0.record_audit(
+        AuditAction::SettingsChanged,
+        None,
+        Some(&format!("Config import from {}", path.display())),
+    )?;
+
+    Ok(Some(ConfigImport {
+        includes_secrets: bundle.includes_secrets,
+        portal_profile_count: profiles.len() as u32,
+        selector_override_count: bundle.selectors.overrides.len() as u32,
+        changed,
+    }))
+}
+
+/// Let the user pick the download directory in the native dialog, check it is
+/// writable and save it
+///
+/// # Returns
+/// The normalized directory, or `None` when the dialog was cancelled
+#[tauri::command]
+#[specta::specta]
+#[tracing::instrument(skip_all)]
+pub async fn choose_download_directory(
+    app: AppHandle,
+    service: State<'_, SettingsService>,
+    db: State<'_, DatabaseState>,
+) -> Result<Option<String>, AppError> {
+    let current = service.get().download_directory;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let mut dialog = app.dialog().file().set_title("Chọn thư mục tải xuống");
+    if std::path::Path::new(&current).is_dir() {
+        dialog = dialog.set_directory(&current);
+    }
+    dialog.pick_folder(move |folder| {
+        let _ = tx.send(folder);
+    });
+
+    let Some(folder) = rx.await.ok().flatten() else {
+        return Ok(None);
+    };
+    let folder = folder
+        .into_path()
+        .map_err(|e| AppError::ConfigError(format!("Unsupported download directory: {}", e)))?;
+
+    let directory = ensure_writable_dir(&folder.to_string_lossy())?
+        .to_string_lossy()
+        .to_string();
+    service.set_download_directory(&directory)?;
+    if directory != current {
+        db.0.record_audit(AuditAction::SettingsChanged, None, Some("download_directory"))?;
+    }
+
+    Ok(Some(directory))
 }
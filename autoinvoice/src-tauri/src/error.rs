@@ -23,11 +23,86 @@ pub enum AppError {
     #[error("Download failed: {0}")]
     DownloadFailed(String),
 
+    #[error("Invoice not found: {0}")]
+    InvoiceNotFound(String),
+
+    #[error("No manual captcha entered for invoice {0}, skipped")]
+    ManualCaptchaSkipped(String),
+
+    #[error("Invoice {0} needs a manual captcha, left for later")]
+    NeedsManualCaptcha(String),
+
+    #[error("Upload failed: {0}")]
+    UploadError(String),
+
+    #[error("Invoice XML error: {0}")]
+    XmlError(String),
+
     #[error("Invalid configuration: {0}")]
     ConfigError(String),
 
     #[error("IO error: {0}")]
     IoError(String),
+
+    #[error("Application is locked")]
+    Locked,
+
+    #[error("Incorrect passcode")]
+    InvalidPasscode,
+
+    #[error("Not allowed in read-only mode")]
+    ReadOnly,
+
+    #[error("Invalid argument '{argument}': {reason}")]
+    InvalidArgument { argument: String, reason: String },
+
+    #[error("Path not allowed: {0}")]
+    PathNotAllowed(String),
+
+    #[error("AI budget exceeded: {0}")]
+    BudgetExceeded(String),
+
+    /// A bug, caught instead of taking the batch down; see `panic_report`
+    #[error("Internal error: {0}")]
+    Panicked(String),
+}
+
+/// Whether another attempt at the same invoice could succeed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// A timeout, a dropped connection or a page that did not load; worth
+    /// retrying after a pause
+    Transient,
+    /// Trying again gives the same answer and only spends captchas
+    Permanent,
+}
+
+impl AppError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            AppError::BrowserError(_)
+            | AppError::NetworkError(_)
+            | AppError::ElementNotFound(_)
+            | AppError::CaptchaFailed(_)
+            | AppError::DownloadFailed(_)
+            | AppError::UploadError(_) => ErrorClass::Transient,
+            AppError::ExcelError(_)
+            | AppError::DatabaseError(_)
+            | AppError::InvoiceNotFound(_)
+            | AppError::ManualCaptchaSkipped(_)
+            | AppError::NeedsManualCaptcha(_)
+            | AppError::XmlError(_)
+            | AppError::ConfigError(_)
+            | AppError::IoError(_)
+            | AppError::Locked
+            | AppError::InvalidPasscode
+            | AppError::ReadOnly
+            | AppError::InvalidArgument { .. }
+            | AppError::PathNotAllowed(_)
+            | AppError::BudgetExceeded(_)
+            | AppError::Panicked(_) => ErrorClass::Permanent,
+        }
+    }
 }
 
 impl From<std::io::Error> for AppError {
@@ -60,12 +135,68 @@ impl From<reqwest::Error> for AppError {
     }
 }
 
+/// Error the frontend can act on, e.g. by marking the field at fault
+#[derive(serde::Serialize, specta::Type)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum StructuredError {
+    InvalidArgument { argument: String, reason: String },
+}
+
+/// What an `AppError` is sent to the frontend as
+#[derive(serde::Serialize, specta::Type)]
+#[serde(untagged)]
+enum ErrorPayload {
+    Message(String),
+    Structured(StructuredError),
+}
+
 // Convert to Tauri-friendly error
 impl serde::Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        let payload = match self {
+            AppError::InvalidArgument { argument, reason } => {
+                ErrorPayload::Structured(StructuredError::InvalidArgument {
+                    argument: argument.clone(),
+                    reason: reason.clone(),
+                })
+            }
+            _ => ErrorPayload::Message(self.to_string()),
+        };
+        serde::Serialize::serialize(&payload, serializer)
+    }
+}
+
+// Errors reach the frontend as their display string, or as a tagged object
+// when they are a `StructuredError`
+impl specta::Type for AppError {
+    fn inline(
+        type_map: &mut specta::TypeCollection,
+        generics: specta::Generics,
+    ) -> specta::DataType {
+        <ErrorPayload as specta::Type>::inline(type_map, generics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_for_frontend() {
+        let invalid = AppError::InvalidArgument {
+            argument: "batch_id".to_string(),
+            reason: "not a UUID".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&invalid).unwrap(),
+            serde_json::json!({ "kind": "invalid-argument", "argument": "batch_id", "reason": "not a UUID" })
+        );
+        assert_eq!(
+            serde_json::to_value(AppError::ReadOnly).unwrap(),
+            serde_json::json!("Not allowed in read-only mode")
+        );
     }
 }
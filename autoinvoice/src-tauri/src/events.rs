@@ -0,0 +1,195 @@
+//! Event names and payloads emitted to the frontend.
+//!
+//! Payload types are exported to TypeScript together with the command
+//! signatures (see `specta_builder` in `lib.rs`), and the names below are
+//! exported as constants so the frontend never hard-codes event strings.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::BTreeMap;
+
+use crate::services::ai_budget::{BudgetAction, BudgetScope};
+use crate::status::{BatchStatus, InvoiceStatus};
+
+pub const DOWNLOAD_PROGRESS: &str = "download:progress";
+pub const DOWNLOAD_LOG: &str = "download:log";
+pub const DOWNLOAD_LOG_BATCH: &str = "download:log-batch";
+pub const INVOICE_STATUS: &str = "invoice:status";
+pub const INVOICE_STATUS_BATCH: &str = "invoice:status-batch";
+pub const BATCH_STATUS: &str = "batch:status";
+pub const DOWNLOADS_CANCELLED: &str = "downloads:cancelled";
+pub const CAPTCHA_REQUIRED: &str = "captcha:required";
+pub const CONNECTIVITY_CHANGED: &str = "connectivity:changed";
+pub const SELECTORS_OUTDATED: &str = "selectors:outdated";
+pub const BROWSER_PREVIEW: &str = "browser:preview";
+pub const UPLOAD_PROGRESS: &str = "upload:progress";
+pub const INVOICES_AVAILABLE: &str = "invoices:available";
+pub const SETTINGS_CHANGED: &str = "settings:changed";
+pub const BUDGET_EXCEEDED: &str = "budget:exceeded";
+pub const BATCH_COMPLETED: &str = "batch:completed";
+
+/// Event names keyed by a stable identifier, exported as `EVENT_NAMES`
+pub fn names() -> BTreeMap<&'static str, &'static str> {
+    BTreeMap::from([
+        ("downloadProgress", DOWNLOAD_PROGRESS),
+        ("downloadLog", DOWNLOAD_LOG),
+        ("downloadLogBatch", DOWNLOAD_LOG_BATCH),
+        ("invoiceStatus", INVOICE_STATUS),
+        ("invoiceStatusBatch", INVOICE_STATUS_BATCH),
+        ("batchStatus", BATCH_STATUS),
+        ("downloadsCancelled", DOWNLOADS_CANCELLED),
+        ("captchaRequired", CAPTCHA_REQUIRED),
+        ("connectivityChanged", CONNECTIVITY_CHANGED),
+        ("selectorsOutdated", SELECTORS_OUTDATED),
+        ("browserPreview", BROWSER_PREVIEW),
+        ("uploadProgress", UPLOAD_PROGRESS),
+        ("invoicesAvailable", INVOICES_AVAILABLE),
+        ("settingsChanged", SETTINGS_CHANGED),
+        ("budgetExceeded", BUDGET_EXCEEDED),
+        ("batchCompleted", BATCH_COMPLETED),
+    ])
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ProgressEvent {
+    pub batch_id: String,
+    pub current: u32,
+    pub total: u32,
+    pub percentage: u32,
+}
+
+/// Lowest level of backend log entries sent to the log viewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogLevel {
+    Info,
+    /// Also selector attempts and CDP network responses
+    Debug,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct LogEvent {
+    pub batch_id: String,
+    pub timestamp: String,
+    pub level: String,
+    pub message: String,
+    /// Request id of the entry in the backend log, see `tracing_log`
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct InvoiceStatusEvent {
+    pub batch_id: String,
+    pub invoice_id: String,
+    pub status: InvoiceStatus,
+    pub error: Option<String>,
+    pub file_path: Option<String>,
+}
+
+/// Log entries held back by event throttling, in emission order
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct LogBatchEvent {
+    pub batch_id: String,
+    pub entries: Vec<LogEvent>,
+}
+
+/// Latest status of each invoice that changed while events were throttled
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct InvoiceStatusBatchEvent {
+    pub batch_id: String,
+    pub statuses: Vec<InvoiceStatusEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct BatchStatusEvent {
+    pub batch_id: String,
+    pub batch_name: Option<String>,
+    pub status: BatchStatus,
+}
+
+/// A batch run ended, successfully or not; `get_batch_result` has the
+/// invoices
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct BatchCompletedEvent {
+    pub batch_id: String,
+    pub batch_name: Option<String>,
+    pub status: BatchStatus,
+    pub total: u32,
+    pub success_count: u32,
+    pub failed_count: u32,
+    /// Why the batch could not run, when it stopped on an error
+    pub error: Option<String>,
+}
+
+/// Every running batch was cancelled at once
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct DownloadsCancelledEvent {
+    pub batch_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CaptchaRequiredEvent {
+    pub batch_id: String,
+    pub invoice_id: String,
+    pub invoice_code: String,
+    pub image_base64: String,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ConnectivityEvent {
+    pub online: bool,
+    /// Batches waiting in the offline queue
+    pub queued_batches: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct SelectorsOutdatedEvent {
+    pub local_version: u32,
+    pub min_version: u32,
+    pub message: Option<String>,
+}
+
+/// Downscaled JPEG of the page the bot is working on
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct BrowserPreviewEvent {
+    pub batch_id: String,
+    pub invoice_id: String,
+    pub image_base64: String,
+    pub captured_at: String,
+}
+
+/// A finished batch being copied to remote storage, one event per file
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct UploadProgressEvent {
+    pub batch_id: String,
+    /// Where the batch goes, e.g. "WebDAV" or "Google Drive"
+    pub target: String,
+    pub file_name: String,
+    pub current: u32,
+    pub total: u32,
+}
+
+/// Invoices an earlier batch recorded as not found, downloaded by this one
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct InvoicesAvailableEvent {
+    pub batch_id: String,
+    pub codes: Vec<String>,
+}
+
+/// Saved settings changed, from this window or elsewhere in the app
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct SettingsChangedEvent {
+    /// Names of the changed settings, without their values
+    pub changed: Vec<String>,
+}
+
+/// A batch reached its AI captcha budget, see `ai_budget`
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct BudgetExceededEvent {
+    pub batch_id: String,
+    pub scope: BudgetScope,
+    pub limit_usd: f64,
+    pub spent_usd: f64,
+    /// Manual captchas from now on, or paused until `continue_over_budget`
+    pub action: BudgetAction,
+}
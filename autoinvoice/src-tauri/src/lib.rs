@@ -1,47 +1,319 @@
 mod error;
+mod events;
+mod status;
 mod services;
 mod commands;
 
 use std::sync::Arc;
-use tauri::Manager;
+use tauri::menu::{Menu, MenuItem, Submenu};
+use tauri::{AppHandle, Manager};
 use commands::download::DownloadState;
+use error::AppError;
+use services::app_lock::AppLock;
 use services::database::Database;
+use services::ipc_validation::{self, UrlAllowList};
+use services::portal_profiles;
+use services::path_policy::PathPolicy;
+use services::settings_service::SettingsService;
 
 /// Database state wrapper for Tauri
 pub struct DatabaseState(pub Arc<Database>);
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_dialog::init())
-        .setup(|app| {
-            // Initialize database in app data directory
-            let app_data_dir = app.path().app_data_dir()
-                .expect("Failed to get app data directory");
-            let db = Database::new(app_data_dir)
-                .expect("Failed to initialize database");
-            app.manage(DatabaseState(Arc::new(db)));
-            Ok(())
-        })
-        .manage(DownloadState::default())
-        .invoke_handler(tauri::generate_handler![
+/// Where the generated TypeScript bindings are written, relative to src-tauri
+const BINDINGS_PATH: &str = "../src/bindings.ts";
+
+/// Collect command signatures, event payloads and event names for TypeScript export
+fn specta_builder() -> tauri_specta::Builder<tauri::Wry> {
+    tauri_specta::Builder::<tauri::Wry>::new()
+        .commands(tauri_specta::collect_commands![
             // Excel commands
+            commands::choose_source_file,
+            commands::load_demo_invoices,
             commands::parse_excel,
+            commands::parse_pdf_for_codes,
+            commands::reparse_source,
+            commands::get_batches_for_source,
+            // Portal account commands
+            commands::list_portal_invoices,
+            commands::discover_invoices,
             // Download commands
             commands::start_download,
+            commands::get_batch_result,
+            commands::queue_download,
+            commands::check_inbox,
+            commands::get_retry_later_invoices,
+            commands::start_queued_download,
             commands::cancel_download,
+            commands::cancel_all_downloads,
+            commands::get_active_downloads,
+            commands::set_log_level,
+            commands::get_log_level,
+            commands::get_unfinished_batches,
+            commands::resume_batch,
+            commands::discard_unfinished_batch,
             commands::submit_manual_captcha,
+            commands::get_pending_captchas,
+            commands::skip_manual_captcha,
+            commands::set_defer_manual_captchas,
+            commands::continue_over_budget,
+            commands::stream_browser_preview,
+            commands::estimate_batch,
             // Settings commands
             commands::get_settings,
             commands::save_settings,
+            commands::test_api_key,
+            commands::get_ai_spend,
+            commands::get_ai_key_usage,
+            commands::get_captcha_dataset,
+            commands::get_local_captcha_model,
+            commands::choose_download_directory,
+            commands::get_portal_profiles,
+            commands::save_portal_profile,
+            commands::delete_portal_profile,
+            commands::export_config,
+            commands::import_config,
+            commands::get_onboarding_state,
+            commands::complete_onboarding_step,
+            // Cloud drive commands
+            commands::start_cloud_login,
+            commands::complete_cloud_login,
+            commands::disconnect_cloud_drive,
+            commands::get_cloud_drive_status,
             // History commands
             commands::get_batches,
             commands::get_batch_invoices,
+            commands::get_invoice_xml,
+            commands::get_invoice_signatures,
+            commands::get_invoice_metadata,
+            commands::get_pdf_optimization,
+            commands::get_vat_summary,
+            commands::get_batch_anomalies,
+            commands::compare_batches,
             commands::delete_batch,
             commands::get_failed_invoices,
+            commands::ignore_invoices,
+            commands::get_invoice_note,
+            commands::set_invoice_note,
+            commands::print_batch_summary,
+            commands::export_batch_invoices,
+            commands::package_batch,
+            commands::get_storage_report,
+            // Diagnostics commands
+            commands::export_repro_bundle,
+            commands::inspect_page,
+            commands::check_selector_compatibility,
+            commands::get_performance_metrics,
+            commands::clear_performance_metrics,
+            // Audit commands
+            commands::get_audit_log,
+            // Security commands
+            commands::get_lock_status,
+            commands::unlock_app,
+            commands::lock_app,
+            commands::set_passcode,
+            commands::set_read_only,
         ])
+        .typ::<events::ProgressEvent>()
+        .typ::<events::LogEvent>()
+        .typ::<events::InvoiceStatusEvent>()
+        .typ::<events::LogBatchEvent>()
+        .typ::<events::InvoiceStatusBatchEvent>()
+        .typ::<events::BatchStatusEvent>()
+        .typ::<events::DownloadsCancelledEvent>()
+        .typ::<events::CaptchaRequiredEvent>()
+        .typ::<events::ConnectivityEvent>()
+        .typ::<events::SelectorsOutdatedEvent>()
+        .typ::<events::BrowserPreviewEvent>()
+        .typ::<events::UploadProgressEvent>()
+        .typ::<events::InvoicesAvailableEvent>()
+        .typ::<events::SettingsChangedEvent>()
+        .typ::<events::BudgetExceededEvent>()
+        .typ::<events::BatchCompletedEvent>()
+        .constant("EVENT_NAMES", events::names())
+}
+
+/// How the TypeScript bindings are rendered
+fn typescript() -> specta_typescript::Typescript {
+    specta_typescript::Typescript::default()
+        .header("// @ts-nocheck")
+        .bigint(specta_typescript::BigIntExportBehavior::Number)
+}
+
+fn export_bindings(builder: &tauri_specta::Builder<tauri::Wry>) -> Result<(), String> {
+    builder
+        .export(typescript(), BINDINGS_PATH)
+        .map_err(|e| e.to_string())
+}
+
+/// Menu item cancelling every running batch
+const CANCEL_ALL_MENU_ID: &str = "cancel-all-downloads";
+
+/// The platform's default menu with a "Tải xuống" menu added
+fn build_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let menu = Menu::default(app)?;
+    let cancel_all = MenuItem::with_id(app, CANCEL_ALL_MENU_ID, "Hủy tất cả", true, None::<&str>)?;
+    menu.append(&Submenu::with_items(app, "Tải xuống", true, &[&cancel_all])?)?;
+    Ok(menu)
+}
+
+/// Refuse every command but unlocking while the app is locked, so history and
+/// the API key stay hidden until the passcode is entered, and refuse writes in
+/// read-only mode
+fn guard_locked(
+    handler: impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let checked = match invoke.message.webview().try_state::<AppLock>() {
+            Some(lock) => lock.check(invoke.message.command()),
+            None => Err(AppError::Locked),
+        };
+
+        if let Err(e) = checked {
+            invoke.resolver.reject(e);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+/// Refuse commands whose ids, paths or URLs are malformed or point outside
+/// what the app works with, before they run
+fn validate_inputs(
+    handler: impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        if let tauri::ipc::InvokeBody::Json(args) = invoke.message.payload() {
+            let webview = invoke.message.webview();
+            let allow_list = || {
+                let db = webview.try_state::<DatabaseState>();
+                UrlAllowList {
+                    profiles: portal_profiles::merge(
+                        db.as_ref().and_then(|db| db.0.get_portal_profiles().ok()).unwrap_or_default(),
+                    ),
+                    vnpt_url: webview
+                        .try_state::<SettingsService>()
+                        .map(|settings| settings.get().vnpt_url)
+                        .unwrap_or_default(),
+                }
+            };
+            if let Err(e) = ipc_validation::validate_args(args, allow_list) {
+                invoke.resolver.reject(e);
+                return true;
+            }
+        }
+        handler(invoke)
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let builder = specta_builder();
+
+    #[cfg(debug_assertions)]
+    export_bindings(&builder).expect("Failed to export TypeScript bindings");
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            // Initialize database in app data directory
+            let app_data_dir = app.path().app_data_dir()
+                .expect("Failed to get app data directory");
+            // Without a backend log the app runs the same, only untraced
+            let _ = services::tracing_log::init(&app_data_dir);
+            // Panics in invoice downloads become errors with a report
+            services::panic_report::install_hook();
+            let db = Database::new(app_data_dir)
+                .expect("Failed to initialize database");
+
+            // Profiles of browsers killed with the previous run
+            services::browser_profile::remove_stale_profiles();
+
+            let db = Arc::new(db);
+            let settings = SettingsService::new(db.clone()).expect("Failed to load settings");
+
+            // Warm a browser on the saved lookup page so the first batch starts
+            // faster; demo batches need none
+            let current = settings.get();
+            if !current.demo_mode {
+                let viewport = current.browser_viewport();
+                app.state::<DownloadState>()
+                    .browser_pool
+                    .prewarm(current.vnpt_url, viewport);
+            }
+
+            // Start locked when a passcode is set
+            let locked = db.get_passcode_hash().map(|hash| hash.is_some()).unwrap_or(true);
+            let read_only = db.get_read_only().unwrap_or(true);
+            app.manage(AppLock::new(locked, read_only));
+
+            // Tell the frontend about saved settings, wherever they were saved
+            services::settings_service::spawn_notifier(app.handle().clone(), settings.subscribe());
+            // Watch the network so queued batches can start once online
+            services::connectivity::spawn_monitor(app.handle().clone(), db.clone(), settings.subscribe());
+            // Warn early when the portal HTML is known to have outgrown our selectors
+            services::compatibility::spawn_startup_check(app.handle().clone());
+            // Queue invoices from notification emails when inbox ingestion is on
+            services::inbox::spawn_poller(app.handle().clone(), db.clone(), settings.subscribe());
+            // Look not-found invoices up again once their retry interval passed
+            services::retry_later::spawn_scheduler(app.handle().clone(), db.clone(), settings.subscribe());
+
+            app.manage(settings);
+            app.manage(DatabaseState(db));
+            Ok(())
+        })
+        .manage(DownloadState::default())
+        .manage(PathPolicy::default())
+        .menu(build_menu)
+        .on_menu_event(|app, event| {
+            if event.id() == CANCEL_ALL_MENU_ID {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    let db = app.state::<DatabaseState>().0.clone();
+                    let _ = commands::download::cancel_all(&app, &app.state::<DownloadState>(), &db).await;
+                });
+            }
+        })
+        .invoke_handler(guard_locked(validate_inputs(builder.invoke_handler())))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_command_is_classified_for_read_only_mode() {
+        use services::app_lock::{READ_COMMANDS, WRITE_COMMANDS};
+
+        let bindings = specta_builder().export_str(typescript()).unwrap();
+        let commands: Vec<&str> = bindings
+            .split("TAURI_INVOKE(\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .collect();
+        assert!(commands.contains(&"start_download"));
+
+        for command in commands {
+            assert!(
+                READ_COMMANDS.contains(&command) != WRITE_COMMANDS.contains(&command),
+                "{} must be listed once, in READ_COMMANDS or WRITE_COMMANDS of app_lock.rs",
+                command
+            );
+        }
+    }
+
+    /// The committed bindings are regenerated by running the app in debug mode
+    #[test]
+    fn test_bindings_up_to_date() {
+        let generated = specta_builder().export_str(typescript()).unwrap();
+        let committed = std::fs::read_to_string(BINDINGS_PATH).unwrap();
+        assert!(
+            generated == committed,
+            "{} is out of date; run the app in debug mode to regenerate it",
+            BINDINGS_PATH
+        );
+    }
+}
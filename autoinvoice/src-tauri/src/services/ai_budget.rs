@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::AppError;
+use crate::services::database::Database;
+use crate::status::impl_setting_parse;
+
+/// gpt-4o-mini list prices, in USD per million tokens
+const INPUT_USD_PER_MILLION: f64 = 0.15;
+const OUTPUT_USD_PER_MILLION: f64 = 0.60;
+
+/// What a batch does once its AI spend crosses a cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetAction {
+    /// Ask the user for the remaining captchas
+    #[default]
+    ManualCaptcha,
+    /// Wait until the user lets the batch go over the cap, or cancels it
+    Pause,
+}
+
+impl BudgetAction {
+    pub const ALL: &'static [BudgetAction] = &[BudgetAction::ManualCaptcha, BudgetAction::Pause];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetAction::ManualCaptcha => "manual-captcha",
+            BudgetAction::Pause => "pause",
+        }
+    }
+}
+
+impl_setting_parse!(BudgetAction);
+
+/// Which cap was crossed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetScope {
+    Batch,
+    Month,
+}
+
+/// A cap and what was spent against it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Type)]
+pub struct BudgetOverrun {
+    pub scope: BudgetScope,
+    pub limit_usd: f64,
+    pub spent_usd: f64,
+}
+
+/// AI calls made with one API key in a month
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct AiKeyUsage {
+    /// Last characters of the key, see `captcha::key_hint`
+    pub key_hint: String,
+    pub calls: u32,
+    pub cost_usd: f64,
+}
+
+/// Estimated price of a completion from its token usage
+pub fn cost_usd(prompt_tokens: u32, completion_tokens: u32) -> f64 {
+    (prompt_tokens as f64 * INPUT_USD_PER_MILLION + completion_tokens as f64 * OUTPUT_USD_PER_MILLION)
+        / 1_000_000.0
+}
+
+/// Key of the current month in the spend table, e.g. `2025-03`
+pub fn current_month() -> String {
+    chrono::Local::now().format("%Y-%m").to_string()
+}
+
+/// AI spend of a batch, shared by its sub-batches, against the per-batch and
+/// monthly caps; a cap of 0 means none
+pub struct AiBudget {
+    batch_limit_usd: f64,
+    month_limit_usd: f64,
+    batch_spent_usd: Mutex<f64>,
+    /// Across all batches, as last read from the database
+    month_spent_usd: Mutex<f64>,
+    /// Set once the user lets the batch go over its caps
+    lifted: AtomicBool,
+    /// Set once `budget:exceeded` was sent for the batch
+    announced: AtomicBool,
+    /// Keeps the monthly spend across batches and restarts
+    db: Option<Arc<Database>>,
+}
+
+impl AiBudget {
+    pub fn new(batch_limit_usd: f64, month_limit_usd: f64, db: Option<Arc<Database>>) -> Result<Self, AppError> {
+        let month_spent_usd = match &db {
+            Some(db) => db.get_ai_spend(&current_month())?,
+            None => 0.0,
+        };
+        Ok(Self {
+            batch_limit_usd,
+            month_limit_usd,
+            batch_spent_usd: Mutex::new(0.0),
+            month_spent_usd: Mutex::new(month_spent_usd),
+            lifted: AtomicBool::new(false),
+            announced: AtomicBool::new(false),
+            db,
+        })
+    }
+
+    /// Add the cost of one API call, attributed to the key that served it
+    pub fn record(&self, key_hint: &str, cost_usd: f64) {
+        *self.batch_spent_usd.lock().unwrap() += cost_usd;
+        let month_spent = match &self.db {
+            Some(db) => db.add_ai_spend(&current_month(), key_hint, cost_usd).ok(),
+            None => None,
+        };
+        let mut month_spent_usd = self.month_spent_usd.lock().unwrap();
+        *month_spent_usd = month_spent.unwrap_or(*month_spent_usd + cost_usd);
+    }
+
+    pub fn batch_spent_usd(&self) -> f64 {
+        *self.batch_spent_usd.lock().unwrap()
+    }
+
+    /// The cap the spend reached, unless the user lifted the caps
+    pub fn overrun(&self) -> Option<BudgetOverrun> {
+        if self.lifted.load(Ordering::SeqCst) {
+            return None;
+        }
+        let batch = BudgetOverrun {
+            scope: BudgetScope::Batch,
+            limit_usd: self.batch_limit_usd,
+            spent_usd: self.batch_spent_usd(),
+        };
+        let month = BudgetOverrun {
+            scope: BudgetScope::Month,
+            limit_usd: self.month_limit_usd,
+            spent_usd: *self.month_spent_usd.lock().unwrap(),
+        };
+        [batch, month]
+            .into_iter()
+            .find(|cap| cap.limit_usd > 0.0 && cap.spent_usd >= cap.limit_usd)
+    }
+
+    /// Error for an API call refused because a cap was reached
+    pub fn check(&self) -> Result<(), AppError> {
+        match self.overrun() {
+            Some(overrun) => Err(AppError::BudgetExceeded(format!(
+                "{} AI budget of ${:.2} reached (${:.4} spent)",
+                match overrun.scope {
+                    BudgetScope::Batch => "Batch",
+                    BudgetScope::Month => "Monthly",
+                },
+                overrun.limit_usd,
+                overrun.spent_usd
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Let the batch go over its caps
+    pub fn lift(&self) {
+        self.lifted.store(true, Ordering::SeqCst);
+    }
+
+    /// True the first time it is called for the batch
+    pub fn take_announcement(&self) -> bool {
+        !self.announced.swap(true, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_caps() {
+        // A captcha image is ~1000 prompt tokens
+        assert!((cost_usd(1_000, 4) - 0.0001524).abs() < 1e-12);
+
+        let budget = AiBudget::new(0.001, 0.0, None).unwrap();
+        assert!(budget.check().is_ok());
+        for _ in 0..6 {
+            budget.record("…AAAA", cost_usd(1_000, 4));
+        }
+        assert!(budget.check().is_ok());
+        budget.record("…BBBB", cost_usd(1_000, 4));
+        let overrun = budget.overrun().unwrap();
+        assert_eq!(overrun.scope, BudgetScope::Batch);
+        assert!(matches!(budget.check(), Err(AppError::BudgetExceeded(_))));
+        assert!(budget.take_announcement());
+        assert!(!budget.take_announcement());
+
+        budget.lift();
+        assert!(budget.check().is_ok());
+
+        let unlimited = AiBudget::new(0.0, 0.0, None).unwrap();
+        unlimited.record("…AAAA", 100.0);
+        assert!(unlimited.overrun().is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use specta::Type;
+
+/// Past invoices of a seller needed before its amounts are judged
+const MIN_SELLER_HISTORY: usize = 5;
+
+/// How many times above or below the seller's median an amount is an outlier
+const OUTLIER_FACTOR: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnomalyKind {
+    /// Same seller, series and number as another downloaded invoice
+    Duplicate,
+    /// Issued after today
+    FutureDate,
+    /// Total far outside the seller's usual range
+    AmountOutlier,
+}
+
+/// A suspicious invoice of a batch
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct InvoiceAnomaly {
+    pub invoice_id: String,
+    pub invoice_code: String,
+    pub kind: AnomalyKind,
+    pub message: String,
+}
+
+/// What is known of a downloaded invoice, from its XML or its PDF
+#[derive(Debug, Clone)]
+pub struct InvoiceFacts {
+    pub invoice_id: String,
+    pub invoice_code: String,
+    /// Part of the batch being checked, rather than an earlier one
+    pub in_batch: bool,
+    pub seller_tax_code: Option<String>,
+    pub series: Option<String>,
+    pub number: Option<String>,
+    pub issued_date: Option<String>,
+    pub total_payable: Option<f64>,
+}
+
+impl InvoiceFacts {
+    /// Seller, series and number, when all are known
+    fn identity(&self) -> Option<(&str, &str, &str)> {
+        let seller = self.seller_tax_code.as_deref().filter(|v| !v.is_empty())?;
+        let series = self.series.as_deref().filter(|v| !v.is_empty())?;
+        let number = self.number.as_deref().filter(|v| !v.is_empty())?;
+        // Numbers are zero-padded in some sources and not in others
+        Some((seller, series, number.trim_start_matches('0')))
+    }
+}
+
+/// Flag the invoices of the batch against every downloaded invoice in `all`
+pub fn detect(all: &[InvoiceFacts], today: NaiveDate) -> Vec<InvoiceAnomaly> {
+    let mut anomalies = Vec::new();
+
+    for invoice in all.iter().filter(|f| f.in_batch) {
+        let mut flag = |kind, message: String| {
+            anomalies.push(InvoiceAnomaly {
+                invoice_id: invoice.invoice_id.clone(),
+                invoice_code: invoice.invoice_code.clone(),
+                kind,
+                message,
+            })
+        };
+
+        if let Some(identity) = invoice.identity() {
+            let others: Vec<&str> = all
+                .iter()
+                .filter(|other| {
+                    other.invoice_id != invoice.invoice_id && other.identity() == Some(identity)
+                })
+                .map(|other| other.invoice_code.as_str())
+                .collect();
+            if !others.is_empty() {
+                flag(
+                    AnomalyKind::Duplicate,
+                    format!(
+                        "Invoice {}/{} of seller {} was also downloaded as {}",
+                        identity.1,
+                        identity.2,
+                        identity.0,
+                        others.join(", ")
+                    ),
+                );
+            }
+        }
+
+        if let Some(date) = invoice
+            .issued_date
+            .as_deref()
+            .and_then(|date| date.get(..10))
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        {
+            if date > today {
+                flag(AnomalyKind::FutureDate, format!("Issued in the future, on {}", date));
+            }
+        }
+
+        if let (Some(seller), Some(total)) = (&invoice.seller_tax_code, invoice.total_payable) {
+            let mut history: Vec<f64> = all
+                .iter()
+                .filter(|other| other.seller_tax_code.as_ref() == Some(seller) && !other.in_batch)
+                .filter_map(|other| other.total_payable)
+                .filter(|amount| *amount > 0.0)
+                .collect();
+            if history.len() >= MIN_SELLER_HISTORY && total > 0.0 {
+                history.sort_by(|a, b| a.total_cmp(b));
+                let median = history[history.len() / 2];
+                if total > median * OUTLIER_FACTOR || total < median / OUTLIER_FACTOR {
+                    flag(
+                        AnomalyKind::AmountOutlier,
+                        format!(
+                            "Total {:.0} is far from seller {}'s usual {:.0} ({} earlier invoices)",
+                            total,
+                            seller,
+                            median,
+                            history.len()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(id: &str, in_batch: bool, number: &str, date: &str, total: f64) -> InvoiceFacts {
+        InvoiceFacts {
+            invoice_id: id.to_string(),
+            invoice_code: format!("CODE-{}", id),
+            in_batch,
+            seller_tax_code: Some("0100109106".to_string()),
+            series: Some("C24TAA".to_string()),
+            number: Some(number.to_string()),
+            issued_date: Some(date.to_string()),
+            total_payable: Some(total),
+        }
+    }
+
+    #[test]
+    fn test_detect_anomalies() {
+        let mut all: Vec<InvoiceFacts> = (1..=5)
+            .map(|i| facts(&format!("old{}", i), false, &i.to_string(), "2024-01-10", 1_000_000.0))
+            .collect();
+        all.push(facts("a", true, "00000003", "2024-05-01", 1_100_000.0));
+        all.push(facts("b", true, "10", "2024-12-01", 1_000_000.0));
+        all.push(facts("c", true, "11", "2024-05-01", 50_000_000.0));
+
+        let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let anomalies = detect(&all, today);
+        let kinds: Vec<(&str, AnomalyKind)> = anomalies
+            .iter()
+            .map(|a| (a.invoice_id.as_str(), a.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a", AnomalyKind::Duplicate),
+                ("b", AnomalyKind::FutureDate),
+                ("c", AnomalyKind::AmountOutlier),
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::AppError;
+
+/// Commands the frontend may call while the app is locked
+const OPEN_WHILE_LOCKED: &[&str] = &["get_lock_status", "unlock_app"];
+
+/// Commands that only read, the only ones allowed in read-only mode; a new
+/// command is refused there until it is listed
+pub(crate) const READ_COMMANDS: &[&str] = &[
+    // Excel and portal listing, nothing recorded until a batch starts
+    "choose_source_file",
+    "parse_excel",
+    "parse_pdf_for_codes",
+    "reparse_source",
+    "get_batches_for_source",
+    "list_portal_invoices",
+    // Downloads
+    "get_batch_result",
+    "get_retry_later_invoices",
+    "get_active_downloads",
+    "set_log_level",
+    "get_log_level",
+    "get_unfinished_batches",
+    "get_pending_captchas",
+    "stream_browser_preview",
+    "estimate_batch",
+    // Settings
+    "get_settings",
+    "test_api_key",
+    "get_ai_spend",
+    "get_ai_key_usage",
+    "get_captcha_dataset",
+    "get_local_captcha_model",
+    "get_portal_profiles",
+    "export_config",
+    "get_onboarding_state",
+    "get_cloud_drive_status",
+    // History and exports of it
+    "get_batches",
+    "get_batch_invoices",
+    "get_invoice_xml",
+    "get_invoice_signatures",
+    "get_invoice_metadata",
+    "get_pdf_optimization",
+    "get_vat_summary",
+    "get_batch_anomalies",
+    "compare_batches",
+    "get_failed_invoices",
+    "get_invoice_note",
+    "print_batch_summary",
+    "export_batch_invoices",
+    "package_batch",
+    "get_storage_report",
+    // Diagnostics
+    "export_repro_bundle",
+    "inspect_page",
+    "check_selector_compatibility",
+    "get_performance_metrics",
+    "get_audit_log",
+    // Security, including leaving read-only mode with the passcode
+    "get_lock_status",
+    "unlock_app",
+    "lock_app",
+    "set_read_only",
+];
+
+/// Commands that start downloads or change stored data; only listed so a
+/// test can check every command is classified
+#[cfg(test)]
+pub(crate) const WRITE_COMMANDS: &[&str] = &[
+    "load_demo_invoices",
+    "discover_invoices",
+    "start_download",
+    "queue_download",
+    "check_inbox",
+    "start_queued_download",
+    "cancel_download",
+    "cancel_all_downloads",
+    "resume_batch",
+    "discard_unfinished_batch",
+    "submit_manual_captcha",
+    "skip_manual_captcha",
+    "set_defer_manual_captchas",
+    "continue_over_budget",
+    "save_settings",
+    "choose_download_directory",
+    "save_portal_profile",
+    "delete_portal_profile",
+    "import_config",
+    "complete_onboarding_step",
+    "start_cloud_login",
+    "complete_cloud_login",
+    "disconnect_cloud_drive",
+    "delete_batch",
+    "ignore_invoices",
+    "set_invoice_note",
+    "clear_performance_metrics",
+    "set_passcode",
+];
+
+/// Which commands are currently refused: everything but unlocking while locked
+/// (starts locked when a passcode is set), writes while in read-only mode
+pub struct AppLock {
+    locked: AtomicBool,
+    read_only: AtomicBool,
+}
+
+impl AppLock {
+    pub fn new(locked: bool, read_only: bool) -> Self {
+        Self {
+            locked: AtomicBool::new(locked),
+            read_only: AtomicBool::new(read_only),
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    pub fn lock(&self) {
+        self.locked.store(true, Ordering::SeqCst);
+    }
+
+    pub fn unlock(&self) {
+        self.locked.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::SeqCst);
+    }
+
+    /// Why `command` may not run right now, if it may not
+    pub fn check(&self, command: &str) -> Result<(), AppError> {
+        if self.is_locked() && !OPEN_WHILE_LOCKED.contains(&command) {
+            return Err(AppError::Locked);
+        }
+        if self.is_read_only() && !READ_COMMANDS.contains(&command) {
+            return Err(AppError::ReadOnly);
+        }
+        Ok(())
+    }
+}
+
+/// Hash a passcode as an argon2 PHC string for the settings table
+pub fn hash_passcode(passcode: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(passcode.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::ConfigError(format!("Failed to hash passcode: {}", e)))
+}
+
+/// Check a passcode against a stored PHC string; a malformed hash never matches
+pub fn verify_passcode(passcode: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(passcode.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passcode_round_trip() {
+        let hash = hash_passcode("1234").unwrap();
+
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_passcode("1234", &hash));
+        assert!(!verify_passcode("4321", &hash));
+        assert!(!verify_passcode("1234", "not a hash"));
+    }
+
+    #[test]
+    fn test_locked_app_only_allows_unlock() {
+        let lock = AppLock::new(true, false);
+        assert!(lock.check("unlock_app").is_ok());
+        assert!(matches!(lock.check("get_settings"), Err(AppError::Locked)));
+
+        lock.unlock();
+        assert!(lock.check("get_settings").is_ok());
+    }
+
+    #[test]
+    fn test_read_only_refuses_writes() {
+        let lock = AppLock::new(false, true);
+        assert!(lock.check("get_batches").is_ok());
+        assert!(matches!(lock.check("start_download"), Err(AppError::ReadOnly)));
+        assert!(matches!(lock.check("delete_batch"), Err(AppError::ReadOnly)));
+        assert!(matches!(lock.check("set_invoice_note"), Err(AppError::ReadOnly)));
+        // Commands nobody classified yet are refused too
+        assert!(matches!(lock.check("new_command"), Err(AppError::ReadOnly)));
+        assert!(lock.check("set_read_only").is_ok());
+
+        lock.set_read_only(false);
+        assert!(lock.check("delete_batch").is_ok());
+    }
+}
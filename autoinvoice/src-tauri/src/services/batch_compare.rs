@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use specta::Type;
+
+use crate::commands::history::HistoryInvoice;
+use crate::status::InvoiceStatus;
+
+/// Invoice found in both batches with a different outcome
+#[derive(Debug, Clone, PartialEq, Serialize, Type)]
+pub struct StatusChange {
+    pub code: String,
+    pub status_a: InvoiceStatus,
+    pub status_b: InvoiceStatus,
+}
+
+/// Differences between two batches, e.g. a spreadsheet and its corrected
+/// re-run; codes are sorted
+#[derive(Debug, Clone, Default, Serialize, Type)]
+pub struct BatchComparison {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub status_changed: Vec<StatusChange>,
+}
+
+/// Compare the invoices of batch A with those of batch B by lookup code
+pub fn compare(a: &[HistoryInvoice], b: &[HistoryInvoice]) -> BatchComparison {
+    let (a, b) = (statuses(a), statuses(b));
+
+    let only = |of: &BTreeMap<&str, InvoiceStatus>, other: &BTreeMap<&str, InvoiceStatus>| {
+        of.keys()
+            .filter(|code| !other.contains_key(*code))
+            .map(|code| code.to_string())
+            .collect()
+    };
+    let status_changed = a
+        .iter()
+        .filter_map(|(code, &status_a)| {
+            let status_b = *b.get(code)?;
+            (status_a != status_b).then(|| StatusChange {
+                code: code.to_string(),
+                status_a,
+                status_b,
+            })
+        })
+        .collect();
+
+    BatchComparison {
+        only_in_a: only(&a, &b),
+        only_in_b: only(&b, &a),
+        status_changed,
+    }
+}
+
+/// Status of each code; a code listed twice counts as downloaded when
+/// either row was
+fn statuses(invoices: &[HistoryInvoice]) -> BTreeMap<&str, InvoiceStatus> {
+    let mut statuses = BTreeMap::new();
+    for invoice in invoices {
+        let status = statuses.entry(invoice.code.trim()).or_insert(invoice.status);
+        if *status != InvoiceStatus::Success {
+            *status = invoice.status;
+        }
+    }
+    statuses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(code: &str, status: InvoiceStatus) -> HistoryInvoice {
+        HistoryInvoice {
+            id: uuid::Uuid::new_v4().to_string(),
+            batch_id: String::new(),
+            code: code.to_string(),
+            status,
+            error: None,
+            file_path: None,
+            downloaded_at: None,
+            repro_path: None,
+            portal: None,
+            remote_url: None,
+            signature_status: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_batches() {
+        let a = [
+            invoice("C24TAA0000001_Ab", InvoiceStatus::Success),
+            invoice("C24TAA0000002_Ab", InvoiceStatus::Failed),
+            invoice("C24TAA0000003_Ab", InvoiceStatus::NotFound),
+        ];
+        let b = [
+            invoice("C24TAA0000004_Ab", InvoiceStatus::Success),
+            invoice("C24TAA0000002_Ab", InvoiceStatus::Success),
+            invoice("C24TAA0000001_Ab", InvoiceStatus::Success),
+            // Listed twice, downloaded once
+            invoice("C24TAA0000003_Ab", InvoiceStatus::Success),
+            invoice("C24TAA0000003_Ab", InvoiceStatus::Failed),
+        ];
+
+        let comparison = compare(&a, &b);
+        assert!(comparison.only_in_a.is_empty());
+        assert_eq!(comparison.only_in_b, vec!["C24TAA0000004_Ab"]);
+        assert_eq!(
+            comparison.status_changed,
+            vec![
+                StatusChange {
+                    code: "C24TAA0000002_Ab".to_string(),
+                    status_a: InvoiceStatus::Failed,
+                    status_b: InvoiceStatus::Success,
+                },
+                StatusChange {
+                    code: "C24TAA0000003_Ab".to_string(),
+                    status_a: InvoiceStatus::NotFound,
+                    status_b: InvoiceStatus::Success,
+                },
+            ]
+        );
+    }
+}
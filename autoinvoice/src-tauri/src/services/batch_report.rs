@@ -0,0 +1,264 @@
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point,
+};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::commands::history::{DownloadBatch, HistoryInvoice};
+use crate::error::AppError;
+use crate::status::InvoiceStatus;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 5.5;
+
+/// Space kept free at the bottom of the last page for signatures
+const SIGNATURE_HEIGHT: f32 = 45.0;
+
+/// Longest note printed next to an invoice before it is cut
+const MAX_NOTE_CHARS: usize = 48;
+
+/// Table column x positions, in mm
+const COL_INDEX: f32 = MARGIN;
+const COL_CODE: f32 = MARGIN + 12.0;
+const COL_STATUS: f32 = MARGIN + 75.0;
+const COL_NOTE: f32 = MARGIN + 102.0;
+
+/// Unicode fonts tried in order; without one, text is printed without diacritics
+const SYSTEM_FONTS: &[&str] = &[
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+];
+
+/// Font used for the report, and whether it can print Vietnamese diacritics
+struct ReportFont {
+    font: IndirectFontRef,
+    unicode: bool,
+}
+
+impl ReportFont {
+    fn load(doc: &PdfDocumentReference) -> Result<Self, AppError> {
+        for path in SYSTEM_FONTS {
+            let Ok(file) = File::open(path) else {
+                continue;
+            };
+            if let Ok(font) = doc.add_external_font(file) {
+                return Ok(Self { font, unicode: true });
+            }
+        }
+
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(pdf_error)?;
+        Ok(Self {
+            font,
+            unicode: false,
+        })
+    }
+
+    fn text(&self, layer: &PdfLayerReference, text: &str, size: f32, x: f32, y: f32) {
+        let text = if self.unicode {
+            text.to_string()
+        } else {
+            fold_diacritics(text)
+        };
+        layer.use_text(text, size, Mm(x), Mm(y), &self.font);
+    }
+}
+
+/// Render a printable summary of a batch: totals, every invoice with its
+/// status, and signature blocks for the paper archive
+pub fn write_batch_summary(
+    path: &Path,
+    batch: &DownloadBatch,
+    invoices: &[HistoryInvoice],
+) -> Result<(), AppError> {
+    let (doc, page, layer) = PdfDocument::new(
+        format!("Báo cáo lô tải hóa đơn {}", batch.display_name()),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Layer 1",
+    );
+    let font = ReportFont::load(&doc)?;
+    let mut layer = doc.get_page(page).get_layer(layer);
+
+    let mut y = PAGE_HEIGHT - MARGIN - 5.0;
+    font.text(&layer, "BÁO CÁO LÔ TẢI HÓA ĐƠN", 16.0, MARGIN, y);
+    y -= 10.0;
+
+    let count = |status: InvoiceStatus| invoices.iter().filter(|i| i.status == status).count();
+    let success = count(InvoiceStatus::Success);
+    let failed = count(InvoiceStatus::Failed);
+    let not_found = count(InvoiceStatus::NotFound);
+    let header_lines = [
+        format!("Lô: {}", batch.display_name()),
+        format!("Mã lô: {}", batch.id),
+        format!("File nguồn: {}", batch.source_file.as_deref().unwrap_or("-")),
+        format!("Ngày tạo: {}", format_date(&batch.created_at)),
+        format!("Thư mục lưu: {}", batch.download_directory),
+        format!(
+            "Tổng: {}    Thành công: {}    Thất bại: {}    Không tìm thấy: {}    Khác: {}",
+            invoices.len(),
+            success,
+            failed,
+            not_found,
+            invoices.len() - success - failed - not_found
+        ),
+    ];
+    for line in &header_lines {
+        font.text(&layer, line, 10.0, MARGIN, y);
+        y -= 6.0;
+    }
+    y -= 4.0;
+
+    y = table_header(&font, &layer, y);
+
+    for (idx, invoice) in invoices.iter().enumerate() {
+        if y < MARGIN + ROW_HEIGHT {
+            let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            layer = doc.get_page(page).get_layer(page_layer);
+            y = table_header(&font, &layer, PAGE_HEIGHT - MARGIN - 5.0);
+        }
+
+        font.text(&layer, &(idx + 1).to_string(), 9.0, COL_INDEX, y);
+        font.text(&layer, &invoice.code, 9.0, COL_CODE, y);
+        font.text(&layer, status_label(invoice.status), 9.0, COL_STATUS, y);
+        font.text(&layer, &truncate(&invoice_note(invoice), MAX_NOTE_CHARS), 9.0, COL_NOTE, y);
+        y -= ROW_HEIGHT;
+    }
+
+    if y < MARGIN + SIGNATURE_HEIGHT {
+        let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        layer = doc.get_page(page).get_layer(page_layer);
+    }
+    signatures(&font, &layer);
+
+    let file = File::create(path)?;
+    doc.save(&mut BufWriter::new(file)).map_err(pdf_error)
+}
+
+/// Print the table column titles with a rule below, returning the first row position
+fn table_header(font: &ReportFont, layer: &PdfLayerReference, y: f32) -> f32 {
+    font.text(layer, "STT", 9.0, COL_INDEX, y);
+    font.text(layer, "Mã hóa đơn", 9.0, COL_CODE, y);
+    font.text(layer, "Trạng thái", 9.0, COL_STATUS, y);
+    font.text(layer, "Tệp / Lỗi", 9.0, COL_NOTE, y);
+
+    let rule_y = y - 2.0;
+    layer.set_outline_thickness(0.5);
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(MARGIN), Mm(rule_y)), false),
+            (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(rule_y)), false),
+        ],
+        is_closed: false,
+    });
+
+    y - ROW_HEIGHT - 1.0
+}
+
+fn signatures(font: &ReportFont, layer: &PdfLayerReference) {
+    let y = MARGIN + SIGNATURE_HEIGHT - 10.0;
+
+    for (title, x) in [("Người lập", MARGIN + 20.0), ("Kế toán trưởng", PAGE_WIDTH - MARGIN - 55.0)] {
+        font.text(layer, title, 10.0, x, y);
+        font.text(layer, "(Ký, ghi rõ họ tên)", 8.0, x - 2.0, y - 5.0);
+    }
+}
+
+fn status_label(status: InvoiceStatus) -> &'static str {
+    match status {
+        InvoiceStatus::Pending => "Chờ xử lý",
+        InvoiceStatus::Downloading => "Đang tải",
+        InvoiceStatus::Success => "Thành công",
+        InvoiceStatus::Failed => "Thất bại",
+        InvoiceStatus::Skipped => "Bỏ qua",
+        InvoiceStatus::Cancelled => "Đã hủy",
+        InvoiceStatus::NeedsManual => "Cần nhập captcha",
+        InvoiceStatus::NotFound => "Không tìm thấy",
+        InvoiceStatus::Ignored => "Đã bỏ qua",
+    }
+}
+
+/// File name for downloaded invoices, the error otherwise
+fn invoice_note(invoice: &HistoryInvoice) -> String {
+    if let Some(file_path) = &invoice.file_path {
+        return Path::new(file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| file_path.clone());
+    }
+
+    invoice.error.clone().unwrap_or_default()
+}
+
+fn format_date(rfc3339: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(rfc3339)
+        .map(|date| date.with_timezone(&chrono::Local).format("%d/%m/%Y %H:%M").to_string())
+        .unwrap_or_else(|_| rfc3339.to_string())
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Strip Vietnamese diacritics for fonts that only cover Latin-1
+pub fn fold_diacritics(text: &str) -> String {
+    const GROUPS: &[(&str, char)] = &[
+        ("àáảãạăằắẳẵặâầấẩẫậ", 'a'),
+        ("èéẻẽẹêềếểễệ", 'e'),
+        ("ìíỉĩị", 'i'),
+        ("òóỏõọôồốổỗộơờớởỡợ", 'o'),
+        ("ùúủũụưừứửữự", 'u'),
+        ("ỳýỷỹỵ", 'y'),
+        ("đ", 'd'),
+    ];
+
+    text.chars()
+        .map(|c| {
+            let lower = c.to_lowercase().next().unwrap_or(c);
+            let folded = GROUPS
+                .iter()
+                .find(|(accented, _)| accented.contains(lower))
+                .map(|(_, base)| *base);
+
+            match folded {
+                Some(base) if c.is_uppercase() => base.to_ascii_uppercase(),
+                Some(base) => base,
+                None if c == '…' => '.',
+                None => c,
+            }
+        })
+        .collect()
+}
+
+fn pdf_error(e: printpdf::Error) -> AppError {
+    AppError::IoError(format!("Failed to render PDF: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_diacritics() {
+        assert_eq!(fold_diacritics("BÁO CÁO LÔ TẢI HÓA ĐƠN"), "BAO CAO LO TAI HOA DON");
+        assert_eq!(fold_diacritics("Kế toán trưởng"), "Ke toan truong");
+        assert_eq!(fold_diacritics("C25TLK0019654_Ln"), "C25TLK0019654_Ln");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("0123456789ab", 10), "012345678…");
+    }
+}
@@ -0,0 +1,82 @@
+use crate::services::downloader::InvoiceDownloadRequest;
+
+/// Invoices that are looked up on the same VNPT portal
+#[derive(Debug, Clone)]
+pub struct PortalGroup {
+    pub vnpt_url: String,
+    pub invoices: Vec<InvoiceDownloadRequest>,
+}
+
+/// Group invoices by their per-row portal URL, in order of first appearance.
+/// Invoices without one go to `default_url`.
+pub fn group_by_portal(invoices: &[InvoiceDownloadRequest], default_url: &str) -> Vec<PortalGroup> {
+    let mut groups: Vec<PortalGroup> = Vec::new();
+
+    for invoice in invoices {
+        let url = invoice
+            .portal_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .unwrap_or(default_url);
+
+        match groups.iter_mut().find(|group| group.vnpt_url == url) {
+            Some(group) => group.invoices.push(invoice.clone()),
+            None => groups.push(PortalGroup {
+                vnpt_url: url.to_string(),
+                invoices: vec![invoice.clone()],
+            }),
+        }
+    }
+
+    groups
+}
+
+/// Id of the `index`-th sub-batch of `parent_id`
+pub fn sub_batch_id(parent_id: &str, index: usize) -> String {
+    format!("{}-{}", parent_id, index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: &str, portal_url: Option<&str>) -> InvoiceDownloadRequest {
+        InvoiceDownloadRequest {
+            id: id.to_string(),
+            code: format!("C25TLK{}_Ln", id),
+            seller_tax_code: None,
+            lookup_mode: Default::default(),
+            invoice_series: None,
+            invoice_number: None,
+            portal_url: portal_url.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_group_by_portal() {
+        let a = "https://0101-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey";
+        let b = "https://0202-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey";
+        let invoices = vec![
+            request("1", Some(b)),
+            request("2", None),
+            request("3", Some(a)),
+            request("4", Some(b)),
+            request("5", Some(" ")),
+        ];
+
+        let groups = group_by_portal(&invoices, a);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].vnpt_url, b);
+        assert_eq!(
+            groups[0].invoices.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(),
+            ["1", "4"]
+        );
+        assert_eq!(groups[1].vnpt_url, a);
+        assert_eq!(
+            groups[1].invoices.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(),
+            ["2", "3", "5"]
+        );
+        assert_eq!(sub_batch_id("batch", 1), "batch-2");
+    }
+}
@@ -1,9 +1,165 @@
-use headless_chrome::{Browser, LaunchOptions, Tab};
-use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
-use std::sync::Arc;
+use headless_chrome::{Browser, Element, LaunchOptions, Tab};
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Emulation;
+use headless_chrome::protocol::cdp::Page::{self, CaptureScreenshotFormatOption};
+use serde::Serialize;
+use specta::Type;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, System};
 
 use crate::error::AppError;
+use crate::services::browser_profile::{BrowserProfile, DebugPort};
+use crate::services::chrome_version::{self, HeadlessMode};
+use crate::services::pdf_fetch;
+
+/// Flags Chrome is launched with, tried in turn, each on a new port, when
+/// it fails to come up
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LaunchFallback {
+    /// `--no-sandbox` and `--disable-gpu`, in the headless mode that suits
+    /// the installed Chrome
+    Standard,
+    /// The headless mode of recent Chrome, when its version is unknown or
+    /// the old mode does not start
+    NewHeadless,
+    /// Also no zygote process and no software rasterizer, for containers and
+    /// locked-down machines
+    Minimal,
+}
+
+const LAUNCH_FALLBACKS: [LaunchFallback; 3] = [
+    LaunchFallback::Standard,
+    LaunchFallback::NewHeadless,
+    LaunchFallback::Minimal,
+];
+
+impl LaunchFallback {
+    fn headless_mode(self, headless: bool, chrome_major: Option<u32>) -> HeadlessMode {
+        chrome_version::headless_mode(headless, chrome_major, self != LaunchFallback::Standard)
+    }
+
+    /// Flags besides the headless mode and the ones for the Chrome version
+    fn args(self) -> Vec<&'static OsStr> {
+        match self {
+            LaunchFallback::Minimal => vec![OsStr::new("--no-zygote"), OsStr::new("--disable-software-rasterizer")],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// What to do about a launch failure, by what Chrome or the OS said
+const LAUNCH_HINTS: &[(&str, &str)] = &[
+    (
+        "error while loading shared libraries",
+        "Chrome is missing system libraries, install the packages it depends on (e.g. libnss3, libgbm1)",
+    ),
+    (
+        "no such file or directory",
+        "Chrome not installed, install Google Chrome or Chromium or set CHROME to its path",
+    ),
+    ("permission denied", "Chrome cannot be run, check the permissions of its executable"),
+    (
+        "sandbox",
+        "Chrome's sandbox cannot start on this machine, run the app as a regular user",
+    ),
+    (
+        "no available ports",
+        "No free debugging port for Chrome, close other browsers the app started",
+    ),
+    (
+        "port is already in use",
+        "No free debugging port for Chrome, close other browsers the app started",
+    ),
+    (
+        "didn't give us a websocket url",
+        "Chrome started but did not answer, an antivirus or another Chrome on the same profile may be blocking it",
+    ),
+];
+
+/// Guards against parent-pid cycles when walking the process tree
+const MAX_PROCESS_DEPTH: usize = 32;
+
+/// Captchas are captured at twice the page resolution so thin strokes survive
+const CAPTCHA_CAPTURE_SCALE: f64 = 2.0;
+
+/// Default margin captured around the captcha image, in CSS pixels
+pub const DEFAULT_CAPTCHA_PADDING_PX: u32 = 4;
+
+/// Largest accepted captcha margin, in CSS pixels
+pub const MAX_CAPTCHA_PADDING_PX: u32 = 50;
+
+/// Window size, device scale and zoom the pages are rendered at; captcha
+/// images come out clipped or blurry at some zoom levels
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserViewport {
+    pub width: u32,
+    pub height: u32,
+    pub device_scale_factor: f64,
+    /// Page zoom in percent
+    pub zoom_percent: u32,
+}
+
+impl Default for BrowserViewport {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            device_scale_factor: 1.0,
+            zoom_percent: 100,
+        }
+    }
+}
+
+impl BrowserViewport {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !(800..=3840).contains(&self.width) || !(600..=2160).contains(&self.height) {
+            return Err(AppError::ConfigError(format!(
+                "Browser window must be between 800x600 and 3840x2160, got {}x{}",
+                self.width, self.height
+            )));
+        }
+        if !(0.5..=4.0).contains(&self.device_scale_factor) {
+            return Err(AppError::ConfigError(format!(
+                "Device scale factor must be between 0.5 and 4, got {}",
+                self.device_scale_factor
+            )));
+        }
+        if !(25..=400).contains(&self.zoom_percent) {
+            return Err(AppError::ConfigError(format!(
+                "Browser zoom must be between 25% and 400%, got {}%",
+                self.zoom_percent
+            )));
+        }
+        Ok(())
+    }
+
+    /// Force the size, scale and zoom on a tab, whatever the OS display settings
+    fn apply(&self, tab: &Tab) -> Result<(), AppError> {
+        tab.call_method(Emulation::SetDeviceMetricsOverride {
+            width: self.width,
+            height: self.height,
+            device_scale_factor: self.device_scale_factor,
+            mobile: false,
+            scale: Some(self.zoom_percent as f64 / 100.0),
+            screen_width: None,
+            screen_height: None,
+            position_x: None,
+            position_y: None,
+            dont_set_visible_size: None,
+            screen_orientation: None,
+            viewport: None,
+            display_feature: None,
+            device_posture: None,
+        })
+        .map_err(|e| AppError::BrowserError(format!("Failed to set browser viewport: {}", e)))?;
+        Ok(())
+    }
+}
 
 /// Selectors for VNPT Invoice portal elements
 pub mod selectors {
@@ -42,72 +198,477 @@ pub mod selectors {
         "a[href*='/HomeNoLogin/downloadPDF']",
     ];
 
+    /// Download XML link
+    pub const XML_DOWNLOAD_LINK: &[&str] = &[
+        "a[title='Tải file xml'][href*='/HomeNoLogin/downloadXML']",
+        "a[title='Tải file xml']",
+        "a[href*='/HomeNoLogin/downloadXML']",
+    ];
+
+    /// Download link of the converted, printable PDF ("hóa đơn chuyển đổi")
+    pub const CONVERTED_DOWNLOAD_LINK: &[&str] = &[
+        "a[title*='chuyển đổi'][href*='ChuyenDoi']",
+        "a[title*='chuyển đổi']",
+        "a[href*='/HomeNoLogin/downloadPDFChuyenDoi']",
+        "a[href*='ChuyenDoi']",
+    ];
+
     /// Error message elements
     pub const ERROR_MESSAGE: &str = ".validation-summary-errors, .alert-danger, label.error";
+
+    /// All known selectors grouped by the page element they locate
+    pub fn all() -> Vec<(&'static str, &'static [&'static str])> {
+        vec![
+            ("invoice_input", INVOICE_INPUT),
+            ("captcha_image", CAPTCHA_IMAGE),
+            ("captcha_input", CAPTCHA_INPUT),
+            ("submit_button", &[SUBMIT_BUTTON]),
+            ("download_link", DOWNLOAD_LINK),
+            ("xml_download_link", XML_DOWNLOAD_LINK),
+            ("converted_download_link", CONVERTED_DOWNLOAD_LINK),
+            ("error_message", &[ERROR_MESSAGE]),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct SelectorMatch {
+    pub field: String,
+    pub selector: String,
+    pub matched: bool,
+}
+
+/// Handle on the browser tab for screenshots taken from another thread
+#[derive(Clone)]
+pub struct TabPreview {
+    tab: Arc<Tab>,
+}
+
+impl TabPreview {
+    /// JPEG of the visible page, scaled by `scale`
+    pub fn capture_jpeg(&self, scale: f64, quality: u32) -> Result<Vec<u8>, AppError> {
+        let size = self
+            .tab
+            .evaluate("[window.innerWidth, window.innerHeight]", false)
+            .map_err(|e| AppError::BrowserError(format!("Failed to read viewport size: {}", e)))?
+            .value
+            .and_then(|value| serde_json::from_value::<(f64, f64)>(value).ok())
+            .ok_or_else(|| AppError::BrowserError("Viewport size is not available".to_string()))?;
+
+        let clip = Page::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: size.0,
+            height: size.1,
+            scale,
+        };
+        self.tab
+            .capture_screenshot(CaptureScreenshotFormatOption::Jpeg, Some(quality), Some(clip), true)
+            .map_err(|e| AppError::BrowserError(format!("Failed to capture preview: {}", e)))
+    }
+}
+
+/// A second tab of a browser, loading the lookup page on its own thread
+pub struct LookaheadTab {
+    tab: Arc<Tab>,
+    captcha_selectors: Vec<String>,
+    captcha_padding: u32,
+}
+
+impl LookaheadTab {
+    /// Load the lookup page and screenshot its captcha
+    pub fn prefetch_captcha(&self, url: &str) -> Result<Vec<u8>, AppError> {
+        load_search_page(&self.tab, url)?;
+
+        for selector in &self.captcha_selectors {
+            if let Ok(element) = self.tab.find_element(selector) {
+                return capture_captcha(&self.tab, &element, self.captcha_padding);
+            }
+        }
+
+        Err(AppError::ElementNotFound("Captcha image".to_string()))
+    }
 }
 
 pub struct VnptBrowser {
     browser: Browser,
     tab: Arc<Tab>,
+    console_logs: Arc<Mutex<Vec<String>>>,
+    selector_overrides: Mutex<HashMap<String, Vec<String>>>,
+    /// Selector attempts and network responses, collected while debug logging is on
+    debug_trace: Option<Arc<Mutex<Vec<String>>>>,
+    viewport: BrowserViewport,
+    /// Margin captured around the captcha image, in CSS pixels
+    captcha_padding: AtomicU32,
+    /// Released after the Chrome process above is gone
+    port: DebugPort,
+    profile: BrowserProfile,
 }
 
 impl VnptBrowser {
-    /// Create a new browser instance
-    pub fn new(headless: bool) -> Result<Self, AppError> {
-        let browser = Browser::new(LaunchOptions {
-            headless,
-            sandbox: false,
-            window_size: Some((1920, 1080)),
-            ..Default::default()
+    /// Create a new browser instance with a temporary profile of its own
+    pub fn new(headless: bool, viewport: &BrowserViewport) -> Result<Self, AppError> {
+        Self::with_profile(headless, viewport, BrowserProfile::temporary()?)
+    }
+
+    /// Create a new browser instance on `profile`
+    pub fn with_profile(
+        headless: bool,
+        viewport: &BrowserViewport,
+        profile: BrowserProfile,
+    ) -> Result<Self, AppError> {
+        let (browser, tab, port) = Self::launch(headless, viewport, &profile)?;
+
+        let console_logs = Arc::new(Mutex::new(Vec::new()));
+        Self::capture_console(&tab, console_logs.clone())?;
+
+        Ok(Self {
+            browser,
+            tab,
+            console_logs,
+            selector_overrides: Mutex::new(HashMap::new()),
+            debug_trace: None,
+            viewport: viewport.clone(),
+            captcha_padding: AtomicU32::new(DEFAULT_CAPTCHA_PADDING_PX),
+            port,
+            profile,
         })
-        .map_err(|e| AppError::BrowserError(format!("Failed to launch browser: {}", e)))?;
+    }
+
+    /// Start Chrome on `profile` with a debugging port no other browser of
+    /// the app holds, trying another port and other flags if it does not
+    /// come up
+    fn launch(
+        headless: bool,
+        viewport: &BrowserViewport,
+        profile: &BrowserProfile,
+    ) -> Result<(Browser, Arc<Tab>, DebugPort), AppError> {
+        let chrome = find_chrome()?;
+        let chrome_major = chrome_version::major_version(&chrome);
+        let disabled_features = chrome_version::disabled_features(chrome_major);
+        let mut failures = Vec::new();
+        let (browser, port) = 'launched: {
+            for fallback in LAUNCH_FALLBACKS {
+                let mode = fallback.headless_mode(headless, chrome_major);
+                let mut args = fallback.args();
+                if mode == HeadlessMode::New {
+                    args.push(OsStr::new("--headless=new"));
+                }
+                if let Some(features) = &disabled_features {
+                    args.push(OsStr::new(features));
+                }
+
+                let port = DebugPort::reserve()?;
+                let launched = Browser::new(LaunchOptions {
+                    headless: mode == HeadlessMode::Old,
+                    sandbox: false,
+                    window_size: Some((viewport.width, viewport.height)),
+                    port: Some(port.number()),
+                    path: Some(chrome.clone()),
+                    user_data_dir: Some(profile.path().to_path_buf()),
+                    args,
+                    ..Default::default()
+                });
+                match launched {
+                    Ok(browser) => break 'launched (browser, port),
+                    Err(e) => {
+                        tracing::warn!("Chrome did not start with {:?} flags: {}", fallback, e);
+                        failures.push(e.to_string());
+                    }
+                }
+            }
+            return Err(launch_error(&failures));
+        };
 
         let tab = browser
             .new_tab()
             .map_err(|e| AppError::BrowserError(format!("Failed to create tab: {}", e)))?;
+        viewport.apply(&tab)?;
+
+        Ok((browser, tab, port))
+    }
+
+    /// Margin captured around the captcha image, in CSS pixels
+    pub fn set_captcha_padding(&self, padding: u32) {
+        self.captcha_padding.store(padding, Ordering::Relaxed);
+    }
+
+    /// Whether the browser renders at `viewport`
+    pub fn has_viewport(&self, viewport: &BrowserViewport) -> bool {
+        self.viewport == *viewport
+    }
+
+    /// Replace the Chrome process with a fresh one, keeping selector overrides
+    /// and the console history. The old process is killed on drop, or first
+    /// when it holds a persistent profile the new one needs.
+    pub fn relaunch(&mut self, headless: bool) -> Result<(), AppError> {
+        if self.profile.is_persistent() {
+            self.kill_process();
+        }
+        let (browser, tab, port) = Self::launch(headless, &self.viewport, &self.profile)?;
+        Self::capture_console(&tab, self.console_logs.clone())?;
+        if let Some(trace) = &self.debug_trace {
+            Self::capture_network(&tab, trace.clone())?;
+        }
+
+        self.tab = tab;
+        self.browser = browser;
+        self.port = port;
+
+        Ok(())
+    }
+
+    fn kill_process(&self) {
+        let Some(pid) = self.browser.get_process_id().map(Pid::from_u32) else {
+            return;
+        };
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        if let Some(process) = system.process(pid) {
+            if process.kill() {
+                process.wait();
+            }
+        }
+    }
+
+    /// Resident memory of the Chrome process and its children (renderers, GPU) in MB
+    pub fn memory_usage_mb(&self) -> Option<u64> {
+        let root = Pid::from_u32(self.browser.get_process_id()?);
+
+        let mut system = System::new();
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::new().with_memory(),
+        );
+        let processes = system.processes();
+
+        let is_in_tree = |process: &Process| {
+            let mut current = Some(process);
+            for _ in 0..MAX_PROCESS_DEPTH {
+                match current {
+                    Some(p) if p.pid() == root => return true,
+                    Some(p) => current = p.parent().and_then(|parent| processes.get(&parent)),
+                    None => return false,
+                }
+            }
+            false
+        };
+
+        let bytes: u64 = processes
+            .values()
+            .filter(|p| is_in_tree(p))
+            .map(|p| p.memory())
+            .sum();
+
+        Some(bytes / 1024 / 1024)
+    }
+
+    /// Replace the selector overrides tried before the built-in selectors
+    ///
+    /// # Returns
+    /// The overrides replaced, to restore after another portal's lookup
+    pub fn set_selector_overrides(&self, overrides: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+        match self.selector_overrides.lock() {
+            Ok(mut current) => std::mem::replace(&mut *current, overrides),
+            Err(_) => HashMap::new(),
+        }
+    }
 
-        Ok(Self { browser, tab })
+    /// Try `selector` first for `field` from now on
+    pub fn add_selector_override(&self, field: &str, selector: &str) {
+        if let Ok(mut current) = self.selector_overrides.lock() {
+            let selectors = current.entry(field.to_string()).or_default();
+            selectors.retain(|s| s != selector);
+            selectors.insert(0, selector.to_string());
+        }
+    }
+
+    /// Selectors to try for a field: overrides first, then the built-in list
+    fn candidates(&self, field: &str, builtin: &[&str]) -> Vec<String> {
+        let mut candidates = self
+            .selector_overrides
+            .lock()
+            .ok()
+            .and_then(|overrides| overrides.get(field).cloned())
+            .unwrap_or_default();
+
+        for selector in builtin {
+            if !candidates.iter().any(|c| c == selector) {
+                candidates.push(selector.to_string());
+            }
+        }
+
+        candidates
+    }
+
+    /// Record selector attempts and a one-line summary of every network
+    /// response, drained with `take_debug_trace`
+    pub fn enable_debug_trace(&mut self) -> Result<(), AppError> {
+        if self.debug_trace.is_none() {
+            let trace = Arc::new(Mutex::new(Vec::new()));
+            Self::capture_network(&self.tab, trace.clone())?;
+            self.debug_trace = Some(trace);
+        }
+        Ok(())
+    }
+
+    /// Trace lines collected since the last call
+    pub fn take_debug_trace(&self) -> Vec<String> {
+        self.debug_trace
+            .as_ref()
+            .and_then(|trace| trace.lock().ok().map(|mut lines| std::mem::take(&mut *lines)))
+            .unwrap_or_default()
+    }
+
+    fn trace(&self, line: String) {
+        if let Some(Ok(mut lines)) = self.debug_trace.as_ref().map(|trace| trace.lock()) {
+            lines.push(line);
+        }
+    }
+
+    fn capture_network(tab: &Arc<Tab>, sink: Arc<Mutex<Vec<String>>>) -> Result<(), AppError> {
+        tab.register_response_handling(
+            "debug-trace",
+            Box::new(move |params, _body| {
+                let line = format!(
+                    "[cdp] {} {} ({})",
+                    params.response.status, params.response.url, params.response.mime_type
+                );
+                if let Ok(mut lines) = sink.lock() {
+                    lines.push(line);
+                }
+            }),
+        )
+        .map_err(|e| AppError::BrowserError(format!("Failed to attach network listener: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// First element matching `selector`, tracing the attempt for `field`
+    fn find(&self, field: &str, selector: &str) -> Option<Element<'_>> {
+        let element = self.tab.find_element(selector).ok();
+        if self.debug_trace.is_some() {
+            let outcome = if element.is_some() { "matched" } else { "no match" };
+            self.trace(format!("[selector] {}: {} -> {}", field, selector, outcome));
+        }
+        element
+    }
+
+    /// Collect console messages and browser log entries for diagnostics
+    fn capture_console(tab: &Arc<Tab>, sink: Arc<Mutex<Vec<String>>>) -> Result<(), AppError> {
+        tab.enable_log()
+            .map_err(|e| AppError::BrowserError(format!("Failed to enable log domain: {}", e)))?;
+        tab.enable_runtime()
+            .map_err(|e| AppError::BrowserError(format!("Failed to enable runtime domain: {}", e)))?;
+
+        tab.add_event_listener(Arc::new(move |event: &Event| {
+            let line = match event {
+                Event::RuntimeConsoleAPICalled(e) => {
+                    let text = e
+                        .params
+                        .args
+                        .iter()
+                        .map(|arg| {
+                            arg.value
+                                .as_ref()
+                                .map(|v| v.to_string())
+                                .or_else(|| arg.description.clone())
+                                .unwrap_or_default()
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    format!("[console.{:?}] {}", e.params.Type, text)
+                }
+                Event::LogEntryAdded(e) => {
+                    format!("[{:?}] {}", e.params.entry.level, e.params.entry.text)
+                }
+                _ => return,
+            };
+
+            if let Ok(mut logs) = sink.lock() {
+                logs.push(line);
+            }
+        }))
+        .map_err(|e| AppError::BrowserError(format!("Failed to attach console listener: {}", e)))?;
+
+        Ok(())
     }
 
     /// Navigate to the VNPT search page
     pub fn navigate_to_search(&self, url: &str) -> Result<(), AppError> {
-        self.tab
-            .navigate_to(url)
-            .map_err(|e| AppError::BrowserError(format!("Failed to navigate: {}", e)))?;
+        load_search_page(&self.tab, url)
+    }
 
-        self.tab
-            .wait_until_navigated()
-            .map_err(|e| AppError::BrowserError(format!("Navigation timeout: {}", e)))?;
+    /// Open a second tab for preparing the next invoice's captcha while this
+    /// tab is still busy
+    pub fn open_lookahead_tab(&self) -> Result<LookaheadTab, AppError> {
+        let tab = self
+            .browser
+            .new_tab()
+            .map_err(|e| AppError::BrowserError(format!("Failed to create tab: {}", e)))?;
+        self.viewport.apply(&tab)?;
 
-        // Wait a bit for page to fully load
-        std::thread::sleep(Duration::from_secs(2));
+        Ok(LookaheadTab {
+            tab,
+            captcha_selectors: self.candidates("captcha_image", selectors::CAPTCHA_IMAGE),
+            captcha_padding: self.captcha_padding.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Continue on the lookahead tab, closing the current one
+    pub fn switch_to(&mut self, lookahead: LookaheadTab) -> Result<(), AppError> {
+        Self::capture_console(&lookahead.tab, self.console_logs.clone())?;
+        if let Some(trace) = &self.debug_trace {
+            Self::capture_network(&lookahead.tab, trace.clone())?;
+        }
+
+        let previous = std::mem::replace(&mut self.tab, lookahead.tab);
+        let _ = previous.close(false);
 
         Ok(())
     }
 
     /// Fill in the invoice code
     pub fn fill_invoice_code(&self, code: &str) -> Result<(), AppError> {
+        self.fill_input("invoice_input", selectors::INVOICE_INPUT, code, "Invoice code input field")
+    }
+
+    /// Fill a field that only exists on other portals (seller tax code,
+    /// invoice series, ...); its selectors come from the overrides
+    pub fn fill_portal_field(&self, field: &str, value: &str) -> Result<(), AppError> {
+        self.fill_input(field, &[], value, &format!("{} input field", field))
+    }
+
+    /// Click, clear and type into the first input matching `field`
+    pub fn fill_input(
+        &self,
+        field: &str,
+        builtin: &[&str],
+        value: &str,
+        label: &str,
+    ) -> Result<(), AppError> {
         // Try each selector until one works
-        for selector in selectors::INVOICE_INPUT {
-            if let Ok(element) = self.tab.find_element(selector) {
+        for selector in &self.candidates(field, builtin) {
+            if let Some(element) = self.find(field, selector) {
                 element
                     .click()
-                    .map_err(|e| AppError::BrowserError(format!("Failed to click input: {}", e)))?;
+                    .map_err(|e| AppError::BrowserError(format!("Failed to click {}: {}", field, e)))?;
 
-                // Clear field via JS before typing
+                // Clear field via JS to avoid stale text
                 self.tab
-                    .evaluate(&format!("document.querySelector('{}').value = '';", selector), false)
-                    .map_err(|_| AppError::BrowserError("Failed to clear invoice input".to_string()))?;
+                    .evaluate(&format!("document.querySelector({}).value = '';", js_string(selector)), false)
+                    .map_err(|_| AppError::BrowserError(format!("Failed to clear {}", field)))?;
 
                 element
-                    .type_into(code)
-                    .map_err(|e| AppError::BrowserError(format!("Failed to type code: {}", e)))?;
+                    .type_into(value)
+                    .map_err(|e| AppError::BrowserError(format!("Failed to type into {}: {}", field, e)))?;
 
                 return Ok(());
             }
         }
 
-        Err(AppError::ElementNotFound("Invoice code input field".to_string()))
+        Err(AppError::ElementNotFound(label.to_string()))
     }
 
     /// Get a screenshot of the captcha image
@@ -116,13 +677,9 @@ impl VnptBrowser {
         std::thread::sleep(Duration::from_millis(500));
 
         // Try each selector
-        for selector in selectors::CAPTCHA_IMAGE {
-            if let Ok(element) = self.tab.find_element(selector) {
-                let screenshot = element
-                    .capture_screenshot(CaptureScreenshotFormatOption::Png)
-                    .map_err(|e| AppError::BrowserError(format!("Failed to screenshot captcha: {}", e)))?;
-
-                return Ok(screenshot);
+        for selector in &self.candidates("captcha_image", selectors::CAPTCHA_IMAGE) {
+            if let Some(element) = self.find("captcha_image", selector) {
+                return capture_captcha(&self.tab, &element, self.captcha_padding.load(Ordering::Relaxed));
             }
         }
 
@@ -131,53 +688,87 @@ impl VnptBrowser {
 
     /// Fill in the captcha text
     pub fn fill_captcha(&self, text: &str) -> Result<(), AppError> {
-        for selector in selectors::CAPTCHA_INPUT {
-            if let Ok(input) = self.tab.find_element(selector) {
-                // Click to focus
-                input
+        self.fill_input("captcha_input", selectors::CAPTCHA_INPUT, text, "Captcha input field")
+    }
+
+    /// Click the submit button
+    pub fn submit(&self) -> Result<(), AppError> {
+        for selector in &self.candidates("submit_button", &[selectors::SUBMIT_BUTTON]) {
+            if let Some(button) = self.find("submit_button", selector) {
+                button
                     .click()
-                    .map_err(|e| AppError::BrowserError(format!("Failed to click captcha input: {}", e)))?;
+                    .map_err(|e| AppError::BrowserError(format!("Failed to click submit: {}", e)))?;
 
-                // Clear field via JS to avoid stale text
-                self.tab
-                    .evaluate(&format!("document.querySelector('{}').value = '';", selector), false)
-                    .map_err(|_| AppError::BrowserError("Failed to clear captcha field".to_string()))?;
+                // Wait for page to load
+                std::thread::sleep(Duration::from_secs(3));
 
-                // Type the captcha text
-                input
-                    .type_into(text)
-                    .map_err(|e| AppError::BrowserError(format!("Failed to type captcha: {}", e)))?;
+                return Ok(());
+            }
+        }
+
+        Err(AppError::ElementNotFound("Submit button".to_string()))
+    }
+
+    /// Click the first element matching `field` and let the page settle
+    pub fn click(&self, field: &str, builtin: &[&str], label: &str) -> Result<(), AppError> {
+        for selector in &self.candidates(field, builtin) {
+            if let Some(element) = self.find(field, selector) {
+                element
+                    .click()
+                    .map_err(|e| AppError::BrowserError(format!("Failed to click {}: {}", field, e)))?;
+
+                std::thread::sleep(Duration::from_secs(3));
 
                 return Ok(());
             }
         }
 
-        Err(AppError::ElementNotFound("Captcha input field".to_string()))
+        Err(AppError::ElementNotFound(label.to_string()))
     }
 
-    /// Click the submit button
-    pub fn submit(&self) -> Result<(), AppError> {
-        let button = self
-            .tab
-            .find_element(selectors::SUBMIT_BUTTON)
-            .map_err(|_| AppError::ElementNotFound("Submit button".to_string()))?;
+    /// Whether any selector of `field` matches the current page
+    pub fn has_element(&self, field: &str, builtin: &[&str]) -> bool {
+        self.candidates(field, builtin)
+            .iter()
+            .any(|selector| self.find(field, selector).is_some())
+    }
 
-        button
-            .click()
-            .map_err(|e| AppError::BrowserError(format!("Failed to click submit: {}", e)))?;
+    /// Text of every cell of the first table matching `field`, row by row,
+    /// header cells included
+    pub fn read_table(&self, field: &str, builtin: &[&str]) -> Result<Vec<Vec<String>>, AppError> {
+        for selector in &self.candidates(field, builtin) {
+            if self.find(field, selector).is_none() {
+                continue;
+            }
 
-        // Wait for page to load
-        std::thread::sleep(Duration::from_secs(3));
+            let script = format!(
+                "JSON.stringify(Array.from(document.querySelector({}).querySelectorAll('tr'))\
+                 .map(tr => Array.from(tr.querySelectorAll('th, td')).map(cell => cell.innerText.trim())))",
+                js_string(selector)
+            );
+            let json = self
+                .tab
+                .evaluate(&script, false)
+                .map_err(|e| AppError::BrowserError(format!("Failed to read {}: {}", field, e)))?
+                .value
+                .and_then(|value| value.as_str().map(str::to_string))
+                .ok_or_else(|| AppError::BrowserError(format!("{} has no rows", field)))?;
 
-        Ok(())
+            return serde_json::from_str(&json)
+                .map_err(|e| AppError::BrowserError(format!("Failed to read {}: {}", field, e)));
+        }
+
+        Err(AppError::ElementNotFound(field.to_string()))
     }
 
     /// Check if there's an error message on the page
     pub fn check_for_error(&self) -> Option<String> {
-        if let Ok(element) = self.tab.find_element(selectors::ERROR_MESSAGE) {
-            if let Ok(text) = element.get_inner_text() {
-                if !text.trim().is_empty() {
-                    return Some(text);
+        for selector in &self.candidates("error_message", &[selectors::ERROR_MESSAGE]) {
+            if let Some(element) = self.find("error_message", selector) {
+                if let Ok(text) = element.get_inner_text() {
+                    if !text.trim().is_empty() {
+                        return Some(text);
+                    }
                 }
             }
         }
@@ -186,52 +777,68 @@ impl VnptBrowser {
 
     /// Get the download link URL
     pub fn get_download_link(&self) -> Result<String, AppError> {
-        for selector in selectors::DOWNLOAD_LINK {
-            if let Ok(element) = self.tab.find_element(selector) {
+        self.find_href("download_link", selectors::DOWNLOAD_LINK)?
+            .ok_or_else(|| AppError::ElementNotFound("Download PDF link".to_string()))
+    }
+
+    fn find_href(&self, field: &str, builtin: &[&str]) -> Result<Option<String>, AppError> {
+        for selector in &self.candidates(field, builtin) {
+            if let Some(element) = self.find(field, selector) {
                 if let Some(href) = element
                     .get_attribute_value("href")
-                    .map_err(|_| AppError::ElementNotFound("Download link href".to_string()))?
+                    .map_err(|_| AppError::ElementNotFound(format!("{} href", field)))?
                 {
-                    return Ok(href);
+                    return Ok(Some(href));
                 }
             }
         }
-
-        Err(AppError::ElementNotFound("Download PDF link".to_string()))
+        Ok(None)
     }
 
     /// Download PDF from the current page
     /// Returns the PDF bytes
-    pub fn download_pdf(&self, base_url: &str) -> Result<Vec<u8>, AppError> {
+    pub fn download_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
         let href = self.get_download_link()?;
 
-        // Construct full URL if needed
-        let full_url = if href.starts_with("http") {
-            href
-        } else {
-            // Extract base URL without path
-            let base = url::Url::parse(base_url)
-                .map_err(|e| AppError::BrowserError(format!("Invalid base URL: {}", e)))?;
+        // Download PDF directly via HTTP request (no browser navigation needed)
+        pdf_fetch::fetch_pdf(client, &absolute_url(base_url, &href)?, pdf_fetch::MAX_PDF_SIZE_BYTES)
+    }
 
-            format!("{}://{}{}", base.scheme(), base.host_str().unwrap_or(""), href)
-        };
+    /// Download the invoice XML from the current page
+    pub fn download_xml(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        let href = self
+            .find_href("xml_download_link", selectors::XML_DOWNLOAD_LINK)?
+            .ok_or_else(|| AppError::ElementNotFound("Download XML link".to_string()))?;
 
-        // Download PDF directly via HTTP request (no browser navigation needed)
-        let response = reqwest::blocking::get(&full_url)
-            .map_err(|e| AppError::DownloadFailed(format!("HTTP request failed: {}", e)))?;
+        pdf_fetch::fetch_pdf(client, &absolute_url(base_url, &href)?, pdf_fetch::MAX_PDF_SIZE_BYTES)
+    }
 
-        if !response.status().is_success() {
-            return Err(AppError::DownloadFailed(format!(
-                "Download failed with status: {}",
-                response.status()
-            )));
-        }
+    /// Download the converted, printable PDF from the current page
+    pub fn download_converted_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        let href = self
+            .find_href("converted_download_link", selectors::CONVERTED_DOWNLOAD_LINK)?
+            .ok_or_else(|| AppError::ElementNotFound("Download converted PDF link".to_string()))?;
 
-        let bytes = response
-            .bytes()
-            .map_err(|e| AppError::DownloadFailed(format!("Failed to read response: {}", e)))?;
+        pdf_fetch::fetch_pdf(client, &absolute_url(base_url, &href)?, pdf_fetch::MAX_PDF_SIZE_BYTES)
+    }
 
-        Ok(bytes.to_vec())
+    /// Handle for the live preview; it follows this tab, not a relaunched one
+    pub fn preview(&self) -> TabPreview {
+        TabPreview {
+            tab: self.tab.clone(),
+        }
     }
 
     /// Take a full page screenshot (for debugging)
@@ -241,6 +848,48 @@ impl VnptBrowser {
             .map_err(|e| AppError::BrowserError(format!("Failed to take screenshot: {}", e)))
     }
 
+    /// Get the full HTML of the current page
+    pub fn page_html(&self) -> Result<String, AppError> {
+        let result = self
+            .tab
+            .evaluate("document.documentElement.outerHTML", false)
+            .map_err(|e| AppError::BrowserError(format!("Failed to read page HTML: {}", e)))?;
+
+        result
+            .value
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .ok_or_else(|| AppError::BrowserError("Page HTML is not available".to_string()))
+    }
+
+    /// Check whether an element matching the selector is currently on the page
+    pub fn element_exists(&self, selector: &str) -> bool {
+        self.tab.find_element(selector).is_ok()
+    }
+
+    /// Check every known selector against the current page
+    pub fn inspect_selectors(&self) -> Vec<SelectorMatch> {
+        selectors::all()
+            .into_iter()
+            .flat_map(|(field, builtin)| {
+                self.candidates(field, builtin)
+                    .into_iter()
+                    .map(move |selector| SelectorMatch {
+                        field: field.to_string(),
+                        matched: self.element_exists(&selector),
+                        selector,
+                    })
+            })
+            .collect()
+    }
+
+    /// Console messages captured since the browser was launched
+    pub fn console_logs(&self) -> Vec<String> {
+        self.console_logs
+            .lock()
+            .map(|logs| logs.clone())
+            .unwrap_or_default()
+    }
+
     /// Get the current page URL
     pub fn current_url(&self) -> Result<String, AppError> {
         self.tab
@@ -270,3 +919,146 @@ impl VnptBrowser {
         Ok(())
     }
 }
+
+/// Screenshot the captcha with `padding` pixels around it, upscaled, so
+/// characters touching its edges are not cut off
+fn capture_captcha(tab: &Tab, element: &Element<'_>, padding: u32) -> Result<Vec<u8>, AppError> {
+    element
+        .scroll_into_view()
+        .map_err(|e| AppError::BrowserError(format!("Failed to scroll to captcha: {}", e)))?;
+    let bounds = element
+        .get_box_model()
+        .map_err(|e| AppError::BrowserError(format!("Failed to locate captcha: {}", e)))?
+        .border_viewport();
+
+    let padding = padding as f64;
+    let x = (bounds.x - padding).max(0.0);
+    let y = (bounds.y - padding).max(0.0);
+    let clip = Page::Viewport {
+        x,
+        y,
+        width: bounds.x + bounds.width + padding - x,
+        height: bounds.y + bounds.height + padding - y,
+        scale: CAPTCHA_CAPTURE_SCALE,
+    };
+
+    tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, Some(clip), true)
+        .map_err(|e| AppError::BrowserError(format!("Failed to screenshot captcha: {}", e)))
+}
+
+fn load_search_page(tab: &Tab, url: &str) -> Result<(), AppError> {
+    tab.navigate_to(url)
+        .map_err(|e| AppError::BrowserError(format!("Failed to navigate: {}", e)))?;
+
+    tab.wait_until_navigated()
+        .map_err(|e| AppError::BrowserError(format!("Navigation timeout: {}", e)))?;
+
+    // Wait a bit for page to fully load
+    std::thread::sleep(Duration::from_secs(2));
+
+    Ok(())
+}
+
+/// Chrome or Chromium the browser launches: `$CHROME`, else the usual
+/// install locations and `PATH`
+pub fn find_chrome() -> Result<PathBuf, AppError> {
+    headless_chrome::browser::default_executable().map_err(|e| {
+        AppError::BrowserError(format!("Chrome not found, install Google Chrome or Chromium: {}", e))
+    })
+}
+
+/// What headless_chrome and the OS report once the connection to Chrome is
+/// gone, e.g. after Chrome crashed
+const CONNECTION_LOST_PATTERNS: &[&str] = &[
+    "underlying connection is closed",
+    "connection closed",
+    "connection reset",
+    "broken pipe",
+    "receiving on a closed channel",
+    "target closed",
+];
+
+/// Whether `error` means the browser itself is gone rather than the page
+/// misbehaving, so only a new browser can get further
+pub fn is_connection_lost(error: &AppError) -> bool {
+    match error {
+        AppError::BrowserError(message) => {
+            let message = message.to_lowercase();
+            CONNECTION_LOST_PATTERNS.iter().any(|pattern| message.contains(pattern))
+        }
+        _ => false,
+    }
+}
+
+/// Error for a Chrome that would not start, saying what to do about it when
+/// one of the failures is a known one, the first hint listed winning
+fn launch_error(failures: &[String]) -> AppError {
+    let last = failures.last().map(String::as_str).unwrap_or("unknown error");
+    let failures: Vec<String> = failures.iter().map(|failure| failure.to_lowercase()).collect();
+    let hint = LAUNCH_HINTS
+        .iter()
+        .find(|(pattern, _)| failures.iter().any(|failure| failure.contains(pattern)))
+        .map(|(_, hint)| *hint);
+    match hint {
+        Some(hint) => AppError::BrowserError(format!("{} ({})", hint, last)),
+        None => AppError::BrowserError(format!("Failed to launch browser: {}", last)),
+    }
+}
+
+/// Resolve a link of the page against the portal's origin
+fn absolute_url(base_url: &str, href: &str) -> Result<String, AppError> {
+    if href.starts_with("http") {
+        return Ok(href.to_string());
+    }
+
+    // Extract base URL without path
+    let base = url::Url::parse(base_url)
+        .map_err(|e| AppError::BrowserError(format!("Invalid base URL: {}", e)))?;
+
+    Ok(format!("{}://{}{}", base.scheme(), base.host_str().unwrap_or(""), href))
+}
+
+/// Quote a string as a JavaScript string literal
+fn js_string(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_error_hints() {
+        let failures = vec![
+            "Chrome launched, but didn't give us a WebSocket URL before we timed out".to_string(),
+            "/opt/google/chrome/chrome: error while loading shared libraries: libnss3.so".to_string(),
+        ];
+        let error = launch_error(&failures).to_string();
+        assert!(error.contains("Chrome is missing system libraries"), "{}", error);
+        assert!(error.ends_with("(/opt/google/chrome/chrome: error while loading shared libraries: libnss3.so)"));
+
+        let error = launch_error(&["Unexpected CDP reply".to_string()]).to_string();
+        assert_eq!(error, "Browser error: Failed to launch browser: Unexpected CDP reply");
+    }
+
+    #[test]
+    fn test_connection_lost() {
+        assert!(is_connection_lost(&AppError::BrowserError(
+            "Failed to navigate: Unable to make method calls because underlying connection is closed".to_string()
+        )));
+        assert!(!is_connection_lost(&AppError::BrowserError("Navigation timed out".to_string())));
+        assert!(!is_connection_lost(&AppError::NetworkError("connection reset by peer".to_string())));
+    }
+
+    #[test]
+    fn test_launch_fallback_flags() {
+        assert_eq!(LaunchFallback::Standard.headless_mode(true, None), HeadlessMode::Old);
+        assert_eq!(LaunchFallback::NewHeadless.headless_mode(true, None), HeadlessMode::New);
+        // Chrome without the old mode gets the new one from the start
+        assert_eq!(LaunchFallback::Standard.headless_mode(true, Some(133)), HeadlessMode::New);
+        // A visible browser stays visible whatever the fallback
+        assert_eq!(LaunchFallback::Minimal.headless_mode(false, Some(133)), HeadlessMode::Off);
+        assert!(LaunchFallback::Standard.args().is_empty());
+        assert!(LaunchFallback::Minimal.args().contains(&OsStr::new("--no-zygote")));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use crate::error::AppError;
+use crate::services::browser::{LookaheadTab, VnptBrowser};
+
+/// What the download pipeline does with a browser: load the lookup form,
+/// fill it, screenshot the captcha, submit and fetch the files. Implemented
+/// by `VnptBrowser` on Chrome, and by a scripted mock in tests so the retry
+/// and cancellation logic runs without one
+pub trait BrowserDriver {
+    fn navigate_to_search(&self, url: &str) -> Result<(), AppError>;
+
+    fn fill_invoice_code(&self, code: &str) -> Result<(), AppError>;
+
+    /// Fill a field of the tax-authority form, e.g. `seller_tax_code`
+    fn fill_portal_field(&self, field: &str, value: &str) -> Result<(), AppError>;
+
+    /// PNG of the captcha image
+    fn get_captcha_screenshot(&self) -> Result<Vec<u8>, AppError>;
+
+    fn fill_captcha(&self, text: &str) -> Result<(), AppError>;
+
+    fn submit(&self) -> Result<(), AppError>;
+
+    /// Message the portal showed after submitting, if any
+    fn check_for_error(&self) -> Option<String>;
+
+    fn download_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError>;
+
+    fn download_xml(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError>;
+
+    /// The converted, printable PDF ("hóa đơn chuyển đổi")
+    fn download_converted_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError>;
+
+    /// Second tab loading the next invoice's captcha
+    fn open_lookahead_tab(&self) -> Result<LookaheadTab, AppError>;
+
+    fn memory_usage_mb(&self) -> Option<u64>;
+
+    /// Start a fresh browser process in place of this one
+    fn relaunch(&mut self, headless: bool) -> Result<(), AppError>;
+
+    /// Replace the selector overrides, returning the previous ones
+    fn set_selector_overrides(&self, overrides: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>>;
+
+    fn add_selector_override(&self, field: &str, selector: &str);
+
+    fn page_html(&self) -> Result<String, AppError>;
+
+    fn element_exists(&self, selector: &str) -> bool;
+}
+
+impl BrowserDriver for VnptBrowser {
+    fn navigate_to_search(&self, url: &str) -> Result<(), AppError> {
+        VnptBrowser::navigate_to_search(self, url)
+    }
+
+    fn fill_invoice_code(&self, code: &str) -> Result<(), AppError> {
+        VnptBrowser::fill_invoice_code(self, code)
+    }
+
+    fn fill_portal_field(&self, field: &str, value: &str) -> Result<(), AppError> {
+        VnptBrowser::fill_portal_field(self, field, value)
+    }
+
+    fn get_captcha_screenshot(&self) -> Result<Vec<u8>, AppError> {
+        VnptBrowser::get_captcha_screenshot(self)
+    }
+
+    fn fill_captcha(&self, text: &str) -> Result<(), AppError> {
+        VnptBrowser::fill_captcha(self, text)
+    }
+
+    fn submit(&self) -> Result<(), AppError> {
+        VnptBrowser::submit(self)
+    }
+
+    fn check_for_error(&self) -> Option<String> {
+        VnptBrowser::check_for_error(self)
+    }
+
+    fn download_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        VnptBrowser::download_pdf(self, base_url, client)
+    }
+
+    fn download_xml(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        VnptBrowser::download_xml(self, base_url, client)
+    }
+
+    fn download_converted_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        VnptBrowser::download_converted_pdf(self, base_url, client)
+    }
+
+    fn open_lookahead_tab(&self) -> Result<LookaheadTab, AppError> {
+        VnptBrowser::open_lookahead_tab(self)
+    }
+
+    fn memory_usage_mb(&self) -> Option<u64> {
+        VnptBrowser::memory_usage_mb(self)
+    }
+
+    fn relaunch(&mut self, headless: bool) -> Result<(), AppError> {
+        VnptBrowser::relaunch(self, headless)
+    }
+
+    fn set_selector_overrides(&self, overrides: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+        VnptBrowser::set_selector_overrides(self, overrides)
+    }
+
+    fn add_selector_override(&self, field: &str, selector: &str) {
+        VnptBrowser::add_selector_override(self, field, selector)
+    }
+
+    fn page_html(&self) -> Result<String, AppError> {
+        VnptBrowser::page_html(self)
+    }
+
+    fn element_exists(&self, selector: &str) -> bool {
+        VnptBrowser::element_exists(self, selector)
+    }
+}
+
+/// Browser that answers every lookup with the next scripted outcome
+#[cfg(test)]
+pub mod mock {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::Mutex;
+
+    use super::BrowserDriver;
+    use crate::error::AppError;
+    use crate::services::browser::LookaheadTab;
+
+    /// How the portal answers one submitted lookup
+    #[derive(Debug, Clone)]
+    pub enum Outcome {
+        /// Lookup page did not load
+        NavigationFails,
+        /// Chrome crashed, taking the CDP connection with it
+        ConnectionLost,
+        /// Message shown instead of the invoice, e.g. a wrong captcha
+        PageError(&'static str),
+        /// The invoice page, with a PDF to download
+        Found,
+    }
+
+    #[derive(Default)]
+    pub struct MockDriver {
+        script: Mutex<VecDeque<Outcome>>,
+        current: Mutex<Option<Outcome>>,
+        /// Every call made, in order, e.g. `navigate`, `submit`
+        pub calls: Mutex<Vec<String>>,
+        pub selector_overrides: Mutex<HashMap<String, Vec<String>>>,
+    }
+
+    impl MockDriver {
+        /// Outcomes of the successive lookups; after the last one the
+        /// lookup page stops loading
+        pub fn new(script: impl IntoIterator<Item = Outcome>) -> Self {
+            Self {
+                script: Mutex::new(script.into_iter().collect()),
+                ..Self::default()
+            }
+        }
+
+        pub fn count(&self, call: &str) -> usize {
+            self.calls.lock().unwrap().iter().filter(|made| *made == call).count()
+        }
+
+        fn record(&self, call: &str) {
+            self.calls.lock().unwrap().push(call.to_string());
+        }
+    }
+
+    impl BrowserDriver for MockDriver {
+        fn navigate_to_search(&self, _url: &str) -> Result<(), AppError> {
+            self.record("navigate");
+            let outcome = self.script.lock().unwrap().pop_front().unwrap_or(Outcome::NavigationFails);
+            let failed = match outcome {
+                Outcome::NavigationFails => Some("Navigation timed out"),
+                Outcome::ConnectionLost => Some("Unable to make method calls because underlying connection is closed"),
+                _ => None,
+            };
+            *self.current.lock().unwrap() = Some(outcome);
+            match failed {
+                Some(message) => Err(AppError::BrowserError(message.to_string())),
+                None => Ok(()),
+            }
+        }
+
+        fn fill_invoice_code(&self, _code: &str) -> Result<(), AppError> {
+            self.record("fill_invoice_code");
+            Ok(())
+        }
+
+        fn fill_portal_field(&self, field: &str, _value: &str) -> Result<(), AppError> {
+            self.record(field);
+            Ok(())
+        }
+
+        fn get_captcha_screenshot(&self) -> Result<Vec<u8>, AppError> {
+            self.record("captcha_screenshot");
+            Ok(vec![0x89, b'P', b'N', b'G'])
+        }
+
+        fn fill_captcha(&self, _text: &str) -> Result<(), AppError> {
+            self.record("fill_captcha");
+            Ok(())
+        }
+
+        fn submit(&self) -> Result<(), AppError> {
+            self.record("submit");
+            Ok(())
+        }
+
+        fn check_for_error(&self) -> Option<String> {
+            match &*self.current.lock().unwrap() {
+                Some(Outcome::PageError(message)) => Some(message.to_string()),
+                _ => None,
+            }
+        }
+
+        fn download_pdf(
+            &self,
+            _base_url: &str,
+            _client: &reqwest::blocking::Client,
+        ) -> Result<Vec<u8>, AppError> {
+            self.record("download_pdf");
+            match &*self.current.lock().unwrap() {
+                Some(Outcome::Found) => Ok(b"%PDF-1.4 mock".to_vec()),
+                _ => Err(AppError::ElementNotFound("Download PDF link".to_string())),
+            }
+        }
+
+        fn download_xml(
+            &self,
+            _base_url: &str,
+            _client: &reqwest::blocking::Client,
+        ) -> Result<Vec<u8>, AppError> {
+            Err(AppError::ElementNotFound("Download XML link".to_string()))
+        }
+
+        fn download_converted_pdf(
+            &self,
+            _base_url: &str,
+            _client: &reqwest::blocking::Client,
+        ) -> Result<Vec<u8>, AppError> {
+            self.record("download_converted_pdf");
+            Err(AppError::ElementNotFound("Download converted PDF link".to_string()))
+        }
+
+        fn open_lookahead_tab(&self) -> Result<LookaheadTab, AppError> {
+            Err(AppError::BrowserError("No second tab in the mock".to_string()))
+        }
+
+        fn memory_usage_mb(&self) -> Option<u64> {
+            None
+        }
+
+        fn relaunch(&mut self, _headless: bool) -> Result<(), AppError> {
+            self.record("relaunch");
+            Ok(())
+        }
+
+        fn set_selector_overrides(&self, overrides: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+            std::mem::replace(&mut *self.selector_overrides.lock().unwrap(), overrides)
+        }
+
+        fn add_selector_override(&self, _field: &str, _selector: &str) {}
+
+        fn page_html(&self) -> Result<String, AppError> {
+            Ok(String::new())
+        }
+
+        fn element_exists(&self, _selector: &str) -> bool {
+            false
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::services::browser::{BrowserViewport, VnptBrowser};
+use crate::services::captcha_dataset::CaptchaSource;
+
+/// A lookup page parked longer than this may carry an expired captcha or session
+const MAX_WARM_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Captcha of the parked page, solved while the previous invoice downloaded
+pub struct PrefetchedCaptcha {
+    pub image: Vec<u8>,
+    pub text: String,
+    pub source: CaptchaSource,
+}
+
+/// A browser taken from the pool, already on the lookup page
+pub struct ParkedBrowser {
+    pub browser: VnptBrowser,
+    pub captcha: Option<PrefetchedCaptcha>,
+}
+
+struct WarmBrowser {
+    browser: VnptBrowser,
+    url: String,
+    headless: bool,
+    captcha: Option<PrefetchedCaptcha>,
+    warmed_at: Instant,
+}
+
+impl WarmBrowser {
+    fn is_usable_for(&self, url: &str, headless: bool, viewport: &BrowserViewport) -> bool {
+        self.url == url
+            && self.headless == headless
+            && self.browser.has_viewport(viewport)
+            && self.warmed_at.elapsed() < MAX_WARM_AGE
+    }
+}
+
+/// Keeps one headless browser launched and parked on the lookup page, so the
+/// first invoice of a batch skips the Chromium cold start and navigation
+#[derive(Default)]
+pub struct BrowserPool {
+    warm: Mutex<Option<WarmBrowser>>,
+    warming: AtomicBool,
+}
+
+impl BrowserPool {
+    /// Take the warm browser if it was prepared for `url` in the same mode and
+    /// viewport and is still fresh. A stale browser is dropped, so call this
+    /// from a blocking thread.
+    pub fn take(&self, url: &str, headless: bool, viewport: &BrowserViewport) -> Option<ParkedBrowser> {
+        let warm = self.warm.lock().ok()?.take()?;
+
+        if warm.is_usable_for(url, headless, viewport) {
+            Some(ParkedBrowser {
+                browser: warm.browser,
+                captcha: warm.captcha,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Hand a browser already on the lookup page of `url` to the next invoice,
+    /// replacing any warm one. Call this from a blocking thread.
+    pub fn park(
+        &self,
+        browser: VnptBrowser,
+        url: &str,
+        headless: bool,
+        captcha: Option<PrefetchedCaptcha>,
+    ) {
+        let replaced = self.warm.lock().ok().and_then(|mut warm| {
+            warm.replace(WarmBrowser {
+                browser,
+                url: url.to_string(),
+                headless,
+                captcha,
+                warmed_at: Instant::now(),
+            })
+        });
+        drop(replaced);
+    }
+
+    /// Launch a headless browser on `url` unless one is already warm or warming.
+    /// Blocks until the page has loaded.
+    pub fn warm_blocking(&self, url: &str, viewport: &BrowserViewport) -> Result<(), AppError> {
+        if url.is_empty() || self.warming.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let result = self.launch_warm(url, viewport);
+        self.warming.store(false, Ordering::SeqCst);
+
+        result
+    }
+
+    fn launch_warm(&self, url: &str, viewport: &BrowserViewport) -> Result<(), AppError> {
+        let already_warm = self
+            .warm
+            .lock()
+            .map(|warm| warm.as_ref().is_some_and(|w| w.is_usable_for(url, true, viewport)))
+            .unwrap_or(false);
+        if already_warm {
+            return Ok(());
+        }
+
+        let browser = VnptBrowser::new(true, viewport)?;
+        browser.navigate_to_search(url)?;
+
+        if let Ok(mut warm) = self.warm.lock() {
+            *warm = Some(WarmBrowser {
+                browser,
+                url: url.to_string(),
+                headless: true,
+                captcha: None,
+                warmed_at: Instant::now(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Warm the pool on a background thread; failures only mean a cold start later
+    pub fn prewarm(self: &Arc<Self>, url: String, viewport: BrowserViewport) {
+        let pool = self.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let _ = pool.warm_blocking(&url, &viewport);
+        });
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::events::{self, BrowserPreviewEvent};
+use crate::services::browser::TabPreview;
+
+/// How often a preview frame is captured while streaming
+const PREVIEW_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// Frames are a fraction of the page size to keep events small
+const PREVIEW_SCALE: f64 = 0.5;
+const PREVIEW_JPEG_QUALITY: u32 = 60;
+
+/// How often the idle thread checks whether to stop
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+/// Emits preview frames of one invoice's tab while `enabled` is set; stops
+/// when dropped, i.e. when the invoice is done
+pub struct PreviewStream {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PreviewStream {
+    pub fn spawn(
+        app: AppHandle,
+        batch_id: String,
+        invoice_id: String,
+        tab: TabPreview,
+        enabled: Arc<AtomicBool>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        let handle = thread::spawn(move || {
+            let mut next_frame = Instant::now();
+
+            while !stopped.load(Ordering::SeqCst) {
+                if enabled.load(Ordering::SeqCst) && Instant::now() >= next_frame {
+                    next_frame = Instant::now() + PREVIEW_INTERVAL;
+
+                    // The tab may be mid-navigation or closed; skip the frame
+                    if let Ok(jpeg) = tab.capture_jpeg(PREVIEW_SCALE, PREVIEW_JPEG_QUALITY) {
+                        let _ = app.emit(
+                            events::BROWSER_PREVIEW,
+                            BrowserPreviewEvent {
+                                batch_id: batch_id.clone(),
+                                invoice_id: invoice_id.clone(),
+                                image_base64: base64::Engine::encode(
+                                    &base64::engine::general_purpose::STANDARD,
+                                    &jpeg,
+                                ),
+                                captured_at: chrono::Utc::now().to_rfc3339(),
+                            },
+                        );
+                    }
+                }
+                thread::sleep(STOP_POLL);
+            }
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for PreviewStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::error::AppError;
+
+/// Prefix of the temporary profile directories, so leftovers can be found
+const TEMP_PROFILE_PREFIX: &str = "autoinvoice-chrome-";
+
+/// Temporary profiles older than this are left over from a crash
+const STALE_PROFILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Debugging ports handed to Chrome, away from the usual 9222
+const DEBUG_PORTS: std::ops::Range<u16> = 9300..9800;
+
+/// Profile directories and ports held by running browsers of this process
+fn in_use() -> &'static Mutex<(HashSet<PathBuf>, HashSet<u16>)> {
+    static IN_USE: OnceLock<Mutex<(HashSet<PathBuf>, HashSet<u16>)>> = OnceLock::new();
+    IN_USE.get_or_init(|| Mutex::new((HashSet::new(), HashSet::new())))
+}
+
+/// Chrome `--user-data-dir` of one browser process; a temporary one is
+/// deleted when dropped, a persistent one is released for the next browser
+#[derive(Debug)]
+pub struct BrowserProfile {
+    dir: PathBuf,
+    persistent: bool,
+}
+
+impl BrowserProfile {
+    /// Fresh profile nothing else uses
+    pub fn temporary() -> Result<Self, AppError> {
+        let dir = std::env::temp_dir().join(format!("{}{}", TEMP_PROFILE_PREFIX, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| AppError::IoError(format!("Failed to create browser profile: {}", e)))?;
+        Ok(Self {
+            dir,
+            persistent: false,
+        })
+    }
+
+    /// Profile kept across runs for the portal at `url`, so its cookies and
+    /// session are reused; a temporary one while another browser holds it,
+    /// as Chrome locks a profile to one process
+    pub fn for_portal(app_data_dir: &Path, url: &str) -> Result<Self, AppError> {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| "default".to_string());
+        let dir = app_data_dir.join("chrome-profiles").join(host);
+
+        let claimed = in_use().lock().map(|mut in_use| in_use.0.insert(dir.clone())).unwrap_or(false);
+        if !claimed {
+            return Self::temporary();
+        }
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            release_dir(&dir);
+            return Err(AppError::IoError(format!("Failed to create browser profile: {}", e)));
+        }
+        Ok(Self {
+            dir,
+            persistent: true,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+}
+
+impl Drop for BrowserProfile {
+    fn drop(&mut self) {
+        if self.persistent {
+            release_dir(&self.dir);
+        } else {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+fn release_dir(dir: &Path) {
+    if let Ok(mut in_use) = in_use().lock() {
+        in_use.0.remove(dir);
+    }
+}
+
+/// Remote debugging port reserved for one browser, so browsers launched at
+/// the same time never race for the same one
+#[derive(Debug)]
+pub struct DebugPort(u16);
+
+impl DebugPort {
+    pub fn reserve() -> Result<Self, AppError> {
+        let mut in_use = in_use()
+            .lock()
+            .map_err(|_| AppError::BrowserError("Port registry poisoned".to_string()))?;
+        let port = DEBUG_PORTS
+            .clone()
+            .find(|port| !in_use.1.contains(port) && TcpListener::bind(("127.0.0.1", *port)).is_ok())
+            .ok_or_else(|| AppError::BrowserError("No free port for the browser".to_string()))?;
+        in_use.1.insert(port);
+        Ok(Self(port))
+    }
+
+    pub fn number(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Drop for DebugPort {
+    fn drop(&mut self) {
+        if let Ok(mut in_use) = in_use().lock() {
+            in_use.1.remove(&self.0);
+        }
+    }
+}
+
+/// Delete temporary profiles left behind by browsers that were killed
+/// with the app
+pub fn remove_stale_profiles() {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_ours = entry.file_name().to_string_lossy().starts_with(TEMP_PROFILE_PREFIX);
+        let is_stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_PROFILE_AGE);
+        if is_ours && is_stale {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_are_isolated() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_data_dir = tmp.path();
+
+        let first = BrowserProfile::for_portal(app_data_dir, "https://a.vnpt-invoice.com.vn/").unwrap();
+        assert!(first.is_persistent());
+        // Chrome locks a profile, so a second browser on the portal gets its own
+        let second = BrowserProfile::for_portal(app_data_dir, "https://a.vnpt-invoice.com.vn/").unwrap();
+        assert!(!second.is_persistent());
+        let temporary = second.path().to_path_buf();
+        drop(second);
+        assert!(!temporary.exists());
+
+        drop(first);
+        assert!(BrowserProfile::for_portal(app_data_dir, "https://a.vnpt-invoice.com.vn/")
+            .unwrap()
+            .is_persistent());
+
+        let (a, b) = (DebugPort::reserve().unwrap(), DebugPort::reserve().unwrap());
+        assert_ne!(a.number(), b.number());
+    }
+}
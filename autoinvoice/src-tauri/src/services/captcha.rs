@@ -1,7 +1,133 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 use crate::error::AppError;
+use crate::services::ai_budget::{cost_usd, AiBudget};
+use crate::services::captcha_dataset::CaptchaSource;
+use crate::services::http::HttpClients;
+use crate::services::local_captcha::LocalCaptchaModel;
+
+/// Model that solves captchas and answers text prompts
+pub const MODEL: &str = "gpt-4o-mini";
+
+const CHAT_COMPLETIONS_URL: &str = "https://api.openai.com/v1/chat/completions";
+const MODELS_URL: &str = "https://api.openai.com/v1/models";
+
+/// Azure OpenAI REST version used when none is set
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-06-01";
+
+/// A `MODEL` deployment on Azure OpenAI, called instead of api.openai.com
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct AzureOpenAiConfig {
+    /// Resource URL, e.g. `https://contoso.openai.azure.com`
+    pub endpoint: String,
+    /// Name the model was deployed under in the Azure resource
+    pub deployment: String,
+    /// REST API version, e.g. `2024-06-01`
+    pub api_version: String,
+}
+
+impl AzureOpenAiConfig {
+    /// Check the settings are complete enough to call the deployment
+    pub fn validate(&self) -> Result<(), AppError> {
+        let endpoint = url::Url::parse(&self.endpoint)
+            .map_err(|_| AppError::ConfigError(format!("Invalid Azure OpenAI endpoint: {}", self.endpoint)))?;
+        if endpoint.scheme() != "https" || endpoint.host_str().is_none() {
+            return Err(AppError::ConfigError(format!(
+                "Invalid Azure OpenAI endpoint: {}",
+                self.endpoint
+            )));
+        }
+        // Azure allows letters, digits, `-`, `_` and `.` in deployment names
+        let deployment_ok = !self.deployment.is_empty()
+            && self
+                .deployment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !deployment_ok {
+            return Err(AppError::ConfigError(format!(
+                "Invalid Azure OpenAI deployment name: {}",
+                self.deployment
+            )));
+        }
+        if self.api_version.trim().is_empty() {
+            return Err(AppError::ConfigError("Azure OpenAI API version is not set".to_string()));
+        }
+        Ok(())
+    }
+
+    fn chat_completions_url(&self) -> String {
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            self.endpoint.trim_end_matches('/'),
+            self.deployment,
+            self.api_version
+        )
+    }
+}
+
+/// Why an API key works or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiKeyStatus {
+    Ok,
+    /// Wrong, revoked or mistyped
+    InvalidKey,
+    /// No credit left, or the spending limit is reached
+    QuotaExceeded,
+    /// Too many requests for now; works again later
+    RateLimited,
+    /// The key cannot use `MODEL`
+    ModelUnavailable,
+    /// OpenAI could not be reached
+    Unreachable,
+    Error,
+}
+
+/// Result of `test_api_key`
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ApiKeyCheck {
+    pub status: ApiKeyStatus,
+    /// Round trip of a one-token completion, in milliseconds
+    pub latency_ms: Option<u32>,
+    /// Models the key can use, sorted
+    pub models: Vec<String>,
+    /// Error from OpenAI when the check failed
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorDetail {
+    #[serde(default)]
+    message: String,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    code: Option<String>,
+}
 
 #[derive(Debug, Serialize)]
 struct OpenAIRequest {
@@ -16,14 +142,14 @@ struct Message {
     content: Vec<Content>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 enum Content {
     Text { r#type: String, text: String },
     Image { r#type: String, image_url: ImageUrl },
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 struct ImageUrl {
     url: String,
 }
@@ -31,6 +157,14 @@ struct ImageUrl {
 #[derive(Debug, Deserialize)]
 struct OpenAIResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,14 +177,116 @@ struct ResponseMessage {
     content: String,
 }
 
+/// Short form of an API key that tells keys apart in logs and spend
+/// reports without revealing them, e.g. `…x9Qa`
+pub fn key_hint(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    format!("…{}", tail)
+}
+
+/// API keys of a solver and the one in use; shared by the clones working
+/// for one batch, so a key that hit its limit is skipped by all of them
+#[derive(Debug)]
+struct KeyRing {
+    keys: Vec<String>,
+    current: AtomicUsize,
+}
+
+impl KeyRing {
+    fn new(keys: Vec<String>) -> Self {
+        Self {
+            keys,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    fn current(&self) -> (usize, &str) {
+        let index = self.current.load(Ordering::SeqCst) % self.keys.len().max(1);
+        (index, self.keys.get(index).map(String::as_str).unwrap_or_default())
+    }
+
+    /// Move past the key at `index`, unless another call already did
+    fn rotate_from(&self, index: usize) {
+        let next = (index + 1) % self.keys.len().max(1);
+        let _ = self.current.compare_exchange(index, next, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
 #[derive(Clone)]
 pub struct CaptchaSolver {
-    api_key: String,
+    /// The main key first, then the backups it fails over to
+    keys: Arc<KeyRing>,
+    http: HttpClients,
+    /// Recorded answers keyed by the SHA-256 of the image, used instead of
+    /// the API when replaying a fixture
+    answers: Option<Arc<HashMap<String, String>>>,
+    /// Spend caps of the batch the solver works for
+    budget: Option<Arc<AiBudget>>,
+    /// Offline model tried before the API, and the confidence its answer needs
+    local: Option<(Arc<LocalCaptchaModel>, f64)>,
+    /// Azure deployment that replaces api.openai.com
+    azure: Option<AzureOpenAiConfig>,
 }
 
 impl CaptchaSolver {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    pub fn new(api_key: String, http: HttpClients) -> Self {
+        Self {
+            keys: Arc::new(KeyRing::new(vec![api_key])),
+            http,
+            answers: None,
+            budget: None,
+            local: None,
+            azure: None,
+        }
+    }
+
+    /// Send requests to an Azure OpenAI deployment instead of OpenAI; the
+    /// API keys are then keys of the Azure resource
+    pub fn with_azure(mut self, azure: AzureOpenAiConfig) -> Self {
+        self.azure = Some(azure);
+        self
+    }
+
+    /// Keys to switch to when the current one is rate limited or out of
+    /// quota; blank and repeated keys are ignored
+    pub fn with_backup_keys(mut self, backup_keys: Vec<String>) -> Self {
+        let mut keys = self.keys.keys.clone();
+        for key in backup_keys {
+            let key = key.trim().to_string();
+            if !key.is_empty() && !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        self.keys = Arc::new(KeyRing::new(keys));
+        self
+    }
+
+    /// Read captchas with `model` first, asking the API only when its answer
+    /// is less confident than `min_confidence`
+    pub fn with_local_model(mut self, model: Arc<LocalCaptchaModel>, min_confidence: f64) -> Self {
+        self.local = Some((model, min_confidence));
+        self
+    }
+
+    /// Record the cost of every call against `budget`, and refuse calls
+    /// once it is exceeded
+    pub fn with_budget(mut self, budget: Arc<AiBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Solver that only knows the captchas of a recorded fixture, for
+    /// replays and demo batches
+    pub fn from_answers(answers: HashMap<String, String>, http: HttpClients) -> Self {
+        Self {
+            keys: Arc::new(KeyRing::new(vec![String::new()])),
+            http,
+            answers: Some(Arc::new(answers)),
+            budget: None,
+            local: None,
+            azure: None,
+        }
     }
 
     /// Solve a captcha image using OpenAI Vision API (GPT-4o-mini) - blocking version
@@ -59,12 +295,22 @@ impl CaptchaSolver {
     /// * `image_bytes` - The captcha image as PNG bytes
     ///
     /// # Returns
-    /// The extracted captcha text
-    pub fn solve_blocking(&self, image_bytes: &[u8]) -> Result<String, AppError> {
-        if self.api_key.is_empty() {
-            return Err(AppError::ConfigError(
-                "OpenAI API key is not set".to_string(),
-            ));
+    /// The extracted captcha text and who read it
+    pub fn solve_blocking(&self, image_bytes: &[u8]) -> Result<(String, CaptchaSource), AppError> {
+        // Recorded answers stand in for the API
+        if let Some(answers) = &self.answers {
+            let hash = hex::encode(Sha256::digest(image_bytes));
+            let text = answers.get(&hash).cloned().ok_or(AppError::CaptchaFailed(1))?;
+            return Ok((text, CaptchaSource::Ai));
+        }
+
+        // A failed or unsure local read falls through to the API
+        if let Some((model, min_confidence)) = &self.local {
+            if let Ok(answer) = model.solve(image_bytes) {
+                if answer.confidence >= *min_confidence {
+                    return Ok((answer.text, CaptchaSource::Local));
+                }
+            }
         }
 
         let base64_image = STANDARD.encode(image_bytes);
@@ -73,74 +319,287 @@ impl CaptchaSolver {
 Return ONLY the captcha text, nothing else. No explanations, no quotes, just the raw text. \
 The captcha usually contains 4 alphanumeric characters.";
 
-        let request = OpenAIRequest {
-            model: "gpt-4o-mini".to_string(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: vec![
-                    Content::Text {
-                        r#type: "text".to_string(),
-                        text: prompt.to_string(),
+        let captcha_text = self.chat_blocking(
+            vec![
+                Content::Text {
+                    r#type: "text".to_string(),
+                    text: prompt.to_string(),
+                },
+                Content::Image {
+                    r#type: "image_url".to_string(),
+                    image_url: ImageUrl {
+                        url: format!("data:image/png;base64,{}", base64_image),
                     },
-                    Content::Image {
-                        r#type: "image_url".to_string(),
-                        image_url: ImageUrl {
-                            url: format!("data:image/png;base64,{}", base64_image),
-                        },
-                    },
-                ],
+                },
+            ],
+            100,
+        )?;
+
+        // Clean up the response (remove quotes, whitespace, etc.)
+        let cleaned = captcha_text
+            .trim_matches(|c: char| c == '"' || c == '\'' || c.is_whitespace())
+            .to_string();
+
+        if cleaned.is_empty() {
+            return Err(AppError::CaptchaFailed(1));
+        }
+
+        Ok((cleaned, CaptchaSource::Ai))
+    }
+
+    /// Send a text-only prompt to the same model - blocking version
+    ///
+    /// # Returns
+    /// The trimmed model answer
+    pub fn ask_blocking(&self, prompt: &str, max_tokens: u32) -> Result<String, AppError> {
+        let answer = self.chat_blocking(
+            vec![Content::Text {
+                r#type: "text".to_string(),
+                text: prompt.to_string(),
             }],
-            max_tokens: 100,
+            max_tokens,
+        )?;
+
+        if answer.is_empty() {
+            return Err(AppError::NetworkError("OpenAI returned an empty answer".to_string()));
+        }
+
+        Ok(answer)
+    }
+
+    /// List the models the key can use and time a one-token completion,
+    /// telling a wrong key, an empty quota and rate limiting apart. On Azure
+    /// the key serves one deployment, so only the completion is tried
+    pub fn check_key_blocking(&self) -> ApiKeyCheck {
+        let mut check = ApiKeyCheck {
+            status: ApiKeyStatus::Ok,
+            latency_ms: None,
+            models: Vec::new(),
+            message: None,
+        };
+        let fail = |mut check: ApiKeyCheck, (status, message): (ApiKeyStatus, String)| {
+            check.status = status;
+            check.message = Some(message);
+            check
         };
 
-        let client = reqwest::blocking::Client::new();
-        let response = client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .map_err(|e| AppError::NetworkError(format!("Failed to call OpenAI API: {}", e)))?;
+        if let Some(azure) = &self.azure {
+            check.models = vec![azure.deployment.clone()];
+        } else {
+            let listed = self.http.blocking().and_then(|client| {
+                client
+                    .get(MODELS_URL)
+                    .header("Authorization", format!("Bearer {}", self.keys.current().1))
+                    .send()
+                    .map_err(|e| AppError::NetworkError(format!("Failed to call OpenAI API: {}", e)))
+            });
+            let response = match listed {
+                Ok(response) => response,
+                Err(e) => return fail(check, (ApiKeyStatus::Unreachable, e.to_string())),
+            };
+            if !response.status().is_success() {
+                let status = response.status();
+                return fail(check, classify_error(status, &response.text().unwrap_or_default()));
+            }
+            let mut models: Vec<String> = match response.json::<ModelList>() {
+                Ok(list) => list.data.into_iter().map(|model| model.id).collect(),
+                Err(e) => return fail(check, (ApiKeyStatus::Error, format!("Failed to parse model list: {}", e))),
+            };
+            models.sort();
+            check.models = models;
+            if !check.models.iter().any(|model| model == MODEL) {
+                let message = format!("The key cannot use {}", MODEL);
+                return fail(check, (ApiKeyStatus::ModelUnavailable, message));
+            }
+        }
 
+        // Listing models is free, so only a completion shows an empty quota
+        let started = Instant::now();
+        let content = vec![Content::Text {
+            r#type: "text".to_string(),
+            text: "Reply with OK".to_string(),
+        }];
+        let response = match self.send_chat(self.keys.current().1, content, 1) {
+            Ok(response) => response,
+            Err(e) => return fail(check, (ApiKeyStatus::Unreachable, e.to_string())),
+        };
+        check.latency_ms = Some(started.elapsed().as_millis() as u32);
         if !response.status().is_success() {
+            let status = response.status();
+            return fail(check, classify_error(status, &response.text().unwrap_or_default()));
+        }
+        check
+    }
+
+    /// Call the chat completions endpoint with a single user message,
+    /// moving on to the next key when one is rate limited or out of quota
+    fn chat_blocking(&self, content: Vec<Content>, max_tokens: u32) -> Result<String, AppError> {
+        if self.keys.current().1.is_empty() {
+            return Err(AppError::ConfigError(
+                "OpenAI API key is not set".to_string(),
+            ));
+        }
+        if let Some(budget) = &self.budget {
+            budget.check()?;
+        }
+
+        let mut tried = 0;
+        let (api_key, response) = loop {
+            let (index, api_key) = self.keys.current();
+            let response = self.send_chat(api_key, content.clone(), max_tokens)?;
+            tried += 1;
+            if response.status().is_success() {
+                break (api_key, response);
+            }
+
             let status = response.status();
             let error_text = response.text().unwrap_or_default();
+            let (kind, _) = classify_error(status, &error_text);
+            let limited = matches!(kind, ApiKeyStatus::RateLimited | ApiKeyStatus::QuotaExceeded);
+            if limited && tried < self.keys.keys.len() {
+                self.keys.rotate_from(index);
+                continue;
+            }
             return Err(AppError::NetworkError(format!(
-                "OpenAI API error ({}): {}",
-                status, error_text
+                "OpenAI API error ({}) with key {}: {}",
+                status,
+                key_hint(api_key),
+                error_text
             )));
-        }
+        };
 
         let result: OpenAIResponse = response
             .json()
             .map_err(|e| AppError::NetworkError(format!("Failed to parse OpenAI response: {}", e)))?;
+        if let (Some(budget), Some(usage)) = (&self.budget, &result.usage) {
+            budget.record(&key_hint(api_key), cost_usd(usage.prompt_tokens, usage.completion_tokens));
+        }
 
-        let captcha_text = result
+        Ok(result
             .choices
             .first()
             .map(|c| c.message.content.trim().to_string())
-            .ok_or_else(|| AppError::CaptchaFailed(1))?;
-
-        // Clean up the response (remove quotes, whitespace, etc.)
-        let cleaned = captcha_text
-            .trim_matches(|c: char| c == '"' || c == '\'' || c.is_whitespace())
-            .to_string();
+            .unwrap_or_default())
+    }
 
-        if cleaned.is_empty() {
-            return Err(AppError::CaptchaFailed(1));
-        }
+    fn send_chat(
+        &self,
+        api_key: &str,
+        content: Vec<Content>,
+        max_tokens: u32,
+    ) -> Result<reqwest::blocking::Response, AppError> {
+        let request = OpenAIRequest {
+            model: MODEL.to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content,
+            }],
+            max_tokens,
+        };
 
-        Ok(cleaned)
+        let client = self.http.blocking()?;
+        // Azure names the deployment in the URL and takes the key in `api-key`
+        let post = match &self.azure {
+            Some(azure) => client.post(azure.chat_completions_url()).header("api-key", api_key),
+            None => client
+                .post(CHAT_COMPLETIONS_URL)
+                .header("Authorization", format!("Bearer {}", api_key)),
+        };
+        post
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .map_err(|e| AppError::NetworkError(format!("Failed to call OpenAI API: {}", e)))
     }
 }
 
+/// Status of a failed OpenAI call, with the message from its error body
+fn classify_error(status: StatusCode, body: &str) -> (ApiKeyStatus, String) {
+    let detail = serde_json::from_str::<ErrorBody>(body).unwrap_or_default().error;
+    let is = |value: &str| detail.code.as_deref() == Some(value) || detail.kind.as_deref() == Some(value);
+    let kind = match status {
+        StatusCode::UNAUTHORIZED => ApiKeyStatus::InvalidKey,
+        StatusCode::TOO_MANY_REQUESTS if is("insufficient_quota") => ApiKeyStatus::QuotaExceeded,
+        StatusCode::TOO_MANY_REQUESTS => ApiKeyStatus::RateLimited,
+        _ if is("model_not_found") || is("DeploymentNotFound") => ApiKeyStatus::ModelUnavailable,
+        _ => ApiKeyStatus::Error,
+    };
+    let message = if detail.message.is_empty() {
+        format!("OpenAI API error ({})", status)
+    } else {
+        detail.message
+    };
+    (kind, message)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_captcha_solver_creation() {
-        let solver = CaptchaSolver::new("test-api-key".to_string());
-        assert!(!solver.api_key.is_empty());
+        let http = HttpClients::new(Default::default()).unwrap();
+        let solver = CaptchaSolver::new("test-api-key".to_string(), http);
+        assert!(!solver.keys.current().1.is_empty());
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let http = HttpClients::new(Default::default()).unwrap();
+        let solver = CaptchaSolver::new("sk-main-AAAA".to_string(), http)
+            .with_backup_keys(vec![" sk-backup-BBBB ".to_string(), String::new(), "sk-main-AAAA".to_string()]);
+        let clone = solver.clone();
+        assert_eq!(solver.keys.keys.len(), 2);
+        assert_eq!(key_hint(solver.keys.current().1), "…AAAA");
+
+        // Two clones hitting the limit of the same key move on only once
+        solver.keys.rotate_from(0);
+        clone.keys.rotate_from(0);
+        assert_eq!(clone.keys.current(), (1, "sk-backup-BBBB"));
+        solver.keys.rotate_from(1);
+        assert_eq!(clone.keys.current().0, 0);
+        assert_eq!(key_hint("ab"), "…ab");
+    }
+
+    #[test]
+    fn test_classify_api_error() {
+        let quota = r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}}"#;
+        let (status, message) = classify_error(StatusCode::TOO_MANY_REQUESTS, quota);
+        assert_eq!(status, ApiKeyStatus::QuotaExceeded);
+        assert_eq!(message, "You exceeded your current quota");
+
+        let rate = r#"{"error": {"message": "Rate limit reached", "type": "requests", "code": "rate_limit_exceeded"}}"#;
+        assert_eq!(classify_error(StatusCode::TOO_MANY_REQUESTS, rate).0, ApiKeyStatus::RateLimited);
+        assert_eq!(classify_error(StatusCode::UNAUTHORIZED, "").0, ApiKeyStatus::InvalidKey);
+        let model = r#"{"error": {"message": "no access", "code": "model_not_found"}}"#;
+        assert_eq!(classify_error(StatusCode::NOT_FOUND, model).0, ApiKeyStatus::ModelUnavailable);
+        assert_eq!(classify_error(StatusCode::BAD_GATEWAY, "<html>").1, "OpenAI API error (502 Bad Gateway)");
+        let deployment = r#"{"error": {"code": "DeploymentNotFound", "message": "The API deployment for this resource does not exist."}}"#;
+        assert_eq!(classify_error(StatusCode::NOT_FOUND, deployment).0, ApiKeyStatus::ModelUnavailable);
+    }
+
+    #[test]
+    fn test_azure_config() {
+        let azure = AzureOpenAiConfig {
+            endpoint: "https://contoso.openai.azure.com/".to_string(),
+            deployment: "gpt-4o-mini".to_string(),
+            api_version: DEFAULT_AZURE_API_VERSION.to_string(),
+        };
+        assert!(azure.validate().is_ok());
+        assert_eq!(
+            azure.chat_completions_url(),
+            "https://contoso.openai.azure.com/openai/deployments/gpt-4o-mini/chat/completions?api-version=2024-06-01"
+        );
+
+        let plain_http = AzureOpenAiConfig {
+            endpoint: "http://contoso.openai.azure.com".to_string(),
+            ..azure.clone()
+        };
+        assert!(plain_http.validate().is_err());
+        let bad_deployment = AzureOpenAiConfig {
+            deployment: "gpt 4o/mini".to_string(),
+            ..azure
+        };
+        assert!(bad_deployment.validate().is_err());
     }
 }
@@ -0,0 +1,140 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+
+/// Folder under the app data directory used when no dataset folder is set
+pub const DEFAULT_DATASET_DIR: &str = "captcha-dataset";
+
+const IMAGES_DIR: &str = "images";
+const LABELS_FILE: &str = "labels.csv";
+const LABELS_HEADER: &str = "file,answer,source,portal,saved_at\n";
+
+/// Who read the captcha that the portal accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaSource {
+    Ai,
+    /// The offline model, see `LocalCaptchaModel`
+    Local,
+    Manual,
+}
+
+impl CaptchaSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptchaSource::Ai => "ai",
+            CaptchaSource::Local => "local",
+            CaptchaSource::Manual => "manual",
+        }
+    }
+}
+
+/// Where the dataset is kept and how many samples it holds
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CaptchaDataset {
+    pub path: String,
+    pub sample_count: u32,
+}
+
+/// The configured folder, or `<app data>/captcha-dataset` when none is set
+pub fn dataset_dir(configured: &str, app_data_dir: &Path) -> PathBuf {
+    match configured.trim() {
+        "" => app_data_dir.join(DEFAULT_DATASET_DIR),
+        dir => PathBuf::from(dir),
+    }
+}
+
+/// Save a captcha the portal accepted as `images/<hash>.png` and label it in
+/// `labels.csv`, the layout most OCR training scripts read; an image already
+/// in the dataset is not added twice
+///
+/// # Returns
+/// Whether the sample was new
+pub fn save_sample(
+    dir: &Path,
+    portal_url: &str,
+    image: &[u8],
+    answer: &str,
+    source: CaptchaSource,
+) -> Result<bool, AppError> {
+    let images_dir = dir.join(IMAGES_DIR);
+    fs::create_dir_all(&images_dir)?;
+
+    // 16 bytes of the hash are plenty to tell captchas apart
+    let file = format!("{}.png", &hex::encode(Sha256::digest(image))[..32]);
+    let image_path = images_dir.join(&file);
+    if image_path.exists() {
+        return Ok(false);
+    }
+    fs::write(&image_path, image)?;
+
+    let portal = url::Url::parse(portal_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let labels_path = dir.join(LABELS_FILE);
+    let mut labels = OpenOptions::new().create(true).append(true).open(&labels_path)?;
+    let mut line = String::new();
+    if labels.metadata()?.len() == 0 {
+        line.push_str(LABELS_HEADER);
+    }
+    // Answers are short alphanumerics; commas or quotes would only come from
+    // a typo in a manual answer, so they are dropped rather than quoted
+    let answer: String = answer.chars().filter(|c| !matches!(c, ',' | '"' | '\n' | '\r')).collect();
+    line.push_str(&format!(
+        "{}/{},{},{},{},{}\n",
+        IMAGES_DIR,
+        file,
+        answer,
+        source.as_str(),
+        portal,
+        chrono::Utc::now().to_rfc3339()
+    ));
+    // One write per sample, so concurrent batches do not interleave lines
+    labels.write_all(line.as_bytes())?;
+    Ok(true)
+}
+
+/// Count the labelled samples in the dataset at `dir`
+pub fn describe(dir: &Path) -> Result<CaptchaDataset, AppError> {
+    let sample_count = match fs::read_to_string(dir.join(LABELS_FILE)) {
+        Ok(labels) => labels.lines().skip(1).filter(|line| !line.trim().is_empty()).count() as u32,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(CaptchaDataset {
+        path: dir.to_string_lossy().to_string(),
+        sample_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_samples() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_data_dir = tmp.path();
+        let dir = dataset_dir(" ", app_data_dir);
+        assert_eq!(dir, app_data_dir.join(DEFAULT_DATASET_DIR));
+        let url = "https://a.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey";
+
+        assert!(save_sample(&dir, url, b"png one", "4kx9", CaptchaSource::Ai).unwrap());
+        assert!(save_sample(&dir, url, b"png two", "b7,m2", CaptchaSource::Manual).unwrap());
+        assert!(!save_sample(&dir, url, b"png one", "4kx9", CaptchaSource::Ai).unwrap());
+
+        let labels = fs::read_to_string(dir.join(LABELS_FILE)).unwrap();
+        let lines: Vec<&str> = labels.lines().collect();
+        assert_eq!(lines[0], LABELS_HEADER.trim_end());
+        assert!(lines[1].contains(",4kx9,ai,a.vnpt-invoice.com.vn,"));
+        assert!(lines[2].contains(",b7m2,manual,"));
+        let file = lines[1].split(',').next().unwrap();
+        assert_eq!(fs::read(dir.join(file)).unwrap(), b"png one");
+        assert_eq!(describe(&dir).unwrap().sample_count, 2);
+    }
+}
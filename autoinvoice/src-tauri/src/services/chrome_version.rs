@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// First major version taking `--headless=new`
+pub const NEW_HEADLESS_SINCE: u32 = 109;
+
+/// First major version whose `chrome` binary has no old headless mode left;
+/// it moved to the separate `chrome-headless-shell`
+pub const OLD_HEADLESS_REMOVED: u32 = 132;
+
+/// First major version showing PDFs in an out-of-process frame, which
+/// screenshots and downloads of the PDF tab do not see
+pub const PDF_OOPIF_SINCE: u32 = 126;
+
+/// `--disable-features` of the launcher, which a second `--disable-features`
+/// would replace
+const DEFAULT_DISABLED_FEATURES: &str = "TranslateUI,BlinkGenPropertyTrees";
+
+/// How Chrome is told to run without a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadlessMode {
+    Off,
+    /// `--headless`, gone from recent Chrome
+    Old,
+    /// `--headless=new`
+    New,
+}
+
+/// Major version of the Chrome at `chrome`, detected once per executable;
+/// `None` when it cannot be told, and the launch flags then stay the ones
+/// known to work on most versions
+pub fn major_version(chrome: &Path) -> Option<u32> {
+    static DETECTED: OnceLock<Mutex<HashMap<PathBuf, Option<u32>>>> = OnceLock::new();
+    let detected = DETECTED.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(version) = detected.lock().ok().and_then(|detected| detected.get(chrome).copied()) {
+        return version;
+    }
+
+    let version = detect_major_version(chrome);
+    match version {
+        Some(major) => tracing::info!("Chrome {} at {}", major, chrome.display()),
+        None => tracing::warn!("Could not tell the Chrome version at {}", chrome.display()),
+    }
+    if let Ok(mut detected) = detected.lock() {
+        detected.insert(chrome.to_path_buf(), version);
+    }
+    version
+}
+
+/// `chrome --version` would open a browser window on Windows, where the
+/// version is the name of a folder next to the executable instead
+#[cfg(target_os = "windows")]
+fn detect_major_version(chrome: &Path) -> Option<u32> {
+    version_from_install_dir(chrome)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn detect_major_version(chrome: &Path) -> Option<u32> {
+    std::process::Command::new(chrome)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| parse_major_version(&String::from_utf8_lossy(&output.stdout)))
+        .or_else(|| version_from_install_dir(chrome))
+}
+
+/// Major version in e.g. "Google Chrome 131.0.6778.85" or "Chromium 120.0.6099.224 snap"
+fn parse_major_version(text: &str) -> Option<u32> {
+    text.split_whitespace().find_map(|word| {
+        let parts: Vec<&str> = word.split('.').collect();
+        if parts.len() < 3 || !parts.iter().all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())) {
+            return None;
+        }
+        parts[0].parse().ok()
+    })
+}
+
+/// Highest version among the `131.0.6778.85` folders installers put next
+/// to the executable
+fn version_from_install_dir(chrome: &Path) -> Option<u32> {
+    std::fs::read_dir(chrome.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| parse_major_version(&entry.file_name().to_string_lossy()))
+        .max()
+}
+
+/// Headless mode for a Chrome of `major`; `prefer_new` when its default does
+/// not start
+pub fn headless_mode(headless: bool, major: Option<u32>, prefer_new: bool) -> HeadlessMode {
+    if !headless {
+        return HeadlessMode::Off;
+    }
+    match major {
+        Some(major) if major < NEW_HEADLESS_SINCE => HeadlessMode::Old,
+        Some(major) if major >= OLD_HEADLESS_REMOVED => HeadlessMode::New,
+        _ if prefer_new => HeadlessMode::New,
+        _ => HeadlessMode::Old,
+    }
+}
+
+/// `--disable-features` for a Chrome of `major`, keeping the launcher's own
+pub fn disabled_features(major: Option<u32>) -> Option<String> {
+    match major {
+        Some(major) if major >= PDF_OOPIF_SINCE => Some(format!("--disable-features={},PdfOopif", DEFAULT_DISABLED_FEATURES)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_major_version() {
+        assert_eq!(parse_major_version("Google Chrome 131.0.6778.85 \n"), Some(131));
+        assert_eq!(parse_major_version("Chromium 120.0.6099.224 snap"), Some(120));
+        assert_eq!(parse_major_version("Microsoft Edge 118.0.2088.46"), Some(118));
+        assert_eq!(parse_major_version("131.0.6778.85"), Some(131));
+        assert_eq!(parse_major_version("chrome_proxy.exe"), None);
+        assert_eq!(parse_major_version("1.0"), None);
+        assert_eq!(parse_major_version(""), None);
+    }
+
+    #[test]
+    fn test_version_from_install_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("130.0.6723.117")).unwrap();
+        std::fs::create_dir_all(dir.join("131.0.6778.86")).unwrap();
+        std::fs::create_dir_all(dir.join("SetupMetrics")).unwrap();
+        std::fs::write(dir.join("chrome.exe"), b"").unwrap();
+
+        assert_eq!(version_from_install_dir(&dir.join("chrome.exe")), Some(131));
+    }
+
+    #[test]
+    fn test_launch_flags_by_version() {
+        assert_eq!(headless_mode(false, Some(131), true), HeadlessMode::Off);
+        assert_eq!(headless_mode(true, Some(100), true), HeadlessMode::Old);
+        assert_eq!(headless_mode(true, Some(120), false), HeadlessMode::Old);
+        assert_eq!(headless_mode(true, Some(120), true), HeadlessMode::New);
+        assert_eq!(headless_mode(true, Some(133), false), HeadlessMode::New);
+        assert_eq!(headless_mode(true, None, false), HeadlessMode::Old);
+
+        assert_eq!(disabled_features(Some(125)), None);
+        assert_eq!(
+            disabled_features(Some(131)).as_deref(),
+            Some("--disable-features=TranslateUI,BlinkGenPropertyTrees,PdfOopif")
+        );
+    }
+}
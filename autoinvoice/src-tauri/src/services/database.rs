@@ -1,9 +1,381 @@
 use rusqlite::{Connection, params, OptionalExtension};
 use std::path::PathBuf;
 use std::sync::Mutex;
+use std::time::Duration;
 use crate::error::AppError;
+use crate::commands::audit::{AuditEntry, AuditLogFilter};
 use crate::commands::history::{DownloadBatch, HistoryInvoice};
 use crate::commands::settings::Settings;
+use crate::services::browser::{BrowserViewport, DEFAULT_CAPTCHA_PADDING_PX};
+use crate::services::captcha::DEFAULT_AZURE_API_VERSION;
+use crate::services::downloader::{DEFAULT_INVOICE_DELAY_SECS, DEFAULT_NOT_FOUND_VERIFY_DELAY_SECS};
+use crate::services::manual_captcha::DEFAULT_MANUAL_CAPTCHA_TIMEOUT_SECS;
+use crate::services::cloud_drive::CloudProvider;
+use crate::services::estimator::InvoiceStats;
+use crate::services::ai_budget::{AiKeyUsage, BudgetAction};
+use crate::services::dedupe::DuplicateFiles;
+use crate::services::file_naming::Transliteration;
+use crate::services::invoice_export::CsvDelimiter;
+use crate::services::disk_usage::RecordedFile;
+use crate::services::pdf_optimize::PdfOptimization;
+use crate::services::pdf_fetch::PdfVariant;
+use crate::services::hooks::HookScope;
+use crate::services::anomalies::InvoiceFacts;
+use crate::services::invoice_xml::XmlInvoice;
+use crate::services::pdf_metadata::{InvoiceMetadata, MetadataSource};
+use crate::services::portal_profiles::PortalProfile;
+use crate::services::xml_signature::SignatureCheck;
+use crate::services::local_captcha::DEFAULT_MIN_CONFIDENCE;
+use crate::services::onboarding::OnboardingStep;
+use crate::services::inbox::{InboxCursor, DEFAULT_IMAP_FOLDER, DEFAULT_IMAP_PORT};
+use crate::services::retry_later::{RetryLaterInvoice, DEFAULT_RETRY_NOT_FOUND_HOURS};
+use crate::services::sftp::{SftpMode, DEFAULT_SFTP_PORT};
+use crate::services::status_writer::InvoiceWrite;
+use crate::status::{AuditAction, BatchStatus, InvoiceStatus, SignatureStatus, TimingStage};
+
+/// Schema migrations applied in order on top of the base schema.
+/// The SQLite `user_version` pragma stores how many have been applied.
+const MIGRATIONS: &[&str] = &[
+    // 1: repro bundle written when an invoice fails
+    "ALTER TABLE invoices ADD COLUMN repro_path TEXT;",
+    // 2: restrict invoice status to the values of `InvoiceStatus`
+    r#"
+    CREATE TABLE invoices_new (
+        id TEXT PRIMARY KEY,
+        batch_id TEXT NOT NULL,
+        code TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending'
+            CHECK (status IN ('pending', 'downloading', 'success', 'failed', 'skipped', 'cancelled')),
+        error TEXT,
+        file_path TEXT,
+        downloaded_at TEXT,
+        repro_path TEXT,
+        FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
+    );
+
+    INSERT INTO invoices_new (id, batch_id, code, status, error, file_path, downloaded_at, repro_path)
+    SELECT id, batch_id, code,
+        CASE WHEN status IN ('pending', 'downloading', 'success', 'failed', 'skipped', 'cancelled')
+            THEN status ELSE 'failed' END,
+        error, file_path, downloaded_at, repro_path
+    FROM invoices;
+
+    DROP TABLE invoices;
+    ALTER TABLE invoices_new RENAME TO invoices;
+
+    CREATE INDEX IF NOT EXISTS idx_invoices_batch_id ON invoices(batch_id);
+    CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
+    "#,
+    // 3: batch lifecycle status; batches from before this column existed had finished
+    r#"
+    ALTER TABLE batches ADD COLUMN status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'paused', 'completed', 'cancelled', 'failed'));
+    UPDATE batches SET status = 'completed';
+    "#,
+    // 4: per-invoice timings used to estimate new batches
+    r#"
+    ALTER TABLE invoices ADD COLUMN duration_ms INTEGER;
+    ALTER TABLE invoices ADD COLUMN captcha_attempts INTEGER;
+    "#,
+    // 5: portal that served the invoice
+    r#"
+    ALTER TABLE invoices ADD COLUMN portal TEXT
+        CHECK (portal IS NULL OR portal IN ('vnpt', 'tax-authority'));
+    "#,
+    // 6: per-portal sub-batches linked to the batch they were split from
+    r#"
+    ALTER TABLE batches ADD COLUMN parent_id TEXT REFERENCES batches(id) ON DELETE CASCADE;
+    CREATE INDEX IF NOT EXISTS idx_batches_parent_id ON batches(parent_id);
+    "#,
+    // 7: download requests of batches queued to start once the app is online
+    r#"
+    CREATE TABLE queued_downloads (
+        batch_id TEXT PRIMARY KEY,
+        request TEXT NOT NULL,
+        queued_at TEXT NOT NULL,
+        FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
+    );
+    "#,
+    // 8: significant user actions, kept for internal controls; batch_id is not
+    // a foreign key so entries outlive deleted batches
+    r#"
+    CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp TEXT NOT NULL,
+        user TEXT NOT NULL,
+        action TEXT NOT NULL
+            CHECK (action IN ('batch-started', 'batch-cancelled', 'batch-deleted', 'settings-changed', 'export')),
+        batch_id TEXT,
+        details TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_audit_log_timestamp ON audit_log(timestamp);
+    CREATE INDEX IF NOT EXISTS idx_audit_log_action ON audit_log(action);
+    "#,
+    // 9: opt-in per-stage timings for the local performance metrics
+    r#"
+    CREATE TABLE stage_timings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        batch_id TEXT NOT NULL,
+        invoice_id TEXT NOT NULL,
+        stage TEXT NOT NULL
+            CHECK (stage IN ('navigation', 'captcha-solve', 'submit', 'download')),
+        duration_ms INTEGER NOT NULL,
+        recorded_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_stage_timings_recorded_at ON stage_timings(recorded_at);
+    "#,
+    // 10: config each batch ran with, per-batch overrides included
+    "ALTER TABLE batches ADD COLUMN effective_config TEXT;",
+    // 11: allow 'needs-manual' for invoices deferred to an interactive captcha pass
+    r#"
+    CREATE TABLE invoices_new (
+        id TEXT PRIMARY KEY,
+        batch_id TEXT NOT NULL,
+        code TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending'
+            CHECK (status IN ('pending', 'downloading', 'success', 'failed', 'skipped', 'cancelled', 'needs-manual')),
+        error TEXT,
+        file_path TEXT,
+        downloaded_at TEXT,
+        repro_path TEXT,
+        duration_ms INTEGER,
+        captcha_attempts INTEGER,
+        portal TEXT CHECK (portal IS NULL OR portal IN ('vnpt', 'tax-authority')),
+        FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
+    );
+
+    INSERT INTO invoices_new
+        (id, batch_id, code, status, error, file_path, downloaded_at, repro_path, duration_ms, captcha_attempts, portal)
+    SELECT id, batch_id, code, status, error, file_path, downloaded_at, repro_path, duration_ms, captcha_attempts, portal
+    FROM invoices;
+
+    DROP TABLE invoices;
+    ALTER TABLE invoices_new RENAME TO invoices;
+
+    CREATE INDEX IF NOT EXISTS idx_invoices_batch_id ON invoices(batch_id);
+    CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
+    "#,
+    // 12: where an invoice was uploaded after download
+    "ALTER TABLE invoices ADD COLUMN remote_url TEXT;",
+    // 13: structured content of the invoice's XML; the columns next to `data`
+    // are the ones reports filter and sum on
+    r#"
+    CREATE TABLE invoice_xml (
+        invoice_id TEXT PRIMARY KEY,
+        xml_path TEXT NOT NULL,
+        seller_tax_code TEXT,
+        issued_date TEXT,
+        total_before_tax REAL NOT NULL,
+        total_tax REAL NOT NULL,
+        total_payable REAL NOT NULL,
+        valid INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        parsed_at TEXT NOT NULL,
+        FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS idx_invoice_xml_seller_tax_code ON invoice_xml(seller_tax_code);
+    "#,
+    // 14: XML-DSig verification of the invoice XML
+    r#"
+    ALTER TABLE invoice_xml ADD COLUMN signature_status TEXT
+        CHECK (signature_status IS NULL OR signature_status IN ('valid', 'invalid', 'unsigned'));
+    ALTER TABLE invoice_xml ADD COLUMN signatures TEXT;
+    "#,
+    // 15: fields read from the invoice PDF, by text extraction or OCR
+    r#"
+    CREATE TABLE invoice_metadata (
+        invoice_id TEXT PRIMARY KEY,
+        source TEXT NOT NULL CHECK (source IN ('text', 'ocr')),
+        series TEXT,
+        number TEXT,
+        issued_date TEXT,
+        seller_tax_code TEXT,
+        total_before_tax REAL,
+        total_tax REAL,
+        total_payable REAL,
+        extracted_at TEXT NOT NULL,
+        FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+    );
+    "#,
+    // 16: human-readable batch name and the Excel file it was imported from
+    r#"
+    ALTER TABLE batches ADD COLUMN name TEXT;
+    ALTER TABLE batches ADD COLUMN source_file TEXT;
+    "#,
+    // 17: full path and SHA-256 of the source Excel file
+    r#"
+    ALTER TABLE batches ADD COLUMN source_path TEXT;
+    ALTER TABLE batches ADD COLUMN source_hash TEXT;
+    CREATE INDEX IF NOT EXISTS idx_batches_source_hash ON batches(source_hash);
+    "#,
+    // 18: politeness settings per portal host, over the built-in defaults
+    r#"
+    CREATE TABLE portal_profiles (
+        host TEXT PRIMARY KEY,
+        base_delay_secs INTEGER NOT NULL,
+        max_concurrency INTEGER NOT NULL CHECK (max_concurrency > 0),
+        max_retries INTEGER NOT NULL CHECK (max_retries > 0)
+    );
+    "#,
+    // 19: allow 'not-found' for invoices the portal has no record of
+    r#"
+    CREATE TABLE invoices_new (
+        id TEXT PRIMARY KEY,
+        batch_id TEXT NOT NULL,
+        code TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending'
+            CHECK (status IN ('pending', 'downloading', 'success', 'failed', 'skipped', 'cancelled', 'needs-manual', 'not-found')),
+        error TEXT,
+        file_path TEXT,
+        downloaded_at TEXT,
+        repro_path TEXT,
+        duration_ms INTEGER,
+        captcha_attempts INTEGER,
+        portal TEXT CHECK (portal IS NULL OR portal IN ('vnpt', 'tax-authority')),
+        remote_url TEXT,
+        FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
+    );
+
+    INSERT INTO invoices_new
+        (id, batch_id, code, status, error, file_path, downloaded_at, repro_path, duration_ms, captcha_attempts, portal, remote_url)
+    SELECT id, batch_id, code, status, error, file_path, downloaded_at, repro_path, duration_ms, captcha_attempts, portal, remote_url
+    FROM invoices;
+
+    DROP TABLE invoices;
+    ALTER TABLE invoices_new RENAME TO invoices;
+
+    CREATE INDEX IF NOT EXISTS idx_invoices_batch_id ON invoices(batch_id);
+    CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
+    "#,
+    // 20: allow 'ignored' for failed invoices the user gave up on, with a note
+    // saying why
+    r#"
+    CREATE TABLE invoices_new (
+        id TEXT PRIMARY KEY,
+        batch_id TEXT NOT NULL,
+        code TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending'
+            CHECK (status IN ('pending', 'downloading', 'success', 'failed', 'skipped', 'cancelled', 'needs-manual', 'not-found', 'ignored')),
+        error TEXT,
+        file_path TEXT,
+        downloaded_at TEXT,
+        repro_path TEXT,
+        duration_ms INTEGER,
+        captcha_attempts INTEGER,
+        portal TEXT CHECK (portal IS NULL OR portal IN ('vnpt', 'tax-authority')),
+        remote_url TEXT,
+        note TEXT,
+        FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
+    );
+
+    INSERT INTO invoices_new
+        (id, batch_id, code, status, error, file_path, downloaded_at, repro_path, duration_ms, captcha_attempts, portal, remote_url)
+    SELECT id, batch_id, code, status, error, file_path, downloaded_at, repro_path, duration_ms, captcha_attempts, portal, remote_url
+    FROM invoices;
+
+    DROP TABLE invoices;
+    ALTER TABLE invoices_new RENAME TO invoices;
+
+    CREATE INDEX IF NOT EXISTS idx_invoices_batch_id ON invoices(batch_id);
+    CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
+    "#,
+    // 21: outcome of compressing and linearizing each invoice PDF
+    r#"
+    CREATE TABLE pdf_optimizations (
+        invoice_id TEXT PRIMARY KEY,
+        original_bytes INTEGER NOT NULL,
+        optimized_bytes INTEGER NOT NULL,
+        linearized INTEGER NOT NULL,
+        original_path TEXT,
+        skipped TEXT,
+        optimized_at TEXT NOT NULL,
+        FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+    );
+    "#,
+    // 22: estimated AI captcha spend per month, for the monthly budget
+    r#"
+    CREATE TABLE ai_spend (
+        month TEXT PRIMARY KEY,
+        cost_usd REAL NOT NULL
+    );
+    "#,
+    // 23: AI spend per month and API key, for cost attribution
+    r#"
+    CREATE TABLE ai_key_usage (
+        month TEXT NOT NULL,
+        key_hint TEXT NOT NULL,
+        calls INTEGER NOT NULL,
+        cost_usd REAL NOT NULL,
+        PRIMARY KEY (month, key_hint)
+    );
+    "#,
+    // 24: indexes for lookups by code and download time; (batch_id, status)
+    // also serves lookups by batch alone
+    r#"
+    CREATE INDEX IF NOT EXISTS idx_invoices_code ON invoices(code);
+    CREATE INDEX IF NOT EXISTS idx_invoices_downloaded_at ON invoices(downloaded_at);
+    CREATE INDEX IF NOT EXISTS idx_invoices_batch_id_status ON invoices(batch_id, status);
+    DROP INDEX IF EXISTS idx_invoices_batch_id;
+    "#,
+];
+
+/// How long a statement waits for another connection's lock, e.g. a second
+/// app instance or a backup tool, before failing as busy
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Audit entries returned when the filter sets no limit
+const DEFAULT_AUDIT_LIMIT: u32 = 1000;
+
+/// How many recent stage timings feed the performance metrics
+const METRICS_SAMPLE_LIMIT: u32 = 20_000;
+
+/// How many recent finished invoices feed the batch estimate
+const STATS_SAMPLE_LIMIT: u32 = 500;
+
+const BATCH_COLUMNS: &str =
+    "id, created_at, total_count, success_count, failed_count, download_directory, status, parent_id, effective_config, name, source_file, source_path, source_hash";
+
+/// Ids of a batch and its sub-batches, bound to `?1`
+const BATCH_TREE: &str = "SELECT id FROM batches WHERE id = ?1 OR parent_id = ?1";
+
+fn batch_from_row(row: &rusqlite::Row) -> rusqlite::Result<DownloadBatch> {
+    Ok(DownloadBatch {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        total_count: row.get(2)?,
+        success_count: row.get(3)?,
+        failed_count: row.get(4)?,
+        download_directory: row.get(5)?,
+        status: row.get(6)?,
+        parent_id: row.get(7)?,
+        effective_config: row.get(8)?,
+        name: row.get(9)?,
+        source_file: row.get(10)?,
+        source_path: row.get(11)?,
+        source_hash: row.get(12)?,
+    })
+}
+
+const INVOICE_COLUMNS: &str = "id, batch_id, code, status, error, file_path, downloaded_at, \
+    repro_path, portal, remote_url, \
+    (SELECT signature_status FROM invoice_xml WHERE invoice_id = invoices.id), note";
+
+fn invoice_from_row(row: &rusqlite::Row) -> rusqlite::Result<HistoryInvoice> {
+    Ok(HistoryInvoice {
+        id: row.get(0)?,
+        batch_id: row.get(1)?,
+        code: row.get(2)?,
+        status: row.get(3)?,
+        error: row.get(4)?,
+        file_path: row.get(5)?,
+        downloaded_at: row.get(6)?,
+        repro_path: row.get(7)?,
+        portal: row.get(8)?,
+        remote_url: row.get(9)?,
+        signature_status: row.get(10)?,
+        note: row.get(11)?,
+    })
+}
 
 /// Database service for persisting download history
 pub struct Database {
@@ -19,6 +391,23 @@ impl Database {
         let db_path = app_data_dir.join("autoinvoice.db");
         let conn = Connection::open(&db_path)
             .map_err(|e| AppError::DatabaseError(format!("Failed to open database: {}", e)))?;
+        Self::configure(&conn)?;
+
+        let db = Self {
+            conn: Mutex::new(conn),
+        };
+
+        db.init_schema()?;
+        Ok(db)
+    }
+
+    /// Database with the full schema that lives as long as the value, for
+    /// tests that need no file
+    #[cfg(test)]
+    pub fn new_in_memory() -> Result<Self, AppError> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to open database: {}", e)))?;
+        Self::configure(&conn)?;
 
         let db = Self {
             conn: Mutex::new(conn),
@@ -28,6 +417,19 @@ impl Database {
         Ok(db)
     }
 
+    /// Connection settings: WAL so history reads do not wait for a batch's
+    /// writes, NORMAL sync (durable in WAL mode but for the last commits on
+    /// power loss), and a busy timeout instead of failing at once
+    fn configure(conn: &Connection) -> Result<(), AppError> {
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to enable WAL: {}", e)))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|e| AppError::DatabaseError(format!("Failed to set synchronous mode: {}", e)))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to set busy timeout: {}", e)))?;
+        Ok(())
+    }
+
     /// Initialize database schema
     fn init_schema(&self) -> Result<(), AppError> {
         let conn = self.conn.lock().unwrap();
@@ -54,7 +456,6 @@ impl Database {
                 FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
             );
 
-            CREATE INDEX IF NOT EXISTS idx_invoices_batch_id ON invoices(batch_id);
             CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
 
             CREATE TABLE IF NOT EXISTS settings (
@@ -65,6 +466,62 @@ impl Database {
         )
         .map_err(|e| AppError::DatabaseError(format!("Failed to init schema: {}", e)))?;
 
+        // Migrations rebuild tables by dropping them, which with enforcement
+        // on would cascade into the rows that reference them; the bundled
+        // SQLite enables it by default, so it is turned off explicitly
+        conn.pragma_update(None, "foreign_keys", false)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to disable foreign keys: {}", e)))?;
+        Self::run_migrations(&conn)?;
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to enable foreign keys: {}", e)))?;
+        Self::repair_orphans(&conn)?;
+
+        Ok(())
+    }
+
+    /// Delete rows whose parent is gone, left behind while enforcement was
+    /// off; their own children follow by cascade
+    ///
+    /// # Returns
+    /// How many rows were deleted directly
+    fn repair_orphans(conn: &Connection) -> Result<usize, AppError> {
+        let orphans: Vec<(String, i64)> = conn
+            .prepare("PRAGMA foreign_key_check")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .map_err(|e| AppError::DatabaseError(format!("Failed to check foreign keys: {}", e)))?;
+
+        let mut repaired = 0;
+        for (table, rowid) in orphans {
+            repaired += conn
+                .execute(&format!("DELETE FROM \"{}\" WHERE rowid = ?1", table), [rowid])
+                .map_err(|e| AppError::DatabaseError(format!("Failed to delete orphaned row: {}", e)))?;
+        }
+        Ok(repaired)
+    }
+
+    /// Apply pending schema migrations
+    fn run_migrations(conn: &Connection) -> Result<(), AppError> {
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to read schema version: {}", e)))?;
+
+        for (idx, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+            // Each migration and its version bump commit together
+            let tx = conn
+                .unchecked_transaction()
+                .map_err(|e| AppError::DatabaseError(format!("Failed to start migration: {}", e)))?;
+            tx.execute_batch(sql).map_err(|e| {
+                AppError::DatabaseError(format!("Failed to apply migration {}: {}", idx + 1, e))
+            })?;
+            tx.pragma_update(None, "user_version", idx + 1)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to update schema version: {}", e)))?;
+            tx.commit()
+                .map_err(|e| AppError::DatabaseError(format!("Failed to commit migration: {}", e)))?;
+        }
+
         Ok(())
     }
 
@@ -73,8 +530,8 @@ impl Database {
         let conn = self.conn.lock().unwrap();
 
         conn.execute(
-            "INSERT INTO batches (id, created_at, total_count, success_count, failed_count, download_directory)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO batches (id, created_at, total_count, success_count, failed_count, download_directory, status, parent_id, effective_config, name, source_file, source_path, source_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 batch.id,
                 batch.created_at,
@@ -82,6 +539,13 @@ impl Database {
                 batch.success_count,
                 batch.failed_count,
                 batch.download_directory,
+                batch.status,
+                batch.parent_id,
+                batch.effective_config,
+                batch.name,
+                batch.source_file,
+                batch.source_path,
+                batch.source_hash,
             ],
         )
         .map_err(|e| AppError::DatabaseError(format!("Failed to create batch: {}", e)))?;
@@ -107,28 +571,64 @@ impl Database {
         Ok(())
     }
 
-    /// Get all batches ordered by created_at desc
+    /// Update the number of invoices of a batch that grows while queued
+    pub fn update_batch_total(&self, batch_id: &str, total_count: u32) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE batches SET total_count = ?1 WHERE id = ?2",
+            params![total_count, batch_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update batch: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Update batch lifecycle status
+    pub fn update_batch_status(&self, batch_id: &str, status: BatchStatus) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE batches SET status = ?1 WHERE id = ?2",
+            params![status, batch_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update batch: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Recalculate batch counts from its invoice rows, including those of its sub-batches
+    pub fn refresh_batch_counts(&self, batch_id: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            &format!(
+                "UPDATE batches SET
+                    success_count = (SELECT COUNT(*) FROM invoices WHERE batch_id IN ({tree}) AND status = ?2),
+                    failed_count = (SELECT COUNT(*) FROM invoices WHERE batch_id IN ({tree}) AND status IN (?3, ?4))
+                 WHERE id = ?1",
+                tree = BATCH_TREE
+            ),
+            params![batch_id, InvoiceStatus::Success, InvoiceStatus::Failed, InvoiceStatus::NotFound],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update batch: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Get all top-level batches ordered by created_at desc
     pub fn get_batches(&self) -> Result<Vec<DownloadBatch>, AppError> {
         let conn = self.conn.lock().unwrap();
 
         let mut stmt = conn
-            .prepare(
-                "SELECT id, created_at, total_count, success_count, failed_count, download_directory
-                 FROM batches ORDER BY created_at DESC",
-            )
+            .prepare(&format!(
+                "SELECT {} FROM batches WHERE parent_id IS NULL ORDER BY created_at DESC",
+                BATCH_COLUMNS
+            ))
             .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
 
         let batches = stmt
-            .query_map([], |row| {
-                Ok(DownloadBatch {
-                    id: row.get(0)?,
-                    created_at: row.get(1)?,
-                    total_count: row.get(2)?,
-                    success_count: row.get(3)?,
-                    failed_count: row.get(4)?,
-                    download_directory: row.get(5)?,
-                })
-            })
+            .query_map([], batch_from_row)
             .map_err(|e| AppError::DatabaseError(format!("Failed to query batches: {}", e)))?
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| AppError::DatabaseError(format!("Failed to collect batches: {}", e)))?;
@@ -141,215 +641,1974 @@ impl Database {
         let conn = self.conn.lock().unwrap();
 
         let mut stmt = conn
-            .prepare(
-                "SELECT id, created_at, total_count, success_count, failed_count, download_directory
-                 FROM batches WHERE id = ?1",
-            )
+            .prepare(&format!("SELECT {} FROM batches WHERE id = ?1", BATCH_COLUMNS))
             .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
 
         let batch = stmt
-            .query_row([batch_id], |row| {
-                Ok(DownloadBatch {
-                    id: row.get(0)?,
-                    created_at: row.get(1)?,
-                    total_count: row.get(2)?,
-                    success_count: row.get(3)?,
-                    failed_count: row.get(4)?,
-                    download_directory: row.get(5)?,
-                })
-            })
+            .query_row([batch_id], batch_from_row)
             .optional()
             .map_err(|e| AppError::DatabaseError(format!("Failed to query batch: {}", e)))?;
 
         Ok(batch)
     }
 
-    /// Delete a batch and all its invoices
+    /// Delete a batch; its sub-batches, invoices, their XML and metadata and
+    /// its queue entry go with it by cascade
     pub fn delete_batch(&self, batch_id: &str) -> Result<(), AppError> {
         let conn = self.conn.lock().unwrap();
 
-        conn.execute("DELETE FROM invoices WHERE batch_id = ?1", [batch_id])
-            .map_err(|e| AppError::DatabaseError(format!("Failed to delete invoices: {}", e)))?;
-
         conn.execute("DELETE FROM batches WHERE id = ?1", [batch_id])
             .map_err(|e| AppError::DatabaseError(format!("Failed to delete batch: {}", e)))?;
 
         Ok(())
     }
 
-    /// Create an invoice record
-    pub fn create_invoice(&self, invoice: &HistoryInvoice) -> Result<(), AppError> {
+    /// Store the serialized download request of a queued batch
+    pub fn queue_download(&self, batch_id: &str, request: &str) -> Result<(), AppError> {
         let conn = self.conn.lock().unwrap();
 
         conn.execute(
-            "INSERT INTO invoices (id, batch_id, code, status, error, file_path, downloaded_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                invoice.id,
-                invoice.batch_id,
-                invoice.code,
-                invoice.status,
-                invoice.error,
-                invoice.file_path,
-                invoice.downloaded_at,
-            ],
+            "INSERT OR REPLACE INTO queued_downloads (batch_id, request, queued_at) VALUES (?1, ?2, ?3)",
+            params![batch_id, request, chrono::Utc::now().to_rfc3339()],
         )
-        .map_err(|e| AppError::DatabaseError(format!("Failed to create invoice: {}", e)))?;
+        .map_err(|e| AppError::DatabaseError(format!("Failed to queue download: {}", e)))?;
 
         Ok(())
     }
 
-    /// Update invoice status
-    pub fn update_invoice_status(
-        &self,
-        invoice_id: &str,
-        status: &str,
-        error: Option<&str>,
-        file_path: Option<&str>,
-    ) -> Result<(), AppError> {
+    /// Queued download request of a batch; it stays queued until
+    /// `remove_queued_download`
+    pub fn get_queued_download(&self, batch_id: &str) -> Result<Option<String>, AppError> {
         let conn = self.conn.lock().unwrap();
 
-        let downloaded_at = if status == "success" || status == "failed" {
-            Some(chrono::Utc::now().to_rfc3339())
-        } else {
-            None
-        };
-
-        conn.execute(
-            "UPDATE invoices SET status = ?1, error = ?2, file_path = ?3, downloaded_at = ?4 WHERE id = ?5",
-            params![status, error, file_path, downloaded_at, invoice_id],
+        conn.query_row(
+            "SELECT request FROM queued_downloads WHERE batch_id = ?1",
+            [batch_id],
+            |row| row.get(0),
         )
-        .map_err(|e| AppError::DatabaseError(format!("Failed to update invoice: {}", e)))?;
+        .optional()
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query queued download: {}", e)))
+    }
+
+    /// Take a batch off the download queue, once it was started
+    pub fn remove_queued_download(&self, batch_id: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute("DELETE FROM queued_downloads WHERE batch_id = ?1", [batch_id])
+            .map_err(|e| AppError::DatabaseError(format!("Failed to delete queued download: {}", e)))?;
 
         Ok(())
     }
 
-    /// Get invoices for a batch
-    pub fn get_batch_invoices(&self, batch_id: &str) -> Result<Vec<HistoryInvoice>, AppError> {
+    /// Completed top-level batches imported from a file with this SHA-256,
+    /// newest first
+    pub fn get_completed_batches_by_source(&self, source_hash: &str) -> Result<Vec<DownloadBatch>, AppError> {
         let conn = self.conn.lock().unwrap();
 
         let mut stmt = conn
-            .prepare(
-                "SELECT id, batch_id, code, status, error, file_path, downloaded_at
-                 FROM invoices WHERE batch_id = ?1 ORDER BY id",
-            )
+            .prepare(&format!(
+                "SELECT {} FROM batches
+                 WHERE source_hash = ?1 AND status = ?2 AND parent_id IS NULL
+                 ORDER BY created_at DESC",
+                BATCH_COLUMNS
+            ))
             .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
 
-        let invoices = stmt
-            .query_map([batch_id], |row| {
-                Ok(HistoryInvoice {
-                    id: row.get(0)?,
-                    batch_id: row.get(1)?,
-                    code: row.get(2)?,
-                    status: row.get(3)?,
-                    error: row.get(4)?,
-                    file_path: row.get(5)?,
-                    downloaded_at: row.get(6)?,
-                })
-            })
-            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+        let batches = stmt
+            .query_map(params![source_hash, BatchStatus::Completed], batch_from_row)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query batches: {}", e)))?
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect batches: {}", e)))?;
 
-        Ok(invoices)
+        Ok(batches)
     }
 
-    /// Get failed invoices for a batch (for re-download)
-    pub fn get_failed_invoices(&self, batch_id: &str) -> Result<Vec<HistoryInvoice>, AppError> {
+    /// Portal profiles saved by the user, without the built-in ones
+    pub fn get_portal_profiles(&self) -> Result<Vec<PortalProfile>, AppError> {
         let conn = self.conn.lock().unwrap();
 
         let mut stmt = conn
             .prepare(
-                "SELECT id, batch_id, code, status, error, file_path, downloaded_at
-                 FROM invoices WHERE batch_id = ?1 AND status = 'failed' ORDER BY id",
+                "SELECT host, base_delay_secs, max_concurrency, max_retries
+                 FROM portal_profiles ORDER BY host",
             )
             .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
 
-        let invoices = stmt
-            .query_map([batch_id], |row| {
-                Ok(HistoryInvoice {
-                    id: row.get(0)?,
-                    batch_id: row.get(1)?,
-                    code: row.get(2)?,
-                    status: row.get(3)?,
-                    error: row.get(4)?,
-                    file_path: row.get(5)?,
-                    downloaded_at: row.get(6)?,
+        let profiles = stmt
+            .query_map([], |row| {
+                Ok(PortalProfile {
+                    host: row.get(0)?,
+                    base_delay_secs: row.get(1)?,
+                    max_concurrency: row.get(2)?,
+                    max_retries: row.get(3)?,
+                    builtin: false,
                 })
             })
-            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query portal profiles: {}", e)))?
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect portal profiles: {}", e)))?;
 
-        Ok(invoices)
+        Ok(profiles)
     }
 
-    /// Get application settings
-    pub fn get_settings(&self) -> Result<Settings, AppError> {
+    /// Insert or replace the profile of a host
+    pub fn save_portal_profile(&self, profile: &PortalProfile) -> Result<(), AppError> {
         let conn = self.conn.lock().unwrap();
 
-        let get_setting = |key: &str| -> Result<String, AppError> {
-            let mut stmt = conn
-                .prepare("SELECT value FROM settings WHERE key = ?1")
-                .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO portal_profiles (host, base_delay_secs, max_concurrency, max_retries)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                profile.host,
+                profile.base_delay_secs,
+                profile.max_concurrency,
+                profile.max_retries
+            ],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save portal profile: {}", e)))?;
 
-            let value: Option<String> = stmt
-                .query_row([key], |row| row.get(0))
-                .optional()
-                .map_err(|e| AppError::DatabaseError(format!("Failed to query setting: {}", e)))?;
+        Ok(())
+    }
 
-            Ok(value.unwrap_or_default())
-        };
+    /// Forget the saved profile of a host; a built-in one applies again
+    pub fn delete_portal_profile(&self, host: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
 
-        let download_directory = get_setting("download_directory")?;
-        let download_directory = if download_directory.is_empty() {
-            Self::get_default_download_directory()
-        } else {
-            download_directory
-        };
+        conn.execute("DELETE FROM portal_profiles WHERE host = ?1", [host])
+            .map_err(|e| AppError::DatabaseError(format!("Failed to delete portal profile: {}", e)))?;
 
-        Ok(Settings {
-            openai_api_key: get_setting("openai_api_key")?,
-            vnpt_url: get_setting("vnpt_url")?,
-            download_directory,
-        })
+        Ok(())
     }
 
-    /// Get platform-specific default download directory
-    fn get_default_download_directory() -> String {
-        #[cfg(target_os = "windows")]
-        {
-            // Windows: use D:\ if exists, otherwise Documents
-            if std::path::Path::new("D:\\").exists() {
-                return "D:\\".to_string();
-            }
+    /// Replace every saved portal profile, e.g. with imported ones
+    pub fn replace_portal_profiles(&self, profiles: &[PortalProfile]) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save portal profiles: {}", e)))?;
+
+        tx.execute("DELETE FROM portal_profiles", [])
+            .map_err(|e| AppError::DatabaseError(format!("Failed to delete portal profiles: {}", e)))?;
+        for profile in profiles {
+            tx.execute(
+                "INSERT OR REPLACE INTO portal_profiles (host, base_delay_secs, max_concurrency, max_retries)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    profile.host,
+                    profile.base_delay_secs,
+                    profile.max_concurrency,
+                    profile.max_retries
+                ],
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save portal profile: {}", e)))?;
         }
 
-        // macOS and fallback: use Documents directory
-        dirs::document_dir()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_default()
+        tx.commit()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save portal profiles: {}", e)))
     }
 
-    /// Save application settings
-    pub fn save_settings(&self, settings: &Settings) -> Result<(), AppError> {
+    /// Ids of queued batches, oldest first
+    pub fn get_queued_batch_ids(&self) -> Result<Vec<String>, AppError> {
         let conn = self.conn.lock().unwrap();
 
-        let save_setting = |key: &str, value: &str| -> Result<(), rusqlite::Error> {
+        let mut stmt = conn
+            .prepare("SELECT batch_id FROM queued_downloads ORDER BY queued_at")
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let batch_ids = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query queued downloads: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect queued downloads: {}", e)))?;
+
+        Ok(batch_ids)
+    }
+
+    /// Store the stage timings of one invoice
+    pub fn record_stage_timings(
+        &self,
+        batch_id: &str,
+        invoice_id: &str,
+        timings: &[(TimingStage, u64)],
+    ) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+        let recorded_at = chrono::Utc::now().to_rfc3339();
+
+        for (stage, duration_ms) in timings {
             conn.execute(
-                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-                params![key, value],
-            )?;
-            Ok(())
-        };
+                "INSERT INTO stage_timings (batch_id, invoice_id, stage, duration_ms, recorded_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![batch_id, invoice_id, stage, duration_ms, recorded_at],
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to record stage timing: {}", e)))?;
+        }
 
-        save_setting("openai_api_key", &settings.openai_api_key)
-            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
-        save_setting("vnpt_url", &settings.vnpt_url)
-            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
-        save_setting("download_directory", &settings.download_directory)
-            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        Ok(())
+    }
+
+    /// Most recent stage timings, optionally only those recorded since `since` (RFC 3339)
+    pub fn get_stage_timings(&self, since: Option<&str>) -> Result<Vec<(TimingStage, u64)>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT stage, duration_ms FROM stage_timings
+                 WHERE ?1 IS NULL OR recorded_at >= ?1
+                 ORDER BY id DESC LIMIT ?2",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let timings = stmt
+            .query_map(params![since, METRICS_SAMPLE_LIMIT], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query stage timings: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect stage timings: {}", e)))?;
+
+        Ok(timings)
+    }
+
+    /// Delete all stored stage timings
+    pub fn clear_stage_timings(&self) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute("DELETE FROM stage_timings", [])
+            .map_err(|e| AppError::DatabaseError(format!("Failed to clear stage timings: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Append an entry to the audit log on behalf of the OS user running the app
+    pub fn record_audit(
+        &self,
+        action: AuditAction,
+        batch_id: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO audit_log (timestamp, user, action, batch_id, details) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![chrono::Utc::now().to_rfc3339(), current_user(), action, batch_id, details],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to record audit entry: {}", e)))?;
 
         Ok(())
     }
+
+    /// Audit entries matching `filter`, newest first
+    pub fn get_audit_log(&self, filter: &AuditLogFilter) -> Result<Vec<AuditEntry>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, timestamp, user, action, batch_id, details FROM audit_log
+                 WHERE (?1 IS NULL OR action = ?1)
+                   AND (?2 IS NULL OR batch_id = ?2)
+                   AND (?3 IS NULL OR user = ?3)
+                   AND (?4 IS NULL OR timestamp >= ?4)
+                   AND (?5 IS NULL OR timestamp < ?5)
+                 ORDER BY id DESC
+                 LIMIT ?6",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let entries = stmt
+            .query_map(
+                params![
+                    filter.action,
+                    filter.batch_id,
+                    filter.user,
+                    filter.from,
+                    filter.to,
+                    filter.limit.unwrap_or(DEFAULT_AUDIT_LIMIT),
+                ],
+                |row| {
+                    Ok(AuditEntry {
+                        id: row.get(0)?,
+                        timestamp: row.get(1)?,
+                        user: row.get(2)?,
+                        action: row.get(3)?,
+                        batch_id: row.get(4)?,
+                        details: row.get(5)?,
+                    })
+                },
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query audit log: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect audit log: {}", e)))?;
+
+        Ok(entries)
+    }
+
+    /// Create an invoice record
+    pub fn create_invoice(&self, invoice: &HistoryInvoice) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO invoices (id, batch_id, code, status, error, file_path, downloaded_at, repro_path, portal, remote_url)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                invoice.id,
+                invoice.batch_id,
+                invoice.code,
+                invoice.status,
+                invoice.error,
+                invoice.file_path,
+                invoice.downloaded_at,
+                invoice.repro_path,
+                invoice.portal,
+                invoice.remote_url,
+            ],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to create invoice: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Apply invoice changes queued by a `StatusWriter` in one transaction
+    pub fn apply_invoice_writes(&self, writes: &[InvoiceWrite]) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update invoices: {}", e)))?;
+
+        for write in writes {
+            match write {
+                InvoiceWrite::Status {
+                    invoice_id,
+                    status,
+                    error,
+                    file_path,
+                } => {
+                    let downloaded_at = matches!(
+                        status,
+                        InvoiceStatus::Success | InvoiceStatus::Failed | InvoiceStatus::NotFound
+                    )
+                    .then(|| chrono::Utc::now().to_rfc3339());
+                    tx.execute(
+                        "UPDATE invoices SET status = ?1, error = ?2, file_path = ?3, downloaded_at = ?4 WHERE id = ?5",
+                        params![status, error, file_path, downloaded_at, invoice_id],
+                    )
+                }
+                InvoiceWrite::Portal { invoice_id, portal } => tx.execute(
+                    "UPDATE invoices SET portal = ?1 WHERE id = ?2",
+                    params![portal, invoice_id],
+                ),
+                InvoiceWrite::Metrics {
+                    invoice_id,
+                    duration_ms,
+                    captcha_attempts,
+                } => tx.execute(
+                    "UPDATE invoices SET duration_ms = ?1, captcha_attempts = ?2 WHERE id = ?3",
+                    params![*duration_ms as i64, captcha_attempts, invoice_id],
+                ),
+            }
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update invoice: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to update invoices: {}", e)))
+    }
+
+    /// Mark invoices that did not download as ignored, with the reason, and
+    /// recount their batches; returns how many were marked
+    pub fn ignore_invoices(&self, invoice_ids: &[String], note: &str) -> Result<usize, AppError> {
+        let mut batch_ids = Vec::new();
+        {
+            let conn = self.conn.lock().unwrap();
+
+            for invoice_id in invoice_ids {
+                let batch_id: Option<String> = conn
+                    .query_row(
+                        "UPDATE invoices SET status = ?1, note = ?2
+                         WHERE id = ?3 AND status IN (?4, ?5, ?6)
+                         RETURNING batch_id",
+                        params![
+                            InvoiceStatus::Ignored,
+                            note,
+                            invoice_id,
+                            InvoiceStatus::Failed,
+                            InvoiceStatus::NotFound,
+                            InvoiceStatus::NeedsManual
+                        ],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(|e| AppError::DatabaseError(format!("Failed to update invoice: {}", e)))?;
+                batch_ids.extend(batch_id);
+            }
+        }
+        let ignored = batch_ids.len();
+
+        batch_ids.sort();
+        batch_ids.dedup();
+        for batch_id in &batch_ids {
+            self.refresh_batch_counts(batch_id)?;
+            // The parent of a split batch counts its sub-batches' invoices
+            if let Some(parent_id) = self.get_batch(batch_id)?.and_then(|batch| batch.parent_id) {
+                self.refresh_batch_counts(&parent_id)?;
+            }
+        }
+
+        Ok(ignored)
+    }
+
+    /// Set or clear the user's note on an invoice
+    pub fn set_invoice_note(&self, invoice_id: &str, note: Option<&str>) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE invoices SET note = ?1 WHERE id = ?2",
+            params![note, invoice_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update invoice: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Attach a repro bundle path to an invoice
+    pub fn set_invoice_repro_path(&self, invoice_id: &str, repro_path: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE invoices SET repro_path = ?1 WHERE id = ?2",
+            params![repro_path, invoice_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update invoice: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Record where an invoice was uploaded
+    pub fn set_invoice_remote_url(&self, invoice_id: &str, remote_url: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE invoices SET remote_url = ?1 WHERE id = ?2",
+            params![remote_url, invoice_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to update invoice: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Store the parsed XML of an invoice, replacing an earlier parse
+    pub fn save_invoice_xml(
+        &self,
+        invoice_id: &str,
+        xml_path: &str,
+        invoice: &XmlInvoice,
+    ) -> Result<(), AppError> {
+        let data = serde_json::to_string(invoice)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to serialize invoice XML: {}", e)))?;
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO invoice_xml
+                (invoice_id, xml_path, seller_tax_code, issued_date, total_before_tax, total_tax, total_payable, valid, data, parsed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                invoice_id,
+                xml_path,
+                invoice.seller.tax_code,
+                invoice.issued_date,
+                invoice.total_before_tax,
+                invoice.total_tax,
+                invoice.total_payable,
+                invoice.is_valid(),
+                data,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save invoice XML: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Parsed XML of invoices issued between `from` and `to` (YYYY-MM-DD, inclusive)
+    pub fn get_invoice_xml_issued_between(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<XmlInvoice>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT data FROM invoice_xml
+                 WHERE substr(issued_date, 1, 10) BETWEEN ?1 AND ?2
+                 ORDER BY issued_date",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let rows = stmt
+            .query_map([from, to], |row| row.get::<_, String>(0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoice XML: {}", e)))?;
+
+        rows.map(|data| {
+            let data = data.map_err(|e| AppError::DatabaseError(format!("Failed to read row: {}", e)))?;
+            serde_json::from_str(&data)
+                .map_err(|e| AppError::DatabaseError(format!("Corrupt invoice XML data: {}", e)))
+        })
+        .collect()
+    }
+
+    /// Seller, number, date and total of every downloaded invoice, from its
+    /// XML or else its PDF, marking those of `batch_id` and its sub-batches
+    pub fn get_invoice_facts(&self, batch_id: &str) -> Result<Vec<InvoiceFacts>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT i.id, i.code, i.batch_id IN ({}),
+                        COALESCE(x.seller_tax_code, m.seller_tax_code),
+                        COALESCE(json_extract(x.data, '$.series'), m.series),
+                        COALESCE(json_extract(x.data, '$.number'), m.number),
+                        COALESCE(x.issued_date, m.issued_date),
+                        COALESCE(x.total_payable, m.total_payable)
+                 FROM invoices i
+                 LEFT JOIN invoice_xml x ON x.invoice_id = i.id
+                 LEFT JOIN invoice_metadata m ON m.invoice_id = i.id
+                 WHERE i.status = ?2",
+                BATCH_TREE
+            ))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let rows = stmt
+            .query_map(params![batch_id, InvoiceStatus::Success], |row| {
+                Ok(InvoiceFacts {
+                    invoice_id: row.get(0)?,
+                    invoice_code: row.get(1)?,
+                    in_batch: row.get(2)?,
+                    seller_tax_code: row.get(3)?,
+                    series: row.get(4)?,
+                    number: row.get(5)?,
+                    issued_date: row.get(6)?,
+                    total_payable: row.get(7)?,
+                })
+            })
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to read row: {}", e)))
+    }
+
+    /// Store the fields read from an invoice PDF, replacing earlier ones
+    pub fn save_invoice_metadata(
+        &self,
+        invoice_id: &str,
+        metadata: &InvoiceMetadata,
+    ) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO invoice_metadata
+                (invoice_id, source, series, number, issued_date, seller_tax_code, total_before_tax, total_tax, total_payable, extracted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                invoice_id,
+                metadata.source.as_str(),
+                metadata.series,
+                metadata.number,
+                metadata.issued_date,
+                metadata.seller_tax_code,
+                metadata.total_before_tax,
+                metadata.total_tax,
+                metadata.total_payable,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save invoice metadata: {}", e)))?;
+
+        Ok(())
+    }
+
+    pub fn get_invoice_metadata(&self, invoice_id: &str) -> Result<Option<InvoiceMetadata>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT source, series, number, issued_date, seller_tax_code, total_before_tax, total_tax, total_payable
+             FROM invoice_metadata WHERE invoice_id = ?1",
+            [invoice_id],
+            |row| {
+                Ok(InvoiceMetadata {
+                    source: MetadataSource::parse(&row.get::<_, String>(0)?),
+                    series: row.get(1)?,
+                    number: row.get(2)?,
+                    issued_date: row.get(3)?,
+                    seller_tax_code: row.get(4)?,
+                    total_before_tax: row.get(5)?,
+                    total_tax: row.get(6)?,
+                    total_payable: row.get(7)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query invoice metadata: {}", e)))
+    }
+
+    /// Record what optimizing an invoice PDF did, replacing an earlier result
+    pub fn save_pdf_optimization(
+        &self,
+        invoice_id: &str,
+        optimization: &PdfOptimization,
+    ) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO pdf_optimizations
+                (invoice_id, original_bytes, optimized_bytes, linearized, original_path, skipped, optimized_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                invoice_id,
+                optimization.original_bytes,
+                optimization.optimized_bytes,
+                optimization.linearized,
+                optimization.original_path,
+                optimization.skipped,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save PDF optimization: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Add to the AI spend of `month` (`YYYY-MM`), returning the new total
+    /// Add the cost of one call made with the key `key_hint`
+    ///
+    /// # Returns
+    /// The spend of the month across all keys
+    pub fn add_ai_spend(&self, month: &str, key_hint: &str, cost_usd: f64) -> Result<f64, AppError> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save AI spend: {}", e)))?;
+
+        tx.execute(
+            "INSERT INTO ai_key_usage (month, key_hint, calls, cost_usd) VALUES (?1, ?2, 1, ?3)
+             ON CONFLICT(month, key_hint) DO UPDATE SET
+                calls = calls + 1,
+                cost_usd = cost_usd + excluded.cost_usd",
+            params![month, key_hint, cost_usd],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save AI key usage: {}", e)))?;
+        let total = tx
+            .query_row(
+                "INSERT INTO ai_spend (month, cost_usd) VALUES (?1, ?2)
+                 ON CONFLICT(month) DO UPDATE SET cost_usd = cost_usd + excluded.cost_usd
+                 RETURNING cost_usd",
+                params![month, cost_usd],
+                |row| row.get(0),
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save AI spend: {}", e)))?;
+        tx.commit()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save AI spend: {}", e)))?;
+        Ok(total)
+    }
+
+    /// Calls and spend of each API key in `month`, most expensive first
+    pub fn get_ai_key_usage(&self, month: &str) -> Result<Vec<AiKeyUsage>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT key_hint, calls, cost_usd FROM ai_key_usage
+                 WHERE month = ?1 ORDER BY cost_usd DESC",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query AI key usage: {}", e)))?;
+        let rows = stmt
+            .query_map([month], |row| {
+                Ok(AiKeyUsage {
+                    key_hint: row.get(0)?,
+                    calls: row.get(1)?,
+                    cost_usd: row.get(2)?,
+                })
+            })
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query AI key usage: {}", e)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to read AI key usage: {}", e)))
+    }
+
+    pub fn get_ai_spend(&self, month: &str) -> Result<f64, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row("SELECT cost_usd FROM ai_spend WHERE month = ?1", [month], |row| row.get(0))
+            .optional()
+            .map(|spend| spend.unwrap_or(0.0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query AI spend: {}", e)))
+    }
+
+    pub fn get_pdf_optimization(&self, invoice_id: &str) -> Result<Option<PdfOptimization>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT original_bytes, optimized_bytes, linearized, original_path, skipped
+             FROM pdf_optimizations WHERE invoice_id = ?1",
+            [invoice_id],
+            |row| {
+                Ok(PdfOptimization {
+                    original_bytes: row.get(0)?,
+                    optimized_bytes: row.get(1)?,
+                    linearized: row.get(2)?,
+                    original_path: row.get(3)?,
+                    skipped: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query PDF optimization: {}", e)))
+    }
+
+    /// Store the signature checks of an invoice whose XML was saved before
+    pub fn set_invoice_signatures(
+        &self,
+        invoice_id: &str,
+        status: SignatureStatus,
+        checks: &[SignatureCheck],
+    ) -> Result<(), AppError> {
+        let signatures = serde_json::to_string(checks)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to serialize signatures: {}", e)))?;
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE invoice_xml SET signature_status = ?1, signatures = ?2 WHERE invoice_id = ?3",
+            params![status, signatures, invoice_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save signatures: {}", e)))?;
+
+        Ok(())
+    }
+
+    pub fn get_invoice_signatures(&self, invoice_id: &str) -> Result<Vec<SignatureCheck>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let signatures: Option<String> = conn
+            .query_row(
+                "SELECT signatures FROM invoice_xml WHERE invoice_id = ?1",
+                [invoice_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query signatures: {}", e)))?
+            .flatten();
+
+        match signatures {
+            Some(signatures) => serde_json::from_str(&signatures)
+                .map_err(|e| AppError::DatabaseError(format!("Corrupt signature data: {}", e))),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn get_invoice_xml(&self, invoice_id: &str) -> Result<Option<XmlInvoice>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let data: Option<String> = conn
+            .query_row(
+                "SELECT data FROM invoice_xml WHERE invoice_id = ?1",
+                [invoice_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoice XML: {}", e)))?;
+
+        data.map(|data| {
+            serde_json::from_str(&data)
+                .map_err(|e| AppError::DatabaseError(format!("Corrupt invoice XML data: {}", e)))
+        })
+        .transpose()
+    }
+
+    /// Averages over the most recent finished invoices that have timings
+    ///
+    /// The unary `+` keeps SQLite off the status index, which would match
+    /// most rows and sort them all, so it walks `downloaded_at` backwards
+    /// and stops at the limit
+    pub fn get_invoice_stats(&self) -> Result<InvoiceStats, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(status = ?1), 0),
+                    COALESCE(AVG(duration_ms), 0),
+                    COALESCE(AVG(captcha_attempts), 0),
+                    COALESCE(SUM(captcha_attempts), 0)
+             FROM (
+                SELECT status, duration_ms, captcha_attempts FROM invoices
+                WHERE duration_ms IS NOT NULL AND +status IN (?1, ?2)
+                ORDER BY downloaded_at DESC
+                LIMIT ?3
+             )",
+            params![InvoiceStatus::Success, InvoiceStatus::Failed, STATS_SAMPLE_LIMIT],
+            |row| {
+                Ok(InvoiceStats {
+                    sample_size: row.get(0)?,
+                    success_count: row.get(1)?,
+                    avg_duration_ms: row.get(2)?,
+                    avg_captcha_attempts: row.get(3)?,
+                    total_captcha_attempts: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query invoice stats: {}", e)))
+    }
+
+    /// Get a specific invoice by ID
+    pub fn get_invoice(&self, invoice_id: &str) -> Result<Option<HistoryInvoice>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM invoices WHERE id = ?1", INVOICE_COLUMNS))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let invoice = stmt
+            .query_row([invoice_id], invoice_from_row)
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoice: {}", e)))?;
+
+        Ok(invoice)
+    }
+
+    /// Get invoices for a batch and its sub-batches
+    pub fn get_batch_invoices(&self, batch_id: &str) -> Result<Vec<HistoryInvoice>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM invoices WHERE batch_id IN ({}) ORDER BY id",
+                INVOICE_COLUMNS, BATCH_TREE
+            ))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let invoices = stmt
+            .query_map([batch_id], invoice_from_row)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+
+        Ok(invoices)
+    }
+
+    /// Get failed invoices for a batch and its sub-batches (for re-download)
+    pub fn get_failed_invoices(&self, batch_id: &str) -> Result<Vec<HistoryInvoice>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM invoices WHERE batch_id IN ({}) AND status = ?2 ORDER BY id",
+                INVOICE_COLUMNS, BATCH_TREE
+            ))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let invoices = stmt
+            .query_map(params![batch_id, InvoiceStatus::Failed], invoice_from_row)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+
+        Ok(invoices)
+    }
+
+    /// Files of earlier downloads of the invoice with this code
+    pub fn get_downloaded_files(&self, code: &str) -> Result<Vec<String>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT DISTINCT file_path FROM invoices
+                 WHERE code = ?1 AND status = ?2 AND file_path IS NOT NULL",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let files = stmt
+            .query_map(params![code, InvoiceStatus::Success], |row| row.get(0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+
+        Ok(files)
+    }
+
+    /// Every file recorded on an invoice, the kept original of an optimized
+    /// PDF included, with its top-level batch, month and seller
+    pub fn get_recorded_files(&self) -> Result<Vec<RecordedFile>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT CASE WHEN kept.original = 1 THEN o.original_path ELSE i.file_path END,
+                        COALESCE(b.parent_id, b.id), COALESCE(p.name, b.name, p.id, b.id),
+                        i.downloaded_at,
+                        COALESCE(json_extract(x.data, '$.seller.name'), x.seller_tax_code, m.seller_tax_code)
+                 FROM invoices i
+                 JOIN batches b ON b.id = i.batch_id
+                 LEFT JOIN batches p ON p.id = b.parent_id
+                 LEFT JOIN invoice_xml x ON x.invoice_id = i.id
+                 LEFT JOIN invoice_metadata m ON m.invoice_id = i.id
+                 LEFT JOIN pdf_optimizations o ON o.invoice_id = i.id
+                 CROSS JOIN (SELECT 0 AS original UNION ALL SELECT 1) kept
+                 WHERE i.file_path IS NOT NULL AND (kept.original = 0 OR o.original_path IS NOT NULL)",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let files = stmt
+            .query_map([], |row| {
+                Ok(RecordedFile {
+                    file_path: row.get(0)?,
+                    batch_id: row.get(1)?,
+                    batch_name: row.get(2)?,
+                    downloaded_at: row.get(3)?,
+                    seller: row.get(4)?,
+                })
+            })
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+
+        Ok(files)
+    }
+
+    /// Get application settings
+    pub fn get_settings(&self) -> Result<Settings, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let get_setting = |key: &str| -> Result<String, AppError> {
+            let mut stmt = conn
+                .prepare("SELECT value FROM settings WHERE key = ?1")
+                .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+            let value: Option<String> = stmt
+                .query_row([key], |row| row.get(0))
+                .optional()
+                .map_err(|e| AppError::DatabaseError(format!("Failed to query setting: {}", e)))?;
+
+            Ok(value.unwrap_or_default())
+        };
+
+        let download_directory = get_setting("download_directory")?;
+        let download_directory = if download_directory.is_empty() {
+            Self::get_default_download_directory()
+        } else {
+            download_directory
+        };
+
+        let invoice_delay_secs = get_setting("invoice_delay_secs")?
+            .parse()
+            .unwrap_or(DEFAULT_INVOICE_DELAY_SECS);
+
+        let default_viewport = BrowserViewport::default();
+        let browser_window_width = get_setting("browser_window_width")?
+            .parse()
+            .unwrap_or(default_viewport.width);
+        let browser_window_height = get_setting("browser_window_height")?
+            .parse()
+            .unwrap_or(default_viewport.height);
+        let browser_device_scale_factor = get_setting("browser_device_scale_factor")?
+            .parse()
+            .unwrap_or(default_viewport.device_scale_factor);
+        let browser_zoom_percent = get_setting("browser_zoom_percent")?
+            .parse()
+            .unwrap_or(default_viewport.zoom_percent);
+
+        let captcha_padding_px = get_setting("captcha_padding_px")?
+            .parse()
+            .unwrap_or(DEFAULT_CAPTCHA_PADDING_PX);
+
+        let not_found_verify_delay_secs = get_setting("not_found_verify_delay_secs")?
+            .parse()
+            .unwrap_or(DEFAULT_NOT_FOUND_VERIFY_DELAY_SECS);
+
+        let retry_not_found_hours = get_setting("retry_not_found_hours")?
+            .parse()
+            .unwrap_or(DEFAULT_RETRY_NOT_FOUND_HOURS);
+
+        let local_captcha_min_confidence = get_setting("local_captcha_min_confidence")?
+            .parse()
+            .unwrap_or(DEFAULT_MIN_CONFIDENCE);
+
+        let ai_budget_per_batch_usd = get_setting("ai_budget_per_batch_usd")?.parse().unwrap_or(0.0);
+        let ai_budget_per_month_usd = get_setting("ai_budget_per_month_usd")?.parse().unwrap_or(0.0);
+
+        let manual_captcha_timeout_secs = get_setting("manual_captcha_timeout_secs")?
+            .parse()
+            .unwrap_or(DEFAULT_MANUAL_CAPTCHA_TIMEOUT_SECS);
+
+        Ok(Settings {
+            openai_api_key: get_setting("openai_api_key")?,
+            openai_backup_api_keys: get_setting("openai_backup_api_keys")?
+                .lines()
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_string)
+                .collect(),
+            use_azure_openai: get_setting("use_azure_openai")? == "true",
+            azure_openai_endpoint: get_setting("azure_openai_endpoint")?,
+            azure_openai_deployment: get_setting("azure_openai_deployment")?,
+            azure_openai_api_version: match get_setting("azure_openai_api_version")? {
+                version if version.is_empty() => DEFAULT_AZURE_API_VERSION.to_string(),
+                version => version,
+            },
+            vnpt_url: get_setting("vnpt_url")?,
+            download_directory,
+            invoice_delay_secs,
+            quiet_hours_start: get_setting("quiet_hours_start")?,
+            quiet_hours_end: get_setting("quiet_hours_end")?,
+            fallback_to_tax_portal: get_setting("fallback_to_tax_portal")? == "true",
+            verify_not_found: get_setting("verify_not_found")? == "true",
+            not_found_verify_delay_secs,
+            retry_not_found_hours,
+            auto_start_queued: get_setting("auto_start_queued")? == "true",
+            collect_metrics: get_setting("collect_metrics")? == "true",
+            download_xml: get_setting("download_xml")? == "true",
+            pdf_variant: PdfVariant::parse(&get_setting("pdf_variant")?),
+            ocr_scanned_pdfs: get_setting("ocr_scanned_pdfs")? == "true",
+            tesseract_path: get_setting("tesseract_path")?,
+            optimize_pdfs: get_setting("optimize_pdfs")? == "true",
+            keep_original_pdfs: get_setting("keep_original_pdfs")? == "true",
+            linearize_pdfs: get_setting("linearize_pdfs")? == "true",
+            qpdf_path: get_setting("qpdf_path")?,
+            stamp_pdfs: get_setting("stamp_pdfs")? == "true",
+            ai_budget_per_batch_usd,
+            ai_budget_per_month_usd,
+            ai_budget_action: BudgetAction::parse(&get_setting("ai_budget_action")?),
+            captcha_lookahead: get_setting("captcha_lookahead")? == "true",
+            local_captcha_solver: get_setting("local_captcha_solver")? == "true",
+            local_captcha_min_confidence,
+            collect_captcha_dataset: get_setting("collect_captcha_dataset")? == "true",
+            captcha_dataset_dir: get_setting("captcha_dataset_dir")?,
+            persistent_browser_profiles: get_setting("persistent_browser_profiles")? == "true",
+            demo_mode: get_setting("demo_mode")? == "true",
+            duplicate_files: DuplicateFiles::parse(&get_setting("duplicate_files")?),
+            file_name_template: get_setting("file_name_template")?,
+            folder_template: get_setting("folder_template")?,
+            file_name_transliteration: Transliteration::parse(&get_setting("file_name_transliteration")?),
+            csv_delimiter: CsvDelimiter::parse(&get_setting("csv_delimiter")?),
+            csv_decimal_comma: get_setting("csv_decimal_comma")? == "true",
+            csv_bom: get_setting("csv_bom")? == "true",
+            csv_day_first_dates: get_setting("csv_day_first_dates")? == "true",
+            browser_window_width,
+            browser_window_height,
+            browser_device_scale_factor,
+            browser_zoom_percent,
+            captcha_padding_px,
+            manual_captcha_timeout_secs,
+            portal_login_url: get_setting("portal_login_url")?,
+            portal_invoice_list_url: get_setting("portal_invoice_list_url")?,
+            portal_username: get_setting("portal_username")?,
+            portal_password: get_setting("portal_password")?,
+            inbox_enabled: get_setting("inbox_enabled")? == "true",
+            imap_host: get_setting("imap_host")?,
+            imap_port: get_setting("imap_port")?.parse().unwrap_or(DEFAULT_IMAP_PORT),
+            imap_username: get_setting("imap_username")?,
+            imap_password: get_setting("imap_password")?,
+            imap_folder: Some(get_setting("imap_folder")?)
+                .filter(|folder| !folder.is_empty())
+                .unwrap_or_else(|| DEFAULT_IMAP_FOLDER.to_string()),
+            post_download_hook: get_setting("post_download_hook")?,
+            post_download_hook_scope: HookScope::parse(&get_setting("post_download_hook_scope")?),
+            upload_to_s3: get_setting("upload_to_s3")? == "true",
+            s3_endpoint: get_setting("s3_endpoint")?,
+            s3_region: get_setting("s3_region")?,
+            s3_bucket: get_setting("s3_bucket")?,
+            s3_access_key: get_setting("s3_access_key")?,
+            s3_secret_key: get_setting("s3_secret_key")?,
+            s3_prefix: get_setting("s3_prefix")?,
+            upload_to_webdav: get_setting("upload_to_webdav")? == "true",
+            webdav_url: get_setting("webdav_url")?,
+            webdav_username: get_setting("webdav_username")?,
+            webdav_password: get_setting("webdav_password")?,
+            upload_to_sftp: get_setting("upload_to_sftp")? == "true",
+            sftp_host: get_setting("sftp_host")?,
+            sftp_port: get_setting("sftp_port")?.parse().unwrap_or(DEFAULT_SFTP_PORT),
+            sftp_username: get_setting("sftp_username")?,
+            sftp_password: get_setting("sftp_password")?,
+            sftp_private_key_path: get_setting("sftp_private_key_path")?,
+            sftp_remote_dir: get_setting("sftp_remote_dir")?,
+            sftp_mode: SftpMode::parse(&get_setting("sftp_mode")?),
+            sftp_host_key_fingerprint: get_setting("sftp_host_key_fingerprint")?,
+            cloud_provider: CloudProvider::parse(&get_setting("cloud_provider")?),
+            cloud_client_id: get_setting("cloud_client_id")?,
+            cloud_client_secret: get_setting("cloud_client_secret")?,
+            cloud_folder: get_setting("cloud_folder")?,
+        })
+    }
+
+    /// Get platform-specific default download directory
+    fn get_default_download_directory() -> String {
+        #[cfg(target_os = "windows")]
+        {
+            // Windows: use D:\ if exists, otherwise Documents
+            if std::path::Path::new("D:\\").exists() {
+                return "D:\\".to_string();
+            }
+        }
+
+        // macOS and fallback: use Documents directory
+        dirs::document_dir()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Argon2 hash of the app passcode, if one is set
+    pub fn get_passcode_hash(&self) -> Result<Option<String>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT value FROM settings WHERE key = 'passcode_hash'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query passcode: {}", e)))
+    }
+
+    /// Store the app passcode hash, or remove it with `None`
+    pub fn set_passcode_hash(&self, hash: Option<&str>) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        match hash {
+            Some(hash) => conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES ('passcode_hash', ?1)",
+                [hash],
+            ),
+            None => conn.execute("DELETE FROM settings WHERE key = 'passcode_hash'", []),
+        }
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save passcode: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// OAuth refresh token of the connected cloud drive
+    pub fn get_cloud_refresh_token(&self) -> Result<Option<String>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT value FROM settings WHERE key = 'cloud_refresh_token'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query cloud sign-in: {}", e)))
+    }
+
+    /// Store the cloud drive refresh token, or sign out with `None`
+    pub fn set_cloud_refresh_token(&self, token: Option<&str>) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        match token {
+            Some(token) => conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES ('cloud_refresh_token', ?1)",
+                [token],
+            ),
+            None => conn.execute("DELETE FROM settings WHERE key = 'cloud_refresh_token'", []),
+        }
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save cloud sign-in: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Where the last inbox poll stopped, `None` before the first poll
+    pub fn get_inbox_cursor(&self) -> Result<Option<InboxCursor>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let value: Option<String> = conn
+            .query_row("SELECT value FROM settings WHERE key = 'inbox_cursor'", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query inbox cursor: {}", e)))?;
+
+        Ok(value.and_then(|value| {
+            let (uid_validity, last_uid) = value.split_once(':')?;
+            Some(InboxCursor {
+                uid_validity: uid_validity.parse().ok()?,
+                last_uid: last_uid.parse().ok()?,
+            })
+        }))
+    }
+
+    pub fn set_inbox_cursor(&self, cursor: InboxCursor) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('inbox_cursor', ?1)",
+            [format!("{}:{}", cursor.uid_validity, cursor.last_uid)],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save inbox cursor: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Batch the inbox poller last queued invoices into
+    pub fn get_inbox_batch_id(&self) -> Result<Option<String>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row("SELECT value FROM settings WHERE key = 'inbox_batch_id'", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query inbox batch: {}", e)))
+    }
+
+    pub fn set_inbox_batch_id(&self, batch_id: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('inbox_batch_id', ?1)",
+            [batch_id],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save inbox batch: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Whether an invoice with this lookup code was already downloaded
+    pub fn is_code_downloaded(&self, code: &str) -> Result<bool, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT 1 FROM invoices WHERE code = ?1 AND status = ?2 LIMIT 1",
+            params![code, InvoiceStatus::Success],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|found| found.is_some())
+        .map_err(|e| AppError::DatabaseError(format!("Failed to query invoice: {}", e)))
+    }
+
+    /// Not-found invoices waiting to be looked up again: the latest lookup of
+    /// each code that was never downloaded, with how often it was not found
+    pub fn get_retry_later_invoices(&self) -> Result<Vec<RetryLaterInvoice>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT i.id, i.batch_id, i.code, i.error, i.downloaded_at,
+                    (SELECT COUNT(*) FROM invoices j WHERE j.code = i.code AND j.status = ?1),
+                    json_extract(b.effective_config, '$.vnpt_url')
+                 FROM invoices i JOIN batches b ON b.id = i.batch_id
+                 WHERE i.status = ?1 AND i.downloaded_at IS NOT NULL
+                    AND NOT EXISTS (SELECT 1 FROM invoices j WHERE j.code = i.code AND j.rowid > i.rowid)
+                    AND NOT EXISTS (SELECT 1 FROM invoices j WHERE j.code = i.code AND j.status = ?2)
+                 ORDER BY i.downloaded_at",
+            )
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let invoices = stmt
+            .query_map(params![InvoiceStatus::NotFound, InvoiceStatus::Success], |row| {
+                Ok(RetryLaterInvoice {
+                    invoice_id: row.get(0)?,
+                    batch_id: row.get(1)?,
+                    code: row.get(2)?,
+                    error: row.get(3)?,
+                    not_found_at: row.get(4)?,
+                    attempts: row.get(5)?,
+                    portal_url: row.get(6)?,
+                })
+            })
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+
+        Ok(invoices)
+    }
+
+    /// Codes downloaded by a batch that an earlier batch recorded as not found
+    pub fn get_found_later_codes(&self, batch_id: &str) -> Result<Vec<String>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT DISTINCT i.code FROM invoices i
+                 WHERE i.batch_id IN ({tree}) AND i.status = ?2
+                    AND EXISTS (SELECT 1 FROM invoices j
+                        WHERE j.code = i.code AND j.status = ?3 AND j.batch_id NOT IN ({tree}))
+                 ORDER BY i.code",
+                tree = BATCH_TREE
+            ))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
+
+        let codes = stmt
+            .query_map(params![batch_id, InvoiceStatus::Success, InvoiceStatus::NotFound], |row| row.get(0))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query invoices: {}", e)))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to collect invoices: {}", e)))?;
+
+        Ok(codes)
+    }
+
+    /// Whether the app was left in read-only mode
+    pub fn get_read_only(&self) -> Result<bool, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let value: Option<String> = conn
+            .query_row("SELECT value FROM settings WHERE key = 'read_only'", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query read-only mode: {}", e)))?;
+
+        Ok(value.is_some_and(|v| v == "true"))
+    }
+
+    pub fn set_read_only(&self, read_only: bool) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('read_only', ?1)",
+            [read_only.to_string()],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save read-only mode: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Steps of the first-run wizard the user got through
+    pub fn get_onboarding_steps(&self) -> Result<Vec<OnboardingStep>, AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let value: Option<String> = conn
+            .query_row("SELECT value FROM settings WHERE key = 'onboarding_steps'", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("Failed to query onboarding steps: {}", e)))?;
+
+        Ok(value
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(OnboardingStep::parse)
+            .collect())
+    }
+
+    pub fn set_onboarding_steps(&self, steps: &[OnboardingStep]) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let value: Vec<&str> = steps.iter().map(OnboardingStep::as_str).collect();
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('onboarding_steps', ?1)",
+            [value.join(",")],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save onboarding steps: {}", e)))?;
+
+        Ok(())
+    }
+
+    pub fn set_download_directory(&self, download_directory: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('download_directory', ?1)",
+            [download_directory],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save download directory: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Pin the SFTP server's host key, as first seen when none was set
+    pub fn set_sftp_host_key_fingerprint(&self, fingerprint: &str) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('sftp_host_key_fingerprint', ?1)",
+            [fingerprint],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save SFTP host key: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Save application settings
+    pub fn save_settings(&self, settings: &Settings) -> Result<(), AppError> {
+        let conn = self.conn.lock().unwrap();
+
+        let save_setting = |key: &str, value: &str| -> Result<(), rusqlite::Error> {
+            conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+            Ok(())
+        };
+
+        save_setting("openai_api_key", &settings.openai_api_key)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        // One key per line; keys never contain whitespace
+        save_setting("openai_backup_api_keys", &settings.openai_backup_api_keys.join("\n"))
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("use_azure_openai", &settings.use_azure_openai.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("azure_openai_endpoint", &settings.azure_openai_endpoint)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("azure_openai_deployment", &settings.azure_openai_deployment)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("azure_openai_api_version", &settings.azure_openai_api_version)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("vnpt_url", &settings.vnpt_url)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("download_directory", &settings.download_directory)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("invoice_delay_secs", &settings.invoice_delay_secs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("quiet_hours_start", &settings.quiet_hours_start)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("quiet_hours_end", &settings.quiet_hours_end)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("fallback_to_tax_portal", &settings.fallback_to_tax_portal.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("verify_not_found", &settings.verify_not_found.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("not_found_verify_delay_secs", &settings.not_found_verify_delay_secs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("retry_not_found_hours", &settings.retry_not_found_hours.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("auto_start_queued", &settings.auto_start_queued.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("collect_metrics", &settings.collect_metrics.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("download_xml", &settings.download_xml.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("pdf_variant", settings.pdf_variant.as_str())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("ocr_scanned_pdfs", &settings.ocr_scanned_pdfs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("tesseract_path", settings.tesseract_path.trim())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("optimize_pdfs", &settings.optimize_pdfs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("keep_original_pdfs", &settings.keep_original_pdfs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("linearize_pdfs", &settings.linearize_pdfs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("qpdf_path", settings.qpdf_path.trim())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("stamp_pdfs", &settings.stamp_pdfs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("ai_budget_per_batch_usd", &settings.ai_budget_per_batch_usd.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("ai_budget_per_month_usd", &settings.ai_budget_per_month_usd.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("ai_budget_action", settings.ai_budget_action.as_str())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("captcha_lookahead", &settings.captcha_lookahead.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("local_captcha_solver", &settings.local_captcha_solver.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("local_captcha_min_confidence", &settings.local_captcha_min_confidence.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("collect_captcha_dataset", &settings.collect_captcha_dataset.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("captcha_dataset_dir", &settings.captcha_dataset_dir)
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("persistent_browser_profiles", &settings.persistent_browser_profiles.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("demo_mode", &settings.demo_mode.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("duplicate_files", settings.duplicate_files.as_str())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("file_name_template", settings.file_name_template.trim())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("folder_template", settings.folder_template.trim())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("file_name_transliteration", settings.file_name_transliteration.as_str())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("csv_delimiter", settings.csv_delimiter.as_str())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("csv_decimal_comma", &settings.csv_decimal_comma.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("csv_bom", &settings.csv_bom.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("csv_day_first_dates", &settings.csv_day_first_dates.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("browser_window_width", &settings.browser_window_width.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("browser_window_height", &settings.browser_window_height.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting(
+            "browser_device_scale_factor",
+            &settings.browser_device_scale_factor.to_string(),
+        )
+        .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("browser_zoom_percent", &settings.browser_zoom_percent.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("captcha_padding_px", &settings.captcha_padding_px.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("manual_captcha_timeout_secs", &settings.manual_captcha_timeout_secs.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        let account = settings.portal_account();
+        for (key, value) in [
+            ("portal_login_url", &account.login_url),
+            ("portal_invoice_list_url", &account.invoice_list_url),
+            ("portal_username", &account.username),
+            ("portal_password", &account.password),
+        ] {
+            save_setting(key, value)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        }
+        let imap = settings.imap_config();
+        for (key, value) in [
+            ("inbox_enabled", settings.inbox_enabled.to_string().as_str()),
+            ("imap_host", &imap.host),
+            ("imap_port", imap.port.to_string().as_str()),
+            ("imap_username", &imap.username),
+            ("imap_password", &imap.password),
+            ("imap_folder", &imap.folder),
+        ] {
+            save_setting(key, value)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        }
+        save_setting("post_download_hook", settings.post_download_hook.trim())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("post_download_hook_scope", settings.post_download_hook_scope.as_str())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        save_setting("upload_to_s3", &settings.upload_to_s3.to_string())
+            .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        let s3 = settings.s3_config();
+        for (key, value) in [
+            ("s3_endpoint", &s3.endpoint),
+            ("s3_region", &s3.region),
+            ("s3_bucket", &s3.bucket),
+            ("s3_access_key", &s3.access_key),
+            ("s3_secret_key", &s3.secret_key),
+            ("s3_prefix", &s3.prefix),
+        ] {
+            save_setting(key, value)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        }
+        let webdav = settings.webdav_config();
+        for (key, value) in [
+            ("upload_to_webdav", settings.upload_to_webdav.to_string().as_str()),
+            ("webdav_url", &webdav.url),
+            ("webdav_username", &webdav.username),
+            ("webdav_password", &webdav.password),
+        ] {
+            save_setting(key, value)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        }
+        let sftp = settings.sftp_config();
+        for (key, value) in [
+            ("upload_to_sftp", settings.upload_to_sftp.to_string().as_str()),
+            ("sftp_host", &sftp.host),
+            ("sftp_port", sftp.port.to_string().as_str()),
+            ("sftp_username", &sftp.username),
+            ("sftp_password", &sftp.password),
+            ("sftp_private_key_path", &sftp.private_key_path),
+            ("sftp_remote_dir", &sftp.remote_dir),
+            ("sftp_mode", sftp.mode.as_str()),
+            ("sftp_host_key_fingerprint", &sftp.host_key_fingerprint),
+        ] {
+            save_setting(key, value)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        }
+        let cloud = settings.cloud_config();
+        for (key, value) in [
+            ("cloud_provider", cloud.provider.as_str()),
+            ("cloud_client_id", &cloud.client_id),
+            ("cloud_client_secret", &cloud.client_secret),
+            ("cloud_folder", &cloud.folder),
+        ] {
+            save_setting(key, value)
+                .map_err(|e| AppError::DatabaseError(format!("Failed to save setting: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Login name of the OS user, the closest thing to a user account this app has
+fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::settings_service;
+    use std::sync::Arc;
+
+    fn batch(id: &str) -> DownloadBatch {
+        DownloadBatch {
+            id: id.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            total_count: 1,
+            success_count: 0,
+            failed_count: 0,
+            download_directory: String::new(),
+            status: BatchStatus::Queued,
+            parent_id: None,
+            effective_config: None,
+            name: None,
+            source_file: None,
+            source_path: None,
+            source_hash: None,
+        }
+    }
+
+    fn invoice(id: &str, batch_id: &str, code: &str) -> HistoryInvoice {
+        HistoryInvoice {
+            id: id.to_string(),
+            batch_id: batch_id.to_string(),
+            code: code.to_string(),
+            status: InvoiceStatus::Pending,
+            error: None,
+            file_path: None,
+            downloaded_at: None,
+            repro_path: None,
+            portal: None,
+            remote_url: None,
+            signature_status: None,
+            note: None,
+        }
+    }
+
+    fn status(invoice_id: &str, status: InvoiceStatus, file_path: Option<&str>) -> InvoiceWrite {
+        InvoiceWrite::Status {
+            invoice_id: invoice_id.to_string(),
+            status,
+            error: (status == InvoiceStatus::Failed).then(|| "Captcha failed after 3 attempts".to_string()),
+            file_path: file_path.map(str::to_string),
+        }
+    }
+
+    fn user_version(db: &Database) -> usize {
+        db.conn
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schema_init() {
+        let db = Database::new_in_memory().unwrap();
+        assert_eq!(user_version(&db), MIGRATIONS.len());
+
+        let tables: Vec<String> = db
+            .conn
+            .lock()
+            .unwrap()
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|name| name.unwrap())
+            .collect();
+        for table in ["batches", "invoices", "settings", "invoice_metadata", "queued_downloads"] {
+            assert!(tables.iter().any(|name| name == table), "{} missing from {:?}", table, tables);
+        }
+
+        // Opening an up-to-date database again changes nothing
+        db.create_batch(&batch("b1")).unwrap();
+        db.init_schema().unwrap();
+        assert_eq!(user_version(&db), MIGRATIONS.len());
+        assert!(db.get_batch("b1").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_migrate_first_schema() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        // The schema and rows of the first release, before any migration
+        {
+            let conn = Connection::open(dir.join("autoinvoice.db")).unwrap();
+            conn.execute_batch(
+                r#"
+                CREATE TABLE batches (
+                    id TEXT PRIMARY KEY,
+                    created_at TEXT NOT NULL,
+                    total_count INTEGER NOT NULL,
+                    success_count INTEGER NOT NULL DEFAULT 0,
+                    failed_count INTEGER NOT NULL DEFAULT 0,
+                    download_directory TEXT NOT NULL
+                );
+                CREATE TABLE invoices (
+                    id TEXT PRIMARY KEY,
+                    batch_id TEXT NOT NULL,
+                    code TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    error TEXT,
+                    file_path TEXT,
+                    downloaded_at TEXT,
+                    FOREIGN KEY (batch_id) REFERENCES batches(id) ON DELETE CASCADE
+                );
+                CREATE TABLE settings (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL
+                );
+
+                INSERT INTO batches VALUES ('b1', '2024-05-01T08:00:00+00:00', 2, 1, 1, '/tmp/T5');
+                INSERT INTO invoices VALUES
+                    ('i1', 'b1', 'ABC123', 'success', NULL, '/tmp/T5/ABC123.pdf', '2024-05-01T08:01:00+00:00'),
+                    ('i2', 'b1', 'DEF456', 'error', 'timeout', NULL, '2024-05-01T08:02:00+00:00');
+                INSERT INTO settings VALUES ('vnpt_url', 'https://example-tt78.vnpt-invoice.com.vn/');
+                "#,
+            )
+            .unwrap();
+        }
+
+        let db = Database::new(dir.to_path_buf()).unwrap();
+        assert_eq!(user_version(&db), MIGRATIONS.len());
+
+        let batch = db.get_batch("b1").unwrap().unwrap();
+        assert_eq!(batch.status, BatchStatus::Completed);
+        assert_eq!(batch.download_directory, "/tmp/T5");
+
+        let downloaded = db.get_invoice("i1").unwrap().unwrap();
+        assert_eq!(downloaded.status, InvoiceStatus::Success);
+        assert_eq!(downloaded.file_path.as_deref(), Some("/tmp/T5/ABC123.pdf"));
+        // Statuses outside `InvoiceStatus` became failures
+        let unknown = db.get_invoice("i2").unwrap().unwrap();
+        assert_eq!(unknown.status, InvoiceStatus::Failed);
+        assert_eq!(unknown.error.as_deref(), Some("timeout"));
+
+        assert_eq!(db.get_settings().unwrap().vnpt_url, "https://example-tt78.vnpt-invoice.com.vn/");
+    }
+
+    #[test]
+    fn test_batch_and_invoice_crud() {
+        let db = Database::new_in_memory().unwrap();
+        db.create_batch(&DownloadBatch {
+            total_count: 2,
+            name: Some("T5".to_string()),
+            ..batch("b1")
+        })
+        .unwrap();
+        db.create_invoice(&invoice("i1", "b1", "ABC123")).unwrap();
+        db.create_invoice(&invoice("i2", "b1", "DEF456")).unwrap();
+        assert!(db.create_invoice(&invoice("i1", "b1", "ABC123")).is_err());
+
+        db.apply_invoice_writes(&[
+            status("i1", InvoiceStatus::Success, Some("/tmp/T5/ABC123.pdf")),
+            status("i2", InvoiceStatus::Failed, None),
+        ])
+        .unwrap();
+        db.refresh_batch_counts("b1").unwrap();
+        db.update_batch_status("b1", BatchStatus::Completed).unwrap();
+
+        let stored = db.get_batch("b1").unwrap().unwrap();
+        assert_eq!(stored.name.as_deref(), Some("T5"));
+        assert_eq!((stored.success_count, stored.failed_count), (1, 1));
+        assert_eq!(stored.status, BatchStatus::Completed);
+        assert_eq!(db.get_batches().unwrap().len(), 1);
+
+        let invoices = db.get_batch_invoices("b1").unwrap();
+        assert_eq!(invoices.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), ["i1", "i2"]);
+        assert!(invoices[0].downloaded_at.is_some());
+        let failed = db.get_failed_invoices("b1").unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].error.as_deref(), Some("Captcha failed after 3 attempts"));
+
+        assert!(db.is_code_downloaded("ABC123").unwrap());
+        assert!(!db.is_code_downloaded("DEF456").unwrap());
+        assert_eq!(db.get_downloaded_files("ABC123").unwrap(), ["/tmp/T5/ABC123.pdf"]);
+
+        db.delete_batch("b1").unwrap();
+        assert!(db.get_batch("b1").unwrap().is_none());
+        assert!(db.get_invoice("i1").unwrap().is_none());
+        assert!(db.get_batches().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let db = Database::new_in_memory().unwrap();
+
+        // Nothing saved yet: every setting has its default
+        let defaults = db.get_settings().unwrap();
+        assert!(!defaults.download_directory.is_empty());
+        assert_eq!(defaults.invoice_delay_secs, DEFAULT_INVOICE_DELAY_SECS);
+        assert_eq!(defaults.duplicate_files, DuplicateFiles::Keep);
+        assert!(!defaults.download_xml);
+
+        let edited = Settings {
+            vnpt_url: "https://example-tt78.vnpt-invoice.com.vn/".to_string(),
+            openai_backup_api_keys: vec!["sk-backup-1".to_string(), "sk-backup-2".to_string()],
+            download_xml: true,
+            invoice_delay_secs: 7,
+            browser_device_scale_factor: 1.5,
+            duplicate_files: DuplicateFiles::HardLink,
+            pdf_variant: PdfVariant::Both,
+            file_name_template: "{seller}_{number}".to_string(),
+            file_name_transliteration: Transliteration::Ascii,
+            ..defaults
+        };
+        db.save_settings(&edited).unwrap();
+        let saved = db.get_settings().unwrap();
+        assert!(
+            settings_service::changed_fields(&edited, &saved).is_empty(),
+            "{:?}",
+            settings_service::changed_fields(&edited, &saved)
+        );
+        assert_eq!(saved.openai_backup_api_keys, ["sk-backup-1", "sk-backup-2"]);
+
+        db.set_download_directory("/tmp/invoices").unwrap();
+        assert_eq!(db.get_settings().unwrap().download_directory, "/tmp/invoices");
+        assert!(!db.get_read_only().unwrap());
+        db.set_read_only(true).unwrap();
+        assert!(db.get_read_only().unwrap());
+    }
+
+    #[test]
+    fn test_concurrent_invoice_writes() {
+        const THREADS: usize = 8;
+        const INVOICES_PER_THREAD: usize = 25;
+
+        let db = Arc::new(Database::new_in_memory().unwrap());
+        db.create_batch(&batch("b1")).unwrap();
+
+        let workers: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for n in 0..INVOICES_PER_THREAD {
+                        let id = format!("i{}-{}", thread, n);
+                        db.create_invoice(&invoice(&id, "b1", &format!("C{}{:03}", thread, n))).unwrap();
+                        let outcome = if n % 5 == 0 { InvoiceStatus::Failed } else { InvoiceStatus::Success };
+                        db.apply_invoice_writes(&[status(&id, outcome, None)]).unwrap();
+                        db.refresh_batch_counts("b1").unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let total = THREADS * INVOICES_PER_THREAD;
+        let failed = THREADS * INVOICES_PER_THREAD.div_ceil(5);
+        assert_eq!(db.get_batch_invoices("b1").unwrap().len(), total);
+        let stored = db.get_batch("b1").unwrap().unwrap();
+        assert_eq!(stored.success_count as usize, total - failed);
+        assert_eq!(stored.failed_count as usize, failed);
+    }
+
+    #[test]
+    fn test_connection_pragmas() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = Database::new(tmp.path().to_path_buf()).unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            let pragma = |name: &str| conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, i64>(0)).unwrap();
+            let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+            assert_eq!(journal_mode, "wal");
+            assert_eq!(pragma("foreign_keys"), 1);
+            // NORMAL
+            assert_eq!(pragma("synchronous"), 1);
+            assert_eq!(pragma("busy_timeout"), BUSY_TIMEOUT.as_millis() as i64);
+        }
+
+        // Enforced now: an invoice needs its batch
+        assert!(db.create_invoice(&invoice("i1", "missing", "ABC123")).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_reader_and_writer() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let db = Arc::new(Database::new(dir.to_path_buf()).unwrap());
+        db.create_batch(&batch("b1")).unwrap();
+
+        // Another connection, e.g. a second app instance, holds the write lock
+        let other = Database::new(dir.to_path_buf()).unwrap();
+        other
+            .conn
+            .lock()
+            .unwrap()
+            .execute_batch("BEGIN IMMEDIATE; UPDATE batches SET total_count = 2 WHERE id = 'b1';")
+            .unwrap();
+
+        // Readers see the last commit without waiting
+        assert_eq!(db.get_batch("b1").unwrap().unwrap().total_count, 1);
+
+        // A writer waits for the lock instead of failing as busy
+        let writer = {
+            let db = db.clone();
+            std::thread::spawn(move || db.create_batch(&batch("b2")))
+        };
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!writer.is_finished());
+        other.conn.lock().unwrap().execute_batch("COMMIT").unwrap();
+        writer.join().unwrap().unwrap();
+
+        assert_eq!(db.get_batch("b1").unwrap().unwrap().total_count, 2);
+        assert!(db.get_batch("b2").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_cascading_delete_and_orphan_repair() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let db = Database::new(dir.to_path_buf()).unwrap();
+        let count = |db: &Database, table: &str| -> i64 {
+            db.conn
+                .lock()
+                .unwrap()
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+                .unwrap()
+        };
+        let metadata = |conn: &Connection, invoice_id: &str| {
+            conn.execute(
+                "INSERT INTO invoice_metadata (invoice_id, source, extracted_at) VALUES (?1, 'text', '')",
+                [invoice_id],
+            )
+            .unwrap();
+        };
+
+        db.create_batch(&batch("b1")).unwrap();
+        db.create_batch(&DownloadBatch {
+            parent_id: Some("b1".to_string()),
+            ..batch("b1-retry")
+        })
+        .unwrap();
+        for (id, batch_id) in [("i1", "b1"), ("i2", "b1-retry")] {
+            db.conn
+                .lock()
+                .unwrap()
+                .execute("INSERT INTO invoices (id, batch_id, code) VALUES (?1, ?2, 'ABC123')", [id, batch_id])
+                .unwrap();
+            metadata(&db.conn.lock().unwrap(), id);
+        }
+        db.queue_download("b1", "{}").unwrap();
+
+        db.delete_batch("b1").unwrap();
+        for table in ["batches", "invoices", "invoice_metadata", "queued_downloads"] {
+            assert_eq!(count(&db, table), 0, "{}", table);
+        }
+
+        // Rows an older version left behind without enforcement
+        drop(db);
+        {
+            let conn = Connection::open(dir.join("autoinvoice.db")).unwrap();
+            conn.pragma_update(None, "foreign_keys", false).unwrap();
+            conn.execute("INSERT INTO invoices (id, batch_id, code) VALUES ('i3', 'gone', 'ABC123')", [])
+                .unwrap();
+            metadata(&conn, "i3");
+            metadata(&conn, "also-gone");
+        }
+        let db = Database::new(dir.to_path_buf()).unwrap();
+        assert_eq!(count(&db, "invoices"), 0);
+        assert_eq!(count(&db, "invoice_metadata"), 0);
+    }
+
+    #[test]
+    fn test_invoice_lookups_use_indexes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = Database::new(tmp.path().to_path_buf()).unwrap();
+        let conn = db.conn.lock().unwrap();
+        let plan = |sql: &str| -> String {
+            let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap();
+            let steps: Vec<String> = stmt.query_map([], |row| row.get(3)).unwrap().map(|step| step.unwrap()).collect();
+            steps.join("\n")
+        };
+
+        let dedup = plan("SELECT 1 FROM invoices WHERE code = 'ABC123' AND status = 'success' LIMIT 1");
+        assert!(dedup.contains("USING INDEX idx_invoices_code"), "{}", dedup);
+
+        let stats = plan(
+            "SELECT status FROM invoices WHERE duration_ms IS NOT NULL AND +status IN ('success', 'failed')
+             ORDER BY downloaded_at DESC LIMIT 500",
+        );
+        assert!(stats.contains("USING INDEX idx_invoices_downloaded_at"), "{}", stats);
+        assert!(!stats.contains("TEMP B-TREE"), "{}", stats);
+
+        let failed = plan(&format!(
+            "SELECT id FROM invoices WHERE batch_id IN ({}) AND status = 'failed'",
+            BATCH_TREE.replace("?1", "'b1'")
+        ));
+        assert!(failed.contains("idx_invoices_batch_id_status (batch_id=? AND status=?)"), "{}", failed);
+    }
 }
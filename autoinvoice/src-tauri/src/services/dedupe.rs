@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+use crate::status::impl_setting_parse;
+
+/// What to do with a downloaded PDF identical to one already in the archive,
+/// e.g. the same invoice in two batches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateFiles {
+    /// Write another copy
+    #[default]
+    Keep,
+    /// Hard-link to the existing file, so the copy takes no extra space;
+    /// falls back to a copy across volumes
+    HardLink,
+    /// Write nothing; the invoice points at the existing file
+    Skip,
+}
+
+impl DuplicateFiles {
+    pub const ALL: &'static [DuplicateFiles] = &[
+        DuplicateFiles::Keep,
+        DuplicateFiles::HardLink,
+        DuplicateFiles::Skip,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateFiles::Keep => "keep",
+            DuplicateFiles::HardLink => "hard-link",
+            DuplicateFiles::Skip => "skip",
+        }
+    }
+}
+
+impl_setting_parse!(DuplicateFiles);
+
+/// First of `candidates` whose content has the SHA-256 of `bytes`; files
+/// that are gone or unreadable are passed over
+pub fn find_identical(bytes: &[u8], candidates: &[String]) -> Option<PathBuf> {
+    let hash = Sha256::digest(bytes);
+    candidates
+        .iter()
+        .map(PathBuf::from)
+        .find(|candidate| std::fs::read(candidate).is_ok_and(|content| Sha256::digest(content) == hash))
+}
+
+/// Save `bytes` at `target`, reusing `existing` when it holds the same
+/// content; returns where the invoice's file now is
+pub fn save(
+    bytes: &[u8],
+    target: &Path,
+    existing: Option<&Path>,
+    mode: DuplicateFiles,
+) -> Result<PathBuf, AppError> {
+    match (existing, mode) {
+        (Some(existing), _) if existing == target => Ok(target.to_path_buf()),
+        (Some(existing), DuplicateFiles::Skip) => Ok(existing.to_path_buf()),
+        (Some(existing), DuplicateFiles::HardLink) => {
+            if target.exists() {
+                std::fs::remove_file(target)?;
+            }
+            if std::fs::hard_link(existing, target).is_err() {
+                std::fs::write(target, bytes)?;
+            }
+            Ok(target.to_path_buf())
+        }
+        _ => {
+            std::fs::write(target, bytes)?;
+            Ok(target.to_path_buf())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_duplicate() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("lo-1")).unwrap();
+        std::fs::create_dir_all(dir.join("lo-2")).unwrap();
+        let pdf = b"%PDF-1.4 invoice".as_slice();
+        let first = dir.join("lo-1").join("C24TAA0000001_Ab.pdf");
+        std::fs::write(&first, pdf).unwrap();
+
+        let candidates = vec![
+            dir.join("gone.pdf").to_string_lossy().to_string(),
+            first.to_string_lossy().to_string(),
+        ];
+        let existing = find_identical(pdf, &candidates);
+        assert_eq!(existing.as_deref(), Some(first.as_path()));
+        assert_eq!(find_identical(b"%PDF-1.4 other", &candidates), None);
+
+        let target = dir.join("lo-2").join("C24TAA0000001_Ab.pdf");
+        let skipped = save(pdf, &target, existing.as_deref(), DuplicateFiles::Skip).unwrap();
+        assert_eq!(skipped, first);
+        assert!(!target.exists());
+
+        let linked = save(pdf, &target, existing.as_deref(), DuplicateFiles::HardLink).unwrap();
+        assert_eq!(linked, target);
+        assert_eq!(std::fs::read(&target).unwrap(), pdf);
+    }
+}
@@ -0,0 +1,268 @@
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::services::excel_parser::{DetectedUrl, ExcelParseResult, InvoiceCode, LookupMode};
+use crate::services::fixtures::{PortalFixture, RecordedLookup};
+
+/// Portal the demo batches pretend to download from; never contacted
+pub const DEMO_PORTAL_URL: &str = "https://demo-tt78.vnpt-invoice.com.vn/";
+
+/// What the demo captcha reads
+const DEMO_CAPTCHA_TEXT: &str = "d3m0";
+
+/// 1×1 grey PNG standing in for the captcha image
+const DEMO_CAPTCHA_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x7e, 0x9b,
+    0x55, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x38, 0x01, 0x00, 0x00,
+    0xca, 0x00, 0xc9, 0x99, 0xca, 0x5d, 0x7e, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// File name the sample invoices are shown under
+const SAMPLE_FILE_NAME: &str = "hoa-don-mau.xlsx";
+
+/// How the demo portal answers a sample invoice
+#[derive(Clone, Copy)]
+enum SampleOutcome {
+    Found,
+    /// First captcha rejected, as happens on the real portal
+    CaptchaRejectedOnce,
+    NotFound,
+}
+
+struct SampleInvoice {
+    code: &'static str,
+    seller: &'static str,
+    seller_tax_code: &'static str,
+    number: u32,
+    date: (u32, u32, u32),
+    before_tax: u64,
+    outcome: SampleOutcome,
+}
+
+const SAMPLE_INVOICES: &[SampleInvoice] = &[
+    SampleInvoice {
+        code: "C25TDM0000001_Dm",
+        seller: "CONG TY TNHH VAN PHONG PHAM AN BINH",
+        seller_tax_code: "0101234567",
+        number: 1021,
+        date: (3, 3, 2025),
+        before_tax: 1_250_000,
+        outcome: SampleOutcome::Found,
+    },
+    SampleInvoice {
+        code: "C25TDM0000002_Dm",
+        seller: "CONG TY CP DICH VU VIEN THONG MINH PHAT",
+        seller_tax_code: "0309876543",
+        number: 87,
+        date: (5, 3, 2025),
+        before_tax: 880_000,
+        outcome: SampleOutcome::CaptchaRejectedOnce,
+    },
+    SampleInvoice {
+        code: "C25TDM0000003_Dm",
+        seller: "CONG TY TNHH THIET BI SO HONG HA",
+        seller_tax_code: "0312345678",
+        number: 4410,
+        date: (12, 3, 2025),
+        before_tax: 15_600_000,
+        outcome: SampleOutcome::Found,
+    },
+    SampleInvoice {
+        code: "C25TDM0000004_Dm",
+        seller: "CONG TY CP VAN TAI SAO VIET",
+        seller_tax_code: "0105554443",
+        number: 352,
+        date: (20, 3, 2025),
+        before_tax: 2_400_000,
+        outcome: SampleOutcome::NotFound,
+    },
+    SampleInvoice {
+        code: "C25TDM0000005_Dm",
+        seller: "CONG TY TNHH NUOC UONG TRONG AN",
+        seller_tax_code: "0316667778",
+        number: 96,
+        date: (28, 3, 2025),
+        before_tax: 540_000,
+        outcome: SampleOutcome::CaptchaRejectedOnce,
+    },
+];
+
+/// Sample invoices, as if read from an Excel sheet, for learning the
+/// import, download and history steps without a real file
+pub fn sample_invoices() -> ExcelParseResult {
+    let invoices: Vec<InvoiceCode> = SAMPLE_INVOICES
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| InvoiceCode {
+            id: uuid::Uuid::new_v4().to_string(),
+            code: sample.code.to_string(),
+            row_number: index + 2,
+            seller_tax_code: Some(sample.seller_tax_code.to_string()),
+            lookup_mode: LookupMode::Code,
+            invoice_series: None,
+            invoice_number: None,
+            portal_url: None,
+        })
+        .collect();
+    let codes: Vec<&str> = SAMPLE_INVOICES.iter().map(|sample| sample.code).collect();
+
+    ExcelParseResult {
+        detected_url: Some(DEMO_PORTAL_URL.to_string()),
+        detected_urls: vec![DetectedUrl {
+            url: DEMO_PORTAL_URL.to_string(),
+            count: invoices.len(),
+            rows: invoices.iter().map(|invoice| invoice.row_number).collect(),
+        }],
+        total_rows: invoices.len(),
+        invoices,
+        sheet_name: "Demo".to_string(),
+        file_name: SAMPLE_FILE_NAME.to_string(),
+        // No file behind it; the hash still lets a second demo batch be
+        // recognised as already downloaded
+        file_path: String::new(),
+        file_hash: hex::encode(Sha256::digest(codes.join("\n"))),
+    }
+}
+
+/// Answer of the demo captcha, for `CaptchaSolver::from_answers`
+pub fn captcha_answers() -> HashMap<String, String> {
+    HashMap::from([(
+        hex::encode(Sha256::digest(DEMO_CAPTCHA_PNG)),
+        DEMO_CAPTCHA_TEXT.to_string(),
+    )])
+}
+
+/// Write the fixture the demo portal serves for `invoice_code` into `dir`;
+/// codes that are not sample invoices are not found
+pub fn write_fixture(dir: &Path, invoice_code: &str) -> Result<PortalFixture, AppError> {
+    let sample = SAMPLE_INVOICES.iter().find(|sample| sample.code == invoice_code);
+    let outcome = sample.map_or(SampleOutcome::NotFound, |sample| sample.outcome);
+
+    let captcha_image = format!("{}.png", hex::encode(Sha256::digest(DEMO_CAPTCHA_PNG)));
+    let lookup = |page_error: Option<&str>| RecordedLookup {
+        url: DEMO_PORTAL_URL.to_string(),
+        captcha_image: Some(captcha_image.clone()),
+        captcha_text: Some(DEMO_CAPTCHA_TEXT.to_string()),
+        page_error: page_error.map(str::to_string),
+        ..RecordedLookup::default()
+    };
+    let found = || RecordedLookup {
+        page_html: Some("invoice.html".to_string()),
+        pdf: Some("invoice.pdf".to_string()),
+        ..lookup(None)
+    };
+    let lookups = match outcome {
+        SampleOutcome::Found => vec![found()],
+        SampleOutcome::CaptchaRejectedOnce => vec![lookup(Some("Mã captcha không đúng")), found()],
+        SampleOutcome::NotFound => vec![lookup(Some("Không tồn tại hóa đơn có mã tra cứu này"))],
+    };
+
+    let mut files = vec![(captcha_image.clone(), DEMO_CAPTCHA_PNG.to_vec())];
+    if let Some(sample) = sample.filter(|_| !matches!(outcome, SampleOutcome::NotFound)) {
+        files.push(("invoice.html".to_string(), invoice_html(sample).into_bytes()));
+        files.push(("invoice.pdf".to_string(), invoice_pdf(sample)?));
+    }
+
+    let fixture = PortalFixture {
+        invoice_code: invoice_code.to_string(),
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+        lookups,
+    };
+    fixture.save(dir, files)?;
+    Ok(fixture)
+}
+
+/// Lines printed on a sample invoice, worded so `pdf_metadata` reads them
+fn invoice_lines(sample: &SampleInvoice) -> Vec<String> {
+    let tax = sample.before_tax / 10;
+    let (day, month, year) = sample.date;
+    vec![
+        "HOA DON GIA TRI GIA TANG (BAN DEMO)".to_string(),
+        "Ky hieu (Serial): 1C25TDM".to_string(),
+        format!("So (No.): {:07}", sample.number),
+        format!("Ngay (Date) {:02} thang (month) {:02} nam (year) {}", day, month, year),
+        format!("Don vi ban hang: {}", sample.seller),
+        format!("Ma so thue (Tax code): {}", sample.seller_tax_code),
+        format!("Ma tra cuu: {}", sample.code),
+        format!("Cong tien hang (Total amount): {}", sample.before_tax),
+        format!("Tien thue GTGT (VAT amount): {}", tax),
+        format!("Tong cong tien thanh toan (Total payment): {}", sample.before_tax + tax),
+    ]
+}
+
+fn invoice_html(sample: &SampleInvoice) -> String {
+    let rows: String = invoice_lines(sample)
+        .iter()
+        .map(|line| format!("<p>{}</p>", line))
+        .collect();
+    format!("<html><body><div id=\"invoice\">{}</div></body></html>", rows)
+}
+
+fn invoice_pdf(sample: &SampleInvoice) -> Result<Vec<u8>, AppError> {
+    let pdf_error = |e: printpdf::Error| AppError::IoError(format!("Failed to render PDF: {}", e));
+    let (doc, page, layer) = PdfDocument::new(
+        format!("Hoa don {}", sample.code),
+        Mm(210.0),
+        Mm(297.0),
+        "Layer 1",
+    );
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(pdf_error)?;
+    let layer = doc.get_page(page).get_layer(layer);
+
+    let mut y = 270.0;
+    for line in invoice_lines(sample) {
+        layer.use_text(line, 11.0, Mm(20.0), Mm(y), &font);
+        y -= 8.0;
+    }
+    doc.save_to_bytes().map_err(pdf_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::browser_driver::BrowserDriver;
+    use crate::services::file_naming;
+    use crate::services::fixtures::ReplayDriver;
+    use crate::services::pdf_metadata::{self, MetadataSource};
+
+    #[test]
+    fn test_demo_portal() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert_eq!(sample_invoices().invoices.len(), SAMPLE_INVOICES.len());
+        assert_eq!(captcha_answers().len(), 1);
+
+        // The first captcha is rejected, the second gets the invoice
+        let fixture = write_fixture(dir, "C25TDM0000002_Dm").unwrap();
+        let portal = ReplayDriver::new(dir, &fixture);
+        portal.navigate_to_search(DEMO_PORTAL_URL).unwrap();
+        assert!(portal.check_for_error().unwrap().contains("captcha"));
+        portal.navigate_to_search(DEMO_PORTAL_URL).unwrap();
+        assert_eq!(portal.check_for_error(), None);
+
+        // The sample PDF reads like a real invoice
+        let client = reqwest::blocking::Client::new();
+        let pdf = portal.download_pdf(DEMO_PORTAL_URL, &client).unwrap();
+        let text = pdf_metadata::extract_text_from_bytes(&pdf).unwrap();
+        let fields = pdf_metadata::extract_fields(&text, MetadataSource::Text);
+        assert_eq!(fields.number.as_deref(), Some("87"));
+        assert_eq!(fields.total_payable, Some(968_000.0));
+
+        // So does the result page, for the file name templates
+        let page = file_naming::scrape_page(&portal.page_html().unwrap());
+        assert_eq!(page.seller_name.as_deref(), Some("CONG TY CP DICH VU VIEN THONG MINH PHAT"));
+        assert_eq!(page.invoice_number.as_deref(), Some("87"));
+
+        // Codes that are not samples are unknown to the demo portal
+        let fixture = write_fixture(dir, "C24TAA0000001_Ab").unwrap();
+        let portal = ReplayDriver::new(dir, &fixture);
+        portal.navigate_to_search(DEMO_PORTAL_URL).unwrap();
+        assert!(portal.check_for_error().unwrap().contains("Không tồn tại"));
+        assert!(portal.download_pdf(DEMO_PORTAL_URL, &client).is_err());
+    }
+}
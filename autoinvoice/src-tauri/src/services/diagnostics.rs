@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::error::AppError;
+use crate::services::browser::{find_chrome, VnptBrowser};
+use crate::services::chrome_version;
+use crate::services::journal::BatchJournal;
+use crate::status::TimingStage;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedStep {
+    pub timestamp: String,
+    pub attempt: u32,
+    pub action: String,
+    pub detail: Option<String>,
+}
+
+/// Records the steps taken while downloading a single invoice so a failure
+/// can be reproduced later from the bundle alone
+#[derive(Debug, Default)]
+pub struct StepRecorder {
+    steps: Vec<RecordedStep>,
+    last_captcha: Option<Vec<u8>>,
+    /// Durations of the timed stages, in milliseconds, in the order they finished
+    timings: Vec<(TimingStage, u64)>,
+    /// Batch journal each step is also written to
+    journal: Option<Arc<BatchJournal>>,
+}
+
+impl StepRecorder {
+    pub fn with_journal(journal: Option<Arc<BatchJournal>>) -> Self {
+        Self {
+            journal,
+            ..Self::default()
+        }
+    }
+
+    /// Record a step for the given attempt
+    pub fn record(&mut self, attempt: u32, action: &str, detail: Option<String>) {
+        if let Some(journal) = &self.journal {
+            // Best effort: a full disk must not fail the download
+            let _ = journal.phase(attempt, action);
+        }
+        self.steps.push(RecordedStep {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            attempt,
+            action: action.to_string(),
+            detail,
+        });
+    }
+
+    /// Keep the most recent captcha image for the bundle
+    pub fn set_captcha_image(&mut self, image: &[u8]) {
+        self.last_captcha = Some(image.to_vec());
+    }
+
+    pub fn steps(&self) -> &[RecordedStep] {
+        &self.steps
+    }
+
+    /// Record how long a stage took since `started`
+    pub fn record_timing(&mut self, stage: TimingStage, started: Instant) {
+        self.timings.push((stage, started.elapsed().as_millis() as u64));
+    }
+
+    pub fn timings(&self) -> &[(TimingStage, u64)] {
+        &self.timings
+    }
+
+    /// Number of recorded steps with the given action
+    pub fn count(&self, action: &str) -> u32 {
+        self.steps.iter().filter(|step| step.action == action).count() as u32
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ReproSummary<'a> {
+    batch_id: &'a str,
+    invoice_code: &'a str,
+    portal_url: &'a str,
+    page_url: Option<String>,
+    /// Major version of the Chrome the invoice was looked up in
+    chrome_version: Option<u32>,
+    error: &'a str,
+    created_at: String,
+    steps: &'a [RecordedStep],
+}
+
+/// Write a repro bundle (screenshot, page HTML, console logs, captcha image and
+/// recorded steps) for a failed invoice into `bundle_dir`
+///
+/// Browser artifacts are collected best-effort: a crashed tab should still
+/// produce a bundle with whatever could be captured.
+///
+/// # Returns
+/// Path to the created zip file
+pub fn write_repro_bundle(
+    bundle_dir: &Path,
+    batch_id: &str,
+    invoice_code: &str,
+    portal_url: &str,
+    error: &str,
+    recorder: &StepRecorder,
+    browser: &VnptBrowser,
+) -> Result<PathBuf, AppError> {
+    std::fs::create_dir_all(bundle_dir)
+        .map_err(|e| AppError::IoError(format!("Failed to create repro directory: {}", e)))?;
+
+    let safe_code = invoice_code.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
+    let filename = format!(
+        "{}_{}_{}.zip",
+        batch_id,
+        safe_code,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    let bundle_path = bundle_dir.join(filename);
+
+    let summary = ReproSummary {
+        batch_id,
+        invoice_code,
+        portal_url,
+        page_url: browser.current_url().ok(),
+        chrome_version: find_chrome().ok().and_then(|chrome| chrome_version::major_version(&chrome)),
+        error,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        steps: recorder.steps(),
+    };
+    let summary_json = serde_json::to_vec_pretty(&summary)
+        .map_err(|e| AppError::IoError(format!("Failed to serialize repro summary: {}", e)))?;
+
+    let file = std::fs::File::create(&bundle_path)?;
+    let mut zip = ZipWriter::new(file);
+
+    add_zip_entry(&mut zip, "steps.json", &summary_json)?;
+
+    if let Ok(screenshot) = browser.take_screenshot() {
+        add_zip_entry(&mut zip, "screenshot.png", &screenshot)?;
+    }
+
+    if let Ok(html) = browser.page_html() {
+        add_zip_entry(&mut zip, "page.html", html.as_bytes())?;
+    }
+
+    let console_logs = browser.console_logs().join("\n");
+    add_zip_entry(&mut zip, "console.log", console_logs.as_bytes())?;
+
+    if let Some(captcha) = &recorder.last_captcha {
+        add_zip_entry(&mut zip, "captcha.png", captcha)?;
+    }
+
+    zip.finish()
+        .map_err(|e| AppError::IoError(format!("Failed to finalize repro bundle: {}", e)))?;
+
+    Ok(bundle_path)
+}
+
+fn add_zip_entry(
+    zip: &mut ZipWriter<std::fs::File>,
+    name: &str,
+    contents: &[u8],
+) -> Result<(), AppError> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(name, options)
+        .map_err(|e| AppError::IoError(format!("Failed to add {} to bundle: {}", name, e)))?;
+    zip.write_all(contents)?;
+
+    Ok(())
+}
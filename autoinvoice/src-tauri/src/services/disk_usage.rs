@@ -0,0 +1,199 @@
+use serde::Serialize;
+use specta::Type;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+use crate::services::manifest::{CHECKSUMS_FILE, MANIFEST_FILE};
+
+/// Label used for invoices whose seller or month is not known
+const UNKNOWN: &str = "?";
+
+/// File recorded on an invoice, with what the report groups it by
+#[derive(Debug, Clone)]
+pub struct RecordedFile {
+    pub file_path: String,
+    /// Top-level batch, so the sub-batches of a split batch count as one
+    pub batch_id: String,
+    pub batch_name: String,
+    pub downloaded_at: Option<String>,
+    /// Seller name from the XML, else its tax code
+    pub seller: Option<String>,
+}
+
+/// Space taken by the files of one batch, month or seller
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct UsageGroup {
+    pub key: String,
+    pub label: String,
+    pub file_count: u32,
+    pub bytes: u64,
+}
+
+/// File in the download directory that no invoice record points at
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct UnreferencedFile {
+    pub path: String,
+    pub bytes: u64,
+    /// RFC 3339, UTC
+    pub modified: Option<String>,
+}
+
+/// Where the space in the download directory goes; groups are largest first
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct StorageReport {
+    pub root: String,
+    pub file_count: u32,
+    pub total_bytes: u64,
+    pub by_batch: Vec<UsageGroup>,
+    pub by_month: Vec<UsageGroup>,
+    pub by_seller: Vec<UsageGroup>,
+    /// Candidates for deletion, largest first
+    pub unreferenced: Vec<UnreferencedFile>,
+    pub unreferenced_bytes: u64,
+}
+
+/// Walk `root` and attribute every file to the invoice that recorded it; an
+/// invoice's XML counts with its PDF
+pub fn build(root: &Path, recorded: &[RecordedFile]) -> Result<StorageReport, AppError> {
+    let by_path: HashMap<PathBuf, &RecordedFile> = recorded
+        .iter()
+        .map(|file| (PathBuf::from(&file.file_path), file))
+        .collect();
+
+    let mut files = Vec::new();
+    if root.is_dir() {
+        walk(root, &mut files)?;
+    }
+
+    let mut by_batch = Groups::default();
+    let mut by_month = Groups::default();
+    let mut by_seller = Groups::default();
+    let mut unreferenced = Vec::new();
+    let mut total_bytes = 0;
+
+    for (path, metadata) in &files {
+        let bytes = metadata.len();
+        total_bytes += bytes;
+
+        let owner = by_path
+            .get(path)
+            .or_else(|| by_path.get(&path.with_extension("pdf")));
+        let Some(owner) = owner else {
+            if !is_bookkeeping(path) {
+                unreferenced.push(UnreferencedFile {
+                    path: path.to_string_lossy().to_string(),
+                    bytes,
+                    modified: metadata
+                        .modified()
+                        .ok()
+                        .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()),
+                });
+            }
+            continue;
+        };
+
+        by_batch.add(&owner.batch_id, &owner.batch_name, bytes);
+        let month = owner
+            .downloaded_at
+            .as_deref()
+            .and_then(|at| at.get(..7))
+            .unwrap_or(UNKNOWN);
+        by_month.add(month, month, bytes);
+        let seller = owner.seller.as_deref().filter(|s| !s.is_empty()).unwrap_or(UNKNOWN);
+        by_seller.add(seller, seller, bytes);
+    }
+
+    unreferenced.sort_by_key(|file| std::cmp::Reverse(file.bytes));
+    Ok(StorageReport {
+        root: root.to_string_lossy().to_string(),
+        file_count: files.len() as u32,
+        total_bytes,
+        by_batch: by_batch.into_sorted(),
+        by_month: by_month.into_sorted(),
+        by_seller: by_seller.into_sorted(),
+        unreferenced_bytes: unreferenced.iter().map(|file| file.bytes).sum(),
+        unreferenced,
+    })
+}
+
+/// Files the app writes next to the invoices, which no invoice records
+fn is_bookkeeping(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == MANIFEST_FILE || name == CHECKSUMS_FILE)
+}
+
+/// Regular files under `dir`; symlinks are not followed
+fn walk(dir: &Path, files: &mut Vec<(PathBuf, std::fs::Metadata)>) -> Result<(), AppError> {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                files.push((entry.path(), metadata));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Groups(HashMap<String, UsageGroup>);
+
+impl Groups {
+    fn add(&mut self, key: &str, label: &str, bytes: u64) {
+        let group = self.0.entry(key.to_string()).or_insert_with(|| UsageGroup {
+            key: key.to_string(),
+            label: label.to_string(),
+            file_count: 0,
+            bytes: 0,
+        });
+        group.file_count += 1;
+        group.bytes += bytes;
+    }
+
+    fn into_sorted(self) -> Vec<UsageGroup> {
+        let mut groups: Vec<UsageGroup> = self.0.into_values().collect();
+        groups.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.key.cmp(&b.key)));
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_report() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("lo-1")).unwrap();
+        let pdf = dir.join("lo-1").join("C24TAA0000001_Ab.pdf");
+        std::fs::write(&pdf, [0u8; 300]).unwrap();
+        std::fs::write(pdf.with_extension("xml"), [0u8; 100]).unwrap();
+        std::fs::write(dir.join("lo-1").join(MANIFEST_FILE), b"{}").unwrap();
+        std::fs::write(dir.join("old.pdf"), [0u8; 50]).unwrap();
+
+        let recorded = [RecordedFile {
+            file_path: pdf.to_string_lossy().to_string(),
+            batch_id: "b1".to_string(),
+            batch_name: "Tháng 3".to_string(),
+            downloaded_at: Some("2025-03-05T02:00:00+00:00".to_string()),
+            seller: None,
+        }];
+
+        let report = build(dir, &recorded).unwrap();
+        assert_eq!(report.file_count, 4);
+        assert_eq!(report.total_bytes, 452);
+        assert_eq!(report.by_batch[0].label, "Tháng 3");
+        assert_eq!(report.by_batch[0].bytes, 400);
+        assert_eq!(report.by_month[0].key, "2025-03");
+        assert_eq!(report.by_seller[0].key, UNKNOWN);
+        assert_eq!(report.unreferenced.len(), 1);
+        assert!(report.unreferenced[0].path.ends_with("old.pdf"));
+        assert_eq!(report.unreferenced_bytes, 50);
+    }
+}
@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+
+use crate::commands::settings::Settings;
+use crate::error::AppError;
+use crate::services::cloud_drive::CloudConfig;
+use crate::services::demo;
+use crate::services::downloader::DownloadConfig;
+use crate::services::hooks;
+use crate::services::path_policy;
+use crate::services::pdf_fetch::PdfVariant;
+use crate::services::schedule::QuietHours;
+
+/// Upper bound for per-invoice attempts, so a typo cannot hammer the portal
+const MAX_RETRIES_LIMIT: u32 = 10;
+
+/// Builds the config of a batch from the stored settings, with per-batch
+/// overrides, and validates it before anything is downloaded
+pub struct DownloadConfigBuilder {
+    config: DownloadConfig,
+    /// Saved download directory; a batch may only write inside it
+    download_root: String,
+}
+
+impl DownloadConfig {
+    pub fn builder(settings: &Settings) -> DownloadConfigBuilder {
+        DownloadConfigBuilder {
+            config: DownloadConfig {
+                vnpt_url: settings.vnpt_url.clone(),
+                openai_api_key: settings.openai_api_key.clone(),
+                openai_backup_api_keys: settings.openai_backup_api_keys.clone(),
+                use_azure_openai: settings.use_azure_openai,
+                azure_openai: settings.azure_openai_config(),
+                download_directory: settings.download_directory.clone(),
+                invoice_delay_secs: settings.invoice_delay_secs,
+                quiet_hours_start: settings.quiet_hours_start.clone(),
+                quiet_hours_end: settings.quiet_hours_end.clone(),
+                fallback_to_tax_portal: settings.fallback_to_tax_portal,
+                verify_not_found: settings.verify_not_found,
+                not_found_verify_delay_secs: settings.not_found_verify_delay_secs,
+                collect_metrics: settings.collect_metrics,
+                download_xml: settings.download_xml,
+                pdf_variant: settings.pdf_variant,
+                ocr_scanned_pdfs: settings.ocr_scanned_pdfs,
+                tesseract_path: settings.tesseract_path.trim().to_string(),
+                optimize_pdfs: settings.optimize_pdfs,
+                keep_original_pdfs: settings.keep_original_pdfs,
+                linearize_pdfs: settings.linearize_pdfs,
+                qpdf_path: settings.qpdf_path.trim().to_string(),
+                stamp_pdfs: settings.stamp_pdfs,
+                ai_budget_per_batch_usd: settings.ai_budget_per_batch_usd,
+                ai_budget_per_month_usd: settings.ai_budget_per_month_usd,
+                ai_budget_action: settings.ai_budget_action,
+                captcha_lookahead: settings.captcha_lookahead,
+                local_captcha_solver: settings.local_captcha_solver,
+                local_captcha_min_confidence: settings.local_captcha_min_confidence,
+                collect_captcha_dataset: settings.collect_captcha_dataset,
+                captcha_dataset_dir: settings.captcha_dataset_dir.trim().to_string(),
+                persistent_browser_profiles: settings.persistent_browser_profiles,
+                record_fixtures: false,
+                demo_mode: settings.demo_mode,
+                duplicate_files: settings.duplicate_files,
+                file_naming: settings.file_naming(),
+                browser_window_width: settings.browser_window_width,
+                browser_window_height: settings.browser_window_height,
+                browser_device_scale_factor: settings.browser_device_scale_factor,
+                browser_zoom_percent: settings.browser_zoom_percent,
+                captcha_padding_px: settings.captcha_padding_px,
+                manual_captcha_timeout_secs: settings.manual_captcha_timeout_secs,
+                post_download_hook: settings.post_download_hook.trim().to_string(),
+                post_download_hook_scope: settings.post_download_hook_scope,
+                upload_to_s3: settings.upload_to_s3,
+                s3: settings.s3_config(),
+                upload_to_webdav: settings.upload_to_webdav,
+                webdav: settings.webdav_config(),
+                upload_to_sftp: settings.upload_to_sftp,
+                sftp: settings.sftp_config(),
+                cloud: settings.cloud_config(),
+                ..DownloadConfig::default()
+            },
+            download_root: settings.download_directory.clone(),
+        }
+    }
+}
+
+impl DownloadConfigBuilder {
+    /// Portal detected in the uploaded sheet, used instead of the saved one
+    pub fn vnpt_url(mut self, vnpt_url: impl Into<String>) -> Self {
+        self.config.vnpt_url = vnpt_url.into();
+        self
+    }
+
+    /// Folder of this batch, inside the saved download directory
+    pub fn download_directory(mut self, download_directory: impl Into<String>) -> Self {
+        self.config.download_directory = download_directory.into();
+        self
+    }
+
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.config.headless = headless;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.config.max_retries = max_retries;
+        self
+    }
+
+    /// Keep an unattended run going instead of waiting for manual captchas
+    pub fn defer_manual_captchas(mut self, defer: bool) -> Self {
+        self.config.defer_manual_captchas = defer;
+        self
+    }
+
+    pub fn two_pass(mut self, two_pass: bool) -> Self {
+        self.config.two_pass = two_pass;
+        self
+    }
+
+    pub fn record_fixtures(mut self, record_fixtures: bool) -> Self {
+        self.config.record_fixtures = record_fixtures;
+        self
+    }
+
+    /// Check the config can work, creating the download directory if needed
+    pub fn build(mut self) -> Result<DownloadConfig, AppError> {
+        if self.config.demo_mode {
+            self.keep_offline();
+        }
+        self.config.vnpt_url = validate_portal_url(&self.config.vnpt_url)?;
+
+        if self.config.openai_api_key.trim().is_empty() && !self.config.demo_mode {
+            return Err(AppError::ConfigError(
+                "OpenAI API key is not set; add it in Settings".to_string(),
+            ));
+        }
+
+        if !(1..=MAX_RETRIES_LIMIT).contains(&self.config.max_retries) {
+            return Err(AppError::ConfigError(format!(
+                "Retries per invoice must be between 1 and {}, got {}",
+                MAX_RETRIES_LIMIT, self.config.max_retries
+            )));
+        }
+
+        QuietHours::parse(&self.config.quiet_hours_start, &self.config.quiet_hours_end)?;
+        hooks::validate_hook(&self.config.post_download_hook)?;
+        if self.config.upload_to_s3 {
+            self.config.s3.validate()?;
+        }
+        if self.config.upload_to_webdav {
+            self.config.webdav.validate()?;
+        }
+        if self.config.upload_to_sftp {
+            self.config.sftp.validate()?;
+        }
+        self.config.cloud.validate()?;
+        self.config.file_naming.validate()?;
+
+        if self.config.download_directory.trim() != self.download_root.trim() {
+            let root = ensure_writable_dir(&self.download_root)?;
+            path_policy::ensure_within(&root, Path::new(self.config.download_directory.trim()))?;
+        }
+        let directory = ensure_writable_dir(&self.config.download_directory)?;
+        self.config.download_directory = directory.to_string_lossy().to_string();
+
+        Ok(self.config)
+    }
+
+    /// Demo batches replay sample invoices, so nothing that reaches outside
+    /// the app runs: no portal, API call, upload or hook
+    fn keep_offline(&mut self) {
+        let config = &mut self.config;
+        config.vnpt_url = demo::DEMO_PORTAL_URL.to_string();
+        config.openai_api_key.clear();
+        config.openai_backup_api_keys.clear();
+        config.upload_to_s3 = false;
+        config.upload_to_webdav = false;
+        config.upload_to_sftp = false;
+        config.cloud = CloudConfig::default();
+        config.post_download_hook.clear();
+        config.fallback_to_tax_portal = false;
+        config.verify_not_found = false;
+        config.download_xml = false;
+        config.pdf_variant = PdfVariant::Original;
+        config.record_fixtures = false;
+    }
+}
+
+/// Trim the portal URL and check it is an absolute http(s) URL
+fn validate_portal_url(vnpt_url: &str) -> Result<String, AppError> {
+    let vnpt_url = vnpt_url.trim();
+    if vnpt_url.is_empty() {
+        return Err(AppError::ConfigError(
+            "No VNPT portal URL; set one in Settings or upload a sheet that contains it".to_string(),
+        ));
+    }
+
+    let parsed = url::Url::parse(vnpt_url)
+        .map_err(|e| AppError::ConfigError(format!("Invalid VNPT portal URL {}: {}", vnpt_url, e)))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AppError::ConfigError(format!(
+            "VNPT portal URL must start with http:// or https://: {}",
+            vnpt_url
+        )));
+    }
+
+    Ok(vnpt_url.to_string())
+}
+
+/// Create `directory` if missing and prove it is writable with a temp file
+///
+/// # Returns
+/// The canonical path of the directory
+pub fn ensure_writable_dir(directory: &str) -> Result<PathBuf, AppError> {
+    let directory = directory.trim();
+    if directory.is_empty() {
+        return Err(AppError::ConfigError("No download directory selected".to_string()));
+    }
+
+    std::fs::create_dir_all(directory).map_err(|e| {
+        AppError::ConfigError(format!("Cannot create download directory {}: {}", directory, e))
+    })?;
+
+    let probe = PathBuf::from(directory).join(format!(".autoinvoice-write-test-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"").map_err(|e| {
+        AppError::ConfigError(format!("Download directory {} is not writable: {}", directory, e))
+    })?;
+    let _ = std::fs::remove_file(&probe);
+
+    std::fs::canonicalize(directory).map_err(|e| {
+        AppError::ConfigError(format!("Cannot resolve download directory {}: {}", directory, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(download_directory: &str) -> Settings {
+        Settings {
+            openai_api_key: "sk-test".to_string(),
+            vnpt_url: "https://example-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey".to_string(),
+            download_directory: download_directory.to_string(),
+            invoice_delay_secs: 2,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn test_builder_validates_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let dir_str = dir.to_string_lossy().to_string();
+
+        let config = DownloadConfig::builder(&settings(&dir_str)).build().unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(config.invoice_delay_secs, 2);
+        assert!(config.headless);
+
+        let err = DownloadConfig::builder(&settings(&dir_str))
+            .vnpt_url("example.vnpt-invoice.com.vn")
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid VNPT portal URL"));
+
+        let err = DownloadConfig::builder(&settings(&dir_str))
+            .vnpt_url("ftp://example.vnpt-invoice.com.vn")
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("http://"));
+
+        let err = DownloadConfig::builder(&settings(&dir_str))
+            .max_retries(0)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("Retries per invoice"));
+
+        let config = DownloadConfig::builder(&settings(&dir_str))
+            .download_directory(dir.join("T5").to_string_lossy())
+            .build()
+            .unwrap();
+        assert!(config.download_directory.ends_with("T5"));
+
+        let err = DownloadConfig::builder(&settings(&dir_str))
+            .download_directory(std::env::temp_dir().to_string_lossy())
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("Path not allowed"));
+
+        let err = DownloadConfig::builder(&settings("")).build().unwrap_err();
+        assert!(err.to_string().contains("No download directory"));
+    }
+}
@@ -1,78 +1,711 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, Wry};
 use serde::{Deserialize, Serialize};
+use specta::Type;
 
-use crate::error::AppError;
-use crate::services::browser::VnptBrowser;
-use crate::services::captcha::CaptchaSolver;
+use crate::commands::history::{DownloadBatch, HistoryInvoice};
+use crate::error::{AppError, ErrorClass};
+use crate::events::{
+    self, BatchStatusEvent, BudgetExceededEvent, CaptchaRequiredEvent, InvoiceStatusEvent, InvoicesAvailableEvent, LogEvent,
+    ProgressEvent, UploadProgressEvent,
+};
+use crate::services::ai_budget::{AiBudget, BudgetAction, BudgetScope};
+use crate::services::batch_split::{self, PortalGroup};
+use crate::services::browser::{
+    self, BrowserViewport, LookaheadTab, VnptBrowser, DEFAULT_CAPTCHA_PADDING_PX,
+};
+use crate::services::browser_driver::BrowserDriver;
+use crate::services::browser_pool::{BrowserPool, ParkedBrowser, PrefetchedCaptcha};
+use crate::services::browser_preview::PreviewStream;
+use crate::services::browser_profile::BrowserProfile;
+use crate::services::captcha::{AzureOpenAiConfig, CaptchaSolver};
+use crate::services::captcha_dataset::{self, CaptchaSource};
+use crate::services::database::Database;
+use crate::services::diagnostics::{write_repro_bundle, StepRecorder};
+use crate::services::status_writer::{InvoiceWrite, StatusWriter};
+use crate::services::event_throttle::{EventThrottle, DEFAULT_EVENT_THROTTLE_MS};
+use crate::services::excel_parser::LookupMode;
+use crate::services::demo;
+use crate::services::fixtures::{RecordingDriver, ReplayDriver};
+use crate::services::cloud_drive::{CloudConfig, CloudDrive};
+use crate::services::dedupe::{self, DuplicateFiles};
+use crate::services::file_naming::{self, FileNaming, NameValues, PageFields};
+use crate::services::hooks::{self, HookScope};
+use crate::services::anomalies;
+use crate::services::invoice_xml;
+use crate::services::journal::BatchJournal;
+use crate::services::local_captcha::{self, LocalCaptchaModel};
+use crate::services::ocr;
+use crate::services::panic_report::{self, PanicReport};
+use crate::services::path_policy;
+use crate::services::pdf_fetch::{self, PdfVariant};
+use crate::services::pdf_metadata::{self, MetadataSource};
+use crate::services::pdf_optimize;
+use crate::services::pdf_stamp;
+use crate::services::portal_profiles::{self, PortalProfile, PortalSlots};
+use crate::services::xml_signature;
+use crate::services::manifest::BatchManifest;
+use crate::services::sftp::{self, SftpConfig};
+use crate::services::settings_service::SettingsService;
+use crate::services::storage::{self, S3Config, S3Storage, WebDavConfig, WebDavStorage};
+use crate::services::http::{self, HttpClients, HttpTimeouts};
+use crate::services::manual_captcha::{
+    ManualAnswer, ManualCaptchas, PendingCaptcha, DEFAULT_MANUAL_CAPTCHA_TIMEOUT_SECS,
+};
+use crate::services::schedule::QuietHours;
+use crate::services::selector_config::SelectorConfig;
+use crate::services::selector_repair::repair_selector;
+use crate::services::tax_portal::{self, DEFAULT_TAX_PORTAL_URL};
+use crate::services::tracing_log;
+use crate::status::{BatchStatus, InvoiceStatus, Portal, SignatureStatus, TimingStage};
 
-const MAX_RETRIES: u32 = 3;
+/// Default attempts per invoice before it is marked failed
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Default Chrome memory ceiling before the browser is recycled
+const DEFAULT_MAX_BROWSER_MEMORY_MB: u32 = 1536;
+
+/// Default pause between two invoices, in seconds
+pub const DEFAULT_INVOICE_DELAY_SECS: u32 = 2;
+
+/// Default pause before looking a not-found invoice up again, in seconds
+pub const DEFAULT_NOT_FOUND_VERIFY_DELAY_SECS: u32 = 60;
+
+/// Longest pause accepted for the not-found verification, as it holds the batch
+pub const MAX_NOT_FOUND_VERIFY_DELAY_SECS: u32 = 600;
+
+/// How often a batch paused for quiet hours checks for cancellation
+const QUIET_HOURS_POLL: Duration = Duration::from_secs(30);
+
+/// How often a batch paused on its AI budget checks whether it may continue
+const BUDGET_POLL: Duration = Duration::from_secs(2);
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+fn default_manual_captcha_timeout_secs() -> u32 {
+    DEFAULT_MANUAL_CAPTCHA_TIMEOUT_SECS
+}
+
+fn default_max_browser_memory_mb() -> u32 {
+    DEFAULT_MAX_BROWSER_MEMORY_MB
+}
+
+fn default_invoice_delay_secs() -> u32 {
+    DEFAULT_INVOICE_DELAY_SECS
+}
+
+fn default_tax_portal_url() -> String {
+    DEFAULT_TAX_PORTAL_URL.to_string()
+}
+
+fn default_http_connect_timeout_secs() -> u32 {
+    http::DEFAULT_CONNECT_TIMEOUT_SECS
+}
+
+fn default_event_throttle_ms() -> u32 {
+    DEFAULT_EVENT_THROTTLE_MS
+}
+
+fn default_http_request_timeout_secs() -> u32 {
+    http::DEFAULT_REQUEST_TIMEOUT_SECS
+}
+
+fn default_browser_window_width() -> u32 {
+    BrowserViewport::default().width
+}
+
+fn default_browser_window_height() -> u32 {
+    BrowserViewport::default().height
+}
+
+fn default_browser_device_scale_factor() -> f64 {
+    BrowserViewport::default().device_scale_factor
+}
+
+fn default_browser_zoom_percent() -> u32 {
+    BrowserViewport::default().zoom_percent
+}
+
+fn default_captcha_padding_px() -> u32 {
+    DEFAULT_CAPTCHA_PADDING_PX
+}
+
+fn default_not_found_verify_delay_secs() -> u32 {
+    DEFAULT_NOT_FOUND_VERIFY_DELAY_SECS
+}
+
+fn default_local_captcha_min_confidence() -> f64 {
+    local_captcha::DEFAULT_MIN_CONFIDENCE
+}
+
+/// Page errors meaning the portal has no invoice for the code
+const NOT_FOUND_MARKERS: &[&str] = &["không tìm thấy", "không tồn tại", "not found"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct DownloadConfig {
     pub vnpt_url: String,
     pub openai_api_key: String,
+    /// Keys to fail over to when the main one is rate limited or out of quota
+    #[serde(default)]
+    pub openai_backup_api_keys: Vec<String>,
+    /// Call the `azure_openai` deployment instead of OpenAI
+    #[serde(default)]
+    pub use_azure_openai: bool,
+    #[serde(default)]
+    pub azure_openai: AzureOpenAiConfig,
     pub download_directory: String,
     pub headless: bool,
+    /// Attempts per invoice before it is marked failed
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// How long to wait for a manual captcha before skipping the invoice, in seconds
+    #[serde(default = "default_manual_captcha_timeout_secs")]
+    pub manual_captcha_timeout_secs: u32,
+    /// Record invoices needing a manual captcha as `needs-manual` instead of waiting
+    #[serde(default)]
+    pub defer_manual_captchas: bool,
+    /// Emit `debug` log entries, set from `set_log_level` when the batch starts
+    #[serde(default)]
+    pub debug_logging: bool,
+    /// Download everything solvable unattended first, then ask for the
+    /// deferred captchas one by one in a second pass
+    #[serde(default)]
+    pub two_pass: bool,
+    /// Script run after downloads with JSON context on stdin; empty disables it
+    #[serde(default)]
+    pub post_download_hook: String,
+    #[serde(default)]
+    pub post_download_hook_scope: HookScope,
+    /// Upload each downloaded PDF to `s3` and record the object URL
+    #[serde(default)]
+    pub upload_to_s3: bool,
+    #[serde(default)]
+    pub s3: S3Config,
+    /// Upload finished batches to a folder of `webdav`
+    #[serde(default)]
+    pub upload_to_webdav: bool,
+    #[serde(default)]
+    pub webdav: WebDavConfig,
+    /// Deliver finished batches to `sftp`
+    #[serde(default)]
+    pub upload_to_sftp: bool,
+    #[serde(default)]
+    pub sftp: SftpConfig,
+    /// Drive finished batches are mirrored to
+    #[serde(default)]
+    pub cloud: CloudConfig,
+    /// Recycle the browser when Chrome uses more than this many MB; 0 disables the check
+    #[serde(default = "default_max_browser_memory_mb")]
+    pub max_browser_memory_mb: u32,
+    /// Pause between two invoices, in seconds
+    #[serde(default = "default_invoice_delay_secs")]
+    pub invoice_delay_secs: u32,
+    /// Daily quiet hours ("HH:MM") during which the batch pauses; empty disables them
+    #[serde(default)]
+    pub quiet_hours_start: String,
+    #[serde(default)]
+    pub quiet_hours_end: String,
+    /// Retry invoices VNPT does not know on the tax-authority portal
+    #[serde(default)]
+    pub fallback_to_tax_portal: bool,
+    /// Look a not-found invoice up once more after `not_found_verify_delay_secs`
+    #[serde(default)]
+    pub verify_not_found: bool,
+    #[serde(default = "default_not_found_verify_delay_secs")]
+    pub not_found_verify_delay_secs: u32,
+    #[serde(default = "default_tax_portal_url")]
+    pub tax_portal_url: String,
+    /// Timeouts for PDF downloads and OpenAI calls, in seconds
+    #[serde(default = "default_http_connect_timeout_secs")]
+    pub http_connect_timeout_secs: u32,
+    #[serde(default = "default_http_request_timeout_secs")]
+    pub http_request_timeout_secs: u32,
+    /// Store per-stage timings locally for `get_performance_metrics`
+    #[serde(default)]
+    pub collect_metrics: bool,
+    /// Save `<code>.xml` next to each PDF and store its parsed content
+    #[serde(default)]
+    pub download_xml: bool,
+    /// Original, converted printable or both PDFs of each invoice
+    #[serde(default)]
+    pub pdf_variant: PdfVariant,
+    /// OCR downloaded PDFs that have no text layer, with `tesseract_path`
+    #[serde(default)]
+    pub ocr_scanned_pdfs: bool,
+    #[serde(default)]
+    pub tesseract_path: String,
+    /// Compress each downloaded PDF without loss, see `pdf_optimize`
+    #[serde(default)]
+    pub optimize_pdfs: bool,
+    #[serde(default)]
+    pub keep_original_pdfs: bool,
+    /// Also linearize with qpdf at `qpdf_path`
+    #[serde(default)]
+    pub linearize_pdfs: bool,
+    #[serde(default)]
+    pub qpdf_path: String,
+    /// Stamp "Processed <date> – batch <name>" on each PDF, see `pdf_stamp`
+    #[serde(default)]
+    pub stamp_pdfs: bool,
+    /// Browser window and rendering, see `BrowserViewport`
+    #[serde(default = "default_browser_window_width")]
+    pub browser_window_width: u32,
+    #[serde(default = "default_browser_window_height")]
+    pub browser_window_height: u32,
+    #[serde(default = "default_browser_device_scale_factor")]
+    pub browser_device_scale_factor: f64,
+    #[serde(default = "default_browser_zoom_percent")]
+    pub browser_zoom_percent: u32,
+    /// Margin captured around the captcha image, in CSS pixels
+    #[serde(default = "default_captcha_padding_px")]
+    pub captcha_padding_px: u32,
+    /// Estimated AI captcha spend allowed for the batch and for the month,
+    /// in USD; 0 means no cap
+    #[serde(default)]
+    pub ai_budget_per_batch_usd: f64,
+    #[serde(default)]
+    pub ai_budget_per_month_usd: f64,
+    #[serde(default)]
+    pub ai_budget_action: BudgetAction,
+    /// Solve the next invoice's captcha on a second tab while the current one
+    /// downloads, and hand that tab to the next invoice
+    #[serde(default)]
+    pub captcha_lookahead: bool,
+    /// Read captchas with the offline model first, see `local_captcha`
+    #[serde(default)]
+    pub local_captcha_solver: bool,
+    #[serde(default = "default_local_captcha_min_confidence")]
+    pub local_captcha_min_confidence: f64,
+    /// Keep captchas the portal accepted, with their answers, in
+    /// `captcha_dataset_dir` (the app data folder when empty)
+    #[serde(default)]
+    pub collect_captcha_dataset: bool,
+    #[serde(default)]
+    pub captcha_dataset_dir: String,
+    /// Keep one Chrome profile per portal so cookies and sessions carry over
+    /// between runs, instead of a throwaway profile per browser
+    #[serde(default)]
+    pub persistent_browser_profiles: bool,
+    /// Save the pages, captchas and files each invoice's lookup returned as
+    /// a replayable fixture, see `services::fixtures`
+    #[serde(default)]
+    pub record_fixtures: bool,
+    /// Replay sample invoices from `services::demo` instead of opening the portal
+    #[serde(default)]
+    pub demo_mode: bool,
+    /// What to do with a PDF identical to an earlier download of the invoice
+    #[serde(default)]
+    pub duplicate_files: DuplicateFiles,
+    /// File and folder name templates of the PDFs
+    #[serde(default)]
+    pub file_naming: FileNaming,
+    /// Emit held-back progress, status and log events at most this often, in
+    /// milliseconds; 0 with `event_throttle_invoices` at 0 emits every event
+    #[serde(default = "default_event_throttle_ms")]
+    pub event_throttle_ms: u32,
+    /// Also flush held-back events after this many finished invoices; 0 disables
+    #[serde(default)]
+    pub event_throttle_invoices: u32,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            vnpt_url: String::new(),
+            openai_api_key: String::new(),
+            openai_backup_api_keys: Vec::new(),
+            use_azure_openai: false,
+            azure_openai: AzureOpenAiConfig::default(),
+            download_directory: String::new(),
+            headless: true,
+            max_retries: default_max_retries(),
+            manual_captcha_timeout_secs: default_manual_captcha_timeout_secs(),
+            defer_manual_captchas: false,
+            debug_logging: false,
+            two_pass: false,
+            post_download_hook: String::new(),
+            post_download_hook_scope: HookScope::Invoice,
+            upload_to_s3: false,
+            s3: S3Config::default(),
+            upload_to_webdav: false,
+            webdav: WebDavConfig::default(),
+            upload_to_sftp: false,
+            sftp: SftpConfig::default(),
+            cloud: CloudConfig::default(),
+            max_browser_memory_mb: default_max_browser_memory_mb(),
+            invoice_delay_secs: default_invoice_delay_secs(),
+            quiet_hours_start: String::new(),
+            quiet_hours_end: String::new(),
+            fallback_to_tax_portal: false,
+            verify_not_found: false,
+            not_found_verify_delay_secs: default_not_found_verify_delay_secs(),
+            tax_portal_url: default_tax_portal_url(),
+            http_connect_timeout_secs: default_http_connect_timeout_secs(),
+            http_request_timeout_secs: default_http_request_timeout_secs(),
+            collect_metrics: false,
+            download_xml: false,
+            pdf_variant: PdfVariant::Original,
+            ocr_scanned_pdfs: false,
+            tesseract_path: String::new(),
+            optimize_pdfs: false,
+            keep_original_pdfs: false,
+            linearize_pdfs: false,
+            qpdf_path: String::new(),
+            stamp_pdfs: false,
+            browser_window_width: default_browser_window_width(),
+            browser_window_height: default_browser_window_height(),
+            browser_device_scale_factor: default_browser_device_scale_factor(),
+            browser_zoom_percent: default_browser_zoom_percent(),
+            captcha_padding_px: default_captcha_padding_px(),
+            ai_budget_per_batch_usd: 0.0,
+            ai_budget_per_month_usd: 0.0,
+            ai_budget_action: BudgetAction::ManualCaptcha,
+            captcha_lookahead: false,
+            local_captcha_solver: false,
+            local_captcha_min_confidence: default_local_captcha_min_confidence(),
+            collect_captcha_dataset: false,
+            captcha_dataset_dir: String::new(),
+            persistent_browser_profiles: false,
+            record_fixtures: false,
+            demo_mode: false,
+            duplicate_files: DuplicateFiles::Keep,
+            file_naming: FileNaming::default(),
+            event_throttle_ms: default_event_throttle_ms(),
+            event_throttle_invoices: 0,
+        }
+    }
+}
+
+impl DownloadConfig {
+    /// The config as JSON with the secrets left out, kept on the batch row
+    pub fn effective_json(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(config) = value.as_object_mut() {
+            config.remove("openai_api_key");
+            config.remove("openai_backup_api_keys");
+            if let Some(s3) = config.get_mut("s3").and_then(|s3| s3.as_object_mut()) {
+                s3.remove("secret_key");
+            }
+            if let Some(webdav) = config.get_mut("webdav").and_then(|webdav| webdav.as_object_mut()) {
+                webdav.remove("password");
+            }
+            if let Some(sftp) = config.get_mut("sftp").and_then(|sftp| sftp.as_object_mut()) {
+                sftp.remove("password");
+            }
+            if let Some(cloud) = config.get_mut("cloud").and_then(|cloud| cloud.as_object_mut()) {
+                cloud.remove("client_secret");
+            }
+        }
+        value.to_string()
+    }
+
+    pub fn browser_viewport(&self) -> BrowserViewport {
+        BrowserViewport {
+            width: self.browser_window_width,
+            height: self.browser_window_height,
+            device_scale_factor: self.browser_device_scale_factor,
+            zoom_percent: self.browser_zoom_percent,
+        }
+    }
+
+    pub fn http_timeouts(&self) -> HttpTimeouts {
+        HttpTimeouts {
+            connect: Duration::from_secs(self.http_connect_timeout_secs as u64),
+            request: Duration::from_secs(self.http_request_timeout_secs as u64),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct InvoiceDownloadRequest {
     pub id: String,
     pub code: String,
+    /// Needed for the tax-authority portal fallback and number lookups
+    #[serde(default)]
+    pub seller_tax_code: Option<String>,
+    #[serde(default)]
+    pub lookup_mode: LookupMode,
+    #[serde(default)]
+    pub invoice_series: Option<String>,
+    #[serde(default)]
+    pub invoice_number: Option<String>,
+    /// Portal written next to the invoice in the sheet; batches spanning
+    /// several portals are split into one sub-batch per portal
+    #[serde(default)]
+    pub portal_url: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct ProgressEvent {
-    pub batch_id: String,
-    pub current: u32,
-    pub total: u32,
-    pub percentage: u32,
+/// A downloaded PDF and the portal that served it
+pub struct DownloadedInvoice {
+    pub file_path: String,
+    pub portal: Portal,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct LogEvent {
-    pub batch_id: String,
-    pub timestamp: String,
-    pub level: String,
-    pub message: String,
+pub struct DownloadOrchestrator {
+    config: DownloadConfig,
+    batch_id: String,
+    captcha_solver: CaptchaSolver,
+    /// AI spend of the batch, also held by `captcha_solver`; none in demo mode
+    budget: Option<Arc<AiBudget>>,
+    http: HttpClients,
+    cancelled: Arc<AtomicBool>,
+    db: Arc<Database>,
+    /// Queues invoice changes for the database, shared with sub-batches
+    writes: Arc<StatusWriter>,
+    browser_pool: Arc<BrowserPool>,
+    manual_captchas: Arc<ManualCaptchas>,
+    /// Starts from the config, can be switched on while the batch runs
+    defer_manual_captchas: Arc<AtomicBool>,
+    /// Stream screenshots of the working tab, toggled by `stream_browser_preview`
+    preview: Arc<AtomicBool>,
+    /// Set when downloaded PDFs are uploaded to S3
+    storage: Option<S3Storage>,
+    parent: Option<ParentBatch>,
+    /// Shared with sub-batches, which report under this batch
+    events: Arc<EventThrottle>,
+    label: BatchLabel,
+    /// Crash-safe record of the invoice in progress, shared with sub-batches
+    journal: Option<Arc<BatchJournal>>,
+    /// Politeness profile of the portal host, when one matches
+    portal: Option<PortalProfile>,
+    /// Download slots per host, shared with every running batch
+    portal_slots: Arc<PortalSlots>,
+    /// Progress of the batch as a whole, updated by its sub-batches too
+    activity: Arc<Mutex<ActiveDownload>>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct InvoiceStatusEvent {
-    pub batch_id: String,
-    pub invoice_id: String,
-    pub status: String,
-    pub error: Option<String>,
-    pub file_path: Option<String>,
+/// What the user called a batch and where its invoices came from
+#[derive(Debug, Clone, Default)]
+pub struct BatchLabel {
+    pub name: Option<String>,
+    pub source_file: Option<String>,
+    pub source_path: Option<String>,
+    pub source_hash: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct CaptchaRequiredEvent {
-    pub batch_id: String,
-    pub invoice_id: String,
-    pub invoice_code: String,
-    pub image_base64: String,
+/// The batch a sub-batch was split from; the sub-batch reports its events
+/// under the parent, with progress counted across all sub-batches
+struct ParentBatch {
+    id: String,
+    /// Invoices in the sub-batches before this one
+    offset: u32,
+    total: u32,
 }
 
-pub struct DownloadOrchestrator {
+/// Everything the blocking worker needs to download one invoice
+struct InvoiceTask<R: Runtime = Wry> {
     config: DownloadConfig,
     batch_id: String,
+    invoice_id: String,
+    invoice_code: String,
+    seller_tax_code: Option<String>,
+    lookup_mode: LookupMode,
+    invoice_series: Option<String>,
+    invoice_number: Option<String>,
     captcha_solver: CaptchaSolver,
+    http: HttpClients,
     cancelled: Arc<AtomicBool>,
+    app: AppHandle<R>,
+    events: Arc<EventThrottle>,
+    db: Arc<Database>,
+    writes: Arc<StatusWriter>,
+    browser_pool: Arc<BrowserPool>,
+    manual_captchas: Arc<ManualCaptchas>,
+    defer_manual_captchas: Arc<AtomicBool>,
+    preview: Arc<AtomicBool>,
+    journal: Option<Arc<BatchJournal>>,
+    /// Another invoice follows, so preparing its captcha is worth it
+    lookahead: bool,
 }
 
+/// The next invoice's captcha being prepared on a second tab
+type Lookahead = std::thread::JoinHandle<(LookaheadTab, Result<PrefetchedCaptcha, AppError>)>;
+
 impl DownloadOrchestrator {
-    pub fn new(config: DownloadConfig, batch_id: String) -> Self {
-        let captcha_solver = CaptchaSolver::new(config.openai_api_key.clone());
+    pub fn new(
+        config: DownloadConfig,
+        batch_id: String,
+        db: Arc<Database>,
+        browser_pool: Arc<BrowserPool>,
+        manual_captchas: Arc<ManualCaptchas>,
+    ) -> Result<Self, AppError> {
+        let http = HttpClients::new(config.http_timeouts())?;
+        let budget = if config.demo_mode {
+            None
+        } else {
+            Some(Arc::new(AiBudget::new(
+                config.ai_budget_per_batch_usd,
+                config.ai_budget_per_month_usd,
+                Some(db.clone()),
+            )?))
+        };
+        let captcha_solver = match &budget {
+            Some(budget) => {
+                let solver = CaptchaSolver::new(config.openai_api_key.clone(), http.clone())
+                    .with_backup_keys(config.openai_backup_api_keys.clone())
+                    .with_budget(budget.clone());
+                match config.use_azure_openai {
+                    true => solver.with_azure(config.azure_openai.clone()),
+                    false => solver,
+                }
+            }
+            None => CaptchaSolver::from_answers(demo::captcha_answers(), http.clone()),
+        };
+        let events = Arc::new(EventThrottle::new(
+            config.event_throttle_ms,
+            config.event_throttle_invoices,
+        ));
+        // The automatic pass of a two-pass batch leaves manual captchas for later
+        let defer_manual_captchas = Arc::new(AtomicBool::new(
+            config.defer_manual_captchas || config.two_pass,
+        ));
+        let storage = config
+            .upload_to_s3
+            .then(|| S3Storage::new(config.s3.clone(), http.async_client().clone()));
+        let portal = portal_profile(&db, &config.vnpt_url);
+        let activity = ActiveDownload {
+            batch_id: batch_id.clone(),
+            batch_name: None,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            status: BatchStatus::Queued,
+            current: 0,
+            total: 0,
+            percentage: 0,
+            invoice_id: None,
+            invoice_code: None,
+        };
 
-        Self {
+        Ok(Self {
             config,
             batch_id,
             captcha_solver,
+            budget,
+            http,
             cancelled: Arc::new(AtomicBool::new(false)),
+            writes: Arc::new(StatusWriter::spawn(db.clone())),
+            db,
+            browser_pool,
+            manual_captchas,
+            defer_manual_captchas,
+            preview: Arc::new(AtomicBool::new(false)),
+            storage,
+            parent: None,
+            events,
+            label: BatchLabel::default(),
+            journal: None,
+            activity: Arc::new(Mutex::new(activity)),
+            portal,
+            portal_slots: Arc::new(PortalSlots::default()),
+        })
+    }
+
+    /// Journal the progress of the batch so it can be resumed after a crash
+    pub fn with_journal(mut self, journal: Arc<BatchJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Share download slots per portal host with the other running batches
+    pub fn with_portal_slots(mut self, portal_slots: Arc<PortalSlots>) -> Self {
+        self.portal_slots = portal_slots;
+        self
+    }
+
+    /// Read captchas with the offline `model` before asking the API
+    pub fn with_local_captcha_model(mut self, model: Arc<LocalCaptchaModel>) -> Self {
+        self.captcha_solver = self
+            .captcha_solver
+            .with_local_model(model, self.config.local_captcha_min_confidence);
+        self
+    }
+
+    /// Name the batch and record the file its invoices came from
+    pub fn with_source(mut self, label: BatchLabel) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Orchestrator for the `index`-th sub-batch, sharing this batch's cancellation
+    fn sub_batch(&self, index: usize, vnpt_url: String, offset: u32, total: u32) -> Self {
+        let portal = portal_profile(&self.db, &vnpt_url);
+        Self {
+            config: DownloadConfig {
+                vnpt_url,
+                ..self.config.clone()
+            },
+            batch_id: batch_split::sub_batch_id(&self.batch_id, index),
+            captcha_solver: self.captcha_solver.clone(),
+            budget: self.budget.clone(),
+            http: self.http.clone(),
+            cancelled: self.cancelled.clone(),
+            db: self.db.clone(),
+            writes: self.writes.clone(),
+            browser_pool: self.browser_pool.clone(),
+            manual_captchas: self.manual_captchas.clone(),
+            defer_manual_captchas: self.defer_manual_captchas.clone(),
+            preview: self.preview.clone(),
+            storage: self.storage.clone(),
+            parent: Some(ParentBatch {
+                id: self.batch_id.clone(),
+                offset,
+                total,
+            }),
+            events: self.events.clone(),
+            label: self.label.clone(),
+            journal: self.journal.clone(),
+            portal,
+            portal_slots: self.portal_slots.clone(),
+            activity: self.activity.clone(),
+        }
+    }
+
+    /// What the batch is doing right now
+    pub fn activity(&self) -> ActiveDownload {
+        let mut activity = self
+            .activity
+            .lock()
+            .map(|activity| activity.clone())
+            .unwrap_or_else(|e| e.into_inner().clone());
+        activity.batch_name = self.label.name.clone();
+        activity
+    }
+
+    /// The batch as far as it got, for a start request that arrives while
+    /// it runs
+    pub fn live_result(&self) -> Result<BatchResult, AppError> {
+        let activity = self.activity();
+        let invoices = self.db.get_batch_invoices(&self.batch_id)?;
+        Ok(BatchResult::from_invoices(
+            self.batch_id.clone(),
+            self.label.name.clone(),
+            activity.status,
+            activity.total,
+            invoices,
+        ))
+    }
+
+    fn update_activity(&self, update: impl FnOnce(&mut ActiveDownload)) {
+        if let Ok(mut activity) = self.activity.lock() {
+            update(&mut activity);
+        }
+    }
+
+    /// Pause between two invoices, no shorter than the portal asks for
+    fn invoice_delay(&self) -> Duration {
+        let floor = self.portal.as_ref().map_or(0, |portal| portal.base_delay_secs);
+        Duration::from_secs(self.config.invoice_delay_secs.max(floor) as u64)
+    }
+
+    /// Batch id that events are reported under
+    fn event_batch_id(&self) -> &str {
+        match &self.parent {
+            Some(parent) => &parent.id,
+            None => &self.batch_id,
         }
     }
 
@@ -81,6 +714,21 @@ impl DownloadOrchestrator {
         self.cancelled.store(true, Ordering::SeqCst);
     }
 
+    pub fn set_defer_manual_captchas(&self, defer: bool) {
+        self.defer_manual_captchas.store(defer, Ordering::SeqCst);
+    }
+
+    /// Let the batch spend past its AI budget
+    pub fn continue_over_budget(&self) {
+        if let Some(budget) = &self.budget {
+            budget.lift();
+        }
+    }
+
+    pub fn set_preview(&self, enabled: bool) {
+        self.preview.store(enabled, Ordering::SeqCst);
+    }
+
     /// Check if download has been cancelled
     fn is_cancelled(&self) -> bool {
         self.cancelled.load(Ordering::SeqCst)
@@ -91,45 +739,100 @@ impl DownloadOrchestrator {
         &self,
         app: &AppHandle,
         invoice: &InvoiceDownloadRequest,
-    ) -> Result<String, AppError> {
-        let config = self.config.clone();
-        let invoice_id = invoice.id.clone();
-        let invoice_code = invoice.code.clone();
-        let batch_id = self.batch_id.clone();
-        let captcha_solver = self.captcha_solver.clone();
-        let cancelled = self.cancelled.clone();
-        let app_handle = app.clone();
+        has_next: bool,
+    ) -> Result<DownloadedInvoice, AppError> {
+        let mut config = self.config.clone();
+        if let Some(portal) = &self.portal {
+            config.max_retries = config.max_retries.min(portal.max_retries);
+        }
+        let task = InvoiceTask {
+            config,
+            batch_id: self.event_batch_id().to_string(),
+            invoice_id: invoice.id.clone(),
+            invoice_code: invoice.code.clone(),
+            seller_tax_code: invoice.seller_tax_code.clone(),
+            lookup_mode: invoice.lookup_mode,
+            invoice_series: invoice.invoice_series.clone(),
+            invoice_number: invoice.invoice_number.clone(),
+            captcha_solver: self.captcha_solver.clone(),
+            http: self.http.clone(),
+            cancelled: self.cancelled.clone(),
+            app: app.clone(),
+            events: self.events.clone(),
+            db: self.db.clone(),
+            writes: self.writes.clone(),
+            browser_pool: self.browser_pool.clone(),
+            manual_captchas: self.manual_captchas.clone(),
+            defer_manual_captchas: self.defer_manual_captchas.clone(),
+            preview: self.preview.clone(),
+            journal: self.journal.clone(),
+            lookahead: self.config.captcha_lookahead && has_next,
+        };
 
-        // Run all browser operations in a blocking thread
+        // Run all browser operations in a blocking thread, logging under
+        // the span of this invoice; a panic there fails this invoice only
+        let span = tracing::Span::current();
         tokio::task::spawn_blocking(move || {
-            download_invoice_sync(
-                &config,
-                &batch_id,
-                &invoice_id,
-                &invoice_code,
-                &captcha_solver,
-                &cancelled,
-                &app_handle,
-            )
+            span.in_scope(|| {
+                panic_report::catch(|| download_invoice_sync(&task))
+                    .unwrap_or_else(|report| Err(report_panic(&task, &report)))
+            })
         })
         .await
         .map_err(|e| AppError::BrowserError(format!("Task panicked: {}", e)))?
     }
 
     /// Download multiple invoices
+    #[tracing::instrument(skip_all, fields(batch_id = %self.event_batch_id()))]
     pub async fn download_batch(
         &self,
         app: &AppHandle,
         invoices: Vec<InvoiceDownloadRequest>,
     ) -> Result<BatchResult, AppError> {
+        // Sub-batches run under the parent's ticker
+        let _ticker = match self.parent {
+            None => self.events.spawn_ticker(app),
+            Some(_) => None,
+        };
+
+        if self.parent.is_none() {
+            self.warn_if_source_downloaded(app);
+            let groups = batch_split::group_by_portal(&invoices, &self.config.vnpt_url);
+            if groups.len() > 1 {
+                return self.download_split_batch(app, groups).await;
+            }
+        }
+
         let total = invoices.len() as u32;
-        let mut success_count = 0u32;
-        let mut failed_count = 0u32;
         let mut results: Vec<InvoiceResult> = Vec::new();
 
+        let quiet_hours =
+            QuietHours::parse(&self.config.quiet_hours_start, &self.config.quiet_hours_end)?;
+
+        if let Err(e) = self.record_batch(&invoices) {
+            self.set_batch_status(app, BatchStatus::Failed);
+            return Err(e);
+        }
+        self.set_batch_status(app, BatchStatus::Running);
+        self.emit_debug(app, &format!("Effective config: {}", self.config.effective_json()));
+
+        if let Err(e) = self.http.check_reachable(&self.config.vnpt_url).await {
+            self.emit_log(
+                app,
+                "warn",
+                &format!("Portal {} did not respond: {}", self.config.vnpt_url, e),
+            );
+        }
+
         for (idx, invoice) in invoices.iter().enumerate() {
+            if let Some(quiet_hours) = quiet_hours {
+                self.wait_for_quiet_hours(app, quiet_hours).await;
+            }
+            self.enforce_budget(app).await;
+
             if self.is_cancelled() {
                 self.emit_log(app, "warn", "Download batch cancelled by user");
+                self.cancel_remaining(app, &invoices[idx..], &mut results);
                 break;
             }
 
@@ -138,62 +841,34 @@ impl DownloadOrchestrator {
             // Emit progress
             self.emit_progress(app, current, total);
 
-            // Update invoice status to downloading
-            self.emit_invoice_status(app, &invoice.id, "downloading", None, None);
-
-            self.emit_log(
-                app,
-                "info",
-                &format!("[{}/{}] Downloading: {}", current, total, invoice.code),
-            );
-
-            match self.download_invoice(app, invoice).await {
-                Ok(file_path) => {
-                    success_count += 1;
-                    self.emit_invoice_status(
-                        app,
-                        &invoice.id,
-                        "success",
-                        None,
-                        Some(file_path.clone()),
-                    );
-                    results.push(InvoiceResult {
-                        invoice_id: invoice.id.clone(),
-                        code: invoice.code.clone(),
-                        status: "success".to_string(),
-                        error: None,
-                        file_path: Some(file_path),
-                    });
-                }
-                Err(e) => {
-                    failed_count += 1;
-                    let error_msg = e.to_string();
-                    self.emit_invoice_status(
-                        app,
-                        &invoice.id,
-                        "failed",
-                        Some(error_msg.clone()),
-                        None,
-                    );
-                    results.push(InvoiceResult {
-                        invoice_id: invoice.id.clone(),
-                        code: invoice.code.clone(),
-                        status: "failed".to_string(),
-                        error: Some(error_msg),
-                        file_path: None,
-                    });
-                }
-            }
+            results.push(self.process_invoice(app, invoice, current, total).await);
 
-            // Small delay between downloads to avoid rate limiting
+            // Delay between downloads to avoid rate limiting
             if !self.is_cancelled() && idx < invoices.len() - 1 {
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                tokio::time::sleep(self.invoice_delay()).await;
             }
         }
 
+        if self.config.two_pass && !self.is_cancelled() {
+            self.run_interactive_pass(app, &invoices, &mut results).await;
+        }
+
         // Emit final progress
         self.emit_progress(app, total, total);
 
+        let count = |status: InvoiceStatus| results.iter().filter(|r| r.status == status).count() as u32;
+        let success_count = count(InvoiceStatus::Success);
+        // Not found is a failure of its own kind, counted with the others
+        let failed_count = count(InvoiceStatus::Failed) + count(InvoiceStatus::NotFound);
+
+        self.writes.flush().await;
+        if let Err(e) = self.db.refresh_batch_counts(&self.batch_id) {
+            self.emit_log(app, "warn", &format!("Failed to update batch history: {}", e));
+        }
+        if self.parent.is_none() {
+            self.announce_found_later(app);
+        }
+
         self.emit_log(
             app,
             "info",
@@ -203,266 +878,2472 @@ impl DownloadOrchestrator {
             ),
         );
 
-        Ok(BatchResult {
+        let status = if self.is_cancelled() {
+            BatchStatus::Cancelled
+        } else {
+            BatchStatus::Completed
+        };
+        self.set_batch_status(app, status);
+
+        // Have a browser ready for the next batch
+        if self.config.headless && !self.config.demo_mode && self.parent.is_none() {
+            self.browser_pool
+                .prewarm(self.config.vnpt_url.clone(), self.config.browser_viewport());
+        }
+
+        let result = BatchResult {
             batch_id: self.batch_id.clone(),
+            batch_name: self.label.name.clone(),
+            status,
             total,
             success_count,
             failed_count,
             results,
-        })
+        };
+        // A split batch is delivered once, with all its sub-batches
+        if self.parent.is_none() {
+            self.deliver_batch(app, &result).await;
+        }
+        Ok(result)
     }
 
-    // Event emission helpers
-    fn emit_progress(&self, app: &AppHandle, current: u32, total: u32) {
-        let percentage = if total > 0 {
-            (current as f32 / total as f32 * 100.0) as u32
-        } else {
-            0
+    /// Tell the user about invoices that were missing on an earlier run and
+    /// could be downloaded now
+    fn announce_found_later(&self, app: &AppHandle) {
+        let codes = match self.db.get_found_later_codes(&self.batch_id) {
+            Ok(codes) if !codes.is_empty() => codes,
+            _ => return,
         };
-
-        let _ = app.emit(
-            "download:progress",
-            ProgressEvent {
-                batch_id: self.batch_id.clone(),
-                current,
-                total,
-                percentage,
-            },
+        self.emit_log(
+            app,
+            "info",
+            &format!("{} invoices not found earlier are now available", codes.len()),
         );
-    }
-
-    fn emit_log(&self, app: &AppHandle, level: &str, message: &str) {
         let _ = app.emit(
-            "download:log",
-            LogEvent {
+            events::INVOICES_AVAILABLE,
+            InvoicesAvailableEvent {
                 batch_id: self.batch_id.clone(),
-                timestamp: chrono::Utc::now().to_rfc3339(),
-                level: level.to_string(),
-                message: message.to_string(),
+                codes,
             },
         );
     }
 
-    fn emit_invoice_status(
+    /// Download one invoice, reporting and recording its outcome
+    #[tracing::instrument(skip_all, fields(code = %invoice.code))]
+    async fn process_invoice(
         &self,
         app: &AppHandle,
-        invoice_id: &str,
-        status: &str,
-        error: Option<String>,
-        file_path: Option<String>,
-    ) {
-        let _ = app.emit(
-            "invoice:status",
-            InvoiceStatusEvent {
-                batch_id: self.batch_id.clone(),
-                invoice_id: invoice_id.to_string(),
-                status: status.to_string(),
-                error,
-                file_path,
-            },
-        );
-    }
-}
-
-/// Sync function to download a single invoice - runs in blocking thread
-fn download_invoice_sync(
-    config: &DownloadConfig,
-    batch_id: &str,
-    invoice_id: &str,
-    invoice_code: &str,
-    captcha_solver: &CaptchaSolver,
-    cancelled: &Arc<AtomicBool>,
-    app: &AppHandle,
-) -> Result<String, AppError> {
-    // Create browser instance
-    let browser = VnptBrowser::new(config.headless)?;
-
-    let result = download_invoice_with_retry_sync(
-        config,
-        batch_id,
-        invoice_id,
-        invoice_code,
-        captcha_solver,
-        cancelled,
-        app,
-        &browser,
-    );
-
-    // Browser will be dropped here in the blocking context - no panic
-    drop(browser);
+        invoice: &InvoiceDownloadRequest,
+        current: u32,
+        total: u32,
+    ) -> InvoiceResult {
+        if let Some(journal) = &self.journal {
+            let index = self.parent.as_ref().map_or(0, |parent| parent.offset) + current - 1;
+            if let Err(e) = journal.start_invoice(index, &invoice.id, &invoice.code) {
+                self.emit_log(app, "warn", &format!("Failed to write batch journal: {}", e));
+            }
+        }
 
-    result
-}
+        self.update_activity(|activity| {
+            activity.invoice_id = Some(invoice.id.clone());
+            activity.invoice_code = Some(invoice.code.clone());
+        });
 
-fn download_invoice_with_retry_sync(
-    config: &DownloadConfig,
-    batch_id: &str,
-    invoice_id: &str,
-    invoice_code: &str,
-    captcha_solver: &CaptchaSolver,
-    cancelled: &Arc<AtomicBool>,
-    app: &AppHandle,
-    browser: &VnptBrowser,
-) -> Result<String, AppError> {
-    for attempt in 1..=MAX_RETRIES {
-        if cancelled.load(Ordering::SeqCst) {
-            return Err(AppError::DownloadFailed("Download cancelled".to_string()));
-        }
+        // Update invoice status to downloading
+        self.emit_invoice_status(app, &invoice.id, InvoiceStatus::Downloading, None, None);
+        self.persist_invoice_status(&invoice.id, InvoiceStatus::Downloading, None, None);
 
-        emit_log_sync(
+        self.emit_log(
             app,
-            batch_id,
             "info",
-            &format!(
-                "Attempt {}/{} for invoice {}",
-                attempt, MAX_RETRIES, invoice_code
-            ),
+            &format!("[{}/{}] Downloading: {}", current, total, invoice.code),
         );
 
-        // Navigate to search page
-        browser.navigate_to_search(&config.vnpt_url)?;
-
-        // Fill invoice code
-        browser.fill_invoice_code(invoice_code)?;
-
-        // Get captcha screenshot
-        let captcha_image = browser.get_captcha_screenshot()?;
+        // Other batches on the same portal count against its concurrency
+        let slot = match &self.portal {
+            Some(portal) => self.portal_slots.acquire(portal).await,
+            None => None,
+        };
+        let downloaded = self.download_invoice(app, invoice, current < total).await;
+        drop(slot);
 
-        // Solve captcha with AI (blocking)
-        match captcha_solver.solve_blocking(&captcha_image) {
-            Ok(captcha_text) => {
-                emit_log_sync(
+        let result = match downloaded {
+            Ok(DownloadedInvoice { file_path, portal }) => {
+                self.emit_invoice_status(
                     app,
-                    batch_id,
-                    "info",
-                    &format!("Captcha solved: {}", captcha_text),
+                    &invoice.id,
+                    InvoiceStatus::Success,
+                    None,
+                    Some(file_path.clone()),
                 );
-
-                // Fill captcha
-                browser.fill_captcha(&captcha_text)?;
-
-                // Submit
-                browser.submit()?;
-
-                // Check for errors
-                if let Some(error) = browser.check_for_error() {
-                    emit_log_sync(app, batch_id, "warn", &format!("Page error: {}", error));
-
-                    // If captcha error, retry
-                    if error.to_lowercase().contains("captcha")
-                        || error.to_lowercase().contains("sai")
-                        || error.to_lowercase().contains("không đúng")
-                    {
-                        continue;
-                    }
-                }
-
-                // Try to download
-                match download_pdf_sync(config, browser, invoice_code) {
-                    Ok(file_path) => {
-                        emit_log_sync(
-                            app,
-                            batch_id,
-                            "info",
-                            &format!("Downloaded: {}", file_path),
-                        );
-                        return Ok(file_path);
-                    }
-                    Err(e) => {
-                        emit_log_sync(
-                            app,
-                            batch_id,
-                            "warn",
-                            &format!("Download failed: {}", e),
-                        );
-                    }
+                self.persist_invoice_status(
+                    &invoice.id,
+                    InvoiceStatus::Success,
+                    None,
+                    Some(&file_path),
+                );
+                self.writes.write(InvoiceWrite::Portal {
+                    invoice_id: invoice.id.clone(),
+                    portal,
+                });
+                self.import_invoice_xml(app, invoice, &file_path);
+                self.extract_invoice_metadata(app, invoice, &file_path).await;
+                self.optimize_invoice_pdf(app, invoice, &file_path).await;
+                self.stamp_invoice_pdf(app, invoice, &file_path).await;
+                let remote_url = self.upload_invoice(app, invoice, &file_path).await;
+                InvoiceResult {
+                    invoice_id: invoice.id.clone(),
+                    code: invoice.code.clone(),
+                    status: InvoiceStatus::Success,
+                    error: None,
+                    file_path: Some(file_path),
+                    portal: Some(portal),
+                    remote_url,
                 }
             }
             Err(e) => {
-                emit_log_sync(
+                // An invoice interrupted by cancellation did not fail on its own
+                let status = if self.is_cancelled() {
+                    InvoiceStatus::Cancelled
+                } else if matches!(e, AppError::ManualCaptchaSkipped(_)) {
+                    InvoiceStatus::Skipped
+                } else if matches!(e, AppError::NeedsManualCaptcha(_)) {
+                    InvoiceStatus::NeedsManual
+                } else if matches!(e, AppError::InvoiceNotFound(_)) {
+                    InvoiceStatus::NotFound
+                } else {
+                    InvoiceStatus::Failed
+                };
+                let error_msg = e.to_string();
+                self.emit_invoice_status(
                     app,
-                    batch_id,
-                    "warn",
-                    &format!("Captcha solving failed: {}", e),
+                    &invoice.id,
+                    status,
+                    Some(error_msg.clone()),
+                    None,
                 );
-
-                // Emit captcha required event for manual input
-                if attempt == MAX_RETRIES {
-                    let base64_image = base64::Engine::encode(
-                        &base64::engine::general_purpose::STANDARD,
-                        &captcha_image,
-                    );
-
-                    let _ = app.emit(
-                        "captcha:required",
-                        CaptchaRequiredEvent {
-                            batch_id: batch_id.to_string(),
-                            invoice_id: invoice_id.to_string(),
-                            invoice_code: invoice_code.to_string(),
-                            image_base64: base64_image,
-                        },
-                    );
+                self.persist_invoice_status(&invoice.id, status, Some(&error_msg), None);
+                InvoiceResult {
+                    invoice_id: invoice.id.clone(),
+                    code: invoice.code.clone(),
+                    status,
+                    error: Some(error_msg),
+                    file_path: None,
+                    portal: None,
+                    remote_url: None,
                 }
             }
+        };
+
+        if let Some(journal) = &self.journal {
+            let _ = journal.finish_invoice();
         }
+        self.update_activity(|activity| {
+            activity.invoice_id = None;
+            activity.invoice_code = None;
+        });
+        self.run_invoice_hook(app, &result).await;
+        result
     }
 
-    Err(AppError::CaptchaFailed(MAX_RETRIES))
-}
+    /// Parse the XML saved next to a downloaded PDF and store its content,
+    /// logging the problems validation found
+    fn import_invoice_xml(&self, app: &AppHandle, invoice: &InvoiceDownloadRequest, file_path: &str) {
+        if !self.config.download_xml {
+            return;
+        }
+        let xml_path = Path::new(file_path).with_extension("xml");
+        if !xml_path.is_file() {
+            return;
+        }
 
-fn download_pdf_sync(
-    config: &DownloadConfig,
-    browser: &VnptBrowser,
+        let imported = invoice_xml::parse_file(&xml_path).and_then(|parsed| {
+            self.db
+                .save_invoice_xml(&invoice.id, &xml_path.to_string_lossy(), &parsed)?;
+            Ok(parsed)
+        });
+        match imported {
+            Ok(parsed) if !parsed.is_valid() => self.emit_log(
+                app,
+                "warn",
+                &format!("{}: XML failed validation: {}", invoice.code, parsed.issues.join("; ")),
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                self.emit_log(app, "warn", &format!("{}: {}", invoice.code, e));
+                return;
+            }
+        }
+
+        if let Err(e) = self.verify_invoice_signatures(app, invoice, &xml_path) {
+            self.emit_log(app, "warn", &format!("{}: {}", invoice.code, e));
+        }
+    }
+
+    /// Read number, date and amounts from a downloaded PDF, with OCR when it
+    /// is a scan; missing fields are logged rather than silently left empty
+    async fn extract_invoice_metadata(
+        &self,
+        app: &AppHandle,
+        invoice: &InvoiceDownloadRequest,
+        file_path: &str,
+    ) {
+        let pdf_path = PathBuf::from(file_path);
+        let ocr = self.config.ocr_scanned_pdfs;
+        let tesseract = self.config.tesseract_path.clone();
+        let extracted = tokio::task::spawn_blocking(move || {
+            let text = pdf_metadata::extract_text(&pdf_path)?;
+            if !pdf_metadata::is_scanned(&text) {
+                return Ok(Some(pdf_metadata::extract_fields(&text, MetadataSource::Text)));
+            }
+            if !ocr {
+                return Ok(None);
+            }
+            let text = ocr::ocr_pdf(&tesseract, &pdf_path)?;
+            Ok(Some(pdf_metadata::extract_fields(&text, MetadataSource::Ocr)))
+        })
+        .await
+        .map_err(|e| AppError::IoError(format!("Metadata task panicked: {}", e)))
+        .and_then(|result| result);
+
+        match extracted {
+            Ok(Some(metadata)) => {
+                if metadata.is_empty() {
+                    self.emit_log(
+                        app,
+                        "warn",
+                        &format!("{}: no invoice number, date or total found in the PDF", invoice.code),
+                    );
+                }
+                if let Err(e) = self.db.save_invoice_metadata(&invoice.id, &metadata) {
+                    self.emit_log(app, "warn", &format!("{}: {}", invoice.code, e));
+                }
+            }
+            Ok(None) => self.emit_log(
+                app,
+                "warn",
+                &format!(
+                    "{}: the PDF is a scan without text; turn on OCR in Settings to read it",
+                    invoice.code
+                ),
+            ),
+            Err(e) => self.emit_log(app, "warn", &format!("{}: {}", invoice.code, e)),
+        }
+    }
+
+    /// Compress, and linearize if set, a downloaded PDF before it is uploaded
+    /// and record the outcome; the PDF as downloaded stays usable on failure
+    async fn optimize_invoice_pdf(
+        &self,
+        app: &AppHandle,
+        invoice: &InvoiceDownloadRequest,
+        file_path: &str,
+    ) {
+        if !self.config.optimize_pdfs {
+            return;
+        }
+        let pdf_path = PathBuf::from(file_path);
+        let keep_original = self.config.keep_original_pdfs;
+        let qpdf = self
+            .config
+            .linearize_pdfs
+            .then(|| self.config.qpdf_path.clone());
+        let optimized = tokio::task::spawn_blocking(move || {
+            pdf_optimize::optimize_file(&pdf_path, keep_original, qpdf.as_deref())
+        })
+        .await
+        .map_err(|e| AppError::IoError(format!("PDF optimization task panicked: {}", e)))
+        .and_then(|result| result);
+
+        match optimized {
+            Ok(optimization) => {
+                if let Some(reason) = &optimization.skipped {
+                    self.emit_debug(app, &format!("{}: PDF left as is: {}", invoice.code, reason));
+                }
+                if let Err(e) = self.db.save_pdf_optimization(&invoice.id, &optimization) {
+                    self.emit_log(app, "warn", &format!("{}: {}", invoice.code, e));
+                }
+            }
+            Err(e) => self.emit_log(app, "warn", &format!("{}: PDF optimization failed: {}", invoice.code, e)),
+        }
+    }
+
+    /// Stamp the processing date and batch on a downloaded PDF, which some
+    /// internal controls require before filing; failures are only logged
+    async fn stamp_invoice_pdf(
+        &self,
+        app: &AppHandle,
+        invoice: &InvoiceDownloadRequest,
+        file_path: &str,
+    ) {
+        if !self.config.stamp_pdfs {
+            return;
+        }
+        let pdf_path = PathBuf::from(file_path);
+        let batch_name = self.label.name.as_deref().unwrap_or(self.event_batch_id());
+        let text = pdf_stamp::stamp_text(chrono::Local::now().date_naive(), batch_name);
+        let stamped = tokio::task::spawn_blocking(move || pdf_stamp::stamp_file(&pdf_path, &text))
+            .await
+            .map_err(|e| AppError::IoError(format!("PDF stamp task panicked: {}", e)))
+            .and_then(|result| result);
+
+        if let Err(e) = stamped {
+            self.emit_log(app, "warn", &format!("{}: PDF not stamped: {}", invoice.code, e));
+        }
+    }
+
+    /// Verify the XML-DSig signatures of a saved invoice XML; an invalid
+    /// signature is logged as an error, as the invoice may have been tampered with
+    fn verify_invoice_signatures(
+        &self,
+        app: &AppHandle,
+        invoice: &InvoiceDownloadRequest,
+        xml_path: &Path,
+    ) -> Result<(), AppError> {
+        let checks = xml_signature::verify_file(xml_path)?;
+        let status = xml_signature::overall_status(&checks);
+        self.db.set_invoice_signatures(&invoice.id, status, &checks)?;
+
+        for check in checks.iter().filter(|check| !check.valid) {
+            self.emit_log(
+                app,
+                "error",
+                &format!(
+                    "{}: INVALID {} signature ({}): {}",
+                    invoice.code,
+                    check.signed_by,
+                    check.signer.as_deref().unwrap_or("unknown signer"),
+                    check.error.as_deref().unwrap_or_default()
+                ),
+            );
+        }
+        if status == SignatureStatus::Unsigned {
+            self.emit_log(app, "warn", &format!("{}: XML is not signed", invoice.code));
+        }
+        Ok(())
+    }
+
+    /// Upload a downloaded PDF when remote storage is configured; a failed
+    /// upload is logged and leaves the invoice downloaded
+    ///
+    /// # Returns
+    /// The object URL, when uploaded
+    async fn upload_invoice(
+        &self,
+        app: &AppHandle,
+        invoice: &InvoiceDownloadRequest,
+        file_path: &str,
+    ) -> Option<String> {
+        let storage = self.storage.as_ref()?;
+        let key = storage.object_key(self.event_batch_id(), file_path);
+
+        match storage.upload_file(&key, file_path).await {
+            Ok(url) => {
+                let _ = self.db.set_invoice_remote_url(&invoice.id, &url);
+                self.emit_log(app, "info", &format!("Uploaded {} to {}", invoice.code, url));
+                Some(url)
+            }
+            Err(e) => {
+                self.emit_log(app, "warn", &format!("{}: {}", invoice.code, e));
+                None
+            }
+        }
+    }
+
+    /// Second pass of a two-pass batch: the invoices the automatic pass left
+    /// for a manual captcha, asking the user for each one in turn
+    #[tracing::instrument(skip_all)]
+    async fn run_interactive_pass(
+        &self,
+        app: &AppHandle,
+        invoices: &[InvoiceDownloadRequest],
+        results: &mut [InvoiceResult],
+    ) {
+        let deferred: Vec<usize> = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.status == InvoiceStatus::NeedsManual)
+            .map(|(idx, _)| idx)
+            .collect();
+        if deferred.is_empty() {
+            return;
+        }
+
+        self.emit_log(
+            app,
+            "info",
+            &format!(
+                "Automatic pass done, {} invoices need a manual captcha",
+                deferred.len()
+            ),
+        );
+        self.set_defer_manual_captchas(false);
+
+        let total = deferred.len() as u32;
+        for (idx, &result_idx) in deferred.iter().enumerate() {
+            if self.is_cancelled() {
+                break;
+            }
+            let Some(invoice) = invoices
+                .iter()
+                .find(|invoice| invoice.id == results[result_idx].invoice_id)
+            else {
+                continue;
+            };
+
+            results[result_idx] = self.process_invoice(app, invoice, idx as u32 + 1, total).await;
+
+            if !self.is_cancelled() && idx + 1 < deferred.len() {
+                tokio::time::sleep(self.invoice_delay()).await;
+            }
+        }
+
+        // Sub-batches share the flag; the next one starts with its own automatic pass
+        self.set_defer_manual_captchas(true);
+    }
+
+    /// Download invoices from several portals as one sub-batch per portal,
+    /// run one after another under this batch
+    #[tracing::instrument(skip_all)]
+    async fn download_split_batch(
+        &self,
+        app: &AppHandle,
+        groups: Vec<PortalGroup>,
+    ) -> Result<BatchResult, AppError> {
+        let total: u32 = groups.iter().map(|group| group.invoices.len() as u32).sum();
+
+        if let Err(e) = self.record_batch_row(total) {
+            self.set_batch_status(app, BatchStatus::Failed);
+            return Err(e);
+        }
+        self.set_batch_status(app, BatchStatus::Running);
+        self.emit_log(
+            app,
+            "info",
+            &format!(
+                "Invoices span {} portals, splitting into sub-batches",
+                groups.len()
+            ),
+        );
+
+        let mut success_count = 0u32;
+        let mut failed_count = 0u32;
+        let mut results: Vec<InvoiceResult> = Vec::new();
+        let mut offset = 0u32;
+        let sub_batch_count = groups.len();
+
+        for (idx, group) in groups.into_iter().enumerate() {
+            let count = group.invoices.len() as u32;
+            self.emit_log(
+                app,
+                "info",
+                &format!(
+                    "Sub-batch {}/{}: {} invoices on {}",
+                    idx + 1,
+                    sub_batch_count,
+                    count,
+                    group.vnpt_url
+                ),
+            );
+
+            let sub_batch = self.sub_batch(idx, group.vnpt_url, offset, total);
+            // Boxed because `download_batch` is what called us
+            match Box::pin(sub_batch.download_batch(app, group.invoices)).await {
+                Ok(result) => {
+                    success_count += result.success_count;
+                    failed_count += result.failed_count;
+                    results.extend(result.results);
+                }
+                Err(e) => {
+                    self.emit_log(app, "error", &format!("Sub-batch {} failed: {}", idx + 1, e));
+                }
+            }
+
+            offset += count;
+        }
+
+        self.writes.flush().await;
+        if let Err(e) = self.db.refresh_batch_counts(&self.batch_id) {
+            self.emit_log(app, "warn", &format!("Failed to update batch history: {}", e));
+        }
+
+        let status = if self.is_cancelled() {
+            BatchStatus::Cancelled
+        } else {
+            BatchStatus::Completed
+        };
+        self.set_batch_status(app, status);
+
+        if self.config.headless && !self.config.demo_mode {
+            self.browser_pool
+                .prewarm(self.config.vnpt_url.clone(), self.config.browser_viewport());
+        }
+
+        let result = BatchResult {
+            batch_id: self.batch_id.clone(),
+            batch_name: self.label.name.clone(),
+            status,
+            total,
+            success_count,
+            failed_count,
+            results,
+        };
+        self.deliver_batch(app, &result).await;
+        Ok(result)
+    }
+
+    /// Hand a finished batch on: check it for anomalies, mirror it to the
+    /// cloud drive, then run the batch hook
+    #[tracing::instrument(skip_all)]
+    async fn deliver_batch(&self, app: &AppHandle, result: &BatchResult) {
+        self.report_anomalies(app);
+
+        let delivers = self.config.upload_to_webdav
+            || self.config.upload_to_sftp
+            || self.config.cloud.is_enabled();
+        let downloaded = downloaded_files(result);
+        if delivers && !downloaded.is_empty() {
+            let manifest_dir =
+                std::env::temp_dir().join(format!("autoinvoice-manifest-{}", self.batch_id));
+            let mut files: Vec<String> =
+                downloaded.iter().map(|(_, path)| path.to_string()).collect();
+            let batch_dir = Path::new(&self.config.download_directory);
+            match BatchManifest::build(&self.batch_id, batch_dir, &downloaded)
+                .and_then(|manifest| manifest.write_to(&manifest_dir))
+            {
+                Ok(written) => files
+                    .extend(written.iter().map(|path| path.to_string_lossy().to_string())),
+                Err(e) => self.emit_log(
+                    app,
+                    "warn",
+                    &format!("Failed to write checksum manifest: {}", e),
+                ),
+            }
+
+            if let Err(e) = self.upload_to_webdav(app, &files).await {
+                self.emit_log(app, "warn", &e.to_string());
+            }
+            if let Err(e) = self.deliver_over_sftp(app, &files).await {
+                self.emit_log(app, "warn", &e.to_string());
+            }
+            if let Err(e) = self.mirror_to_cloud(app, &files).await {
+                self.emit_log(app, "warn", &e.to_string());
+            }
+            let _ = std::fs::remove_dir_all(&manifest_dir);
+        }
+        self.run_batch_hook(app, result).await;
+    }
+
+    /// Log the suspicious invoices of the finished batch
+    fn report_anomalies(&self, app: &AppHandle) {
+        let facts = match self.db.get_invoice_facts(&self.batch_id) {
+            Ok(facts) => facts,
+            Err(e) => {
+                self.emit_log(app, "warn", &format!("Failed to check batch for anomalies: {}", e));
+                return;
+            }
+        };
+        for anomaly in anomalies::detect(&facts, chrono::Local::now().date_naive()) {
+            self.emit_log(
+                app,
+                "warn",
+                &format!("{}: {}", anomaly.invoice_code, anomaly.message),
+            );
+        }
+    }
+
+    /// Push the batch's files, as a folder or a zip, to the SFTP server
+    async fn deliver_over_sftp(&self, app: &AppHandle, files: &[String]) -> Result<(), AppError> {
+        if !self.config.upload_to_sftp {
+            return Ok(());
+        }
+
+        let files = files.to_vec();
+        let config = self.config.sftp.clone();
+        let batch_id = self.batch_id.clone();
+        let batch_dir = PathBuf::from(&self.config.download_directory);
+        let event_batch_id = self.event_batch_id().to_string();
+        let progress_app = app.clone();
+        let pin_app = app.clone();
+        let delivery = tokio::task::spawn_blocking(move || {
+            let pin_host_key = |fingerprint: &str| {
+                pin_app
+                    .state::<SettingsService>()
+                    .set_sftp_host_key_fingerprint(fingerprint)
+            };
+            let on_file = |file_name: &str, idx: usize, total: usize| {
+                let _ = progress_app.emit(
+                    events::UPLOAD_PROGRESS,
+                    UploadProgressEvent {
+                        batch_id: event_batch_id.clone(),
+                        target: "SFTP".to_string(),
+                        file_name: file_name.to_string(),
+                        current: idx as u32 + 1,
+                        total: total as u32,
+                    },
+                );
+            };
+            sftp::deliver_batch(&config, &batch_id, &batch_dir, &files, pin_host_key, on_file)
+        })
+        .await
+        .map_err(|e| AppError::UploadError(format!("SFTP task panicked: {}", e)))??;
+
+        if delivery.pinned_host_key {
+            self.emit_log(
+                app,
+                "info",
+                &format!(
+                    "Pinned the SFTP host key of {}: {}",
+                    self.config.sftp.host, delivery.host_key_fingerprint
+                ),
+            );
+        }
+        self.emit_log(
+            app,
+            "info",
+            &format!("Delivered batch to {}:{}", self.config.sftp.host, delivery.remote_path),
+        );
+        Ok(())
+    }
+
+    /// Upload the batch's files to a per-batch folder of the WebDAV share
+    async fn upload_to_webdav(&self, app: &AppHandle, files: &[String]) -> Result<(), AppError> {
+        if !self.config.upload_to_webdav {
+            return Ok(());
+        }
+
+        let storage =
+            WebDavStorage::new(self.config.webdav.clone(), self.http.async_client().clone());
+        let folder = storage.ensure_folder(&self.batch_id).await?;
+        let mut uploaded = 0;
+        for (idx, file_path) in files.iter().enumerate() {
+            self.emit_upload_progress(app, "WebDAV", file_path, idx, files.len());
+            match storage.upload_file(&folder, file_path).await {
+                Ok(_) => uploaded += 1,
+                Err(e) => self.emit_log(app, "warn", &e.to_string()),
+            }
+        }
+
+        self.emit_log(
+            app,
+            "info",
+            &format!("Uploaded {}/{} files to {}", uploaded, files.len(), folder),
+        );
+        Ok(())
+    }
+
+    /// Upload the batch's files to a folder named after it on the configured drive
+    async fn mirror_to_cloud(&self, app: &AppHandle, files: &[String]) -> Result<(), AppError> {
+        let config = &self.config.cloud;
+        if !config.is_enabled() {
+            return Ok(());
+        }
+
+        let refresh_token = self.db.get_cloud_refresh_token()?.ok_or_else(|| {
+            AppError::UploadError(format!(
+                "{} is not connected; sign in from Settings",
+                config.provider.label()
+            ))
+        })?;
+        let drive = CloudDrive::connect(self.http.async_client(), config, &refresh_token).await?;
+        if let Some(rotated) = &drive.rotated_refresh_token {
+            self.db.set_cloud_refresh_token(Some(rotated))?;
+        }
+
+        let folder = drive.ensure_folder(&config.folder, &self.batch_id).await?;
+        let mut uploaded = 0;
+        for (idx, file_path) in files.iter().enumerate() {
+            self.emit_upload_progress(app, config.provider.label(), file_path, idx, files.len());
+            match drive.upload_file(&folder, file_path).await {
+                Ok(()) => uploaded += 1,
+                Err(e) => self.emit_log(app, "warn", &e.to_string()),
+            }
+        }
+
+        self.emit_log(
+            app,
+            "info",
+            &format!(
+                "Mirrored {}/{} files to {}",
+                uploaded,
+                files.len(),
+                config.provider.label()
+            ),
+        );
+        Ok(())
+    }
+
+    fn emit_upload_progress(
+        &self,
+        app: &AppHandle,
+        target: &str,
+        file_path: &str,
+        idx: usize,
+        total: usize,
+    ) {
+        let _ = app.emit(
+            events::UPLOAD_PROGRESS,
+            UploadProgressEvent {
+                batch_id: self.event_batch_id().to_string(),
+                target: target.to_string(),
+                file_name: storage::file_name(file_path),
+                current: idx as u32 + 1,
+                total: total as u32,
+            },
+        );
+    }
+
+    async fn run_invoice_hook(&self, app: &AppHandle, result: &InvoiceResult) {
+        if self.config.post_download_hook_scope != HookScope::Invoice {
+            return;
+        }
+        let context = serde_json::json!({
+            "event": "invoice",
+            "batch_id": self.event_batch_id(),
+            "invoice": result,
+        });
+        self.run_post_download_hook(app, context).await;
+    }
+
+    async fn run_batch_hook(&self, app: &AppHandle, result: &BatchResult) {
+        if self.config.post_download_hook_scope != HookScope::Batch {
+            return;
+        }
+        let context = serde_json::json!({
+            "event": "batch",
+            "batch": result,
+        });
+        self.run_post_download_hook(app, context).await;
+    }
+
+    /// Run the user's hook off the async runtime; a failing hook is logged and
+    /// never fails the download
+    async fn run_post_download_hook(&self, app: &AppHandle, context: serde_json::Value) {
+        if self.config.post_download_hook.is_empty() {
+            return;
+        }
+
+        let path = self.config.post_download_hook.clone();
+        let outcome = tokio::task::spawn_blocking(move || {
+            hooks::run_hook(&path, &context, hooks::HOOK_TIMEOUT)
+        })
+        .await
+        .map_err(|e| AppError::DownloadFailed(format!("Hook task panicked: {}", e)))
+        .and_then(|result| result);
+
+        if let Err(e) = outcome {
+            self.emit_log(app, "warn", &format!("Post-download hook failed: {}", e));
+        }
+    }
+
+    /// Record the batch and its invoices in history, keeping rows from earlier runs
+    pub fn record_batch(&self, invoices: &[InvoiceDownloadRequest]) -> Result<(), AppError> {
+        self.record_batch_row(invoices.len() as u32)?;
+
+        for invoice in invoices {
+            if self.db.get_invoice(&invoice.id)?.is_none() {
+                self.db.create_invoice(&HistoryInvoice {
+                    id: invoice.id.clone(),
+                    batch_id: self.batch_id.clone(),
+                    code: invoice.code.clone(),
+                    status: InvoiceStatus::Pending,
+                    error: None,
+                    file_path: None,
+                    downloaded_at: None,
+                    repro_path: None,
+                    portal: None,
+                    remote_url: None,
+                    signature_status: None,
+                    note: None,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Warn when the same Excel file already has a completed batch
+    fn warn_if_source_downloaded(&self, app: &AppHandle) {
+        let Some(source_hash) = &self.label.source_hash else {
+            return;
+        };
+        match self.db.get_completed_batches_by_source(source_hash) {
+            Ok(batches) => {
+                if let Some(previous) = batches.iter().find(|batch| batch.id != self.batch_id) {
+                    self.emit_log(
+                        app,
+                        "warn",
+                        &format!(
+                            "This Excel file was already downloaded in batch {} ({})",
+                            previous.display_name(),
+                            previous.created_at
+                        ),
+                    );
+                }
+            }
+            Err(e) => self.emit_log(app, "warn", &e.to_string()),
+        }
+    }
+
+    /// Create the batch row unless it exists from an earlier run
+    fn record_batch_row(&self, total_count: u32) -> Result<(), AppError> {
+        if self.db.get_batch(&self.batch_id)?.is_none() {
+            self.db.create_batch(&DownloadBatch {
+                id: self.batch_id.clone(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                total_count,
+                success_count: 0,
+                failed_count: 0,
+                download_directory: self.config.download_directory.clone(),
+                status: BatchStatus::Queued,
+                parent_id: self.parent.as_ref().map(|parent| parent.id.clone()),
+                effective_config: Some(self.config.effective_json()),
+                name: self.label.name.clone(),
+                source_file: self.label.source_file.clone(),
+                source_path: self.label.source_path.clone(),
+                source_hash: self.label.source_hash.clone(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Pause the batch while inside quiet hours, waking periodically to honour cancellation
+    async fn wait_for_quiet_hours(&self, app: &AppHandle, quiet_hours: QuietHours) {
+        if !quiet_hours.contains(chrono::Local::now().time()) {
+            return;
+        }
+
+        self.set_batch_status(app, BatchStatus::Paused);
+        self.emit_log(
+            app,
+            "info",
+            &format!("Quiet hours, pausing until {}", quiet_hours.end()),
+        );
+
+        loop {
+            let now = chrono::Local::now().time();
+            if self.is_cancelled() || !quiet_hours.contains(now) {
+                break;
+            }
+            tokio::time::sleep(quiet_hours.remaining(now).min(QUIET_HOURS_POLL)).await;
+        }
+
+        if !self.is_cancelled() {
+            self.set_batch_status(app, BatchStatus::Running);
+            self.emit_log(app, "info", "Quiet hours over, resuming downloads");
+        }
+    }
+
+    /// Announce a reached AI budget once; when the batch pauses on it, wait
+    /// until `continue_over_budget` or cancellation, otherwise the solver
+    /// refuses API calls and the captchas are asked for by hand
+    async fn enforce_budget(&self, app: &AppHandle) {
+        let Some(budget) = &self.budget else {
+            return;
+        };
+        let Some(overrun) = budget.overrun() else {
+            return;
+        };
+        let action = self.config.ai_budget_action;
+
+        if budget.take_announcement() {
+            let scope = match overrun.scope {
+                BudgetScope::Batch => "batch",
+                BudgetScope::Month => "monthly",
+            };
+            let next = match action {
+                BudgetAction::ManualCaptcha => "solve the remaining captchas by hand",
+                BudgetAction::Pause => "pausing the batch",
+            };
+            self.emit_log(
+                app,
+                "warn",
+                &format!(
+                    "AI {} budget of ${:.2} reached (${:.4} spent), {}",
+                    scope, overrun.limit_usd, overrun.spent_usd, next
+                ),
+            );
+            self.events.flush(app);
+            let _ = app.emit(
+                events::BUDGET_EXCEEDED,
+                BudgetExceededEvent {
+                    batch_id: self.event_batch_id().to_string(),
+                    scope: overrun.scope,
+                    limit_usd: overrun.limit_usd,
+                    spent_usd: overrun.spent_usd,
+                    action,
+                },
+            );
+        }
+
+        if action != BudgetAction::Pause {
+            return;
+        }
+        self.set_batch_status(app, BatchStatus::Paused);
+        while !self.is_cancelled() && budget.overrun().is_some() {
+            tokio::time::sleep(BUDGET_POLL).await;
+        }
+        if !self.is_cancelled() {
+            self.set_batch_status(app, BatchStatus::Running);
+            self.emit_log(app, "info", "Continuing over the AI budget");
+        }
+    }
+
+    /// Persist and emit a batch lifecycle change
+    fn set_batch_status(&self, app: &AppHandle, status: BatchStatus) {
+        self.update_batch_status(app, &self.batch_id, status);
+        if self.parent.is_none() || matches!(status, BatchStatus::Running | BatchStatus::Paused) {
+            self.update_activity(|activity| activity.status = status);
+        }
+
+        // The parent follows the running sub-batch in and out of quiet hours
+        if let Some(parent) = &self.parent {
+            if matches!(status, BatchStatus::Running | BatchStatus::Paused) {
+                self.update_batch_status(app, &parent.id, status);
+            }
+        }
+    }
+
+    fn update_batch_status(&self, app: &AppHandle, batch_id: &str, status: BatchStatus) {
+        if let Err(e) = self.db.update_batch_status(batch_id, status) {
+            self.emit_log(app, "warn", &format!("Failed to update batch status: {}", e));
+        }
+
+        // Held-back events belong before the lifecycle change
+        self.events.flush(app);
+        let _ = app.emit(
+            events::BATCH_STATUS,
+            BatchStatusEvent {
+                batch_id: batch_id.to_string(),
+                batch_name: self.label.name.clone(),
+                status,
+            },
+        );
+    }
+
+    /// Mark invoices that were never started because the batch was cancelled
+    fn cancel_remaining(
+        &self,
+        app: &AppHandle,
+        remaining: &[InvoiceDownloadRequest],
+        results: &mut Vec<InvoiceResult>,
+    ) {
+        for invoice in remaining {
+            self.emit_invoice_status(app, &invoice.id, InvoiceStatus::Cancelled, None, None);
+            self.persist_invoice_status(&invoice.id, InvoiceStatus::Cancelled, None, None);
+            results.push(InvoiceResult {
+                invoice_id: invoice.id.clone(),
+                code: invoice.code.clone(),
+                status: InvoiceStatus::Cancelled,
+                error: None,
+                file_path: None,
+                portal: None,
+                remote_url: None,
+            });
+        }
+    }
+
+    /// Persist an invoice status change; history is best-effort and never fails the download
+    fn persist_invoice_status(
+        &self,
+        invoice_id: &str,
+        status: InvoiceStatus,
+        error: Option<&str>,
+        file_path: Option<&str>,
+    ) {
+        self.writes.write(InvoiceWrite::Status {
+            invoice_id: invoice_id.to_string(),
+            status,
+            error: error.map(str::to_string),
+            file_path: file_path.map(str::to_string),
+        });
+    }
+
+    // Event emission helpers
+    fn emit_progress(&self, app: &AppHandle, current: u32, total: u32) {
+        let (current, total) = match &self.parent {
+            Some(parent) => (parent.offset + current, parent.total),
+            None => (current, total),
+        };
+        let percentage = if total > 0 {
+            (current as f32 / total as f32 * 100.0) as u32
+        } else {
+            0
+        };
+        self.update_activity(|activity| {
+            activity.current = current;
+            activity.total = total;
+            activity.percentage = percentage;
+        });
+
+        self.events.progress(
+            app,
+            ProgressEvent {
+                batch_id: self.event_batch_id().to_string(),
+                current,
+                total,
+                percentage,
+            },
+        );
+    }
+
+    fn emit_log(&self, app: &AppHandle, level: &str, message: &str) {
+        tracing_log::trace_message(level, message);
+        self.events.log(
+            app,
+            LogEvent {
+                batch_id: self.event_batch_id().to_string(),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                level: level.to_string(),
+                message: message.to_string(),
+                request_id: tracing_log::current_request_id(),
+            },
+        );
+    }
+
+    fn emit_debug(&self, app: &AppHandle, message: &str) {
+        if self.config.debug_logging {
+            self.emit_log(app, "debug", message);
+        }
+    }
+
+    fn emit_invoice_status(
+        &self,
+        app: &AppHandle,
+        invoice_id: &str,
+        status: InvoiceStatus,
+        error: Option<String>,
+        file_path: Option<String>,
+    ) {
+        self.events.invoice_status(
+            app,
+            InvoiceStatusEvent {
+                batch_id: self.event_batch_id().to_string(),
+                invoice_id: invoice_id.to_string(),
+                status,
+                error,
+                file_path,
+            },
+        );
+    }
+}
+
+/// Sync function to download a single invoice - runs in blocking thread
+fn download_invoice_sync(task: &InvoiceTask) -> Result<DownloadedInvoice, AppError> {
+    if task.config.demo_mode {
+        return download_demo_invoice_sync(task);
+    }
+    let started = Instant::now();
+
+    // Reuse the warm browser when possible, otherwise launch a new one
+    let viewport = task.config.browser_viewport();
+    let parked = task
+        .browser_pool
+        .take(&task.config.vnpt_url, task.config.headless, &viewport);
+    let prewarmed = parked.is_some();
+    let (mut browser, prefetched) = match parked {
+        Some(ParkedBrowser { browser, captcha }) => (browser, captcha),
+        None => {
+            let profile = browser_profile(task)?;
+            (VnptBrowser::with_profile(task.config.headless, &viewport, profile)?, None)
+        }
+    };
+    if task.config.debug_logging {
+        emit_debug_sync(
+            task,
+            if prewarmed { "Using a prewarmed browser" } else { "Launched a new browser" },
+        );
+        if let Err(e) = browser.enable_debug_trace() {
+            emit_log_sync(task, "warn", &e.to_string());
+        }
+    }
+    browser.set_captcha_padding(task.config.captcha_padding_px);
+    let mut recorder = StepRecorder::with_journal(task.journal.clone());
+    let preview = PreviewStream::spawn(
+        task.app.clone(),
+        task.batch_id.clone(),
+        task.invoice_id.clone(),
+        browser.preview(),
+        task.preview.clone(),
+    );
+
+    if let Ok(app_data_dir) = task.app.path().app_data_dir() {
+        match SelectorConfig::load(&app_data_dir) {
+            Ok(selector_config) => browser.set_selector_overrides(selector_config.overrides),
+            Err(e) => emit_log_sync(
+                task,
+                "warn",
+                &format!("Ignoring selector overrides: {}", e),
+            ),
+        }
+    }
+
+    let mut lookahead = None;
+    let result = match fixture_dir(task) {
+        Some(dir) => {
+            // Every page the run goes through, so no captcha solved earlier
+            let mut recording = RecordingDriver::new(&mut browser, &task.invoice_code);
+            let result =
+                lookup_invoice_sync(task, &mut recording, &mut recorder, false, None, &mut lookahead);
+            match recording.save(&dir) {
+                Ok(dir) => emit_debug_sync(task, &format!("Fixture saved: {}", dir.display())),
+                Err(e) => emit_log_sync(task, "warn", &format!("Failed to save fixture: {}", e)),
+            }
+            result
+        }
+        None => lookup_with_relaunch_sync(
+            task,
+            &mut browser,
+            &mut recorder,
+            prewarmed,
+            prefetched,
+            &mut lookahead,
+        ),
+    };
+
+    if let Err(e) = &result {
+        if !task.cancelled.load(Ordering::SeqCst) {
+            save_repro_bundle(task, &browser, &recorder, &e.to_string());
+        }
+    }
+
+    // Timings feed `estimate_batch`; a cancelled run would skew them
+    if !task.cancelled.load(Ordering::SeqCst) {
+        let captcha_attempts = recorder.count("captcha_solved") + recorder.count("captcha_failed");
+        task.writes.write(InvoiceWrite::Metrics {
+            invoice_id: task.invoice_id.clone(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            captcha_attempts,
+        });
+
+        if task.config.collect_metrics {
+            let _ = task.db.record_stage_timings(&task.batch_id, &task.invoice_id, recorder.timings());
+        }
+    }
+
+    for line in browser.take_debug_trace() {
+        emit_debug_sync(task, &line);
+    }
+
+    // Stop capturing before the browser goes away
+    drop(preview);
+    match lookahead {
+        Some(lookahead) => park_lookahead(task, browser, lookahead),
+        // Browser will be dropped here in the blocking context - no panic
+        None => drop(browser),
+    }
+
+    result
+}
+
+/// Look the invoice up and, when the connection to Chrome dropped on the
+/// way, once more in a relaunched browser before giving up on it
+fn lookup_with_relaunch_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &mut B,
+    recorder: &mut StepRecorder,
+    prewarmed: bool,
+    prefetched: Option<PrefetchedCaptcha>,
+    lookahead: &mut Option<Lookahead>,
+) -> Result<DownloadedInvoice, AppError> {
+    let error = match lookup_invoice_sync(task, browser, recorder, prewarmed, prefetched, lookahead) {
+        Err(e) if browser::is_connection_lost(&e) && !task.cancelled.load(Ordering::SeqCst) => e,
+        result => return result,
+    };
+
+    emit_log_sync(
+        task,
+        "warn",
+        &format!(
+            "Lost the browser while downloading {} ({}), retrying in a new one",
+            task.invoice_code, error
+        ),
+    );
+    recorder.record(0, "browser_relaunched", Some(error.to_string()));
+    // A tab opened ahead went with the old browser
+    *lookahead = None;
+    if let Err(e) = browser.relaunch(task.config.headless) {
+        emit_log_sync(task, "warn", &format!("Failed to relaunch the browser: {}", e));
+        return Err(error);
+    }
+    lookup_invoice_sync(task, browser, recorder, false, None, lookahead)
+}
+
+/// Replay the demo portal's answer for the invoice, without a browser
+fn download_demo_invoice_sync(task: &InvoiceTask) -> Result<DownloadedInvoice, AppError> {
+    let app_data_dir = task
+        .app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    let dir = app_data_dir.join("demo").join(&task.invoice_id);
+    let fixture = demo::write_fixture(&dir, &task.invoice_code)?;
+
+    let mut browser = ReplayDriver::new(&dir, &fixture);
+    let mut recorder = StepRecorder::with_journal(task.journal.clone());
+    let result = lookup_invoice_sync(task, &mut browser, &mut recorder, false, None, &mut None);
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Look the invoice up, check a not-found answer again when enabled, and
+/// fall back to the tax-authority portal
+fn lookup_invoice_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &mut B,
+    recorder: &mut StepRecorder,
+    prewarmed: bool,
+    prefetched: Option<PrefetchedCaptcha>,
+    lookahead: &mut Option<Lookahead>,
+) -> Result<DownloadedInvoice, AppError> {
+    let mut result = match task.lookup_mode {
+        LookupMode::Code => download_invoice_with_retry_sync(
+            task,
+            browser,
+            recorder,
+            prewarmed,
+            prefetched,
+            lookahead,
+        )
+        .map(|file_path| DownloadedInvoice {
+            file_path,
+            portal: Portal::Vnpt,
+        }),
+        // VNPT only looks invoices up by lookup code
+        LookupMode::InvoiceNumber => download_by_number_sync(task, browser, recorder)
+            .map(|file_path| DownloadedInvoice {
+                file_path,
+                portal: Portal::TaxAuthority,
+            }),
+    };
+
+    if task.config.verify_not_found && matches!(result, Err(AppError::InvoiceNotFound(_))) {
+        let delay_secs = task.config.not_found_verify_delay_secs;
+        emit_log_sync(
+            task,
+            "info",
+            &format!(
+                "Invoice {} not found, checking again in {}s",
+                task.invoice_code, delay_secs
+            ),
+        );
+        recorder.record(0, "verify_not_found", Some(delay_secs.to_string()));
+        sleep_unless_cancelled(task, Duration::from_secs(delay_secs as u64));
+        if !task.cancelled.load(Ordering::SeqCst) {
+            result = match task.lookup_mode {
+                LookupMode::Code => download_invoice_with_retry_sync(
+                    task,
+                    browser,
+                    recorder,
+                    false,
+                    None,
+                    lookahead,
+                )
+                .map(|file_path| DownloadedInvoice {
+                    file_path,
+                    portal: Portal::Vnpt,
+                }),
+                LookupMode::InvoiceNumber => download_by_number_sync(task, browser, recorder)
+                    .map(|file_path| DownloadedInvoice {
+                        file_path,
+                        portal: Portal::TaxAuthority,
+                    }),
+            };
+        }
+    }
+
+    if let (LookupMode::Code, Err(AppError::InvoiceNotFound(_))) = (task.lookup_mode, &result) {
+        if let Some(seller_tax_code) = fallback_tax_code(task) {
+            emit_log_sync(
+                task,
+                "info",
+                &format!(
+                    "Invoice {} not found on VNPT, trying the tax-authority portal",
+                    task.invoice_code
+                ),
+            );
+            let fill_form = |browser: &B| {
+                browser.fill_portal_field("seller_tax_code", seller_tax_code)?;
+                browser.fill_invoice_code(&task.invoice_code)
+            };
+            result = download_from_tax_portal_sync(task, browser, recorder, fill_form)
+                .map(|file_path| DownloadedInvoice {
+                    file_path,
+                    portal: Portal::TaxAuthority,
+                });
+        }
+    }
+
+    result
+}
+
+/// Where the run of this invoice is recorded, when the batch records fixtures
+fn fixture_dir(task: &InvoiceTask) -> Option<PathBuf> {
+    if !task.config.record_fixtures {
+        return None;
+    }
+    let safe_code = task.invoice_code.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
+    match task.app.path().app_data_dir() {
+        Ok(dir) => Some(dir.join("fixtures").join(&task.batch_id).join(safe_code)),
+        Err(e) => {
+            emit_log_sync(task, "warn", &format!("Cannot locate app data directory for fixtures: {}", e));
+            None
+        }
+    }
+}
+
+/// Add a captcha the portal accepted to the local training dataset, when
+/// the user opted in; demo captchas are made up, so they are left out
+fn save_captcha_sample<R: Runtime>(
+    task: &InvoiceTask<R>,
+    portal_url: &str,
+    image: &[u8],
+    answer: &str,
+    source: CaptchaSource,
+) {
+    if !task.config.collect_captcha_dataset || task.config.demo_mode {
+        return;
+    }
+    let app_data_dir = match task.app.path().app_data_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            emit_log_sync(task, "warn", &format!("Cannot locate app data directory for the captcha dataset: {}", e));
+            return;
+        }
+    };
+    let dir = captcha_dataset::dataset_dir(&task.config.captcha_dataset_dir, &app_data_dir);
+    if let Err(e) = captcha_dataset::save_sample(&dir, portal_url, image, answer, source) {
+        emit_log_sync(task, "warn", &format!("Failed to save captcha sample: {}", e));
+    }
+}
+
+/// Chrome profile for a new browser: the portal's own when profiles are
+/// kept, otherwise a temporary one
+fn browser_profile(task: &InvoiceTask) -> Result<BrowserProfile, AppError> {
+    if !task.config.persistent_browser_profiles {
+        return BrowserProfile::temporary();
+    }
+    let app_data_dir = task
+        .app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    BrowserProfile::for_portal(&app_data_dir, &task.config.vnpt_url)
+}
+
+/// Profile of the portal at `url`, saved by the user or built in
+fn portal_profile(db: &Database, url: &str) -> Option<PortalProfile> {
+    let saved = db.get_portal_profiles().unwrap_or_default();
+    portal_profiles::resolve(&portal_profiles::merge(saved), url)
+}
+
+/// Load the lookup page on a second tab and solve its captcha on another
+/// thread, so the API call overlaps the current invoice's download
+fn start_lookahead<R: Runtime, B: BrowserDriver>(task: &InvoiceTask<R>, browser: &B) -> Option<Lookahead> {
+    let tab = match browser.open_lookahead_tab() {
+        Ok(tab) => tab,
+        Err(e) => {
+            emit_debug_sync(task, &format!("Captcha lookahead unavailable: {}", e));
+            return None;
+        }
+    };
+    let url = task.config.vnpt_url.clone();
+    let solver = task.captcha_solver.clone();
+
+    Some(std::thread::spawn(move || {
+        let prefetched = tab.prefetch_captcha(&url).and_then(|image| {
+            let (text, source) = solver.solve_blocking(&image)?;
+            Ok(PrefetchedCaptcha { image, text, source })
+        });
+        (tab, prefetched)
+    }))
+}
+
+/// Move the browser onto its lookahead tab and park it for the next invoice;
+/// a tab that never reached the lookup page is not worth keeping
+fn park_lookahead(task: &InvoiceTask, mut browser: VnptBrowser, lookahead: Lookahead) {
+    let Ok((tab, prefetched)) = lookahead.join() else {
+        return;
+    };
+    if task.cancelled.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let captcha = match prefetched {
+        Ok(captcha) => Some(captcha),
+        Err(AppError::BrowserError(e)) => {
+            emit_debug_sync(task, &format!("Captcha lookahead failed: {}", e));
+            return;
+        }
+        // The page loaded; the next invoice solves the captcha itself
+        Err(e) => {
+            emit_debug_sync(task, &format!("Captcha lookahead failed: {}", e));
+            None
+        }
+    };
+
+    if let Err(e) = browser.switch_to(tab) {
+        emit_debug_sync(task, &format!("Captcha lookahead failed: {}", e));
+        return;
+    }
+    task.browser_pool
+        .park(browser, &task.config.vnpt_url, task.config.headless, captcha);
+}
+
+/// Write the report of a panic in the download of an invoice to the app
+/// data dir and turn it into the invoice's error
+fn report_panic(task: &InvoiceTask, report: &PanicReport) -> AppError {
+    let written = task
+        .app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))
+        .and_then(|dir| {
+            panic_report::write_report(
+                &dir.join(panic_report::PANIC_DIR),
+                &task.batch_id,
+                &task.invoice_code,
+                report,
+            )
+        });
+    let error = report.to_error(written.as_ref().ok().map(PathBuf::as_path));
+    if let Err(e) = &written {
+        emit_log_sync(task, "warn", &format!("Failed to write panic report: {}", e));
+    }
+    emit_log_sync(task, "error", &error.to_string());
+    error
+}
+
+/// Write a repro bundle for a failed invoice and link it to the invoice record
+fn save_repro_bundle(
+    task: &InvoiceTask,
+    browser: &VnptBrowser,
+    recorder: &StepRecorder,
+    error: &str,
+) {
+    let bundle_dir = match task.app.path().app_data_dir() {
+        Ok(dir) => dir.join("repro"),
+        Err(e) => {
+            emit_log_sync(
+                task,
+                "warn",
+                &format!("Cannot locate app data directory for repro bundle: {}", e),
+            );
+            return;
+        }
+    };
+
+    match write_repro_bundle(
+        &bundle_dir,
+        &task.batch_id,
+        &task.invoice_code,
+        &task.config.vnpt_url,
+        error,
+        recorder,
+        browser,
+    ) {
+        Ok(path) => {
+            let path = path.to_string_lossy().to_string();
+            let _ = task.db.set_invoice_repro_path(&task.invoice_id, &path);
+            emit_log_sync(
+                task,
+                "info",
+                &format!("Repro bundle saved: {}", path),
+            );
+        }
+        Err(e) => {
+            emit_log_sync(
+                task,
+                "warn",
+                &format!("Failed to write repro bundle: {}", e),
+            );
+        }
+    }
+}
+
+fn download_invoice_with_retry_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &mut B,
+    recorder: &mut StepRecorder,
+    prewarmed: bool,
+    mut prefetched: Option<PrefetchedCaptcha>,
+    lookahead: &mut Option<Lookahead>,
+) -> Result<String, AppError> {
+    let invoice_code = task.invoice_code.as_str();
+    let mut last_error = None;
+
+    for attempt in 1..=task.config.max_retries {
+        if task.cancelled.load(Ordering::SeqCst) {
+            return Err(AppError::DownloadFailed("Download cancelled".to_string()));
+        }
+
+        if attempt > 1 {
+            recycle_if_over_memory(task, browser, recorder, attempt)?;
+        }
+
+        emit_log_sync(
+            task,
+            "info",
+            &format!(
+                "Attempt {}/{} for invoice {}",
+                attempt, task.config.max_retries, invoice_code
+            ),
+        );
+
+        // A captcha solved during the previous invoice is only valid on the first attempt
+        let prefetched = prefetched.take().filter(|_| attempt == 1);
+        let outcome =
+            attempt_download_sync(task, browser, recorder, attempt, prewarmed, prefetched, lookahead);
+        let error = match outcome {
+            Ok(Some(file_path)) => return Ok(file_path),
+            // Captcha rejected or unsolved: a fresh one right away
+            Ok(None) => continue,
+            Err(e) => e,
+        };
+
+        // Not found, skipped or misconfigured stays so however many captchas
+        // are spent on it, and a dead browser fails every attempt
+        if error.class() == ErrorClass::Permanent || browser::is_connection_lost(&error) {
+            return Err(error);
+        }
+        emit_log_sync(task, "warn", &format!("Attempt {} failed: {}", attempt, error));
+        recorder.record(attempt, "transient_error", Some(error.to_string()));
+        if attempt < task.config.max_retries {
+            pause_before_retry(task, attempt);
+        }
+        last_error = Some(error);
+    }
+
+    Err(last_error.unwrap_or(AppError::CaptchaFailed(task.config.max_retries)))
+}
+
+/// Base pause after a transient failure, doubled on every further attempt
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Longest pause between two attempts at the same invoice
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Wait out the backoff after attempt `attempt`
+fn pause_before_retry<R: Runtime>(task: &InvoiceTask<R>, attempt: u32) {
+    let pause = (RETRY_BACKOFF * 2u32.saturating_pow(attempt.saturating_sub(1))).min(MAX_RETRY_BACKOFF);
+    emit_debug_sync(task, &format!("Retrying in {}s", pause.as_secs()));
+    sleep_unless_cancelled(task, pause);
+}
+
+/// Block for `pause`, waking early when the batch is cancelled
+fn sleep_unless_cancelled<R: Runtime>(task: &InvoiceTask<R>, pause: Duration) {
+    let deadline = Instant::now() + pause;
+    while Instant::now() < deadline && !task.cancelled.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(250).min(deadline - Instant::now()));
+    }
+}
+
+/// One pass through the lookup form: `Ok(None)` when the portal rejected the
+/// captcha and a fresh one should be tried
+#[tracing::instrument(skip_all, fields(attempt = attempt))]
+fn attempt_download_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    recorder: &mut StepRecorder,
+    attempt: u32,
+    prewarmed: bool,
+    prefetched: Option<PrefetchedCaptcha>,
+    lookahead: &mut Option<Lookahead>,
+) -> Result<Option<String>, AppError> {
+    let invoice_code = task.invoice_code.as_str();
+
+    // Navigate to search page; a warm browser is already there on the first attempt
+    if attempt == 1 && prewarmed {
+        recorder.record(attempt, "prewarmed", Some(task.config.vnpt_url.clone()));
+    } else {
+        recorder.record(attempt, "navigate", Some(task.config.vnpt_url.clone()));
+        let started = Instant::now();
+        browser.navigate_to_search(&task.config.vnpt_url)?;
+        recorder.record_timing(TimingStage::Navigation, started);
+    }
+
+    // Fill invoice code
+    recorder.record(attempt, "fill_invoice_code", Some(invoice_code.to_string()));
+    with_selector_repair(task, browser, recorder, attempt, "invoice_input", || {
+        browser.fill_invoice_code(invoice_code)
+    })?;
+
+    let (captcha_image, solved) = match prefetched {
+        Some(PrefetchedCaptcha { image, text, source }) => {
+            emit_debug_sync(task, "Using the captcha solved ahead");
+            (image, Ok((text, source)))
+        }
+        None => {
+            // Get captcha screenshot
+            let captcha_image =
+                with_selector_repair(task, browser, recorder, attempt, "captcha_image", || {
+                    browser.get_captcha_screenshot()
+                })?;
+
+            // Solve captcha, locally or with AI (blocking)
+            let started = Instant::now();
+            let solved = task.captcha_solver.solve_blocking(&captcha_image);
+            recorder.record_timing(TimingStage::CaptchaSolve, started);
+            (captcha_image, solved)
+        }
+    };
+    recorder.set_captcha_image(&captcha_image);
+
+    let (captcha_text, captcha_source) = match solved {
+        Ok((captcha_text, captcha_source)) => {
+            emit_log_sync(
+                task,
+                "info",
+                &format!("Captcha solved: {}", captcha_text),
+            );
+            recorder.record(attempt, "captcha_solved", Some(captcha_text.clone()));
+            (captcha_text, captcha_source)
+        }
+        Err(e) => {
+            emit_log_sync(
+                task,
+                "warn",
+                &format!("Captcha solving failed: {}", e),
+            );
+            recorder.record(attempt, "captcha_failed", Some(e.to_string()));
+
+            let over_budget = matches!(e, AppError::BudgetExceeded(_));
+            if attempt < task.config.max_retries && !over_budget {
+                return Ok(None);
+            }
+            // Last attempt, or no AI budget left: ask the user instead of giving up
+            let captcha_text = wait_for_manual_captcha(task, &captcha_image)?;
+            recorder.record(attempt, "captcha_manual", Some(captcha_text.clone()));
+            (captcha_text, CaptchaSource::Manual)
+        }
+    };
+
+    // Fill captcha
+    with_selector_repair(task, browser, recorder, attempt, "captcha_input", || {
+        browser.fill_captcha(&captcha_text)
+    })?;
+
+    // Submit
+    recorder.record(attempt, "submit", None);
+    let started = Instant::now();
+    with_selector_repair(task, browser, recorder, attempt, "submit_button", || {
+        browser.submit()
+    })?;
+    recorder.record_timing(TimingStage::Submit, started);
+
+    // Check for errors
+    if let Some(error) = browser.check_for_error() {
+        emit_log_sync(task, "warn", &format!("Page error: {}", error));
+        recorder.record(attempt, "page_error", Some(error.clone()));
+
+        // If captcha error, retry
+        if error.to_lowercase().contains("captcha")
+            || error.to_lowercase().contains("sai")
+            || error.to_lowercase().contains("không đúng")
+        {
+            return Ok(None);
+        }
+
+        if is_not_found_message(&error) {
+            return Err(AppError::InvoiceNotFound(format!("{}: {}", invoice_code, error.trim())));
+        }
+    }
+
+    save_captcha_sample(task, &task.config.vnpt_url, &captcha_image, &captcha_text, captcha_source);
+
+    // The captcha was accepted; loading the next one earlier could replace
+    // it in the shared session before the portal checked it
+    if task.lookahead && lookahead.is_none() {
+        *lookahead = start_lookahead(task, browser);
+    }
+
+    // Try to download
+    let started = Instant::now();
+    let downloaded = download_pdf_sync(
+        &task.config,
+        &task.http,
+        &task.db,
+        browser,
+        &task.config.vnpt_url,
+        invoice_code,
+        task.seller_tax_code.as_deref(),
+    );
+    recorder.record_timing(TimingStage::Download, started);
+
+    let file_path = downloaded?;
+    emit_log_sync(
+        task,
+        "info",
+        &format!("Downloaded: {}", file_path),
+    );
+    download_xml_sync(task, browser, &task.config.vnpt_url, &file_path);
+    download_converted_sync(task, browser, &task.config.vnpt_url, &file_path);
+    Ok(Some(file_path))
+}
+
+/// Ask the user to solve the captcha and wait for `submit_manual_captcha`;
+/// the invoice is skipped when no answer arrives in time, and left for later
+/// right away when the batch defers manual captchas
+fn wait_for_manual_captcha<R: Runtime>(
+    task: &InvoiceTask<R>,
+    captcha_image: &[u8],
+) -> Result<String, AppError> {
+    if task.defer_manual_captchas.load(Ordering::SeqCst) {
+        return Err(AppError::NeedsManualCaptcha(task.invoice_code.clone()));
+    }
+
+    let captcha = PendingCaptcha {
+        batch_id: task.batch_id.clone(),
+        invoice_id: task.invoice_id.clone(),
+        invoice_code: task.invoice_code.clone(),
+        image_base64: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, captcha_image),
+        requested_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let event = CaptchaRequiredEvent {
+        batch_id: captcha.batch_id.clone(),
+        invoice_id: captcha.invoice_id.clone(),
+        invoice_code: captcha.invoice_code.clone(),
+        image_base64: captcha.image_base64.clone(),
+    };
+
+    let rx = task.manual_captchas.register(captcha);
+    // Held-back status events go out first so the UI shows the invoice as waiting
+    task.events.flush(&task.app);
+    let _ = task.app.emit(events::CAPTCHA_REQUIRED, event);
+
+    let timeout_secs = task.config.manual_captcha_timeout_secs;
+    emit_log_sync(
+        task,
+        "warn",
+        &format!(
+            "Waiting up to {}s for a manual captcha for invoice {}",
+            timeout_secs, task.invoice_code
+        ),
+    );
+
+    let answer = task.manual_captchas.wait_blocking(
+        &task.invoice_id,
+        rx,
+        Duration::from_secs(timeout_secs as u64),
+        &task.cancelled,
+    );
+    match answer {
+        Some(ManualAnswer::Captcha(captcha_text)) => Ok(captcha_text),
+        Some(ManualAnswer::Defer) => Err(AppError::NeedsManualCaptcha(task.invoice_code.clone())),
+        Some(ManualAnswer::Skip) | None => {
+            Err(AppError::ManualCaptchaSkipped(task.invoice_code.clone()))
+        }
+    }
+}
+
+/// Whether the invoice should be retried on the tax-authority portal, and with which tax code
+fn fallback_tax_code<R: Runtime>(task: &InvoiceTask<R>) -> Option<&str> {
+    if !task.config.fallback_to_tax_portal {
+        return None;
+    }
+
+    let tax_code = task
+        .seller_tax_code
+        .as_deref()
+        .map(str::trim)
+        .filter(|code| !code.is_empty());
+
+    if tax_code.is_none() {
+        emit_log_sync(
+            task,
+            "warn",
+            &format!(
+                "No seller tax code for {}, skipping the tax-authority portal",
+                task.invoice_code
+            ),
+        );
+    }
+
+    tax_code
+}
+
+/// Look the invoice up on the tax-authority portal by series, number and seller tax code
+fn download_by_number_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    recorder: &mut StepRecorder,
+) -> Result<String, AppError> {
+    let field = |value: &Option<String>, name: &str| {
+        value.clone().filter(|v| !v.trim().is_empty()).ok_or_else(|| {
+            AppError::ConfigError(format!(
+                "Missing {} for number lookup of invoice {}",
+                name, task.invoice_code
+            ))
+        })
+    };
+    let seller_tax_code = field(&task.seller_tax_code, "seller tax code")?;
+    let series = field(&task.invoice_series, "invoice series")?;
+    let number = field(&task.invoice_number, "invoice number")?;
+
+    download_from_tax_portal_sync(task, browser, recorder, |browser| {
+        browser.fill_portal_field("seller_tax_code", &seller_tax_code)?;
+        browser.fill_portal_field("invoice_series", &series)?;
+        browser.fill_portal_field("invoice_number", &number)
+    })
+}
+
+/// Look the invoice up on the tax-authority portal; `fill_form` enters the lookup fields
+fn download_from_tax_portal_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    recorder: &mut StepRecorder,
+    fill_form: impl Fn(&B) -> Result<(), AppError>,
+) -> Result<String, AppError> {
+    // The browser goes back to the pool for VNPT lookups afterwards
+    let vnpt_overrides = browser.set_selector_overrides(tax_portal::selector_overrides());
+    let result = tax_portal_attempts(task, browser, recorder, fill_form);
+    browser.set_selector_overrides(vnpt_overrides);
+    result
+}
+
+fn tax_portal_attempts<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    recorder: &mut StepRecorder,
+    fill_form: impl Fn(&B) -> Result<(), AppError>,
+) -> Result<String, AppError> {
+    let invoice_code = task.invoice_code.as_str();
+    let portal_url = task.config.tax_portal_url.as_str();
+
+    for attempt in 1..=task.config.max_retries {
+        if task.cancelled.load(Ordering::SeqCst) {
+            return Err(AppError::DownloadFailed("Download cancelled".to_string()));
+        }
+
+        emit_log_sync(
+            task,
+            "info",
+            &format!(
+                "Tax portal attempt {}/{} for invoice {}",
+                attempt, task.config.max_retries, invoice_code
+            ),
+        );
+
+        recorder.record(attempt, "tax_portal_navigate", Some(portal_url.to_string()));
+        let started = Instant::now();
+        browser.navigate_to_search(portal_url)?;
+        recorder.record_timing(TimingStage::Navigation, started);
+
+        fill_form(browser)?;
+
+        let captcha_image = browser.get_captcha_screenshot()?;
+        recorder.set_captcha_image(&captcha_image);
+
+        let started = Instant::now();
+        let solved = task.captcha_solver.solve_blocking(&captcha_image);
+        recorder.record_timing(TimingStage::CaptchaSolve, started);
+
+        let (captcha_text, captcha_source) = match solved {
+            Ok(solved) => solved,
+            Err(e @ AppError::BudgetExceeded(_)) => {
+                emit_log_sync(task, "warn", &format!("Captcha solving failed: {}", e));
+                recorder.record(attempt, "captcha_failed", Some(e.to_string()));
+                (wait_for_manual_captcha(task, &captcha_image)?, CaptchaSource::Manual)
+            }
+            Err(e) => {
+                emit_log_sync(task, "warn", &format!("Captcha solving failed: {}", e));
+                recorder.record(attempt, "captcha_failed", Some(e.to_string()));
+                continue;
+            }
+        };
+        recorder.record(attempt, "captcha_solved", Some(captcha_text.clone()));
+
+        browser.fill_captcha(&captcha_text)?;
+        recorder.record(attempt, "submit", None);
+        let started = Instant::now();
+        browser.submit()?;
+        recorder.record_timing(TimingStage::Submit, started);
+
+        if let Some(error) = browser.check_for_error() {
+            emit_log_sync(task, "warn", &format!("Tax portal error: {}", error));
+            recorder.record(attempt, "page_error", Some(error.clone()));
+
+            if is_not_found_message(&error) {
+                return Err(AppError::InvoiceNotFound(format!(
+                    "{} (also not on the tax-authority portal)",
+                    invoice_code
+                )));
+            }
+            continue;
+        }
+        save_captcha_sample(task, portal_url, &captcha_image, &captcha_text, captcha_source);
+
+        let started = Instant::now();
+        let downloaded = download_pdf_sync(
+            &task.config,
+            &task.http,
+            &task.db,
+            browser,
+            portal_url,
+            invoice_code,
+            task.seller_tax_code.as_deref(),
+        );
+        recorder.record_timing(TimingStage::Download, started);
+
+        match downloaded {
+            Ok(file_path) => {
+                emit_log_sync(
+                    task,
+                    "info",
+                    &format!("Downloaded from tax portal: {}", file_path),
+                );
+                download_xml_sync(task, browser, portal_url, &file_path);
+                download_converted_sync(task, browser, portal_url, &file_path);
+                return Ok(file_path);
+            }
+            Err(e) => {
+                emit_log_sync(task, "warn", &format!("Download failed: {}", e));
+                recorder.record(attempt, "download_failed", Some(e.to_string()));
+            }
+        }
+    }
+
+    Err(AppError::CaptchaFailed(task.config.max_retries))
+}
+
+fn is_not_found_message(error: &str) -> bool {
+    let error = error.to_lowercase();
+    NOT_FOUND_MARKERS.iter().any(|marker| error.contains(marker))
+}
+
+/// Relaunch Chrome when it grew past the configured memory ceiling, so long
+/// retries on a leaky portal page cannot exhaust RAM
+fn recycle_if_over_memory<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &mut B,
+    recorder: &mut StepRecorder,
+    attempt: u32,
+) -> Result<(), AppError> {
+    let limit = task.config.max_browser_memory_mb as u64;
+    if limit == 0 {
+        return Ok(());
+    }
+
+    let used = match browser.memory_usage_mb() {
+        Some(used) if used > limit => used,
+        _ => return Ok(()),
+    };
+
+    emit_log_sync(
+        task,
+        "warn",
+        &format!(
+            "Browser uses {} MB (limit {} MB), restarting it",
+            used, limit
+        ),
+    );
+    recorder.record(attempt, "browser_recycled", Some(format!("{} MB", used)));
+
+    browser.relaunch(task.config.headless)
+}
+
+/// Run a browser step and, if none of the selectors for `field` match anymore,
+/// ask the LLM for a replacement selector and retry the step once.
+///
+/// The download link is deliberately not repaired here: it is also missing
+/// whenever the lookup itself fails, which is not a selector problem.
+fn with_selector_repair<T, R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    recorder: &mut StepRecorder,
+    attempt: u32,
+    field: &str,
+    step: impl Fn() -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let missing = match step() {
+        Err(AppError::ElementNotFound(missing)) => missing,
+        other => return other,
+    };
+
+    emit_log_sync(
+        task,
+        "warn",
+        &format!("No selector matched {}, asking AI for a replacement", missing),
+    );
+
+    match repair_selector(&task.captcha_solver, browser, field) {
+        Ok(selector) => {
+            emit_log_sync(
+                task,
+                "info",
+                &format!("Selector repaired for {}: {}", field, selector),
+            );
+            recorder.record(
+                attempt,
+                "selector_repaired",
+                Some(format!("{}: {}", field, selector)),
+            );
+            persist_selector_override(task, field, &selector);
+
+            step()
+        }
+        Err(e) => {
+            emit_log_sync(
+                task,
+                "warn",
+                &format!("Selector repair failed for {}: {}", field, e),
+            );
+            Err(AppError::ElementNotFound(missing))
+        }
+    }
+}
+
+/// Save a repaired selector so later runs use it straight away
+fn persist_selector_override<R: Runtime>(task: &InvoiceTask<R>, field: &str, selector: &str) {
+    let result = task
+        .app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::IoError(e.to_string()))
+        .and_then(|app_data_dir| {
+            let mut selector_config = SelectorConfig::load(&app_data_dir)?;
+            selector_config.add_override(field, selector);
+            selector_config.save(&app_data_dir)
+        });
+
+    if let Err(e) = result {
+        emit_log_sync(
+            task,
+            "warn",
+            &format!("Failed to save repaired selector: {}", e),
+        );
+    }
+}
+
+fn download_pdf_sync<B: BrowserDriver>(
+    config: &DownloadConfig,
+    http: &HttpClients,
+    db: &Database,
+    browser: &B,
+    base_url: &str,
     invoice_code: &str,
+    seller_tax_code: Option<&str>,
 ) -> Result<String, AppError> {
-    // Get PDF bytes
-    let pdf_bytes = browser.download_pdf(&config.vnpt_url)?;
+    // Seller and number for the name templates, from the page the PDF link is on
+    let page = if config.file_naming.uses_page_fields() {
+        browser.page_html().map(|html| file_naming::scrape_page(&html)).unwrap_or_else(|e| {
+            tracing::warn!("Could not read the result page of {}: {}", invoice_code, e);
+            PageFields::default()
+        })
+    } else {
+        PageFields::default()
+    };
+
+    // Get PDF bytes; the converted one only replaces the original when asked
+    let client = http.blocking()?;
+    let pdf_bytes = match config.pdf_variant {
+        PdfVariant::Converted => browser.download_converted_pdf(base_url, &client).or_else(|e| {
+            tracing::warn!("No converted PDF for {}, keeping the original: {}", invoice_code, e);
+            browser.download_pdf(base_url, &client)
+        })?,
+        PdfVariant::Original | PdfVariant::Both => browser.download_pdf(base_url, &client)?,
+    };
 
     if pdf_bytes.is_empty() {
         return Err(AppError::DownloadFailed("Empty PDF received".to_string()));
     }
 
-    // Create filename from invoice code
-    let safe_code = invoice_code.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
-    let filename = format!("{}.pdf", safe_code);
+    // Name the file from the templates, the invoice code by default
+    let relative_path = config.file_naming.relative_path(&NameValues {
+        code: invoice_code,
+        seller_tax_code,
+        page: &page,
+    });
 
     // Ensure download directory exists
     let download_path = PathBuf::from(&config.download_directory);
     std::fs::create_dir_all(&download_path)?;
 
-    // Save file
-    let file_path = download_path.join(&filename);
-    std::fs::write(&file_path, &pdf_bytes)?;
+    // Save file, never outside the batch's folder
+    let file_path = path_policy::ensure_within(&download_path, &download_path.join(&relative_path))?;
+    if let Some(folder) = file_path.parent() {
+        std::fs::create_dir_all(folder)?;
+    }
+    let existing = match config.duplicate_files {
+        DuplicateFiles::Keep => None,
+        _ => dedupe::find_identical(&pdf_bytes, &db.get_downloaded_files(invoice_code)?),
+    };
+    let file_path = dedupe::save(&pdf_bytes, &file_path, existing.as_deref(), config.duplicate_files)?;
 
     Ok(file_path.to_string_lossy().to_string())
 }
 
-fn emit_log_sync(app: &AppHandle, batch_id: &str, level: &str, message: &str) {
-    let _ = app.emit(
-        "download:log",
+/// Save the invoice XML next to its PDF when enabled; the PDF counts as the
+/// download, so a missing XML is only logged
+fn download_xml_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    base_url: &str,
+    pdf_path: &str,
+) {
+    if !task.config.download_xml {
+        return;
+    }
+
+    let saved = task
+        .http
+        .blocking()
+        .and_then(|client| browser.download_xml(base_url, &client))
+        .and_then(|xml| {
+            std::fs::write(Path::new(pdf_path).with_extension("xml"), xml).map_err(AppError::from)
+        });
+    if let Err(e) = saved {
+        emit_log_sync(task, "warn", &format!("XML download failed: {}", e));
+    }
+}
+
+/// Save the converted, printable PDF next to the original when both are
+/// kept; portals without one only get a warning
+fn download_converted_sync<R: Runtime, B: BrowserDriver>(
+    task: &InvoiceTask<R>,
+    browser: &B,
+    base_url: &str,
+    pdf_path: &str,
+) {
+    if task.config.pdf_variant != PdfVariant::Both {
+        return;
+    }
+
+    let saved = task
+        .http
+        .blocking()
+        .and_then(|client| browser.download_converted_pdf(base_url, &client))
+        .and_then(|pdf| {
+            std::fs::write(pdf_fetch::converted_path(Path::new(pdf_path)), pdf).map_err(AppError::from)
+        });
+    if let Err(e) = saved {
+        emit_log_sync(task, "warn", &format!("Converted PDF download failed: {}", e));
+    }
+}
+
+fn emit_log_sync<R: Runtime>(task: &InvoiceTask<R>, level: &str, message: &str) {
+    tracing_log::trace_message(level, message);
+    task.events.log(
+        &task.app,
         LogEvent {
-            batch_id: batch_id.to_string(),
+            batch_id: task.batch_id.clone(),
             timestamp: chrono::Utc::now().to_rfc3339(),
             level: level.to_string(),
             message: message.to_string(),
+            request_id: tracing_log::current_request_id(),
         },
     );
 }
 
-#[derive(Debug, Clone, Serialize)]
+fn emit_debug_sync<R: Runtime>(task: &InvoiceTask<R>, message: &str) {
+    if task.config.debug_logging {
+        emit_log_sync(task, "debug", message);
+    }
+}
+
+/// `(invoice code, file path)` of every invoice of the batch that has a PDF
+fn downloaded_files(result: &BatchResult) -> Vec<(&str, &str)> {
+    result
+        .results
+        .iter()
+        .filter_map(|invoice| Some((invoice.code.as_str(), invoice.file_path.as_deref()?)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
 pub struct InvoiceResult {
     pub invoice_id: String,
     pub code: String,
-    pub status: String,
+    pub status: InvoiceStatus,
     pub error: Option<String>,
     pub file_path: Option<String>,
+    pub portal: Option<Portal>,
+    pub remote_url: Option<String>,
+}
+
+/// A running batch, for a reloaded UI to re-attach to
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct ActiveDownload {
+    pub batch_id: String,
+    pub batch_name: Option<String>,
+    pub started_at: String,
+    pub status: BatchStatus,
+    /// Invoices reached so far, across sub-batches
+    pub current: u32,
+    pub total: u32,
+    pub percentage: u32,
+    /// Invoice being downloaded, if any
+    pub invoice_id: Option<String>,
+    pub invoice_code: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Type)]
 pub struct BatchResult {
     pub batch_id: String,
+    pub batch_name: Option<String>,
+    pub status: BatchStatus,
     pub total: u32,
     pub success_count: u32,
     pub failed_count: u32,
     pub results: Vec<InvoiceResult>,
 }
+
+impl BatchResult {
+    /// Result of a batch from its invoices in the history
+    pub fn from_invoices(
+        batch_id: String,
+        batch_name: Option<String>,
+        status: BatchStatus,
+        total: u32,
+        invoices: Vec<HistoryInvoice>,
+    ) -> Self {
+        let count = |status: InvoiceStatus| invoices.iter().filter(|i| i.status == status).count() as u32;
+        let success_count = count(InvoiceStatus::Success);
+        let failed_count = count(InvoiceStatus::Failed) + count(InvoiceStatus::NotFound);
+        let results = invoices
+            .into_iter()
+            .map(|invoice| InvoiceResult {
+                invoice_id: invoice.id,
+                code: invoice.code,
+                status: invoice.status,
+                error: invoice.error,
+                file_path: invoice.file_path,
+                portal: invoice.portal,
+                remote_url: invoice.remote_url,
+            })
+            .collect();
+        Self {
+            batch_id,
+            batch_name,
+            status,
+            total,
+            success_count,
+            failed_count,
+            results,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::browser_driver::mock::{MockDriver, Outcome};
+    use crate::services::fixtures::PortalFixture;
+    use sha2::{Digest, Sha256};
+    use tauri::test::{mock_app, MockRuntime};
+
+    /// Invoice task on the mock runtime, without an API key so solving a
+    /// captcha fails at once
+    fn task(
+        app: &tauri::App<MockRuntime>,
+        dir: &Path,
+        max_retries: u32,
+    ) -> InvoiceTask<MockRuntime> {
+        let config = DownloadConfig {
+            vnpt_url: "https://example-tt78.vnpt-invoice.com.vn/".to_string(),
+            download_directory: dir.join("downloads").to_string_lossy().to_string(),
+            max_retries,
+            ..DownloadConfig::default()
+        };
+        let http = HttpClients::new(config.http_timeouts()).unwrap();
+        let db = Arc::new(Database::new(dir.join("data")).unwrap());
+        InvoiceTask {
+            batch_id: uuid::Uuid::new_v4().to_string(),
+            invoice_id: uuid::Uuid::new_v4().to_string(),
+            invoice_code: "C24TAA0000001_Ab".to_string(),
+            seller_tax_code: None,
+            lookup_mode: LookupMode::Code,
+            invoice_series: None,
+            invoice_number: None,
+            captcha_solver: CaptchaSolver::new(String::new(), http.clone()),
+            http,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            app: app.handle().clone(),
+            events: Arc::new(EventThrottle::new(0, 0)),
+            writes: Arc::new(StatusWriter::spawn(db.clone())),
+            db,
+            browser_pool: Arc::new(BrowserPool::default()),
+            manual_captchas: Arc::new(ManualCaptchas::default()),
+            defer_manual_captchas: Arc::new(AtomicBool::new(true)),
+            preview: Arc::new(AtomicBool::new(false)),
+            journal: None,
+            lookahead: false,
+            config,
+        }
+    }
+
+    fn solved_ahead() -> Option<PrefetchedCaptcha> {
+        Some(PrefetchedCaptcha {
+            image: Vec::new(),
+            text: "a1b2".to_string(),
+            source: CaptchaSource::Ai,
+        })
+    }
+
+    fn run(
+        task: &InvoiceTask<MockRuntime>,
+        browser: &mut MockDriver,
+        prefetched: Option<PrefetchedCaptcha>,
+    ) -> Result<String, AppError> {
+        let mut recorder = StepRecorder::with_journal(None);
+        download_invoice_with_retry_sync(task, browser, &mut recorder, false, prefetched, &mut None)
+    }
+
+    #[test]
+    fn test_retry_stops_at_success_or_permanent_error() {
+        let app = mock_app();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let mut browser = MockDriver::new([Outcome::Found]);
+        let file_path = run(&task(&app, dir, 3), &mut browser, solved_ahead()).unwrap();
+        assert!(Path::new(&file_path).is_file());
+
+        // Looking the invoice up again would not make it exist
+        let mut browser = MockDriver::new([Outcome::PageError("Không tồn tại hóa đơn có mã tra cứu này")]);
+        let err = run(&task(&app, dir, 3), &mut browser, solved_ahead()).unwrap_err();
+        assert!(matches!(err, AppError::InvoiceNotFound(_)));
+        assert_eq!(browser.count("navigate"), 1);
+
+        // A rejected captcha gets a fresh one, then the page stops loading
+        let mut browser = MockDriver::new([Outcome::PageError("Mã captcha không đúng")]);
+        let err = run(&task(&app, dir, 2), &mut browser, solved_ahead()).unwrap_err();
+        assert!(matches!(err, AppError::BrowserError(_)));
+        assert_eq!(browser.count("navigate"), 2);
+
+        // An unsolved captcha on the last attempt is left for the user
+        let mut browser = MockDriver::new([Outcome::Found]);
+        let err = run(&task(&app, dir, 1), &mut browser, None).unwrap_err();
+        assert!(matches!(err, AppError::NeedsManualCaptcha(_)));
+        assert_eq!(browser.count("submit"), 0);
+    }
+
+    #[test]
+    fn test_relaunch_after_lost_connection() {
+        let app = mock_app();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let task = task(&app, dir, 3);
+        let lookup = |browser: &mut MockDriver| {
+            let mut recorder = StepRecorder::with_journal(None);
+            lookup_with_relaunch_sync(&task, browser, &mut recorder, false, solved_ahead(), &mut None)
+        };
+
+        // Chrome crashed once: the invoice goes through in a new browser
+        let mut browser = MockDriver::new([Outcome::ConnectionLost, Outcome::Found]);
+        let downloaded = lookup(&mut browser);
+        assert!(downloaded.is_ok(), "{:?}", downloaded.err());
+        assert_eq!(browser.count("relaunch"), 1);
+
+        // Only once, and without spending the other attempts on a dead browser
+        let mut browser = MockDriver::new([Outcome::ConnectionLost, Outcome::ConnectionLost]);
+        let err = lookup(&mut browser).unwrap_err();
+        assert!(browser::is_connection_lost(&err));
+        assert_eq!(browser.count("relaunch"), 1);
+        assert_eq!(browser.count("navigate"), 2);
+    }
+
+    #[test]
+    fn test_converted_pdf_falls_back_to_original() {
+        let app = mock_app();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        // The mock portal has no converted PDF: the original is kept instead
+        let mut converted = task(&app, dir, 3);
+        converted.config.pdf_variant = PdfVariant::Converted;
+        let mut browser = MockDriver::new([Outcome::Found]);
+        let file_path = run(&converted, &mut browser, solved_ahead()).unwrap();
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"%PDF-1.4 mock");
+        assert_eq!(browser.count("download_converted_pdf"), 1);
+
+        // Both: the original still counts as the download
+        let mut both = task(&app, dir, 3);
+        both.config.pdf_variant = PdfVariant::Both;
+        let mut browser = MockDriver::new([Outcome::Found]);
+        let file_path = run(&both, &mut browser, solved_ahead()).unwrap();
+        assert!(Path::new(&file_path).is_file());
+        assert!(!pdf_fetch::converted_path(Path::new(&file_path)).exists());
+        assert_eq!(browser.count("download_converted_pdf"), 1);
+    }
+
+    #[test]
+    fn test_tax_portal_restores_selector_overrides() {
+        let app = mock_app();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let task = task(&app, dir, 1);
+
+        let browser = MockDriver::new([Outcome::Found]);
+        let vnpt = std::collections::HashMap::from([("invoice_code".to_string(), vec!["#strCode".to_string()])]);
+        browser.set_selector_overrides(vnpt.clone());
+
+        // Fails without an API key, and the VNPT selectors are back either way
+        let mut recorder = StepRecorder::with_journal(None);
+        let looked_up = download_from_tax_portal_sync(&task, &browser, &mut recorder, |_| Ok(()));
+        assert!(matches!(looked_up, Err(AppError::CaptchaFailed(1))));
+        assert_eq!(browser.count("navigate"), 1);
+        assert_eq!(*browser.selector_overrides.lock().unwrap(), vnpt);
+    }
+
+    #[test]
+    fn test_cancel_during_backoff() {
+        let app = mock_app();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let task = task(&app, dir, 5);
+
+        let cancelled = task.cancelled.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            cancelled.store(true, Ordering::SeqCst);
+        });
+
+        let started = Instant::now();
+        let mut browser = MockDriver::new([Outcome::NavigationFails]);
+        let err = run(&task, &mut browser, None).unwrap_err();
+        canceller.join().unwrap();
+
+        assert!(err.to_string().contains("cancelled"));
+        assert_eq!(browser.count("navigate"), 1);
+        assert!(started.elapsed() < RETRY_BACKOFF);
+    }
+
+    #[test]
+    fn test_replay_recorded_fixture() {
+        let app = mock_app();
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let fixture_dir = dir.join("fixture");
+
+        // Record a run where the first captcha is rejected
+        let mut recording_task = task(&app, dir, 3);
+        let mock_captcha = hex::encode(Sha256::digest([0x89, b'P', b'N', b'G']));
+        recording_task.captcha_solver = CaptchaSolver::from_answers(
+            std::collections::HashMap::from([(mock_captcha, "a1b2".to_string())]),
+            recording_task.http.clone(),
+        );
+        let mut browser = MockDriver::new([Outcome::PageError("Mã captcha không đúng"), Outcome::Found]);
+        let mut recording = RecordingDriver::new(&mut browser, &recording_task.invoice_code);
+        let mut recorder = StepRecorder::with_journal(None);
+        let recorded =
+            lookup_invoice_sync(&recording_task, &mut recording, &mut recorder, false, None, &mut None);
+        assert!(recorded.is_ok());
+        recording.save(&fixture_dir).unwrap();
+
+        // Replay it without the mock, answering captchas from the fixture
+        let fixture = PortalFixture::load(&fixture_dir).unwrap();
+        assert_eq!(fixture.lookups.len(), 2);
+        let mut replay_task = task(&app, &dir.join("replay"), 3);
+        replay_task.captcha_solver =
+            CaptchaSolver::from_answers(fixture.captcha_answers(), replay_task.http.clone());
+        let mut replay = ReplayDriver::new(&fixture_dir, &fixture);
+        let mut recorder = StepRecorder::with_journal(None);
+        let replayed =
+            lookup_invoice_sync(&replay_task, &mut replay, &mut recorder, false, None, &mut None).unwrap();
+
+        assert_eq!(recorder.count("page_error"), 1);
+        assert_eq!(std::fs::read(replayed.file_path).unwrap(), b"%PDF-1.4 mock");
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Serialize;
+use specta::Type;
+
+/// Fewer finished invoices than this and the defaults below are used instead
+const MIN_SAMPLE_SIZE: u32 = 5;
+
+/// Per-invoice defaults used until enough history has been recorded
+const DEFAULT_INVOICE_SECS: f64 = 20.0;
+const DEFAULT_CAPTCHA_ATTEMPTS: f64 = 1.5;
+
+/// Approximate gpt-4o-mini price of one captcha image request, in USD
+const COST_PER_CAPTCHA_USD: f64 = 0.0005;
+
+/// Aggregates over recently finished invoices
+#[derive(Debug, Clone, Default)]
+pub struct InvoiceStats {
+    pub sample_size: u32,
+    pub success_count: u32,
+    pub avg_duration_ms: f64,
+    pub avg_captcha_attempts: f64,
+    pub total_captcha_attempts: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct BatchEstimate {
+    pub invoice_count: u32,
+    pub estimated_duration_secs: u64,
+    pub estimated_cost_usd: f64,
+    pub avg_invoice_secs: f64,
+    pub avg_captcha_attempts: f64,
+    /// Share of captcha attempts that led to a downloaded invoice
+    pub captcha_solve_rate: Option<f64>,
+    pub sample_size: u32,
+    /// False when there was too little history and defaults were used
+    pub from_history: bool,
+}
+
+/// Predict duration and OpenAI cost of downloading `invoice_count` invoices
+pub fn estimate_batch(
+    stats: &InvoiceStats,
+    invoice_count: u32,
+    invoice_delay_secs: u32,
+) -> BatchEstimate {
+    let from_history = stats.sample_size >= MIN_SAMPLE_SIZE;
+
+    let (avg_invoice_secs, avg_captcha_attempts) = if from_history {
+        (stats.avg_duration_ms / 1000.0, stats.avg_captcha_attempts)
+    } else {
+        (DEFAULT_INVOICE_SECS, DEFAULT_CAPTCHA_ATTEMPTS)
+    };
+
+    let captcha_solve_rate = if from_history && stats.total_captcha_attempts > 0 {
+        Some(stats.success_count as f64 / stats.total_captcha_attempts as f64)
+    } else {
+        None
+    };
+
+    let count = invoice_count as f64;
+    let delays = invoice_count.saturating_sub(1) as f64 * invoice_delay_secs as f64;
+
+    BatchEstimate {
+        invoice_count,
+        estimated_duration_secs: (count * avg_invoice_secs + delays).round() as u64,
+        estimated_cost_usd: count * avg_captcha_attempts * COST_PER_CAPTCHA_USD,
+        avg_invoice_secs,
+        avg_captcha_attempts,
+        captcha_solve_rate,
+        sample_size: stats.sample_size,
+        from_history,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_from_history() {
+        let stats = InvoiceStats {
+            sample_size: 10,
+            success_count: 8,
+            avg_duration_ms: 12_000.0,
+            avg_captcha_attempts: 2.0,
+            total_captcha_attempts: 20,
+        };
+
+        let estimate = estimate_batch(&stats, 100, 2);
+        assert!(estimate.from_history);
+        assert_eq!(estimate.estimated_duration_secs, 100 * 12 + 99 * 2);
+        assert!((estimate.estimated_cost_usd - 0.1).abs() < 1e-9);
+        assert_eq!(estimate.captcha_solve_rate, Some(0.4));
+    }
+
+    #[test]
+    fn test_estimate_without_history() {
+        let estimate = estimate_batch(&InvoiceStats::default(), 0, 2);
+        assert!(!estimate.from_history);
+        assert_eq!(estimate.estimated_duration_secs, 0);
+        assert_eq!(estimate.captcha_solve_rate, None);
+    }
+}
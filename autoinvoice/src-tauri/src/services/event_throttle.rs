@@ -0,0 +1,163 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::events::{
+    self, InvoiceStatusBatchEvent, InvoiceStatusEvent, LogBatchEvent, LogEvent, ProgressEvent,
+};
+
+pub const DEFAULT_EVENT_THROTTLE_MS: u32 = 250;
+
+/// Events held back since the last flush
+#[derive(Default)]
+struct Pending {
+    progress: Option<ProgressEvent>,
+    /// Latest status per invoice, in the order the invoices first changed
+    statuses: Vec<InvoiceStatusEvent>,
+    logs: Vec<LogEvent>,
+    /// Invoices that reached a final status since the last flush
+    finished_invoices: u32,
+    last_flush: Option<Instant>,
+}
+
+/// Coalesces the progress, status and log events of a batch so huge batches
+/// do not flood the IPC channel. Held-back events go out together at most
+/// every `interval` or every `every_invoices` finished invoices, whichever
+/// comes first; with both at zero every event is emitted straight away.
+pub struct EventThrottle {
+    interval: Duration,
+    every_invoices: u32,
+    pending: Mutex<Pending>,
+}
+
+impl EventThrottle {
+    pub fn new(interval_ms: u32, every_invoices: u32) -> Self {
+        Self {
+            interval: Duration::from_millis(interval_ms as u64),
+            every_invoices,
+            pending: Mutex::new(Pending::default()),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.interval.is_zero() || self.every_invoices > 0
+    }
+
+    pub fn progress<R: Runtime>(&self, app: &AppHandle<R>, event: ProgressEvent) {
+        if !self.enabled() {
+            let _ = app.emit(events::DOWNLOAD_PROGRESS, event);
+            return;
+        }
+
+        self.pending.lock().unwrap().progress = Some(event);
+        self.flush_if_due(app);
+    }
+
+    pub fn log<R: Runtime>(&self, app: &AppHandle<R>, event: LogEvent) {
+        if !self.enabled() {
+            let _ = app.emit(events::DOWNLOAD_LOG, event);
+            return;
+        }
+
+        self.pending.lock().unwrap().logs.push(event);
+        self.flush_if_due(app);
+    }
+
+    pub fn invoice_status<R: Runtime>(&self, app: &AppHandle<R>, event: InvoiceStatusEvent) {
+        if !self.enabled() {
+            let _ = app.emit(events::INVOICE_STATUS, event);
+            return;
+        }
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if event.status.is_final() {
+                pending.finished_invoices += 1;
+            }
+            match pending
+                .statuses
+                .iter_mut()
+                .find(|pending| pending.invoice_id == event.invoice_id)
+            {
+                Some(pending) => *pending = event,
+                None => pending.statuses.push(event),
+            }
+        }
+        self.flush_if_due(app);
+    }
+
+    fn flush_if_due<R: Runtime>(&self, app: &AppHandle<R>) {
+        let due = {
+            let pending = self.pending.lock().unwrap();
+            let interval_due = !self.interval.is_zero()
+                && pending
+                    .last_flush
+                    .is_none_or(|last_flush| last_flush.elapsed() >= self.interval);
+            let invoices_due =
+                self.every_invoices > 0 && pending.finished_invoices >= self.every_invoices;
+            interval_due || invoices_due
+        };
+
+        if due {
+            self.flush(app);
+        }
+    }
+
+    /// Emit everything held back: logs first, then statuses, then the latest progress
+    pub fn flush<R: Runtime>(&self, app: &AppHandle<R>) {
+        let pending = {
+            let mut pending = self.pending.lock().unwrap();
+            let taken = std::mem::take(&mut *pending);
+            pending.last_flush = Some(Instant::now());
+            taken
+        };
+
+        if let Some(first) = pending.logs.first() {
+            let _ = app.emit(
+                events::DOWNLOAD_LOG_BATCH,
+                LogBatchEvent {
+                    batch_id: first.batch_id.clone(),
+                    entries: pending.logs,
+                },
+            );
+        }
+        if let Some(first) = pending.statuses.first() {
+            let _ = app.emit(
+                events::INVOICE_STATUS_BATCH,
+                InvoiceStatusBatchEvent {
+                    batch_id: first.batch_id.clone(),
+                    statuses: pending.statuses,
+                },
+            );
+        }
+        if let Some(progress) = pending.progress {
+            let _ = app.emit(events::DOWNLOAD_PROGRESS, progress);
+        }
+    }
+
+    /// Flush every `interval` until the returned ticker is dropped, so held-back
+    /// events go out even while the batch sits in an invoice delay or quiet hours
+    pub fn spawn_ticker(self: &Arc<Self>, app: &AppHandle) -> Option<FlushTicker> {
+        if self.interval.is_zero() {
+            return None;
+        }
+
+        let throttle = self.clone();
+        let app = app.clone();
+        Some(FlushTicker(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(throttle.interval).await;
+                throttle.flush(&app);
+            }
+        })))
+    }
+}
+
+/// Stops the periodic flush when dropped
+pub struct FlushTicker(tokio::task::JoinHandle<()>);
+
+impl Drop for FlushTicker {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -1,28 +1,76 @@
 use calamine::{open_workbook, Reader, Xlsx, Data};
 use serde::{Deserialize, Serialize};
+use specta::Type;
 use std::path::Path;
 
 use crate::error::AppError;
+use crate::services::{manifest, pdf_metadata};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Digits of a seller tax code, not counting a branch suffix
+const TAX_CODE_DIGITS: usize = 10;
+
+/// Characters that end a URL written in a cell or a sentence
+const URL_TERMINATORS: &[char] = &['"', '\'', '<', '>', '(', ')', '[', ']', '{', '}', ',', ';', '|'];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct InvoiceCode {
     pub id: String,
     pub code: String,
     pub row_number: usize,
+    /// Seller tax code, when the sheet has a tax code column
+    pub seller_tax_code: Option<String>,
+    pub lookup_mode: LookupMode,
+    /// Invoice series (ký hiệu), for `LookupMode::InvoiceNumber`
+    pub invoice_series: Option<String>,
+    /// Invoice number (số hóa đơn), for `LookupMode::InvoiceNumber`
+    pub invoice_number: Option<String>,
+    /// VNPT portal URL written on the same row, for files mixing several providers
+    pub portal_url: Option<String>,
+}
+
+/// How an invoice is looked up on the portal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum LookupMode {
+    /// Lookup code (mã tra cứu) issued by the provider
+    #[default]
+    Code,
+    /// Invoice series + number + seller tax code, for clients without lookup codes
+    InvoiceNumber,
+}
+
+/// A portal URL found in the sheet and where it appears
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct DetectedUrl {
+    pub url: String,
+    /// Number of occurrences
+    pub count: usize,
+    /// 1-indexed rows the URL appears on
+    pub rows: Vec<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct ExcelParseResult {
     pub invoices: Vec<InvoiceCode>,
+    /// First URL found, kept for single-portal files
     pub detected_url: Option<String>,
+    /// Every distinct URL found, in order of first appearance
+    pub detected_urls: Vec<DetectedUrl>,
     pub total_rows: usize,
     pub sheet_name: String,
+    /// Name of the parsed file, the default name of its batch
+    pub file_name: String,
+    pub file_path: String,
+    /// SHA-256 of the file, to recognise a sheet that was already downloaded
+    pub file_hash: String,
 }
 
 /// Parse an Excel file to extract invoice codes
 ///
 /// Looks for a column containing "MÃ TRA CỨU" in the header
-/// and extracts all valid invoice codes (containing 'C' and '_')
+/// and extracts all valid invoice codes (containing 'C' and '_').
+/// Sheets without lookup codes are read by invoice series, number and
+/// seller tax code instead.
 pub fn parse_excel_file(file_path: &str) -> Result<ExcelParseResult, AppError> {
     let path = Path::new(file_path);
 
@@ -30,6 +78,7 @@ pub fn parse_excel_file(file_path: &str) -> Result<ExcelParseResult, AppError> {
         return Err(AppError::ExcelError(format!("File not found: {}", file_path)));
     }
 
+    let (file_hash, _) = manifest::hash_file(path)?;
     let mut workbook: Xlsx<_> = open_workbook(path)?;
 
     // Get the first sheet
@@ -44,93 +93,260 @@ pub fn parse_excel_file(file_path: &str) -> Result<ExcelParseResult, AppError> {
         .worksheet_range(&sheet_name)
         .map_err(|e| AppError::ExcelError(e.to_string()))?;
 
-    let mut invoices = Vec::new();
-    let mut detected_url: Option<String> = None;
-    let mut header_row: Option<usize> = None;
-    let mut code_col: Option<usize> = None;
     let total_rows = range.height();
+    // The range starts at the first filled row; row numbers are the sheet's
+    let first_row = range.start().map_or(0, |(row, _)| row as usize);
+    let rows: Vec<&[Data]> = range.rows().collect();
+    let mut invoices = parse_rows(&rows).ok_or_else(|| {
+        AppError::ExcelError(
+            "Could not find column 'MÃ TRA CỨU' (or 'SỐ HÓA ĐƠN', 'KÝ HIỆU' and 'MST') in Excel file"
+                .to_string(),
+        )
+    })?;
+    for invoice in &mut invoices {
+        invoice.row_number += first_row;
+    }
 
-    // Find header row with "MÃ TRA CỨU"
-    for (row_idx, row) in range.rows().enumerate() {
-        for (col_idx, cell) in row.iter().enumerate() {
-            if let Data::String(text) = cell {
-                let upper = text.to_uppercase();
+    // Scan all cells for VNPT URLs
+    let detected_urls = detect_urls(range.rows().enumerate().flat_map(|(row_idx, row)| {
+        row.iter().filter_map(move |cell| match cell {
+            Data::String(text) => Some((first_row + row_idx + 1, text.as_str())),
+            _ => None,
+        })
+    }));
+    let detected_url = detected_urls.first().map(|detected| detected.url.clone());
 
-                // Check for invoice code column header
-                if upper.contains("MÃ TRA CỨU") {
-                    header_row = Some(row_idx);
-                    code_col = Some(col_idx);
-                }
+    Ok(ExcelParseResult {
+        invoices,
+        detected_url,
+        detected_urls,
+        total_rows,
+        sheet_name,
+        file_name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        file_path: file_path.to_string(),
+        file_hash,
+    })
+}
 
-                // Try to detect VNPT URL from any cell
-                if detected_url.is_none() && text.contains("vnpt-invoice.com.vn") {
-                    // Extract URL from text
-                    if let Some(url) = extract_vnpt_url(text) {
-                        detected_url = Some(url);
-                    }
-                }
+/// Read lookup codes out of any PDF with a text layer, e.g. a supplier's
+/// summary statement; row numbers are lines of the extracted text
+pub fn parse_pdf_file(file_path: &str) -> Result<ExcelParseResult, AppError> {
+    let path = Path::new(file_path);
+    if !path.exists() {
+        return Err(AppError::ExcelError(format!("File not found: {}", file_path)));
+    }
+
+    let (file_hash, _) = manifest::hash_file(path)?;
+    let text = pdf_metadata::extract_text(path)?;
+    if pdf_metadata::is_scanned(&text) {
+        return Err(AppError::ExcelError(format!(
+            "{} has no text layer; scanned PDFs cannot be searched for codes",
+            file_path
+        )));
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut invoices: Vec<InvoiceCode> = Vec::new();
+    for (line_idx, line) in lines.iter().enumerate() {
+        let portal_url = extract_vnpt_urls(line).into_iter().next();
+        for code in find_invoice_codes(line) {
+            if invoices.iter().any(|invoice| invoice.code == code) {
+                continue;
             }
+            invoices.push(InvoiceCode {
+                id: uuid::Uuid::new_v4().to_string(),
+                code,
+                row_number: line_idx + 1,
+                seller_tax_code: None,
+                lookup_mode: LookupMode::Code,
+                invoice_series: None,
+                invoice_number: None,
+                portal_url: portal_url.clone(),
+            });
         }
+    }
+
+    let detected_urls = detect_urls(lines.iter().enumerate().map(|(idx, line)| (idx + 1, *line)));
+    Ok(ExcelParseResult {
+        invoices,
+        detected_url: detected_urls.first().map(|detected| detected.url.clone()),
+        detected_urls,
+        total_rows: lines.len(),
+        sheet_name: "PDF".to_string(),
+        file_name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        file_path: file_path.to_string(),
+        file_hash,
+    })
+}
+
+/// Invoices of a table of text cells laid out like the Excel sheets, e.g. an
+/// invoice list scraped from a portal; `None` without a recognised header row
+pub fn parse_text_rows(rows: &[Vec<String>]) -> Option<Vec<InvoiceCode>> {
+    let rows: Vec<Vec<Data>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| Data::String(cell.clone())).collect())
+        .collect();
+    let rows: Vec<&[Data]> = rows.iter().map(Vec::as_slice).collect();
+    parse_rows(&rows)
+}
+
+/// Find the header row with "MÃ TRA CỨU", or with invoice number, series and
+/// tax code, and read one invoice from each valid row below it
+fn parse_rows(rows: &[&[Data]]) -> Option<Vec<InvoiceCode>> {
+    let candidates: Vec<(usize, HeaderColumns)> = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(row_idx, row)| {
+            let columns = HeaderColumns::detect(row);
+            columns.lookup_mode().map(|_| (row_idx, columns))
+        })
+        .collect();
+    // A merged title such as "Danh sách mã tra cứu" reads like a header too;
+    // a real header labels more than one column
+    let labels = |row_idx: usize| rows[row_idx].iter().filter(|cell| matches!(cell, Data::String(_))).count();
+    let (header, columns) = candidates
+        .iter()
+        .find(|(row_idx, _)| labels(*row_idx) > 1)
+        .or(candidates.first())?;
+
+    let cell_at = |row: &[Data], col: Option<usize>| {
+        col.and_then(|c| row.get(c))
+            .and_then(cell_text)
+            .filter(|text| !text.is_empty())
+    };
+
+    let mut invoices = Vec::new();
+    for (row_idx, row) in rows.iter().enumerate().skip(header + 1) {
+        let seller_tax_code = columns.tax_code.and_then(|c| row.get(c)).and_then(tax_code_text);
+        let portal_url = row.iter().find_map(|cell| match cell {
+            Data::String(text) => extract_vnpt_urls(text).into_iter().next(),
+            _ => None,
+        });
 
-        if header_row.is_some() {
-            break;
+        match columns.lookup_mode() {
+            Some(LookupMode::Code) => {
+                let Some(code_text) = cell_at(row, columns.code) else {
+                    continue;
+                };
+
+                // Validate code format: contains C and _
+                // Example valid codes: C25TLK0019654_Ln, C25TLK0019655_Ln
+                if is_valid_invoice_code(&code_text) {
+                    invoices.push(InvoiceCode {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        code: code_text,
+                        row_number: row_idx + 1, // 1-indexed for display
+                        seller_tax_code,
+                        lookup_mode: LookupMode::Code,
+                        invoice_series: None,
+                        invoice_number: None,
+                        portal_url,
+                    });
+                }
+            }
+            Some(LookupMode::InvoiceNumber) => {
+                let series = cell_at(row, columns.series);
+                let number = cell_at(row, columns.number);
+
+                if let (Some(series), Some(number), Some(_)) = (series, number, &seller_tax_code) {
+                    invoices.push(InvoiceCode {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        code: format!("{}-{}", series, number),
+                        row_number: row_idx + 1,
+                        seller_tax_code,
+                        lookup_mode: LookupMode::InvoiceNumber,
+                        invoice_series: Some(series),
+                        invoice_number: Some(number),
+                        portal_url,
+                    });
+                }
+            }
+            None => {}
         }
     }
 
-    let (header, col) = match (header_row, code_col) {
-        (Some(h), Some(c)) => (h, c),
-        _ => return Err(AppError::ExcelError(
-            "Could not find column 'MÃ TRA CỨU' in Excel file".to_string()
-        )),
-    };
+    Some(invoices)
+}
+
+/// Column positions found in the header row
+#[derive(Debug, Default, PartialEq)]
+struct HeaderColumns {
+    code: Option<usize>,
+    series: Option<usize>,
+    number: Option<usize>,
+    tax_code: Option<usize>,
+}
 
-    // Extract invoice codes from found column
-    for (row_idx, row) in range.rows().enumerate().skip(header + 1) {
-        if let Some(cell) = row.get(col) {
-            let code_text = match cell {
-                Data::String(s) => s.trim().to_string(),
-                Data::Int(i) => i.to_string(),
-                Data::Float(f) => f.to_string(),
-                _ => continue,
+impl HeaderColumns {
+    fn detect(row: &[Data]) -> Self {
+        let mut columns = Self::default();
+
+        for (col_idx, cell) in row.iter().enumerate() {
+            let Data::String(text) = cell else {
+                continue;
             };
+            let upper = text.to_uppercase();
 
-            // Validate code format: contains C and _
-            // Example valid codes: C25TLK0019654_Ln, C25TLK0019655_Ln
-            if is_valid_invoice_code(&code_text) {
-                invoices.push(InvoiceCode {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    code: code_text,
-                    row_number: row_idx + 1, // 1-indexed for display
-                });
+            if upper.contains("MÃ TRA CỨU") {
+                columns.code = Some(col_idx);
+            } else if is_seller_tax_code_header(text) {
+                columns.tax_code.get_or_insert(col_idx);
+            } else if upper.contains("MẪU") {
+                // "Mẫu số" / "Ký hiệu mẫu số hóa đơn" is the template, not series or number
+            } else if upper.contains("KÝ HIỆU") {
+                columns.series.get_or_insert(col_idx);
+            } else if upper.contains("SỐ HÓA ĐƠN") || upper.trim() == "SỐ HĐ" {
+                columns.number.get_or_insert(col_idx);
             }
         }
+
+        columns
     }
 
-    // Also scan all cells for URLs if not found yet
-    if detected_url.is_none() {
-        for row in range.rows() {
-            for cell in row.iter() {
-                if let Data::String(text) = cell {
-                    if text.contains("vnpt-invoice.com.vn") {
-                        if let Some(url) = extract_vnpt_url(text) {
-                            detected_url = Some(url);
-                            break;
-                        }
-                    }
-                }
-            }
-            if detected_url.is_some() {
-                break;
-            }
+    /// Lookup codes win when present; otherwise series, number and tax code are all needed
+    fn lookup_mode(&self) -> Option<LookupMode> {
+        if self.code.is_some() {
+            Some(LookupMode::Code)
+        } else if self.series.is_some() && self.number.is_some() && self.tax_code.is_some() {
+            Some(LookupMode::InvoiceNumber)
+        } else {
+            None
         }
     }
+}
+
+/// Header of the seller tax code column ("MST", "Mã số thuế"), excluding the buyer's
+fn is_seller_tax_code_header(text: &str) -> bool {
+    let upper = text.to_uppercase();
+    (upper.contains("MST") || upper.contains("MÃ SỐ THUẾ")) && !upper.contains("MUA")
+}
+
+/// Text of a cell; tax codes typed as numbers keep their digits
+fn cell_text(cell: &Data) -> Option<String> {
+    match cell {
+        Data::String(s) => Some(s.trim().to_string()),
+        Data::Int(i) => Some(i.to_string()),
+        Data::Float(f) if f.fract() == 0.0 => Some(format!("{:.0}", f)),
+        _ => None,
+    }
+}
 
-    Ok(ExcelParseResult {
-        invoices,
-        detected_url,
-        total_rows,
-        sheet_name,
-    })
+/// Seller tax code of a cell; Excel drops the leading zeros of tax codes
+/// typed as numbers, e.g. 0101234567 as 101234567
+fn tax_code_text(cell: &Data) -> Option<String> {
+    let text = cell_text(cell).filter(|text| !text.is_empty())?;
+    match cell {
+        Data::Int(_) | Data::Float(_) if text.len() < TAX_CODE_DIGITS => {
+            Some(format!("{:0>width$}", text, width = TAX_CODE_DIGITS))
+        }
+        _ => Some(text),
+    }
 }
 
 /// Check if a string is a valid invoice code
@@ -142,37 +358,85 @@ fn is_valid_invoice_code(code: &str) -> bool {
         && code.len() > 5
 }
 
-/// Extract VNPT URL from text
+/// Lookup codes written anywhere in free text (an email, a PDF), in order of
+/// first appearance
+pub fn find_invoice_codes(text: &str) -> Vec<String> {
+    let mut codes: Vec<String> = Vec::new();
+    for token in text.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+        if is_valid_invoice_code(token)
+            && token.chars().any(|c| c.is_ascii_digit())
+            && !codes.iter().any(|code| code == token)
+        {
+            codes.push(token.to_string());
+        }
+    }
+    codes
+}
+
+/// Group the VNPT URLs found in `(row_number, cell_text)` pairs
+fn detect_urls<'a>(cells: impl Iterator<Item = (usize, &'a str)>) -> Vec<DetectedUrl> {
+    let mut detected: Vec<DetectedUrl> = Vec::new();
+
+    for (row_number, text) in cells {
+        if !text.contains("vnpt-invoice.com.vn") {
+            continue;
+        }
+
+        for url in extract_vnpt_urls(text) {
+            match detected.iter_mut().find(|d| d.url == url) {
+                Some(existing) => {
+                    existing.count += 1;
+                    if existing.rows.last() != Some(&row_number) {
+                        existing.rows.push(row_number);
+                    }
+                }
+                None => detected.push(DetectedUrl {
+                    url,
+                    count: 1,
+                    rows: vec![row_number],
+                }),
+            }
+        }
+    }
+
+    detected
+}
+
+/// Extract every VNPT URL from text
 /// Looks for patterns like https://xxxx.vnpt-invoice.com.vn/...
-fn extract_vnpt_url(text: &str) -> Option<String> {
-    // Simple pattern matching for VNPT URLs
-    let patterns = [
-        "https://",
-        "http://",
-    ];
+pub fn extract_vnpt_urls(text: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut rest = text;
 
-    for pattern in patterns {
-        if let Some(start_idx) = text.find(pattern) {
-            let url_part = &text[start_idx..];
+    // Simple pattern matching for VNPT URLs
+    while let Some(start_idx) = rest.find("http") {
+        let url_part = &rest[start_idx..];
 
-            // Find end of URL (space, newline, or end of string)
-            let end_idx = url_part
-                .find(|c: char| c.is_whitespace() || c == '"' || c == '\'')
-                .unwrap_or(url_part.len());
+        // Find end of URL (whitespace, quote, bracket, list separator or end of string)
+        let end_idx = url_part
+            .find(|c: char| c.is_whitespace() || URL_TERMINATORS.contains(&c))
+            .unwrap_or(url_part.len());
 
-            let url = &url_part[..end_idx];
+        // Punctuation ending a sentence is not part of the URL
+        let url = url_part[..end_idx].trim_end_matches(['.', ':', '!', '?']);
 
-            if url.contains("vnpt-invoice.com.vn") {
-                return Some(url.to_string());
-            }
+        if (url.starts_with("https://") || url.starts_with("http://"))
+            && url.contains("vnpt-invoice.com.vn")
+        {
+            urls.push(url.to_string());
         }
+
+        rest = &url_part[end_idx.max(1)..];
     }
 
-    None
+    urls
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+    use rust_xlsxwriter::{Format, Workbook};
+
     use super::*;
 
     #[test]
@@ -184,11 +448,293 @@ mod tests {
         assert!(!is_valid_invoice_code("C123")); // too short
     }
 
+    #[test]
+    fn test_find_invoice_codes() {
+        let text = "Mã tra cứu: C25TLK0019654_Ln\nMã tra cứu (C25TLK0019654_Ln), MAIL_CC, C25TLK0019655_Ln.";
+        assert_eq!(find_invoice_codes(text), vec!["C25TLK0019654_Ln", "C25TLK0019655_Ln"]);
+    }
+
+    #[test]
+    fn test_header_columns() {
+        let header = |cells: &[&str]| {
+            let row: Vec<Data> = cells.iter().map(|c| Data::String(c.to_string())).collect();
+            HeaderColumns::detect(&row)
+        };
+
+        let by_code = header(&["STT", "Mã tra cứu", "MST người bán"]);
+        assert_eq!(by_code.lookup_mode(), Some(LookupMode::Code));
+        assert_eq!(by_code.tax_code, Some(2));
+
+        let by_number = header(&["Mẫu số", "Ký hiệu", "Số hóa đơn", "MST người bán", "MST người mua"]);
+        assert_eq!(by_number.lookup_mode(), Some(LookupMode::InvoiceNumber));
+        assert_eq!(
+            by_number,
+            HeaderColumns {
+                code: None,
+                series: Some(1),
+                number: Some(2),
+                tax_code: Some(3),
+            }
+        );
+
+        assert_eq!(header(&["Ký hiệu", "Số hóa đơn"]).lookup_mode(), None);
+    }
+
+    #[test]
+    fn test_is_seller_tax_code_header() {
+        assert!(is_seller_tax_code_header("MST người bán"));
+        assert!(is_seller_tax_code_header("Mã số thuế"));
+        assert!(!is_seller_tax_code_header("MST người mua"));
+        assert!(!is_seller_tax_code_header("Mã tra cứu"));
+    }
+
+    #[test]
+    fn test_detect_urls() {
+        let a = "https://0101-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey";
+        let b = "https://0202-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey";
+        let line_b = format!("Tra cứu tại {} hoặc {}", b, a);
+        let cells = vec![
+            (2, a),
+            (3, "C25TLK0019654_Ln"),
+            (4, line_b.as_str()),
+            (5, a),
+        ];
+
+        assert_eq!(
+            detect_urls(cells.into_iter()),
+            vec![
+                DetectedUrl {
+                    url: a.to_string(),
+                    count: 3,
+                    rows: vec![2, 4, 5],
+                },
+                DetectedUrl {
+                    url: b.to_string(),
+                    count: 1,
+                    rows: vec![4],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_extract_vnpt_url() {
         let text = "Please visit https://3701642642-010-tt78.vnpt-invoice.com.vn/HomeNoLogin for more info";
-        let url = extract_vnpt_url(text);
-        assert!(url.is_some());
-        assert!(url.unwrap().contains("vnpt-invoice.com.vn"));
+        let urls = extract_vnpt_urls(text);
+        assert_eq!(urls.len(), 1);
+        assert!(urls[0].contains("vnpt-invoice.com.vn"));
+        assert!(extract_vnpt_urls("see https://example.com/a").is_empty());
+    }
+
+    #[test]
+    fn test_numeric_tax_code() {
+        assert_eq!(tax_code_text(&Data::Float(101234567.0)).as_deref(), Some("0101234567"));
+        assert_eq!(tax_code_text(&Data::Int(3701642642)).as_deref(), Some("3701642642"));
+        assert_eq!(tax_code_text(&Data::String(" 0101234567-001 ".to_string())).as_deref(), Some("0101234567-001"));
+        assert_eq!(tax_code_text(&Data::Empty), None);
+    }
+
+    /// Table headers of generated workbooks, in a generated order; portal
+    /// URLs follow in a note column
+    const HEADERS: [&str; 3] = ["STT", "Mã tra cứu", "MST người bán"];
+
+    const SEPARATORS: [&str; 7] = [" ", "\n", "\t", ", ", ",", ";", " | "];
+
+    #[derive(Debug, Clone)]
+    struct SheetRow {
+        code: String,
+        tax_code: String,
+        /// Typed as a number, so Excel drops the leading zero
+        tax_code_as_number: bool,
+        urls: Vec<String>,
+        separator: &'static str,
+        /// Note cell merged across two columns
+        merged_note: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    struct Sheet {
+        /// Merged title above the table, which mentions "mã tra cứu" too
+        title: bool,
+        /// Rows between the title and the header; empty ones stay blank
+        preamble: Vec<&'static str>,
+        /// Order of `HEADERS`
+        columns: Vec<usize>,
+        /// `None` for blank rows inside the table
+        rows: Vec<Option<SheetRow>>,
+    }
+
+    fn portal_url() -> impl Strategy<Value = String> {
+        "https://[0-9]{10}(-[0-9]{3})?-tt78\\.vnpt-invoice\\.com\\.vn(/[A-Za-z]{1,12}){0,2}/?"
+    }
+
+    fn sheet_row() -> impl Strategy<Value = SheetRow> {
+        (
+            "C[0-9]{2}T[A-Z]{2}[0-9]{7}_[A-Z][a-z]",
+            "0[0-9]{9}",
+            any::<bool>(),
+            prop::collection::vec(portal_url(), 0..3),
+            prop::sample::select(SEPARATORS.to_vec()),
+            any::<bool>(),
+        )
+            .prop_map(|(code, tax_code, tax_code_as_number, urls, separator, merged_note)| SheetRow {
+                code,
+                tax_code,
+                tax_code_as_number,
+                urls,
+                separator,
+                merged_note,
+            })
+    }
+
+    fn sheet() -> impl Strategy<Value = Sheet> {
+        (
+            any::<bool>(),
+            // Up to row 10 for the header
+            prop::collection::vec(
+                prop::sample::select(vec!["", "CÔNG TY TNHH AN BÌNH", "Kỳ: 05/2025", "Người lập: Kế toán"]),
+                0..10,
+            ),
+            Just((0..HEADERS.len()).collect::<Vec<_>>()).prop_shuffle(),
+            prop::collection::vec(prop::option::weighted(0.85, sheet_row()), 1..20),
+        )
+            .prop_map(|(title, preamble, columns, rows)| Sheet {
+                title,
+                preamble,
+                columns,
+                rows,
+            })
+    }
+
+    /// Write `sheet` to `path`
+    ///
+    /// # Returns
+    /// The rows expected back, with their 1-indexed row numbers
+    fn write_workbook(sheet: &Sheet, path: &Path) -> Vec<(usize, SheetRow)> {
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        let note_col = HEADERS.len() as u16;
+        let mut row = 0u32;
+
+        if sheet.title {
+            worksheet
+                .merge_range(0, 0, 0, note_col + 1, "Danh sách mã tra cứu hóa đơn tháng 05/2025", &Format::new())
+                .unwrap();
+            row += 1;
+        }
+        for line in &sheet.preamble {
+            if !line.is_empty() {
+                worksheet.write_string(row, 0, *line).unwrap();
+            }
+            row += 1;
+        }
+        for (col, &header) in sheet.columns.iter().enumerate() {
+            worksheet.write_string(row, col as u16, HEADERS[header]).unwrap();
+        }
+        worksheet.write_string(row, note_col, "Ghi chú").unwrap();
+        row += 1;
+
+        let mut expected = Vec::new();
+        for (index, invoice) in sheet.rows.iter().enumerate() {
+            if let Some(invoice) = invoice {
+                for (col, &header) in sheet.columns.iter().enumerate() {
+                    let col = col as u16;
+                    match header {
+                        0 => worksheet.write_number(row, col, (index + 1) as f64),
+                        1 => worksheet.write_string(row, col, &invoice.code),
+                        _ if invoice.tax_code_as_number => {
+                            worksheet.write_number(row, col, invoice.tax_code.parse::<f64>().unwrap())
+                        }
+                        _ => worksheet.write_string(row, col, &invoice.tax_code),
+                    }
+                    .unwrap();
+                }
+
+                let note = invoice.urls.join(invoice.separator);
+                if invoice.merged_note && !note.is_empty() {
+                    worksheet.merge_range(row, note_col, row, note_col + 1, &note, &Format::new()).unwrap();
+                } else if !note.is_empty() {
+                    worksheet.write_string(row, note_col, &note).unwrap();
+                }
+                expected.push((row as usize + 1, invoice.clone()));
+            }
+            row += 1;
+        }
+        worksheet
+            .write_string(row, 0, format!("Tổng cộng: {} hóa đơn", expected.len()))
+            .unwrap();
+
+        workbook.save(path).unwrap();
+        expected
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(48))]
+
+        #[test]
+        fn test_parse_generated_workbook(sheet in sheet()) {
+            let tmp = tempfile::tempdir().unwrap();
+            let path = tmp.path().join("hoa-don.xlsx");
+            let expected = write_workbook(&sheet, &path);
+            let parsed = parse_excel_file(&path.to_string_lossy());
+            let parsed = parsed.unwrap();
+
+            prop_assert_eq!(parsed.invoices.len(), expected.len());
+            for (invoice, (row_number, row)) in parsed.invoices.iter().zip(&expected) {
+                prop_assert_eq!(&invoice.code, &row.code);
+                prop_assert_eq!(invoice.row_number, *row_number);
+                prop_assert_eq!(invoice.seller_tax_code.as_deref(), Some(row.tax_code.as_str()));
+                prop_assert_eq!(invoice.portal_url.as_ref(), row.urls.first());
+            }
+
+            let mut urls: Vec<&String> = Vec::new();
+            for url in expected.iter().flat_map(|(_, row)| &row.urls) {
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
+            prop_assert_eq!(parsed.detected_urls.iter().map(|d| &d.url).collect::<Vec<_>>(), urls);
+            for detected in &parsed.detected_urls {
+                let rows: Vec<usize> = expected
+                    .iter()
+                    .filter(|(_, row)| row.urls.contains(&detected.url))
+                    .map(|(row_number, _)| *row_number)
+                    .collect();
+                prop_assert_eq!(&detected.rows, &rows);
+            }
+        }
+
+        #[test]
+        fn test_extract_urls_between_separators(
+            urls in prop::collection::vec(portal_url(), 1..4),
+            separator in prop::sample::select(SEPARATORS.to_vec()),
+            prefix in prop::sample::select(vec!["", "Tra cứu tại ", "(", "\"", "Link: ", "<a href='"]),
+            suffix in prop::sample::select(vec!["", ".", ")", "\"", "'>", " để tải hóa đơn", ":"]),
+        ) {
+            let text = format!("{}{}{}", prefix, urls.join(separator), suffix);
+            prop_assert_eq!(extract_vnpt_urls(&text), urls);
+        }
+
+        #[test]
+        fn test_extract_urls_from_any_text(
+            pieces in prop::collection::vec(
+                prop_oneof![
+                    portal_url(),
+                    "\\PC{0,8}",
+                    prop::sample::select(vec!["http", "https://", "vnpt-invoice.com.vn", ".", ":", "/", "\"", "\n"])
+                        .prop_map(str::to_string),
+                ],
+                0..12,
+            ),
+        ) {
+            let text = pieces.concat();
+            for url in extract_vnpt_urls(&text) {
+                prop_assert!(text.contains(url.as_str()));
+                prop_assert!(url.starts_with("http"));
+                prop_assert!(url.contains("vnpt-invoice.com.vn"));
+                prop_assert!(!url.contains(|c: char| c.is_whitespace() || URL_TERMINATORS.contains(&c)));
+            }
+            let _ = find_invoice_codes(&text);
+        }
     }
 }
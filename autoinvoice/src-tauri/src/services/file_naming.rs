@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::error::AppError;
+use crate::services::batch_report::fold_diacritics;
+use crate::services::pdf_metadata::{self, MetadataSource};
+use crate::status::impl_setting_parse;
+
+/// Placeholders the file and folder templates may use
+const PLACEHOLDERS: &[&str] = &["code", "seller", "number", "tax_code"];
+
+/// Characters Windows refuses in file names
+const RESERVED_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Legal seller names run long; path limits do not
+const MAX_VALUE_CHARS: usize = 80;
+
+/// How names from the invoice are spelled in file and folder names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transliteration {
+    /// Keep Vietnamese as written
+    #[default]
+    Keep,
+    /// Drop diacritics, e.g. "Công ty Hưng Thịnh" as "Cong ty Hung Thinh",
+    /// for filesystems and tools that only take ASCII
+    Ascii,
+    /// As `Ascii`, with underscores for spaces
+    AsciiUnderscore,
+}
+
+impl Transliteration {
+    pub const ALL: &'static [Transliteration] = &[
+        Transliteration::Keep,
+        Transliteration::Ascii,
+        Transliteration::AsciiUnderscore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transliteration::Keep => "keep",
+            Transliteration::Ascii => "ascii",
+            Transliteration::AsciiUnderscore => "ascii-underscore",
+        }
+    }
+
+    fn apply(&self, name: &str) -> String {
+        match self {
+            Transliteration::Keep => name.to_string(),
+            Transliteration::Ascii => ascii_only(name),
+            Transliteration::AsciiUnderscore => ascii_only(name).replace(' ', "_"),
+        }
+    }
+}
+
+impl_setting_parse!(Transliteration);
+
+fn ascii_only(name: &str) -> String {
+    fold_diacritics(name)
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect()
+}
+
+/// Invoice fields shown on the lookup result page, read without opening
+/// the PDF; each is `None` when the page does not show it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageFields {
+    pub seller_name: Option<String>,
+    pub invoice_number: Option<String>,
+}
+
+/// Read the seller name and invoice number from the HTML of the result page
+pub fn scrape_page(html: &str) -> PageFields {
+    static SELLER: OnceLock<Regex> = OnceLock::new();
+    let seller = SELLER.get_or_init(|| {
+        Regex::new(
+            r"(?im)^[^\w\n]*(?:[đd][ơo]n v[ịi] b[áa]n(?: h[àa]ng)?|t[êe]n (?:ng[ưu][ờo]i|[đd][ơo]n v[ịi]) b[áa]n(?: h[àa]ng)?|ng[ưu][ờo]i b[áa]n(?: h[àa]ng)?)[^:\n]{0,30}:[ \t]*([^\t\n]*\S)",
+        )
+        .expect("seller name pattern")
+    });
+
+    let text = page_text(html);
+    PageFields {
+        seller_name: seller
+            .captures(&text)
+            .and_then(|captures| captures.get(1))
+            .map(|name| name.as_str().trim().to_string()),
+        invoice_number: pdf_metadata::extract_fields(&text, MetadataSource::Text).number,
+    }
+}
+
+/// Visible text of `html`, one line per block and a tab between table cells
+fn page_text(html: &str) -> String {
+    static PATTERNS: OnceLock<[Regex; 5]> = OnceLock::new();
+    let [hidden, line_break, cell_break, tag, entity] = PATTERNS.get_or_init(|| {
+        [
+            r"(?is)<script\b.*?</script>|<style\b.*?</style>|<!--.*?-->",
+            r"(?i)<br\s*/?>|</(?:p|div|tr|li|h[1-6]|table)>",
+            r"(?i)</t[dh]>",
+            r"<[^>]*>",
+            r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);",
+        ]
+        .map(|pattern| Regex::new(pattern).expect("page text pattern"))
+    });
+
+    let text = hidden.replace_all(html, "");
+    let text = line_break.replace_all(&text, "\n");
+    let text = cell_break.replace_all(&text, "\t");
+    let text = tag.replace_all(&text, "");
+    entity
+        .replace_all(&text, |captures: &regex::Captures| {
+            let name = &captures[1];
+            let decoded = match name {
+                "nbsp" => Some(' '),
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => name
+                    .strip_prefix("#x")
+                    .or_else(|| name.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| name.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            decoded.map_or_else(|| captures[0].to_string(), String::from)
+        })
+        .to_string()
+}
+
+/// What the placeholders of the templates stand for, for one invoice
+pub struct NameValues<'a> {
+    pub code: &'a str,
+    pub seller_tax_code: Option<&'a str>,
+    pub page: &'a PageFields,
+}
+
+impl NameValues<'_> {
+    fn get(&self, placeholder: &str) -> Option<&str> {
+        match placeholder {
+            "code" => Some(self.code),
+            "seller" => self.page.seller_name.as_deref(),
+            "number" => self.page.invoice_number.as_deref(),
+            "tax_code" => self.seller_tax_code,
+            _ => None,
+        }
+    }
+}
+
+/// File and folder names of downloaded invoices, from templates such as
+/// `{seller}/{number}_{code}`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct FileNaming {
+    /// PDF name without extension; empty names it after the lookup code
+    pub file_name_template: String,
+    /// Subfolder of the batch folder, `/` nesting further; empty saves into
+    /// the batch folder itself
+    pub folder_template: String,
+    pub transliteration: Transliteration,
+}
+
+impl FileNaming {
+    /// Check the templates only use known placeholders
+    pub fn validate(&self) -> Result<(), AppError> {
+        for (name, template) in [
+            ("file name", &self.file_name_template),
+            ("folder", &self.folder_template),
+        ] {
+            let mut rest = template.as_str();
+            while let Some(start) = rest.find(['{', '}']) {
+                let placeholder = rest[start..]
+                    .strip_prefix('{')
+                    .and_then(|open| open.split_once('}'))
+                    .map(|(placeholder, _)| placeholder)
+                    .filter(|placeholder| PLACEHOLDERS.contains(placeholder))
+                    .ok_or_else(|| {
+                        AppError::ConfigError(format!(
+                            "Unknown placeholder in the {} template {}; use {}",
+                            name,
+                            template,
+                            PLACEHOLDERS.iter().map(|p| format!("{{{}}}", p)).collect::<Vec<_>>().join(", ")
+                        ))
+                    })?;
+                rest = &rest[start + placeholder.len() + 2..];
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a template needs the fields of the result page
+    pub fn uses_page_fields(&self) -> bool {
+        [&self.file_name_template, &self.folder_template]
+            .iter()
+            .any(|template| template.contains("{seller}") || template.contains("{number}"))
+    }
+
+    /// Path of the invoice's PDF, relative to the batch folder; a template
+    /// that renders empty falls back to the lookup code
+    pub fn relative_path(&self, values: &NameValues) -> PathBuf {
+        let mut path: PathBuf = self
+            .folder_template
+            .split(['/', '\\'])
+            .map(|segment| self.render(segment, values))
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let file_name = match self.render(self.file_name_template.trim(), values) {
+            name if name.is_empty() => self.render("{code}", values),
+            name => name,
+        };
+        path.push(format!("{}.pdf", file_name));
+        path
+    }
+
+    fn render(&self, template: &str, values: &NameValues) -> String {
+        let mut rendered = template.to_string();
+        for placeholder in PLACEHOLDERS {
+            let value: String = values
+                .get(placeholder)
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(MAX_VALUE_CHARS)
+                .collect();
+            rendered = rendered.replace(&format!("{{{}}}", placeholder), &value);
+        }
+
+        let rendered: String = rendered
+            .chars()
+            .map(|c| if RESERVED_CHARS.contains(&c) || c.is_control() { '_' } else { c })
+            .collect();
+        // Windows drops trailing dots and spaces; a missing value leaves
+        // separators dangling
+        self.transliteration
+            .apply(&rendered)
+            .trim_matches([' ', '.', '_', '-'])
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESULT_PAGE: &str = r#"<html><head><script>var label = "Đơn vị bán hàng: x";</script></head><body>
+        <table>
+          <tr><td>Ký hiệu:</td><td>1C25TAA</td></tr>
+          <tr><td>Số hóa đơn:</td><td>0000123</td></tr>
+          <tr><td>Mã số thuế người bán:</td><td>0101234567</td></tr>
+          <tr><td>Đơn vị bán hàng:</td><td>C&#212;NG TY TNHH H&#431;NG THỊNH &amp; CỘNG SỰ</td></tr>
+          <tr><td colspan="2">Người bán hàng (Ký, ghi rõ họ tên)</td></tr>
+        </table>
+        </body></html>"#;
+
+    fn naming(file_name_template: &str, folder_template: &str, transliteration: Transliteration) -> FileNaming {
+        FileNaming {
+            file_name_template: file_name_template.to_string(),
+            folder_template: folder_template.to_string(),
+            transliteration,
+        }
+    }
+
+    #[test]
+    fn test_scrape_result_page() {
+        let fields = scrape_page(RESULT_PAGE);
+        assert_eq!(fields.seller_name.as_deref(), Some("CÔNG TY TNHH HƯNG THỊNH & CỘNG SỰ"));
+        assert_eq!(fields.invoice_number.as_deref(), Some("123"));
+
+        assert_eq!(scrape_page("<p>Không tìm thấy hóa đơn</p>"), PageFields::default());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let page = scrape_page(RESULT_PAGE);
+        let values = NameValues {
+            code: "C24TAA0000001/Ab",
+            seller_tax_code: Some("0101234567"),
+            page: &page,
+        };
+
+        // The default keeps the lookup code as the file name
+        assert_eq!(
+            FileNaming::default().relative_path(&values),
+            PathBuf::from("C24TAA0000001_Ab.pdf")
+        );
+        assert_eq!(
+            naming("{number}_{code}", "{seller}", Transliteration::Keep).relative_path(&values),
+            PathBuf::from("CÔNG TY TNHH HƯNG THỊNH & CỘNG SỰ").join("123_C24TAA0000001_Ab.pdf")
+        );
+        assert_eq!(
+            naming("{number}", "{tax_code}/{seller}", Transliteration::AsciiUnderscore).relative_path(&values),
+            PathBuf::from("0101234567").join("CONG_TY_TNHH_HUNG_THINH_&_CONG_SU").join("123.pdf")
+        );
+
+        // Missing fields leave no dangling separators or empty names
+        let empty = PageFields::default();
+        let values = NameValues {
+            code: "ABC123",
+            seller_tax_code: None,
+            page: &empty,
+        };
+        assert_eq!(
+            naming("{seller}", "{seller}/../{tax_code}", Transliteration::Ascii).relative_path(&values),
+            PathBuf::from("ABC123.pdf")
+        );
+        assert_eq!(
+            naming("{code} - {seller}", "", Transliteration::Keep).relative_path(&values),
+            PathBuf::from("ABC123.pdf")
+        );
+    }
+
+    #[test]
+    fn test_validate_templates() {
+        assert!(naming("{seller}_{number}", "{tax_code}/{seller}", Transliteration::Keep).validate().is_ok());
+        assert!(FileNaming::default().validate().is_ok());
+        assert!(naming("{date}", "", Transliteration::Keep).validate().is_err());
+        assert!(naming("{code", "", Transliteration::Keep).validate().is_err());
+        assert!(naming("", "seller}", Transliteration::Keep).validate().is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::AppError;
+use crate::services::browser::LookaheadTab;
+use crate::services::browser_driver::BrowserDriver;
+
+/// Manifest of a fixture directory, next to the files it names
+const MANIFEST_FILE: &str = "fixture.json";
+
+/// One pass through the lookup form, as the portal answered it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordedLookup {
+    /// Page loaded; empty when the browser was already on it
+    pub url: String,
+    /// Why the page did not load
+    #[serde(default)]
+    pub navigation_error: Option<String>,
+    /// Captcha PNG, named after its SHA-256
+    #[serde(default)]
+    pub captcha_image: Option<String>,
+    /// Text typed into the captcha field
+    #[serde(default)]
+    pub captcha_text: Option<String>,
+    /// Message the portal showed after submitting
+    #[serde(default)]
+    pub page_error: Option<String>,
+    /// Page HTML after submitting
+    #[serde(default)]
+    pub page_html: Option<String>,
+    #[serde(default)]
+    pub pdf: Option<String>,
+    #[serde(default)]
+    pub xml: Option<String>,
+    #[serde(default)]
+    pub converted_pdf: Option<String>,
+}
+
+/// What the portal served while one invoice was downloaded, saved by
+/// `RecordingDriver` and served back by `ReplayDriver`, so a run can be
+/// replayed in CI and offline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PortalFixture {
+    pub invoice_code: String,
+    pub recorded_at: String,
+    pub lookups: Vec<RecordedLookup>,
+}
+
+impl PortalFixture {
+    /// Write the manifest and the files it names into `dir`, replacing an
+    /// earlier recording
+    pub fn save(&self, dir: &Path, files: Vec<(String, Vec<u8>)>) -> Result<PathBuf, AppError> {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+        std::fs::create_dir_all(dir)
+            .map_err(|e| AppError::IoError(format!("Failed to create fixture directory: {}", e)))?;
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents)?;
+        }
+        let manifest = serde_json::to_vec_pretty(self)
+            .map_err(|e| AppError::IoError(format!("Failed to serialize fixture: {}", e)))?;
+        std::fs::write(dir.join(MANIFEST_FILE), manifest)?;
+
+        Ok(dir.to_path_buf())
+    }
+
+    #[cfg(test)]
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        let manifest = std::fs::read(dir.join(MANIFEST_FILE))
+            .map_err(|e| AppError::IoError(format!("Cannot read fixture {}: {}", dir.display(), e)))?;
+        serde_json::from_slice(&manifest)
+            .map_err(|e| AppError::IoError(format!("Invalid fixture {}: {}", dir.display(), e)))
+    }
+
+    /// Captcha text typed for each recorded image, keyed by the image's
+    /// SHA-256, for `CaptchaSolver::from_answers`
+    #[cfg(test)]
+    pub fn captcha_answers(&self) -> HashMap<String, String> {
+        self.lookups
+            .iter()
+            .filter_map(|lookup| {
+                let hash = Path::new(lookup.captcha_image.as_deref()?).file_stem()?;
+                Some((hash.to_string_lossy().to_string(), lookup.captcha_text.clone()?))
+            })
+            .collect()
+    }
+}
+
+/// Browser that passes every call on to `inner` and keeps the pages,
+/// captchas and files it returned, to be saved as a fixture
+pub struct RecordingDriver<'a, B: BrowserDriver> {
+    inner: &'a mut B,
+    fixture: Mutex<PortalFixture>,
+    /// Contents of the files named in the fixture
+    files: Mutex<Vec<(String, Vec<u8>)>>,
+}
+
+impl<'a, B: BrowserDriver> RecordingDriver<'a, B> {
+    pub fn new(inner: &'a mut B, invoice_code: &str) -> Self {
+        Self {
+            inner,
+            fixture: Mutex::new(PortalFixture {
+                invoice_code: invoice_code.to_string(),
+                recorded_at: chrono::Utc::now().to_rfc3339(),
+                lookups: Vec::new(),
+            }),
+            files: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Write what was recorded into `dir`, see `PortalFixture::save`
+    pub fn save(self, dir: &Path) -> Result<PathBuf, AppError> {
+        let fixture = self.fixture.into_inner().unwrap_or_else(|e| e.into_inner());
+        let files = self.files.into_inner().unwrap_or_else(|e| e.into_inner());
+        fixture.save(dir, files)
+    }
+
+    /// Change the current lookup, starting one when the browser was already
+    /// on the lookup page
+    fn update(&self, change: impl FnOnce(usize, &mut RecordedLookup)) {
+        let mut fixture = self.fixture.lock().unwrap_or_else(|e| e.into_inner());
+        if fixture.lookups.is_empty() {
+            fixture.lookups.push(RecordedLookup::default());
+        }
+        let index = fixture.lookups.len() - 1;
+        change(index + 1, &mut fixture.lookups[index]);
+    }
+
+    fn add_file(&self, name: String, contents: Vec<u8>) {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).push((name, contents));
+    }
+}
+
+impl<B: BrowserDriver> BrowserDriver for RecordingDriver<'_, B> {
+    fn navigate_to_search(&self, url: &str) -> Result<(), AppError> {
+        let navigated = self.inner.navigate_to_search(url);
+        self.fixture
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .lookups
+            .push(RecordedLookup {
+                url: url.to_string(),
+                navigation_error: navigated.as_ref().err().map(|e| e.to_string()),
+                ..RecordedLookup::default()
+            });
+        navigated
+    }
+
+    fn fill_invoice_code(&self, code: &str) -> Result<(), AppError> {
+        self.inner.fill_invoice_code(code)
+    }
+
+    fn fill_portal_field(&self, field: &str, value: &str) -> Result<(), AppError> {
+        self.inner.fill_portal_field(field, value)
+    }
+
+    fn get_captcha_screenshot(&self) -> Result<Vec<u8>, AppError> {
+        let image = self.inner.get_captcha_screenshot()?;
+        let name = format!("{}.png", hex::encode(Sha256::digest(&image)));
+        self.update(|_, lookup| lookup.captcha_image = Some(name.clone()));
+        self.add_file(name, image.clone());
+        Ok(image)
+    }
+
+    fn fill_captcha(&self, text: &str) -> Result<(), AppError> {
+        self.update(|_, lookup| lookup.captcha_text = Some(text.to_string()));
+        self.inner.fill_captcha(text)
+    }
+
+    fn submit(&self) -> Result<(), AppError> {
+        self.inner.submit()
+    }
+
+    fn check_for_error(&self) -> Option<String> {
+        let error = self.inner.check_for_error();
+        let html = self.inner.page_html().ok();
+        let mut html_file = None;
+        self.update(|index, lookup| {
+            lookup.page_error = error.clone();
+            if html.is_some() {
+                let name = format!("lookup-{}.html", index);
+                lookup.page_html = Some(name.clone());
+                html_file = Some(name);
+            }
+        });
+        if let (Some(name), Some(html)) = (html_file, html) {
+            self.add_file(name, html.into_bytes());
+        }
+        error
+    }
+
+    fn download_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        let pdf = self.inner.download_pdf(base_url, client)?;
+        let mut name = String::new();
+        self.update(|index, lookup| {
+            name = format!("lookup-{}.pdf", index);
+            lookup.pdf = Some(name.clone());
+        });
+        self.add_file(name, pdf.clone());
+        Ok(pdf)
+    }
+
+    fn download_xml(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        let xml = self.inner.download_xml(base_url, client)?;
+        let mut name = String::new();
+        self.update(|index, lookup| {
+            name = format!("lookup-{}.xml", index);
+            lookup.xml = Some(name.clone());
+        });
+        self.add_file(name, xml.clone());
+        Ok(xml)
+    }
+
+    fn download_converted_pdf(
+        &self,
+        base_url: &str,
+        client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        let pdf = self.inner.download_converted_pdf(base_url, client)?;
+        let mut name = String::new();
+        self.update(|index, lookup| {
+            name = format!("lookup-{}-chuyen-doi.pdf", index);
+            lookup.converted_pdf = Some(name.clone());
+        });
+        self.add_file(name, pdf.clone());
+        Ok(pdf)
+    }
+
+    fn open_lookahead_tab(&self) -> Result<LookaheadTab, AppError> {
+        self.inner.open_lookahead_tab()
+    }
+
+    fn memory_usage_mb(&self) -> Option<u64> {
+        self.inner.memory_usage_mb()
+    }
+
+    fn relaunch(&mut self, headless: bool) -> Result<(), AppError> {
+        self.inner.relaunch(headless)
+    }
+
+    fn set_selector_overrides(&self, overrides: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+        self.inner.set_selector_overrides(overrides)
+    }
+
+    fn add_selector_override(&self, field: &str, selector: &str) {
+        self.inner.add_selector_override(field, selector)
+    }
+
+    fn page_html(&self) -> Result<String, AppError> {
+        self.inner.page_html()
+    }
+
+    fn element_exists(&self, selector: &str) -> bool {
+        self.inner.element_exists(selector)
+    }
+}
+
+/// Browser that serves a recorded fixture back, one lookup per page load,
+/// without Chrome or the network; pair it with a solver answering the
+/// recorded captchas, see `CaptchaSolver::from_answers`
+pub struct ReplayDriver {
+    dir: PathBuf,
+    lookups: Mutex<VecDeque<RecordedLookup>>,
+    current: Mutex<Option<RecordedLookup>>,
+}
+
+impl ReplayDriver {
+    pub fn new(dir: &Path, fixture: &PortalFixture) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            lookups: Mutex::new(fixture.lookups.iter().cloned().collect()),
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Move on to the next recorded lookup
+    fn advance(&self) -> Option<RecordedLookup> {
+        let next = self.lookups.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+        *self.current.lock().unwrap_or_else(|e| e.into_inner()) = next.clone();
+        next
+    }
+
+    /// The lookup being replayed; the first one when the run started on a
+    /// page loaded earlier
+    fn current(&self) -> Option<RecordedLookup> {
+        let current = self.current.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        current.or_else(|| self.advance())
+    }
+
+    fn read(&self, file: Option<String>, missing: &str) -> Result<Vec<u8>, AppError> {
+        let file = file.ok_or_else(|| AppError::ElementNotFound(missing.to_string()))?;
+        std::fs::read(self.dir.join(&file))
+            .map_err(|e| AppError::IoError(format!("Cannot read fixture file {}: {}", file, e)))
+    }
+}
+
+impl BrowserDriver for ReplayDriver {
+    fn navigate_to_search(&self, _url: &str) -> Result<(), AppError> {
+        let lookup = self
+            .advance()
+            .ok_or_else(|| AppError::BrowserError("Fixture has no more recorded lookups".to_string()))?;
+        match lookup.navigation_error {
+            Some(error) => Err(AppError::BrowserError(error)),
+            None => Ok(()),
+        }
+    }
+
+    fn fill_invoice_code(&self, _code: &str) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn fill_portal_field(&self, _field: &str, _value: &str) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn get_captcha_screenshot(&self) -> Result<Vec<u8>, AppError> {
+        self.read(self.current().and_then(|lookup| lookup.captcha_image), "Captcha image")
+    }
+
+    fn fill_captcha(&self, _text: &str) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn submit(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn check_for_error(&self) -> Option<String> {
+        self.current().and_then(|lookup| lookup.page_error)
+    }
+
+    fn download_pdf(
+        &self,
+        _base_url: &str,
+        _client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        self.read(self.current().and_then(|lookup| lookup.pdf), "Download PDF link")
+    }
+
+    fn download_xml(
+        &self,
+        _base_url: &str,
+        _client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        self.read(self.current().and_then(|lookup| lookup.xml), "Download XML link")
+    }
+
+    fn download_converted_pdf(
+        &self,
+        _base_url: &str,
+        _client: &reqwest::blocking::Client,
+    ) -> Result<Vec<u8>, AppError> {
+        self.read(
+            self.current().and_then(|lookup| lookup.converted_pdf),
+            "Download converted PDF link",
+        )
+    }
+
+    fn open_lookahead_tab(&self) -> Result<LookaheadTab, AppError> {
+        Err(AppError::BrowserError("No second tab when replaying".to_string()))
+    }
+
+    fn memory_usage_mb(&self) -> Option<u64> {
+        None
+    }
+
+    fn relaunch(&mut self, _headless: bool) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn set_selector_overrides(&self, _overrides: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+        HashMap::new()
+    }
+
+    fn add_selector_override(&self, _field: &str, _selector: &str) {}
+
+    fn page_html(&self) -> Result<String, AppError> {
+        let html = self.read(self.current().and_then(|lookup| lookup.page_html), "Page HTML")?;
+        Ok(String::from_utf8_lossy(&html).to_string())
+    }
+
+    fn element_exists(&self, _selector: &str) -> bool {
+        false
+    }
+}
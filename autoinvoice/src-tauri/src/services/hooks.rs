@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::status::impl_setting_parse;
+
+/// Longest a hook may run before it is killed
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often a running hook is checked for exit
+const HOOK_POLL: Duration = Duration::from_millis(100);
+
+/// Longest stderr excerpt kept in the error of a failed hook
+const MAX_STDERR_CHARS: usize = 500;
+
+/// When the post-download hook runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookScope {
+    /// After every invoice, whatever its outcome
+    #[default]
+    Invoice,
+    /// Once, after the whole batch
+    Batch,
+}
+
+impl HookScope {
+    pub const ALL: &'static [HookScope] = &[HookScope::Invoice, HookScope::Batch];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookScope::Invoice => "invoice",
+            HookScope::Batch => "batch",
+        }
+    }
+}
+
+impl_setting_parse!(HookScope);
+
+/// Check the hook points at an existing file before a batch relies on it
+pub fn validate_hook(path: &str) -> Result<(), AppError> {
+    if path.is_empty() || Path::new(path).is_file() {
+        return Ok(());
+    }
+
+    Err(AppError::ConfigError(format!(
+        "Post-download hook not found: {}",
+        path
+    )))
+}
+
+/// Run the hook at `path` with `context` as JSON on stdin, killing it after `timeout`
+pub fn run_hook(path: &str, context: &serde_json::Value, timeout: Duration) -> Result<(), AppError> {
+    let mut child = Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::ConfigError(format!("Failed to start hook {}: {}", path, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores its input closes the pipe early; that is not an error
+        let _ = stdin.write_all(context.to_string().as_bytes());
+    }
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait()? {
+            Some(status) => break status,
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AppError::DownloadFailed(format!(
+                    "Hook {} did not finish within {}s",
+                    path,
+                    timeout.as_secs()
+                )));
+            }
+            None => thread::sleep(HOOK_POLL),
+        }
+    };
+
+    if status.success() {
+        return Ok(());
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let stderr: String = stderr.trim().chars().take(MAX_STDERR_CHARS).collect();
+
+    Err(AppError::DownloadFailed(format!(
+        "Hook {} exited with {}: {}",
+        path, status, stderr
+    )))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_run_hook_passes_context_on_stdin() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let output = dir.join("context.json");
+        let script = dir.join("hook.sh");
+        std::fs::write(&script, format!("#!/bin/sh\ncat > '{}'\n", output.display())).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let context = serde_json::json!({ "event": "invoice", "code": "C25TLK0019654" });
+        run_hook(&script.to_string_lossy(), &context, HOOK_TIMEOUT).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(written, context);
+
+        std::fs::write(&script, "#!/bin/sh\necho broken >&2\nexit 3\n").unwrap();
+        let err = run_hook(&script.to_string_lossy(), &context, HOOK_TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("broken"));
+    }
+}
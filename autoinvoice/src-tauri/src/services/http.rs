@@ -0,0 +1,95 @@
+use reqwest::blocking::Client as BlockingClient;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::AppError;
+
+/// Idle connections kept per host; a batch talks to one portal and the OpenAI API
+const POOL_MAX_IDLE_PER_HOST: usize = 4;
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u32 = 10;
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpTimeouts {
+    pub connect: Duration,
+    /// Whole request, including reading the body
+    pub request: Duration,
+}
+
+impl Default for HttpTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS as u64),
+            request: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS as u64),
+        }
+    }
+}
+
+/// HTTP clients shared by everything a batch fetches, so pooled connections
+/// and TLS sessions to the portal and the OpenAI API outlive a single invoice.
+/// HTTP/2 is used whenever the server negotiates it.
+#[derive(Clone)]
+pub struct HttpClients {
+    timeouts: HttpTimeouts,
+    async_client: reqwest::Client,
+    /// Built on first use from a worker thread: creating a blocking client
+    /// on an async runtime thread panics
+    blocking: Arc<Mutex<Option<BlockingClient>>>,
+}
+
+impl HttpClients {
+    pub fn new(timeouts: HttpTimeouts) -> Result<Self, AppError> {
+        let async_client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.request)
+            .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .build()
+            .map_err(|e| AppError::NetworkError(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self {
+            timeouts,
+            async_client,
+            blocking: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    pub fn async_client(&self) -> &reqwest::Client {
+        &self.async_client
+    }
+
+    /// Client for blocking worker threads; must not be called from async code
+    pub fn blocking(&self) -> Result<BlockingClient, AppError> {
+        let mut blocking = self.blocking.lock().unwrap();
+
+        if let Some(client) = blocking.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let client = BlockingClient::builder()
+            .connect_timeout(self.timeouts.connect)
+            .timeout(self.timeouts.request)
+            .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .build()
+            .map_err(|e| AppError::NetworkError(format!("Failed to build HTTP client: {}", e)))?;
+
+        *blocking = Some(client.clone());
+        Ok(client)
+    }
+
+    /// Whether `url` answers at all; any HTTP status counts as reachable
+    pub async fn check_reachable(&self, url: &str) -> Result<(), AppError> {
+        self.async_client
+            .head(url)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| AppError::NetworkError(e.to_string()))
+    }
+}
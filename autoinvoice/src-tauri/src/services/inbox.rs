@@ -0,0 +1,552 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use native_tls::{TlsConnector, TlsStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::commands::download::{queue_request, DownloadOverrides, DownloadState, StartDownloadRequest};
+use crate::commands::settings::Settings;
+use crate::error::AppError;
+use crate::services::app_lock::AppLock;
+use crate::services::database::Database;
+use crate::services::downloader::InvoiceDownloadRequest;
+use crate::services::excel_parser::{self, LookupMode};
+use crate::services::pdf_metadata;
+use crate::services::settings_service::SettingsService;
+
+pub const DEFAULT_IMAP_PORT: u16 = 993;
+pub const DEFAULT_IMAP_FOLDER: &str = "INBOX";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const IMAP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Mail read on the first poll, so connecting a busy mailbox does not
+/// queue years of invoices
+const FIRST_POLL_DAYS: i64 = 7;
+
+/// Messages fetched per poll; the rest wait for the next one
+const MAX_MESSAGES_PER_POLL: usize = 50;
+
+/// Mailbox watched for invoice notification emails
+#[derive(Debug, Clone, Default)]
+pub struct ImapConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub folder: String,
+}
+
+impl ImapConfig {
+    pub fn validate(&self) -> Result<(), AppError> {
+        for (name, value) in [
+            ("server", &self.host),
+            ("username", &self.username),
+            ("password", &self.password),
+            ("folder", &self.folder),
+        ] {
+            if value.is_empty() {
+                return Err(AppError::ConfigError(format!("IMAP {} is required", name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Messages already read: the mailbox's UIDVALIDITY and the highest UID seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InboxCursor {
+    pub uid_validity: u32,
+    pub last_uid: u32,
+}
+
+/// What an email holds that can point at invoices
+#[derive(Debug, Default)]
+struct MailContent {
+    subject: String,
+    texts: Vec<String>,
+    pdfs: Vec<Vec<u8>>,
+}
+
+impl MailContent {
+    /// Lookup codes from the body and the PDF attachments, each with the
+    /// portal the mail links to
+    fn invoices(&self) -> Vec<(String, Option<String>)> {
+        let mut text = self.texts.join("\n");
+        for pdf in &self.pdfs {
+            if let Ok(pdf_text) = pdf_metadata::extract_text_from_bytes(pdf) {
+                text.push('\n');
+                text.push_str(&pdf_text);
+            }
+        }
+
+        let is_notification = text.contains("vnpt-invoice.com.vn")
+            || format!("{} {}", self.subject, text).to_lowercase().contains("hóa đơn");
+        if !is_notification {
+            return Vec::new();
+        }
+
+        let portal_url = excel_parser::extract_vnpt_urls(&text).into_iter().next();
+        excel_parser::find_invoice_codes(&text)
+            .into_iter()
+            .map(|code| (code, portal_url.clone()))
+            .collect()
+    }
+}
+
+/// Poll the mailbox every few minutes while inbox ingestion is enabled and
+/// queue the invoices of new notification emails into the inbox batch
+pub fn spawn_poller(app: AppHandle, db: Arc<Database>, mut settings_rx: watch::Receiver<Settings>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let settings = settings_rx.borrow_and_update().clone();
+            let read_only = app.state::<AppLock>().is_read_only();
+            if settings.inbox_enabled && !read_only {
+                // A failed poll is retried on the next tick
+                let _ = poll(&app, &db, settings.imap_config()).await;
+            }
+
+            // Poll right away once ingestion is turned on or the mailbox changes
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                Ok(()) = settings_rx.changed() => {}
+            }
+        }
+    });
+}
+
+/// Read new mail once and queue what it points at
+///
+/// # Returns
+/// Number of invoices added to the inbox batch
+pub async fn poll(app: &AppHandle, db: &Arc<Database>, config: ImapConfig) -> Result<usize, AppError> {
+    config.validate()?;
+    let cursor = db.get_inbox_cursor()?;
+    let (cursor, mails) = tokio::task::spawn_blocking(move || fetch_new_mail(&config, cursor))
+        .await
+        .map_err(|e| AppError::NetworkError(format!("Task panicked: {}", e)))??;
+
+    let mut invoices: Vec<InvoiceDownloadRequest> = Vec::new();
+    for (code, portal_url) in mails.iter().flat_map(|raw| parse_mail(raw).invoices()) {
+        if invoices.iter().any(|invoice| invoice.code == code) || db.is_code_downloaded(&code)? {
+            continue;
+        }
+        invoices.push(InvoiceDownloadRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            code,
+            seller_tax_code: None,
+            lookup_mode: LookupMode::Code,
+            invoice_series: None,
+            invoice_number: None,
+            portal_url,
+        });
+    }
+
+    let added = add_to_inbox_batch(app, db, invoices)?;
+    db.set_inbox_cursor(cursor)?;
+    Ok(added)
+}
+
+/// Append invoices to the inbox batch while it is still queued; once it was
+/// started, a new inbox batch takes its place
+fn add_to_inbox_batch(
+    app: &AppHandle,
+    db: &Arc<Database>,
+    invoices: Vec<InvoiceDownloadRequest>,
+) -> Result<usize, AppError> {
+    let queued = match db.get_inbox_batch_id()? {
+        Some(batch_id) => db.get_queued_download(&batch_id)?,
+        None => None,
+    };
+    let mut request = match queued {
+        Some(serialized) => serde_json::from_str(&serialized)
+            .map_err(|e| AppError::ConfigError(format!("Invalid queued download request: {}", e)))?,
+        None if invoices.is_empty() => return Ok(0),
+        None => StartDownloadRequest {
+            batch_id: uuid::Uuid::new_v4().to_string(),
+            name: Some(format!("Hộp thư {}", chrono::Local::now().format("%d/%m/%Y"))),
+            source_file: None,
+            source_path: None,
+            source_hash: None,
+            invoices: Vec::new(),
+            overrides: DownloadOverrides::default(),
+        },
+    };
+
+    let before = request.invoices.len();
+    for invoice in invoices {
+        if !request.invoices.iter().any(|queued| queued.code == invoice.code) {
+            request.invoices.push(invoice);
+        }
+    }
+
+    let settings = app.state::<SettingsService>().get();
+    queue_request(&app.state::<DownloadState>(), &settings, db, &request)?;
+    db.update_batch_total(&request.batch_id, request.invoices.len() as u32)?;
+    db.set_inbox_batch_id(&request.batch_id)?;
+    Ok(request.invoices.len() - before)
+}
+
+/// Raw messages that arrived after `cursor`, and the cursor to save once
+/// they are queued
+fn fetch_new_mail(
+    config: &ImapConfig,
+    cursor: Option<InboxCursor>,
+) -> Result<(InboxCursor, Vec<Vec<u8>>), AppError> {
+    let mut session = ImapSession::connect(config)?;
+    session.command(&format!("LOGIN {} {}", quote(&config.username), quote(&config.password)))?;
+    let selected = session.command(&format!("SELECT {}", quote(&config.folder)))?;
+    let uid_validity = selected
+        .iter()
+        .find_map(|line| response_code_value(&line.text, "UIDVALIDITY"))
+        .unwrap_or_default();
+
+    let search = match cursor {
+        Some(cursor) if cursor.uid_validity == uid_validity => {
+            format!("UID SEARCH UID {}:*", cursor.last_uid + 1)
+        }
+        _ => {
+            let since = chrono::Local::now() - chrono::Duration::days(FIRST_POLL_DAYS);
+            format!("UID SEARCH SINCE {}", since.format("%d-%b-%Y"))
+        }
+    };
+    let last_uid = cursor
+        .filter(|cursor| cursor.uid_validity == uid_validity)
+        .map(|cursor| cursor.last_uid)
+        .unwrap_or_default();
+
+    // "n:*" always matches the newest message, even when it is older than n
+    let mut uids: Vec<u32> = session
+        .command(&search)?
+        .iter()
+        .filter_map(|line| line.text.strip_prefix("* SEARCH"))
+        .flat_map(|uids| uids.split_whitespace().filter_map(|uid| uid.parse().ok()))
+        .filter(|uid| *uid > last_uid)
+        .collect();
+    uids.sort_unstable();
+    uids.truncate(MAX_MESSAGES_PER_POLL);
+
+    let mut mails = Vec::new();
+    for uid in &uids {
+        let fetched = session.command(&format!("UID FETCH {} BODY.PEEK[]", uid))?;
+        if let Some(raw) = fetched.into_iter().find_map(|line| line.literals.into_iter().next()) {
+            mails.push(raw);
+        }
+    }
+    let _ = session.command("LOGOUT");
+
+    let cursor = InboxCursor {
+        uid_validity,
+        last_uid: uids.last().copied().unwrap_or(last_uid),
+    };
+    Ok((cursor, mails))
+}
+
+/// One response line, with the literals sent inside it
+struct ImapLine {
+    text: String,
+    literals: Vec<Vec<u8>>,
+}
+
+/// Just enough IMAP over TLS to log in, search and fetch messages
+struct ImapSession {
+    stream: BufReader<TlsStream<TcpStream>>,
+    next_tag: u32,
+}
+
+impl ImapSession {
+    fn connect(config: &ImapConfig) -> Result<Self, AppError> {
+        let tcp = TcpStream::connect((config.host.as_str(), config.port))
+            .map_err(|e| AppError::NetworkError(format!("Cannot reach IMAP server {}: {}", config.host, e)))?;
+        tcp.set_read_timeout(Some(IMAP_TIMEOUT))?;
+        tcp.set_write_timeout(Some(IMAP_TIMEOUT))?;
+        let tls = TlsConnector::new()
+            .map_err(|e| AppError::NetworkError(format!("TLS setup failed: {}", e)))?
+            .connect(&config.host, tcp)
+            .map_err(|e| AppError::NetworkError(format!("TLS handshake with {} failed: {}", config.host, e)))?;
+
+        let mut session = Self {
+            stream: BufReader::new(tls),
+            next_tag: 1,
+        };
+        let greeting = session.read_line()?;
+        if !greeting.text.starts_with("* OK") && !greeting.text.starts_with("* PREAUTH") {
+            return Err(AppError::NetworkError(format!("IMAP server refused: {}", greeting.text)));
+        }
+        Ok(session)
+    }
+
+    /// Send a command and collect its untagged responses
+    fn command(&mut self, command: &str) -> Result<Vec<ImapLine>, AppError> {
+        let tag = format!("a{}", self.next_tag);
+        self.next_tag += 1;
+        write!(self.stream.get_mut(), "{} {}\r\n", tag, command)?;
+        self.stream.get_mut().flush()?;
+
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            if let Some(status) = line.text.strip_prefix(&format!("{} ", tag)) {
+                if status.starts_with("OK") {
+                    return Ok(lines);
+                }
+                // Never echo the LOGIN command, it holds the password
+                let verb = command.split_whitespace().next().unwrap_or_default();
+                return Err(AppError::NetworkError(format!("IMAP {} failed: {}", verb, status)));
+            }
+            lines.push(line);
+        }
+    }
+
+    /// Read a response line, following the `{n}` literals it announces
+    fn read_line(&mut self) -> Result<ImapLine, AppError> {
+        let mut line = ImapLine {
+            text: String::new(),
+            literals: Vec::new(),
+        };
+        loop {
+            let mut chunk = Vec::new();
+            if self.stream.read_until(b'\n', &mut chunk)? == 0 {
+                return Err(AppError::NetworkError("IMAP server closed the connection".to_string()));
+            }
+            let chunk = String::from_utf8_lossy(&chunk);
+            let chunk = chunk.trim_end_matches(['\r', '\n']);
+            line.text.push_str(chunk);
+
+            let Some(size) = literal_size(chunk) else {
+                return Ok(line);
+            };
+            let mut literal = vec![0; size];
+            self.stream.read_exact(&mut literal)?;
+            line.literals.push(literal);
+        }
+    }
+}
+
+/// Size announced by a line ending in `{n}`
+fn literal_size(line: &str) -> Option<usize> {
+    line.strip_suffix('}')?.rsplit_once('{')?.1.parse().ok()
+}
+
+/// Number in a response code such as `[UIDVALIDITY 3857529045]`
+fn response_code_value(line: &str, code: &str) -> Option<u32> {
+    let rest = &line[line.find(&format!("[{} ", code))? + code.len() + 2..];
+    rest[..rest.find(']')?].trim().parse().ok()
+}
+
+/// IMAP quoted string
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Subject, text parts and PDF attachments of a raw RFC 5322 message
+fn parse_mail(raw: &[u8]) -> MailContent {
+    let mut content = MailContent::default();
+    let (headers, _) = split_part(raw);
+    content.subject = decode_words(&header(&headers, "subject").unwrap_or_default());
+    collect_parts(raw, &mut content);
+    content
+}
+
+fn collect_parts(raw: &[u8], content: &mut MailContent) {
+    let (headers, body) = split_part(raw);
+    let content_type = header(&headers, "content-type").unwrap_or_else(|| "text/plain".to_string());
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+
+    if mime.starts_with("multipart/") {
+        if let Some(boundary) = parameter(&content_type, "boundary") {
+            for part in split_multipart(body, &boundary) {
+                collect_parts(part, content);
+            }
+        }
+        return;
+    }
+
+    let decoded = match header(&headers, "content-transfer-encoding")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "base64" => {
+            let compact: Vec<u8> = body.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+            STANDARD.decode(compact).unwrap_or_default()
+        }
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.to_vec(),
+    };
+
+    let file_name = header(&headers, "content-disposition")
+        .and_then(|disposition| parameter(&disposition, "filename"))
+        .or_else(|| parameter(&content_type, "name"))
+        .unwrap_or_default()
+        .to_lowercase();
+    if mime == "application/pdf" || file_name.ends_with(".pdf") {
+        content.pdfs.push(decoded);
+    } else if mime.starts_with("text/") {
+        content.texts.push(String::from_utf8_lossy(&decoded).to_string());
+    }
+}
+
+/// Headers (unfolded, one per line) and body of a message or part
+fn split_part(raw: &[u8]) -> (String, &[u8]) {
+    let (head, body) = match find(raw, b"\r\n\r\n") {
+        Some(end) => (&raw[..end], &raw[end + 4..]),
+        None => match find(raw, b"\n\n") {
+            Some(end) => (&raw[..end], &raw[end + 2..]),
+            None => (raw, &raw[raw.len()..]),
+        },
+    };
+    let head = String::from_utf8_lossy(head)
+        .replace("\r\n", "\n")
+        .replace("\n ", " ")
+        .replace("\n\t", " ");
+    (head, body)
+}
+
+/// Value of the first header called `name`
+fn header(headers: &str, name: &str) -> Option<String> {
+    headers.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_string())
+    })
+}
+
+/// Parameter of a header value such as `multipart/mixed; boundary="abc"`
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Parts between the `--boundary` delimiters of a multipart body
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut rest = body;
+    let Some(first) = find(rest, delimiter.as_bytes()) else {
+        return parts;
+    };
+    rest = &rest[first + delimiter.len()..];
+
+    // Each part starts after the delimiter's line break and ends before the next one
+    while !rest.starts_with(b"--") {
+        let start = rest.iter().position(|b| *b == b'\n').map_or(rest.len(), |i| i + 1);
+        rest = &rest[start..];
+        let Some(end) = find(rest, delimiter.as_bytes()) else {
+            break;
+        };
+        parts.push(trim_line_break(&rest[..end]));
+        rest = &rest[end + delimiter.len()..];
+    }
+    parts
+}
+
+fn trim_line_break(part: &[u8]) -> &[u8] {
+    let part = part.strip_suffix(b"\n").unwrap_or(part);
+    part.strip_suffix(b"\r").unwrap_or(part)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        match body[i] {
+            b'=' if body[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if body[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < body.len() => {
+                match u8::from_str_radix(&String::from_utf8_lossy(&body[i + 1..i + 3]), 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                    }
+                    Err(_) => {
+                        decoded.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Decode the `=?utf-8?B?...?=` and `=?utf-8?Q?...?=` words of a header;
+/// Vietnamese subjects always come encoded
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("=?") {
+        decoded.push_str(rest[..start].trim_end_matches([' ', '\t']));
+        let word = &rest[start + 2..];
+        let parts: Vec<&str> = word.splitn(3, '?').collect();
+        let Some(end) = parts.get(2).and_then(|text| text.find("?=")) else {
+            decoded.push_str(&rest[start..]);
+            return decoded;
+        };
+        let text = &parts[2][..end];
+        let bytes = match parts[1].to_ascii_uppercase().as_str() {
+            "B" => STANDARD.decode(text).unwrap_or_default(),
+            _ => decode_quoted_printable(text.replace('_', " ").as_bytes()),
+        };
+        decoded.push_str(&String::from_utf8_lossy(&bytes));
+        rest = &parts[2][end + 2..];
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notification_mail() {
+        let raw = b"Subject: =?UTF-8?B?VGjDtG5nIGLDoW8gaMOzYSDEkcahbg==?=\r\n\
+Content-Type: multipart/alternative; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+M=C3=A3 tra c=E1=BB=A9u: C25TLK0019654_Ln\r\n\
+Tra c=E1=BB=A9u t=E1=BA=A1i https://0101-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey\r\n\
+--b1--\r\n";
+
+        let mail = parse_mail(raw);
+        assert_eq!(mail.subject, "Thông báo hóa đơn");
+        assert_eq!(
+            mail.invoices(),
+            vec![(
+                "C25TLK0019654_Ln".to_string(),
+                Some("https://0101-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey".to_string())
+            )]
+        );
+
+        assert_eq!(literal_size("* 1 FETCH (UID 7 BODY[] {1234}"), Some(1234));
+        assert_eq!(
+            response_code_value("* OK [UIDVALIDITY 3857529045] UIDs valid", "UIDVALIDITY"),
+            Some(3857529045)
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+
+use crate::commands::history::HistoryInvoice;
+use crate::commands::settings::Settings;
+use crate::error::AppError;
+use crate::services::pdf_metadata::InvoiceMetadata;
+use crate::status::InvoiceStatus;
+use crate::status::impl_setting_parse;
+
+/// Byte order mark, which tells Excel a CSV is UTF-8
+const UTF8_BOM: &str = "\u{feff}";
+
+/// File format of an invoice list export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Field separator of CSV exports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum CsvDelimiter {
+    #[default]
+    Comma,
+    /// What Excel expects when the list separator is `;`, as with Vietnamese
+    /// regional settings
+    Semicolon,
+}
+
+impl CsvDelimiter {
+    pub const ALL: &'static [CsvDelimiter] = &[CsvDelimiter::Comma, CsvDelimiter::Semicolon];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CsvDelimiter::Comma => "comma",
+            CsvDelimiter::Semicolon => "semicolon",
+        }
+    }
+
+    fn as_char(&self) -> char {
+        match self {
+            CsvDelimiter::Comma => ',',
+            CsvDelimiter::Semicolon => ';',
+        }
+    }
+}
+
+impl_setting_parse!(CsvDelimiter);
+
+/// How CSV exports are written, so they open correctly in Excel whatever
+/// its regional settings
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsvOptions {
+    pub delimiter: CsvDelimiter,
+    /// `1234,5` rather than `1234.5`
+    pub decimal_comma: bool,
+    /// Start with a UTF-8 BOM, without which Excel garbles Vietnamese text
+    pub bom: bool,
+    /// `dd/MM/yyyy` rather than ISO 8601 dates
+    pub day_first_dates: bool,
+}
+
+impl CsvOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            delimiter: settings.csv_delimiter,
+            decimal_comma: settings.csv_decimal_comma,
+            bom: settings.csv_bom,
+            day_first_dates: settings.csv_day_first_dates,
+        }
+    }
+}
+
+/// Invoices whose status is in `statuses`; all of them when it is empty
+pub fn filter<'a>(invoices: &'a [HistoryInvoice], statuses: &[InvoiceStatus]) -> Vec<&'a HistoryInvoice> {
+    invoices
+        .iter()
+        .filter(|invoice| statuses.is_empty() || statuses.contains(&invoice.status))
+        .collect()
+}
+
+/// Render invoices as CSV with a header row, or as a JSON array; the CSV
+/// also has the issue date and total read from each PDF, keyed by invoice id
+pub fn render(
+    invoices: &[&HistoryInvoice],
+    metadata: &HashMap<String, InvoiceMetadata>,
+    format: ExportFormat,
+    options: &CsvOptions,
+) -> Result<String, AppError> {
+    match format {
+        ExportFormat::Csv => Ok(to_csv(invoices, metadata, options)),
+        ExportFormat::Json => serde_json::to_string_pretty(invoices)
+            .map_err(|e| AppError::IoError(format!("Failed to serialize invoices: {}", e))),
+    }
+}
+
+fn to_csv(
+    invoices: &[&HistoryInvoice],
+    metadata: &HashMap<String, InvoiceMetadata>,
+    options: &CsvOptions,
+) -> String {
+    let delimiter = options.delimiter.as_char();
+    let header = [
+        "code",
+        "status",
+        "error",
+        "note",
+        "file_path",
+        "downloaded_at",
+        "issued_date",
+        "total_payable",
+    ];
+
+    let mut csv = String::new();
+    if options.bom {
+        csv.push_str(UTF8_BOM);
+    }
+    csv.push_str(&header.join(&delimiter.to_string()));
+    csv.push_str("\r\n");
+    for invoice in invoices {
+        let metadata = metadata.get(&invoice.id);
+        let fields = [
+            invoice.code.clone(),
+            invoice.status.as_str().to_string(),
+            invoice.error.clone().unwrap_or_default(),
+            invoice.note.clone().unwrap_or_default(),
+            invoice.file_path.clone().unwrap_or_default(),
+            invoice
+                .downloaded_at
+                .as_deref()
+                .map(|at| format_timestamp(at, options))
+                .unwrap_or_default(),
+            metadata
+                .and_then(|metadata| metadata.issued_date.as_deref())
+                .map(|date| format_date(date, options))
+                .unwrap_or_default(),
+            metadata
+                .and_then(|metadata| metadata.total_payable)
+                .map(|amount| format_amount(amount, options))
+                .unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field, delimiter)).collect();
+        csv.push_str(&row.join(&delimiter.to_string()));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn format_amount(amount: f64, options: &CsvOptions) -> String {
+    let amount = amount.to_string();
+    if options.decimal_comma {
+        amount.replace('.', ",")
+    } else {
+        amount
+    }
+}
+
+/// `YYYY-MM-DD` as `dd/MM/yyyy` when asked; anything else is kept as is
+fn format_date(date: &str, options: &CsvOptions) -> String {
+    match chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(date) if options.day_first_dates => date.format("%d/%m/%Y").to_string(),
+        _ => date.to_string(),
+    }
+}
+
+/// RFC 3339 as local `dd/MM/yyyy HH:mm:ss` when asked
+fn format_timestamp(at: &str, options: &CsvOptions) -> String {
+    match chrono::DateTime::parse_from_rfc3339(at) {
+        Ok(at) if options.day_first_dates => at
+            .with_timezone(&chrono::Local)
+            .format("%d/%m/%Y %H:%M:%S")
+            .to_string(),
+        _ => at.to_string(),
+    }
+}
+
+/// Quote a field when it holds the delimiter, a quote or a line break
+/// (RFC 4180)
+fn csv_field(value: &str, delimiter: char) -> String {
+    if value.contains([delimiter, '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::pdf_metadata::MetadataSource;
+
+    fn invoice(code: &str, status: InvoiceStatus, error: Option<&str>) -> HistoryInvoice {
+        HistoryInvoice {
+            id: uuid::Uuid::new_v4().to_string(),
+            batch_id: String::new(),
+            code: code.to_string(),
+            status,
+            error: error.map(str::to_string),
+            file_path: None,
+            downloaded_at: None,
+            repro_path: None,
+            portal: None,
+            remote_url: None,
+            signature_status: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_export_failed_invoices() {
+        let invoices = [
+            invoice("C24TAA0000001_Ab", InvoiceStatus::Success, None),
+            invoice("C24TAA0000002_Ab", InvoiceStatus::NotFound, Some("Không tồn tại hóa đơn, mã \"sai\"")),
+            invoice("C24TAA0000003_Ab", InvoiceStatus::Failed, Some("Timeout")),
+        ];
+
+        let selected = filter(&invoices, &[InvoiceStatus::Failed, InvoiceStatus::NotFound]);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(filter(&invoices, &[]).len(), 3);
+
+        let options = CsvOptions::default();
+        let csv = render(&selected, &HashMap::new(), ExportFormat::Csv, &options).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "code,status,error,note,file_path,downloaded_at,issued_date,total_payable");
+        assert_eq!(
+            lines[1],
+            "C24TAA0000002_Ab,not-found,\"Không tồn tại hóa đơn, mã \"\"sai\"\"\",,,,,"
+        );
+        assert_eq!(lines[2], "C24TAA0000003_Ab,failed,Timeout,,,,,");
+
+        let json = render(&selected, &HashMap::new(), ExportFormat::Json, &options).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json[1]["code"], "C24TAA0000003_Ab");
+    }
+
+    #[test]
+    fn test_export_for_vietnamese_excel() {
+        let invoices = [invoice("C24TAA0000001_Ab", InvoiceStatus::Success, Some("a; b"))];
+        let metadata = HashMap::from([(
+            invoices[0].id.clone(),
+            InvoiceMetadata {
+                source: MetadataSource::Text,
+                series: None,
+                number: None,
+                issued_date: Some("2025-03-05".to_string()),
+                seller_tax_code: None,
+                total_before_tax: None,
+                total_tax: None,
+                total_payable: Some(1234567.5),
+            },
+        )]);
+        let options = CsvOptions {
+            delimiter: CsvDelimiter::Semicolon,
+            decimal_comma: true,
+            bom: true,
+            day_first_dates: true,
+        };
+
+        let csv = render(&filter(&invoices, &[]), &metadata, ExportFormat::Csv, &options).unwrap();
+        let csv = csv.strip_prefix(UTF8_BOM).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].starts_with("code;status;"));
+        assert_eq!(lines[1], "C24TAA0000001_Ab;success;\"a; b\";;;;05/03/2025;1234567,5");
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::error::AppError;
+
+/// Line totals may differ from the invoice totals by rounding
+const AMOUNT_TOLERANCE: f64 = 1.0;
+
+/// Structured content of a Circular 78 (TT78) e-invoice XML
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct XmlInvoice {
+    /// Template number (KHMSHDon)
+    pub template: String,
+    /// Series (KHHDon)
+    pub series: String,
+    /// Invoice number (SHDon)
+    pub number: String,
+    /// Issue date (NLap), as written in the XML
+    pub issued_date: String,
+    /// Currency (DVTTe), VND unless stated
+    pub currency: String,
+    pub exchange_rate: Option<f64>,
+    /// Code the tax authority assigned (MCCQT), absent on invoices without one
+    pub authority_code: Option<String>,
+    pub seller: XmlParty,
+    pub buyer: XmlParty,
+    pub lines: Vec<XmlLine>,
+    pub tax_breakdown: Vec<XmlTaxRate>,
+    pub total_before_tax: f64,
+    pub total_tax: f64,
+    pub total_payable: f64,
+    pub total_in_words: String,
+    /// Missing required fields and inconsistent totals; empty when the
+    /// invoice passed validation
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct XmlParty {
+    pub name: String,
+    pub tax_code: String,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct XmlLine {
+    /// Line kind (TChat): 1 goods, 2 promotion, 3 discount, 4 note
+    pub kind: u32,
+    pub name: String,
+    pub unit: String,
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub amount: f64,
+    /// Rate as written, e.g. "10%", "KCT"
+    pub tax_rate: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct XmlTaxRate {
+    pub tax_rate: String,
+    pub taxable_amount: f64,
+    pub tax_amount: f64,
+}
+
+impl XmlInvoice {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Read and parse an invoice XML file
+pub fn parse_file(path: &Path) -> Result<XmlInvoice, AppError> {
+    let xml = std::fs::read_to_string(path)
+        .map_err(|e| AppError::XmlError(format!("Failed to read {}: {}", path.display(), e)))?;
+    parse(&xml)
+}
+
+/// Parse a TT78 invoice, also when wrapped in a transmission envelope
+/// (`TDiep`), and validate it
+pub fn parse(xml: &str) -> Result<XmlInvoice, AppError> {
+    let document = Document::parse(xml)
+        .map_err(|e| AppError::XmlError(format!("Malformed XML: {}", e)))?;
+    let data = document
+        .descendants()
+        .find(|node| node.has_tag_name("DLHDon"))
+        .ok_or_else(|| AppError::XmlError("Not a TT78 invoice: no DLHDon element".to_string()))?;
+
+    let general = child(data, "TTChung");
+    let content = child(data, "NDHDon");
+    let totals = content.and_then(|node| child(node, "TToan"));
+    let mut issues = Vec::new();
+
+    let mut invoice = XmlInvoice {
+        template: text(general, "KHMSHDon"),
+        series: text(general, "KHHDon"),
+        number: text(general, "SHDon"),
+        issued_date: text(general, "NLap"),
+        currency: Some(text(general, "DVTTe"))
+            .filter(|currency| !currency.is_empty())
+            .unwrap_or_else(|| "VND".to_string()),
+        exchange_rate: optional_number(general, "TGia", &mut issues),
+        authority_code: data
+            .parent()
+            .and_then(|invoice| child(invoice, "MCCQT"))
+            .and_then(|node| node.text())
+            .map(|code| code.trim().to_string())
+            .filter(|code| !code.is_empty()),
+        seller: party(content.and_then(|node| child(node, "NBan"))),
+        buyer: party(content.and_then(|node| child(node, "NMua"))),
+        lines: content
+            .and_then(|node| child(node, "DSHHDVu"))
+            .map(|list| {
+                list.children()
+                    .filter(|node| node.has_tag_name("HHDVu"))
+                    .map(|line| XmlLine {
+                        kind: optional_number(Some(line), "TChat", &mut issues)
+                            .map(|kind| kind as u32)
+                            .unwrap_or(1),
+                        name: text(Some(line), "THHDVu"),
+                        unit: text(Some(line), "DVTinh"),
+                        quantity: number(Some(line), "SLuong", &mut issues),
+                        unit_price: number(Some(line), "DGia", &mut issues),
+                        amount: number(Some(line), "ThTien", &mut issues),
+                        tax_rate: text(Some(line), "TSuat"),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        tax_breakdown: totals
+            .and_then(|node| child(node, "THTTLTSuat"))
+            .map(|list| {
+                list.children()
+                    .filter(|node| node.has_tag_name("LTSuat"))
+                    .map(|rate| XmlTaxRate {
+                        tax_rate: text(Some(rate), "TSuat"),
+                        taxable_amount: number(Some(rate), "ThTien", &mut issues),
+                        tax_amount: number(Some(rate), "TThue", &mut issues),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        total_before_tax: number(totals, "TgTCThue", &mut issues),
+        total_tax: number(totals, "TgTThue", &mut issues),
+        total_payable: number(totals, "TgTTTBSo", &mut issues),
+        total_in_words: text(totals, "TgTTTBChu"),
+        issues: Vec::new(),
+    };
+
+    issues.extend(validate(&invoice));
+    invoice.issues = issues;
+    Ok(invoice)
+}
+
+/// Required fields and the arithmetic between lines, tax rates and totals
+fn validate(invoice: &XmlInvoice) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    for (value, field) in [
+        (&invoice.series, "series (KHHDon)"),
+        (&invoice.number, "invoice number (SHDon)"),
+        (&invoice.issued_date, "issue date (NLap)"),
+        (&invoice.seller.name, "seller name"),
+        (&invoice.seller.tax_code, "seller tax code"),
+    ] {
+        if value.is_empty() {
+            issues.push(format!("Missing {}", field));
+        }
+    }
+    for (party, role) in [(&invoice.seller, "seller"), (&invoice.buyer, "buyer")] {
+        if !party.tax_code.is_empty() && !is_tax_code(&party.tax_code) {
+            issues.push(format!("Invalid {} tax code: {}", role, party.tax_code));
+        }
+    }
+    if invoice.lines.is_empty() {
+        issues.push("Invoice has no lines".to_string());
+    }
+
+    // Promotions and notes carry no value; discounts reduce it
+    let lines_total: f64 = invoice
+        .lines
+        .iter()
+        .map(|line| match line.kind {
+            2 | 4 => 0.0,
+            3 => -line.amount,
+            _ => line.amount,
+        })
+        .sum();
+    if !invoice.lines.is_empty() && !amounts_match(lines_total, invoice.total_before_tax) {
+        issues.push(format!(
+            "Lines add up to {}, but the total before tax is {}",
+            lines_total, invoice.total_before_tax
+        ));
+    }
+
+    if !invoice.tax_breakdown.is_empty() {
+        let tax_total: f64 = invoice.tax_breakdown.iter().map(|rate| rate.tax_amount).sum();
+        if !amounts_match(tax_total, invoice.total_tax) {
+            issues.push(format!(
+                "Tax rates add up to {}, but the total tax is {}",
+                tax_total, invoice.total_tax
+            ));
+        }
+    }
+
+    if !amounts_match(invoice.total_before_tax + invoice.total_tax, invoice.total_payable) {
+        issues.push(format!(
+            "Total payable {} is not the total before tax {} plus tax {}",
+            invoice.total_payable, invoice.total_before_tax, invoice.total_tax
+        ));
+    }
+
+    issues
+}
+
+fn amounts_match(a: f64, b: f64) -> bool {
+    (a - b).abs() <= AMOUNT_TOLERANCE
+}
+
+/// 10 digits, or 10 digits and a 3-digit branch suffix
+fn is_tax_code(value: &str) -> bool {
+    let (main, branch) = value.split_once('-').unwrap_or((value, "000"));
+    main.len() == 10
+        && branch.len() == 3
+        && main.chars().chain(branch.chars()).all(|c| c.is_ascii_digit())
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, tag: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(tag))
+}
+
+fn text(node: Option<Node>, tag: &str) -> String {
+    node.and_then(|node| child(node, tag))
+        .and_then(|node| node.text())
+        .map(|value| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn party(node: Option<Node>) -> XmlParty {
+    XmlParty {
+        name: text(node, "Ten"),
+        tax_code: text(node, "MST"),
+        address: text(node, "DChi"),
+    }
+}
+
+fn optional_number(node: Option<Node>, tag: &str, issues: &mut Vec<String>) -> Option<f64> {
+    let value = text(node, tag);
+    if value.is_empty() {
+        return None;
+    }
+    match value.parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            issues.push(format!("{} is not a number: {}", tag, value));
+            None
+        }
+    }
+}
+
+fn number(node: Option<Node>, tag: &str, issues: &mut Vec<String>) -> f64 {
+    optional_number(node, tag, issues).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<HDon>
+  <DLHDon Id="data">
+    <TTChung>
+      <KHMSHDon>1</KHMSHDon>
+      <KHHDon>C25TAA</KHHDon>
+      <SHDon>123</SHDon>
+      <NLap>2025-03-01</NLap>
+      <DVTTe>VND</DVTTe>
+    </TTChung>
+    <NDHDon>
+      <NBan><Ten>Công ty A</Ten><MST>0101234567</MST><DChi>Hà Nội</DChi></NBan>
+      <NMua><Ten>Công ty B</Ten><MST>0309876543-001</MST></NMua>
+      <DSHHDVu>
+        <HHDVu><TChat>1</TChat><THHDVu>Giấy A4</THHDVu><SLuong>10</SLuong><DGia>50000</DGia><ThTien>500000</ThTien><TSuat>10%</TSuat></HHDVu>
+        <HHDVu><TChat>3</TChat><THHDVu>Chiết khấu</THHDVu><ThTien>100000</ThTien><TSuat>10%</TSuat></HHDVu>
+      </DSHHDVu>
+      <TToan>
+        <THTTLTSuat><LTSuat><TSuat>10%</TSuat><ThTien>400000</ThTien><TThue>40000</TThue></LTSuat></THTTLTSuat>
+        <TgTCThue>400000</TgTCThue>
+        <TgTThue>40000</TgTThue>
+        <TgTTTBSo>440000</TgTTTBSo>
+      </TToan>
+    </NDHDon>
+  </DLHDon>
+  <MCCQT>00ABCDEF</MCCQT>
+</HDon>"#;
+
+    #[test]
+    fn test_parse_and_validate() {
+        let invoice = parse(SAMPLE).unwrap();
+        assert!(invoice.is_valid(), "{:?}", invoice.issues);
+        assert_eq!(invoice.series, "C25TAA");
+        assert_eq!(invoice.seller.tax_code, "0101234567");
+        assert_eq!(invoice.lines.len(), 2);
+        assert_eq!(invoice.authority_code.as_deref(), Some("00ABCDEF"));
+
+        let broken = parse(&SAMPLE.replace("<TgTTTBSo>440000", "<TgTTTBSo>450000")).unwrap();
+        assert_eq!(broken.issues.len(), 1);
+
+        assert!(parse("<HDon/>").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use serde_json::Value;
+use std::cell::OnceCell;
+use std::path::{Component, Path};
+
+use crate::error::AppError;
+use crate::services::portal_profiles::{self, PortalProfile};
+
+/// Portals a URL argument may point at: the portal profiles, built in or
+/// saved, and the lookup page in the settings
+pub struct UrlAllowList {
+    pub profiles: Vec<PortalProfile>,
+    pub vnpt_url: String,
+}
+
+impl UrlAllowList {
+    fn allows(&self, url: &url::Url) -> bool {
+        let Some(host) = url.host_str().map(str::to_lowercase) else {
+            return false;
+        };
+        let saved_host = url::Url::parse(&self.vnpt_url)
+            .ok()
+            .and_then(|saved| saved.host_str().map(str::to_lowercase));
+
+        saved_host.as_deref() == Some(host.as_str())
+            || portal_profiles::resolve(&self.profiles, url.as_str()).is_some()
+    }
+}
+
+/// Check the arguments of a command as the webview sent them, before the
+/// command sees them, so a compromised page cannot reach outside the files,
+/// records and portals the app works with
+///
+/// # Arguments
+/// * `args` - JSON object of arguments, keyed in camelCase
+/// * `allow_list` - Read only when a URL argument is present
+pub fn validate_args(args: &Value, allow_list: impl Fn() -> UrlAllowList) -> Result<(), AppError> {
+    let Some(args) = args.as_object() else {
+        return Ok(());
+    };
+    let allowed = OnceCell::new();
+    let check_url =
+        |argument: &str, value: &str| validate_url(argument, value, allowed.get_or_init(&allow_list));
+
+    for (argument, value) in args {
+        match (argument.as_str(), value) {
+            ("batchId" | "batchA" | "batchB" | "invoiceId" | "fileToken", Value::String(id)) => validate_id(argument, id)?,
+            ("invoiceIds", Value::Array(ids)) => {
+                for id in ids {
+                    validate_id(argument, id.as_str().unwrap_or_default())?;
+                }
+            }
+            ("destination", Value::String(path)) => validate_path(argument, path)?,
+            ("fileHash", Value::String(hash)) => validate_hash(argument, hash)?,
+            ("url", Value::String(url)) => check_url(argument, url)?,
+            ("request", Value::Object(request)) => {
+                if let Some(Value::String(id)) = request.get("batch_id") {
+                    validate_id("request.batch_id", id)?;
+                }
+                let invoices = request.get("invoices").and_then(Value::as_array);
+                for invoice in invoices.into_iter().flatten() {
+                    if let Some(Value::String(id)) = invoice.get("id") {
+                        validate_id("request.invoices.id", id)?;
+                    }
+                    if let Some(Value::String(url)) = invoice.get("portal_url") {
+                        check_url("request.invoices.portal_url", url)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn invalid(argument: &str, reason: impl Into<String>) -> AppError {
+    AppError::InvalidArgument {
+        argument: argument.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Batch and invoice ids are UUIDs, generated by the app or the frontend,
+/// and so are the tokens of picked files
+fn validate_id(argument: &str, id: &str) -> Result<(), AppError> {
+    uuid::Uuid::parse_str(id)
+        .map(|_| ())
+        .map_err(|_| invalid(argument, "not a UUID"))
+}
+
+/// Absolute path without `..`, as the file dialogs return them
+fn validate_path(argument: &str, path: &str) -> Result<(), AppError> {
+    if path.contains('\0') {
+        return Err(invalid(argument, "contains a NUL byte"));
+    }
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err(invalid(argument, "not an absolute path"));
+    }
+    if path.components().any(|component| component == Component::ParentDir) {
+        return Err(invalid(argument, "must not contain '..'"));
+    }
+    Ok(())
+}
+
+/// Hex SHA-256 of a source file
+fn validate_hash(argument: &str, hash: &str) -> Result<(), AppError> {
+    if hash.len() != 64 || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(invalid(argument, "not a SHA-256 hash"));
+    }
+    Ok(())
+}
+
+fn validate_url(argument: &str, value: &str, allow_list: &UrlAllowList) -> Result<(), AppError> {
+    let url = url::Url::parse(value).map_err(|_| invalid(argument, "not a URL"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid(argument, "only http and https URLs are allowed"));
+    }
+    if !allow_list.allows(&url) {
+        return Err(invalid(
+            argument,
+            format!(
+                "{} is not a known portal; add a portal profile for it first",
+                url.host_str().unwrap_or_default()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn allow_list() -> UrlAllowList {
+        UrlAllowList {
+            profiles: portal_profiles::merge(Vec::new()),
+            vnpt_url: "https://hoadon.example.vn/".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_args() {
+        let batch_id = uuid::Uuid::new_v4().to_string();
+        assert!(validate_args(&json!({ "batchId": batch_id }), allow_list).is_ok());
+        assert!(validate_args(&json!({ "batchId": "../../etc" }), allow_list).is_err());
+        assert!(validate_args(&json!({ "invoiceIds": [batch_id] }), allow_list).is_ok());
+        assert!(validate_args(&json!({ "invoiceIds": [batch_id, 7] }), allow_list).is_err());
+
+        assert!(validate_args(&json!({ "url": "https://abc-tt78.vnpt-invoice.com.vn/" }), allow_list).is_ok());
+        assert!(validate_args(&json!({ "url": "https://hoadon.example.vn/tra-cuu" }), allow_list).is_ok());
+        assert!(validate_args(&json!({ "url": "https://attacker.example.com/" }), allow_list).is_err());
+        assert!(validate_args(&json!({ "url": "file:///etc/passwd" }), allow_list).is_err());
+
+        let request = json!({
+            "request": {
+                "batch_id": batch_id,
+                "invoices": [{ "id": "1; DROP TABLE", "code": "C24TAA0000001_Ab" }],
+            }
+        });
+        assert!(validate_args(&request, allow_list).is_err());
+
+        // URLs are only checked when present, without reading the settings
+        let no_settings = || -> UrlAllowList { panic!("allow list read without a URL") };
+        assert!(validate_args(&json!({ "invoiceCount": 3 }), no_settings).is_ok());
+    }
+
+    #[test]
+    fn test_validate_path() {
+        let root = if cfg!(windows) { "C:\\Users\\ketoan" } else { "/home/ketoan" };
+        assert!(validate_path("destination", &format!("{}/hoa-don.xlsx", root)).is_ok());
+        assert!(validate_path("destination", "hoa-don.xlsx").is_err());
+        assert!(validate_path("destination", &format!("{}/../../etc/passwd", root)).is_err());
+    }
+}
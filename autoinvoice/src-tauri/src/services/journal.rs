@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::AppError;
+
+/// Directory under the app data dir holding the journals of running batches
+pub const JOURNAL_DIR: &str = "journal";
+
+/// Phase written when an invoice has its final status
+const DONE_PHASE: &str = "done";
+
+/// One line of the journal: the invoice being worked on and how far it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalLine {
+    at: String,
+    invoice_index: u32,
+    invoice_id: String,
+    invoice_code: String,
+    attempt: u32,
+    phase: String,
+}
+
+/// First line of the journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalHeader {
+    batch_id: String,
+    batch_name: Option<String>,
+    started_at: String,
+    total: u32,
+}
+
+/// A batch whose journal survived the app: it was running when the app
+/// was killed or crashed
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct UnfinishedBatch {
+    pub batch_id: String,
+    pub batch_name: Option<String>,
+    pub started_at: String,
+    pub total: u32,
+    /// Invoices that reached their final status before the shutdown
+    pub done_count: u32,
+    /// Invoice in progress at the shutdown, when one was
+    pub invoice_index: Option<u32>,
+    pub invoice_code: Option<String>,
+    pub attempt: Option<u32>,
+    pub phase: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Append-only journal of a running batch, flushed to disk on every line so
+/// it stays ahead of the database; deleted when the batch ends cleanly
+#[derive(Debug)]
+pub struct BatchJournal {
+    path: PathBuf,
+    request_path: PathBuf,
+    file: Mutex<File>,
+    /// Invoice currently downloading: (index, id, code)
+    current: Mutex<Option<(u32, String, String)>>,
+}
+
+impl BatchJournal {
+    /// Start the journal of a batch; `request` is kept to resume it later
+    pub fn create(
+        app_data_dir: &Path,
+        batch_id: &str,
+        batch_name: Option<&str>,
+        total: u32,
+        request: &str,
+    ) -> Result<Self, AppError> {
+        let dir = app_data_dir.join(JOURNAL_DIR);
+        fs::create_dir_all(&dir)?;
+        let (path, request_path) = journal_paths(&dir, batch_id);
+
+        write_synced(&request_path, request.as_bytes())?;
+        let header = JournalHeader {
+            batch_id: batch_id.to_string(),
+            batch_name: batch_name.map(str::to_string),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            total,
+        };
+        write_synced(&path, format!("{}\n", to_json(&header)?).as_bytes())?;
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Self {
+            path,
+            request_path,
+            file: Mutex::new(file),
+            current: Mutex::new(None),
+        })
+    }
+
+    /// Record that the `index`-th invoice of the batch is starting
+    pub fn start_invoice(&self, index: u32, invoice_id: &str, invoice_code: &str) -> Result<(), AppError> {
+        if let Ok(mut current) = self.current.lock() {
+            *current = Some((index, invoice_id.to_string(), invoice_code.to_string()));
+        }
+        self.phase(0, "start")
+    }
+
+    /// Record the step the current invoice reached
+    pub fn phase(&self, attempt: u32, phase: &str) -> Result<(), AppError> {
+        let Some((index, invoice_id, invoice_code)) =
+            self.current.lock().ok().and_then(|current| current.clone())
+        else {
+            return Ok(());
+        };
+        let line = JournalLine {
+            at: chrono::Utc::now().to_rfc3339(),
+            invoice_index: index,
+            invoice_id,
+            invoice_code,
+            attempt,
+            phase: phase.to_string(),
+        };
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| AppError::IoError("Journal lock poisoned".to_string()))?;
+        writeln!(file, "{}", to_json(&line)?)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Record that the current invoice has its final status
+    pub fn finish_invoice(&self) -> Result<(), AppError> {
+        self.phase(0, DONE_PHASE)?;
+        if let Ok(mut current) = self.current.lock() {
+            *current = None;
+        }
+        Ok(())
+    }
+
+    /// The batch ended, whatever its outcome: nothing is left to resume
+    pub fn close(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(&self.request_path);
+    }
+}
+
+/// Batches left with a journal by an unclean shutdown
+pub fn unfinished(app_data_dir: &Path) -> Vec<UnfinishedBatch> {
+    let Ok(entries) = fs::read_dir(app_data_dir.join(JOURNAL_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut batches: Vec<UnfinishedBatch> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .filter_map(|path| read_journal(&path).map(|(batch, _)| batch))
+        .collect();
+    batches.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+    batches
+}
+
+/// Request of an unfinished batch and the ids of its invoices that were done;
+/// the journal stays until `discard`
+pub fn read_request(app_data_dir: &Path, batch_id: &str) -> Result<(String, HashSet<String>), AppError> {
+    let (path, request_path) = journal_paths(&app_data_dir.join(JOURNAL_DIR), batch_id);
+    let (_, done) = read_journal(&path)
+        .ok_or_else(|| AppError::ConfigError(format!("No journal for batch {}", batch_id)))?;
+    let request = fs::read_to_string(&request_path)
+        .map_err(|e| AppError::IoError(format!("Failed to read journaled request: {}", e)))?;
+
+    Ok((request, done))
+}
+
+/// Forget an unfinished batch
+pub fn discard(app_data_dir: &Path, batch_id: &str) {
+    let (path, request_path) = journal_paths(&app_data_dir.join(JOURNAL_DIR), batch_id);
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(request_path);
+}
+
+fn journal_paths(dir: &Path, batch_id: &str) -> (PathBuf, PathBuf) {
+    (
+        dir.join(format!("{}.log", batch_id)),
+        dir.join(format!("{}.request.json", batch_id)),
+    )
+}
+
+/// Replay a journal; a line torn by the crash is skipped
+fn read_journal(path: &Path) -> Option<(UnfinishedBatch, HashSet<String>)> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut lines = content.lines();
+    let header: JournalHeader = serde_json::from_str(lines.next()?).ok()?;
+
+    let mut done = HashSet::new();
+    let mut last: Option<JournalLine> = None;
+    for line in lines.filter_map(|line| serde_json::from_str::<JournalLine>(line).ok()) {
+        if line.phase == DONE_PHASE {
+            done.insert(line.invoice_id.clone());
+            last = None;
+        } else {
+            last = Some(line);
+        }
+    }
+
+    let batch = UnfinishedBatch {
+        batch_id: header.batch_id,
+        batch_name: header.batch_name,
+        started_at: header.started_at,
+        total: header.total,
+        done_count: done.len() as u32,
+        invoice_index: last.as_ref().map(|line| line.invoice_index),
+        invoice_code: last.as_ref().map(|line| line.invoice_code.clone()),
+        attempt: last.as_ref().map(|line| line.attempt),
+        phase: last.as_ref().map(|line| line.phase.clone()),
+        updated_at: last.map(|line| line.at),
+    };
+    Some((batch, done))
+}
+
+/// Write a file through a temporary one, so a crash never leaves it half written
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<(), AppError> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, AppError> {
+    serde_json::to_string(value)
+        .map_err(|e| AppError::IoError(format!("Failed to serialize journal: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_unfinished_journal() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let journal = BatchJournal::create(dir, "b1", Some("T5"), 3, "{}").unwrap();
+        journal.start_invoice(0, "i1", "C1").unwrap();
+        journal.finish_invoice().unwrap();
+        journal.start_invoice(1, "i2", "C2").unwrap();
+        journal.phase(2, "submit").unwrap();
+        // Simulate a crash mid-write
+        drop(journal);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_DIR).join("b1.log"))
+            .unwrap()
+            .write_all(b"{\"at\":\"2024")
+            .unwrap();
+
+        let batches = unfinished(dir);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].done_count, 1);
+        assert_eq!(batches[0].invoice_code.as_deref(), Some("C2"));
+        assert_eq!(batches[0].attempt, Some(2));
+        assert_eq!(batches[0].phase.as_deref(), Some("submit"));
+
+        let (request, done) = read_request(dir, "b1").unwrap();
+        assert_eq!(request, "{}");
+        assert!(done.contains("i1"));
+        assert_eq!(unfinished(dir).len(), 1);
+
+        discard(dir, "b1");
+        assert!(unfinished(dir).is_empty());
+    }
+}
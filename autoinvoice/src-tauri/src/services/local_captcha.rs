@@ -0,0 +1,268 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "local-captcha")]
+use tract_onnx::prelude::*;
+
+use crate::error::AppError;
+
+/// Folder, in the app data directory, of a model trained by the user; it
+/// wins over the bundled one
+pub const USER_MODEL_DIR: &str = "captcha-model";
+
+/// Folder of the bundled model, in the app resources
+pub const BUNDLED_MODEL_DIR: &str = "models/captcha";
+
+const MODEL_FILE: &str = "model.onnx";
+const META_FILE: &str = "model.json";
+
+/// Default lowest confidence at which a local answer is used instead of
+/// asking the AI provider
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.9;
+
+/// `model.json` next to `model.onnx`: the input size the model was trained
+/// on and the characters of its output classes
+#[derive(Debug, Clone, Deserialize)]
+struct ModelMeta {
+    height: usize,
+    width: usize,
+    /// Class `i + 1` is `alphabet[i]`; class 0 is the CTC blank
+    alphabet: String,
+}
+
+/// A captcha read by the local model
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalAnswer {
+    pub text: String,
+    /// Lowest probability among the characters read, 0 to 1
+    pub confidence: f64,
+}
+
+#[cfg(feature = "local-captcha")]
+type Plan = TypedRunnableModel<TypedModel>;
+
+/// Built without the `local-captcha` feature no model ever loads
+#[cfg(not(feature = "local-captcha"))]
+type Plan = std::convert::Infallible;
+
+/// CRNN captcha reader run with tract, entirely offline. The model takes a
+/// `1×1×height×width` grayscale image scaled to 0..1 and returns
+/// `1×steps×classes` scores, decoded greedily as CTC
+pub struct LocalCaptchaModel {
+    plan: Plan,
+    meta: ModelMeta,
+    alphabet: Vec<char>,
+    path: PathBuf,
+}
+
+impl LocalCaptchaModel {
+    /// The user's model when there is one, otherwise the bundled one; none
+    /// when this build cannot run a model
+    pub fn find(app_data_dir: &Path, resource_dir: Option<&Path>) -> Option<PathBuf> {
+        if !cfg!(feature = "local-captcha") {
+            return None;
+        }
+        std::iter::once(app_data_dir.join(USER_MODEL_DIR))
+            .chain(resource_dir.map(|dir| dir.join(BUNDLED_MODEL_DIR)))
+            .find(|dir| dir.join(MODEL_FILE).is_file() && dir.join(META_FILE).is_file())
+    }
+
+    /// Load `model.onnx` and `model.json` from `dir`
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        let meta: ModelMeta = serde_json::from_slice(&std::fs::read(dir.join(META_FILE))?)
+            .map_err(|e| AppError::ConfigError(format!("Invalid captcha model description: {}", e)))?;
+        let alphabet: Vec<char> = meta.alphabet.chars().collect();
+        if meta.height == 0 || meta.width == 0 || alphabet.is_empty() {
+            return Err(AppError::ConfigError(
+                "The captcha model description needs a size and an alphabet".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            plan: compile(&dir.join(MODEL_FILE), &meta)?,
+            meta,
+            alphabet,
+            path: dir.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the captcha in a PNG image
+    pub fn solve(&self, png_bytes: &[u8]) -> Result<LocalAnswer, AppError> {
+        let pixels = grayscale(png_bytes, self.meta.width, self.meta.height)?;
+        Ok(ctc_decode(&self.scores(pixels)?, &self.alphabet))
+    }
+
+    /// Scores of every class at every step, for the model's input image
+    #[cfg(feature = "local-captcha")]
+    fn scores(&self, pixels: Vec<f32>) -> Result<Vec<Vec<f32>>, AppError> {
+        let input = tract_ndarray::Array4::from_shape_vec((1, 1, self.meta.height, self.meta.width), pixels)
+            .map_err(|e| AppError::IoError(format!("Bad captcha model input: {}", e)))?;
+
+        let outputs = self
+            .plan
+            .run(tvec!(Tensor::from(input).into()))
+            .map_err(|e| AppError::IoError(format!("Captcha model failed: {}", e)))?;
+        let scores = outputs[0]
+            .to_array_view::<f32>()
+            .map_err(|e| AppError::IoError(format!("Unexpected captcha model output: {}", e)))?;
+        let shape = scores.shape();
+        if shape.len() != 3 || shape[0] != 1 || shape[2] != self.alphabet.len() + 1 {
+            return Err(AppError::IoError(format!(
+                "Captcha model output is {:?}, expected [1, steps, {}]",
+                shape,
+                self.alphabet.len() + 1
+            )));
+        }
+
+        Ok(scores
+            .outer_iter()
+            .next()
+            .map(|steps| steps.outer_iter().map(|step| step.iter().copied().collect()).collect())
+            .unwrap_or_default())
+    }
+
+    #[cfg(not(feature = "local-captcha"))]
+    fn scores(&self, _pixels: Vec<f32>) -> Result<Vec<Vec<f32>>, AppError> {
+        match self.plan {}
+    }
+}
+
+#[cfg(feature = "local-captcha")]
+fn compile(model_path: &Path, meta: &ModelMeta) -> Result<Plan, AppError> {
+    tract_onnx::onnx()
+        .model_for_path(model_path)
+        .and_then(|model| model.with_input_fact(0, f32::fact([1, 1, meta.height, meta.width]).into()))
+        .and_then(|model| model.into_optimized())
+        .and_then(|model| model.into_runnable())
+        .map_err(|e| AppError::ConfigError(format!("Cannot load captcha model: {}", e)))
+}
+
+#[cfg(not(feature = "local-captcha"))]
+fn compile(_model_path: &Path, _meta: &ModelMeta) -> Result<Plan, AppError> {
+    Err(AppError::ConfigError(
+        "this build has no offline captcha solver (the local-captcha feature is off)".to_string(),
+    ))
+}
+
+/// Greedy CTC: the best class of every step, repeats merged and blanks
+/// dropped
+fn ctc_decode(steps: &[Vec<f32>], alphabet: &[char]) -> LocalAnswer {
+    let mut text = String::new();
+    let mut confidence = 1.0f64;
+    let mut previous = 0;
+    for scores in steps {
+        let probabilities = softmax(scores);
+        let (class, probability) = probabilities
+            .iter()
+            .copied()
+            .enumerate()
+            .fold((0, f64::MIN), |best, (class, p)| if p > best.1 { (class, p) } else { best });
+        if class != 0 && class != previous {
+            if let Some(c) = alphabet.get(class - 1) {
+                text.push(*c);
+                confidence = confidence.min(probability);
+            }
+        }
+        previous = class;
+    }
+    if text.is_empty() {
+        confidence = 0.0;
+    }
+    LocalAnswer { text, confidence }
+}
+
+fn softmax(scores: &[f32]) -> Vec<f64> {
+    let max = scores.iter().copied().fold(f32::MIN, f32::max) as f64;
+    let exps: Vec<f64> = scores.iter().map(|&s| (s as f64 - max).exp()).collect();
+    let sum: f64 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+/// Decode a PNG into `width×height` gray levels from 0 to 1, scaled bilinearly
+fn grayscale(png_bytes: &[u8], width: usize, height: usize) -> Result<Vec<f32>, AppError> {
+    let mut decoder = png::Decoder::new(png_bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| AppError::IoError(format!("Cannot read captcha image: {}", e)))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| AppError::IoError(format!("Cannot read captcha image: {}", e)))?;
+
+    let channels = frame.color_type.samples();
+    let (src_width, src_height) = (frame.width as usize, frame.height as usize);
+    if src_width == 0 || src_height == 0 {
+        return Err(AppError::IoError("The captcha image is empty".to_string()));
+    }
+    let gray: Vec<f32> = buffer[..frame.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| match pixel {
+            [g] | [g, _] => *g as f32 / 255.0,
+            [r, g, b, ..] => (0.299 * *r as f32 + 0.587 * *g as f32 + 0.114 * *b as f32) / 255.0,
+            [] => 0.0,
+        })
+        .collect();
+
+    let at = |x: usize, y: usize| gray[y.min(src_height - 1) * src_width + x.min(src_width - 1)];
+    let mut scaled = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let sy = ((y as f32 + 0.5) * src_height as f32 / height as f32 - 0.5).max(0.0);
+        let (y0, fy) = (sy.floor() as usize, sy.fract());
+        for x in 0..width {
+            let sx = ((x as f32 + 0.5) * src_width as f32 / width as f32 - 0.5).max(0.0);
+            let (x0, fx) = (sx.floor() as usize, sx.fract());
+            let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1, y0) * fx;
+            let bottom = at(x0, y0 + 1) * (1.0 - fx) + at(x0 + 1, y0 + 1) * fx;
+            scaled.push(top * (1.0 - fy) + bottom * fy);
+        }
+    }
+    Ok(scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_and_preprocess() {
+        let alphabet: Vec<char> = "abc".chars().collect();
+        let step = |class: usize, score: f32| {
+            let mut scores = vec![0.0; 4];
+            scores[class] = score;
+            scores
+        };
+        // "a a - b b - b" reads "abb"
+        let steps = vec![step(1, 9.0), step(1, 9.0), step(0, 9.0), step(2, 9.0), step(2, 2.0), step(0, 9.0), step(2, 9.0)];
+        let answer = ctc_decode(&steps, &alphabet);
+        assert_eq!(answer.text, "abb");
+        // The hesitant repeat of "b" does not count, only where a character starts
+        assert!(answer.confidence > 0.99);
+        let unsure = ctc_decode(&[step(3, 0.5)], &alphabet);
+        assert_eq!(unsure.text, "c");
+        assert!(unsure.confidence < 0.5);
+        assert_eq!(ctc_decode(&[step(0, 9.0)], &alphabet).confidence, 0.0);
+
+        // A 2×1 RGB image, black then white, scaled to 4×2
+        let mut png_bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_bytes, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0, 0, 0, 255, 255, 255]).unwrap();
+        }
+        let pixels = grayscale(&png_bytes, 4, 2).unwrap();
+        assert_eq!(pixels.len(), 8);
+        assert_eq!(pixels[0], 0.0);
+        assert!((pixels[3] - 1.0).abs() < 1e-6);
+        assert!(pixels[1] > 0.0 && pixels[1] < pixels[2]);
+        assert_eq!(pixels[..4], pixels[4..]);
+
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(LocalCaptchaModel::find(tmp.path(), None).is_none());
+    }
+}
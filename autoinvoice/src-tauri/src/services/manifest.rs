@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+use crate::services::storage::entry_name;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// Contents of a delivered batch, so the receiving side can check nothing is
+/// missing or corrupted
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchManifest {
+    pub batch_id: String,
+    pub created_at: String,
+    pub app_version: String,
+    pub file_count: usize,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    /// Path in the batch, as in its zip and SFTP folder, see `entry_name`
+    pub file_name: String,
+    pub invoice_code: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl BatchManifest {
+    /// Hash every `(invoice code, file path)` of the batch downloaded to
+    /// `batch_dir`
+    pub fn build(
+        batch_id: &str,
+        batch_dir: &Path,
+        files: &[(&str, &str)],
+    ) -> Result<Self, AppError> {
+        let entries = files
+            .iter()
+            .map(|(code, path)| {
+                let (sha256, size) = hash_file(Path::new(path))?;
+                Ok(ManifestEntry {
+                    file_name: entry_name(batch_dir, path),
+                    invoice_code: code.to_string(),
+                    size,
+                    sha256,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        Ok(Self {
+            batch_id: batch_id.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            file_count: entries.len(),
+            files: entries,
+        })
+    }
+
+    /// `sha256sum` compatible listing, checkable with `sha256sum -c SHA256SUMS`
+    pub fn checksums(&self) -> String {
+        self.files
+            .iter()
+            .map(|entry| format!("{}  {}\n", entry.sha256, entry.file_name))
+            .collect()
+    }
+
+    /// Write `manifest.json` and `SHA256SUMS` into `dir`
+    ///
+    /// # Returns
+    /// Paths of the two files, to be delivered alongside the PDFs
+    pub fn write_to(&self, dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+        std::fs::create_dir_all(dir)?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AppError::IoError(format!("Failed to serialize manifest: {}", e)))?;
+        std::fs::write(&manifest_path, json)?;
+
+        let checksums_path = dir.join(CHECKSUMS_FILE);
+        std::fs::write(&checksums_path, self.checksums())?;
+
+        Ok(vec![manifest_path, checksums_path])
+    }
+}
+
+/// SHA-256 (hex) and size of a file
+pub fn hash_file(path: &Path) -> Result<(String, u64), AppError> {
+    let mut file = File::open(path)
+        .map_err(|e| AppError::IoError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_checksums() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let pdf = dir.join("AB-123.pdf");
+        std::fs::write(&pdf, b"abc").unwrap();
+        let pdf = pdf.to_string_lossy().to_string();
+
+        let manifest = BatchManifest::build("batch-1", dir, &[("AB-123", pdf.as_str())]).unwrap();
+        assert_eq!(manifest.file_count, 1);
+        assert_eq!(manifest.files[0].size, 3);
+        assert_eq!(
+            manifest.checksums(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  AB-123.pdf\n"
+        );
+
+        let written = manifest.write_to(&dir.join("out")).unwrap();
+        assert!(written.iter().all(|path| path.is_file()));
+    }
+}
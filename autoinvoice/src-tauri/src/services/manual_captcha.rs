@@ -0,0 +1,170 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use specta::Type;
+use tokio::sync::oneshot;
+
+use crate::error::AppError;
+
+/// Default wait for a manual captcha answer before the invoice is skipped
+pub const DEFAULT_MANUAL_CAPTCHA_TIMEOUT_SECS: u32 = 120;
+
+/// How often a waiting download checks for an answer and for cancellation
+const ANSWER_POLL: Duration = Duration::from_millis(250);
+
+/// An invoice whose captcha the AI could not solve, waiting for the user
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct PendingCaptcha {
+    pub batch_id: String,
+    pub invoice_id: String,
+    pub invoice_code: String,
+    pub image_base64: String,
+    pub requested_at: String,
+}
+
+/// What the user decided for a waiting invoice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManualAnswer {
+    Captcha(String),
+    /// Give up on this invoice
+    Skip,
+    /// Leave it for a later interactive pass
+    Defer,
+}
+
+struct Waiting {
+    captcha: PendingCaptcha,
+    answer: oneshot::Sender<ManualAnswer>,
+}
+
+/// Invoices waiting for a manual captcha, keyed by invoice id; downloads
+/// block on their receiver until `submit_manual_captcha` answers
+#[derive(Default)]
+pub struct ManualCaptchas {
+    waiting: Mutex<HashMap<String, Waiting>>,
+}
+
+impl ManualCaptchas {
+    /// Register an invoice as waiting; a newer request for the same invoice
+    /// replaces the older one
+    pub fn register(&self, captcha: PendingCaptcha) -> oneshot::Receiver<ManualAnswer> {
+        let (answer, rx) = oneshot::channel();
+        let invoice_id = captcha.invoice_id.clone();
+
+        self.waiting
+            .lock()
+            .unwrap()
+            .insert(invoice_id, Waiting { captcha, answer });
+        rx
+    }
+
+    /// Every invoice still waiting, oldest request first
+    pub fn pending(&self) -> Vec<PendingCaptcha> {
+        let mut pending: Vec<PendingCaptcha> = self
+            .waiting
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.captcha.clone())
+            .collect();
+        pending.sort_by(|a, b| a.requested_at.cmp(&b.requested_at));
+        pending
+    }
+
+    /// Ids of the invoices of `batch_id` still waiting
+    pub fn waiting_in_batch(&self, batch_id: &str) -> Vec<String> {
+        self.waiting
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.captcha.batch_id == batch_id)
+            .map(|entry| entry.captcha.invoice_id.clone())
+            .collect()
+    }
+
+    /// Hand the user's answer to the download waiting on `invoice_id`
+    pub fn answer(&self, batch_id: &str, invoice_id: &str, answer: ManualAnswer) -> Result<(), AppError> {
+        let mut waiting = self.waiting.lock().unwrap();
+
+        let entry = match waiting.entry(invoice_id.to_string()) {
+            Entry::Occupied(entry) if entry.get().captcha.batch_id == batch_id => entry.remove(),
+            _ => {
+                return Err(AppError::ConfigError(format!(
+                    "Invoice {} is not waiting for a manual captcha",
+                    invoice_id
+                )))
+            }
+        };
+
+        entry.answer.send(answer).map_err(|_| {
+            AppError::ConfigError(format!("Invoice {} stopped waiting for its captcha", invoice_id))
+        })
+    }
+
+    /// Block until the answer arrives, `timeout` passes or the batch is cancelled
+    pub fn wait_blocking(
+        &self,
+        invoice_id: &str,
+        mut rx: oneshot::Receiver<ManualAnswer>,
+        timeout: Duration,
+        cancelled: &AtomicBool,
+    ) -> Option<ManualAnswer> {
+        let started = Instant::now();
+
+        let answer = loop {
+            match rx.try_recv() {
+                Ok(answer) => break Some(answer),
+                Err(oneshot::error::TryRecvError::Closed) => break None,
+                Err(oneshot::error::TryRecvError::Empty) => {
+                    if cancelled.load(Ordering::SeqCst) || started.elapsed() >= timeout {
+                        break None;
+                    }
+                    thread::sleep(ANSWER_POLL);
+                }
+            }
+        };
+
+        self.waiting.lock().unwrap().remove(invoice_id);
+        answer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(invoice_id: &str) -> PendingCaptcha {
+        PendingCaptcha {
+            batch_id: "batch".to_string(),
+            invoice_id: invoice_id.to_string(),
+            invoice_code: "C25TLK0019654".to_string(),
+            image_base64: String::new(),
+            requested_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn test_manual_captcha_answer_and_timeout() {
+        let captchas = ManualCaptchas::default();
+        let cancelled = AtomicBool::new(false);
+
+        let rx = captchas.register(pending("a"));
+        assert_eq!(captchas.pending().len(), 1);
+        assert_eq!(captchas.waiting_in_batch("batch"), vec!["a".to_string()]);
+        let answer = ManualAnswer::Captcha("k3x9".to_string());
+        assert!(captchas.answer("other-batch", "a", answer.clone()).is_err());
+        captchas.answer("batch", "a", answer.clone()).unwrap();
+        let received = captchas.wait_blocking("a", rx, Duration::from_secs(1), &cancelled);
+        assert_eq!(received, Some(answer));
+        assert!(captchas.pending().is_empty());
+
+        let rx = captchas.register(pending("b"));
+        assert_eq!(captchas.wait_blocking("b", rx, Duration::ZERO, &cancelled), None);
+        assert!(captchas.answer("batch", "b", ManualAnswer::Skip).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use specta::Type;
+
+use crate::status::TimingStage;
+
+/// Timing summary of one download stage
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct StageMetrics {
+    pub stage: TimingStage,
+    pub samples: u32,
+    pub avg_ms: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Summarize raw stage timings, one entry per stage that has samples,
+/// in download order
+pub fn summarize(timings: &[(TimingStage, u64)]) -> Vec<StageMetrics> {
+    TimingStage::ALL
+        .iter()
+        .filter_map(|stage| {
+            let mut durations: Vec<u64> = timings
+                .iter()
+                .filter(|(s, _)| s == stage)
+                .map(|(_, duration_ms)| *duration_ms)
+                .collect();
+            if durations.is_empty() {
+                return None;
+            }
+            durations.sort_unstable();
+
+            Some(StageMetrics {
+                stage: *stage,
+                samples: durations.len() as u32,
+                avg_ms: durations.iter().sum::<u64>() / durations.len() as u64,
+                p50_ms: percentile(&durations, 50),
+                p95_ms: percentile(&durations, 95),
+                max_ms: durations[durations.len() - 1],
+            })
+        })
+        .collect()
+}
+
+/// Nearest-rank percentile of sorted, non-empty durations
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_stage_timings() {
+        let mut timings: Vec<(TimingStage, u64)> =
+            (1..=100).map(|ms| (TimingStage::CaptchaSolve, ms)).collect();
+        timings.push((TimingStage::Navigation, 400));
+
+        let metrics = summarize(&timings);
+        assert_eq!(metrics.len(), 2);
+
+        assert_eq!(metrics[0].stage, TimingStage::Navigation);
+        assert_eq!(metrics[0].p95_ms, 400);
+
+        let captcha = &metrics[1];
+        assert_eq!(captcha.samples, 100);
+        assert_eq!(captcha.avg_ms, 50);
+        assert_eq!(captcha.p50_ms, 50);
+        assert_eq!(captcha.p95_ms, 95);
+        assert_eq!(captcha.max_ms, 100);
+    }
+}
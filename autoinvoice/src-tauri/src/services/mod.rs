@@ -1,5 +1,64 @@
 pub mod excel_parser;
 pub mod captcha;
+pub mod captcha_dataset;
+pub mod local_captcha;
 pub mod browser;
+pub mod browser_pool;
+pub mod browser_profile;
+pub mod chrome_version;
 pub mod downloader;
 pub mod database;
+pub mod diagnostics;
+pub mod selector_config;
+pub mod selector_repair;
+pub mod schedule;
+pub mod estimator;
+pub mod tax_portal;
+pub mod batch_split;
+pub mod pdf_fetch;
+pub mod http;
+pub mod connectivity;
+pub mod compatibility;
+pub mod batch_report;
+pub mod app_lock;
+pub mod metrics;
+pub mod event_throttle;
+pub mod status_writer;
+pub mod download_config;
+pub mod manual_captcha;
+pub mod browser_preview;
+pub mod hooks;
+pub mod storage;
+pub mod cloud_drive;
+pub mod sftp;
+pub mod manifest;
+pub mod invoice_xml;
+pub mod xml_signature;
+pub mod pdf_metadata;
+pub mod ocr;
+pub mod vat_summary;
+pub mod anomalies;
+pub mod journal;
+pub mod portal_profiles;
+pub mod portal_account;
+pub mod inbox;
+pub mod ipc_validation;
+pub mod retry_later;
+pub mod path_policy;
+pub mod settings_service;
+pub mod browser_driver;
+pub mod fixtures;
+pub mod demo;
+pub mod batch_compare;
+pub mod invoice_export;
+pub mod dedupe;
+pub mod file_naming;
+pub mod disk_usage;
+pub mod pdf_optimize;
+pub mod pdf_stamp;
+pub mod zip_package;
+pub mod config_bundle;
+pub mod onboarding;
+pub mod ai_budget;
+pub mod tracing_log;
+pub mod panic_report;
@@ -0,0 +1,81 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::AppError;
+use crate::services::pdf_metadata;
+
+/// Vietnamese for the invoice, English for the bilingual labels
+const OCR_LANGUAGES: &str = "vie+eng";
+
+/// Longest stderr excerpt kept in the error of a failed run
+const MAX_STDERR_CHARS: usize = 500;
+
+/// Program run for OCR: the configured path, or `tesseract` from PATH
+pub fn tesseract_command(path: &str) -> &str {
+    if path.trim().is_empty() {
+        "tesseract"
+    } else {
+        path.trim()
+    }
+}
+
+/// Check Tesseract starts and has the Vietnamese language data
+pub fn check_tesseract(path: &str) -> Result<(), AppError> {
+    let command = tesseract_command(path);
+    let output = Command::new(command)
+        .arg("--list-langs")
+        .output()
+        .map_err(|e| AppError::ConfigError(format!("Cannot run Tesseract ({}): {}", command, e)))?;
+
+    // Older versions print the list on stderr
+    let languages = String::from_utf8_lossy(&output.stdout) + String::from_utf8_lossy(&output.stderr);
+    if !languages.lines().any(|line| line.trim() == "vie") {
+        return Err(AppError::ConfigError(
+            "Tesseract has no Vietnamese language data (vie.traineddata)".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// OCR every scanned page of the PDF at `pdf_path`, blocking until done
+pub fn ocr_pdf(tesseract: &str, pdf_path: &Path) -> Result<String, AppError> {
+    let dir = std::env::temp_dir().join(format!("autoinvoice-ocr-{}", uuid::Uuid::new_v4()));
+    let text = pdf_metadata::extract_page_images(pdf_path, &dir).and_then(|images| {
+        if images.is_empty() {
+            return Err(AppError::IoError(format!(
+                "{} has no text and no page images Tesseract can read",
+                pdf_path.display()
+            )));
+        }
+        images
+            .iter()
+            .map(|image| ocr_image(tesseract, image))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|pages| pages.join("\n"))
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    text
+}
+
+fn ocr_image(tesseract: &str, image: &Path) -> Result<String, AppError> {
+    let command = tesseract_command(tesseract);
+    let output = Command::new(command)
+        .arg(image)
+        .arg("stdout")
+        .args(["-l", OCR_LANGUAGES])
+        .output()
+        .map_err(|e| AppError::ConfigError(format!("Cannot run Tesseract ({}): {}", command, e)))?;
+
+    if !output.status.success() {
+        let stderr: String = String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .chars()
+            .take(MAX_STDERR_CHARS)
+            .collect();
+        return Err(AppError::IoError(format!(
+            "Tesseract exited with {}: {}",
+            output.status, stderr
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::services::browser;
+use crate::services::captcha::{ApiKeyCheck, ApiKeyStatus};
+use crate::services::download_config::ensure_writable_dir;
+
+/// A step of the first-run wizard, each backed by a real check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnboardingStep {
+    /// Chrome or Chromium is installed where the app looks for it
+    Browser,
+    /// The saved OpenAI key answers a completion
+    ApiKey,
+    /// The download folder exists, or can be created, and is writable
+    DownloadFolder,
+}
+
+impl OnboardingStep {
+    /// In the order the wizard shows them
+    pub const ALL: &'static [OnboardingStep] = &[
+        OnboardingStep::Browser,
+        OnboardingStep::ApiKey,
+        OnboardingStep::DownloadFolder,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnboardingStep::Browser => "browser",
+            OnboardingStep::ApiKey => "api-key",
+            OnboardingStep::DownloadFolder => "download-folder",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|step| step.as_str() == value)
+    }
+}
+
+/// Outcome of the check behind a step
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct OnboardingCheck {
+    pub step: OnboardingStep,
+    pub ok: bool,
+    /// What was found, e.g. the Chrome path, or why the check failed
+    pub message: Option<String>,
+}
+
+/// Steps of the wizard and which ones the user got through
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct OnboardingState {
+    pub completed: Vec<OnboardingStep>,
+    /// The first step not completed yet, `None` once the wizard is done
+    pub next: Option<OnboardingStep>,
+}
+
+impl OnboardingState {
+    pub fn new(completed: Vec<OnboardingStep>) -> Self {
+        let next = OnboardingStep::ALL
+            .iter()
+            .copied()
+            .find(|step| !completed.contains(step));
+        Self { completed, next }
+    }
+}
+
+pub fn check_browser() -> OnboardingCheck {
+    let (ok, message) = match browser::find_chrome() {
+        Ok(path) => (true, path.display().to_string()),
+        Err(e) => (false, e.to_string()),
+    };
+    OnboardingCheck {
+        step: OnboardingStep::Browser,
+        ok,
+        message: Some(message),
+    }
+}
+
+pub fn check_api_key(check: &ApiKeyCheck) -> OnboardingCheck {
+    OnboardingCheck {
+        step: OnboardingStep::ApiKey,
+        ok: check.status == ApiKeyStatus::Ok,
+        message: check.message.clone(),
+    }
+}
+
+pub fn check_download_folder(directory: &str) -> OnboardingCheck {
+    let (ok, message) = match ensure_writable_dir(directory) {
+        Ok(path) => (true, path.display().to_string()),
+        Err(e) => (false, e.to_string()),
+    };
+    OnboardingCheck {
+        step: OnboardingStep::DownloadFolder,
+        ok,
+        message: Some(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_and_folder_check() {
+        for step in OnboardingStep::ALL {
+            assert_eq!(OnboardingStep::parse(step.as_str()), Some(*step));
+        }
+        assert_eq!(OnboardingStep::parse("welcome"), None);
+
+        let state = OnboardingState::new(vec![OnboardingStep::Browser]);
+        assert_eq!(state.next, Some(OnboardingStep::ApiKey));
+        assert_eq!(OnboardingState::new(OnboardingStep::ALL.to_vec()).next, None);
+
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(check_download_folder(&tmp.path().to_string_lossy()).ok);
+        assert!(!check_download_folder(" ").ok);
+    }
+}
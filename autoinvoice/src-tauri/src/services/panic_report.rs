@@ -0,0 +1,148 @@
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use crate::error::AppError;
+
+/// Folder, in the app data directory, of the reports of caught panics
+pub const PANIC_DIR: &str = "panics";
+
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    /// Panic the hook saw last on this thread, picked up by `catch`
+    static LAST_PANIC: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+/// What a panic left behind: where it happened and how it got there
+#[derive(Debug, Clone)]
+pub struct PanicReport {
+    pub message: String,
+    /// `file:line:column`, when the hook was installed
+    pub location: Option<String>,
+    pub backtrace: Option<String>,
+}
+
+impl PanicReport {
+    fn from_hook(info: &PanicHookInfo<'_>) -> Self {
+        Self {
+            message: payload_message(info.payload()),
+            location: info.location().map(|location| location.to_string()),
+            backtrace: Some(Backtrace::force_capture().to_string()),
+        }
+    }
+
+    /// Error recorded on the invoice; the backtrace stays in the report file
+    pub fn to_error(&self, report_path: Option<&Path>) -> AppError {
+        let mut message = self.message.clone();
+        if let Some(location) = &self.location {
+            message.push_str(&format!(" at {}", location));
+        }
+        if let Some(path) = report_path {
+            message.push_str(&format!(" (details in {})", path.display()));
+        }
+        AppError::Panicked(message)
+    }
+}
+
+fn payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Keep the message, location and backtrace of every panic for `catch`,
+/// then report it as before
+pub fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let report = PanicReport::from_hook(info);
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(report));
+            previous(info);
+        }));
+    });
+}
+
+/// Run `f`, turning a panic into the report of what panicked instead of
+/// unwinding further
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, PanicReport> {
+    LAST_PANIC.with(|last| last.borrow_mut().take());
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_else(|| PanicReport {
+                message: payload_message(payload.as_ref()),
+                location: None,
+                backtrace: None,
+            })
+    })
+}
+
+/// Write a report to `<dir>/<batch>_<code>_<time>.txt` for a bug report
+pub fn write_report(
+    dir: &Path,
+    batch_id: &str,
+    invoice_code: &str,
+    report: &PanicReport,
+) -> Result<PathBuf, AppError> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| AppError::IoError(format!("Failed to create panic report directory: {}", e)))?;
+
+    let safe_code = invoice_code.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
+    let path = dir.join(format!(
+        "{}_{}_{}.txt",
+        batch_id,
+        safe_code,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    ));
+    let contents = format!(
+        "batch: {}\ninvoice: {}\ntime: {}\nversion: {}\nmessage: {}\nlocation: {}\n\n{}\n",
+        batch_id,
+        invoice_code,
+        chrono::Utc::now().to_rfc3339(),
+        env!("CARGO_PKG_VERSION"),
+        report.message,
+        report.location.as_deref().unwrap_or("unknown"),
+        report.backtrace.as_deref().unwrap_or("no backtrace captured"),
+    );
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catch_keeps_panic_details() {
+        install_hook();
+
+        let caught = catch(|| -> Result<(), AppError> {
+            let selectors: Vec<&str> = Vec::new();
+            panic!("no selector for {}", selectors.len())
+        });
+        let report = caught.unwrap_err();
+        assert_eq!(report.message, "no selector for 0");
+        assert!(report.location.as_deref().unwrap().contains("panic_report.rs"));
+        assert!(report.backtrace.is_some());
+
+        // Nothing left over for the next call
+        assert!(matches!(catch(|| 42), Ok(42)));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_report(tmp.path(), "batch-1", "AB/123", &report).unwrap();
+        assert!(path.file_name().unwrap().to_string_lossy().starts_with("batch-1_AB_123_"));
+        assert!(std::fs::read_to_string(&path).unwrap().contains("message: no selector for 0"));
+
+        let error = report.to_error(Some(&path)).to_string();
+        assert!(error.starts_with("Internal error: no selector for 0 at "), "{}", error);
+        assert!(error.contains("details in"));
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::AppError;
+
+/// Files the user picked in a dialog opened by the backend, the only ones a
+/// command may read on behalf of the webview; each is known by a token so
+/// the webview never hands the backend a path to read
+#[derive(Default)]
+pub struct PathPolicy {
+    granted: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl PathPolicy {
+    /// Allow reading a file the user picked, returning its token
+    pub fn grant(&self, path: PathBuf) -> Result<String, AppError> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| AppError::IoError(format!("Cannot open {}: {}", path.display(), e)))?;
+        let token = uuid::Uuid::new_v4().to_string();
+        self.granted
+            .lock()
+            .map_err(|_| AppError::IoError("Path policy poisoned".to_string()))?
+            .insert(token.clone(), path);
+        Ok(token)
+    }
+
+    /// Path of the file picked under `token`
+    pub fn resolve(&self, token: &str) -> Result<PathBuf, AppError> {
+        self.granted
+            .lock()
+            .map_err(|_| AppError::IoError("Path policy poisoned".to_string()))?
+            .get(token)
+            .cloned()
+            .ok_or_else(|| AppError::PathNotAllowed("file was not chosen in this session".to_string()))
+    }
+
+    /// Check a path the webview reports back, e.g. a batch's source file,
+    /// is one the user picked
+    pub fn check_granted(&self, path: &str) -> Result<(), AppError> {
+        let path = Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path));
+        let granted = self
+            .granted
+            .lock()
+            .map_err(|_| AppError::IoError("Path policy poisoned".to_string()))?
+            .values()
+            .any(|granted| *granted == path);
+        if !granted {
+            return Err(AppError::PathNotAllowed(format!(
+                "{} was not chosen in this session",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Resolve `path`, which may not exist yet, and check it stays inside
+/// `root` once symlinks and `..` are followed
+///
+/// # Returns
+/// The resolved path, to write to instead of `path`
+pub fn ensure_within(root: &Path, path: &Path) -> Result<PathBuf, AppError> {
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| AppError::IoError(format!("Cannot open {}: {}", root.display(), e)))?;
+
+    // Resolve the deepest part that exists; the missing rest cannot be a
+    // symlink, only a `..`, which `file_name` refuses
+    let mut existing = path;
+    let mut missing = Vec::new();
+    let mut resolved = loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            break canonical;
+        }
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return Err(AppError::PathNotAllowed(format!("{} cannot be resolved", path.display()))),
+        }
+    };
+    resolved.extend(missing.into_iter().rev());
+
+    if !resolved.starts_with(&canonical_root) {
+        return Err(AppError::PathNotAllowed(format!(
+            "{} is outside {}",
+            resolved.display(),
+            root.display()
+        )));
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_policy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let downloads = root.join("downloads");
+        std::fs::create_dir_all(&downloads).unwrap();
+
+        assert!(ensure_within(&downloads, &downloads.join("C24TAA0000001_Ab.pdf")).is_ok());
+        assert!(ensure_within(&downloads, &downloads.join("..").join("escaped.pdf")).is_err());
+        assert!(ensure_within(&downloads, &root.join("escaped.pdf")).is_err());
+        assert!(ensure_within(&downloads, &downloads.join("2024/T5")).is_ok());
+        assert!(ensure_within(&downloads, &downloads.join("new/../../escaped")).is_err());
+
+        let sheet = root.join("hoa-don.xlsx");
+        std::fs::write(&sheet, b"").unwrap();
+        let policy = PathPolicy::default();
+        let token = policy.grant(sheet.clone()).unwrap();
+        assert_eq!(policy.resolve(&token).unwrap(), sheet.canonicalize().unwrap());
+        assert!(policy.check_granted(&sheet.to_string_lossy()).is_ok());
+        assert!(policy.resolve(&uuid::Uuid::new_v4().to_string()).is_err());
+        assert!(policy.check_granted("/etc/passwd").is_err());
+    }
+}
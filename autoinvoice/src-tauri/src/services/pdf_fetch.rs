@@ -0,0 +1,218 @@
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::AppError;
+use crate::status::impl_setting_parse;
+
+/// Attempts per PDF, counting resumed requests
+const MAX_FETCH_ATTEMPTS: u32 = 4;
+
+/// Invoice PDFs are a few hundred KB; anything this large is not an invoice
+pub const MAX_PDF_SIZE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Base pause before retrying, multiplied by the attempt number
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Which PDF of an invoice to keep, where the portal offers the printable
+/// "hóa đơn chuyển đổi" besides the original
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum PdfVariant {
+    /// The original e-invoice PDF
+    #[default]
+    Original,
+    /// The converted, printable PDF; the original when the portal has none
+    Converted,
+    /// The original as `<code>.pdf` and the converted one as
+    /// `<code>_chuyen-doi.pdf`
+    Both,
+}
+
+impl PdfVariant {
+    pub const ALL: &'static [PdfVariant] = &[
+        PdfVariant::Original,
+        PdfVariant::Converted,
+        PdfVariant::Both,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PdfVariant::Original => "original",
+            PdfVariant::Converted => "converted",
+            PdfVariant::Both => "both",
+        }
+    }
+}
+
+impl_setting_parse!(PdfVariant);
+
+/// Where `PdfVariant::Both` saves the converted PDF of the invoice at `pdf_path`
+pub fn converted_path(pdf_path: &Path) -> PathBuf {
+    let stem = pdf_path.file_stem().unwrap_or_default().to_string_lossy();
+    pdf_path.with_file_name(format!("{}_chuyen-doi.pdf", stem))
+}
+
+/// Download `url`, resuming with a range request when the connection drops
+/// mid-body, and refusing responses larger than `max_size` bytes
+pub fn fetch_pdf(client: &Client, url: &str, max_size: u64) -> Result<Vec<u8>, AppError> {
+    let mut body: Vec<u8> = Vec::new();
+    let mut expected_size: Option<u64> = None;
+    let mut last_error = String::new();
+
+    for attempt in 1..=MAX_FETCH_ATTEMPTS {
+        if attempt > 1 {
+            std::thread::sleep(RETRY_BACKOFF * (attempt - 1));
+        }
+
+        let mut request = client.get(url);
+        if !body.is_empty() {
+            request = request.header(RANGE, format!("bytes={}-", body.len()));
+        }
+
+        let response = match request.send() {
+            Ok(response) => response,
+            Err(e) => {
+                last_error = format!("HTTP request failed: {}", e);
+                continue;
+            }
+        };
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT if !body.is_empty() => {
+                expected_size = content_range_total(&response).or(expected_size);
+            }
+            status if status.is_success() => {
+                // A full response, either the first one or the server ignored the range
+                body.clear();
+                expected_size = header_u64(&response, CONTENT_LENGTH.as_str());
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE if Some(body.len() as u64) == expected_size => {
+                return Ok(body);
+            }
+            status if status.is_server_error() => {
+                last_error = format!("Download failed with status: {}", status);
+                continue;
+            }
+            status => {
+                return Err(AppError::DownloadFailed(format!(
+                    "Download failed with status: {}",
+                    status
+                )));
+            }
+        }
+
+        if let Some(size) = expected_size {
+            if size > max_size {
+                return Err(AppError::DownloadFailed(format!(
+                    "PDF is {} bytes, larger than the {} byte limit",
+                    size, max_size
+                )));
+            }
+        }
+
+        match read_body(response, &mut body, max_size) {
+            Ok(()) if expected_size.is_none_or(|size| body.len() as u64 >= size) => {
+                return Ok(body);
+            }
+            Ok(()) => {
+                last_error = format!(
+                    "Connection closed after {} of {} bytes",
+                    body.len(),
+                    expected_size.unwrap_or_default()
+                );
+            }
+            Err(ReadError::TooLarge) => {
+                return Err(AppError::DownloadFailed(format!(
+                    "PDF exceeds the {} byte limit",
+                    max_size
+                )));
+            }
+            Err(ReadError::Io(e)) => {
+                last_error = format!("Failed to read response after {} bytes: {}", body.len(), e);
+            }
+        }
+    }
+
+    Err(AppError::DownloadFailed(format!(
+        "{} (after {} attempts)",
+        last_error, MAX_FETCH_ATTEMPTS
+    )))
+}
+
+enum ReadError {
+    TooLarge,
+    Io(std::io::Error),
+}
+
+/// Append the response body to `body`, keeping what arrived before an error
+fn read_body(mut response: Response, body: &mut Vec<u8>, max_size: u64) -> Result<(), ReadError> {
+    let mut chunk = vec![0u8; READ_CHUNK_BYTES];
+
+    loop {
+        let read = response.read(&mut chunk).map_err(ReadError::Io)?;
+        if read == 0 {
+            return Ok(());
+        }
+
+        body.extend_from_slice(&chunk[..read]);
+        if body.len() as u64 > max_size {
+            return Err(ReadError::TooLarge);
+        }
+    }
+}
+
+fn header_u64(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn content_range_total(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_range_total)
+}
+
+/// Total size from a `Content-Range: bytes 100-199/200` header
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    let (_, total) = value.trim().strip_prefix("bytes ")?.rsplit_once('/')?;
+    total.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range_total() {
+        assert_eq!(parse_content_range_total("bytes 100-199/200"), Some(200));
+        assert_eq!(parse_content_range_total("bytes 0-0/1"), Some(1));
+        assert_eq!(parse_content_range_total("bytes 0-99/*"), None);
+        assert_eq!(parse_content_range_total("items 0-9/10"), None);
+    }
+
+    #[test]
+    fn test_pdf_variant() {
+        for variant in [PdfVariant::Original, PdfVariant::Converted, PdfVariant::Both] {
+            assert_eq!(PdfVariant::parse(variant.as_str()), variant);
+        }
+        assert_eq!(PdfVariant::parse(""), PdfVariant::Original);
+
+        assert_eq!(
+            converted_path(Path::new("/tmp/lot-1/AB_123.pdf")),
+            PathBuf::from("/tmp/lot-1/AB_123_chuyen-doi.pdf")
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use lopdf::{Dictionary, Document, Object, Stream};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::error::AppError;
+
+/// Fewer characters than this on every page means the PDF is a scan
+const MIN_TEXT_CHARS: usize = 40;
+
+/// Smaller images are logos, stamps or QR codes rather than scanned pages
+const MIN_PAGE_IMAGE_PIXELS: i64 = 500 * 500;
+
+/// Where the fields of an invoice PDF were read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetadataSource {
+    /// The PDF's text layer
+    Text,
+    /// OCR of the scanned pages
+    Ocr,
+}
+
+impl MetadataSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataSource::Text => "text",
+            MetadataSource::Ocr => "ocr",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "ocr" => MetadataSource::Ocr,
+            _ => MetadataSource::Text,
+        }
+    }
+}
+
+/// Fields read from the printed invoice; each is `None` when not found
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct InvoiceMetadata {
+    pub source: MetadataSource,
+    pub series: Option<String>,
+    pub number: Option<String>,
+    /// Issue date as YYYY-MM-DD
+    pub issued_date: Option<String>,
+    pub seller_tax_code: Option<String>,
+    pub total_before_tax: Option<f64>,
+    pub total_tax: Option<f64>,
+    pub total_payable: Option<f64>,
+}
+
+impl InvoiceMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.number.is_none() && self.issued_date.is_none() && self.total_payable.is_none()
+    }
+}
+
+/// Text of every page, empty for pages whose fonts cannot be decoded
+pub fn extract_text(path: &Path) -> Result<String, AppError> {
+    let document = load(path)?;
+    let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    Ok(document.extract_text(&pages).unwrap_or_default())
+}
+
+/// Text of every page of a PDF held in memory, e.g. an email attachment
+pub fn extract_text_from_bytes(bytes: &[u8]) -> Result<String, AppError> {
+    let document = Document::load_mem(bytes)
+        .map_err(|e| AppError::IoError(format!("Failed to read PDF: {}", e)))?;
+    let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    Ok(document.extract_text(&pages).unwrap_or_default())
+}
+
+/// Whether `text` is too short to be the text layer of an invoice
+pub fn is_scanned(text: &str) -> bool {
+    text.chars().filter(|c| c.is_alphanumeric()).count() < MIN_TEXT_CHARS
+}
+
+/// Write the page-sized images of the PDF into `dir` in formats Tesseract
+/// reads; images in encodings it cannot read are skipped
+///
+/// # Returns
+/// The written files, in page order
+pub fn extract_page_images(path: &Path, dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let document = load(path)?;
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+
+    for (page_number, page_id) in document.get_pages() {
+        let (inline, referenced) = document.get_page_resources(page_id);
+        let resources = inline
+            .into_iter()
+            .chain(referenced.into_iter().filter_map(|id| document.get_dictionary(id).ok()));
+
+        for resource in resources {
+            let Some(xobjects) = resource
+                .get(b"XObject")
+                .and_then(|object| document.dereference(object))
+                .and_then(|(_, object)| object.as_dict())
+                .ok()
+            else {
+                continue;
+            };
+
+            for (idx, (_, object)) in xobjects.iter().enumerate() {
+                let Ok((_, Object::Stream(stream))) = document.dereference(object) else {
+                    continue;
+                };
+                let stem = dir.join(format!("page-{}-{}", page_number, idx));
+                if let Some(file) = write_image(&document, stream, &stem)? {
+                    written.push(file);
+                }
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+fn load(path: &Path) -> Result<Document, AppError> {
+    Document::load(path)
+        .map_err(|e| AppError::IoError(format!("Failed to read PDF {}: {}", path.display(), e)))
+}
+
+/// Write one image XObject as JPEG, JPEG 2000, TIFF or PNM next to `stem`
+fn write_image(document: &Document, stream: &Stream, stem: &Path) -> Result<Option<PathBuf>, AppError> {
+    let dict = &stream.dict;
+    if dict.get(b"Subtype").and_then(Object::as_name_str).ok() != Some("Image") {
+        return Ok(None);
+    }
+    let width = integer(dict, b"Width");
+    let height = integer(dict, b"Height");
+    if width * height < MIN_PAGE_IMAGE_PIXELS {
+        return Ok(None);
+    }
+
+    let filters = stream.filters().unwrap_or_default();
+    let (extension, bytes) = match filters.last().map(String::as_str) {
+        Some("DCTDecode") => ("jpg", stream.content.clone()),
+        Some("JPXDecode") => ("jp2", stream.content.clone()),
+        Some("CCITTFaxDecode") => {
+            let params = dict
+                .get(b"DecodeParms")
+                .and_then(|object| document.dereference(object))
+                .and_then(|(_, object)| object.as_dict())
+                .ok();
+            let k = params.map(|params| integer(params, b"K")).unwrap_or(0);
+            ("tif", ccitt_tiff(width as u32, height as u32, k, &stream.content))
+        }
+        Some("FlateDecode") if filters.len() == 1 => {
+            let mut raw = Vec::new();
+            flate2::read::ZlibDecoder::new(stream.content.as_slice())
+                .read_to_end(&mut raw)
+                .map_err(|e| AppError::IoError(format!("Corrupt PDF image: {}", e)))?;
+            match pnm(width as usize, height as usize, integer(dict, b"BitsPerComponent"), &raw) {
+                Some(image) => image,
+                None => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    let file = stem.with_extension(extension);
+    std::fs::write(&file, bytes)?;
+    Ok(Some(file))
+}
+
+fn integer(dict: &Dictionary, key: &[u8]) -> i64 {
+    dict.get(key).and_then(Object::as_i64).unwrap_or(0)
+}
+
+/// Raw 8-bit gray or RGB samples as PGM/PPM, 1-bit samples as PBM
+fn pnm(width: usize, height: usize, bits: i64, raw: &[u8]) -> Option<(&'static str, Vec<u8>)> {
+    let (extension, magic, pixels) = match bits {
+        8 if raw.len() >= width * height * 3 => ("ppm", "P6", raw[..width * height * 3].to_vec()),
+        8 if raw.len() >= width * height => ("pgm", "P5", raw[..width * height].to_vec()),
+        // PDF gray uses 1 for white, PBM uses 1 for black
+        1 if raw.len() >= width.div_ceil(8) * height => (
+            "pbm",
+            "P4",
+            raw[..width.div_ceil(8) * height].iter().map(|byte| !byte).collect(),
+        ),
+        _ => return None,
+    };
+
+    let max_value = if magic == "P4" { "" } else { "255\n" };
+    let mut image = format!("{}\n{} {}\n{}", magic, width, height, max_value).into_bytes();
+    image.extend(pixels);
+    Some((extension, image))
+}
+
+/// Wrap CCITT fax data in a single-strip TIFF; `k` is the PDF `K` parameter
+fn ccitt_tiff(width: u32, height: u32, k: i64, data: &[u8]) -> Vec<u8> {
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+
+    let compression = if k < 0 { 4 } else { 3 };
+    let mut entries: Vec<(u16, u16, u32)> = vec![
+        (256, LONG, width),
+        (257, LONG, height),
+        (258, SHORT, 1),
+        (259, SHORT, compression),
+        // WhiteIsZero, which is what the fax runs decode to
+        (262, SHORT, 0),
+        (273, LONG, 0),
+        (277, SHORT, 1),
+        (278, LONG, height),
+        (279, LONG, data.len() as u32),
+    ];
+    if compression == 3 {
+        entries.push((292, LONG, u32::from(k > 0)));
+    }
+
+    let data_offset = 8 + 2 + entries.len() as u32 * 12 + 4;
+    let mut tiff = Vec::with_capacity(data_offset as usize + data.len());
+    tiff.extend_from_slice(b"II");
+    tiff.extend_from_slice(&42u16.to_le_bytes());
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+    tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, kind, value) in entries {
+        let value = if tag == 273 { data_offset } else { value };
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&kind.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        tiff.extend_from_slice(&value.to_le_bytes());
+    }
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(data);
+    tiff
+}
+
+/// Read the invoice fields from the printed labels of a Vietnamese invoice;
+/// accents are optional since OCR and some PDF fonts drop them
+pub fn extract_fields(text: &str, source: MetadataSource) -> InvoiceMetadata {
+    static PATTERNS: OnceLock<[Regex; 8]> = OnceLock::new();
+    let [series, number, date, numeric_date, tax_code, before_tax, tax, payable] =
+        PATTERNS.get_or_init(|| {
+            [
+                r"(?i)k[ýy] hi[ệe]u[^:\n]{0,20}:\s*([0-9]?[A-Z][A-Z0-9]{4,7})",
+                r"(?i)\bs[ốo](?: h[óo]a [đd][ơo]n)?[^:\n0-9]{0,12}:\s*0*(\d{1,8})\b",
+                r"(?i)ng[àa]y\D{0,12}(\d{1,2})\D{1,12}th[áa]ng\D{0,12}(\d{1,2})\D{1,12}n[ăa]m\D{0,12}(\d{4})",
+                r"\b(\d{1,2})/(\d{1,2})/(\d{4})\b",
+                r"(?i)m[ãa] s[ốo] thu[ếe][^:\n]{0,20}:\s*([0-9][0-9 ]{9,}(?:-\s*[0-9]{3})?)",
+                r"(?i)c[ộo]ng ti[ềe]n h[àa]ng[^:\n0-9]{0,30}:?\s*([0-9][0-9.,]*)",
+                r"(?i)ti[ềe]n thu[ếe] gtgt[^:\n0-9]{0,30}:?\s*([0-9][0-9.,]*)",
+                r"(?i)t[ổo]ng (?:c[ộo]ng )?ti[ềe]n thanh to[áa]n[^:\n0-9]{0,30}:?\s*([0-9][0-9.,]*)",
+            ]
+            .map(|pattern| Regex::new(pattern).expect("invoice field pattern"))
+        });
+
+    let first = |pattern: &Regex| {
+        pattern
+            .captures(text)
+            .and_then(|captures| captures.get(1))
+            .map(|value| value.as_str().trim().to_string())
+    };
+    let amount = |pattern: &Regex| {
+        // VND has no minor unit; dots and commas only group thousands
+        first(pattern).and_then(|value| {
+            value
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect::<String>()
+                .parse()
+                .ok()
+        })
+    };
+    let issued_date = date
+        .captures(text)
+        .or_else(|| numeric_date.captures(text))
+        .and_then(|captures| {
+            let day: u32 = captures[1].parse().ok()?;
+            let month: u32 = captures[2].parse().ok()?;
+            let year: i32 = captures[3].parse().ok()?;
+            chrono::NaiveDate::from_ymd_opt(year, month, day)
+        })
+        .map(|date| date.format("%Y-%m-%d").to_string());
+
+    InvoiceMetadata {
+        source,
+        series: first(series),
+        number: first(number),
+        issued_date,
+        seller_tax_code: first(tax_code).map(|code| code.replace(' ', "")),
+        total_before_tax: amount(before_tax),
+        total_tax: amount(tax),
+        total_payable: amount(payable),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_fields_from_ocr_text() {
+        let text = "HÓA ĐƠN GIÁ TRỊ GIA TĂNG\n\
+            Ký hiệu (Serial): 1C25TAA\n\
+            Số (No.): 0000123\n\
+            Ngày (Date) 05 tháng (month) 03 năm (year) 2025\n\
+            Đơn vị bán hàng: CÔNG TY A\n\
+            Mã số thuế (Tax code): 0 1 0 1 2 3 4 5 6 7\n\
+            Cộng tiền hàng (Total amount): 400.000\n\
+            Tiền thuế GTGT (VAT amount): 40.000\n\
+            Tổng cộng tiền thanh toán (Total payment): 440.000";
+
+        let fields = extract_fields(text, MetadataSource::Ocr);
+        assert_eq!(fields.series.as_deref(), Some("1C25TAA"));
+        assert_eq!(fields.number.as_deref(), Some("123"));
+        assert_eq!(fields.issued_date.as_deref(), Some("2025-03-05"));
+        assert_eq!(fields.seller_tax_code.as_deref(), Some("0101234567"));
+        assert_eq!(fields.total_before_tax, Some(400_000.0));
+        assert_eq!(fields.total_tax, Some(40_000.0));
+        assert_eq!(fields.total_payable, Some(440_000.0));
+
+        assert!(is_scanned("  \n 1 "));
+        assert_eq!(&ccitt_tiff(8, 2, -1, b"xx")[..4], b"II*\0");
+    }
+}
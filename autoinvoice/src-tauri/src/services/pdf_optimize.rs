@@ -0,0 +1,242 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use lopdf::{Document, Object};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::AppError;
+
+/// Folder, next to the PDFs, where originals are kept when asked to
+pub const ORIGINALS_DIR: &str = "originals";
+
+/// Longest stderr excerpt kept in the error of a failed run
+const MAX_STDERR_CHARS: usize = 500;
+
+/// What optimizing an invoice PDF did
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct PdfOptimization {
+    pub original_bytes: u64,
+    pub optimized_bytes: u64,
+    pub linearized: bool,
+    /// Copy of the PDF as downloaded, when originals are kept
+    pub original_path: Option<String>,
+    /// Why the PDF was left as downloaded, e.g. it is signed
+    pub skipped: Option<String>,
+}
+
+/// Program run to linearize: the configured path, or `qpdf` from PATH
+pub fn qpdf_command(path: &str) -> &str {
+    if path.trim().is_empty() {
+        "qpdf"
+    } else {
+        path.trim()
+    }
+}
+
+/// Check qpdf starts
+pub fn check_qpdf(path: &str) -> Result<(), AppError> {
+    let command = qpdf_command(path);
+    Command::new(command)
+        .arg("--version")
+        .output()
+        .map_err(|e| AppError::ConfigError(format!("Cannot run qpdf ({}): {}", command, e)))?;
+    Ok(())
+}
+
+/// Compress the PDF at `pdf_path` in place, without loss, and linearize it
+/// with qpdf when `qpdf` is given; signed PDFs are left alone, as rewriting
+/// them would break the signature
+pub fn optimize_file(
+    pdf_path: &Path,
+    keep_original: bool,
+    qpdf: Option<&str>,
+) -> Result<PdfOptimization, AppError> {
+    let original = std::fs::read(pdf_path)?;
+    let mut result = PdfOptimization {
+        original_bytes: original.len() as u64,
+        optimized_bytes: original.len() as u64,
+        linearized: false,
+        original_path: None,
+        skipped: None,
+    };
+
+    let mut doc = Document::load_mem(&original)
+        .map_err(|e| AppError::IoError(format!("Failed to read PDF: {}", e)))?;
+    if is_signed(&doc) {
+        result.skipped = Some("the PDF is digitally signed".to_string());
+        return Ok(result);
+    }
+
+    let compressed = compress(&mut doc)?;
+    let mut optimized = if compressed.len() < original.len() {
+        compressed
+    } else {
+        original.clone()
+    };
+    if let Some(qpdf) = qpdf {
+        optimized = linearize(qpdf, &optimized)?;
+        result.linearized = true;
+    }
+
+    if optimized == original {
+        result.skipped = Some("compressing did not make it smaller".to_string());
+        return Ok(result);
+    }
+
+    if keep_original {
+        let original_path = original_path(pdf_path);
+        if let Some(dir) = original_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&original_path, &original)?;
+        result.original_path = Some(original_path.to_string_lossy().to_string());
+    }
+    std::fs::write(pdf_path, &optimized)?;
+    result.optimized_bytes = optimized.len() as u64;
+    Ok(result)
+}
+
+/// `<folder>/originals/<file>` for `<folder>/<file>`
+fn original_path(pdf_path: &Path) -> PathBuf {
+    let dir = pdf_path.parent().unwrap_or(Path::new("."));
+    dir.join(ORIGINALS_DIR).join(pdf_path.file_name().unwrap_or_default())
+}
+
+/// Whether the PDF carries a digital signature
+fn is_signed(doc: &Document) -> bool {
+    doc.objects.values().any(|object| {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &stream.dict,
+            _ => return false,
+        };
+        dict.has(b"ByteRange") || dict.get(b"Type").and_then(Object::as_name).is_ok_and(|name| name == b"Sig")
+    })
+}
+
+/// Drop unused objects, deflate uncompressed streams and re-deflate
+/// Flate streams (images included) at the best level
+fn compress(doc: &mut Document) -> Result<Vec<u8>, AppError> {
+    doc.prune_objects();
+    doc.delete_zero_length_streams();
+    for object in doc.objects.values_mut() {
+        if let Object::Stream(stream) = object {
+            if is_flate_only(stream.dict.get(b"Filter").ok()) {
+                if let Some(content) = redeflate(&stream.content) {
+                    stream.set_content(content);
+                }
+            }
+        }
+    }
+    doc.compress();
+    doc.renumber_objects();
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes)
+        .map_err(|e| AppError::IoError(format!("Failed to write PDF: {}", e)))?;
+    Ok(bytes)
+}
+
+fn is_flate_only(filter: Option<&Object>) -> bool {
+    match filter {
+        Some(Object::Name(name)) => name == b"FlateDecode",
+        Some(Object::Array(filters)) => {
+            matches!(filters.as_slice(), [Object::Name(name)] if name == b"FlateDecode")
+        }
+        _ => false,
+    }
+}
+
+/// The same data deflated at the best level, when that is smaller; the
+/// decode parameters (predictors) stay valid as the inflated bytes are kept
+fn redeflate(content: &[u8]) -> Option<Vec<u8>> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(content).read_to_end(&mut inflated).ok()?;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&inflated).ok()?;
+    let deflated = encoder.finish().ok()?;
+    (deflated.len() < content.len()).then_some(deflated)
+}
+
+/// Linearize ("fast web view") with qpdf, so viewers show the first page
+/// before the whole file is read
+fn linearize(qpdf: &str, pdf: &[u8]) -> Result<Vec<u8>, AppError> {
+    let dir = std::env::temp_dir().join(format!("autoinvoice-qpdf-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let (input, output) = (dir.join("in.pdf"), dir.join("out.pdf"));
+
+    let command = qpdf_command(qpdf);
+    let linearized = std::fs::write(&input, pdf)
+        .map_err(AppError::from)
+        .and_then(|_| {
+            Command::new(command)
+                .arg("--linearize")
+                .arg(&input)
+                .arg(&output)
+                .output()
+                .map_err(|e| AppError::ConfigError(format!("Cannot run qpdf ({}): {}", command, e)))
+        })
+        .and_then(|run| {
+            // Exit code 3 means warnings, with the output written
+            if !run.status.success() && run.status.code() != Some(3) {
+                let stderr: String = String::from_utf8_lossy(&run.stderr)
+                    .trim()
+                    .chars()
+                    .take(MAX_STDERR_CHARS)
+                    .collect();
+                return Err(AppError::IoError(format!("qpdf exited with {}: {}", run.status, stderr)));
+            }
+            Ok(std::fs::read(&output)?)
+        });
+    let _ = std::fs::remove_dir_all(&dir);
+    linearized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_optimize_pdf() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        // A page whose content and image are stored uncompressed
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let image_id = doc.add_object(Stream::new(
+            dictionary! { "Type" => "XObject", "Subtype" => "Image", "Width" => 100, "Height" => 100,
+                          "ColorSpace" => "DeviceGray", "BitsPerComponent" => 8 },
+            vec![0x80; 100 * 100],
+        ));
+        let content_id = doc.add_object(Stream::new(dictionary! {}, b"q 100 0 0 100 0 0 cm /Im0 Do Q".repeat(20)));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page", "Parent" => pages_id, "Contents" => content_id,
+            "Resources" => dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        let pdf = tmp.path().join("C24TAA0000001_Ab.pdf");
+        doc.save(&pdf).unwrap();
+
+        let result = optimize_file(&pdf, true, None).unwrap();
+        assert!(result.skipped.is_none());
+        assert!(result.optimized_bytes < result.original_bytes / 2);
+        assert_eq!(std::fs::metadata(&pdf).unwrap().len(), result.optimized_bytes);
+        let original = PathBuf::from(result.original_path.unwrap());
+        assert_eq!(std::fs::metadata(original).unwrap().len(), result.original_bytes);
+        assert_eq!(Document::load(&pdf).unwrap().get_pages().len(), 1);
+
+        // Once is enough
+        let again = optimize_file(&pdf, false, None).unwrap();
+        assert!(again.skipped.is_some());
+    }
+}
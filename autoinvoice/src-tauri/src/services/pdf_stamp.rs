@@ -0,0 +1,218 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, IncrementalDocument, Object, ObjectId, Stream, StringFormat};
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::services::batch_report::fold_diacritics;
+
+/// Resource name of the stamp font, unlikely to clash with the page's own
+const FONT_NAME: &str = "AutoInvoiceStamp";
+const FONT_SIZE: f32 = 9.0;
+/// Distance of the stamp from the top-left corner of the page, in points
+const OFFSET: f32 = 20.0;
+/// A4, for pages without a MediaBox
+const DEFAULT_MEDIA_BOX: [f32; 4] = [0.0, 0.0, 595.0, 842.0];
+
+/// Text stamped on an invoice, e.g. "Processed 05/03/2025 – batch Tháng 3"
+pub fn stamp_text(processed: chrono::NaiveDate, batch_name: &str) -> String {
+    format!("Processed {} – batch {}", processed.format("%d/%m/%Y"), batch_name)
+}
+
+/// Write `text` in red at the top-left of the first page of the PDF at
+/// `pdf_path`. The stamp is appended as an incremental update, so the bytes
+/// as downloaded stay untouched and a signature over them still verifies;
+/// viewers report the stamp as a change made after signing
+pub fn stamp_file(pdf_path: &Path, text: &str) -> Result<(), AppError> {
+    let original = std::fs::read(pdf_path)?;
+    let mut doc = IncrementalDocument::load_from(original.as_slice())
+        .map_err(|e| AppError::IoError(format!("Failed to read PDF: {}", e)))?;
+    let prev = doc.get_prev_documents();
+    if prev.trailer.has(b"Encrypt") {
+        return Err(AppError::IoError("Encrypted PDFs cannot be stamped".to_string()));
+    }
+    let page_id = *prev
+        .get_pages()
+        .get(&1)
+        .ok_or_else(|| AppError::IoError("The PDF has no pages".to_string()))?;
+
+    let mut resources = inherited(prev, page_id, b"Resources")
+        .map(|resources| resolve_dict(prev, resources))
+        .unwrap_or_default();
+    let mut fonts = resources
+        .get(b"Font")
+        .map(|fonts| resolve_dict(prev, fonts))
+        .unwrap_or_default();
+    let media_box = inherited(prev, page_id, b"MediaBox")
+        .and_then(|media_box| media_box_of(prev, media_box))
+        .unwrap_or(DEFAULT_MEDIA_BOX);
+    let mut contents = page_contents(prev, page_id);
+
+    let new = &mut doc.new_document;
+    let font_id = new.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    fonts.set(FONT_NAME, font_id);
+    resources.set("Font", fonts);
+
+    // Wrap the page's own content in q/Q so a transform it leaves behind does
+    // not move the stamp
+    let open_id = new.add_object(Stream::new(dictionary! {}, b"q\n".to_vec()));
+    let stamp_id = new.add_object(Stream::new(
+        dictionary! {},
+        stamp_content(text, media_box[0] + OFFSET, media_box[3] - OFFSET)?,
+    ));
+    contents.insert(0, open_id.into());
+    contents.push(stamp_id.into());
+
+    doc.opt_clone_object_to_new_document(page_id)
+        .map_err(|e| AppError::IoError(format!("Failed to read PDF: {}", e)))?;
+    let page = doc
+        .new_document
+        .get_object_mut(page_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| AppError::IoError(format!("Failed to read PDF: {}", e)))?;
+    page.set("Resources", resources);
+    page.set("Contents", contents);
+
+    let mut stamped = Vec::new();
+    doc.save_to(&mut stamped)
+        .map_err(|e| AppError::IoError(format!("Failed to write PDF: {}", e)))?;
+    std::fs::write(pdf_path, stamped)?;
+    Ok(())
+}
+
+fn stamp_content(text: &str, x: f32, y: f32) -> Result<Vec<u8>, AppError> {
+    let operations = vec![
+        Operation::new("Q", vec![]),
+        Operation::new("q", vec![]),
+        Operation::new("rg", vec![0.8.into(), 0.into(), 0.into()]),
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec![FONT_NAME.into(), FONT_SIZE.into()]),
+        Operation::new("Td", vec![x.into(), (y - FONT_SIZE).into()]),
+        Operation::new("Tj", vec![Object::String(win_ansi(text), StringFormat::Literal)]),
+        Operation::new("ET", vec![]),
+        Operation::new("Q", vec![]),
+    ];
+    Content { operations }
+        .encode()
+        .map_err(|e| AppError::IoError(format!("Failed to write PDF: {}", e)))
+}
+
+/// Encode for the standard Helvetica font, which has no Vietnamese letters
+fn win_ansi(text: &str) -> Vec<u8> {
+    fold_diacritics(text)
+        .chars()
+        .map(|c| match c {
+            '–' => 0x96,
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Attribute of the page, or of the nearest page tree node that sets it
+fn inherited<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    // Bounded, in case of a cycle in a malformed page tree
+    for _ in 0..32 {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        node = node
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok()?;
+    }
+    None
+}
+
+/// Copy of a dictionary given directly or by reference
+fn resolve_dict(doc: &Document, object: &Object) -> Dictionary {
+    let object = match object {
+        Object::Reference(id) => doc.get_object(*id).ok(),
+        object => Some(object),
+    };
+    object
+        .and_then(|object| object.as_dict().ok())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn media_box_of(doc: &Document, object: &Object) -> Option<[f32; 4]> {
+    let object = match object {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        object => object,
+    };
+    let values: Vec<f32> = object
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|value| value.as_float().ok())
+        .collect();
+    values.try_into().ok()
+}
+
+/// References to the content streams of a page
+fn page_contents(doc: &Document, page_id: ObjectId) -> Vec<Object> {
+    let contents = doc
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"Contents"));
+    match contents {
+        Ok(Object::Reference(id)) => match doc.get_object(*id) {
+            Ok(Object::Array(streams)) => streams.clone(),
+            _ => vec![Object::Reference(*id)],
+        },
+        Ok(Object::Array(streams)) => streams.clone(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_first_page() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        // Resources and MediaBox inherited from the page tree, as many
+        // generators write them
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier" });
+        let content_id = doc.add_object(Stream::new(dictionary! {}, b"BT /F1 12 Tf 50 700 Td (Invoice) Tj ET".to_vec()));
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content_id });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        let pdf = tmp.path().join("C24TAA0000001_Ab.pdf");
+        doc.save(&pdf).unwrap();
+        let original = std::fs::read(&pdf).unwrap();
+
+        let text = stamp_text(chrono::NaiveDate::from_ymd_opt(2025, 3, 5).unwrap(), "Tháng 3");
+        assert_eq!(text, "Processed 05/03/2025 – batch Tháng 3");
+        stamp_file(&pdf, &text).unwrap();
+
+        let stamped = std::fs::read(&pdf).unwrap();
+        assert!(stamped.starts_with(&original));
+        let doc = Document::load_mem(&stamped).unwrap();
+        let page_id = doc.get_pages()[&1];
+        let content = String::from_utf8_lossy(&doc.get_page_content(page_id).unwrap()).to_string();
+        assert!(content.contains("(Invoice) Tj"));
+        assert!(content.contains("Thang 3"));
+        let fonts = doc.get_page_fonts(page_id);
+        assert!(fonts.contains_key(b"F1".as_slice()));
+        assert!(fonts.contains_key(FONT_NAME.as_bytes()));
+    }
+}
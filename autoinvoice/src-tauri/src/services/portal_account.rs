@@ -0,0 +1,244 @@
+use chrono::NaiveDate;
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+use crate::services::browser::{self, VnptBrowser};
+use crate::services::captcha::CaptchaSolver;
+use crate::services::excel_parser::{self, ExcelParseResult, InvoiceCode};
+
+/// Login attempts before giving up; a misread captcha costs one
+const MAX_LOGIN_ATTEMPTS: u32 = 3;
+
+/// Invoice list pages read for one period, in case the next link never ends
+const MAX_LIST_PAGES: u32 = 200;
+
+/// Account on the portal's logged-in area, where a company sees every
+/// invoice issued to it
+#[derive(Debug, Clone, Default)]
+pub struct PortalAccount {
+    pub login_url: String,
+    /// Page listing the account's invoices, filtered by issue date
+    pub invoice_list_url: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Selectors for the login form and the invoice list, keyed like
+/// `browser::selectors::all()` so they can be overridden the same way
+pub mod selectors {
+    pub const LOGIN_USERNAME: &[&str] = &[
+        "#UserName",
+        "input[name='UserName']",
+        "input[name='username']",
+        "input[autocomplete='username']",
+    ];
+
+    pub const LOGIN_PASSWORD: &[&str] = &[
+        "#Password",
+        "input[name='Password']",
+        "input[type='password']",
+    ];
+
+    pub const LOGIN_BUTTON: &[&str] = &[
+        "#btnLogin",
+        "button[type='submit']",
+        "input[type='submit']",
+    ];
+
+    /// Only shown once logged in
+    pub const LOGGED_IN: &[&str] = &[
+        "a[href*='LogOff']",
+        "a[href*='Logout' i]",
+        "a[href*='DangXuat' i]",
+    ];
+
+    /// Issue date range of the invoice list, typed as dd/mm/yyyy
+    pub const PERIOD_FROM: &[&str] = &["#FromDate", "input[name='FromDate']", "input[name='fromDate']"];
+
+    pub const PERIOD_TO: &[&str] = &["#ToDate", "input[name='ToDate']", "input[name='toDate']"];
+
+    pub const LIST_SEARCH_BUTTON: &[&str] = &["#btnSearch", "button[type='submit']"];
+
+    /// Table of invoices, with a "Mã tra cứu" column
+    pub const INVOICE_TABLE: &[&str] = &["#tblInvoice", "table.table", "table"];
+
+    /// Link to the next page of the list; absent or disabled on the last page
+    pub const NEXT_PAGE: &[&str] = &[
+        ".pagination li.next:not(.disabled) a",
+        ".pagination a[rel='next']",
+        "a.paginate_button.next:not(.disabled)",
+        "a[aria-label='Next']:not([aria-disabled='true'])",
+    ];
+}
+
+impl PortalAccount {
+    pub fn is_configured(&self) -> bool {
+        !self.username.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !self.is_configured() {
+            return Ok(());
+        }
+        if self.password.is_empty() {
+            return Err(AppError::ConfigError("Portal password is required".to_string()));
+        }
+        for (name, url) in [("login", &self.login_url), ("invoice list", &self.invoice_list_url)] {
+            if url::Url::parse(url).is_err() {
+                return Err(AppError::ConfigError(format!(
+                    "Portal {} URL is not a valid URL: '{}'",
+                    name, url
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Log in to the portal, solving the login captcha when the form has one
+pub fn log_in(
+    browser: &VnptBrowser,
+    account: &PortalAccount,
+    solver: &CaptchaSolver,
+) -> Result<(), AppError> {
+    if !account.is_configured() {
+        return Err(AppError::ConfigError("No portal account is configured".to_string()));
+    }
+
+    let mut last_error = None;
+    for _ in 0..MAX_LOGIN_ATTEMPTS {
+        browser.navigate_to_search(&account.login_url)?;
+        browser.fill_input("login_username", selectors::LOGIN_USERNAME, &account.username, "Username input field")?;
+        browser.fill_input("login_password", selectors::LOGIN_PASSWORD, &account.password, "Password input field")?;
+        if browser.has_element("captcha_image", browser::selectors::CAPTCHA_IMAGE) {
+            let image = browser.get_captcha_screenshot()?;
+            browser.fill_captcha(&solver.solve_blocking(&image)?.0)?;
+        }
+        browser.click("login_button", selectors::LOGIN_BUTTON, "Login button")?;
+
+        if browser.has_element("logged_in", selectors::LOGGED_IN) {
+            return Ok(());
+        }
+        last_error = browser.check_for_error();
+    }
+
+    Err(AppError::BrowserError(format!(
+        "Portal login failed: {}",
+        last_error
+            .map(|error| error.trim().to_string())
+            .unwrap_or_else(|| "still on the login page".to_string())
+    )))
+}
+
+/// Invoices the logged-in account received between `from` and `to`, every
+/// page of the list
+pub fn list_invoices(
+    browser: &VnptBrowser,
+    account: &PortalAccount,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<InvoiceCode>, AppError> {
+    browser.navigate_to_search(&account.invoice_list_url)?;
+    browser.fill_input("period_from", selectors::PERIOD_FROM, &portal_date(from), "Period start input field")?;
+    browser.fill_input("period_to", selectors::PERIOD_TO, &portal_date(to), "Period end input field")?;
+    browser.click("list_search_button", selectors::LIST_SEARCH_BUTTON, "Invoice list search button")?;
+
+    let mut invoices: Vec<InvoiceCode> = Vec::new();
+    for _ in 0..MAX_LIST_PAGES {
+        let rows = browser.read_table("invoice_table", selectors::INVOICE_TABLE)?;
+        let page = excel_parser::parse_text_rows(&rows).ok_or_else(|| {
+            AppError::BrowserError("The portal's invoice list has no 'Mã tra cứu' column".to_string())
+        })?;
+        if !merge_page(&mut invoices, page)
+            || !browser.has_element("next_page", selectors::NEXT_PAGE)
+        {
+            break;
+        }
+        browser.click("next_page", selectors::NEXT_PAGE, "Next page link")?;
+    }
+
+    Ok(invoices)
+}
+
+/// Append the invoices of a page not seen on earlier ones, numbering rows
+/// across pages; false when the page brought nothing new, i.e. the portal
+/// served the same page again
+fn merge_page(invoices: &mut Vec<InvoiceCode>, page: Vec<InvoiceCode>) -> bool {
+    let before = invoices.len();
+    for mut invoice in page {
+        if invoices.iter().any(|seen| seen.code == invoice.code) {
+            continue;
+        }
+        invoice.row_number = invoices.len() + 1;
+        invoices.push(invoice);
+    }
+    invoices.len() > before
+}
+
+/// Discovered invoices dressed as a parsed sheet; the hash covers the codes,
+/// so listing the same period again is recognised as an earlier source
+pub fn as_parse_result(
+    invoices: Vec<InvoiceCode>,
+    account: &PortalAccount,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> ExcelParseResult {
+    let mut codes: Vec<&str> = invoices.iter().map(|invoice| invoice.code.as_str()).collect();
+    codes.sort_unstable();
+    let file_hash = hex::encode(Sha256::digest(codes.join("\n").as_bytes()));
+
+    ExcelParseResult {
+        total_rows: invoices.len(),
+        invoices,
+        detected_url: None,
+        detected_urls: Vec::new(),
+        sheet_name: account.username.clone(),
+        file_name: format!("Tài khoản cổng {} - {}", portal_date(from), portal_date(to)),
+        file_path: String::new(),
+        file_hash,
+    }
+}
+
+/// Dates as the portal's date pickers expect them
+fn portal_date(date: NaiveDate) -> String {
+    date.format("%d/%m/%Y").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invoice_list_rows() {
+        let row = |cells: &[&str]| cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let rows = vec![
+            row(&["STT", "Ngày hóa đơn", "Mã tra cứu", "MST người bán"]),
+            row(&["1", "02/05/2024", "C24TAA0000123_Ab", "0100109106"]),
+            row(&["", "", "Tổng cộng", ""]),
+        ];
+
+        let invoices = excel_parser::parse_text_rows(&rows).unwrap();
+        assert_eq!(invoices.len(), 1);
+        assert_eq!(invoices[0].code, "C24TAA0000123_Ab");
+        assert_eq!(invoices[0].seller_tax_code.as_deref(), Some("0100109106"));
+        assert_eq!(portal_date(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()), "02/05/2024");
+    }
+
+    #[test]
+    fn test_merge_pages() {
+        let page = |codes: &[&str]| {
+            let mut rows = vec![vec!["Mã tra cứu".to_string()]];
+            rows.extend(codes.iter().map(|code| vec![code.to_string()]));
+            excel_parser::parse_text_rows(&rows).unwrap()
+        };
+
+        let mut invoices = Vec::new();
+        assert!(merge_page(&mut invoices, page(&["C24TAA0000001_Ab", "C24TAA0000002_Ab"])));
+        assert!(merge_page(&mut invoices, page(&["C24TAA0000003_Ab"])));
+        // A last page served again brings nothing new
+        assert!(!merge_page(&mut invoices, page(&["C24TAA0000003_Ab"])));
+
+        assert_eq!(invoices.len(), 3);
+        assert_eq!(invoices[2].row_number, 3);
+    }
+}
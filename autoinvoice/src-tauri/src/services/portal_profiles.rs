@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::AppError;
+
+/// How gently a portal host is treated. The delay is a floor and the retry
+/// limit a cap on the user's settings, so a fast setup tuned for one portal
+/// never hammers another.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct PortalProfile {
+    /// Host the profile applies to, including its subdomains
+    pub host: String,
+    /// Shortest pause between two invoices, in seconds
+    pub base_delay_secs: u32,
+    /// Invoices downloaded from the host at the same time, across batches
+    pub max_concurrency: u32,
+    /// Most attempts per invoice
+    pub max_retries: u32,
+    /// Shipped with the app rather than saved by the user
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+/// Defaults for the portals the app knows, overridable per host
+const BUILTIN_PROFILES: &[(&str, u32, u32, u32)] = &[
+    ("vnpt-invoice.com.vn", 2, 1, 3),
+    ("hoadondientu.gdt.gov.vn", 5, 1, 2),
+];
+
+impl PortalProfile {
+    fn builtin(host: &str, base_delay_secs: u32, max_concurrency: u32, max_retries: u32) -> Self {
+        Self {
+            host: host.to_string(),
+            base_delay_secs,
+            max_concurrency,
+            max_retries,
+            builtin: true,
+        }
+    }
+
+    /// Lowercase host, without scheme, path or leading dot
+    pub fn validate(&self) -> Result<Self, AppError> {
+        let host = self.host.trim().trim_start_matches('.').to_lowercase();
+        if host.is_empty() || host.contains(['/', ':', ' ']) {
+            return Err(AppError::ConfigError(format!(
+                "Invalid portal host: '{}', use a host name like vnpt-invoice.com.vn",
+                self.host
+            )));
+        }
+        if self.max_concurrency == 0 || self.max_retries == 0 {
+            return Err(AppError::ConfigError(
+                "Concurrency and retries of a portal profile must be at least 1".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            host,
+            builtin: false,
+            ..self.clone()
+        })
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        host == self.host || host.ends_with(&format!(".{}", self.host))
+    }
+}
+
+/// Built-in profiles not replaced by a saved one, followed by the saved ones
+pub fn merge(saved: Vec<PortalProfile>) -> Vec<PortalProfile> {
+    let mut profiles: Vec<PortalProfile> = BUILTIN_PROFILES
+        .iter()
+        .filter(|(host, ..)| !saved.iter().any(|p| p.host == *host))
+        .map(|&(host, delay, concurrency, retries)| {
+            PortalProfile::builtin(host, delay, concurrency, retries)
+        })
+        .collect();
+    profiles.extend(saved);
+    profiles
+}
+
+/// Most specific profile for the host of `url`, if any matches
+pub fn resolve(profiles: &[PortalProfile], url: &str) -> Option<PortalProfile> {
+    let host = url::Url::parse(url).ok()?.host_str()?.to_lowercase();
+    profiles
+        .iter()
+        .filter(|profile| profile.matches(&host))
+        .max_by_key(|profile| profile.host.len())
+        .cloned()
+}
+
+/// Download slots per portal host, shared by every running batch
+#[derive(Default)]
+pub struct PortalSlots {
+    hosts: Mutex<HashMap<String, (u32, Arc<Semaphore>)>>,
+}
+
+impl PortalSlots {
+    /// Wait for a free slot of `profile`'s host; held until the permit drops
+    pub async fn acquire(&self, profile: &PortalProfile) -> Option<OwnedSemaphorePermit> {
+        let semaphore = {
+            let mut hosts = self.hosts.lock().ok()?;
+            let entry = hosts.entry(profile.host.clone()).or_insert_with(|| {
+                (
+                    profile.max_concurrency,
+                    Arc::new(Semaphore::new(profile.max_concurrency as usize)),
+                )
+            });
+            // A changed limit takes effect once the current holders finish
+            if entry.0 != profile.max_concurrency {
+                *entry = (
+                    profile.max_concurrency,
+                    Arc::new(Semaphore::new(profile.max_concurrency as usize)),
+                );
+            }
+            entry.1.clone()
+        };
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_most_specific_profile() {
+        let saved = vec![PortalProfile {
+            host: "0101-tt78.vnpt-invoice.com.vn".to_string(),
+            base_delay_secs: 0,
+            max_concurrency: 2,
+            max_retries: 5,
+            builtin: false,
+        }];
+        let profiles = merge(saved);
+
+        let own = resolve(
+            &profiles,
+            "https://0101-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey",
+        )
+        .unwrap();
+        assert_eq!(own.max_concurrency, 2);
+
+        let other = resolve(
+            &profiles,
+            "https://0202-tt78.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey",
+        )
+        .unwrap();
+        assert_eq!(other.host, "vnpt-invoice.com.vn");
+        assert!(other.builtin);
+
+        assert!(resolve(&profiles, "https://evil-vnpt-invoice.com.vn.example.com/").is_none());
+        assert!(resolve(&profiles, "https://example.com/").is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use specta::Type;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::commands::download::{run_download, DownloadOverrides, DownloadState, StartDownloadRequest};
+use crate::commands::settings::Settings;
+use crate::error::AppError;
+use crate::services::app_lock::AppLock;
+use crate::services::database::Database;
+use crate::services::downloader::{BatchResult, InvoiceDownloadRequest};
+use crate::services::excel_parser::LookupMode;
+
+/// Default hours before a not-found invoice is looked up again
+pub const DEFAULT_RETRY_NOT_FOUND_HOURS: u32 = 24;
+
+/// Longest accepted retry interval, a month
+pub const MAX_RETRY_NOT_FOUND_HOURS: u32 = 24 * 30;
+
+/// Lookups of a code before it leaves the queue, about a week at the default interval
+const MAX_NOT_FOUND_ATTEMPTS: u32 = 7;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Latest lookup of an invoice the portal did not have yet
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct RetryLaterInvoice {
+    pub invoice_id: String,
+    pub batch_id: String,
+    pub code: String,
+    /// Portal message of the last lookup
+    pub error: Option<String>,
+    pub not_found_at: String,
+    /// How many lookups found nothing so far
+    pub attempts: u32,
+    /// Portal the last lookup ran against
+    pub portal_url: Option<String>,
+}
+
+impl RetryLaterInvoice {
+    fn is_due(&self, retry_after: ChronoDuration, now: DateTime<Utc>) -> bool {
+        DateTime::parse_from_rfc3339(&self.not_found_at)
+            .map(|not_found_at| not_found_at.with_timezone(&Utc) + retry_after <= now)
+            .unwrap_or(false)
+    }
+}
+
+/// Every few minutes, look up again the not-found invoices whose retry
+/// interval has passed, as one batch
+pub fn spawn_scheduler(app: AppHandle, db: Arc<Database>, mut settings_rx: watch::Receiver<Settings>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let settings = settings_rx.borrow_and_update().clone();
+            let read_only = app.state::<AppLock>().is_read_only();
+            if settings.retry_not_found_hours > 0 && !read_only {
+                // A failed run is retried on the next tick
+                let _ = retry_due(&app, &db, settings.retry_not_found_hours).await;
+            }
+
+            // A shorter interval applies without waiting for the next tick
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                Ok(()) = settings_rx.changed() => {}
+            }
+        }
+    });
+}
+
+/// Invoices still in the queue, i.e. not given up on yet
+pub fn pending(db: &Database) -> Result<Vec<RetryLaterInvoice>, AppError> {
+    Ok(db
+        .get_retry_later_invoices()?
+        .into_iter()
+        .filter(|invoice| invoice.attempts < MAX_NOT_FOUND_ATTEMPTS)
+        .collect())
+}
+
+async fn retry_due(app: &AppHandle, db: &Arc<Database>, hours: u32) -> Result<Option<BatchResult>, AppError> {
+    let retry_after = ChronoDuration::hours(hours as i64);
+    let Some(request) = due_request(pending(db)?, retry_after, Utc::now()) else {
+        return Ok(None);
+    };
+
+    let state = app.state::<DownloadState>();
+    run_download(app, &state, db.clone(), request).await.map(Some)
+}
+
+/// Batch looking up the invoices whose interval has passed, if any
+fn due_request(
+    invoices: Vec<RetryLaterInvoice>,
+    retry_after: ChronoDuration,
+    now: DateTime<Utc>,
+) -> Option<StartDownloadRequest> {
+    let invoices: Vec<InvoiceDownloadRequest> = invoices
+        .into_iter()
+        .filter(|invoice| invoice.is_due(retry_after, now))
+        .map(|invoice| InvoiceDownloadRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            code: invoice.code,
+            seller_tax_code: None,
+            lookup_mode: LookupMode::Code,
+            invoice_series: None,
+            invoice_number: None,
+            portal_url: invoice.portal_url,
+        })
+        .collect();
+    if invoices.is_empty() {
+        return None;
+    }
+
+    Some(StartDownloadRequest {
+        batch_id: uuid::Uuid::new_v4().to_string(),
+        name: Some(format!(
+            "Thử lại hóa đơn chưa có {}",
+            now.with_timezone(&chrono::Local).format("%d/%m/%Y")
+        )),
+        source_file: None,
+        source_path: None,
+        source_hash: None,
+        invoices,
+        overrides: DownloadOverrides::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_request() {
+        let now = Utc::now();
+        let invoice = |code: &str, hours_ago: i64| RetryLaterInvoice {
+            invoice_id: code.to_string(),
+            batch_id: "batch".to_string(),
+            code: code.to_string(),
+            error: None,
+            not_found_at: (now - ChronoDuration::hours(hours_ago)).to_rfc3339(),
+            attempts: 1,
+            portal_url: Some("https://example.vnpt-invoice.com.vn/".to_string()),
+        };
+
+        let request = due_request(
+            vec![invoice("C24TAA0000001_Ab", 30), invoice("C24TAA0000002_Ab", 2)],
+            ChronoDuration::hours(24),
+            now,
+        )
+        .unwrap();
+        assert_eq!(request.invoices.len(), 1);
+        assert_eq!(request.invoices[0].code, "C24TAA0000001_Ab");
+        assert!(request.invoices[0].portal_url.is_some());
+
+        assert!(due_request(vec![invoice("C24TAA0000002_Ab", 2)], ChronoDuration::hours(24), now).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{NaiveTime, Timelike};
+use std::time::Duration;
+
+use crate::error::AppError;
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Daily window during which downloads pause, e.g. 22:00-06:00.
+/// The window may wrap past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parse "HH:MM" bounds; both empty means quiet hours are disabled
+    pub fn parse(start: &str, end: &str) -> Result<Option<Self>, AppError> {
+        let (start, end) = (start.trim(), end.trim());
+        if start.is_empty() && end.is_empty() {
+            return Ok(None);
+        }
+
+        let parse_time = |value: &str| {
+            NaiveTime::parse_from_str(value, TIME_FORMAT).map_err(|_| {
+                AppError::ConfigError(format!("Invalid quiet hours time '{}', expected HH:MM", value))
+            })
+        };
+
+        let start = parse_time(start)?;
+        let end = parse_time(end)?;
+        if start == end {
+            return Err(AppError::ConfigError(
+                "Quiet hours start and end must differ".to_string(),
+            ));
+        }
+
+        Ok(Some(Self { start, end }))
+    }
+
+    /// Whether `time` falls inside the window (start inclusive, end exclusive)
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Time left until the window ends, measured from `time`
+    pub fn remaining(&self, time: NaiveTime) -> Duration {
+        let now = time.num_seconds_from_midnight() as i64;
+        let end = self.end.num_seconds_from_midnight() as i64;
+        let secs = (end - now).rem_euclid(24 * 60 * 60);
+        Duration::from_secs(secs as u64)
+    }
+
+    pub fn end(&self) -> String {
+        self.end.format(TIME_FORMAT).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, TIME_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_parse_quiet_hours() {
+        assert_eq!(QuietHours::parse("", "").unwrap(), None);
+        assert!(QuietHours::parse("22:00", "06:00").unwrap().is_some());
+        assert!(QuietHours::parse("22:00", "").is_err());
+        assert!(QuietHours::parse("25:00", "06:00").is_err());
+        assert!(QuietHours::parse("08:00", "08:00").is_err());
+    }
+
+    #[test]
+    fn test_quiet_hours_window() {
+        let overnight = QuietHours::parse("22:00", "06:00").unwrap().unwrap();
+        assert!(overnight.contains(time("23:30")));
+        assert!(overnight.contains(time("05:59")));
+        assert!(!overnight.contains(time("06:00")));
+        assert!(!overnight.contains(time("12:00")));
+        assert_eq!(overnight.remaining(time("23:00")), Duration::from_secs(7 * 3600));
+
+        let lunch = QuietHours::parse("12:00", "13:30").unwrap().unwrap();
+        assert!(lunch.contains(time("12:45")));
+        assert!(!lunch.contains(time("13:30")));
+        assert_eq!(lunch.remaining(time("12:30")), Duration::from_secs(3600));
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+
+const SELECTOR_CONFIG_FILE: &str = "selectors.json";
+
+/// Version of the selectors built into this release; bump it whenever
+/// `browser::selectors` changes to follow the portal HTML
+pub const BUILTIN_SELECTOR_VERSION: u32 = 1;
+
+/// Local selector overrides, tried before the built-in selectors.
+/// Keys are the field names from `browser::selectors::all()`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SelectorConfig {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub overrides: HashMap<String, Vec<String>>,
+}
+
+impl SelectorConfig {
+    /// Location of the selector config inside the app data directory
+    pub fn path(app_data_dir: &Path) -> PathBuf {
+        app_data_dir.join(SELECTOR_CONFIG_FILE)
+    }
+
+    /// Load the selector config, returning an empty config if none was saved yet
+    pub fn load(app_data_dir: &Path) -> Result<Self, AppError> {
+        let path = Self::path(app_data_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| AppError::ConfigError(format!("Invalid selector config: {}", e)))
+    }
+
+    /// Save the selector config to the app data directory
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), AppError> {
+        std::fs::create_dir_all(app_data_dir)?;
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| AppError::ConfigError(format!("Failed to serialize selector config: {}", e)))?;
+        std::fs::write(Self::path(app_data_dir), content)?;
+
+        Ok(())
+    }
+
+    /// Selector version in effect: a saved config newer than the built-in
+    /// selectors brings its own version
+    pub fn effective_version(&self) -> u32 {
+        self.version.max(BUILTIN_SELECTOR_VERSION)
+    }
+
+    /// Put a selector at the front of the overrides for a field
+    pub fn add_override(&mut self, field: &str, selector: &str) {
+        let selectors = self.overrides.entry(field.to_string()).or_default();
+        selectors.retain(|s| s != selector);
+        selectors.insert(0, selector.to_string());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::error::AppError;
+use crate::services::browser_driver::BrowserDriver;
+use crate::services::captcha::CaptchaSolver;
+
+/// Upper bound on the HTML sent to the model, keeps the prompt cheap
+const MAX_HTML_CHARS: usize = 30_000;
+
+/// Blocks that carry no useful structure for locating form elements
+const STRIPPED_BLOCKS: &[(&str, &str)] = &[
+    ("<script", "</script>"),
+    ("<style", "</style>"),
+    ("<svg", "</svg>"),
+    ("<!--", "-->"),
+];
+
+/// Human description of a selector field for the repair prompt
+fn describe_field(field: &str) -> Option<&'static str> {
+    match field {
+        "invoice_input" => Some("the text input where the invoice lookup code (mã tra cứu) is typed"),
+        "captcha_image" => Some("the captcha image shown next to the lookup form"),
+        "captcha_input" => Some("the text input where the captcha answer is typed"),
+        "submit_button" => Some("the button that submits the invoice lookup form"),
+        "download_link" => Some("the link that downloads the invoice PDF file"),
+        "xml_download_link" => Some("the link that downloads the invoice XML file"),
+        "converted_download_link" => {
+            Some("the link that downloads the converted, printable invoice PDF (hóa đơn chuyển đổi)")
+        }
+        _ => None,
+    }
+}
+
+/// Ask the LLM for a new selector for `field` based on the current page,
+/// validate it against the live page and register it on the browser
+///
+/// # Returns
+/// The working selector
+pub fn repair_selector(
+    solver: &CaptchaSolver,
+    browser: &impl BrowserDriver,
+    field: &str,
+) -> Result<String, AppError> {
+    let description = describe_field(field)
+        .ok_or_else(|| AppError::ConfigError(format!("Unknown selector field: {}", field)))?;
+
+    let html = strip_html(&browser.page_html()?);
+
+    let prompt = format!(
+        "The following HTML is from a Vietnamese e-invoice lookup page. \
+Give a CSS selector that uniquely matches {}. \
+Return ONLY the CSS selector, nothing else. No explanations, no quotes, no code fences.\n\n{}",
+        description, html
+    );
+
+    let answer = solver.ask_blocking(&prompt, 100)?;
+
+    let selector = clean_selector(&answer).ok_or_else(|| {
+        AppError::ElementNotFound(format!("No selector suggested for {}", field))
+    })?;
+
+    if !browser.element_exists(&selector) {
+        return Err(AppError::ElementNotFound(format!(
+            "Suggested selector '{}' for {} does not match the page",
+            selector, field
+        )));
+    }
+
+    browser.add_selector_override(field, &selector);
+
+    Ok(selector)
+}
+
+/// Remove scripts, styles, inline SVG and comments, collapse whitespace and
+/// truncate so the page fits in a prompt
+fn strip_html(html: &str) -> String {
+    // ASCII lowercasing keeps byte offsets identical to the original
+    let lower = html.to_ascii_lowercase();
+    let mut stripped = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while pos < html.len() {
+        let next_block = STRIPPED_BLOCKS
+            .iter()
+            .filter_map(|(open, close)| lower[pos..].find(open).map(|idx| (pos + idx, *close)))
+            .min_by_key(|(idx, _)| *idx);
+
+        match next_block {
+            Some((start, close)) => {
+                stripped.push_str(&html[pos..start]);
+                pos = match lower[start..].find(close) {
+                    Some(end) => start + end + close.len(),
+                    None => html.len(),
+                };
+            }
+            None => {
+                stripped.push_str(&html[pos..]);
+                break;
+            }
+        }
+    }
+
+    let collapsed = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed.chars().take(MAX_HTML_CHARS).collect()
+}
+
+/// Extract the selector from a model answer, tolerating code fences and quotes
+fn clean_selector(answer: &str) -> Option<String> {
+    answer
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("```"))
+        .map(|line| line.trim_matches(|c: char| c == '`' || c == '"').trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html() {
+        let html = "<html><head><script>var a = '<b>';</script><STYLE>p {}</STYLE></head>\n\
+<body><!-- note --><form>  <input id=\"strFkey\">  </form></body></html>";
+        assert_eq!(
+            strip_html(html),
+            "<html><head></head> <body><form> <input id=\"strFkey\"> </form></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_clean_selector() {
+        assert_eq!(clean_selector("#strFkey").as_deref(), Some("#strFkey"));
+        assert_eq!(
+            clean_selector("```css\ninput[name='captch']\n```").as_deref(),
+            Some("input[name='captch']")
+        );
+        assert_eq!(clean_selector("`#captch`").as_deref(), Some("#captch"));
+        assert_eq!(clean_selector("  \n"), None);
+    }
+}
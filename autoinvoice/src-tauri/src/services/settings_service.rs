@@ -0,0 +1,109 @@
+use serde_json::Value;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+
+use crate::commands::settings::Settings;
+use crate::error::AppError;
+use crate::events::{self, SettingsChangedEvent};
+use crate::services::database::Database;
+
+/// Saved settings, read from SQLite once and kept in memory; every write
+/// goes through here so running components subscribed to the channel see
+/// the change
+pub struct SettingsService {
+    db: Arc<Database>,
+    current: watch::Sender<Settings>,
+}
+
+impl SettingsService {
+    pub fn new(db: Arc<Database>) -> Result<Self, AppError> {
+        let (current, _) = watch::channel(db.get_settings()?);
+        Ok(Self { db, current })
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.borrow().clone()
+    }
+
+    /// Receiver that wakes up whenever settings are saved
+    pub fn subscribe(&self) -> watch::Receiver<Settings> {
+        self.current.subscribe()
+    }
+
+    pub fn save(&self, settings: &Settings) -> Result<(), AppError> {
+        self.db.save_settings(settings)?;
+        self.reload()
+    }
+
+    pub fn set_download_directory(&self, download_directory: &str) -> Result<(), AppError> {
+        self.db.set_download_directory(download_directory)?;
+        self.reload()
+    }
+
+    pub fn set_sftp_host_key_fingerprint(&self, fingerprint: &str) -> Result<(), AppError> {
+        self.db.set_sftp_host_key_fingerprint(fingerprint)?;
+        self.reload()
+    }
+
+    /// Read the settings again, as stored, and tell the subscribers
+    fn reload(&self) -> Result<(), AppError> {
+        self.current.send_replace(self.db.get_settings()?);
+        Ok(())
+    }
+}
+
+/// Names of the settings that differ; values are left out so the API key never
+/// ends up in the audit log or an event
+pub fn changed_fields(previous: &Settings, current: &Settings) -> Vec<String> {
+    let (Ok(Value::Object(previous)), Ok(Value::Object(current))) =
+        (serde_json::to_value(previous), serde_json::to_value(current))
+    else {
+        return Vec::new();
+    };
+
+    current
+        .iter()
+        .filter(|(key, value)| previous.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// Tell the frontend which settings changed, whoever saved them
+pub fn spawn_notifier(app: AppHandle, mut settings: watch::Receiver<Settings>) {
+    tauri::async_runtime::spawn(async move {
+        let mut previous = settings.borrow_and_update().clone();
+        while settings.changed().await.is_ok() {
+            let current = settings.borrow_and_update().clone();
+            let changed = changed_fields(&previous, &current);
+            if !changed.is_empty() {
+                let _ = app.emit(events::SETTINGS_CHANGED, SettingsChangedEvent { changed });
+            }
+            previous = current;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_fields() {
+        let previous = Settings {
+            invoice_delay_secs: 2,
+            openai_api_key: "sk-old".to_string(),
+            ..Settings::default()
+        };
+        let current = Settings {
+            invoice_delay_secs: 5,
+            openai_api_key: "sk-new".to_string(),
+            ..Settings::default()
+        };
+
+        let mut changed = changed_fields(&previous, &current);
+        changed.sort();
+        assert_eq!(changed, vec!["invoice_delay_secs", "openai_api_key"]);
+        assert!(changed_fields(&current, &current).is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use std::fs::File;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use ssh2::{HashType, Session, Sftp};
+
+use crate::error::AppError;
+use crate::services::storage::entry_name;
+use crate::services::zip_package::write_zip;
+use crate::status::impl_setting_parse;
+
+pub const DEFAULT_SFTP_PORT: u16 = 22;
+
+/// How long connecting and each SSH operation may take
+const SFTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a finished batch is handed over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum SftpMode {
+    /// One folder per batch with the PDFs inside
+    #[default]
+    Folder,
+    /// One `<batch id>.zip` per batch
+    Zip,
+}
+
+impl SftpMode {
+    pub const ALL: &'static [SftpMode] = &[SftpMode::Folder, SftpMode::Zip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SftpMode::Folder => "folder",
+            SftpMode::Zip => "zip",
+        }
+    }
+}
+
+impl_setting_parse!(SftpMode);
+
+/// Server finished batches are delivered to, e.g. an accounting firm's drop box
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct SftpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// Used when no private key is set
+    pub password: String,
+    /// Path to an OpenSSH private key file
+    pub private_key_path: String,
+    /// Directory on the server batches are put in
+    pub remote_dir: String,
+    pub mode: SftpMode,
+    /// Expected host key, as printed by `ssh-keygen -l` ("SHA256:..."); when
+    /// empty, the key seen on the first connection is pinned before logging in
+    pub host_key_fingerprint: String,
+}
+
+impl SftpConfig {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.host.trim().is_empty() {
+            return Err(AppError::ConfigError("SFTP host is not set".to_string()));
+        }
+        if self.username.trim().is_empty() {
+            return Err(AppError::ConfigError("SFTP username is not set".to_string()));
+        }
+        if !self.private_key_path.is_empty() && !Path::new(&self.private_key_path).is_file() {
+            return Err(AppError::ConfigError(format!(
+                "SSH private key not found: {}",
+                self.private_key_path
+            )));
+        }
+        Ok(())
+    }
+
+    fn port(&self) -> u16 {
+        if self.port == 0 {
+            DEFAULT_SFTP_PORT
+        } else {
+            self.port
+        }
+    }
+}
+
+/// Where a delivered batch ended up
+pub struct SftpDelivery {
+    pub remote_path: String,
+    /// Host key the server presented
+    pub host_key_fingerprint: String,
+    /// The key was seen for the first time and has just been pinned
+    pub pinned_host_key: bool,
+}
+
+/// Push `files` of `batch_id` to the server, blocking until done
+///
+/// With no host key configured, `pin_host_key` is given the one the server
+/// presents and must store it before any credentials are sent; an error
+/// aborts the connection. `on_file` is called before each upload with the
+/// file's path in the batch, its 0-based index and the number of uploads.
+pub fn deliver_batch(
+    config: &SftpConfig,
+    batch_id: &str,
+    batch_dir: &Path,
+    files: &[String],
+    pin_host_key: impl FnOnce(&str) -> Result<(), AppError>,
+    mut on_file: impl FnMut(&str, usize, usize),
+) -> Result<SftpDelivery, AppError> {
+    let pinned_host_key = config.host_key_fingerprint.trim().is_empty();
+    let (session, host_key_fingerprint) = connect(config, pin_host_key)?;
+    let sftp = session
+        .sftp()
+        .map_err(|e| AppError::UploadError(format!("SFTP subsystem unavailable: {}", e)))?;
+
+    let remote_dir = match config.remote_dir.trim_end_matches('/') {
+        "" if config.remote_dir.starts_with('/') => "/",
+        dir => dir,
+    };
+    create_dir_all(&sftp, remote_dir)?;
+
+    let remote_path = match config.mode {
+        SftpMode::Zip => {
+            let zip_name = format!("{}.zip", batch_id);
+            let local_zip = std::env::temp_dir().join(&zip_name);
+            let remote_zip = join_remote(remote_dir, &zip_name);
+
+            on_file(&zip_name, 0, 1);
+            let uploaded = write_zip(&local_zip, batch_dir, files, None)
+                .and_then(|_| upload(&sftp, &local_zip, &remote_zip));
+            let _ = std::fs::remove_file(&local_zip);
+            uploaded?;
+            remote_zip
+        }
+        SftpMode::Folder => {
+            let remote_folder = join_remote(remote_dir, batch_id);
+            create_dir_all(&sftp, &remote_folder)?;
+
+            for (idx, file_path) in files.iter().enumerate() {
+                let name = entry_name(batch_dir, file_path);
+                on_file(&name, idx, files.len());
+                if let Some((folder, _)) = name.rsplit_once('/') {
+                    create_dir_all(&sftp, &join_remote(&remote_folder, folder))?;
+                }
+                upload(&sftp, Path::new(file_path), &join_remote(&remote_folder, &name))?;
+            }
+            remote_folder
+        }
+    };
+
+    Ok(SftpDelivery {
+        remote_path,
+        host_key_fingerprint,
+        pinned_host_key,
+    })
+}
+
+fn connect(
+    config: &SftpConfig,
+    pin_host_key: impl FnOnce(&str) -> Result<(), AppError>,
+) -> Result<(Session, String), AppError> {
+    let address = (config.host.trim(), config.port())
+        .to_socket_addrs()
+        .map_err(|e| AppError::UploadError(format!("Cannot resolve {}: {}", config.host, e)))?
+        .next()
+        .ok_or_else(|| AppError::UploadError(format!("Cannot resolve {}", config.host)))?;
+    let tcp = TcpStream::connect_timeout(&address, SFTP_TIMEOUT)
+        .map_err(|e| AppError::UploadError(format!("Cannot connect to {}: {}", address, e)))?;
+
+    let mut session = Session::new()
+        .map_err(|e| AppError::UploadError(format!("Failed to start SSH session: {}", e)))?;
+    session.set_timeout(SFTP_TIMEOUT.as_millis() as u32);
+    session.set_tcp_stream(tcp);
+    session
+        .handshake()
+        .map_err(|e| AppError::UploadError(format!("SSH handshake failed: {}", e)))?;
+
+    let fingerprint = session
+        .host_key_hash(HashType::Sha256)
+        .map(|hash| {
+            format!(
+                "SHA256:{}",
+                base64::engine::general_purpose::STANDARD_NO_PAD.encode(hash)
+            )
+        })
+        .ok_or_else(|| AppError::UploadError("Server sent no host key".to_string()))?;
+    let expected = config.host_key_fingerprint.trim();
+    if expected.is_empty() {
+        // Trust on first use: the key is stored before the password or key
+        // is offered, so a later impostor is refused
+        pin_host_key(&fingerprint)?;
+    } else if expected != fingerprint {
+        return Err(AppError::UploadError(format!(
+            "Host key of {} changed: expected {}, got {}",
+            config.host, expected, fingerprint
+        )));
+    }
+
+    let username = config.username.trim();
+    let authenticated = if config.private_key_path.is_empty() {
+        session.userauth_password(username, &config.password)
+    } else {
+        // A non-empty password doubles as the key passphrase
+        let passphrase = Some(config.password.as_str()).filter(|p| !p.is_empty());
+        session.userauth_pubkey_file(username, None, Path::new(&config.private_key_path), passphrase)
+    };
+    authenticated
+        .map_err(|e| AppError::UploadError(format!("SFTP login as {} failed: {}", username, e)))?;
+
+    Ok((session, fingerprint))
+}
+
+/// `mkdir -p` on the server
+fn create_dir_all(sftp: &Sftp, remote_dir: &str) -> Result<(), AppError> {
+    let mut path = String::new();
+    if remote_dir.starts_with('/') {
+        path.push('/');
+    }
+
+    for part in remote_dir.split('/').filter(|part| !part.is_empty()) {
+        path = join_remote(&path, part);
+        if sftp.stat(Path::new(&path)).is_err() {
+            sftp.mkdir(Path::new(&path), 0o755).map_err(|e| {
+                AppError::UploadError(format!("Cannot create {} on the server: {}", path, e))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn upload(sftp: &Sftp, local: &Path, remote: &str) -> Result<(), AppError> {
+    let mut source = File::open(local)
+        .map_err(|e| AppError::IoError(format!("Failed to read {}: {}", local.display(), e)))?;
+    let mut target = sftp
+        .create(Path::new(remote))
+        .map_err(|e| AppError::UploadError(format!("Cannot write {} on the server: {}", remote, e)))?;
+
+    io::copy(&mut source, &mut target)
+        .map_err(|e| AppError::UploadError(format!("Upload of {} failed: {}", remote, e)))?;
+    Ok(())
+}
+
+fn join_remote(dir: &str, name: &str) -> String {
+    match dir {
+        "" => name.to_string(),
+        dir if dir.ends_with('/') => format!("{}{}", dir, name),
+        dir => format!("{}/{}", dir, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip_and_remote_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let files: Vec<String> = ["a.pdf", "b.pdf"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, name.as_bytes()).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+
+        let zip_path = dir.join("batch.zip");
+        write_zip(&zip_path, dir, &files, None).unwrap();
+        let archive = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["a.pdf", "b.pdf"]);
+
+        assert_eq!(join_remote("/", "batch"), "/batch");
+        assert_eq!(join_remote("upload", "batch"), "upload/batch");
+        assert_eq!(join_remote("", "batch"), "batch");
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::services::database::Database;
+use crate::status::{InvoiceStatus, Portal};
+
+/// How long the writer waits for more changes before writing what it has
+const WRITE_WINDOW: Duration = Duration::from_millis(50);
+
+/// Most changes written in one transaction
+const MAX_WRITES_PER_TRANSACTION: usize = 64;
+
+/// A best-effort change to an invoice row made while it downloads
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvoiceWrite {
+    Status {
+        invoice_id: String,
+        status: InvoiceStatus,
+        error: Option<String>,
+        file_path: Option<String>,
+    },
+    /// Portal that served the PDF
+    Portal { invoice_id: String, portal: Portal },
+    /// How long the download took and how many captchas it needed
+    Metrics {
+        invoice_id: String,
+        duration_ms: u64,
+        captcha_attempts: u32,
+    },
+}
+
+enum Message {
+    Write(InvoiceWrite),
+    /// Answered once every change sent before it is written
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the invoice changes of a batch from a background thread, a few
+/// dozen per transaction, so workers flipping statuses do not each wait on
+/// the connection mutex. Changes keep their order; the thread ends once
+/// every clone of the writer is dropped and the queue is written.
+pub struct StatusWriter {
+    sender: Sender<Message>,
+}
+
+impl StatusWriter {
+    pub fn spawn(db: Arc<Database>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || write_loop(&db, receiver));
+        Self { sender }
+    }
+
+    pub fn write(&self, write: InvoiceWrite) {
+        let _ = self.sender.send(Message::Write(write));
+    }
+
+    /// Wait until every change queued so far is in the database, e.g.
+    /// before counting the batch's invoices
+    pub async fn flush(&self) {
+        let (ack, done) = oneshot::channel();
+        if self.sender.send(Message::Flush(ack)).is_ok() {
+            let _ = done.await;
+        }
+    }
+}
+
+fn write_loop(db: &Database, receiver: Receiver<Message>) {
+    while let Ok(first) = receiver.recv() {
+        let deadline = Instant::now() + WRITE_WINDOW;
+        let mut writes = Vec::new();
+        let mut flushes = Vec::new();
+        let mut next = Some(first);
+
+        while let Some(message) = next.take() {
+            match message {
+                Message::Write(write) => writes.push(write),
+                Message::Flush(ack) => {
+                    flushes.push(ack);
+                    break;
+                }
+            }
+            if writes.len() >= MAX_WRITES_PER_TRANSACTION {
+                break;
+            }
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message) => next = Some(message),
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {}
+            }
+        }
+
+        // History is best-effort and never fails the download
+        if !writes.is_empty() {
+            let _ = db.apply_invoice_writes(&writes);
+        }
+        for ack in flushes {
+            let _ = ack.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::history::{DownloadBatch, HistoryInvoice};
+    use crate::status::BatchStatus;
+
+    #[tokio::test]
+    async fn test_writes_are_flushed_in_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let db = Arc::new(Database::new(dir.to_path_buf()).unwrap());
+        db.create_batch(&DownloadBatch {
+            id: "b1".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            total_count: 1,
+            success_count: 0,
+            failed_count: 0,
+            download_directory: dir.to_string_lossy().to_string(),
+            status: BatchStatus::Running,
+            parent_id: None,
+            effective_config: None,
+            name: None,
+            source_file: None,
+            source_path: None,
+            source_hash: None,
+        })
+        .unwrap();
+        db.create_invoice(&HistoryInvoice {
+            id: "i1".to_string(),
+            batch_id: "b1".to_string(),
+            code: "ABC123".to_string(),
+            status: InvoiceStatus::Pending,
+            error: None,
+            file_path: None,
+            downloaded_at: None,
+            repro_path: None,
+            portal: None,
+            remote_url: None,
+            signature_status: None,
+            note: None,
+        })
+        .unwrap();
+
+        let writer = StatusWriter::spawn(db.clone());
+        for status in [InvoiceStatus::Downloading, InvoiceStatus::Failed, InvoiceStatus::Success] {
+            writer.write(InvoiceWrite::Status {
+                invoice_id: "i1".to_string(),
+                status,
+                error: None,
+                file_path: Some("/tmp/ABC123.pdf".to_string()),
+            });
+        }
+        writer.write(InvoiceWrite::Portal {
+            invoice_id: "i1".to_string(),
+            portal: Portal::TaxAuthority,
+        });
+        writer.flush().await;
+
+        let invoice = db.get_invoice("i1").unwrap().unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Success);
+        assert_eq!(invoice.portal, Some(Portal::TaxAuthority));
+        assert!(invoice.downloaded_at.is_some());
+
+        drop(writer);
+    }
+}
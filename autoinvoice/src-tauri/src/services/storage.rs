@@ -0,0 +1,381 @@
+use std::path::{Component, Path};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+
+use crate::error::AppError;
+
+/// Region used when the settings leave it empty; MinIO accepts any region
+pub const DEFAULT_S3_REGION: &str = "us-east-1";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where invoices are uploaded in an S3-compatible store (AWS S3, MinIO, ...)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct S3Config {
+    /// Service URL, e.g. `https://s3.ap-southeast-1.amazonaws.com` or a MinIO server
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Key prefix objects are stored under, without leading or trailing slashes
+    pub prefix: String,
+}
+
+impl S3Config {
+    /// Check the settings are complete enough to upload
+    pub fn validate(&self) -> Result<(), AppError> {
+        let endpoint = url::Url::parse(&self.endpoint)
+            .map_err(|_| AppError::ConfigError(format!("Invalid S3 endpoint: {}", self.endpoint)))?;
+        if !matches!(endpoint.scheme(), "http" | "https") || endpoint.host_str().is_none() {
+            return Err(AppError::ConfigError(format!(
+                "Invalid S3 endpoint: {}",
+                self.endpoint
+            )));
+        }
+
+        let missing = [
+            ("bucket", &self.bucket),
+            ("access key", &self.access_key),
+            ("secret key", &self.secret_key),
+        ]
+        .into_iter()
+        .find(|(_, value)| value.trim().is_empty());
+
+        match missing {
+            Some((name, _)) => Err(AppError::ConfigError(format!("S3 {} is not set", name))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Uploads files to one bucket with AWS Signature V4, using path-style URLs so
+/// MinIO and other self-hosted servers work without DNS per bucket
+#[derive(Clone)]
+pub struct S3Storage {
+    config: S3Config,
+    client: reqwest::Client,
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config, client: reqwest::Client) -> Self {
+        Self { config, client }
+    }
+
+    /// Object key for a file of `batch_id`: `<prefix>/<batch_id>/<file name>`
+    pub fn object_key(&self, batch_id: &str, file_path: &str) -> String {
+        let file_name = file_name(file_path);
+
+        [self.config.prefix.trim_matches('/'), batch_id, &file_name]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// URL of the object stored under `key`
+    pub fn object_url(&self, key: &str) -> String {
+        format!(
+            "{}{}",
+            self.config.endpoint.trim_end_matches('/'),
+            self.canonical_path(key)
+        )
+    }
+
+    /// Upload the file at `file_path` under `key`
+    ///
+    /// # Returns
+    /// The object URL
+    pub async fn upload_file(&self, key: &str, file_path: &str) -> Result<String, AppError> {
+        let body = tokio::fs::read(file_path)
+            .await
+            .map_err(|e| AppError::IoError(format!("Failed to read {}: {}", file_path, e)))?;
+
+        let url = self.object_url(key);
+        let host = host_header(&self.config.endpoint)?;
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            self.canonical_path(key),
+            host,
+            payload_hash,
+            amz_date,
+            SIGNED_HEADERS,
+            payload_hash
+        );
+        let authorization = self.authorization(&amz_date, &canonical_request);
+
+        let response = self
+            .client
+            .put(&url)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("x-amz-date", &amz_date)
+            .header("authorization", authorization)
+            .header("content-type", content_type(file_path))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| AppError::UploadError(format!("S3 upload of {} failed: {}", key, e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let detail = response.text().await.unwrap_or_default();
+            return Err(AppError::UploadError(format!(
+                "S3 rejected {} with {}: {}",
+                key,
+                status,
+                detail.chars().take(300).collect::<String>()
+            )));
+        }
+
+        Ok(url)
+    }
+
+    /// `/<bucket>/<key>` with every segment URI-encoded as SigV4 expects
+    fn canonical_path(&self, key: &str) -> String {
+        let mut path = format!("/{}", uri_encode(&self.config.bucket));
+        for segment in key.split('/') {
+            path.push('/');
+            path.push_str(&uri_encode(segment));
+        }
+        path
+    }
+
+    fn region(&self) -> &str {
+        match self.config.region.trim() {
+            "" => DEFAULT_S3_REGION,
+            region => region,
+        }
+    }
+
+    fn authorization(&self, amz_date: &str, canonical_request: &str) -> String {
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region());
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = signing_key(&self.config.secret_key, date, self.region(), "s3");
+        let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key, scope, SIGNED_HEADERS, signature
+        )
+    }
+}
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// SigV4 key for one day, region and service
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+/// Percent-encode everything but the unreserved characters of RFC 3986
+pub fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Host header value, with the port when it is not the scheme's default
+fn host_header(endpoint: &str) -> Result<String, AppError> {
+    let url = url::Url::parse(endpoint)
+        .map_err(|_| AppError::ConfigError(format!("Invalid S3 endpoint: {}", endpoint)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::ConfigError(format!("Invalid S3 endpoint: {}", endpoint)))?;
+
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+fn content_type(file_path: &str) -> &'static str {
+    match Path::new(file_path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("pdf") => "application/pdf",
+        Some(ext) if ext.eq_ignore_ascii_case("xml") => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A WebDAV folder batches are uploaded to, e.g. a Nextcloud
+/// `https://cloud.example.com/remote.php/dav/files/<user>/Invoices`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct WebDavConfig {
+    pub url: String,
+    pub username: String,
+    /// App password; never the account password
+    pub password: String,
+}
+
+impl WebDavConfig {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let url = url::Url::parse(&self.url)
+            .map_err(|_| AppError::ConfigError(format!("Invalid WebDAV URL: {}", self.url)))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return Err(AppError::ConfigError(format!("Invalid WebDAV URL: {}", self.url)));
+        }
+        if self.username.trim().is_empty() {
+            return Err(AppError::ConfigError("WebDAV username is not set".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Uploads batches as folders of a WebDAV collection
+#[derive(Clone)]
+pub struct WebDavStorage {
+    config: WebDavConfig,
+    client: reqwest::Client,
+}
+
+impl WebDavStorage {
+    pub fn new(config: WebDavConfig, client: reqwest::Client) -> Self {
+        Self { config, client }
+    }
+
+    /// Create the configured folder and `name` inside it, unless they exist
+    ///
+    /// # Returns
+    /// URL of the batch folder, with a trailing slash
+    pub async fn ensure_folder(&self, name: &str) -> Result<String, AppError> {
+        let base = format!("{}/", self.config.url.trim_end_matches('/'));
+        let folder = format!("{}{}/", base, uri_encode(name));
+
+        for url in [&base, &folder] {
+            let response = self
+                .client
+                .request(reqwest::Method::from_bytes(b"MKCOL").unwrap(), url.as_str())
+                .basic_auth(&self.config.username, Some(&self.config.password))
+                .send()
+                .await
+                .map_err(|e| AppError::UploadError(format!("WebDAV MKCOL {} failed: {}", url, e)))?;
+
+            // 405: the collection already exists
+            let status = response.status();
+            if !status.is_success() && status != reqwest::StatusCode::METHOD_NOT_ALLOWED {
+                return Err(AppError::UploadError(format!(
+                    "WebDAV refused to create {}: {}",
+                    url, status
+                )));
+            }
+        }
+
+        Ok(folder)
+    }
+
+    /// Upload a file into a folder returned by `ensure_folder`
+    pub async fn upload_file(&self, folder: &str, file_path: &str) -> Result<String, AppError> {
+        let body = tokio::fs::read(file_path)
+            .await
+            .map_err(|e| AppError::IoError(format!("Failed to read {}: {}", file_path, e)))?;
+        let url = format!("{}{}", folder, uri_encode(&file_name(file_path)));
+
+        self.client
+            .put(&url)
+            .basic_auth(&self.config.username, Some(&self.config.password))
+            .header("content-type", content_type(file_path))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| AppError::UploadError(format!("WebDAV upload to {} failed: {}", url, e)))?
+            .error_for_status()
+            .map_err(|e| AppError::UploadError(format!("WebDAV rejected {}: {}", url, e)))?;
+
+        Ok(url)
+    }
+}
+
+/// Last component of `file_path`, used as the remote file name
+pub fn file_name(file_path: &str) -> String {
+    Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_path.to_string())
+}
+
+/// `/`-separated path of `file_path` in a delivered batch: below `batch_dir`,
+/// keeping the folders of the folder template, or its file name when it is
+/// not in that folder, e.g. the manifest
+pub fn entry_name(batch_dir: &Path, file_path: &str) -> String {
+    let parts: Option<Vec<String>> = Path::new(file_path)
+        .strip_prefix(batch_dir)
+        .ok()
+        .and_then(|relative| {
+            relative
+                .components()
+                .map(|part| match part {
+                    Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect()
+        });
+    match parts {
+        Some(parts) if !parts.is_empty() => parts.join("/"),
+        _ => file_name(file_path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_s3_signing_and_keys() {
+        // Signing key example from the AWS Signature V4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+
+        let storage = S3Storage::new(
+            S3Config {
+                endpoint: "http://localhost:9000/".to_string(),
+                bucket: "invoices".to_string(),
+                prefix: "/autoinvoice/".to_string(),
+                ..S3Config::default()
+            },
+            reqwest::Client::new(),
+        );
+        let key = storage.object_key("batch-1", "/tmp/out/C25TLK 0019654.pdf");
+        assert_eq!(key, "autoinvoice/batch-1/C25TLK 0019654.pdf");
+        assert_eq!(
+            storage.object_url(&key),
+            "http://localhost:9000/invoices/autoinvoice/batch-1/C25TLK%200019654.pdf"
+        );
+        assert_eq!(host_header("http://localhost:9000").unwrap(), "localhost:9000");
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+/// Public invoice lookup of the General Department of Taxation
+pub const DEFAULT_TAX_PORTAL_URL: &str = "https://hoadondientu.gdt.gov.vn/tra-cuu/tra-cuu-hoa-don";
+
+/// Selectors for the tax-authority lookup form, keyed like `browser::selectors::all()`
+pub mod selectors {
+    /// Seller tax code (MST người bán)
+    pub const SELLER_TAX_CODE_INPUT: &[&str] = &[
+        "input[name='nbmst']",
+        "#nbmst",
+        "input[placeholder*='Mã số thuế']",
+    ];
+
+    /// Lookup code issued by the invoice provider
+    pub const INVOICE_INPUT: &[&str] = &[
+        "input[name='mtc']",
+        "#mtc",
+        "input[placeholder*='Mã tra cứu']",
+    ];
+
+    /// Invoice series (ký hiệu hóa đơn)
+    pub const INVOICE_SERIES_INPUT: &[&str] = &[
+        "input[name='khhdon']",
+        "#khhdon",
+        "input[placeholder*='Ký hiệu']",
+    ];
+
+    /// Invoice number (số hóa đơn)
+    pub const INVOICE_NUMBER_INPUT: &[&str] = &[
+        "input[name='shdon']",
+        "#shdon",
+        "input[placeholder*='Số hóa đơn']",
+    ];
+
+    pub const CAPTCHA_IMAGE: &[&str] = &[
+        "img[alt*='captcha' i]",
+        "div.captcha img",
+        "div.captcha svg",
+    ];
+
+    pub const CAPTCHA_INPUT: &[&str] = &[
+        "input[name='cvalue']",
+        "#cvalue",
+        "input[placeholder*='mã captcha' i]",
+    ];
+
+    pub const SUBMIT_BUTTON: &[&str] = &["button[type='submit']", "button.ant-btn-primary"];
+
+    pub const DOWNLOAD_LINK: &[&str] = &["a[href*='.pdf']", "a[download]"];
+
+    pub const XML_DOWNLOAD_LINK: &[&str] = &["a[href*='.xml']", "a[href*='xml' i]"];
+
+    pub const ERROR_MESSAGE: &[&str] = &[".ant-message-error", ".ant-form-item-explain-error", ".ant-alert-error"];
+}
+
+/// Selector overrides that point a `VnptBrowser` at the tax-authority form
+pub fn selector_overrides() -> HashMap<String, Vec<String>> {
+    [
+        ("seller_tax_code", selectors::SELLER_TAX_CODE_INPUT),
+        ("invoice_input", selectors::INVOICE_INPUT),
+        ("invoice_series", selectors::INVOICE_SERIES_INPUT),
+        ("invoice_number", selectors::INVOICE_NUMBER_INPUT),
+        ("captcha_image", selectors::CAPTCHA_IMAGE),
+        ("captcha_input", selectors::CAPTCHA_INPUT),
+        ("submit_button", selectors::SUBMIT_BUTTON),
+        ("download_link", selectors::DOWNLOAD_LINK),
+        ("xml_download_link", selectors::XML_DOWNLOAD_LINK),
+        ("error_message", selectors::ERROR_MESSAGE),
+    ]
+    .into_iter()
+    .map(|(field, list)| {
+        (
+            field.to_string(),
+            list.iter().map(|s| s.to_string()).collect(),
+        )
+    })
+    .collect()
+}
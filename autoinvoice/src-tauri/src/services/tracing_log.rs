@@ -0,0 +1,358 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+use crate::error::AppError;
+
+/// Folder, in the app data directory, of the backend log
+pub const LOG_DIR: &str = "logs";
+
+const LOG_FILE: &str = "backend.log";
+
+/// Size past which the log moves to `backend.log.1`, replacing the previous one
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Only the app's own spans and events; the HTTP and CDP crates trace a lot
+const TARGET_PREFIX: &str = "autoinvoice";
+
+thread_local! {
+    /// Spans entered on this thread, innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+struct SpanRecord {
+    metadata: &'static Metadata<'static>,
+    parent: Option<u64>,
+    /// Shared by every span under the same root, e.g. a command and the
+    /// batch it started
+    request_id: String,
+    fields: String,
+    refs: usize,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self { path, file, written })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.written + line.len() as u64 > MAX_LOG_BYTES {
+            std::fs::rename(&self.path, self.path.with_extension("log.1"))?;
+            *self = Self::open(self.path.clone())?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Tracing subscriber that gives every root span, a command call or a
+/// background job, a short request id inherited by the spans under it, and
+/// writes events to the backend log tagged with it
+pub struct RequestTracer {
+    spans: Mutex<HashMap<u64, SpanRecord>>,
+    next_id: AtomicU64,
+    debug: AtomicBool,
+    log: Mutex<Option<LogFile>>,
+}
+
+impl RequestTracer {
+    fn new(log: Option<LogFile>) -> Self {
+        Self {
+            spans: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            debug: AtomicBool::new(false),
+            log: Mutex::new(log),
+        }
+    }
+
+    fn current(&self) -> Option<u64> {
+        ENTERED.with(|entered| entered.borrow().last().copied())
+    }
+
+    fn request_id(&self, span: u64) -> Option<String> {
+        let spans = self.spans.lock().ok()?;
+        spans.get(&span).map(|record| record.request_id.clone())
+    }
+
+    /// `request_id command > stage field=value`, outermost span first
+    fn context(&self, span: Option<u64>) -> (String, String) {
+        let Ok(spans) = self.spans.lock() else {
+            return (String::new(), String::new());
+        };
+        let mut path = Vec::new();
+        let mut next = span;
+        while let Some(record) = next.and_then(|id| spans.get(&id)) {
+            path.push(format!("{}{}", record.metadata.name(), record.fields));
+            next = record.parent;
+        }
+        let request_id = span
+            .and_then(|id| spans.get(&id))
+            .map(|record| record.request_id.clone())
+            .unwrap_or_else(|| "-".to_string());
+        path.reverse();
+        (request_id, path.join(" > "))
+    }
+}
+
+/// Short random id, enough to find a request in a day of logs
+pub fn new_request_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
+/// Fields of a span or event as ` name=value`, its message apart
+#[derive(Default)]
+struct FieldText {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldText {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            name => {
+                let _ = write!(self.fields, " {}={}", name, value);
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+impl Subscriber for RequestTracer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.target().starts_with(TARGET_PREFIX) {
+            // Debug events come and go with `set_log_level`
+            Interest::sometimes()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with(TARGET_PREFIX)
+            && (*metadata.level() <= Level::INFO || self.debug.load(Ordering::Relaxed))
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let parent = if span.is_root() {
+            None
+        } else if span.is_contextual() {
+            self.current()
+        } else {
+            span.parent().map(Id::into_u64)
+        };
+        let request_id = parent
+            .and_then(|parent| self.request_id(parent))
+            .unwrap_or_else(new_request_id);
+        let mut fields = FieldText::default();
+        span.record(&mut fields);
+
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.insert(
+                id,
+                SpanRecord {
+                    metadata: span.metadata(),
+                    parent,
+                    request_id,
+                    fields: fields.fields,
+                    refs: 1,
+                },
+            );
+        }
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = FieldText::default();
+        values.record(&mut fields);
+        if let Some(record) = self.spans.lock().ok().as_mut().and_then(|spans| spans.get_mut(&span.into_u64())) {
+            record.fields.push_str(&fields.fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let span = if event.is_root() {
+            None
+        } else if event.is_contextual() {
+            self.current()
+        } else {
+            event.parent().map(Id::into_u64)
+        };
+        let (request_id, path) = self.context(span);
+        let mut text = FieldText::default();
+        event.record(&mut text);
+
+        let line = format!(
+            "{} {:5} [{}] {}: {}{}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            event.metadata().level(),
+            request_id,
+            path,
+            text.message,
+            text.fields
+        );
+        // The log is best-effort and never fails what it traces
+        if let Some(log) = self.log.lock().ok().as_mut().and_then(|log| log.as_mut()) {
+            let _ = log.write_line(&line);
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(index) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(index);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(record) = self.spans.lock().ok().as_mut().and_then(|spans| spans.get_mut(&span.into_u64())) {
+            record.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let Ok(mut spans) = self.spans.lock() else {
+            return false;
+        };
+        let id = span.into_u64();
+        let closed = match spans.get_mut(&id) {
+            Some(record) => {
+                record.refs -= 1;
+                record.refs == 0
+            }
+            None => false,
+        };
+        if closed {
+            spans.remove(&id);
+        }
+        closed
+    }
+
+    fn current_span(&self) -> Current {
+        let current = self.current().and_then(|id| {
+            let spans = self.spans.lock().ok()?;
+            spans.get(&id).map(|record| Current::new(Id::from_u64(id), record.metadata))
+        });
+        current.unwrap_or_else(Current::none)
+    }
+}
+
+/// Write the app's traces to `<app data>/logs/backend.log`
+pub fn init(app_data_dir: &Path) -> Result<(), AppError> {
+    let dir = app_data_dir.join(LOG_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let log = LogFile::open(dir.join(LOG_FILE))?;
+    tracing::subscriber::set_global_default(RequestTracer::new(Some(log)))
+        .map_err(|e| AppError::IoError(format!("Failed to start the backend log: {}", e)))
+}
+
+/// Request id of the span the caller runs in, for events sent to the
+/// frontend, so what the user sees can be found in the backend log
+pub fn current_request_id() -> Option<String> {
+    tracing::dispatcher::get_default(|dispatch| {
+        let tracer = dispatch.downcast_ref::<RequestTracer>()?;
+        tracer.request_id(tracer.current()?)
+    })
+}
+
+/// Write a message sent to the log viewer to the backend log as well, at
+/// its level
+pub fn trace_message(level: &str, message: &str) {
+    match level {
+        "error" => tracing::error!("{}", message),
+        "warn" => tracing::warn!("{}", message),
+        "debug" => tracing::debug!("{}", message),
+        _ => tracing::info!("{}", message),
+    }
+}
+
+/// Also log debug events, e.g. selector attempts
+pub fn set_debug(debug: bool) {
+    tracing::dispatcher::get_default(|dispatch| {
+        if let Some(tracer) = dispatch.downcast_ref::<RequestTracer>() {
+            tracer.debug.store(debug, Ordering::Relaxed);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::Instrument;
+
+    #[tokio::test]
+    async fn test_request_id_follows_spans() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(LOG_FILE);
+        let tracer = RequestTracer::new(Some(LogFile::open(path.clone()).unwrap()));
+        let _default = tracing::subscriber::set_default(tracer);
+
+        let command = tracing::info_span!("start_download");
+        let request_id = command.in_scope(current_request_id).unwrap();
+        assert_eq!(request_id.len(), 8);
+
+        // Work handed to another task or thread keeps the id of its command
+        let batch = async {
+            let span = tracing::info_span!("download_invoice", code = "ABC123");
+            let _entered = span.enter();
+            tracing::error!("Captcha rejected");
+            current_request_id()
+        };
+        assert_eq!(batch.instrument(command.clone()).await.as_deref(), Some(request_id.as_str()));
+        assert_ne!(tracing::info_span!("get_batches").in_scope(current_request_id), Some(request_id.clone()));
+        assert_eq!(current_request_id(), None);
+
+        // Debug events are left out unless asked for
+        command.in_scope(|| tracing::debug!("Trying selector #captcha"));
+        set_debug(true);
+        command.in_scope(|| tracing::debug!("Trying selector #imgCaptcha"));
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", log);
+        assert!(lines[0].contains(&format!(
+            "ERROR [{}] start_download > download_invoice code=ABC123: Captcha rejected",
+            request_id
+        )));
+        assert!(lines[1].contains("Trying selector #imgCaptcha"));
+    }
+}
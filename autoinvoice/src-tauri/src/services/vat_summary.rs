@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use specta::Type;
+
+use crate::error::AppError;
+use crate::services::invoice_xml::XmlInvoice;
+
+/// VAT rate rows in the order of the declaration appendix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum VatRate {
+    /// Không chịu thuế (KCT)
+    NotTaxable,
+    /// Không kê khai, tính nộp thuế (KKKNT)
+    NotDeclared,
+    Zero,
+    Five,
+    Eight,
+    Ten,
+    /// Any other rate, e.g. `KHAC:3.5%`
+    Other,
+}
+
+impl VatRate {
+    pub const ALL: [VatRate; 7] = [
+        VatRate::NotTaxable,
+        VatRate::NotDeclared,
+        VatRate::Zero,
+        VatRate::Five,
+        VatRate::Eight,
+        VatRate::Ten,
+        VatRate::Other,
+    ];
+
+    /// Classify a `TSuat` value from the invoice XML
+    pub fn parse(tax_rate: &str) -> Self {
+        let rate: String = tax_rate
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_uppercase();
+        match rate.as_str() {
+            "KCT" => VatRate::NotTaxable,
+            "KKKNT" => VatRate::NotDeclared,
+            "0%" => VatRate::Zero,
+            "5%" => VatRate::Five,
+            "8%" => VatRate::Eight,
+            "10%" => VatRate::Ten,
+            _ => VatRate::Other,
+        }
+    }
+}
+
+/// First and last day of a month (`2024-05`) or quarter (`2024-Q2`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VatPeriod {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl VatPeriod {
+    pub fn parse(period: &str) -> Result<Self, AppError> {
+        let invalid = || {
+            AppError::ConfigError(format!(
+                "Invalid period '{}', expected YYYY-MM or YYYY-Qn",
+                period
+            ))
+        };
+        let (year, part) = period.trim().split_once('-').ok_or_else(invalid)?;
+        let year: i32 = year.parse().map_err(|_| invalid())?;
+
+        let (first_month, months) = match part.strip_prefix(['Q', 'q']) {
+            Some(quarter) => match quarter.parse::<u32>() {
+                Ok(quarter @ 1..=4) => (quarter * 3 - 2, 3),
+                _ => return Err(invalid()),
+            },
+            None => match part.parse::<u32>() {
+                Ok(month @ 1..=12) => (month, 1),
+                _ => return Err(invalid()),
+            },
+        };
+
+        let from = NaiveDate::from_ymd_opt(year, first_month, 1).ok_or_else(invalid)?;
+        let next = from
+            .checked_add_months(chrono::Months::new(months))
+            .ok_or_else(invalid)?;
+        Ok(VatPeriod { from, to: next.pred_opt().ok_or_else(invalid)? })
+    }
+}
+
+/// Totals of one VAT rate
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct VatSummaryRow {
+    pub rate: VatRate,
+    pub invoice_count: u32,
+    pub taxable_amount: f64,
+    pub tax_amount: f64,
+}
+
+/// Invoice values of a period grouped by VAT rate, in VND
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct VatSummary {
+    pub period: String,
+    pub from: String,
+    pub to: String,
+    pub invoice_count: u32,
+    pub rows: Vec<VatSummaryRow>,
+    pub total_taxable: f64,
+    pub total_tax: f64,
+    /// Invoices left out: duplicates, or foreign currency without a rate
+    pub skipped: Vec<String>,
+}
+
+/// Group the invoices by VAT rate. Each invoice is counted once, even when
+/// it was downloaded by several batches
+pub fn summarize(period: &str, range: VatPeriod, invoices: &[XmlInvoice]) -> VatSummary {
+    let mut rows: Vec<VatSummaryRow> = VatRate::ALL
+        .iter()
+        .map(|rate| VatSummaryRow {
+            rate: *rate,
+            invoice_count: 0,
+            taxable_amount: 0.0,
+            tax_amount: 0.0,
+        })
+        .collect();
+    let mut seen = HashSet::new();
+    let mut skipped = Vec::new();
+    let mut invoice_count = 0;
+
+    for invoice in invoices {
+        let label = format!("{} {}/{}", invoice.seller.tax_code, invoice.series, invoice.number);
+        if !seen.insert((&invoice.seller.tax_code, &invoice.series, &invoice.number)) {
+            skipped.push(format!("{}: duplicate", label));
+            continue;
+        }
+        let Some(to_vnd) = vnd_rate(invoice) else {
+            skipped.push(format!("{}: {} without an exchange rate", label, invoice.currency));
+            continue;
+        };
+        invoice_count += 1;
+
+        let mut counted = HashSet::new();
+        for (tax_rate, taxable, tax) in rate_amounts(invoice) {
+            let rate = VatRate::parse(&tax_rate);
+            let row = &mut rows[VatRate::ALL.iter().position(|r| *r == rate).unwrap()];
+            row.taxable_amount += taxable * to_vnd;
+            row.tax_amount += tax * to_vnd;
+            if counted.insert(rate) {
+                row.invoice_count += 1;
+            }
+        }
+    }
+
+    for row in &mut rows {
+        row.taxable_amount = row.taxable_amount.round();
+        row.tax_amount = row.tax_amount.round();
+    }
+    VatSummary {
+        period: period.trim().to_string(),
+        from: range.from.to_string(),
+        to: range.to.to_string(),
+        invoice_count,
+        total_taxable: rows.iter().map(|row| row.taxable_amount).sum(),
+        total_tax: rows.iter().map(|row| row.tax_amount).sum(),
+        rows,
+        skipped,
+    }
+}
+
+/// Taxable amount and tax per rate, from the tax breakdown, or from the
+/// lines when the invoice has none
+fn rate_amounts(invoice: &XmlInvoice) -> Vec<(String, f64, f64)> {
+    if !invoice.tax_breakdown.is_empty() {
+        return invoice
+            .tax_breakdown
+            .iter()
+            .map(|rate| (rate.tax_rate.clone(), rate.taxable_amount, rate.tax_amount))
+            .collect();
+    }
+
+    // Promotions and notes carry no value; discounts reduce it
+    let mut amounts: Vec<(String, f64, f64)> = Vec::new();
+    for line in &invoice.lines {
+        let amount = match line.kind {
+            2 | 4 => continue,
+            3 => -line.amount,
+            _ => line.amount,
+        };
+        let tax = amount * percent(&line.tax_rate) / 100.0;
+        match amounts.iter_mut().find(|(rate, _, _)| *rate == line.tax_rate) {
+            Some(entry) => {
+                entry.1 += amount;
+                entry.2 += tax;
+            }
+            None => amounts.push((line.tax_rate.clone(), amount, tax)),
+        }
+    }
+    amounts
+}
+
+/// Numeric rate of `10%` or `KHAC:3.5%`, zero for KCT and KKKNT
+fn percent(tax_rate: &str) -> f64 {
+    let rate = tax_rate.rsplit(':').next().unwrap_or_default();
+    rate.trim().trim_end_matches('%').parse().unwrap_or(0.0)
+}
+
+/// Multiplier to VND, or `None` for a foreign currency without a rate
+fn vnd_rate(invoice: &XmlInvoice) -> Option<f64> {
+    match invoice.currency.trim().to_uppercase().as_str() {
+        "" | "VND" => Some(1.0),
+        _ => invoice.exchange_rate.filter(|rate| *rate > 0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::invoice_xml::{XmlLine, XmlTaxRate};
+
+    fn invoice(number: &str, breakdown: Vec<XmlTaxRate>, lines: Vec<XmlLine>) -> XmlInvoice {
+        let mut invoice = XmlInvoice {
+            series: "C24TAA".to_string(),
+            number: number.to_string(),
+            issued_date: "2024-05-10".to_string(),
+            currency: "VND".to_string(),
+            tax_breakdown: breakdown,
+            lines,
+            ..Default::default()
+        };
+        invoice.seller.tax_code = "0100109106".to_string();
+        invoice
+    }
+
+    fn line(kind: u32, amount: f64, tax_rate: &str) -> XmlLine {
+        XmlLine { kind, amount, tax_rate: tax_rate.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_summarize_by_rate() {
+        let range = VatPeriod::parse("2024-Q2").unwrap();
+        assert_eq!(range.from.to_string(), "2024-04-01");
+        assert_eq!(range.to.to_string(), "2024-06-30");
+        assert!(VatPeriod::parse("2024-13").is_err());
+
+        let invoices = vec![
+            invoice(
+                "1",
+                vec![XmlTaxRate { tax_rate: "10%".to_string(), taxable_amount: 400000.0, tax_amount: 40000.0 }],
+                vec![],
+            ),
+            // No breakdown: grouped from the lines, with the discount subtracted
+            invoice(
+                "2",
+                vec![],
+                vec![line(1, 1000000.0, "8%"), line(3, 100000.0, "8%"), line(1, 50000.0, "KCT")],
+            ),
+            invoice("1", vec![], vec![line(1, 999.0, "10%")]),
+        ];
+
+        let summary = summarize("2024-Q2", range, &invoices);
+        assert_eq!(summary.invoice_count, 2);
+        assert_eq!(summary.skipped.len(), 1);
+
+        let row = |rate| summary.rows.iter().find(|row| row.rate == rate).unwrap();
+        assert_eq!(row(VatRate::Ten).taxable_amount, 400000.0);
+        assert_eq!(row(VatRate::Eight).taxable_amount, 900000.0);
+        assert_eq!(row(VatRate::Eight).tax_amount, 72000.0);
+        assert_eq!(row(VatRate::NotTaxable).invoice_count, 1);
+        assert_eq!(summary.total_tax, 112000.0);
+    }
+}
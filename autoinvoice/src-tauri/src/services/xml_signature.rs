@@ -0,0 +1,522 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use base64::Engine;
+use roxmltree::{Document, Node, NodeId, NodeType};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use specta::Type;
+use x509_cert::der::{Decode, Encode};
+use x509_cert::Certificate;
+
+use crate::error::AppError;
+use crate::status::SignatureStatus;
+
+const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+const C14N: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315";
+const C14N_WITH_COMMENTS: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments";
+const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const EXC_C14N_WITH_COMMENTS: &str = "http://www.w3.org/2001/10/xml-exc-c14n#WithComments";
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+
+/// Outcome of checking one `Signature` element of an invoice
+///
+/// Only the signature itself is checked; the certificate chain is not
+/// matched against a trust store.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct SignatureCheck {
+    /// Party the signature belongs to: "seller", "tax-authority", or the
+    /// name of the element it sits in
+    pub signed_by: String,
+    pub valid: bool,
+    /// Subject of the signing certificate
+    pub signer: Option<String>,
+    pub issuer: Option<String>,
+    /// Signing time the signer recorded, as written in the XML
+    pub signing_time: Option<String>,
+    pub certificate_not_before: Option<String>,
+    pub certificate_not_after: Option<String>,
+    /// Why the signature is not valid
+    pub error: Option<String>,
+}
+
+/// Verify every XML-DSig signature in an invoice XML file
+pub fn verify_file(path: &Path) -> Result<Vec<SignatureCheck>, AppError> {
+    let xml = std::fs::read_to_string(path)
+        .map_err(|e| AppError::XmlError(format!("Failed to read {}: {}", path.display(), e)))?;
+    verify(&xml)
+}
+
+pub fn verify(xml: &str) -> Result<Vec<SignatureCheck>, AppError> {
+    let document = Document::parse(xml)
+        .map_err(|e| AppError::XmlError(format!("Malformed XML: {}", e)))?;
+
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().namespace() == Some(DSIG_NS))
+        .filter(|node| node.tag_name().name() == "Signature")
+        .map(|signature| check_signature(&document, signature))
+        .collect())
+}
+
+/// Status of the invoice as a whole: invalid when any signature is
+pub fn overall_status(checks: &[SignatureCheck]) -> SignatureStatus {
+    if checks.is_empty() {
+        SignatureStatus::Unsigned
+    } else if checks.iter().all(|check| check.valid) {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    }
+}
+
+fn check_signature(document: &Document, signature: Node) -> SignatureCheck {
+    let mut check = SignatureCheck {
+        signed_by: match signature.parent_element().map(|parent| parent.tag_name().name()) {
+            Some("NBan") => "seller".to_string(),
+            Some("CQT") => "tax-authority".to_string(),
+            Some(name) => name.to_string(),
+            None => "unknown".to_string(),
+        },
+        valid: false,
+        signer: None,
+        issuer: None,
+        signing_time: signature
+            .descendants()
+            .find(|node| node.has_tag_name("SigningTime"))
+            .and_then(|node| node.text())
+            .map(|time| time.trim().to_string()),
+        certificate_not_before: None,
+        certificate_not_after: None,
+        error: None,
+    };
+
+    let certificate = dsig_child(signature, "KeyInfo")
+        .into_iter()
+        .flat_map(|key_info| key_info.descendants())
+        .find(|node| node.has_tag_name((DSIG_NS, "X509Certificate")))
+        .ok_or_else(|| "Signature carries no certificate".to_string())
+        .and_then(|node| decode_base64(node.text().unwrap_or_default()))
+        .and_then(|der| {
+            Certificate::from_der(&der).map_err(|e| format!("Unreadable certificate: {}", e))
+        });
+    let certificate = match certificate {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            check.error = Some(e);
+            return check;
+        }
+    };
+
+    let tbs = &certificate.tbs_certificate;
+    check.signer = Some(tbs.subject.to_string());
+    check.issuer = Some(tbs.issuer.to_string());
+    check.certificate_not_before = Some(rfc3339(tbs.validity.not_before.to_system_time()));
+    check.certificate_not_after = Some(rfc3339(tbs.validity.not_after.to_system_time()));
+
+    let signed_while_valid = match check.signing_time.as_deref().and_then(parse_signing_time) {
+        Some(signed_at) => {
+            let signed_at = std::time::SystemTime::from(signed_at);
+            tbs.validity.not_before.to_system_time() <= signed_at
+                && signed_at <= tbs.validity.not_after.to_system_time()
+        }
+        None => true,
+    };
+
+    match verify_signature(document, signature, &certificate) {
+        Ok(()) if !signed_while_valid => {
+            check.error = Some("Certificate was not valid at the signing time".to_string())
+        }
+        Ok(()) => check.valid = true,
+        Err(e) => check.error = Some(e),
+    }
+    check
+}
+
+/// Signing times are usually written without an offset, in Vietnam time
+fn parse_signing_time(value: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(value).ok().or_else(|| {
+        let vietnam = chrono::FixedOffset::east_opt(7 * 3600)?;
+        chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+            .ok()?
+            .and_local_timezone(vietnam)
+            .single()
+    })
+}
+
+/// Check each reference digest, that the references cover the invoice
+/// data, then the signature over `SignedInfo`
+fn verify_signature(
+    document: &Document,
+    signature: Node,
+    certificate: &Certificate,
+) -> Result<(), String> {
+    let signed_info =
+        dsig_child(signature, "SignedInfo").ok_or_else(|| "Signature has no SignedInfo".to_string())?;
+
+    let signed = signed_info
+        .children()
+        .filter(|node| node.has_tag_name((DSIG_NS, "Reference")))
+        .map(|reference| verify_reference(document, signature, reference))
+        .collect::<Result<Vec<_>, _>>()?;
+    if signed.is_empty() {
+        return Err("Signature has no Reference".to_string());
+    }
+
+    // A valid signature over, say, only its own SignatureProperties says
+    // nothing about the invoice
+    let invoice_data = document
+        .descendants()
+        .find(|node| node.has_tag_name("DLHDon"))
+        .unwrap_or_else(|| document.root_element());
+    if !signed
+        .iter()
+        .any(|target| invoice_data.ancestors().any(|node| node.id() == *target))
+    {
+        return Err("Signature does not cover the invoice data".to_string());
+    }
+
+    let c14n_method = dsig_child(signed_info, "CanonicalizationMethod")
+        .and_then(|node| node.attribute("Algorithm"))
+        .unwrap_or(C14N);
+    let canonical = canonicalize(signed_info, canonical_mode(c14n_method)?, None);
+
+    let method = dsig_child(signed_info, "SignatureMethod")
+        .and_then(|node| node.attribute("Algorithm"))
+        .unwrap_or_default();
+    let (hashed, scheme) = match method.rsplit_once('#').map(|(_, name)| name) {
+        Some("rsa-sha1") => (Sha1::digest(&canonical).to_vec(), Pkcs1v15Sign::new::<Sha1>()),
+        Some("rsa-sha256") => (Sha256::digest(&canonical).to_vec(), Pkcs1v15Sign::new::<Sha256>()),
+        Some("rsa-sha512") => (Sha512::digest(&canonical).to_vec(), Pkcs1v15Sign::new::<Sha512>()),
+        _ => return Err(format!("Unsupported signature method: {}", method)),
+    };
+
+    let signature_value = dsig_child(signature, "SignatureValue")
+        .and_then(|node| node.text())
+        .ok_or_else(|| "Signature has no SignatureValue".to_string())
+        .and_then(decode_base64)?;
+    let public_key = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .map_err(|e| e.to_string())
+        .and_then(|der| RsaPublicKey::from_public_key_der(&der).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Certificate key is not a usable RSA key: {}", e))?;
+
+    public_key
+        .verify(scheme, &hashed, &signature_value)
+        .map_err(|_| "Signature does not match the signed content".to_string())
+}
+
+/// Check the digest of the element `reference` points to, returning that element
+fn verify_reference(document: &Document, signature: Node, reference: Node) -> Result<NodeId, String> {
+    let uri = reference.attribute("URI").unwrap_or_default();
+    let target = match uri.strip_prefix('#') {
+        None if uri.is_empty() => document.root_element(),
+        Some(id) => document
+            .descendants()
+            .find(|node| {
+                ["Id", "ID", "id"]
+                    .iter()
+                    .any(|name| node.attribute(*name) == Some(id))
+            })
+            .ok_or_else(|| format!("Signed element #{} is missing", id))?,
+        None => return Err(format!("Unsupported reference: {}", uri)),
+    };
+
+    let mut excluded = None;
+    let mut mode = Mode::default();
+    for transform in dsig_child(reference, "Transforms")
+        .into_iter()
+        .flat_map(|transforms| transforms.children())
+        .filter(|node| node.has_tag_name((DSIG_NS, "Transform")))
+    {
+        match transform.attribute("Algorithm").unwrap_or_default() {
+            ENVELOPED_SIGNATURE => excluded = Some(signature.id()),
+            algorithm => mode = canonical_mode(algorithm)?,
+        }
+    }
+    let canonical = canonicalize(target, mode, excluded);
+
+    let method = dsig_child(reference, "DigestMethod")
+        .and_then(|node| node.attribute("Algorithm"))
+        .unwrap_or_default();
+    let digest = match method.rsplit_once('#').map(|(_, name)| name) {
+        Some("sha1") => Sha1::digest(&canonical).to_vec(),
+        Some("sha256") => Sha256::digest(&canonical).to_vec(),
+        Some("sha512") => Sha512::digest(&canonical).to_vec(),
+        _ => return Err(format!("Unsupported digest method: {}", method)),
+    };
+
+    let expected = dsig_child(reference, "DigestValue")
+        .and_then(|node| node.text())
+        .ok_or_else(|| "Reference has no DigestValue".to_string())
+        .and_then(decode_base64)?;
+    if digest != expected {
+        return Err(format!(
+            "Content signed as {} was changed after signing",
+            if uri.is_empty() { "the document" } else { uri }
+        ));
+    }
+    Ok(target.id())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Mode {
+    exclusive: bool,
+    with_comments: bool,
+}
+
+fn canonical_mode(algorithm: &str) -> Result<Mode, String> {
+    match algorithm {
+        C14N => Ok(Mode { exclusive: false, with_comments: false }),
+        C14N_WITH_COMMENTS => Ok(Mode { exclusive: false, with_comments: true }),
+        EXC_C14N => Ok(Mode { exclusive: true, with_comments: false }),
+        EXC_C14N_WITH_COMMENTS => Ok(Mode { exclusive: true, with_comments: true }),
+        _ => Err(format!("Unsupported transform: {}", algorithm)),
+    }
+}
+
+/// Canonical XML 1.0 (inclusive or exclusive) of the subtree at `node`,
+/// leaving out the `excluded` element
+fn canonicalize(node: Node, mode: Mode, excluded: Option<NodeId>) -> Vec<u8> {
+    let mut out = String::new();
+    write_element(&mut out, node, mode, excluded, &BTreeMap::new());
+    out.into_bytes()
+}
+
+/// Namespaces declared on the output so far, default namespace keyed by `None`
+type Rendered<'a> = BTreeMap<Option<&'a str>, &'a str>;
+
+fn write_element<'a>(
+    out: &mut String,
+    node: Node<'a, '_>,
+    mode: Mode,
+    excluded: Option<NodeId>,
+    rendered: &Rendered<'a>,
+) {
+    let element_prefix = prefix_of(node, node.tag_name().namespace(), false);
+
+    // Namespaces the output needs here: all in scope, or only the ones the
+    // element and its attributes use in exclusive mode
+    let mut wanted: Rendered = BTreeMap::new();
+    if mode.exclusive {
+        wanted.insert(element_prefix, node.tag_name().namespace().unwrap_or_default());
+        for attribute in node.attributes() {
+            if let Some(uri) = attribute.namespace() {
+                wanted.insert(prefix_of(node, Some(uri), true), uri);
+            }
+        }
+    } else {
+        wanted.insert(None, "");
+        for namespace in node.namespaces() {
+            wanted.insert(namespace.name(), namespace.uri());
+        }
+    }
+    wanted.remove(&Some("xml"));
+
+    let mut scope = rendered.clone();
+    let mut declarations = Vec::new();
+    for (prefix, uri) in wanted {
+        let shown = rendered.get(&prefix).copied();
+        let needed = match prefix {
+            None if uri.is_empty() => shown.is_some_and(|shown| !shown.is_empty()),
+            _ => shown != Some(uri),
+        };
+        if needed {
+            declarations.push((prefix, uri));
+            scope.insert(prefix, uri);
+        }
+    }
+
+    let name = qualified(element_prefix, node.tag_name().name());
+    out.push('<');
+    out.push_str(&name);
+    for (prefix, uri) in declarations {
+        match prefix {
+            Some(prefix) => out.push_str(&format!(" xmlns:{}=\"", prefix)),
+            None => out.push_str(" xmlns=\""),
+        }
+        escape_attribute(out, uri);
+        out.push('"');
+    }
+
+    let mut attributes: Vec<_> = node.attributes().collect();
+    attributes.sort_by_key(|attribute| (attribute.namespace().unwrap_or_default(), attribute.name()));
+    for attribute in attributes {
+        let prefix = prefix_of(node, attribute.namespace(), true);
+        out.push(' ');
+        out.push_str(&qualified(prefix, attribute.name()));
+        out.push_str("=\"");
+        escape_attribute(out, attribute.value());
+        out.push('"');
+    }
+    out.push('>');
+
+    for child in node.children() {
+        if Some(child.id()) == excluded {
+            continue;
+        }
+        match child.node_type() {
+            NodeType::Element => write_element(out, child, mode, excluded, &scope),
+            NodeType::Text => escape_text(out, child.text().unwrap_or_default()),
+            NodeType::Comment if mode.with_comments => {
+                out.push_str("<!--");
+                out.push_str(child.text().unwrap_or_default());
+                out.push_str("-->");
+            }
+            NodeType::PI => {
+                if let Some(pi) = child.pi() {
+                    out.push_str("<?");
+                    out.push_str(pi.target);
+                    if let Some(value) = pi.value.filter(|value| !value.is_empty()) {
+                        out.push(' ');
+                        out.push_str(value);
+                    }
+                    out.push_str("?>");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    out.push_str("</");
+    out.push_str(&name);
+    out.push('>');
+}
+
+/// Prefix bound to `uri` on `node`; attributes never use the default namespace
+fn prefix_of<'a>(node: Node<'a, '_>, uri: Option<&str>, attribute: bool) -> Option<&'a str> {
+    let uri = uri?;
+    if uri == "http://www.w3.org/XML/1998/namespace" {
+        return Some("xml");
+    }
+    let mut bound = node.namespaces().filter(|namespace| namespace.uri() == uri);
+    if attribute {
+        bound.find_map(|namespace| namespace.name())
+    } else {
+        bound.next().and_then(|namespace| namespace.name())
+    }
+}
+
+fn qualified(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}:{}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+fn escape_text(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape_attribute(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn dsig_child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name((DSIG_NS, name)))
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, String> {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(compact)
+        .map_err(|e| format!("Invalid base64: {}", e))
+}
+
+fn rfc3339(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Invoice signed by the seller over its `DLHDon` and signing time, with a
+    /// self-signed certificate
+    const SIGNED: &str = include_str!("../../tests/fixtures/signed_invoice.xml");
+
+    fn without_reference(xml: &str, uri: &str) -> String {
+        let start = xml.find(&format!("<Reference URI=\"{}\">", uri)).unwrap();
+        let end = start + xml[start..].find("</Reference>").unwrap() + "</Reference>".len();
+        format!("{}{}", &xml[..start], &xml[end..])
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let xml = r#"<doc xmlns="urn:a" xmlns:b="urn:b"><b:e z="1" a="&lt;&#10;"><!-- c --><f/></b:e></doc>"#;
+        let document = Document::parse(xml).unwrap();
+        let e = document.descendants().find(|node| node.has_tag_name("e")).unwrap();
+
+        let inclusive = canonicalize(e, Mode::default(), None);
+        assert_eq!(
+            String::from_utf8(inclusive).unwrap(),
+            r#"<b:e xmlns="urn:a" xmlns:b="urn:b" a="&lt;&#xA;" z="1"><f></f></b:e>"#
+        );
+
+        let exclusive = canonicalize(e, canonical_mode(EXC_C14N).unwrap(), None);
+        assert_eq!(
+            String::from_utf8(exclusive).unwrap(),
+            r#"<b:e xmlns:b="urn:b" a="&lt;&#xA;" z="1"><f xmlns="urn:a"></f></b:e>"#
+        );
+
+        assert_eq!(overall_status(&[]), SignatureStatus::Unsigned);
+        let unsigned = verify(xml).unwrap();
+        assert!(unsigned.is_empty());
+    }
+
+    #[test]
+    fn test_verify_signed_invoice() {
+        let checks = verify(SIGNED).unwrap();
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].valid, "{:?}", checks[0].error);
+        assert_eq!(checks[0].signed_by, "seller");
+        assert_eq!(checks[0].signing_time.as_deref(), Some("2025-03-01T09:30:00"));
+        assert_eq!(overall_status(&checks), SignatureStatus::Valid);
+
+        let tampered = verify(&SIGNED.replace("<TgTTTBSo>440000<", "<TgTTTBSo>4400000<")).unwrap();
+        assert!(!tampered[0].valid);
+        assert_eq!(
+            tampered[0].error.as_deref(),
+            Some("Content signed as #data was changed after signing")
+        );
+        assert_eq!(overall_status(&tampered), SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_signature_must_cover_invoice_data() {
+        let signing_time_only = verify(&without_reference(SIGNED, "#data")).unwrap();
+        assert!(!signing_time_only[0].valid);
+        assert_eq!(
+            signing_time_only[0].error.as_deref(),
+            Some("Signature does not cover the invoice data")
+        );
+
+        let unreferenced = verify(&without_reference(&without_reference(SIGNED, "#data"), "#proptime")).unwrap();
+        assert!(!unreferenced[0].valid);
+        assert_eq!(unreferenced[0].error.as_deref(), Some("Signature has no Reference"));
+    }
+}
@@ -0,0 +1,165 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use specta::Type;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+use crate::error::AppError;
+use crate::services::storage::entry_name;
+
+/// Shortest password accepted for an encrypted package
+pub const MIN_PASSWORD_CHARS: usize = 8;
+
+/// Length of generated passwords; 20 characters of a 32-letter alphabet is
+/// 100 bits
+const GENERATED_PASSWORD_CHARS: usize = 20;
+
+/// No 0/O or 1/I/L, so a password read out over the phone is not mistyped
+const PASSWORD_ALPHABET: &[u8; 32] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789-";
+
+/// Zip written by `package_batch`
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct BatchPackage {
+    pub path: String,
+    pub file_count: u32,
+    /// Password the zip was encrypted with, when it was generated; it is not
+    /// stored anywhere, so this is the only time it can be shown
+    pub generated_password: Option<String>,
+}
+
+/// Random password for one package
+pub fn generate_password() -> String {
+    let mut bytes = [0u8; GENERATED_PASSWORD_CHARS];
+    OsRng.fill_bytes(&mut bytes);
+    // 32 letters, so masking picks each with the same odds
+    bytes
+        .iter()
+        .map(|byte| PASSWORD_ALPHABET[(byte & 31) as usize] as char)
+        .collect()
+}
+
+/// Check a password chosen by the user is long enough to encrypt with
+pub fn validate_password(password: &str) -> Result<(), AppError> {
+    if password.chars().count() < MIN_PASSWORD_CHARS {
+        return Err(AppError::InvalidArgument {
+            argument: "password".to_string(),
+            reason: format!("must be at least {} characters", MIN_PASSWORD_CHARS),
+        });
+    }
+    Ok(())
+}
+
+/// Zip `files` into `zip_path` under their `entry_name` in `batch_dir`; with
+/// a password, every entry is encrypted with AES-256 (WinZip AE-2), which
+/// 7-Zip, WinRAR and the macOS Archive Utility open
+pub fn write_zip(
+    zip_path: &Path,
+    batch_dir: &Path,
+    files: &[String],
+    password: Option<&str>,
+) -> Result<(), AppError> {
+    let mut zip = ZipWriter::new(File::create(zip_path)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let options = match password {
+        Some(password) => options.with_aes_encryption(AesMode::Aes256, password),
+        None => options,
+    };
+
+    for file_path in files {
+        let name = entry_name(batch_dir, file_path);
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| AppError::IoError(format!("Failed to add {} to zip: {}", name, e)))?;
+        io::copy(&mut File::open(file_path)?, &mut zip)?;
+    }
+
+    zip.finish()
+        .map_err(|e| AppError::IoError(format!("Failed to finalize zip: {}", e)))?
+        .flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::manifest::{BatchManifest, CHECKSUMS_FILE, MANIFEST_FILE};
+    use std::io::Read;
+
+    #[test]
+    fn test_encrypted_zip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let pdf = dir.join("C24TAA0000001_Ab.pdf");
+        std::fs::write(&pdf, b"%PDF-1.4 invoice").unwrap();
+
+        let password = generate_password();
+        assert_eq!(password.len(), GENERATED_PASSWORD_CHARS);
+        assert_ne!(password, generate_password());
+
+        let zip_path = dir.join("batch.zip");
+        write_zip(&zip_path, dir, &[pdf.to_string_lossy().to_string()], Some(&password)).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        assert!(archive.by_name("C24TAA0000001_Ab.pdf").is_err());
+        assert!(archive.by_name_decrypt("C24TAA0000001_Ab.pdf", b"wrong password").is_err());
+        let mut content = Vec::new();
+        archive
+            .by_name_decrypt("C24TAA0000001_Ab.pdf", password.as_bytes())
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"%PDF-1.4 invoice");
+    }
+
+    #[test]
+    fn test_zip_keeps_batch_folders() {
+        let tmp = tempfile::tempdir().unwrap();
+        let batch_dir = tmp.path().join("lo-1");
+        // Two sellers numbering their invoices alike, foldered by seller
+        let sellers = [("C25TAA0000087_Ab", "Cong ty A"), ("C25TBB0000087_Cd", "Cong ty B")];
+        let files: Vec<(&str, String)> = sellers
+            .into_iter()
+            .map(|(code, seller)| {
+                let folder = batch_dir.join(seller);
+                std::fs::create_dir_all(&folder).unwrap();
+                let pdf = folder.join("0000087.pdf");
+                std::fs::write(&pdf, code.as_bytes()).unwrap();
+                (code, pdf.to_string_lossy().to_string())
+            })
+            .collect();
+        let downloaded: Vec<(&str, &str)> =
+            files.iter().map(|(code, path)| (*code, path.as_str())).collect();
+
+        let manifest = BatchManifest::build("lo-1", &batch_dir, &downloaded).unwrap();
+        let mut paths: Vec<String> = files.iter().map(|(_, path)| path.clone()).collect();
+        paths.extend(
+            manifest
+                .write_to(&tmp.path().join("manifest"))
+                .unwrap()
+                .iter()
+                .map(|path| path.to_string_lossy().to_string()),
+        );
+        let zip_path = tmp.path().join("lo-1.zip");
+        write_zip(&zip_path, &batch_dir, &paths, None).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["Cong ty A/0000087.pdf", "Cong ty B/0000087.pdf", CHECKSUMS_FILE, MANIFEST_FILE]
+        );
+        let mut content = String::new();
+        archive.by_name("Cong ty B/0000087.pdf").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "C25TBB0000087_Cd");
+
+        // SHA256SUMS checks the zip as extracted
+        let mut checksums = String::new();
+        archive.by_name(CHECKSUMS_FILE).unwrap().read_to_string(&mut checksums).unwrap();
+        let listed: Vec<&str> =
+            checksums.lines().map(|line| line.split_once("  ").unwrap().1).collect();
+        assert_eq!(listed, vec!["Cong ty A/0000087.pdf", "Cong ty B/0000087.pdf"]);
+    }
+}
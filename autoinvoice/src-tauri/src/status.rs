@@ -0,0 +1,300 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::fmt;
+use std::str::FromStr;
+
+/// Status of a single invoice, stored as kebab-case text in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum InvoiceStatus {
+    Pending,
+    Downloading,
+    Success,
+    Failed,
+    Skipped,
+    Cancelled,
+    /// Needs a manual captcha, left for a later interactive pass
+    NeedsManual,
+    /// The portal has no invoice under the code; the error keeps its message
+    NotFound,
+    /// Given up on by the user, e.g. cancelled by the seller; the note says why
+    Ignored,
+}
+
+impl InvoiceStatus {
+    pub const ALL: &'static [InvoiceStatus] = &[
+        InvoiceStatus::Pending,
+        InvoiceStatus::Downloading,
+        InvoiceStatus::Success,
+        InvoiceStatus::Failed,
+        InvoiceStatus::Skipped,
+        InvoiceStatus::Cancelled,
+        InvoiceStatus::NeedsManual,
+        InvoiceStatus::NotFound,
+        InvoiceStatus::Ignored,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Pending => "pending",
+            InvoiceStatus::Downloading => "downloading",
+            InvoiceStatus::Success => "success",
+            InvoiceStatus::Failed => "failed",
+            InvoiceStatus::Skipped => "skipped",
+            InvoiceStatus::Cancelled => "cancelled",
+            InvoiceStatus::NeedsManual => "needs-manual",
+            InvoiceStatus::NotFound => "not-found",
+            InvoiceStatus::Ignored => "ignored",
+        }
+    }
+
+    /// Whether the invoice is done with, successfully or not
+    pub fn is_final(&self) -> bool {
+        !matches!(self, InvoiceStatus::Pending | InvoiceStatus::Downloading)
+    }
+}
+
+/// Lifecycle of a batch run, stored as kebab-case text in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+impl BatchStatus {
+    pub const ALL: &'static [BatchStatus] = &[
+        BatchStatus::Queued,
+        BatchStatus::Running,
+        BatchStatus::Paused,
+        BatchStatus::Completed,
+        BatchStatus::Cancelled,
+        BatchStatus::Failed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchStatus::Queued => "queued",
+            BatchStatus::Running => "running",
+            BatchStatus::Paused => "paused",
+            BatchStatus::Completed => "completed",
+            BatchStatus::Cancelled => "cancelled",
+            BatchStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Portal an invoice was looked up on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum Portal {
+    Vnpt,
+    TaxAuthority,
+}
+
+impl Portal {
+    pub const ALL: &'static [Portal] = &[Portal::Vnpt, Portal::TaxAuthority];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Portal::Vnpt => "vnpt",
+            Portal::TaxAuthority => "tax-authority",
+        }
+    }
+}
+
+/// Result of verifying the XML-DSig signatures of an invoice's XML
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignatureStatus {
+    Valid,
+    /// At least one signature failed verification
+    Invalid,
+    /// The XML carries no signature
+    Unsigned,
+}
+
+impl SignatureStatus {
+    pub const ALL: &'static [SignatureStatus] = &[
+        SignatureStatus::Valid,
+        SignatureStatus::Invalid,
+        SignatureStatus::Unsigned,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignatureStatus::Valid => "valid",
+            SignatureStatus::Invalid => "invalid",
+            SignatureStatus::Unsigned => "unsigned",
+        }
+    }
+}
+
+/// Action recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditAction {
+    BatchStarted,
+    BatchCancelled,
+    BatchDeleted,
+    SettingsChanged,
+    Export,
+}
+
+impl AuditAction {
+    pub const ALL: &'static [AuditAction] = &[
+        AuditAction::BatchStarted,
+        AuditAction::BatchCancelled,
+        AuditAction::BatchDeleted,
+        AuditAction::SettingsChanged,
+        AuditAction::Export,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::BatchStarted => "batch-started",
+            AuditAction::BatchCancelled => "batch-cancelled",
+            AuditAction::BatchDeleted => "batch-deleted",
+            AuditAction::SettingsChanged => "settings-changed",
+            AuditAction::Export => "export",
+        }
+    }
+}
+
+/// Step of an invoice download timed by the local performance metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimingStage {
+    Navigation,
+    CaptchaSolve,
+    Submit,
+    Download,
+}
+
+impl TimingStage {
+    pub const ALL: &'static [TimingStage] = &[
+        TimingStage::Navigation,
+        TimingStage::CaptchaSolve,
+        TimingStage::Submit,
+        TimingStage::Download,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimingStage::Navigation => "navigation",
+            TimingStage::CaptchaSolve => "captcha-solve",
+            TimingStage::Submit => "submit",
+            TimingStage::Download => "download",
+        }
+    }
+}
+
+/// Implement string and SQLite conversions for a status enum with `ALL` and `as_str`
+macro_rules! impl_status_conversions {
+    ($status:ty) => {
+        impl fmt::Display for $status {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $status {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .find(|status| status.as_str() == s)
+                    .copied()
+                    .ok_or_else(|| format!("Unknown status: {}", s))
+            }
+        }
+
+        impl ToSql for $status {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.as_str()))
+            }
+        }
+
+        impl FromSql for $status {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                value
+                    .as_str()?
+                    .parse()
+                    .map_err(|e: String| FromSqlError::Other(e.into()))
+            }
+        }
+    };
+}
+
+/// Implement `parse` for a setting enum with `ALL` and `as_str`
+macro_rules! impl_setting_parse {
+    ($setting:ty) => {
+        impl $setting {
+            /// Parse a stored value, falling back to the default for unknown ones
+            pub fn parse(value: &str) -> Self {
+                Self::ALL
+                    .iter()
+                    .find(|setting| setting.as_str() == value)
+                    .copied()
+                    .unwrap_or_default()
+            }
+        }
+    };
+}
+pub(crate) use impl_setting_parse;
+
+impl_status_conversions!(InvoiceStatus);
+impl_status_conversions!(BatchStatus);
+impl_status_conversions!(Portal);
+impl_status_conversions!(SignatureStatus);
+impl_status_conversions!(AuditAction);
+impl_status_conversions!(TimingStage);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invoice_status_round_trip() {
+        for status in InvoiceStatus::ALL {
+            assert_eq!(status.as_str().parse::<InvoiceStatus>(), Ok(*status));
+            assert_eq!(
+                serde_json::to_string(status).unwrap(),
+                format!("\"{}\"", status.as_str())
+            );
+        }
+        assert!("done".parse::<InvoiceStatus>().is_err());
+    }
+
+    #[test]
+    fn test_setting_round_trip() {
+        use crate::services::{ai_budget, cloud_drive, dedupe, file_naming, hooks, invoice_export, pdf_fetch, sftp};
+
+        macro_rules! assert_round_trip {
+            ($($setting:ty),+) => {$(
+                for setting in <$setting>::ALL {
+                    assert_eq!(<$setting>::parse(setting.as_str()), *setting);
+                    assert_eq!(serde_json::to_string(setting).unwrap(), format!("\"{}\"", setting.as_str()));
+                }
+                assert_eq!(<$setting>::parse("unknown"), <$setting>::default());
+            )+};
+        }
+
+        assert_round_trip!(
+            hooks::HookScope,
+            sftp::SftpMode,
+            ai_budget::BudgetAction,
+            dedupe::DuplicateFiles,
+            invoice_export::CsvDelimiter,
+            pdf_fetch::PdfVariant,
+            file_naming::Transliteration,
+            cloud_drive::CloudProvider
+        );
+    }
+}
@@ -1,6 +1,6 @@
 import { Component, type ReactNode } from 'react';
 import { Layout } from './components/layout/Layout';
-import { useTauriEvents } from './hooks';
+import { useTauriEvents, useActiveDownloads } from './hooks';
 
 // Error Boundary to catch rendering errors
 class ErrorBoundary extends Component<
@@ -40,6 +40,7 @@ function App() {
   // Initialize Tauri event listeners
   useTauriEvents();
   console.log('[AutoInvoice] useTauriEvents initialized');
+  useActiveDownloads();
 
   return (
     <ErrorBoundary>
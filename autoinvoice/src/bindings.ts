@@ -0,0 +1,2082 @@
+// @ts-nocheck
+// This file was generated by [tauri-specta](https://github.com/oscartbeaumont/tauri-specta). Do not edit this file manually.
+
+/** user-defined commands **/
+
+
+export const commands = {
+/**
+ * Let the user pick an Excel sheet or PDF to read invoices from
+ * 
+ * # Returns
+ * The picked file, or `None` when the dialog was cancelled
+ */
+async chooseSourceFile() : Promise<Result<SelectedFile | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("choose_source_file") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Sample invoices to try the app with in demo mode, in place of a sheet
+ */
+async loadDemoInvoices() : Promise<Result<ExcelParseResult, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("load_demo_invoices") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Parse an Excel file and extract invoice codes
+ * 
+ * # Arguments
+ * * `file_token` - Token of the Excel file (.xlsx), from `choose_source_file`
+ * 
+ * # Returns
+ * * `ExcelParseResult` containing invoice codes and optionally detected VNPT URL
+ */
+async parseExcel(fileToken: string) : Promise<Result<ExcelParseResult, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("parse_excel", { fileToken }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Extract lookup codes from the text of any PDF, for the same preview and
+ * download flow as an Excel file
+ * 
+ * # Arguments
+ * * `file_token` - Token of the PDF file, from `choose_source_file`
+ */
+async parsePdfForCodes(fileToken: string) : Promise<Result<ExcelParseResult, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("parse_pdf_for_codes", { fileToken }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Parse the Excel file a batch was started from again, e.g. after fixing
+ * rows in it; compare `file_hash` with the batch's to see if it changed
+ */
+async reparseSource(batchId: string) : Promise<Result<ExcelParseResult, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("reparse_source", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Completed batches already downloaded from a file with this hash
+ */
+async getBatchesForSource(fileHash: string) : Promise<Result<DownloadBatch[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_batches_for_source", { fileHash }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Log in to the configured portal account and list the invoices issued
+ * between two dates, in the shape of a parsed Excel sheet so they go
+ * through the same preview and download flow
+ * 
+ * # Arguments
+ * * `from_date` - First issue date, "YYYY-MM-DD"
+ * * `to_date` - Last issue date, "YYYY-MM-DD"
+ */
+async listPortalInvoices(fromDate: string, toDate: string) : Promise<Result<ExcelParseResult, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_portal_invoices", { fromDate, toDate }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * List the portal account's invoices of a period and download them all as
+ * a new batch, named after the period, in the background; the outcome comes
+ * with `batch:completed`, which also reports a listing that failed
+ * 
+ * # Arguments
+ * * `batch_id` - Id of the batch to create, chosen by the caller to follow its events
+ * * `from_date` - First issue date, "YYYY-MM-DD"
+ * * `to_date` - Last issue date, "YYYY-MM-DD"
+ */
+async discoverInvoices(batchId: string, fromDate: string, toDate: string) : Promise<Result<BatchStarted, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("discover_invoices", { batchId, fromDate, toDate }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Start downloading a batch of invoices in the background
+ * 
+ * Safe to retry with the same `batch_id`: a batch already running or
+ * finished is not started again. Settings errors are returned here; the
+ * batch itself reports through its events and `get_batch_result`
+ */
+async startDownload(request: StartDownloadRequest) : Promise<Result<BatchStarted, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_download", { request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Summary of a batch and its invoices: as far as it got while it runs,
+ * final once `batch:completed` was sent
+ */
+async getBatchResult(batchId: string) : Promise<Result<BatchResult, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_batch_result", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Record a batch without starting it, e.g. while offline; it stays queued
+ * until started with `start_queued_download` or automatically once online
+ */
+async queueDownload(request: StartDownloadRequest) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("queue_download", { request }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Read the mailbox now instead of waiting for the next poll
+ * 
+ * # Returns
+ * Number of invoices added to the inbox batch
+ */
+async checkInbox() : Promise<Result<number, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("check_inbox") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Not-found invoices waiting to be looked up again, oldest first
+ */
+async getRetryLaterInvoices() : Promise<Result<RetryLaterInvoice[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_retry_later_invoices") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Start a batch previously recorded with `queue_download` in the
+ * background, like `start_download`; the outcome comes with
+ * `batch:completed`
+ */
+async startQueuedDownload(batchId: string) : Promise<Result<BatchStarted, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_queued_download", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Cancel an active download batch
+ */
+async cancelDownload(batchId: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_download", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Cancel every running batch
+ */
+async cancelAllDownloads() : Promise<Result<string[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_all_downloads") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Batches running right now, oldest first, so a reloaded UI can re-attach
+ */
+async getActiveDownloads() : Promise<Result<ActiveDownload[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_active_downloads") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Raise backend logging to `debug` (selector attempts, network responses)
+ * or lower it back to `info`; applies to batches started afterwards
+ */
+async setLogLevel(level: LogLevel) : Promise<void> {
+    await TAURI_INVOKE("set_log_level", { level });
+},
+async getLogLevel() : Promise<LogLevel> {
+    return await TAURI_INVOKE("get_log_level");
+},
+/**
+ * Batches that were running when the app last quit uncleanly; batches
+ * running now, whose journal is their live one, are left out
+ */
+async getUnfinishedBatches() : Promise<Result<UnfinishedBatch[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_unfinished_batches") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Continue an unfinished batch from its journal in the background, skipping
+ * the invoices the journal shows as done even when the database missed their
+ * final status; the outcome comes with `batch:completed`
+ */
+async resumeBatch(batchId: string) : Promise<Result<BatchStarted, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resume_batch", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Forget an unfinished batch instead of resuming it; a running batch's
+ * journal is its live one and is kept
+ */
+async discardUnfinishedBatch(batchId: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("discard_unfinished_batch", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Submit a manually solved captcha to the download waiting for it
+ */
+async submitManualCaptcha(batchId: string, invoiceId: string, captchaText: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("submit_manual_captcha", { batchId, invoiceId, captchaText }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Invoices across all batches waiting for the user to solve their captcha,
+ * so they can be answered one after another
+ */
+async getPendingCaptchas() : Promise<PendingCaptcha[]> {
+    return await TAURI_INVOKE("get_pending_captchas");
+},
+/**
+ * Skip an invoice waiting for a manual captcha; the batch moves on
+ */
+async skipManualCaptcha(batchId: string, invoiceId: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("skip_manual_captcha", { batchId, invoiceId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Stop or resume asking for manual captchas in a running batch; while on,
+ * invoices needing one are recorded as `needs-manual` for a later pass,
+ * including those already waiting
+ */
+async setDeferManualCaptchas(batchId: string, enabled: boolean) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_defer_manual_captchas", { batchId, enabled }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Let a running batch go over its AI captcha budget; a batch paused on the
+ * budget resumes, one asking for manual captchas goes back to the API
+ */
+async continueOverBudget(batchId: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("continue_over_budget", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Start or stop streaming screenshots of the tab a running batch works in,
+ * as `browser:preview` events
+ */
+async streamBrowserPreview(batchId: string, enabled: boolean) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("stream_browser_preview", { batchId, enabled }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Predict how long a batch of `invoice_count` invoices will take and what it
+ * will cost, based on previously downloaded invoices
+ */
+async estimateBatch(invoiceCount: number) : Promise<Result<BatchEstimate, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("estimate_batch", { invoiceCount }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Get application settings
+ */
+async getSettings() : Promise<Result<Settings, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_settings") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Save application settings
+ */
+async saveSettings(settings: Settings) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_settings", { settings }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Check an OpenAI key before a batch relies on it: lists the models it can
+ * use and times a one-token completion, telling a wrong key, an empty quota
+ * and rate limiting apart
+ * 
+ * # Arguments
+ * * `key` - Key to test, e.g. the one typed in Settings before saving
+ * * `azure` - Azure OpenAI deployment the key belongs to, if any
+ */
+async testApiKey(key: string, azure: AzureOpenAiConfig | null) : Promise<Result<ApiKeyCheck, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("test_api_key", { key, azure }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Estimated AI captcha spend of the current month, in USD
+ */
+async getAiSpend() : Promise<Result<number, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_ai_spend") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Calls and estimated spend of each OpenAI key this month
+ */
+async getAiKeyUsage() : Promise<Result<AiKeyUsage[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_ai_key_usage") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Folder of the captcha dataset and how many samples it holds
+ */
+async getCaptchaDataset() : Promise<Result<CaptchaDataset, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_captcha_dataset") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Folder of the offline captcha model batches would use, the user's own
+ * before the bundled one; none when no model is installed or the app was
+ * built without the `local-captcha` feature
+ */
+async getLocalCaptchaModel() : Promise<Result<string | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_local_captcha_model") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Let the user pick the download directory in the native dialog, check it is
+ * writable and save it
+ * 
+ * # Returns
+ * The normalized directory, or `None` when the dialog was cancelled
+ */
+async chooseDownloadDirectory() : Promise<Result<string | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("choose_download_directory") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Politeness profiles of the portals, built-in ones included
+ */
+async getPortalProfiles() : Promise<Result<PortalProfile[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_portal_profiles") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Save the profile of a host, replacing a built-in one for the same host
+ */
+async savePortalProfile(profile: PortalProfile) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("save_portal_profile", { profile }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Delete the saved profile of a host
+ */
+async deletePortalProfile(host: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("delete_portal_profile", { host }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Save settings, portal profiles and selector overrides to an encrypted
+ * file, so IT can set up other machines the same way
+ * 
+ * # Arguments
+ * * `destination` - Target directory or file path
+ * * `password` - Password to encrypt with; when empty, a one-time password
+ * is generated and returned
+ * * `include_secrets` - Also export API keys and passwords
+ */
+async exportConfig(destination: string, password: string | null, includeSecrets: boolean) : Promise<Result<ConfigExport, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_config", { destination, password, includeSecrets }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Let the user pick a file written by `export_config` and apply it: its
+ * settings, its portal profiles in place of the saved ones, and its
+ * selector overrides. Secrets the file does not carry keep their value on
+ * this machine
+ * 
+ * # Arguments
+ * * `password` - Password the file was encrypted with
+ * 
+ * # Returns
+ * What was imported, or `None` when the dialog was cancelled
+ */
+async importConfig(password: string) : Promise<Result<ConfigImport | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("import_config", { password }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Steps of the first-run wizard the user got through, and the next one
+ */
+async getOnboardingState() : Promise<Result<OnboardingState, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_onboarding_state") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Run the check behind a wizard step against the saved settings, and
+ * record the step as done when it passes
+ * 
+ * # Arguments
+ * * `step` - Step the user is on; save the settings it asks for first
+ */
+async completeOnboardingStep(step: OnboardingStep) : Promise<Result<OnboardingCheck, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("complete_onboarding_step", { step }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Start signing in to the drive selected in Settings
+ * 
+ * # Returns
+ * The code to enter at `verification_url`; pass it to `complete_cloud_login`
+ */
+async startCloudLogin() : Promise<Result<DeviceLogin, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("start_cloud_login") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Wait until the user approved the device code, then keep the sign-in
+ */
+async completeCloudLogin(login: DeviceLogin) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("complete_cloud_login", { login }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Forget the cloud drive sign-in
+ */
+async disconnectCloudDrive() : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("disconnect_cloud_drive") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Whether a cloud drive sign-in is stored
+ */
+async getCloudDriveStatus() : Promise<Result<boolean, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_cloud_drive_status") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Get list of download batches
+ */
+async getBatches() : Promise<Result<DownloadBatch[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_batches") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Get invoices for a specific batch
+ */
+async getBatchInvoices(batchId: string) : Promise<Result<HistoryInvoice[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_batch_invoices", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Parsed XML of an invoice, when it was downloaded with its XML
+ */
+async getInvoiceXml(invoiceId: string) : Promise<Result<XmlInvoice | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_invoice_xml", { invoiceId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Signature checks of an invoice's XML, empty when it has none
+ */
+async getInvoiceSignatures(invoiceId: string) : Promise<Result<SignatureCheck[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_invoice_signatures", { invoiceId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Number, date and amounts read from an invoice's PDF
+ */
+async getInvoiceMetadata(invoiceId: string) : Promise<Result<InvoiceMetadata | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_invoice_metadata", { invoiceId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Sizes before and after compressing an invoice's PDF, when it was
+ */
+async getPdfOptimization(invoiceId: string) : Promise<Result<PdfOptimization | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_pdf_optimization", { invoiceId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Invoice values of a month (`2024-05`) or quarter (`2024-Q2`) grouped by
+ * VAT rate, as on the VAT declaration appendix. Uses the downloaded XML
+ */
+async getVatSummary(period: string) : Promise<Result<VatSummary, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_vat_summary", { period }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Suspicious invoices of a batch: downloaded twice, dated in the future, or
+ * with a total far outside the seller's earlier invoices
+ */
+async getBatchAnomalies(batchId: string) : Promise<Result<InvoiceAnomaly[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_batch_anomalies", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Codes only in batch A, only in batch B, and those whose status changed,
+ * e.g. to see what a corrected spreadsheet changed
+ */
+async compareBatches(batchA: string, batchB: string) : Promise<Result<BatchComparison, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("compare_batches", { batchA, batchB }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Delete a batch and all its invoices
+ */
+async deleteBatch(batchId: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("delete_batch", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Get failed invoices for a batch (for re-download)
+ */
+async getFailedInvoices(batchId: string) : Promise<Result<HistoryInvoice[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_failed_invoices", { batchId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Mark failed invoices as ignored, e.g. cancelled by the seller, so they no
+ * longer count as failed or come up for re-download
+ * 
+ * # Arguments
+ * * `invoice_ids` - Invoices to mark; those that did not fail are left as is
+ * * `note` - Why they are ignored, shown in history
+ * 
+ * # Returns
+ * * Number of invoices marked
+ */
+async ignoreInvoices(invoiceIds: string[], note: string) : Promise<Result<number, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("ignore_invoices", { invoiceIds, note }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Note the user wrote on an invoice
+ */
+async getInvoiceNote(invoiceId: string) : Promise<Result<string | null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_invoice_note", { invoiceId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Write a note on an invoice, e.g. why it failed or where it was obtained
+ * by hand; an empty note removes it
+ */
+async setInvoiceNote(invoiceId: string, note: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_invoice_note", { invoiceId, note }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Render a printable PDF summary of a batch for the paper archive
+ * 
+ * # Arguments
+ * * `batch_id` - Batch to summarize
+ * * `destination` - Target directory or file path
+ * 
+ * # Returns
+ * * Path of the written PDF
+ */
+async printBatchSummary(batchId: string, destination: string) : Promise<Result<string, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("print_batch_summary", { batchId, destination }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Save the invoices of a batch with the given statuses as CSV or JSON, e.g.
+ * to send the codes that failed back to the supplier. The CSV layout
+ * (delimiter, decimal comma, BOM, dates) follows the settings
+ * 
+ * # Arguments
+ * * `batch_id` - Batch to export, including its sub-batches
+ * * `status_filter` - Statuses to include; empty for all invoices
+ * * `format` - CSV or JSON
+ * * `destination` - Target directory or file path
+ * 
+ * # Returns
+ * * Path of the written file
+ */
+async exportBatchInvoices(batchId: string, statusFilter: InvoiceStatus[], format: ExportFormat, destination: string) : Promise<Result<string, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_batch_invoices", { batchId, statusFilter, format, destination }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Zip the PDFs of a batch with its checksum manifest, e.g. to email the
+ * bundle to the accountant
+ * 
+ * # Arguments
+ * * `batch_id` - Batch to package, including its sub-batches
+ * * `destination` - Target directory or file path
+ * * `encrypt` - Encrypt the zip with AES-256
+ * * `password` - Password to encrypt with; when empty, a one-time password
+ * is generated and returned
+ * 
+ * # Returns
+ * * Path of the zip, and the generated password if any
+ */
+async packageBatch(batchId: string, destination: string, encrypt: boolean, password: string | null) : Promise<Result<BatchPackage, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("package_batch", { batchId, destination, encrypt, password }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Space used in the download directory per batch, month and seller, and the
+ * files no invoice record points at, which can be deleted to reclaim space
+ */
+async getStorageReport() : Promise<Result<StorageReport, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_storage_report") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Copy the repro bundle of a failed invoice to a user-chosen location
+ * 
+ * # Arguments
+ * * `invoice_id` - Invoice whose bundle should be exported
+ * * `destination` - Target directory or file path
+ * 
+ * # Returns
+ * * Path of the exported zip file
+ */
+async exportReproBundle(invoiceId: string, destination: string) : Promise<Result<string, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_repro_bundle", { invoiceId, destination }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Open a portal page and report which known selectors currently match
+ * 
+ * # Arguments
+ * * `url` - Portal lookup page to inspect
+ */
+async inspectPage(url: string) : Promise<Result<PageInspection, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("inspect_page", { url }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Compare the local selector version with the minimum published for the
+ * current portal HTML
+ */
+async checkSelectorCompatibility() : Promise<Result<SelectorCompatibility, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("check_selector_compatibility") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Per-stage download timings recorded on this machine, to help tune
+ * timeouts and delays; empty unless metrics collection is enabled
+ * 
+ * # Arguments
+ * * `since` - Only include timings recorded from this RFC 3339 time
+ */
+async getPerformanceMetrics(since: string | null) : Promise<Result<StageMetrics[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_performance_metrics", { since }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Delete all recorded stage timings
+ */
+async clearPerformanceMetrics() : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("clear_performance_metrics") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Get audit log entries, newest first
+ */
+async getAuditLog(filter: AuditLogFilter) : Promise<Result<AuditEntry[], string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_audit_log", { filter }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Whether a passcode is set and the app is currently locked
+ */
+async getLockStatus() : Promise<Result<LockStatus, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_lock_status") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Unlock the app with its passcode
+ */
+async unlockApp(passcode: string) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("unlock_app", { passcode }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Lock the app again; a no-op without a passcode
+ */
+async lockApp() : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("lock_app") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Set, change or remove the app passcode
+ * 
+ * # Arguments
+ * * `current` - Current passcode, required when one is set
+ * * `passcode` - New passcode; `None` or empty removes the lock
+ */
+async setPasscode(current: string | null, passcode: string | null) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_passcode", { current, passcode }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Switch read-only mode; turning it off requires the passcode when one is set
+ */
+async setReadOnly(enabled: boolean, passcode: string | null) : Promise<Result<null, string | StructuredError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_read_only", { enabled, passcode }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+}
+}
+
+/** user-defined events **/
+
+
+
+/** user-defined constants **/
+
+export const EVENT_NAMES = {"batchCompleted":"batch:completed","batchStatus":"batch:status","browserPreview":"browser:preview","budgetExceeded":"budget:exceeded","captchaRequired":"captcha:required","connectivityChanged":"connectivity:changed","downloadLog":"download:log","downloadLogBatch":"download:log-batch","downloadProgress":"download:progress","downloadsCancelled":"downloads:cancelled","invoiceStatus":"invoice:status","invoiceStatusBatch":"invoice:status-batch","invoicesAvailable":"invoices:available","selectorsOutdated":"selectors:outdated","settingsChanged":"settings:changed","uploadProgress":"upload:progress"} as const;
+
+/** user-defined types **/
+
+/**
+ * A running batch, for a reloaded UI to re-attach to
+ */
+export type ActiveDownload = { batch_id: string; batch_name: string | null; started_at: string; status: BatchStatus; 
+/**
+ * Invoices reached so far, across sub-batches
+ */
+current: number; total: number; percentage: number; 
+/**
+ * Invoice being downloaded, if any
+ */
+invoice_id: string | null; invoice_code: string | null }
+/**
+ * AI calls made with one API key in a month
+ */
+export type AiKeyUsage = { 
+/**
+ * Last characters of the key, see `captcha::key_hint`
+ */
+key_hint: string; calls: number; cost_usd: number }
+export type AnomalyKind = 
+/**
+ * Same seller, series and number as another downloaded invoice
+ */
+"duplicate" | 
+/**
+ * Issued after today
+ */
+"future-date" | 
+/**
+ * Total far outside the seller's usual range
+ */
+"amount-outlier"
+/**
+ * Result of `test_api_key`
+ */
+export type ApiKeyCheck = { status: ApiKeyStatus; 
+/**
+ * Round trip of a one-token completion, in milliseconds
+ */
+latency_ms: number | null; 
+/**
+ * Models the key can use, sorted
+ */
+models: string[]; 
+/**
+ * Error from OpenAI when the check failed
+ */
+message: string | null }
+/**
+ * Why an API key works or not
+ */
+export type ApiKeyStatus = "ok" | 
+/**
+ * Wrong, revoked or mistyped
+ */
+"invalid-key" | 
+/**
+ * No credit left, or the spending limit is reached
+ */
+"quota-exceeded" | 
+/**
+ * Too many requests for now; works again later
+ */
+"rate-limited" | 
+/**
+ * The key cannot use `MODEL`
+ */
+"model-unavailable" | 
+/**
+ * OpenAI could not be reached
+ */
+"unreachable" | "error"
+/**
+ * Action recorded in the audit log
+ */
+export type AuditAction = "batch-started" | "batch-cancelled" | "batch-deleted" | "settings-changed" | "export"
+export type AuditEntry = { id: number; 
+/**
+ * RFC 3339, UTC
+ */
+timestamp: string; 
+/**
+ * OS login of whoever ran the app
+ */
+user: string; action: AuditAction; batch_id: string | null; details: string | null }
+/**
+ * Criteria for `get_audit_log`; unset fields match everything
+ */
+export type AuditLogFilter = { action: AuditAction | null; batch_id: string | null; user: string | null; 
+/**
+ * Inclusive lower bound, RFC 3339
+ */
+from: string | null; 
+/**
+ * Exclusive upper bound, RFC 3339
+ */
+to: string | null; limit: number | null }
+/**
+ * A `MODEL` deployment on Azure OpenAI, called instead of api.openai.com
+ */
+export type AzureOpenAiConfig = { 
+/**
+ * Resource URL, e.g. `https://contoso.openai.azure.com`
+ */
+endpoint: string; 
+/**
+ * Name the model was deployed under in the Azure resource
+ */
+deployment: string; 
+/**
+ * REST API version, e.g. `2024-06-01`
+ */
+api_version: string }
+/**
+ * Differences between two batches, e.g. a spreadsheet and its corrected
+ * re-run; codes are sorted
+ */
+export type BatchComparison = { only_in_a: string[]; only_in_b: string[]; status_changed: StatusChange[] }
+/**
+ * A batch run ended, successfully or not; `get_batch_result` has the
+ * invoices
+ */
+export type BatchCompletedEvent = { batch_id: string; batch_name: string | null; status: BatchStatus; total: number; success_count: number; failed_count: number; 
+/**
+ * Why the batch could not run, when it stopped on an error
+ */
+error: string | null }
+export type BatchEstimate = { invoice_count: number; estimated_duration_secs: number; estimated_cost_usd: number; avg_invoice_secs: number; avg_captcha_attempts: number; 
+/**
+ * Share of captcha attempts that led to a downloaded invoice
+ */
+captcha_solve_rate: number | null; sample_size: number; 
+/**
+ * False when there was too little history and defaults were used
+ */
+from_history: boolean }
+/**
+ * Zip written by `package_batch`
+ */
+export type BatchPackage = { path: string; file_count: number; 
+/**
+ * Password the zip was encrypted with, when it was generated; it is not
+ * stored anywhere, so this is the only time it can be shown
+ */
+generated_password: string | null }
+export type BatchResult = { batch_id: string; batch_name: string | null; status: BatchStatus; total: number; success_count: number; failed_count: number; results: InvoiceResult[] }
+/**
+ * Acknowledgement of a batch started in the background, e.g. by
+ * `start_download`; the outcome comes with `batch:completed`
+ */
+export type BatchStarted = { batch_id: string; 
+/**
+ * False when the batch was already running or finished, e.g. for a
+ * start retried after an IPC timeout
+ */
+started: boolean; status: BatchStatus }
+/**
+ * Lifecycle of a batch run, stored as kebab-case text in the database
+ */
+export type BatchStatus = "queued" | "running" | "paused" | "completed" | "cancelled" | "failed"
+export type BatchStatusEvent = { batch_id: string; batch_name: string | null; status: BatchStatus }
+/**
+ * Downscaled JPEG of the page the bot is working on
+ */
+export type BrowserPreviewEvent = { batch_id: string; invoice_id: string; image_base64: string; captured_at: string }
+/**
+ * What a batch does once its AI spend crosses a cap
+ */
+export type BudgetAction = 
+/**
+ * Ask the user for the remaining captchas
+ */
+"manual-captcha" | 
+/**
+ * Wait until the user lets the batch go over the cap, or cancels it
+ */
+"pause"
+/**
+ * A batch reached its AI captcha budget, see `ai_budget`
+ */
+export type BudgetExceededEvent = { batch_id: string; scope: BudgetScope; limit_usd: number; spent_usd: number; 
+/**
+ * Manual captchas from now on, or paused until `continue_over_budget`
+ */
+action: BudgetAction }
+/**
+ * Which cap was crossed
+ */
+export type BudgetScope = "batch" | "month"
+/**
+ * Where the dataset is kept and how many samples it holds
+ */
+export type CaptchaDataset = { path: string; sample_count: number }
+export type CaptchaRequiredEvent = { batch_id: string; invoice_id: string; invoice_code: string; image_base64: string }
+/**
+ * Drive that finished batches are mirrored to
+ */
+export type CloudProvider = "off" | "google-drive" | "onedrive"
+/**
+ * Bundle written by `export_config`
+ */
+export type ConfigExport = { path: string; includes_secrets: boolean; 
+/**
+ * Password the bundle was encrypted with, when it was generated; it is
+ * not stored anywhere, so this is the only time it can be shown
+ */
+generated_password: string | null }
+/**
+ * What `import_config` applied
+ */
+export type ConfigImport = { includes_secrets: boolean; portal_profile_count: number; selector_override_count: number; 
+/**
+ * Names of the settings the import changed
+ */
+changed: string[] }
+export type ConnectivityEvent = { online: boolean; 
+/**
+ * Batches waiting in the offline queue
+ */
+queued_batches: number }
+/**
+ * Field separator of CSV exports
+ */
+export type CsvDelimiter = "comma" | 
+/**
+ * What Excel expects when the list separator is `;`, as with Vietnamese
+ * regional settings
+ */
+"semicolon"
+/**
+ * A portal URL found in the sheet and where it appears
+ */
+export type DetectedUrl = { url: string; 
+/**
+ * Number of occurrences
+ */
+count: number; 
+/**
+ * 1-indexed rows the URL appears on
+ */
+rows: number[] }
+/**
+ * Code the user enters on the provider's page to let the app upload
+ */
+export type DeviceLogin = { device_code: string; user_code: string; verification_url: string; interval_secs: number; expires_in_secs: number }
+export type DownloadBatch = { id: string; created_at: string; total_count: number; success_count: number; failed_count: number; download_directory: string; status: BatchStatus; 
+/**
+ * Set on the per-portal sub-batches of a split batch
+ */
+parent_id: string | null; 
+/**
+ * Config the batch ran with (JSON, without the API key), including
+ * per-batch overrides
+ */
+effective_config: string | null; 
+/**
+ * Name given when the batch was started, shown instead of the id
+ */
+name: string | null; 
+/**
+ * File name of the Excel sheet the invoices came from
+ */
+source_file: string | null; 
+/**
+ * Full path of that sheet, for `reparse_source`
+ */
+source_path: string | null; 
+/**
+ * SHA-256 of the sheet when the batch was started
+ */
+source_hash: string | null }
+/**
+ * Settings a single batch may change; everything else comes from the saved settings
+ */
+export type DownloadOverrides = { 
+/**
+ * Portal detected in the uploaded sheet
+ */
+vnpt_url: string | null; download_directory: string | null; 
+/**
+ * Show the browser, e.g. to watch a batch that keeps failing
+ */
+headless: boolean | null; 
+/**
+ * Attempts per invoice before it is marked failed
+ */
+max_retries: number | null; 
+/**
+ * Record invoices needing a manual captcha as `needs-manual` instead of waiting
+ */
+defer_manual_captchas: boolean | null; 
+/**
+ * Automatic pass first, then the deferred captchas one by one
+ */
+two_pass: boolean | null; 
+/**
+ * Save what the portal served as fixtures for replaying the run offline
+ */
+record_fixtures: boolean | null }
+/**
+ * Every running batch was cancelled at once
+ */
+export type DownloadsCancelledEvent = { batch_ids: string[] }
+/**
+ * What to do with a downloaded PDF identical to one already in the archive,
+ * e.g. the same invoice in two batches
+ */
+export type DuplicateFiles = 
+/**
+ * Write another copy
+ */
+"keep" | 
+/**
+ * Hard-link to the existing file, so the copy takes no extra space;
+ * falls back to a copy across volumes
+ */
+"hard-link" | 
+/**
+ * Write nothing; the invoice points at the existing file
+ */
+"skip"
+export type ExcelParseResult = { invoices: InvoiceCode[]; 
+/**
+ * First URL found, kept for single-portal files
+ */
+detected_url: string | null; 
+/**
+ * Every distinct URL found, in order of first appearance
+ */
+detected_urls: DetectedUrl[]; total_rows: number; sheet_name: string; 
+/**
+ * Name of the parsed file, the default name of its batch
+ */
+file_name: string; file_path: string; 
+/**
+ * SHA-256 of the file, to recognise a sheet that was already downloaded
+ */
+file_hash: string }
+/**
+ * File format of an invoice list export
+ */
+export type ExportFormat = "csv" | "json"
+export type HistoryInvoice = { id: string; batch_id: string; code: string; status: InvoiceStatus; error: string | null; file_path: string | null; downloaded_at: string | null; repro_path: string | null; 
+/**
+ * Portal that served the PDF
+ */
+portal: Portal | null; 
+/**
+ * Object URL when the PDF was uploaded to remote storage
+ */
+remote_url: string | null; 
+/**
+ * Signature verification of the invoice XML, when one was downloaded
+ */
+signature_status: SignatureStatus | null; 
+/**
+ * User's note, e.g. why the invoice was ignored or where it was
+ * obtained by hand
+ */
+note: string | null }
+/**
+ * When the post-download hook runs
+ */
+export type HookScope = 
+/**
+ * After every invoice, whatever its outcome
+ */
+"invoice" | 
+/**
+ * Once, after the whole batch
+ */
+"batch"
+/**
+ * A suspicious invoice of a batch
+ */
+export type InvoiceAnomaly = { invoice_id: string; invoice_code: string; kind: AnomalyKind; message: string }
+export type InvoiceCode = { id: string; code: string; row_number: number; 
+/**
+ * Seller tax code, when the sheet has a tax code column
+ */
+seller_tax_code: string | null; lookup_mode: LookupMode; 
+/**
+ * Invoice series (ký hiệu), for `LookupMode::InvoiceNumber`
+ */
+invoice_series: string | null; 
+/**
+ * Invoice number (số hóa đơn), for `LookupMode::InvoiceNumber`
+ */
+invoice_number: string | null; 
+/**
+ * VNPT portal URL written on the same row, for files mixing several providers
+ */
+portal_url: string | null }
+export type InvoiceDownloadRequest = { id: string; code: string; 
+/**
+ * Needed for the tax-authority portal fallback and number lookups
+ */
+seller_tax_code?: string | null; lookup_mode?: LookupMode; invoice_series?: string | null; invoice_number?: string | null; 
+/**
+ * Portal written next to the invoice in the sheet; batches spanning
+ * several portals are split into one sub-batch per portal
+ */
+portal_url?: string | null }
+/**
+ * Fields read from the printed invoice; each is `None` when not found
+ */
+export type InvoiceMetadata = { source: MetadataSource; series: string | null; number: string | null; 
+/**
+ * Issue date as YYYY-MM-DD
+ */
+issued_date: string | null; seller_tax_code: string | null; total_before_tax: number | null; total_tax: number | null; total_payable: number | null }
+export type InvoiceResult = { invoice_id: string; code: string; status: InvoiceStatus; error: string | null; file_path: string | null; portal: Portal | null; remote_url: string | null }
+/**
+ * Status of a single invoice, stored as kebab-case text in the database
+ */
+export type InvoiceStatus = "pending" | "downloading" | "success" | "failed" | "skipped" | "cancelled" | 
+/**
+ * Needs a manual captcha, left for a later interactive pass
+ */
+"needs-manual" | 
+/**
+ * The portal has no invoice under the code; the error keeps its message
+ */
+"not-found" | 
+/**
+ * Given up on by the user, e.g. cancelled by the seller; the note says why
+ */
+"ignored"
+/**
+ * Latest status of each invoice that changed while events were throttled
+ */
+export type InvoiceStatusBatchEvent = { batch_id: string; statuses: InvoiceStatusEvent[] }
+export type InvoiceStatusEvent = { batch_id: string; invoice_id: string; status: InvoiceStatus; error: string | null; file_path: string | null }
+/**
+ * Invoices an earlier batch recorded as not found, downloaded by this one
+ */
+export type InvoicesAvailableEvent = { batch_id: string; codes: string[] }
+export type LockStatus = { has_passcode: boolean; locked: boolean; 
+/**
+ * Downloads, deletions and settings changes are refused
+ */
+read_only: boolean }
+/**
+ * Log entries held back by event throttling, in emission order
+ */
+export type LogBatchEvent = { batch_id: string; entries: LogEvent[] }
+export type LogEvent = { batch_id: string; timestamp: string; level: string; message: string; 
+/**
+ * Request id of the entry in the backend log, see `tracing_log`
+ */
+request_id: string | null }
+/**
+ * Lowest level of backend log entries sent to the log viewer
+ */
+export type LogLevel = "info" | 
+/**
+ * Also selector attempts and CDP network responses
+ */
+"debug"
+/**
+ * How an invoice is looked up on the portal
+ */
+export type LookupMode = 
+/**
+ * Lookup code (mã tra cứu) issued by the provider
+ */
+"code" | 
+/**
+ * Invoice series + number + seller tax code, for clients without lookup codes
+ */
+"invoice-number"
+/**
+ * Where the fields of an invoice PDF were read from
+ */
+export type MetadataSource = 
+/**
+ * The PDF's text layer
+ */
+"text" | 
+/**
+ * OCR of the scanned pages
+ */
+"ocr"
+/**
+ * Outcome of the check behind a step
+ */
+export type OnboardingCheck = { step: OnboardingStep; ok: boolean; 
+/**
+ * What was found, e.g. the Chrome path, or why the check failed
+ */
+message: string | null }
+/**
+ * Steps of the wizard and which ones the user got through
+ */
+export type OnboardingState = { completed: OnboardingStep[]; 
+/**
+ * The first step not completed yet, `None` once the wizard is done
+ */
+next: OnboardingStep | null }
+/**
+ * A step of the first-run wizard, each backed by a real check
+ */
+export type OnboardingStep = 
+/**
+ * Chrome or Chromium is installed where the app looks for it
+ */
+"browser" | 
+/**
+ * The saved OpenAI key answers a completion
+ */
+"api-key" | 
+/**
+ * The download folder exists, or can be created, and is writable
+ */
+"download-folder"
+export type PageInspection = { url: string; selectors: SelectorMatch[] }
+/**
+ * What optimizing an invoice PDF did
+ */
+export type PdfOptimization = { original_bytes: number; optimized_bytes: number; linearized: boolean; 
+/**
+ * Copy of the PDF as downloaded, when originals are kept
+ */
+original_path: string | null; 
+/**
+ * Why the PDF was left as downloaded, e.g. it is signed
+ */
+skipped: string | null }
+/**
+ * Which PDF of an invoice to keep, where the portal offers the printable
+ * "hóa đơn chuyển đổi" besides the original
+ */
+export type PdfVariant = 
+/**
+ * The original e-invoice PDF
+ */
+"original" | 
+/**
+ * The converted, printable PDF; the original when the portal has none
+ */
+"converted" | 
+/**
+ * The original as `<code>.pdf` and the converted one as
+ * `<code>_chuyen-doi.pdf`
+ */
+"both"
+/**
+ * An invoice whose captcha the AI could not solve, waiting for the user
+ */
+export type PendingCaptcha = { batch_id: string; invoice_id: string; invoice_code: string; image_base64: string; requested_at: string }
+/**
+ * Portal an invoice was looked up on
+ */
+export type Portal = "vnpt" | "tax-authority"
+/**
+ * How gently a portal host is treated. The delay is a floor and the retry
+ * limit a cap on the user's settings, so a fast setup tuned for one portal
+ * never hammers another.
+ */
+export type PortalProfile = { 
+/**
+ * Host the profile applies to, including its subdomains
+ */
+host: string; 
+/**
+ * Shortest pause between two invoices, in seconds
+ */
+base_delay_secs: number; 
+/**
+ * Invoices downloaded from the host at the same time, across batches
+ */
+max_concurrency: number; 
+/**
+ * Most attempts per invoice
+ */
+max_retries: number; 
+/**
+ * Shipped with the app rather than saved by the user
+ */
+builtin?: boolean }
+export type ProgressEvent = { batch_id: string; current: number; total: number; percentage: number }
+/**
+ * Latest lookup of an invoice the portal did not have yet
+ */
+export type RetryLaterInvoice = { invoice_id: string; batch_id: string; code: string; 
+/**
+ * Portal message of the last lookup
+ */
+error: string | null; not_found_at: string; 
+/**
+ * How many lookups found nothing so far
+ */
+attempts: number; 
+/**
+ * Portal the last lookup ran against
+ */
+portal_url: string | null }
+/**
+ * File picked in `choose_source_file`
+ */
+export type SelectedFile = { 
+/**
+ * Passed to `parse_excel` or `parse_pdf_for_codes` instead of the path
+ */
+token: string; file_name: string }
+export type SelectorCompatibility = { local_version: number; 
+/**
+ * None when the manifest could not be fetched
+ */
+min_version: number | null; outdated: boolean; message: string | null; 
+/**
+ * Why the manifest could not be fetched
+ */
+error: string | null }
+export type SelectorMatch = { field: string; selector: string; matched: boolean }
+export type SelectorsOutdatedEvent = { local_version: number; min_version: number; message: string | null }
+export type Settings = { openai_api_key: string; 
+/**
+ * Keys a batch switches to, in order, when the current one is rate
+ * limited or out of quota
+ */
+openai_backup_api_keys: string[]; 
+/**
+ * Call an Azure OpenAI deployment instead of OpenAI; the API keys are
+ * then keys of the Azure resource
+ */
+use_azure_openai: boolean; azure_openai_endpoint: string; azure_openai_deployment: string; azure_openai_api_version: string; vnpt_url: string; download_directory: string; 
+/**
+ * Pause between two invoices, in seconds
+ */
+invoice_delay_secs: number; 
+/**
+ * Start of the daily quiet hours ("HH:MM"), empty when disabled
+ */
+quiet_hours_start: string; 
+/**
+ * End of the daily quiet hours ("HH:MM"), empty when disabled
+ */
+quiet_hours_end: string; 
+/**
+ * Retry invoices VNPT does not know on the tax-authority portal
+ */
+fallback_to_tax_portal: boolean; 
+/**
+ * Look an invoice the portal does not know up once more after a pause,
+ * as same-day invoices take a while to reach the lookup
+ */
+verify_not_found: boolean; not_found_verify_delay_secs: number; 
+/**
+ * Look not-found invoices up again this many hours later, while the app
+ * is open; 0 disables the retry-later queue
+ */
+retry_not_found_hours: number; 
+/**
+ * Start queued batches as soon as the network is back
+ */
+auto_start_queued: boolean; 
+/**
+ * Keep per-stage timings locally; nothing leaves the machine
+ */
+collect_metrics: boolean; 
+/**
+ * Also download each invoice's XML and parse it for reporting
+ */
+download_xml: boolean; 
+/**
+ * Keep the original PDF, the converted printable one, or both
+ */
+pdf_variant: PdfVariant; 
+/**
+ * OCR PDFs without a text layer to read their number, date and amounts
+ */
+ocr_scanned_pdfs: boolean; 
+/**
+ * Tesseract executable; empty uses `tesseract` from PATH
+ */
+tesseract_path: string; 
+/**
+ * Compress downloaded PDFs without loss
+ */
+optimize_pdfs: boolean; 
+/**
+ * Keep the PDF as downloaded in an `originals` folder when optimizing
+ */
+keep_original_pdfs: boolean; 
+/**
+ * Also linearize optimized PDFs with qpdf
+ */
+linearize_pdfs: boolean; 
+/**
+ * qpdf executable; empty uses `qpdf` from PATH
+ */
+qpdf_path: string; 
+/**
+ * Stamp the first page of each PDF with the processing date and batch
+ */
+stamp_pdfs: boolean; 
+/**
+ * Estimated AI captcha spend allowed per batch and per calendar month,
+ * in USD; 0 means no cap
+ */
+ai_budget_per_batch_usd: number; ai_budget_per_month_usd: number; 
+/**
+ * What a batch does once a cap is reached
+ */
+ai_budget_action: BudgetAction; 
+/**
+ * Solve the next invoice's captcha on a second tab during each download
+ */
+captcha_lookahead: boolean; 
+/**
+ * Read captchas with the offline model first, asking the AI provider
+ * only when it is less confident than `local_captcha_min_confidence`
+ */
+local_captcha_solver: boolean; local_captcha_min_confidence: number; 
+/**
+ * Save captchas the portal accepted, with their answers, as a dataset
+ * for training a local solver; nothing is uploaded
+ */
+collect_captcha_dataset: boolean; 
+/**
+ * Dataset folder; empty uses `captcha-dataset` in the app data folder
+ */
+captcha_dataset_dir: string; 
+/**
+ * Keep one Chrome profile per portal, reusing its cookies and session
+ */
+persistent_browser_profiles: boolean; 
+/**
+ * Download sample invoices from a simulated portal instead of the real
+ * ones, for training; no portal or API is contacted
+ */
+demo_mode: boolean; 
+/**
+ * Hard-link or skip PDFs identical to one already downloaded
+ */
+duplicate_files: DuplicateFiles; 
+/**
+ * Name of each PDF, e.g. `{seller}_{number}`; empty uses the lookup code
+ */
+file_name_template: string; 
+/**
+ * Subfolder of the batch folder for each PDF, e.g. `{seller}`
+ */
+folder_template: string; 
+/**
+ * Spelling of names from the invoice in file and folder names
+ */
+file_name_transliteration: Transliteration; 
+/**
+ * CSV export layout; see `CsvOptions`
+ */
+csv_delimiter: CsvDelimiter; csv_decimal_comma: boolean; csv_bom: boolean; csv_day_first_dates: boolean; 
+/**
+ * Browser window size, device scale and zoom; see `BrowserViewport`
+ */
+browser_window_width: number; browser_window_height: number; browser_device_scale_factor: number; browser_zoom_percent: number; 
+/**
+ * Margin captured around the captcha image, in CSS pixels
+ */
+captcha_padding_px: number; 
+/**
+ * How long a download waits for a manual captcha before skipping the invoice
+ */
+manual_captcha_timeout_secs: number; 
+/**
+ * Portal account listing every invoice of the company; empty username disables it
+ */
+portal_login_url: string; portal_invoice_list_url: string; portal_username: string; portal_password: string; 
+/**
+ * Queue invoices from notification emails in the mailbox below
+ */
+inbox_enabled: boolean; imap_host: string; imap_port: number; imap_username: string; imap_password: string; imap_folder: string; 
+/**
+ * Script or program run after downloads with JSON context on stdin; empty disables it
+ */
+post_download_hook: string; post_download_hook_scope: HookScope; 
+/**
+ * Upload each downloaded PDF to the S3-compatible bucket below
+ */
+upload_to_s3: boolean; s3_endpoint: string; s3_region: string; s3_bucket: string; s3_access_key: string; s3_secret_key: string; 
+/**
+ * Key prefix for uploaded objects, empty for the bucket root
+ */
+s3_prefix: string; 
+/**
+ * Upload finished batches to a WebDAV share such as Nextcloud
+ */
+upload_to_webdav: boolean; webdav_url: string; webdav_username: string; webdav_password: string; 
+/**
+ * Deliver finished batches to an SFTP server
+ */
+upload_to_sftp: boolean; sftp_host: string; sftp_port: number; sftp_username: string; 
+/**
+ * Password, or the key passphrase when a private key is set
+ */
+sftp_password: string; sftp_private_key_path: string; sftp_remote_dir: string; sftp_mode: SftpMode; 
+/**
+ * Expected "SHA256:..." host key; empty pins the one seen first
+ */
+sftp_host_key_fingerprint: string; 
+/**
+ * Drive finished batches are mirrored to, signed in with a device code
+ */
+cloud_provider: CloudProvider; cloud_client_id: string; cloud_client_secret: string; 
+/**
+ * Google Drive folder id or OneDrive folder path; per-batch folders go inside
+ */
+cloud_folder: string }
+/**
+ * Saved settings changed, from this window or elsewhere in the app
+ */
+export type SettingsChangedEvent = { 
+/**
+ * Names of the changed settings, without their values
+ */
+changed: string[] }
+/**
+ * How a finished batch is handed over
+ */
+export type SftpMode = 
+/**
+ * One folder per batch with the PDFs inside
+ */
+"folder" | 
+/**
+ * One `<batch id>.zip` per batch
+ */
+"zip"
+/**
+ * Outcome of checking one `Signature` element of an invoice
+ * 
+ * Only the signature itself is checked; the certificate chain is not
+ * matched against a trust store.
+ */
+export type SignatureCheck = { 
+/**
+ * Party the signature belongs to: "seller", "tax-authority", or the
+ * name of the element it sits in
+ */
+signed_by: string; valid: boolean; 
+/**
+ * Subject of the signing certificate
+ */
+signer: string | null; issuer: string | null; 
+/**
+ * Signing time the signer recorded, as written in the XML
+ */
+signing_time: string | null; certificate_not_before: string | null; certificate_not_after: string | null; 
+/**
+ * Why the signature is not valid
+ */
+error: string | null }
+/**
+ * Result of verifying the XML-DSig signatures of an invoice's XML
+ */
+export type SignatureStatus = "valid" | 
+/**
+ * At least one signature failed verification
+ */
+"invalid" | 
+/**
+ * The XML carries no signature
+ */
+"unsigned"
+/**
+ * Timing summary of one download stage
+ */
+export type StageMetrics = { stage: TimingStage; samples: number; avg_ms: number; p50_ms: number; p95_ms: number; max_ms: number }
+export type StartDownloadRequest = { batch_id: string; 
+/**
+ * Human-readable name, e.g. "Hóa đơn đầu vào T5"
+ */
+name?: string | null; 
+/**
+ * File name of the Excel sheet the invoices came from
+ */
+source_file?: string | null; 
+/**
+ * Path and SHA-256 of that sheet, from `parse_excel`
+ */
+source_path?: string | null; source_hash?: string | null; invoices: InvoiceDownloadRequest[]; 
+/**
+ * Per-batch changes to the saved settings
+ */
+overrides?: DownloadOverrides }
+/**
+ * Invoice found in both batches with a different outcome
+ */
+export type StatusChange = { code: string; status_a: InvoiceStatus; status_b: InvoiceStatus }
+/**
+ * Where the space in the download directory goes; groups are largest first
+ */
+export type StorageReport = { root: string; file_count: number; total_bytes: number; by_batch: UsageGroup[]; by_month: UsageGroup[]; by_seller: UsageGroup[]; 
+/**
+ * Candidates for deletion, largest first
+ */
+unreferenced: UnreferencedFile[]; unreferenced_bytes: number }
+/**
+ * Error the frontend can act on, e.g. by marking the field at fault
+ */
+export type StructuredError = { kind: "invalid-argument"; argument: string; reason: string }
+/**
+ * Step of an invoice download timed by the local performance metrics
+ */
+export type TimingStage = "navigation" | "captcha-solve" | "submit" | "download"
+/**
+ * How names from the invoice are spelled in file and folder names
+ */
+export type Transliteration = 
+/**
+ * Keep Vietnamese as written
+ */
+"keep" | 
+/**
+ * Drop diacritics, e.g. "Công ty Hưng Thịnh" as "Cong ty Hung Thinh",
+ * for filesystems and tools that only take ASCII
+ */
+"ascii" | 
+/**
+ * As `Ascii`, with underscores for spaces
+ */
+"ascii-underscore"
+/**
+ * A batch whose journal survived the app: it was running when the app
+ * was killed or crashed
+ */
+export type UnfinishedBatch = { batch_id: string; batch_name: string | null; started_at: string; total: number; 
+/**
+ * Invoices that reached their final status before the shutdown
+ */
+done_count: number; 
+/**
+ * Invoice in progress at the shutdown, when one was
+ */
+invoice_index: number | null; invoice_code: string | null; attempt: number | null; phase: string | null; updated_at: string | null }
+/**
+ * File in the download directory that no invoice record points at
+ */
+export type UnreferencedFile = { path: string; bytes: number; 
+/**
+ * RFC 3339, UTC
+ */
+modified: string | null }
+/**
+ * A finished batch being copied to remote storage, one event per file
+ */
+export type UploadProgressEvent = { batch_id: string; 
+/**
+ * Where the batch goes, e.g. "WebDAV" or "Google Drive"
+ */
+target: string; file_name: string; current: number; total: number }
+/**
+ * Space taken by the files of one batch, month or seller
+ */
+export type UsageGroup = { key: string; label: string; file_count: number; bytes: number }
+/**
+ * VAT rate rows in the order of the declaration appendix
+ */
+export type VatRate = 
+/**
+ * Không chịu thuế (KCT)
+ */
+"not-taxable" | 
+/**
+ * Không kê khai, tính nộp thuế (KKKNT)
+ */
+"not-declared" | "zero" | "five" | "eight" | "ten" | 
+/**
+ * Any other rate, e.g. `KHAC:3.5%`
+ */
+"other"
+/**
+ * Invoice values of a period grouped by VAT rate, in VND
+ */
+export type VatSummary = { period: string; from: string; to: string; invoice_count: number; rows: VatSummaryRow[]; total_taxable: number; total_tax: number; 
+/**
+ * Invoices left out: duplicates, or foreign currency without a rate
+ */
+skipped: string[] }
+/**
+ * Totals of one VAT rate
+ */
+export type VatSummaryRow = { rate: VatRate; invoice_count: number; taxable_amount: number; tax_amount: number }
+/**
+ * Structured content of a Circular 78 (TT78) e-invoice XML
+ */
+export type XmlInvoice = { 
+/**
+ * Template number (KHMSHDon)
+ */
+template: string; 
+/**
+ * Series (KHHDon)
+ */
+series: string; 
+/**
+ * Invoice number (SHDon)
+ */
+number: string; 
+/**
+ * Issue date (NLap), as written in the XML
+ */
+issued_date: string; 
+/**
+ * Currency (DVTTe), VND unless stated
+ */
+currency: string; exchange_rate: number | null; 
+/**
+ * Code the tax authority assigned (MCCQT), absent on invoices without one
+ */
+authority_code: string | null; seller: XmlParty; buyer: XmlParty; lines: XmlLine[]; tax_breakdown: XmlTaxRate[]; total_before_tax: number; total_tax: number; total_payable: number; total_in_words: string; 
+/**
+ * Missing required fields and inconsistent totals; empty when the
+ * invoice passed validation
+ */
+issues: string[] }
+export type XmlLine = { 
+/**
+ * Line kind (TChat): 1 goods, 2 promotion, 3 discount, 4 note
+ */
+kind: number; name: string; unit: string; quantity: number; unit_price: number; amount: number; 
+/**
+ * Rate as written, e.g. "10%", "KCT"
+ */
+tax_rate: string }
+export type XmlParty = { name: string; tax_code: string; address: string }
+export type XmlTaxRate = { tax_rate: string; taxable_amount: number; tax_amount: number }
+
+/** tauri-specta globals **/
+
+import {
+	invoke as TAURI_INVOKE,
+	Channel as TAURI_CHANNEL,
+} from "@tauri-apps/api/core";
+import * as TAURI_API_EVENT from "@tauri-apps/api/event";
+import { type WebviewWindow as __WebviewWindow__ } from "@tauri-apps/api/webviewWindow";
+
+type __EventObj__<T> = {
+	listen: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.listen<T>>;
+	once: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.once<T>>;
+	emit: null extends T
+		? (payload?: T) => ReturnType<typeof TAURI_API_EVENT.emit>
+		: (payload: T) => ReturnType<typeof TAURI_API_EVENT.emit>;
+};
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
+
+function __makeEvents__<T extends Record<string, any>>(
+	mappings: Record<keyof T, string>,
+) {
+	return new Proxy(
+		{} as unknown as {
+			[K in keyof T]: __EventObj__<T[K]> & {
+				(handle: __WebviewWindow__): __EventObj__<T[K]>;
+			};
+		},
+		{
+			get: (_, event) => {
+				const name = mappings[event as keyof T];
+
+				return new Proxy((() => {}) as any, {
+					apply: (_, __, [window]: [__WebviewWindow__]) => ({
+						listen: (arg: any) => window.listen(name, arg),
+						once: (arg: any) => window.once(name, arg),
+						emit: (arg: any) => window.emit(name, arg),
+					}),
+					get: (_, command: keyof __EventObj__<any>) => {
+						switch (command) {
+							case "listen":
+								return (arg: any) => TAURI_API_EVENT.listen(name, arg);
+							case "once":
+								return (arg: any) => TAURI_API_EVENT.once(name, arg);
+							case "emit":
+								return (arg: any) => TAURI_API_EVENT.emit(name, arg);
+						}
+					},
+				});
+			},
+		},
+	);
+}
@@ -0,0 +1,70 @@
+import { useCallback, useEffect, useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { commands, EVENT_NAMES, type BrowserPreviewEvent } from '../../bindings';
+import { unwrap } from '../../utils/errors';
+
+interface BrowserPreviewProps {
+  batchId: string | null;
+  isDownloading: boolean;
+}
+
+// Live screenshots of the page the bot is working on, without a visible browser
+export function BrowserPreview({ batchId, isDownloading }: BrowserPreviewProps) {
+  const [enabled, setEnabled] = useState(false);
+  const [frame, setFrame] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!enabled || !batchId) return;
+
+    const unlisten = listen<BrowserPreviewEvent>(EVENT_NAMES.browserPreview, (event) => {
+      if (event.payload.batch_id === batchId) {
+        setFrame(event.payload.image_base64);
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [enabled, batchId]);
+
+  // A finished batch stops streaming on its own
+  useEffect(() => {
+    if (!isDownloading) {
+      setEnabled(false);
+      setFrame(null);
+    }
+  }, [isDownloading]);
+
+  const handleToggle = useCallback(async () => {
+    if (!batchId) return;
+    try {
+      unwrap(await commands.streamBrowserPreview(batchId, !enabled));
+      setEnabled(!enabled);
+      if (enabled) setFrame(null);
+    } catch (err) {
+      console.error('Failed to toggle browser preview:', err);
+    }
+  }, [batchId, enabled]);
+
+  if (!isDownloading) return null;
+
+  return (
+    <>
+      <button
+        onClick={handleToggle}
+        className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors"
+      >
+        {enabled ? 'Ẩn trình duyệt' : 'Xem trình duyệt'}
+      </button>
+      {enabled && (
+        <div className="fixed bottom-6 right-6 w-96 bg-white rounded-xl shadow-2xl border border-gray-200 overflow-hidden z-40">
+          <div className="px-3 py-2 border-b border-gray-200 text-xs text-gray-500">Trình duyệt đang tải</div>
+          {frame ? (
+            <img src={`data:image/jpeg;base64,${frame}`} alt="Xem trước trình duyệt" className="w-full h-auto" />
+          ) : (
+            <div className="h-48 flex items-center justify-center text-sm text-gray-400">Đang chờ ảnh...</div>
+          )}
+        </div>
+      )}
+    </>
+  );
+}
@@ -1,51 +1,96 @@
-import { useState, useCallback } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { useState, useCallback, useEffect } from 'react';
+import { commands, type PendingCaptcha } from '../../bindings';
 import { useDownload } from '../../store';
+import { errorMessage, unwrap } from '../../utils/errors';
 
 export function CaptchaModal() {
   const { captchaRequest, setCaptchaRequest, addLog } = useDownload();
   const [captchaInput, setCaptchaInput] = useState('');
   const [isSubmitting, setIsSubmitting] = useState(false);
+  const [pending, setPending] = useState<PendingCaptcha[]>([]);
+
+  // Other invoices may be waiting too; keep the queue so they can be solved in a row
+  useEffect(() => {
+    if (!captchaRequest) return;
+    commands
+      .getPendingCaptchas()
+      .then(setPending)
+      .catch((err) => console.error('Failed to load pending captchas:', err));
+  }, [captchaRequest]);
+
+  // Move on to the oldest invoice still waiting, or close the modal
+  const showNext = useCallback(
+    async (doneInvoiceId: string) => {
+      try {
+        const waiting = await commands.getPendingCaptchas();
+        setCaptchaRequest(waiting.find((c) => c.invoice_id !== doneInvoiceId) ?? null);
+      } catch (err) {
+        console.error('Failed to load pending captchas:', err);
+        setCaptchaRequest(null);
+      }
+    },
+    [setCaptchaRequest]
+  );
 
   const handleSubmit = useCallback(async () => {
     if (!captchaInput.trim() || !captchaRequest) return;
 
     setIsSubmitting(true);
     try {
-      await invoke('submit_manual_captcha', {
-        invoiceId: captchaRequest.invoice_id,
-        captchaText: captchaInput.trim(),
-      });
+      unwrap(
+        await commands.submitManualCaptcha(captchaRequest.batch_id, captchaRequest.invoice_id, captchaInput.trim())
+      );
       addLog({
         timestamp: new Date().toISOString(),
         level: 'info',
         message: `Đã gửi captcha thủ công cho ${captchaRequest.invoice_code}`,
       });
-      setCaptchaRequest(null);
       setCaptchaInput('');
+      await showNext(captchaRequest.invoice_id);
     } catch (err) {
       console.error('Failed to submit captcha:', err);
       addLog({
         timestamp: new Date().toISOString(),
         level: 'error',
-        message: `Lỗi gửi captcha: ${err}`,
+        message: `Lỗi gửi captcha: ${errorMessage(err)}`,
       });
     } finally {
       setIsSubmitting(false);
     }
-  }, [captchaInput, captchaRequest, setCaptchaRequest, addLog]);
+  }, [captchaInput, captchaRequest, showNext, addLog]);
 
-  const handleSkip = useCallback(() => {
-    if (captchaRequest) {
+  const handleSkip = useCallback(async () => {
+    if (!captchaRequest) return;
+    try {
+      unwrap(await commands.skipManualCaptcha(captchaRequest.batch_id, captchaRequest.invoice_id));
       addLog({
         timestamp: new Date().toISOString(),
         level: 'warn',
         message: `Đã bỏ qua captcha cho ${captchaRequest.invoice_code}`,
       });
+    } catch (err) {
+      console.error('Failed to skip captcha:', err);
+    }
+    setCaptchaInput('');
+    await showNext(captchaRequest.invoice_id);
+  }, [captchaRequest, showNext, addLog]);
+
+  // Leave every invoice of the batch needing a captcha for a later pass
+  const handleDeferAll = useCallback(async () => {
+    if (!captchaRequest) return;
+    try {
+      unwrap(await commands.setDeferManualCaptchas(captchaRequest.batch_id, true));
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: 'warn',
+        message: 'Các hóa đơn cần nhập captcha sẽ được để lại cho lượt tải sau',
+      });
+    } catch (err) {
+      console.error('Failed to defer captchas:', err);
     }
-    setCaptchaRequest(null);
     setCaptchaInput('');
-  }, [captchaRequest, setCaptchaRequest, addLog]);
+    await showNext(captchaRequest.invoice_id);
+  }, [captchaRequest, showNext, addLog]);
 
   const handleKeyDown = useCallback(
     (e: React.KeyboardEvent) => {
@@ -69,6 +114,11 @@ export function CaptchaModal() {
           <p className="text-sm text-gray-500 mt-1">
             Giải captcha tự động thất bại cho: <span className="font-mono">{captchaRequest.invoice_code}</span>
           </p>
+          {pending.length > 1 && (
+            <p className="text-xs text-orange-600 mt-1">
+              {pending.length} hóa đơn đang chờ nhập captcha
+            </p>
+          )}
         </div>
 
         {/* Captcha Image */}
@@ -109,6 +159,14 @@ export function CaptchaModal() {
             >
               Bỏ qua
             </button>
+            <button
+              onClick={handleDeferAll}
+              disabled={isSubmitting}
+              className="flex-1 px-4 py-2.5 border border-gray-300 text-gray-700 rounded-lg hover:bg-gray-50 transition-colors disabled:opacity-50"
+              title="Không hỏi captcha nữa trong lô này; các hóa đơn được đánh dấu để tải lại sau"
+            >
+              Để sau tất cả
+            </button>
             <button
               onClick={handleSubmit}
               disabled={!captchaInput.trim() || isSubmitting}
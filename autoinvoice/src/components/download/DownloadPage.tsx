@@ -1,10 +1,17 @@
-import { useCallback, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
-import { open } from '@tauri-apps/plugin-dialog';
+import { useCallback, useEffect, useState } from 'react';
 import { useDownload, useSettings } from '../../store';
 import { InvoiceList } from './InvoiceList';
 import { LogViewer } from './LogViewer';
 import { CaptchaModal } from './CaptchaModal';
+import { BrowserPreview } from './BrowserPreview';
+import { commands, type BatchEstimate, type StartDownloadRequest } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
+
+function formatDuration(totalSecs: number) {
+  const hours = Math.floor(totalSecs / 3600);
+  const minutes = Math.ceil((totalSecs % 3600) / 60);
+  return hours > 0 ? `${hours} giờ ${minutes} phút` : `${minutes} phút`;
+}
 
 export function DownloadPage() {
   const {
@@ -15,38 +22,126 @@ export function DownloadPage() {
     logs,
     captchaRequest,
     downloadDirectory,
+    batchId,
+    uploadProgress,
+    budgetExceeded,
+    sourceFile,
     setDownloadDirectory,
     setStatus,
     setBatchId,
+    setBudgetExceeded,
     addLog,
     clearLogs,
   } = useDownload();
 
-  const { settings, loadSettings } = useSettings();
+  const { settings, loadSettings, setSettings } = useSettings();
 
   // Load settings on mount to ensure API key is available
   useEffect(() => {
     loadSettings();
   }, [loadSettings]);
 
+  const [estimate, setEstimate] = useState<BatchEstimate | null>(null);
+  // Applies to the next batch only; the saved settings are left unchanged
+  const [showBrowser, setShowBrowser] = useState(false);
+  const [deferManualCaptchas, setDeferManualCaptchas] = useState(false);
+  const [twoPass, setTwoPass] = useState(false);
+  const [recordFixtures, setRecordFixtures] = useState(false);
+  // Backend log level, applied when the next batch starts
+  const [debugLogging, setDebugLogging] = useState(false);
+
+  useEffect(() => {
+    commands
+      .getLogLevel()
+      .then((level) => setDebugLogging(level === 'debug'))
+      .catch((err) => console.error('Failed to read log level:', err));
+  }, []);
+
+  const handleDebugLogging = useCallback(async (enabled: boolean) => {
+    try {
+      await commands.setLogLevel(enabled ? 'debug' : 'info');
+      setDebugLogging(enabled);
+    } catch (err) {
+      console.error('Failed to set log level:', err);
+    }
+  }, []);
+
+  // Defaults to the Excel file name without its extension
+  const [batchName, setBatchName] = useState('');
+
+  useEffect(() => {
+    setBatchName(sourceFile ? sourceFile.name.replace(/\.xlsx$/i, '') : '');
+  }, [sourceFile]);
+
+  // Estimate duration and AI cost before the user starts a batch
+  useEffect(() => {
+    if (status !== 'ready' || invoices.length === 0) {
+      setEstimate(null);
+      return;
+    }
+    commands
+      .estimateBatch(invoices.length)
+      .then((result) => setEstimate(unwrap(result)))
+      .catch((err) => console.error('Failed to estimate batch:', err));
+  }, [status, invoices.length, settings.invoice_delay_secs]);
+
   const canStart = status === 'ready' && invoices.length > 0 && downloadDirectory;
   const isDownloading = status === 'downloading';
   const isPaused = status === 'paused';
 
   const handleSelectDirectory = useCallback(async () => {
     try {
-      const selected = await open({
-        directory: true,
-        multiple: false,
-        title: 'Chọn thư mục tải xuống',
-      });
-      if (selected && typeof selected === 'string') {
+      // Batches only write inside the saved download directory, so the
+      // choice is saved through the backend's dialog
+      const selected = unwrap(await commands.chooseDownloadDirectory());
+      if (selected) {
         setDownloadDirectory(selected);
+        setSettings({ download_directory: selected });
       }
     } catch (err) {
       console.error('Failed to select directory:', err);
     }
-  }, [setDownloadDirectory]);
+  }, [setDownloadDirectory, setSettings]);
+
+  const buildRequest = useCallback(
+    (batchId: string): StartDownloadRequest => ({
+      batch_id: batchId,
+      name: batchName.trim() || null,
+      source_file: sourceFile?.name ?? null,
+      source_path: sourceFile?.path ?? null,
+      source_hash: sourceFile?.hash ?? null,
+      invoices: invoices.map((inv) => ({
+        id: inv.id,
+        code: inv.code,
+        seller_tax_code: inv.seller_tax_code ?? null,
+        lookup_mode: inv.lookup_mode ?? 'code',
+        invoice_series: inv.invoice_series ?? null,
+        invoice_number: inv.invoice_number ?? null,
+        portal_url: inv.portal_url ?? null,
+      })),
+      // Everything else is read from the saved settings by the backend
+      overrides: {
+        vnpt_url: detectedVnptUrl || null,
+        download_directory: downloadDirectory || null,
+        headless: showBrowser ? false : null,
+        max_retries: null,
+        defer_manual_captchas: deferManualCaptchas || null,
+        two_pass: twoPass || null,
+        record_fixtures: recordFixtures || null,
+      },
+    }),
+    [
+      invoices,
+      detectedVnptUrl,
+      downloadDirectory,
+      showBrowser,
+      deferManualCaptchas,
+      twoPass,
+      recordFixtures,
+      batchName,
+      sourceFile,
+    ]
+  );
 
   const handleStartDownload = useCallback(async () => {
     if (!canStart) return;
@@ -61,50 +156,57 @@ export function DownloadPage() {
 
       setStatus('downloading');
 
-      const vnptUrl = detectedVnptUrl || settings.vnptUrl;
       const batchId = crypto.randomUUID();
       setBatchId(batchId);
 
-      // Build request matching Rust StartDownloadRequest structure
-      const request = {
-        batch_id: batchId,
-        invoices: invoices.map((inv) => ({
-          id: inv.id,
-          code: inv.code,
-        })),
-        config: {
-          vnpt_url: vnptUrl,
-          openai_api_key: settings.openaiApiKey,
-          download_directory: downloadDirectory,
-          headless: true,
-        },
-      };
-
-      await invoke('start_download', { request });
+      // Returns at once; the batch reports through its events until batch:completed
+      const ack = unwrap(await commands.startDownload(buildRequest(batchId)));
+      if (!ack.started) {
+        addLog({
+          timestamp: new Date().toISOString(),
+          level: 'warn',
+          message: 'Lô này đã được bắt đầu trước đó, không chạy lại',
+        });
+      }
     } catch (err) {
       console.error('Failed to start download:', err);
       setStatus('ready');
       addLog({
         timestamp: new Date().toISOString(),
         level: 'error',
-        message: `Lỗi khởi động: ${err}`,
+        message: `Lỗi khởi động: ${errorMessage(err)}`,
       });
     }
-  }, [
-    canStart,
-    invoices,
-    detectedVnptUrl,
-    settings,
-    downloadDirectory,
-    clearLogs,
-    addLog,
-    setStatus,
-    setBatchId,
-  ]);
+  }, [canStart, buildRequest, clearLogs, addLog, setStatus, setBatchId]);
+
+  // Save the batch to start later, e.g. when there is no network yet
+  const handleQueueDownload = useCallback(async () => {
+    if (!canStart) return;
+
+    try {
+      unwrap(await commands.queueDownload(buildRequest(crypto.randomUUID())));
+      setStatus('queued');
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: 'info',
+        message: settings.auto_start_queued
+          ? 'Đã xếp hàng, sẽ tự động tải khi có mạng'
+          : 'Đã xếp hàng, bắt đầu tải từ mục Lịch sử',
+      });
+    } catch (err) {
+      console.error('Failed to queue download:', err);
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: 'error',
+        message: `Lỗi xếp hàng: ${errorMessage(err)}`,
+      });
+    }
+  }, [canStart, buildRequest, settings.auto_start_queued, addLog, setStatus]);
 
   const handleCancelDownload = useCallback(async () => {
+    if (!batchId) return;
     try {
-      await invoke('cancel_download');
+      unwrap(await commands.cancelDownload(batchId));
       setStatus('cancelled');
       addLog({
         timestamp: new Date().toISOString(),
@@ -114,7 +216,26 @@ export function DownloadPage() {
     } catch (err) {
       console.error('Failed to cancel download:', err);
     }
-  }, [setStatus, addLog]);
+  }, [batchId, setStatus, addLog]);
+
+  const handleContinueOverBudget = useCallback(async () => {
+    if (!batchId) return;
+    try {
+      unwrap(await commands.continueOverBudget(batchId));
+      setBudgetExceeded(null);
+    } catch (err) {
+      console.error('Failed to continue over budget:', err);
+    }
+  }, [batchId, setBudgetExceeded]);
+
+  // Also stops batches started from History or the offline queue
+  const handleCancelAll = useCallback(async () => {
+    try {
+      unwrap(await commands.cancelAllDownloads());
+    } catch (err) {
+      console.error('Failed to cancel downloads:', err);
+    }
+  }, []);
 
   const completedCount = invoices.filter((i) => i.status === 'success').length;
   const failedCount = invoices.filter((i) => i.status === 'failed').length;
@@ -160,6 +281,12 @@ export function DownloadPage() {
                 style={{ width: `${progressPercent}%` }}
               />
             </div>
+            {uploadProgress && (
+              <p className="text-xs text-gray-500 mt-2 truncate" title={uploadProgress.file_name}>
+                Tải lên {uploadProgress.target}: {uploadProgress.current} / {uploadProgress.total} ·{' '}
+                {uploadProgress.file_name}
+              </p>
+            )}
           </div>
         </div>
 
@@ -194,6 +321,48 @@ export function DownloadPage() {
           </button>
         )}
 
+        {(isDownloading || isPaused) && (
+          <button
+            onClick={handleCancelAll}
+            className="px-4 py-2.5 border border-red-300 text-red-700 rounded-lg hover:bg-red-50 transition-colors"
+          >
+            Hủy tất cả
+          </button>
+        )}
+
+        {!isDownloading && !isPaused && (
+          <button
+            onClick={handleQueueDownload}
+            disabled={!canStart}
+            title="Lưu lô để tải sau, khi có kết nối mạng"
+            className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+          >
+            Tải sau
+          </button>
+        )}
+
+        {budgetExceeded && (isDownloading || isPaused) && (
+          <button
+            onClick={handleContinueOverBudget}
+            title={`Đã dùng $${budgetExceeded.spent_usd.toFixed(4)} / $${budgetExceeded.limit_usd.toFixed(2)}`}
+            className="px-4 py-2.5 border border-amber-300 text-amber-700 rounded-lg hover:bg-amber-50 transition-colors"
+          >
+            Tiếp tục dùng AI, vượt ngân sách
+          </button>
+        )}
+
+        <BrowserPreview batchId={batchId} isDownloading={isDownloading} />
+
+        {/* Estimate */}
+        {estimate && status === 'ready' && (
+          <span
+            className="text-sm text-gray-500"
+            title={estimate.from_history ? `Dựa trên ${estimate.sample_size} hóa đơn đã tải` : 'Chưa đủ dữ liệu, dùng giá trị mặc định'}
+          >
+            Ước tính: ~{formatDuration(estimate.estimated_duration_secs)}, ~${estimate.estimated_cost_usd.toFixed(2)}
+          </span>
+        )}
+
         {/* Status indicator */}
         <div className="flex items-center gap-2">
           {status === 'downloading' && (
@@ -208,6 +377,12 @@ export function DownloadPage() {
               <span className="text-sm text-green-600">Hoàn thành</span>
             </>
           )}
+          {status === 'queued' && (
+            <>
+              <div className="w-2 h-2 bg-gray-400 rounded-full" />
+              <span className="text-sm text-gray-600">Đang chờ mạng</span>
+            </>
+          )}
           {status === 'cancelled' && (
             <>
               <div className="w-2 h-2 bg-orange-500 rounded-full" />
@@ -218,6 +393,75 @@ export function DownloadPage() {
 
         {/* Directory selector */}
         <div className="ml-auto flex items-center gap-2">
+          <input
+            type="text"
+            value={batchName}
+            onChange={(e) => setBatchName(e.target.value)}
+            disabled={isDownloading}
+            placeholder="Tên lô"
+            className="w-40 px-3 py-1.5 text-sm border border-gray-300 rounded-lg mr-2"
+          />
+          <label className="flex items-center gap-2 text-sm text-gray-500 mr-2">
+            <input
+              type="checkbox"
+              checked={showBrowser}
+              onChange={(e) => setShowBrowser(e.target.checked)}
+              disabled={isDownloading}
+              className="rounded border-gray-300"
+            />
+            Hiện trình duyệt
+          </label>
+          <label
+            className="flex items-center gap-2 text-sm text-gray-500 mr-2"
+            title="Ghi thêm selector đã thử và phản hồi mạng của trình duyệt cho lô tiếp theo"
+          >
+            <input
+              type="checkbox"
+              checked={debugLogging}
+              onChange={(e) => handleDebugLogging(e.target.checked)}
+              className="rounded border-gray-300"
+            />
+            Log chi tiết
+          </label>
+          <label
+            className="flex items-center gap-2 text-sm text-gray-500 mr-2"
+            title="Không chờ nhập captcha thủ công; các hóa đơn đó được đánh dấu để tải lại sau"
+          >
+            <input
+              type="checkbox"
+              checked={deferManualCaptchas}
+              onChange={(e) => setDeferManualCaptchas(e.target.checked)}
+              disabled={isDownloading}
+              className="rounded border-gray-300"
+            />
+            Chạy không cần người
+          </label>
+          <label
+            className="flex items-center gap-2 text-sm text-gray-500 mr-2"
+            title="Tải tự động mọi hóa đơn trước, sau đó lần lượt hỏi captcha cho các hóa đơn còn lại"
+          >
+            <input
+              type="checkbox"
+              checked={twoPass}
+              onChange={(e) => setTwoPass(e.target.checked)}
+              disabled={isDownloading}
+              className="rounded border-gray-300"
+            />
+            Hai lượt
+          </label>
+          <label
+            className="flex items-center gap-2 text-sm text-gray-500 mr-2"
+            title="Lưu trang, captcha và PDF của từng hóa đơn vào thư mục fixtures để chạy lại khi kiểm thử, không cần mạng"
+          >
+            <input
+              type="checkbox"
+              checked={recordFixtures}
+              onChange={(e) => setRecordFixtures(e.target.checked)}
+              disabled={isDownloading}
+              className="rounded border-gray-300"
+            />
+            Ghi fixture
+          </label>
           <span className="text-sm text-gray-500">Lưu vào:</span>
           <button
             onClick={handleSelectDirectory}
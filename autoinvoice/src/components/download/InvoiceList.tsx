@@ -1,7 +1,7 @@
-import type { InvoiceCode } from '../../types';
+import type { InvoiceRow } from '../../types';
 
 interface InvoiceListProps {
-  invoices: InvoiceCode[];
+  invoices: InvoiceRow[];
 }
 
 const statusConfig = {
@@ -62,7 +62,7 @@ export function InvoiceList({ invoices }: InvoiceListProps) {
   return (
     <div className="divide-y divide-gray-100">
       {invoices.map((invoice) => {
-        const config = statusConfig[invoice.status];
+        const config = statusConfig[invoice.status as keyof typeof statusConfig] || statusConfig.pending;
         return (
           <div
             key={invoice.id}
@@ -6,12 +6,14 @@ interface LogViewerProps {
 }
 
 const levelColors = {
+  debug: 'text-gray-500',
   info: 'text-blue-400',
   warn: 'text-yellow-400',
   error: 'text-red-400',
 };
 
 const levelLabels = {
+  debug: 'DBG ',
   info: 'INFO',
   warn: 'WARN',
   error: 'ERR!',
@@ -64,6 +66,11 @@ export function LogViewer({ logs }: LogViewerProps) {
                   {levelLabels[log.level]}
                 </span>
                 <span className="text-gray-300 break-all">{log.message}</span>
+                {log.request_id && (log.level === 'warn' || log.level === 'error') && (
+                  <span className="text-gray-600 shrink-0" title="Mã yêu cầu trong nhật ký backend">
+                    #{log.request_id}
+                  </span>
+                )}
               </div>
             ))}
           </div>
@@ -2,3 +2,4 @@ export { DownloadPage } from './DownloadPage';
 export { InvoiceList } from './InvoiceList';
 export { LogViewer } from './LogViewer';
 export { CaptchaModal } from './CaptchaModal';
+export { BrowserPreview } from './BrowserPreview';
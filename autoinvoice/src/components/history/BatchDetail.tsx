@@ -1,7 +1,8 @@
-import { useEffect, useCallback, useMemo } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { useEffect, useCallback, useMemo, useState } from 'react';
+import { save } from '@tauri-apps/plugin-dialog';
+import { commands, type ExportFormat, type HistoryInvoice, type InvoiceAnomaly } from '../../bindings';
 import { useHistory, useDownload } from '../../store';
-import type { HistoryInvoice } from '../../types';
+import { unwrap } from '../../utils/errors';
 
 interface BatchDetailProps {
   batchId: string;
@@ -29,10 +30,38 @@ const statusConfig = {
     text: 'text-red-600',
     label: 'Thất bại',
   },
+  skipped: {
+    bg: 'bg-yellow-100',
+    text: 'text-yellow-600',
+    label: 'Bỏ qua',
+  },
+  cancelled: {
+    bg: 'bg-gray-100',
+    text: 'text-gray-500',
+    label: 'Đã hủy',
+  },
+  'needs-manual': {
+    bg: 'bg-orange-100',
+    text: 'text-orange-600',
+    label: 'Cần nhập captcha',
+  },
+  'not-found': {
+    bg: 'bg-purple-100',
+    text: 'text-purple-600',
+    label: 'Không tìm thấy',
+  },
+  ignored: {
+    bg: 'bg-gray-100',
+    text: 'text-gray-500',
+    label: 'Đã bỏ qua',
+  },
 };
 
+// Statuses the user can mark as ignored
+const ignorableStatuses = ['failed', 'not-found', 'needs-manual'];
+
 export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
-  const { batches, batchInvoices, isLoading, loadBatchInvoices } = useHistory();
+  const { batches, batchInvoices, isLoading, loadBatches, loadBatchInvoices } = useHistory();
   const { setInvoices, setStatus } = useDownload();
 
   const batch = useMemo(
@@ -40,8 +69,15 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
     [batches, batchId]
   );
 
+  const [anomalies, setAnomalies] = useState<InvoiceAnomaly[]>([]);
+  const [selectedIds, setSelectedIds] = useState<Set<string>>(new Set());
+
   useEffect(() => {
     loadBatchInvoices(batchId);
+    commands
+      .getBatchAnomalies(batchId)
+      .then((result) => setAnomalies(unwrap(result)))
+      .catch((err) => console.error('Failed to load batch anomalies:', err));
   }, [batchId, loadBatchInvoices]);
 
   const failedInvoices = useMemo(
@@ -49,18 +85,78 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
     [batchInvoices]
   );
 
+  // Deferred by an unattended run; downloaded again in an interactive pass
+  const needsManualInvoices = useMemo(
+    () => batchInvoices.filter((inv) => inv.status === 'needs-manual'),
+    [batchInvoices]
+  );
+
+  // A broken signature means the XML may have been altered after issuing
+  const invalidSignatureInvoices = useMemo(
+    () => batchInvoices.filter((inv) => inv.signature_status === 'invalid'),
+    [batchInvoices]
+  );
+
+  const handleSolveManual = useCallback(() => {
+    if (needsManualInvoices.length === 0) return;
+
+    setInvoices(
+      needsManualInvoices.map((inv) => ({
+        id: inv.id,
+        code: inv.code,
+        row_number: 0,
+        seller_tax_code: null,
+        lookup_mode: 'code' as const,
+        invoice_series: null,
+        invoice_number: null,
+        portal_url: null,
+        status: 'pending' as const,
+      })),
+      null
+    );
+    setStatus('ready');
+    alert('Đã tải các hóa đơn cần nhập captcha. Vào trang Tải xuống để bắt đầu và nhập captcha khi được hỏi.');
+  }, [needsManualInvoices, setInvoices, setStatus]);
+
+  // Import the batch's Excel file again, e.g. after rows were corrected
+  const handleReparseSource = useCallback(async () => {
+    if (!batch) return;
+
+    try {
+      const result = unwrap(await commands.reparseSource(batchId));
+      if (result.file_hash !== batch.source_hash) {
+        alert('File nguồn đã thay đổi kể từ khi tải lô này.');
+      }
+      setInvoices(
+        result.invoices.map((inv) => ({ ...inv, status: 'pending' as const })),
+        result.detected_url,
+        batch.download_directory,
+        { name: result.file_name, path: result.file_path, hash: result.file_hash }
+      );
+      alert(`Đã đọc lại ${result.invoices.length} hóa đơn. Vào trang Tải xuống để bắt đầu.`);
+    } catch (err) {
+      console.error('Failed to reparse source:', err);
+      alert('Không thể đọc lại file nguồn: ' + err);
+    }
+  }, [batch, batchId, setInvoices]);
+
   const handleRedownloadFailed = useCallback(async () => {
     if (failedInvoices.length === 0) return;
 
     try {
       // Get failed invoices from backend
-      const failed = await invoke<HistoryInvoice[]>('get_failed_invoices', { batchId });
+      const failed = unwrap(await commands.getFailedInvoices(batchId));
 
       // Convert to invoice codes for download
       const invoiceCodes = failed.map((inv) => ({
         id: inv.id,
         code: inv.code,
         row_number: 0,
+        seller_tax_code: null,
+        lookup_mode: 'code' as const,
+        invoice_series: null,
+        invoice_number: null,
+        portal_url: null,
         status: 'pending' as const,
       }));
 
@@ -76,6 +172,120 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
     }
   }, [batchId, failedInvoices.length, setInvoices, setStatus]);
 
+  // List of the codes that did not download, to send back to the supplier
+  const handleExportFailed = useCallback(async () => {
+    try {
+      const destination = await save({
+        defaultPath: `hoa-don-loi-${batchId}.csv`,
+        filters: [
+          { name: 'CSV', extensions: ['csv'] },
+          { name: 'JSON', extensions: ['json'] },
+        ],
+      });
+      if (!destination) return;
+
+      const format: ExportFormat = destination.toLowerCase().endsWith('.json') ? 'json' : 'csv';
+      const path = unwrap(await commands.exportBatchInvoices(batchId, ['failed', 'not-found'], format, destination));
+      alert('Đã lưu danh sách: ' + path);
+    } catch (err) {
+      console.error('Failed to export invoices:', err);
+      alert('Lỗi khi xuất danh sách: ' + err);
+    }
+  }, [batchId]);
+
+  const toggleSelected = useCallback((invoiceId: string) => {
+    setSelectedIds((prev) => {
+      const next = new Set(prev);
+      if (next.has(invoiceId)) {
+        next.delete(invoiceId);
+      } else {
+        next.add(invoiceId);
+      }
+      return next;
+    });
+  }, []);
+
+  // Stops invoices that will never download, e.g. cancelled by the seller,
+  // from counting as failed and coming up for re-download
+  const handleIgnoreSelected = useCallback(async () => {
+    if (selectedIds.size === 0) return;
+
+    const note = prompt('Lý do bỏ qua (ví dụ: hóa đơn đã bị người bán hủy):');
+    if (!note?.trim()) return;
+
+    try {
+      const count = unwrap(await commands.ignoreInvoices(Array.from(selectedIds), note));
+      setSelectedIds(new Set());
+      await loadBatches();
+      await loadBatchInvoices(batchId);
+      alert(`Đã bỏ qua ${count} hóa đơn.`);
+    } catch (err) {
+      console.error('Failed to ignore invoices:', err);
+      alert('Lỗi khi bỏ qua hóa đơn: ' + err);
+    }
+  }, [batchId, selectedIds, loadBatches, loadBatchInvoices]);
+
+  // E.g. why an invoice failed or where it was obtained by hand
+  const handleEditNote = useCallback(
+    async (invoice: HistoryInvoice) => {
+      const note = prompt(`Ghi chú cho hóa đơn ${invoice.code}:`, invoice.note ?? '');
+      if (note === null) return;
+
+      try {
+        unwrap(await commands.setInvoiceNote(invoice.id, note));
+        await loadBatchInvoices(batchId);
+      } catch (err) {
+        console.error('Failed to save invoice note:', err);
+        alert('Lỗi khi lưu ghi chú: ' + err);
+      }
+    },
+    [batchId, loadBatchInvoices]
+  );
+
+  const handlePrintSummary = useCallback(async () => {
+    try {
+      const destination = await save({
+        defaultPath: `bao-cao-${batchId}.pdf`,
+        filters: [{ name: 'PDF', extensions: ['pdf'] }],
+      });
+      if (!destination) return;
+
+      const path = unwrap(await commands.printBatchSummary(batchId, destination));
+      alert('Đã lưu báo cáo: ' + path);
+    } catch (err) {
+      console.error('Failed to print batch summary:', err);
+      alert('Lỗi khi tạo báo cáo: ' + err);
+    }
+  }, [batchId]);
+
+  // Bundles are often emailed, so offer to encrypt them; a generated
+  // password is not stored and can only be shown now
+  const handlePackage = useCallback(async () => {
+    try {
+      const destination = await save({
+        defaultPath: `hoa-don-${batchId}.zip`,
+        filters: [{ name: 'ZIP', extensions: ['zip'] }],
+      });
+      if (!destination) return;
+
+      const encrypt = confirm(
+        'Mã hóa file ZIP (AES-256) bằng mật khẩu dùng một lần?\nNên chọn khi gửi hóa đơn qua email.'
+      );
+      const result = unwrap(await commands.packageBatch(batchId, destination, encrypt, null));
+      if (result.generated_password) {
+        prompt(
+          `Đã lưu ${result.path}.\nMật khẩu chỉ hiển thị một lần, hãy sao chép và gửi riêng cho người nhận:`,
+          result.generated_password
+        );
+      } else {
+        alert('Đã lưu: ' + result.path);
+      }
+    } catch (err) {
+      console.error('Failed to package batch:', err);
+      alert('Lỗi khi đóng gói: ' + err);
+    }
+  }, [batchId]);
+
   const formatDate = (dateStr: string | null) => {
     if (!dateStr) return '-';
     try {
@@ -130,8 +340,47 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
             </p>
           </div>
         </div>
-        {failedInvoices.length > 0 && (
-          <div className="mt-4 pt-4 border-t border-gray-200">
+        <div className="mt-4 pt-4 border-t border-gray-200 flex gap-2">
+          <button
+            onClick={handlePrintSummary}
+            className="px-4 py-2 bg-gray-100 text-gray-700 rounded-lg hover:bg-gray-200 transition-colors text-sm flex items-center gap-2"
+          >
+            <svg className="w-4 h-4" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+              <path
+                strokeLinecap="round"
+                strokeLinejoin="round"
+                strokeWidth={2}
+                d="M17 17h2a2 2 0 002-2v-4a2 2 0 00-2-2H5a2 2 0 00-2 2v4a2 2 0 002 2h2m2 4h6a2 2 0 002-2v-4a2 2 0 00-2-2H9a2 2 0 00-2 2v4a2 2 0 002 2zm8-12V5a2 2 0 00-2-2H9a2 2 0 00-2 2v4h10z"
+              />
+            </svg>
+            In báo cáo
+          </button>
+          {batch.success_count > 0 && (
+            <button
+              onClick={handlePackage}
+              className="px-4 py-2 bg-gray-100 text-gray-700 rounded-lg hover:bg-gray-200 transition-colors text-sm"
+            >
+              Đóng gói ZIP
+            </button>
+          )}
+          {batch.failed_count > 0 && (
+            <button
+              onClick={handleExportFailed}
+              className="px-4 py-2 bg-gray-100 text-gray-700 rounded-lg hover:bg-gray-200 transition-colors text-sm"
+            >
+              Xuất danh sách lỗi
+            </button>
+          )}
+          {batch.source_path && (
+            <button
+              onClick={handleReparseSource}
+              title={batch.source_path}
+              className="px-4 py-2 bg-gray-100 text-gray-700 rounded-lg hover:bg-gray-200 transition-colors text-sm"
+            >
+              Đọc lại file nguồn
+            </button>
+          )}
+          {failedInvoices.length > 0 && (
             <button
               onClick={handleRedownloadFailed}
               className="px-4 py-2 bg-orange-600 text-white rounded-lg hover:bg-orange-700 transition-colors text-sm flex items-center gap-2"
@@ -146,10 +395,48 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
               </svg>
               Tải lại {failedInvoices.length} hóa đơn thất bại
             </button>
-          </div>
-        )}
+          )}
+          {needsManualInvoices.length > 0 && (
+            <button
+              onClick={handleSolveManual}
+              className="px-4 py-2 bg-blue-600 text-white rounded-lg hover:bg-blue-700 transition-colors text-sm"
+            >
+              Nhập captcha cho {needsManualInvoices.length} hóa đơn
+            </button>
+          )}
+          {selectedIds.size > 0 && (
+            <button
+              onClick={handleIgnoreSelected}
+              className="px-4 py-2 bg-gray-600 text-white rounded-lg hover:bg-gray-700 transition-colors text-sm"
+            >
+              Bỏ qua {selectedIds.size} hóa đơn đã chọn
+            </button>
+          )}
+        </div>
       </div>
 
+      {invalidSignatureInvoices.length > 0 && (
+        <div className="bg-red-50 border border-red-200 text-red-700 rounded-xl px-4 py-3 text-sm">
+          {invalidSignatureInvoices.length} hóa đơn có chữ ký số không hợp lệ:{' '}
+          <span className="font-mono">
+            {invalidSignatureInvoices.map((inv) => inv.code).join(', ')}
+          </span>
+        </div>
+      )}
+
+      {anomalies.length > 0 && (
+        <div className="bg-amber-50 border border-amber-200 text-amber-800 rounded-xl px-4 py-3 text-sm">
+          <p className="font-medium">{anomalies.length} hóa đơn cần kiểm tra lại:</p>
+          <ul className="mt-1 space-y-0.5">
+            {anomalies.map((a) => (
+              <li key={`${a.invoice_id}-${a.kind}`}>
+                <span className="font-mono">{a.invoice_code}</span>: {a.message}
+              </li>
+            ))}
+          </ul>
+        </div>
+      )}
+
       {/* Invoice List */}
       <div className="flex-1 bg-white rounded-xl shadow-sm border border-gray-200 overflow-hidden flex flex-col min-h-0">
         <div className="p-4 border-b border-gray-200 flex items-center justify-between">
@@ -181,6 +468,7 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
             <table className="w-full">
               <thead className="bg-gray-50 sticky top-0">
                 <tr>
+                  <th className="w-8 px-4 py-2" />
                   <th className="px-4 py-2 text-left text-xs font-medium text-gray-500 uppercase">
                     Mã
                   </th>
@@ -196,13 +484,29 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
                   <th className="px-4 py-2 text-left text-xs font-medium text-gray-500 uppercase">
                     Lỗi
                   </th>
+                  <th className="px-4 py-2 text-left text-xs font-medium text-gray-500 uppercase">
+                    Ghi chú
+                  </th>
                 </tr>
               </thead>
               <tbody className="divide-y divide-gray-100">
                 {batchInvoices.map((invoice) => {
                   const config = statusConfig[invoice.status as keyof typeof statusConfig] || statusConfig.pending;
                   return (
-                    <tr key={invoice.id} className="hover:bg-gray-50">
+                    <tr
+                      key={invoice.id}
+                      className={invoice.signature_status === 'invalid' ? 'bg-red-50' : 'hover:bg-gray-50'}
+                    >
+                      <td className="px-4 py-3">
+                        {ignorableStatuses.includes(invoice.status) && (
+                          <input
+                            type="checkbox"
+                            checked={selectedIds.has(invoice.id)}
+                            onChange={() => toggleSelected(invoice.id)}
+                            className="rounded border-gray-300"
+                          />
+                        )}
+                      </td>
                       <td className="px-4 py-3 font-mono text-sm text-gray-800">
                         {invoice.code}
                       </td>
@@ -212,13 +516,24 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
                         >
                           {config.label}
                         </span>
+                        {invoice.signature_status === 'invalid' && (
+                          <span className="ml-2 px-2 py-1 rounded text-xs bg-red-600 text-white">
+                            Chữ ký sai
+                          </span>
+                        )}
+                        {invoice.signature_status === 'valid' && (
+                          <span className="ml-2 text-xs text-green-600" title="Chữ ký số hợp lệ">
+                            ✓ Đã ký
+                          </span>
+                        )}
                       </td>
                       <td className="px-4 py-3 text-sm text-gray-500">
                         {formatDate(invoice.downloaded_at)}
                       </td>
                       <td className="px-4 py-3 text-sm text-gray-500 max-w-[150px] truncate">
                         {invoice.file_path ? (
-                          <span title={invoice.file_path}>
+                          <span title={invoice.remote_url ?? invoice.file_path}>
+                            {invoice.remote_url && '☁ '}
                             {invoice.file_path.split('/').pop()}
                           </span>
                         ) : (
@@ -232,6 +547,15 @@ export function BatchDetail({ batchId, onBack: _onBack }: BatchDetailProps) {
                           '-'
                         )}
                       </td>
+                      <td className="px-4 py-3 text-sm text-gray-600 max-w-[200px] truncate">
+                        <button
+                          onClick={() => handleEditNote(invoice)}
+                          title={invoice.note ?? 'Thêm ghi chú'}
+                          className="hover:text-blue-600"
+                        >
+                          {invoice.note || '+'}
+                        </button>
+                      </td>
                     </tr>
                   );
                 })}
@@ -1,6 +1,23 @@
 import { useCallback, useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import {
+  commands,
+  EVENT_NAMES,
+  type BatchCompletedEvent,
+  type BatchStatus,
+  type DownloadBatch,
+} from '../../bindings';
 import { useHistory } from '../../store';
-import type { DownloadBatch } from '../../types';
+import { unwrap } from '../../utils/errors';
+
+const batchStatusConfig: Record<BatchStatus, { bg: string; text: string; label: string }> = {
+  queued: { bg: 'bg-gray-100', text: 'text-gray-600', label: 'Đang chờ' },
+  running: { bg: 'bg-blue-100', text: 'text-blue-600', label: 'Đang chạy' },
+  paused: { bg: 'bg-yellow-100', text: 'text-yellow-600', label: 'Tạm dừng' },
+  completed: { bg: 'bg-green-100', text: 'text-green-600', label: 'Hoàn thành' },
+  cancelled: { bg: 'bg-gray-100', text: 'text-gray-500', label: 'Đã hủy' },
+  failed: { bg: 'bg-red-100', text: 'text-red-600', label: 'Lỗi' },
+};
 
 interface BatchListProps {
   batches: DownloadBatch[];
@@ -8,7 +25,7 @@ interface BatchListProps {
 }
 
 export function BatchList({ batches, onSelectBatch }: BatchListProps) {
-  const { deleteBatch } = useHistory();
+  const { deleteBatch, loadBatches } = useHistory();
   const [deletingId, setDeletingId] = useState<string | null>(null);
 
   const formatDate = (dateStr: string) => {
@@ -38,6 +55,33 @@ export function BatchList({ batches, onSelectBatch }: BatchListProps) {
     [deleteBatch]
   );
 
+  // Start a batch saved with "Tải sau"; history refreshes when it finishes
+  const handleStartQueued = useCallback(
+    async (e: React.MouseEvent, batchId: string) => {
+      e.stopPropagation();
+      // Returns at once; the batch reports through its events until batch:completed
+      const unlisten = await listen<BatchCompletedEvent>(EVENT_NAMES.batchCompleted, (event) => {
+        if (event.payload.batch_id !== batchId) {
+          return;
+        }
+        unlisten();
+        loadBatches();
+      });
+      try {
+        const { status } = unwrap(await commands.startQueuedDownload(batchId));
+        // Already over: no batch:completed is coming
+        if (status !== 'running' && status !== 'paused') {
+          unlisten();
+        }
+      } catch (err) {
+        unlisten();
+        console.error('Failed to start queued batch:', err);
+      }
+      await loadBatches();
+    },
+    [loadBatches]
+  );
+
   if (batches.length === 0) {
     return (
       <div className="h-full bg-white rounded-xl shadow-sm border border-gray-200 flex items-center justify-center">
@@ -72,6 +116,9 @@ export function BatchList({ batches, onSelectBatch }: BatchListProps) {
             <th className="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider">
               Ngày
             </th>
+            <th className="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider">
+              Trạng thái
+            </th>
             <th className="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider">
               Tổng
             </th>
@@ -100,7 +147,18 @@ export function BatchList({ batches, onSelectBatch }: BatchListProps) {
                 className="hover:bg-gray-50 cursor-pointer transition-colors"
               >
                 <td className="px-6 py-4 whitespace-nowrap text-sm text-gray-800">
-                  {formatDate(batch.created_at)}
+                  {batch.name && <div className="font-medium">{batch.name}</div>}
+                  <div className={batch.name ? 'text-xs text-gray-500' : undefined}>
+                    {formatDate(batch.created_at)}
+                    {batch.source_file && ` · ${batch.source_file}`}
+                  </div>
+                </td>
+                <td className="px-6 py-4 whitespace-nowrap text-sm">
+                  <span
+                    className={`px-2 py-1 rounded text-xs ${batchStatusConfig[batch.status].bg} ${batchStatusConfig[batch.status].text}`}
+                  >
+                    {batchStatusConfig[batch.status].label}
+                  </span>
                 </td>
                 <td className="px-6 py-4 whitespace-nowrap text-sm text-gray-600">
                   {batch.total_count}
@@ -126,6 +184,22 @@ export function BatchList({ batches, onSelectBatch }: BatchListProps) {
                 </td>
                 <td className="px-6 py-4 whitespace-nowrap text-right text-sm">
                   <div className="flex items-center justify-end gap-2">
+                    {batch.status === 'queued' && (
+                      <button
+                        onClick={(e) => handleStartQueued(e, batch.id)}
+                        className="p-1.5 text-gray-500 hover:text-green-600 hover:bg-green-50 rounded transition-colors"
+                        title="Bắt đầu tải"
+                      >
+                        <svg className="w-4 h-4" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                          <path
+                            strokeLinecap="round"
+                            strokeLinejoin="round"
+                            strokeWidth={2}
+                            d="M14.752 11.168l-3.197-2.132A1 1 0 0010 9.87v4.263a1 1 0 001.555.832l3.197-2.132a1 1 0 000-1.664z"
+                          />
+                        </svg>
+                      </button>
+                    )}
                     <button
                       onClick={(e) => {
                         e.stopPropagation();
@@ -2,6 +2,9 @@ import { useEffect, useState, useCallback } from 'react';
 import { useHistory } from '../../store';
 import { BatchList } from './BatchList';
 import { BatchDetail } from './BatchDetail';
+import { VatSummaryPanel } from './VatSummaryPanel';
+import { StorageReportPanel } from './StorageReportPanel';
+import { UnfinishedBatchesBanner } from './UnfinishedBatchesBanner';
 
 export function HistoryPage() {
   const { batches, selectedBatchId, isLoading, loadBatches, selectBatch } = useHistory();
@@ -83,7 +86,12 @@ export function HistoryPage() {
 
       <div className="flex-1 min-h-0">
         {viewMode === 'list' ? (
-          <BatchList batches={batches} onSelectBatch={handleSelectBatch} />
+          <>
+            <UnfinishedBatchesBanner onResolved={loadBatches} />
+            <VatSummaryPanel />
+            <StorageReportPanel />
+            <BatchList batches={batches} onSelectBatch={handleSelectBatch} />
+          </>
         ) : selectedBatchId ? (
           <BatchDetail batchId={selectedBatchId} onBack={handleBackToList} />
         ) : null}
@@ -0,0 +1,114 @@
+import { useState, useCallback } from 'react';
+import { commands, type StorageReport, type UsageGroup } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
+
+type Grouping = 'by_batch' | 'by_month' | 'by_seller';
+
+const groupingLabels: Record<Grouping, string> = {
+  by_batch: 'Theo phiên tải',
+  by_month: 'Theo tháng',
+  by_seller: 'Theo người bán',
+};
+
+// How many groups and unreferenced files are listed
+const MAX_ROWS = 10;
+
+const formatSize = (bytes: number) => {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  if (bytes < 1024 * 1024 * 1024) return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+  return `${(bytes / 1024 / 1024 / 1024).toFixed(2)} GB`;
+};
+
+const groupLabel = (group: UsageGroup) => (group.label === '?' ? 'Không rõ' : group.label);
+
+export function StorageReportPanel() {
+  const [report, setReport] = useState<StorageReport | null>(null);
+  const [grouping, setGrouping] = useState<Grouping>('by_batch');
+  const [isLoading, setIsLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  // Walking the download directory takes a while, so only on request
+  const loadReport = useCallback(async () => {
+    setIsLoading(true);
+    try {
+      setReport(unwrap(await commands.getStorageReport()));
+      setError(null);
+    } catch (err) {
+      console.error('Failed to load storage report:', err);
+      setError(errorMessage(err));
+    } finally {
+      setIsLoading(false);
+    }
+  }, []);
+
+  return (
+    <div className="bg-white rounded-xl shadow-sm border border-gray-200 p-6 mb-6">
+      <div className="flex items-center justify-between">
+        <h3 className="font-medium text-gray-800">Dung lượng thư mục tải xuống</h3>
+        <div className="flex items-center gap-2">
+          {report && (
+            <select
+              value={grouping}
+              onChange={(e) => setGrouping(e.target.value as Grouping)}
+              className="px-3 py-1.5 text-sm border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              {Object.entries(groupingLabels).map(([value, label]) => (
+                <option key={value} value={value}>
+                  {label}
+                </option>
+              ))}
+            </select>
+          )}
+          <button
+            onClick={loadReport}
+            disabled={isLoading}
+            className="px-3 py-1.5 text-sm bg-gray-100 text-gray-700 rounded-lg hover:bg-gray-200 transition-colors disabled:opacity-50"
+          >
+            {isLoading ? 'Đang quét...' : report ? 'Quét lại' : 'Quét'}
+          </button>
+        </div>
+      </div>
+      {error && <p className="text-sm text-red-600 mt-3">{error}</p>}
+      {report && !error && (
+        <>
+          <p className="text-sm text-gray-500 mt-3">
+            {report.file_count} file, {formatSize(report.total_bytes)} trong{' '}
+            <span className="font-mono">{report.root}</span>
+          </p>
+          <table className="w-full text-sm mt-3">
+            <tbody className="text-gray-700">
+              {report[grouping].slice(0, MAX_ROWS).map((group) => (
+                <tr key={group.key}>
+                  <td className="py-1 truncate max-w-[300px]" title={group.key}>
+                    {groupLabel(group)}
+                  </td>
+                  <td className="py-1 text-right">{group.file_count} file</td>
+                  <td className="py-1 text-right">{formatSize(group.bytes)}</td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+          {report.unreferenced.length > 0 && (
+            <div className="mt-4 pt-4 border-t border-gray-200">
+              <p className="text-sm text-amber-700">
+                {report.unreferenced.length} file ({formatSize(report.unreferenced_bytes)}) không thuộc hóa đơn nào
+                trong lịch sử, có thể xóa để giải phóng dung lượng:
+              </p>
+              <ul className="mt-1 space-y-0.5 text-sm text-gray-600">
+                {report.unreferenced.slice(0, MAX_ROWS).map((file) => (
+                  <li key={file.path} className="flex justify-between gap-4">
+                    <span className="font-mono truncate" title={file.path}>
+                      {file.path}
+                    </span>
+                    <span className="shrink-0">{formatSize(file.bytes)}</span>
+                  </li>
+                ))}
+              </ul>
+            </div>
+          )}
+        </>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,90 @@
+import { useState, useEffect, useCallback } from 'react';
+import { commands, type UnfinishedBatch } from '../../bindings';
+import { unwrap } from '../../utils/errors';
+
+interface UnfinishedBatchesBannerProps {
+  onResolved: () => void;
+}
+
+export function UnfinishedBatchesBanner({ onResolved }: UnfinishedBatchesBannerProps) {
+  const [batches, setBatches] = useState<UnfinishedBatch[]>([]);
+  const [busyId, setBusyId] = useState<string | null>(null);
+
+  const loadBatches = useCallback(async () => {
+    try {
+      setBatches(unwrap(await commands.getUnfinishedBatches()));
+    } catch (err) {
+      console.error('Failed to load unfinished batches:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadBatches();
+  }, [loadBatches]);
+
+  const handleResume = async (batchId: string) => {
+    setBusyId(batchId);
+    setBatches((current) => current.filter((b) => b.batch_id !== batchId));
+    try {
+      // Returns at once; the batch reports through its events until batch:completed
+      unwrap(await commands.resumeBatch(batchId));
+    } catch (err) {
+      console.error('Failed to resume batch:', err);
+    } finally {
+      setBusyId(null);
+      onResolved();
+    }
+  };
+
+  const handleDiscard = async (batchId: string) => {
+    try {
+      unwrap(await commands.discardUnfinishedBatch(batchId));
+      setBatches((current) => current.filter((b) => b.batch_id !== batchId));
+    } catch (err) {
+      console.error('Failed to discard batch:', err);
+    }
+  };
+
+  if (batches.length === 0) {
+    return null;
+  }
+
+  return (
+    <div className="bg-amber-50 border border-amber-200 rounded-xl p-4 mb-6 space-y-3">
+      <p className="text-sm font-medium text-amber-800">
+        Ứng dụng đã đóng đột ngột khi đang tải. Bạn có muốn tiếp tục?
+      </p>
+      {batches.map((batch) => (
+        <div key={batch.batch_id} className="flex items-center justify-between text-sm">
+          <div className="text-amber-900">
+            <span className="font-medium">{batch.batch_name || batch.batch_id.slice(0, 8)}</span>
+            {' — '}
+            {batch.done_count}/{batch.total} hóa đơn đã xong
+            {batch.invoice_code && (
+              <span className="text-amber-700">
+                {' '}
+                (dừng ở {batch.invoice_code}, lần thử {batch.attempt ?? 0}, bước {batch.phase})
+              </span>
+            )}
+          </div>
+          <div className="flex gap-2">
+            <button
+              onClick={() => handleResume(batch.batch_id)}
+              disabled={busyId !== null}
+              className="px-3 py-1 bg-amber-600 text-white rounded-lg hover:bg-amber-700 disabled:opacity-50"
+            >
+              Tiếp tục
+            </button>
+            <button
+              onClick={() => handleDiscard(batch.batch_id)}
+              disabled={busyId !== null}
+              className="px-3 py-1 border border-amber-300 text-amber-800 rounded-lg hover:bg-amber-100 disabled:opacity-50"
+            >
+              Bỏ qua
+            </button>
+          </div>
+        </div>
+      ))}
+    </div>
+  );
+}
@@ -0,0 +1,88 @@
+import { useState, useEffect, useCallback } from 'react';
+import { commands, type VatRate, type VatSummary } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
+
+const rateLabels: Record<VatRate, string> = {
+  'not-taxable': 'Không chịu thuế',
+  'not-declared': 'Không kê khai, tính nộp thuế',
+  zero: 'Thuế suất 0%',
+  five: 'Thuế suất 5%',
+  eight: 'Thuế suất 8%',
+  ten: 'Thuế suất 10%',
+  other: 'Thuế suất khác',
+};
+
+const formatVnd = (amount: number) => amount.toLocaleString('vi-VN');
+
+const currentMonth = () => new Date().toISOString().slice(0, 7);
+
+export function VatSummaryPanel() {
+  const [period, setPeriod] = useState(currentMonth);
+  const [summary, setSummary] = useState<VatSummary | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const loadSummary = useCallback(async () => {
+    try {
+      setSummary(unwrap(await commands.getVatSummary(period)));
+      setError(null);
+    } catch (err) {
+      console.error('Failed to load VAT summary:', err);
+      setError(errorMessage(err));
+    }
+  }, [period]);
+
+  useEffect(() => {
+    loadSummary();
+  }, [loadSummary]);
+
+  return (
+    <div className="bg-white rounded-xl shadow-sm border border-gray-200 p-6 mb-6">
+      <div className="flex items-center justify-between">
+        <h3 className="font-medium text-gray-800">Tổng hợp thuế GTGT theo thuế suất</h3>
+        <input
+          type="text"
+          value={period}
+          onChange={(e) => setPeriod(e.target.value)}
+          placeholder="2024-05 hoặc 2024-Q2"
+          className="w-40 px-3 py-1.5 text-sm border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+      </div>
+      {error && <p className="text-sm text-red-600 mt-3">{error}</p>}
+      {summary && !error && (
+        <>
+          <table className="w-full text-sm mt-3">
+            <thead>
+              <tr className="text-left text-xs text-gray-500 uppercase">
+                <th className="py-1">Nhóm thuế suất</th>
+                <th className="py-1 text-right">Số hóa đơn</th>
+                <th className="py-1 text-right">Giá trị chưa thuế</th>
+                <th className="py-1 text-right">Thuế GTGT</th>
+              </tr>
+            </thead>
+            <tbody className="text-gray-700">
+              {summary.rows.map((row) => (
+                <tr key={row.rate}>
+                  <td className="py-1">{rateLabels[row.rate]}</td>
+                  <td className="py-1 text-right">{row.invoice_count}</td>
+                  <td className="py-1 text-right">{formatVnd(row.taxable_amount)}</td>
+                  <td className="py-1 text-right">{formatVnd(row.tax_amount)}</td>
+                </tr>
+              ))}
+              <tr className="font-medium border-t border-gray-200">
+                <td className="py-1">Tổng cộng</td>
+                <td className="py-1 text-right">{summary.invoice_count}</td>
+                <td className="py-1 text-right">{formatVnd(summary.total_taxable)}</td>
+                <td className="py-1 text-right">{formatVnd(summary.total_tax)}</td>
+              </tr>
+            </tbody>
+          </table>
+          {summary.skipped.length > 0 && (
+            <p className="text-sm text-amber-600 mt-2">
+              Bỏ qua {summary.skipped.length} hóa đơn: {summary.skipped.join('; ')}
+            </p>
+          )}
+        </>
+      )}
+    </div>
+  );
+}
@@ -1,3 +1,5 @@
 export { HistoryPage } from './HistoryPage';
 export { BatchList } from './BatchList';
 export { BatchDetail } from './BatchDetail';
+export { VatSummaryPanel } from './VatSummaryPanel';
+export { StorageReportPanel } from './StorageReportPanel';
@@ -1,4 +1,11 @@
-export function Header() {
+interface HeaderProps {
+  // Set when a passcode exists and the app can be locked
+  onLock?: () => void;
+  readOnly: boolean;
+  onToggleReadOnly: () => void;
+}
+
+export function Header({ onLock, readOnly, onToggleReadOnly }: HeaderProps) {
   return (
     <header className="h-14 bg-white border-b border-gray-200 flex items-center px-6 shadow-sm">
       <div className="flex items-center gap-3">
@@ -18,6 +25,39 @@ export function Header() {
         <h1 className="text-xl font-semibold text-gray-800">AutoInvoice</h1>
       </div>
       <div className="ml-auto text-sm text-gray-500">Tải hóa đơn VNPT tự động</div>
+      <button
+        onClick={onToggleReadOnly}
+        title={readOnly ? 'Tắt chế độ chỉ xem' : 'Bật chế độ chỉ xem'}
+        className={`ml-4 p-2 rounded-lg transition-colors ${
+          readOnly ? 'text-yellow-600 bg-yellow-50 hover:bg-yellow-100' : 'text-gray-500 hover:text-gray-700 hover:bg-gray-100'
+        }`}
+      >
+        <svg className="w-5 h-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+          <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M15 12a3 3 0 11-6 0 3 3 0 016 0z" />
+          <path
+            strokeLinecap="round"
+            strokeLinejoin="round"
+            strokeWidth={2}
+            d="M2.458 12C3.732 7.943 7.523 5 12 5c4.478 0 8.268 2.943 9.542 7-1.274 4.057-5.064 7-9.542 7-4.477 0-8.268-2.943-9.542-7z"
+          />
+        </svg>
+      </button>
+      {onLock && (
+        <button
+          onClick={onLock}
+          title="Khóa ứng dụng"
+          className="ml-2 p-2 text-gray-500 hover:text-gray-700 rounded-lg hover:bg-gray-100 transition-colors"
+        >
+          <svg className="w-5 h-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+            <path
+              strokeLinecap="round"
+              strokeLinejoin="round"
+              strokeWidth={2}
+              d="M12 15v2m-6 4h12a2 2 0 002-2v-6a2 2 0 00-2-2H6a2 2 0 00-2 2v6a2 2 0 002 2zm10-10V7a4 4 0 00-8 0v4h8z"
+            />
+          </svg>
+        </button>
+      )}
     </header>
   );
 }
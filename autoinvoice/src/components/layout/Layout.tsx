@@ -1,7 +1,11 @@
-import { useState } from 'react';
+import { useState, useEffect, useCallback } from 'react';
 import { Header } from './Header';
 import { Sidebar } from './Sidebar';
+import { LockScreen } from './LockScreen';
+import { useSettings } from '../../store';
+import { commands, type LockStatus } from '../../bindings';
 import type { NavItem } from '../../types';
+import { unwrap } from '../../utils/errors';
 import { UploadPage } from '../upload/UploadPage';
 import { DownloadPage } from '../download/DownloadPage';
 import { HistoryPage } from '../history/HistoryPage';
@@ -9,6 +13,48 @@ import { SettingsPage } from '../settings/SettingsPage';
 
 export function Layout() {
   const [activeNav, setActiveNav] = useState<NavItem>('upload');
+  const [lockStatus, setLockStatus] = useState<LockStatus | null>(null);
+  const { settings } = useSettings();
+
+  const refreshLockStatus = useCallback(async () => {
+    try {
+      setLockStatus(unwrap(await commands.getLockStatus()));
+    } catch (err) {
+      console.error('Failed to get lock status:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    refreshLockStatus();
+  }, [refreshLockStatus]);
+
+  const handleLock = useCallback(async () => {
+    try {
+      unwrap(await commands.lockApp());
+      await refreshLockStatus();
+    } catch (err) {
+      console.error('Failed to lock app:', err);
+    }
+  }, [refreshLockStatus]);
+
+  const handleToggleReadOnly = useCallback(async () => {
+    if (!lockStatus) return;
+
+    const enabled = !lockStatus.read_only;
+    let passcode: string | null = null;
+    if (!enabled && lockStatus.has_passcode) {
+      passcode = window.prompt('Nhập mã khóa để tắt chế độ chỉ xem');
+      if (passcode === null) return;
+    }
+
+    try {
+      unwrap(await commands.setReadOnly(enabled, passcode));
+      await refreshLockStatus();
+    } catch (err) {
+      console.error('Failed to toggle read-only mode:', err);
+      alert('Lỗi: ' + err);
+    }
+  }, [lockStatus, refreshLockStatus]);
 
   const renderPage = () => {
     switch (activeNav) {
@@ -25,9 +71,31 @@ export function Layout() {
     }
   };
 
+  if (!lockStatus) {
+    return <div className="h-screen bg-gray-100" />;
+  }
+
+  if (lockStatus.locked) {
+    return <LockScreen onUnlocked={refreshLockStatus} />;
+  }
+
   return (
     <div className="h-screen flex flex-col bg-gray-100">
-      <Header />
+      <Header
+        onLock={lockStatus.has_passcode ? handleLock : undefined}
+        readOnly={lockStatus.read_only}
+        onToggleReadOnly={handleToggleReadOnly}
+      />
+      {lockStatus.read_only && (
+        <div className="px-6 py-2 bg-yellow-50 border-b border-yellow-200 text-sm text-yellow-800">
+          Chế độ chỉ xem: không thể tải, xóa lịch sử hay đổi cài đặt
+        </div>
+      )}
+      {settings.demo_mode && (
+        <div className="px-6 py-2 bg-blue-50 border-b border-blue-200 text-sm text-blue-800">
+          Chế độ demo: hóa đơn được tải từ cổng giả lập, không phải cổng thật
+        </div>
+      )}
       <div className="flex-1 flex overflow-hidden">
         <Sidebar activeItem={activeNav} onNavigate={setActiveNav} />
         <main className="flex-1 overflow-auto p-6">{renderPage()}</main>
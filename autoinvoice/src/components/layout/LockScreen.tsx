@@ -0,0 +1,69 @@
+import { useState, useCallback, type FormEvent } from 'react';
+import { commands } from '../../bindings';
+import { unwrap } from '../../utils/errors';
+
+interface LockScreenProps {
+  onUnlocked: () => void;
+}
+
+export function LockScreen({ onUnlocked }: LockScreenProps) {
+  const [passcode, setPasscode] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [isUnlocking, setIsUnlocking] = useState(false);
+
+  const handleSubmit = useCallback(
+    async (e: FormEvent) => {
+      e.preventDefault();
+      setIsUnlocking(true);
+      setError(null);
+      try {
+        unwrap(await commands.unlockApp(passcode));
+        onUnlocked();
+      } catch (err) {
+        console.error('Failed to unlock:', err);
+        setError('Mã khóa không đúng');
+        setPasscode('');
+      } finally {
+        setIsUnlocking(false);
+      }
+    },
+    [passcode, onUnlocked]
+  );
+
+  return (
+    <div className="h-screen flex items-center justify-center bg-gray-100">
+      <form
+        onSubmit={handleSubmit}
+        className="w-80 bg-white rounded-xl shadow-sm border border-gray-200 p-6"
+      >
+        <div className="flex items-center gap-3 mb-4">
+          <svg className="w-8 h-8 text-blue-600" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+            <path
+              strokeLinecap="round"
+              strokeLinejoin="round"
+              strokeWidth={2}
+              d="M12 15v2m-6 4h12a2 2 0 002-2v-6a2 2 0 00-2-2H6a2 2 0 00-2 2v6a2 2 0 002 2zm10-10V7a4 4 0 00-8 0v4h8z"
+            />
+          </svg>
+          <h1 className="text-xl font-semibold text-gray-800">AutoInvoice đang khóa</h1>
+        </div>
+        <input
+          type="password"
+          autoFocus
+          value={passcode}
+          onChange={(e) => setPasscode(e.target.value)}
+          placeholder="Nhập mã khóa"
+          className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        {error && <p className="text-sm text-red-600 mt-2">{error}</p>}
+        <button
+          type="submit"
+          disabled={isUnlocking || passcode.length === 0}
+          className="mt-4 w-full px-6 py-2.5 bg-blue-600 text-white rounded-lg hover:bg-blue-700 transition-colors disabled:opacity-50"
+        >
+          Mở khóa
+        </button>
+      </form>
+    </div>
+  );
+}
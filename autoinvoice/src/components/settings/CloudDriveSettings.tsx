@@ -0,0 +1,149 @@
+import { useState, useEffect, useCallback } from 'react';
+import { openUrl } from '@tauri-apps/plugin-opener';
+import { commands, type CloudProvider, type DeviceLogin } from '../../bindings';
+import { useSettings } from '../../store';
+import { unwrap } from '../../utils/errors';
+
+const inputClass =
+  'w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500';
+
+export function CloudDriveSettings() {
+  const { settings, setSettings, saveSettings } = useSettings();
+  const [connected, setConnected] = useState(false);
+  const [login, setLogin] = useState<DeviceLogin | null>(null);
+  const [message, setMessage] = useState<{ ok: boolean; text: string } | null>(null);
+
+  const refresh = useCallback(async () => {
+    try {
+      setConnected(unwrap(await commands.getCloudDriveStatus()));
+    } catch (err) {
+      console.error('Failed to get cloud drive status:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  const connect = useCallback(async () => {
+    setMessage(null);
+    try {
+      // Sign-in uses the saved provider and client id
+      await saveSettings();
+      const deviceLogin = unwrap(await commands.startCloudLogin());
+      setLogin(deviceLogin);
+      await openUrl(deviceLogin.verification_url);
+      unwrap(await commands.completeCloudLogin(deviceLogin));
+      setMessage({ ok: true, text: 'Đã kết nối' });
+    } catch (err) {
+      console.error('Failed to connect cloud drive:', err);
+      setMessage({ ok: false, text: 'Lỗi: ' + err });
+    } finally {
+      setLogin(null);
+      await refresh();
+    }
+  }, [saveSettings, refresh]);
+
+  const disconnect = useCallback(async () => {
+    try {
+      unwrap(await commands.disconnectCloudDrive());
+      setMessage(null);
+    } catch (err) {
+      console.error('Failed to disconnect cloud drive:', err);
+    }
+    await refresh();
+  }, [refresh]);
+
+  const enabled = settings.cloud_provider !== 'off';
+
+  return (
+    <div className="p-6">
+      <label className="block text-sm font-medium text-gray-700 mb-2">
+        Sao lưu lô đã tải lên Google Drive / OneDrive
+      </label>
+      <select
+        value={settings.cloud_provider}
+        onChange={(e) => setSettings({ cloud_provider: e.target.value as CloudProvider })}
+        className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+      >
+        <option value="off">Tắt</option>
+        <option value="google-drive">Google Drive</option>
+        <option value="onedrive">OneDrive</option>
+      </select>
+      <p className="text-sm text-gray-400 mt-2">
+        Mỗi lô hoàn tất được tải lên một thư mục riêng trong thư mục dùng chung của công ty
+      </p>
+
+      {enabled && (
+        <>
+          <div className="mt-4 grid grid-cols-2 gap-3">
+            <div>
+              <label className="block text-sm text-gray-600 mb-1">Client ID</label>
+              <input
+                type="text"
+                value={settings.cloud_client_id}
+                onChange={(e) => setSettings({ cloud_client_id: e.target.value })}
+                className={inputClass}
+              />
+            </div>
+            <div>
+              <label className="block text-sm text-gray-600 mb-1">Client secret</label>
+              <input
+                type="password"
+                value={settings.cloud_client_secret}
+                onChange={(e) => setSettings({ cloud_client_secret: e.target.value })}
+                placeholder={settings.cloud_provider === 'onedrive' ? 'Không bắt buộc' : ''}
+                className={inputClass}
+              />
+            </div>
+            <div className="col-span-2">
+              <label className="block text-sm text-gray-600 mb-1">
+                {settings.cloud_provider === 'google-drive' ? 'ID thư mục cha' : 'Đường dẫn thư mục'}
+              </label>
+              <input
+                type="text"
+                value={settings.cloud_folder}
+                onChange={(e) => setSettings({ cloud_folder: e.target.value })}
+                placeholder={settings.cloud_provider === 'google-drive' ? 'Để trống: My Drive' : 'Ke toan/Hoa don'}
+                className={inputClass}
+              />
+            </div>
+          </div>
+
+          <div className="mt-4 flex items-center gap-3">
+            <span className={`text-sm ${connected ? 'text-green-600' : 'text-gray-500'}`}>
+              {connected ? 'Đã đăng nhập' : 'Chưa đăng nhập'}
+            </span>
+            <button
+              onClick={connect}
+              disabled={login !== null || settings.cloud_client_id.trim().length === 0}
+              className="px-4 py-2 bg-blue-600 text-white rounded-lg hover:bg-blue-700 transition-colors disabled:opacity-50"
+            >
+              {connected ? 'Đăng nhập lại' : 'Đăng nhập'}
+            </button>
+            {connected && (
+              <button
+                onClick={disconnect}
+                className="px-4 py-2 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors"
+              >
+                Ngắt kết nối
+              </button>
+            )}
+          </div>
+
+          {login && (
+            <p className="text-sm text-gray-600 mt-2">
+              Nhập mã <span className="font-mono font-semibold">{login.user_code}</span> tại{' '}
+              <span className="font-mono">{login.verification_url}</span> để cho phép tải lên
+            </p>
+          )}
+          {message && (
+            <p className={`text-sm mt-2 ${message.ok ? 'text-green-600' : 'text-red-600'}`}>
+              {message.text}
+            </p>
+          )}
+        </>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,99 @@
+import { useState, useCallback } from 'react';
+import { save } from '@tauri-apps/plugin-dialog';
+import { commands } from '../../bindings';
+import { unwrap } from '../../utils/errors';
+
+export function ConfigTransfer() {
+  const [password, setPassword] = useState('');
+  const [includeSecrets, setIncludeSecrets] = useState(false);
+  const [message, setMessage] = useState<{ ok: boolean; text: string } | null>(null);
+
+  // Without a password a one-time one is generated, shown only now
+  const handleExport = useCallback(async () => {
+    setMessage(null);
+    try {
+      const destination = await save({
+        defaultPath: 'autoinvoice-config.zip',
+        filters: [{ name: 'ZIP', extensions: ['zip'] }],
+      });
+      if (!destination) return;
+
+      const result = unwrap(await commands.exportConfig(destination, password || null, includeSecrets));
+      if (result.generated_password) {
+        prompt(
+          `Đã lưu ${result.path}.\nMật khẩu chỉ hiển thị một lần, hãy sao chép để nhập trên máy khác:`,
+          result.generated_password
+        );
+      }
+      setMessage({ ok: true, text: 'Đã xuất cấu hình: ' + result.path });
+    } catch (err) {
+      console.error('Failed to export config:', err);
+      setMessage({ ok: false, text: 'Lỗi: ' + err });
+    }
+  }, [password, includeSecrets]);
+
+  const handleImport = useCallback(async () => {
+    setMessage(null);
+    if (!confirm('Cài đặt, hồ sơ cổng và bộ chọn hiện tại sẽ bị thay bằng nội dung của tệp. Tiếp tục?')) return;
+    try {
+      const result = unwrap(await commands.importConfig(password));
+      if (!result) return;
+      setPassword('');
+      setMessage({
+        ok: true,
+        text:
+          `Đã nhập ${result.changed.length} cài đặt, ${result.portal_profile_count} hồ sơ cổng, ` +
+          `${result.selector_override_count} bộ chọn` +
+          (result.includes_secrets ? '' : '; API key và mật khẩu trên máy này được giữ nguyên'),
+      });
+    } catch (err) {
+      console.error('Failed to import config:', err);
+      setMessage({ ok: false, text: 'Lỗi: ' + err });
+    }
+  }, [password]);
+
+  return (
+    <div className="mt-6 bg-white rounded-xl shadow-sm border border-gray-200 p-6">
+      <label className="block text-sm font-medium text-gray-700 mb-2">Sao chép cấu hình sang máy khác</label>
+      <div className="flex gap-2">
+        <input
+          type="password"
+          value={password}
+          onChange={(e) => setPassword(e.target.value)}
+          placeholder="Mật khẩu tệp cấu hình (tối thiểu 8 ký tự)"
+          className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        <button
+          onClick={handleExport}
+          className="px-4 py-2.5 bg-blue-600 text-white rounded-lg hover:bg-blue-700 transition-colors"
+        >
+          Xuất
+        </button>
+        <button
+          onClick={handleImport}
+          disabled={password.length === 0}
+          className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors disabled:opacity-50"
+        >
+          Nhập
+        </button>
+      </div>
+      <label className="flex items-center gap-2 mt-3 text-sm text-gray-700">
+        <input
+          type="checkbox"
+          checked={includeSecrets}
+          onChange={(e) => setIncludeSecrets(e.target.checked)}
+          className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+        />
+        Kèm API key và mật khẩu
+      </label>
+      {message && (
+        <p className={`text-sm mt-2 ${message.ok ? 'text-green-600' : 'text-red-600'}`}>
+          {message.text}
+        </p>
+      )}
+      <p className="text-sm text-gray-400 mt-2">
+        Tệp được mã hóa AES-256, gồm cài đặt, hồ sơ cổng và bộ chọn đã sửa, trừ các thư mục, đường dẫn trên máy này và lệnh chạy sau khi tải; để trống mật khẩu khi xuất để tạo mật khẩu ngẫu nhiên
+      </p>
+    </div>
+  );
+}
@@ -0,0 +1,107 @@
+import { useState } from 'react';
+import { commands } from '../../bindings';
+import { useSettings } from '../../store';
+import { errorMessage, unwrap } from '../../utils/errors';
+
+const inputClass =
+  'w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500';
+
+export function InboxSettings() {
+  const { settings, setSettings } = useSettings();
+  const [isChecking, setIsChecking] = useState(false);
+  const [result, setResult] = useState<string | null>(null);
+
+  // Uses the saved settings, like the background poller
+  const handleCheckNow = async () => {
+    setIsChecking(true);
+    setResult(null);
+    try {
+      const added = unwrap(await commands.checkInbox());
+      setResult(added > 0 ? `Đã thêm ${added} hóa đơn vào lô Hộp thư` : 'Không có hóa đơn mới');
+    } catch (err) {
+      setResult(errorMessage(err));
+    } finally {
+      setIsChecking(false);
+    }
+  };
+
+  return (
+    <div className="p-6">
+      <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+        <input
+          type="checkbox"
+          checked={settings.inbox_enabled}
+          onChange={(e) => setSettings({ inbox_enabled: e.target.checked })}
+          className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+        />
+        Lấy hóa đơn từ email thông báo
+      </label>
+      <p className="text-sm text-gray-400 mt-2">
+        Mỗi 5 phút đọc hộp thư qua IMAP, tìm mã tra cứu trong email và file PDF đính kèm rồi thêm vào lô
+        "Hộp thư" đang chờ tải
+      </p>
+
+      {settings.inbox_enabled && (
+        <div className="mt-4 grid grid-cols-2 gap-3">
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Máy chủ IMAP</label>
+            <input
+              type="text"
+              value={settings.imap_host}
+              onChange={(e) => setSettings({ imap_host: e.target.value })}
+              placeholder="imap.gmail.com"
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Cổng</label>
+            <input
+              type="number"
+              value={settings.imap_port}
+              onChange={(e) => setSettings({ imap_port: Number(e.target.value) || 993 })}
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Tên đăng nhập</label>
+            <input
+              type="text"
+              value={settings.imap_username}
+              onChange={(e) => setSettings({ imap_username: e.target.value })}
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Mật khẩu ứng dụng</label>
+            <input
+              type="password"
+              value={settings.imap_password}
+              onChange={(e) => setSettings({ imap_password: e.target.value })}
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Thư mục</label>
+            <input
+              type="text"
+              value={settings.imap_folder}
+              onChange={(e) => setSettings({ imap_folder: e.target.value })}
+              className={inputClass}
+            />
+          </div>
+          <div className="flex items-end gap-3">
+            <button
+              type="button"
+              onClick={handleCheckNow}
+              disabled={isChecking}
+              className="px-4 py-2.5 border border-gray-300 rounded-lg text-sm text-gray-700 hover:bg-gray-50 disabled:opacity-50"
+            >
+              {isChecking ? 'Đang kiểm tra...' : 'Kiểm tra ngay'}
+            </button>
+          </div>
+          {result && <p className="col-span-2 text-sm text-gray-600">{result}</p>}
+        </div>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,87 @@
+import { useState, useEffect, useCallback } from 'react';
+import { commands } from '../../bindings';
+import { unwrap } from '../../utils/errors';
+
+export function PasscodeSettings() {
+  const [hasPasscode, setHasPasscode] = useState(false);
+  const [current, setCurrent] = useState('');
+  const [passcode, setPasscode] = useState('');
+  const [message, setMessage] = useState<{ ok: boolean; text: string } | null>(null);
+
+  const refresh = useCallback(async () => {
+    try {
+      const status = unwrap(await commands.getLockStatus());
+      setHasPasscode(status.has_passcode);
+    } catch (err) {
+      console.error('Failed to get lock status:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  const submit = useCallback(
+    async (newPasscode: string | null) => {
+      setMessage(null);
+      try {
+        unwrap(await commands.setPasscode(hasPasscode ? current : null, newPasscode));
+        setCurrent('');
+        setPasscode('');
+        setMessage({ ok: true, text: newPasscode ? 'Đã lưu mã khóa' : 'Đã gỡ mã khóa' });
+        await refresh();
+      } catch (err) {
+        console.error('Failed to set passcode:', err);
+        setMessage({ ok: false, text: 'Lỗi: ' + err });
+      }
+    },
+    [hasPasscode, current, refresh]
+  );
+
+  return (
+    <div className="mt-6 bg-white rounded-xl shadow-sm border border-gray-200 p-6">
+      <label className="block text-sm font-medium text-gray-700 mb-2">Mã khóa ứng dụng</label>
+      <div className="flex gap-2">
+        {hasPasscode && (
+          <input
+            type="password"
+            value={current}
+            onChange={(e) => setCurrent(e.target.value)}
+            placeholder="Mã khóa hiện tại"
+            className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+          />
+        )}
+        <input
+          type="password"
+          value={passcode}
+          onChange={(e) => setPasscode(e.target.value)}
+          placeholder="Mã khóa mới"
+          className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        <button
+          onClick={() => submit(passcode)}
+          disabled={passcode.trim().length === 0}
+          className="px-4 py-2.5 bg-blue-600 text-white rounded-lg hover:bg-blue-700 transition-colors disabled:opacity-50"
+        >
+          {hasPasscode ? 'Đổi' : 'Đặt'}
+        </button>
+        {hasPasscode && (
+          <button
+            onClick={() => submit(null)}
+            className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors"
+          >
+            Gỡ
+          </button>
+        )}
+      </div>
+      {message && (
+        <p className={`text-sm mt-2 ${message.ok ? 'text-green-600' : 'text-red-600'}`}>
+          {message.text}
+        </p>
+      )}
+      <p className="text-sm text-gray-400 mt-2">
+        Khi có mã khóa, ứng dụng khởi động ở trạng thái khóa và ẩn lịch sử cùng API key cho tới khi nhập đúng mã
+      </p>
+    </div>
+  );
+}
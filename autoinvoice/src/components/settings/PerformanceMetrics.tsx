@@ -0,0 +1,73 @@
+import { useState, useEffect, useCallback } from 'react';
+import { commands, type StageMetrics, type TimingStage } from '../../bindings';
+import { unwrap } from '../../utils/errors';
+
+const stageLabels: Record<TimingStage, string> = {
+  navigation: 'Mở trang tra cứu',
+  'captcha-solve': 'Giải captcha',
+  submit: 'Gửi tra cứu',
+  download: 'Tải PDF',
+};
+
+const formatMs = (ms: number) => (ms >= 1000 ? `${(ms / 1000).toFixed(1)} s` : `${ms} ms`);
+
+export function PerformanceMetrics() {
+  const [metrics, setMetrics] = useState<StageMetrics[]>([]);
+
+  const loadMetrics = useCallback(async () => {
+    try {
+      setMetrics(unwrap(await commands.getPerformanceMetrics(null)));
+    } catch (err) {
+      console.error('Failed to load performance metrics:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadMetrics();
+  }, [loadMetrics]);
+
+  const handleClear = useCallback(async () => {
+    try {
+      unwrap(await commands.clearPerformanceMetrics());
+      setMetrics([]);
+    } catch (err) {
+      console.error('Failed to clear performance metrics:', err);
+    }
+  }, []);
+
+  if (metrics.length === 0) {
+    return <p className="text-sm text-gray-400 mt-3">Chưa có số liệu</p>;
+  }
+
+  return (
+    <div className="mt-3">
+      <table className="w-full text-sm">
+        <thead>
+          <tr className="text-left text-xs text-gray-500 uppercase">
+            <th className="py-1">Bước</th>
+            <th className="py-1">Số lần</th>
+            <th className="py-1">Trung bình</th>
+            <th className="py-1">P50</th>
+            <th className="py-1">P95</th>
+            <th className="py-1">Tối đa</th>
+          </tr>
+        </thead>
+        <tbody className="text-gray-700">
+          {metrics.map((m) => (
+            <tr key={m.stage}>
+              <td className="py-1">{stageLabels[m.stage]}</td>
+              <td className="py-1">{m.samples}</td>
+              <td className="py-1">{formatMs(m.avg_ms)}</td>
+              <td className="py-1">{formatMs(m.p50_ms)}</td>
+              <td className="py-1">{formatMs(m.p95_ms)}</td>
+              <td className="py-1">{formatMs(m.max_ms)}</td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+      <button onClick={handleClear} className="mt-2 text-sm text-gray-500 hover:text-gray-700">
+        Xóa số liệu
+      </button>
+    </div>
+  );
+}
@@ -0,0 +1,122 @@
+import { useState, useEffect, useCallback } from 'react';
+import { commands, type PortalProfile } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
+
+const emptyProfile: PortalProfile = {
+  host: '',
+  base_delay_secs: 2,
+  max_concurrency: 1,
+  max_retries: 3,
+  builtin: false,
+};
+
+export function PortalProfiles() {
+  const [profiles, setProfiles] = useState<PortalProfile[]>([]);
+  const [draft, setDraft] = useState<PortalProfile>(emptyProfile);
+  const [error, setError] = useState<string | null>(null);
+
+  const loadProfiles = useCallback(async () => {
+    try {
+      setProfiles(unwrap(await commands.getPortalProfiles()));
+    } catch (err) {
+      console.error('Failed to load portal profiles:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadProfiles();
+  }, [loadProfiles]);
+
+  const handleSave = async () => {
+    try {
+      unwrap(await commands.savePortalProfile(draft));
+      setDraft(emptyProfile);
+      setError(null);
+      await loadProfiles();
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const handleDelete = async (host: string) => {
+    try {
+      unwrap(await commands.deletePortalProfile(host));
+      await loadProfiles();
+    } catch (err) {
+      console.error('Failed to delete portal profile:', err);
+    }
+  };
+
+  const numberInput = (field: 'base_delay_secs' | 'max_concurrency' | 'max_retries', min: number) => (
+    <input
+      type="number"
+      min={min}
+      value={draft[field]}
+      onChange={(e) => setDraft({ ...draft, [field]: Number(e.target.value) })}
+      className="w-20 px-2 py-1.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+    />
+  );
+
+  return (
+    <div className="p-6">
+      <h3 className="text-sm font-medium text-gray-700">Giới hạn theo cổng tra cứu</h3>
+      <p className="text-sm text-gray-400 mt-1">
+        Thời gian chờ tối thiểu, số hóa đơn tải cùng lúc và số lần thử tối đa cho từng tên miền
+      </p>
+      <table className="w-full text-sm mt-3">
+        <thead>
+          <tr className="text-left text-xs text-gray-500 uppercase">
+            <th className="py-1">Tên miền</th>
+            <th className="py-1">Chờ (giây)</th>
+            <th className="py-1">Cùng lúc</th>
+            <th className="py-1">Số lần thử</th>
+            <th className="py-1" />
+          </tr>
+        </thead>
+        <tbody className="text-gray-700">
+          {profiles.map((profile) => (
+            <tr key={profile.host}>
+              <td className="py-1">
+                {profile.host}
+                {profile.builtin && <span className="ml-2 text-xs text-gray-400">mặc định</span>}
+              </td>
+              <td className="py-1">{profile.base_delay_secs}</td>
+              <td className="py-1">{profile.max_concurrency}</td>
+              <td className="py-1">{profile.max_retries}</td>
+              <td className="py-1 text-right space-x-3">
+                <button onClick={() => setDraft({ ...profile, builtin: false })} className="text-blue-600 hover:underline">
+                  Sửa
+                </button>
+                {!profile.builtin && (
+                  <button onClick={() => handleDelete(profile.host)} className="text-red-600 hover:underline">
+                    Xóa
+                  </button>
+                )}
+              </td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+      <div className="flex items-center gap-2 mt-3">
+        <input
+          type="text"
+          value={draft.host}
+          onChange={(e) => setDraft({ ...draft, host: e.target.value })}
+          placeholder="vnpt-invoice.com.vn"
+          className="flex-1 px-3 py-1.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        {numberInput('base_delay_secs', 0)}
+        {numberInput('max_concurrency', 1)}
+        {numberInput('max_retries', 1)}
+        <button
+          onClick={handleSave}
+          disabled={!draft.host.trim()}
+          className="px-4 py-1.5 bg-blue-600 text-white rounded-lg hover:bg-blue-700 disabled:opacity-50"
+        >
+          Lưu
+        </button>
+      </div>
+      {error && <p className="text-sm text-red-600 mt-2">{error}</p>}
+    </div>
+  );
+}
@@ -0,0 +1,37 @@
+import { useEffect, useState } from 'react';
+import { commands, type RetryLaterInvoice } from '../../bindings';
+import { useSettings } from '../../store';
+import { unwrap } from '../../utils/errors';
+
+export function RetryLaterSettings() {
+  const { settings, setSettings } = useSettings();
+  const [pending, setPending] = useState<RetryLaterInvoice[]>([]);
+
+  useEffect(() => {
+    commands
+      .getRetryLaterInvoices()
+      .then((result) => setPending(unwrap(result)))
+      .catch((err) => console.error('Failed to load the retry-later queue:', err));
+  }, []);
+
+  return (
+    <div className="p-6">
+      <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+        Tự động tra cứu lại hóa đơn không tìm thấy sau
+        <input
+          type="number"
+          min={0}
+          max={720}
+          value={settings.retry_not_found_hours}
+          onChange={(e) => setSettings({ retry_not_found_hours: Math.max(0, Number(e.target.value) || 0) })}
+          className="w-20 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        giờ
+      </label>
+      <p className="text-sm text-gray-400 mt-2">
+        Khi ứng dụng đang mở, hóa đơn chưa được phát hành được thử lại tối đa 7 lần. Nhập 0 để tắt.
+        {pending.length > 0 && ` Đang chờ thử lại: ${pending.length} hóa đơn.`}
+      </p>
+    </div>
+  );
+}
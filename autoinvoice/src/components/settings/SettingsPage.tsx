@@ -1,16 +1,72 @@
 import { useState, useEffect, useCallback } from 'react';
 import { open } from '@tauri-apps/plugin-dialog';
+import {
+  commands,
+  type AiKeyUsage,
+  type ApiKeyCheck,
+  type ApiKeyStatus,
+  type AzureOpenAiConfig,
+  type BudgetAction,
+  type CaptchaDataset,
+  type CsvDelimiter,
+  type DuplicateFiles,
+  type HookScope,
+  type PdfVariant,
+  type Transliteration,
+} from '../../bindings';
 import { useSettings } from '../../store';
+import { unwrap } from '../../utils/errors';
+import { CloudDriveSettings } from './CloudDriveSettings';
+import { ConfigTransfer } from './ConfigTransfer';
+import { InboxSettings } from './InboxSettings';
+import { RetryLaterSettings } from './RetryLaterSettings';
+import { PasscodeSettings } from './PasscodeSettings';
+import { PerformanceMetrics } from './PerformanceMetrics';
+import { PortalProfiles } from './PortalProfiles';
+import { SftpSettings } from './SftpSettings';
+
+const apiKeyStatusLabels: Record<ApiKeyStatus, string> = {
+  ok: 'Key hoạt động',
+  'invalid-key': 'Key không hợp lệ hoặc đã bị thu hồi',
+  'quota-exceeded': 'Tài khoản đã hết hạn mức (quota) hoặc hết tiền',
+  'rate-limited': 'Đang bị giới hạn tần suất, hãy thử lại sau',
+  'model-unavailable': 'Key không dùng được model gpt-4o-mini',
+  unreachable: 'Không kết nối được tới OpenAI',
+  error: 'Lỗi từ OpenAI',
+};
 
 export function SettingsPage() {
   const { settings, settingsLoading, setSettings, loadSettings, saveSettings } = useSettings();
   const [showApiKey, setShowApiKey] = useState(false);
   const [isSaving, setIsSaving] = useState(false);
   const [saveStatus, setSaveStatus] = useState<'idle' | 'success' | 'error'>('idle');
+  const [apiKeyCheck, setApiKeyCheck] = useState<ApiKeyCheck | null>(null);
+  const [isCheckingKey, setIsCheckingKey] = useState(false);
+  const [aiSpendThisMonth, setAiSpendThisMonth] = useState<number | null>(null);
+  const [aiKeyUsage, setAiKeyUsage] = useState<AiKeyUsage[]>([]);
+  const [captchaDataset, setCaptchaDataset] = useState<CaptchaDataset | null>(null);
+  // Folder of the installed offline captcha model, null when there is none
+  const [localCaptchaModel, setLocalCaptchaModel] = useState<string | null>(null);
 
   // Load settings on mount
   useEffect(() => {
     loadSettings();
+    commands
+      .getAiSpend()
+      .then((result) => setAiSpendThisMonth(unwrap(result)))
+      .catch((err) => console.error('Failed to load AI spend:', err));
+    commands
+      .getAiKeyUsage()
+      .then((result) => setAiKeyUsage(unwrap(result)))
+      .catch((err) => console.error('Failed to load AI key usage:', err));
+    commands
+      .getCaptchaDataset()
+      .then((result) => setCaptchaDataset(unwrap(result)))
+      .catch((err) => console.error('Failed to load captcha dataset:', err));
+    commands
+      .getLocalCaptchaModel()
+      .then((result) => setLocalCaptchaModel(unwrap(result)))
+      .catch((err) => console.error('Failed to find offline captcha model:', err));
   }, [loadSettings]);
 
   const handleSave = useCallback(async () => {
@@ -30,16 +86,63 @@ export function SettingsPage() {
 
   const handleBrowseDirectory = useCallback(async () => {
     try {
-      const selected = await open({
-        directory: true,
-        multiple: false,
-        title: 'Chọn thư mục tải xuống mặc định',
-      });
-      if (selected && typeof selected === 'string') {
-        setSettings({ downloadDirectory: selected });
+      // The backend checks the folder is writable and saves it right away
+      const selected = unwrap(await commands.chooseDownloadDirectory());
+      if (selected) {
+        setSettings({ download_directory: selected });
       }
     } catch (err) {
       console.error('Failed to select directory:', err);
+      alert('Không thể dùng thư mục này: ' + err);
+    }
+  }, [setSettings]);
+
+  const handleBrowseHook = useCallback(async () => {
+    try {
+      const selected = await open({ multiple: false, title: 'Chọn script sau khi tải' });
+      if (selected && typeof selected === 'string') {
+        setSettings({ post_download_hook: selected });
+      }
+    } catch (err) {
+      console.error('Failed to select hook:', err);
+    }
+  }, [setSettings]);
+
+  // Tells before a batch why captcha solving would fail
+  const handleTestApiKey = useCallback(async () => {
+    setIsCheckingKey(true);
+    setApiKeyCheck(null);
+    try {
+      const azure: AzureOpenAiConfig | null = settings.use_azure_openai
+        ? {
+            endpoint: settings.azure_openai_endpoint.trim(),
+            deployment: settings.azure_openai_deployment.trim(),
+            api_version: settings.azure_openai_api_version.trim(),
+          }
+        : null;
+      setApiKeyCheck(unwrap(await commands.testApiKey(settings.openai_api_key, azure)));
+    } catch (err) {
+      console.error('Failed to test API key:', err);
+      alert('Lỗi khi kiểm tra API key: ' + err);
+    } finally {
+      setIsCheckingKey(false);
+    }
+  }, [
+    settings.openai_api_key,
+    settings.use_azure_openai,
+    settings.azure_openai_endpoint,
+    settings.azure_openai_deployment,
+    settings.azure_openai_api_version,
+  ]);
+
+  const handleBrowseTesseract = useCallback(async () => {
+    try {
+      const selected = await open({ multiple: false, title: 'Chọn chương trình Tesseract' });
+      if (selected && typeof selected === 'string') {
+        setSettings({ tesseract_path: selected });
+      }
+    } catch (err) {
+      console.error('Failed to select Tesseract:', err);
     }
   }, [setSettings]);
 
@@ -89,8 +192,8 @@ export function SettingsPage() {
             <div className="relative">
               <input
                 type={showApiKey ? 'text' : 'password'}
-                value={settings.openaiApiKey}
-                onChange={(e) => setSettings({ openaiApiKey: e.target.value })}
+                value={settings.openai_api_key}
+                onChange={(e) => setSettings({ openai_api_key: e.target.value })}
                 placeholder="sk-..."
                 className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500 pr-20"
               />
@@ -105,6 +208,93 @@ export function SettingsPage() {
             <p className="text-sm text-gray-400 mt-2">
               Dùng để giải captcha tự động với GPT-4o-mini Vision
             </p>
+            <div className="mt-3 flex items-center gap-3">
+              <button
+                type="button"
+                onClick={handleTestApiKey}
+                disabled={isCheckingKey || !settings.openai_api_key.trim()}
+                className="px-3 py-1.5 bg-gray-100 text-gray-700 rounded-lg hover:bg-gray-200 transition-colors text-sm disabled:opacity-50"
+              >
+                {isCheckingKey ? 'Đang kiểm tra...' : 'Kiểm tra key'}
+              </button>
+              {apiKeyCheck && (
+                <span
+                  className={`text-sm ${apiKeyCheck.status === 'ok' ? 'text-green-600' : 'text-red-600'}`}
+                  title={apiKeyCheck.message ?? undefined}
+                >
+                  {apiKeyStatusLabels[apiKeyCheck.status]}
+                  {apiKeyCheck.latency_ms !== null && ` · ${apiKeyCheck.latency_ms} ms`}
+                  {apiKeyCheck.models.length > 0 && ` · ${apiKeyCheck.models.length} model`}
+                </span>
+              )}
+            </div>
+            <label className="block text-sm font-medium text-gray-700 mt-4 mb-2">
+              Key dự phòng
+            </label>
+            <textarea
+              value={settings.openai_backup_api_keys.join('\n')}
+              onChange={(e) => setSettings({ openai_backup_api_keys: e.target.value.split('\n') })}
+              onBlur={() =>
+                setSettings({
+                  openai_backup_api_keys: settings.openai_backup_api_keys.map((key) => key.trim()).filter(Boolean),
+                })
+              }
+              rows={3}
+              placeholder={'sk-...\nsk-...'}
+              className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500 font-mono text-sm"
+            />
+            <p className="text-sm text-gray-400 mt-2">
+              Mỗi dòng một key. Khi key đang dùng bị giới hạn tốc độ hoặc hết hạn mức, lô tải tự chuyển sang key kế tiếp.
+            </p>
+            {aiKeyUsage.length > 0 && (
+              <ul className="text-sm text-gray-500 mt-2 space-y-0.5">
+                {aiKeyUsage.map((usage) => (
+                  <li key={usage.key_hint}>
+                    <span className="font-mono">{usage.key_hint}</span>: {usage.calls} lượt · $
+                    {usage.cost_usd.toFixed(4)} tháng này
+                  </li>
+                ))}
+              </ul>
+            )}
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700 mt-4">
+              <input
+                type="checkbox"
+                checked={settings.use_azure_openai}
+                onChange={(e) => setSettings({ use_azure_openai: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Dùng Azure OpenAI
+            </label>
+            {settings.use_azure_openai && (
+              <div className="mt-3 space-y-2">
+                <input
+                  type="url"
+                  value={settings.azure_openai_endpoint}
+                  onChange={(e) => setSettings({ azure_openai_endpoint: e.target.value })}
+                  placeholder="https://ten-tai-nguyen.openai.azure.com"
+                  className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+                <div className="flex gap-2">
+                  <input
+                    type="text"
+                    value={settings.azure_openai_deployment}
+                    onChange={(e) => setSettings({ azure_openai_deployment: e.target.value })}
+                    placeholder="Tên deployment, ví dụ gpt-4o-mini"
+                    className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                  <input
+                    type="text"
+                    value={settings.azure_openai_api_version}
+                    onChange={(e) => setSettings({ azure_openai_api_version: e.target.value })}
+                    placeholder="2024-06-01"
+                    className="w-36 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <p className="text-sm text-gray-400">
+                  Gọi deployment GPT-4o-mini trên Azure thay cho OpenAI. Các API key ở trên khi đó là key của tài nguyên Azure.
+                </p>
+              </div>
+            )}
           </div>
 
           {/* VNPT Invoice URL */}
@@ -114,8 +304,8 @@ export function SettingsPage() {
             </label>
             <input
               type="url"
-              value={settings.vnptUrl}
-              onChange={(e) => setSettings({ vnptUrl: e.target.value })}
+              value={settings.vnpt_url}
+              onChange={(e) => setSettings({ vnpt_url: e.target.value })}
               placeholder="https://xxxxx.vnpt-invoice.com.vn/HomeNoLogin/SearchByFkey"
               className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
             />
@@ -132,7 +322,7 @@ export function SettingsPage() {
             <div className="flex gap-2">
               <input
                 type="text"
-                value={settings.downloadDirectory}
+                value={settings.download_directory}
                 placeholder="/Users/you/Downloads/Invoices"
                 className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg bg-gray-50 text-gray-600"
                 readOnly
@@ -148,6 +338,809 @@ export function SettingsPage() {
               Nơi lưu các file PDF hóa đơn đã tải
             </p>
           </div>
+
+          {/* Inter-invoice delay */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">
+              Thời gian chờ giữa các hóa đơn (giây)
+            </label>
+            <input
+              type="number"
+              min={0}
+              value={settings.invoice_delay_secs}
+              onChange={(e) => setSettings({ invoice_delay_secs: Math.max(0, Number(e.target.value) || 0) })}
+              className="w-32 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            />
+            <p className="text-sm text-gray-400 mt-2">
+              Giãn cách các lượt tra cứu để tránh gửi quá nhiều yêu cầu tới cổng hóa đơn
+            </p>
+          </div>
+
+          {/* Manual captcha timeout */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">
+              Thời gian chờ nhập captcha thủ công (giây)
+            </label>
+            <input
+              type="number"
+              min={0}
+              value={settings.manual_captcha_timeout_secs}
+              onChange={(e) =>
+                setSettings({ manual_captcha_timeout_secs: Math.max(0, Number(e.target.value) || 0) })
+              }
+              className="w-32 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            />
+            <p className="text-sm text-gray-400 mt-2">
+              Khi AI không giải được captcha, hóa đơn được bỏ qua nếu không có ai nhập trong thời gian này
+            </p>
+          </div>
+
+          {/* Quiet hours */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">
+              Khung giờ tạm dừng
+            </label>
+            <div className="flex items-center gap-2">
+              <input
+                type="time"
+                value={settings.quiet_hours_start}
+                onChange={(e) => setSettings({ quiet_hours_start: e.target.value })}
+                className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              <span className="text-gray-500">đến</span>
+              <input
+                type="time"
+                value={settings.quiet_hours_end}
+                onChange={(e) => setSettings({ quiet_hours_end: e.target.value })}
+                className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+            </div>
+            <p className="text-sm text-gray-400 mt-2">
+              Tự động tạm dừng tải trong khung giờ này. Để trống để tắt.
+            </p>
+          </div>
+
+          {/* Per-portal politeness */}
+          <PortalProfiles />
+
+          {/* Notification email ingestion */}
+          <InboxSettings />
+
+          {/* Portal account */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">Tài khoản cổng hóa đơn</label>
+            <p className="text-sm text-gray-400 mb-4">
+              Đăng nhập vào cổng để lấy danh sách hóa đơn theo kỳ mà không cần file Excel. Để trống tên đăng nhập để tắt.
+            </p>
+            <div className="grid grid-cols-2 gap-3">
+              <div className="col-span-2">
+                <label className="block text-sm text-gray-600 mb-1">Trang đăng nhập</label>
+                <input
+                  type="url"
+                  value={settings.portal_login_url}
+                  onChange={(e) => setSettings({ portal_login_url: e.target.value })}
+                  placeholder="https://0101234567-tt78.vnpt-invoice.com.vn/Account/LogOn"
+                  className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </div>
+              <div className="col-span-2">
+                <label className="block text-sm text-gray-600 mb-1">Trang danh sách hóa đơn</label>
+                <input
+                  type="url"
+                  value={settings.portal_invoice_list_url}
+                  onChange={(e) => setSettings({ portal_invoice_list_url: e.target.value })}
+                  className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </div>
+              <div>
+                <label className="block text-sm text-gray-600 mb-1">Tên đăng nhập</label>
+                <input
+                  type="text"
+                  value={settings.portal_username}
+                  onChange={(e) => setSettings({ portal_username: e.target.value })}
+                  className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </div>
+              <div>
+                <label className="block text-sm text-gray-600 mb-1">Mật khẩu</label>
+                <input
+                  type="password"
+                  value={settings.portal_password}
+                  onChange={(e) => setSettings({ portal_password: e.target.value })}
+                  className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </div>
+            </div>
+          </div>
+
+          {/* Captcha lookahead */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.captcha_lookahead}
+                onChange={(e) => setSettings({ captcha_lookahead: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Giải trước captcha của hóa đơn kế tiếp
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Mở tab thứ hai để giải captcha trong lúc hóa đơn hiện tại đang tải, giúp tải nhanh hơn
+            </p>
+          </div>
+
+          {/* Offline captcha model */}
+          <div className="p-6 space-y-3">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.local_captcha_solver}
+                onChange={(e) => setSettings({ local_captcha_solver: e.target.checked })}
+                disabled={!localCaptchaModel && !settings.local_captcha_solver}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Giải captcha bằng mô hình cục bộ trước
+            </label>
+            {settings.local_captcha_solver && (
+              <label className="flex items-center gap-2 text-sm text-gray-600">
+                Độ tin cậy tối thiểu
+                <input
+                  type="number"
+                  min={0}
+                  max={1}
+                  step={0.05}
+                  value={settings.local_captcha_min_confidence}
+                  onChange={(e) =>
+                    setSettings({ local_captcha_min_confidence: Math.min(1, Math.max(0, Number(e.target.value) || 0)) })
+                  }
+                  className="w-24 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </label>
+            )}
+            <p className="text-sm text-gray-400">
+              Chạy mô hình nhỏ ngay trên máy, không cần mạng; chỉ gọi AI khi mô hình không chắc chắn.{' '}
+              {localCaptchaModel ? `Mô hình: ${localCaptchaModel}` : 'Chưa cài mô hình nào.'}
+            </p>
+          </div>
+
+          {/* Captcha training dataset */}
+          <div className="p-6 space-y-3">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.collect_captcha_dataset}
+                onChange={(e) => setSettings({ collect_captcha_dataset: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Lưu captcha đã giải đúng làm dữ liệu huấn luyện
+            </label>
+            {settings.collect_captcha_dataset && (
+              <input
+                type="text"
+                value={settings.captcha_dataset_dir}
+                onChange={(e) => setSettings({ captcha_dataset_dir: e.target.value })}
+                placeholder={captchaDataset?.path ?? 'Thư mục dữ liệu ứng dụng'}
+                className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+            )}
+            <p className="text-sm text-gray-400">
+              Ảnh captcha được cổng chấp nhận và đáp án được lưu vào images/ và labels.csv trên máy này, để sau này
+              huấn luyện mô hình giải captcha cục bộ. Không có gì được gửi đi
+              {captchaDataset && captchaDataset.sample_count > 0 && ` · Đã có ${captchaDataset.sample_count} mẫu`}
+            </p>
+          </div>
+
+          {/* AI captcha budget */}
+          <div className="p-6 space-y-3">
+            <label className="block text-sm font-medium text-gray-700">Ngân sách AI giải captcha (USD)</label>
+            <div className="flex items-center gap-6 text-sm text-gray-600">
+              <label className="flex items-center gap-2">
+                Mỗi lô
+                <input
+                  type="number"
+                  min={0}
+                  step={0.01}
+                  value={settings.ai_budget_per_batch_usd}
+                  onChange={(e) => setSettings({ ai_budget_per_batch_usd: Math.max(0, Number(e.target.value) || 0) })}
+                  className="w-28 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </label>
+              <label className="flex items-center gap-2">
+                Mỗi tháng
+                <input
+                  type="number"
+                  min={0}
+                  step={0.01}
+                  value={settings.ai_budget_per_month_usd}
+                  onChange={(e) => setSettings({ ai_budget_per_month_usd: Math.max(0, Number(e.target.value) || 0) })}
+                  className="w-28 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+              </label>
+            </div>
+            <select
+              value={settings.ai_budget_action}
+              onChange={(e) => setSettings({ ai_budget_action: e.target.value as BudgetAction })}
+              className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              <option value="manual-captcha">Khi hết ngân sách: chuyển sang nhập captcha thủ công</option>
+              <option value="pause">Khi hết ngân sách: tạm dừng lô</option>
+            </select>
+            <p className="text-sm text-gray-400">
+              Chi phí ước tính theo số token GPT-4o-mini đã dùng; 0 là không giới hạn
+              {aiSpendThisMonth !== null && ` · Tháng này đã dùng $${aiSpendThisMonth.toFixed(4)}`}
+            </p>
+          </div>
+
+          {/* Persistent browser profiles */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.persistent_browser_profiles}
+                onChange={(e) => setSettings({ persistent_browser_profiles: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Giữ hồ sơ trình duyệt riêng cho từng cổng
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Dùng lại cookie và phiên đăng nhập giữa các lần tải. Khi tải song song, các trình duyệt khác dùng hồ sơ tạm
+            </p>
+          </div>
+
+          {/* Duplicate files */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">File trùng lặp</label>
+            <select
+              value={settings.duplicate_files}
+              onChange={(e) => setSettings({ duplicate_files: e.target.value as DuplicateFiles })}
+              className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              <option value="keep">Lưu thêm bản sao</option>
+              <option value="hard-link">Tạo liên kết cứng (hard link)</option>
+              <option value="skip">Không lưu, dùng file đã có</option>
+            </select>
+            <p className="text-sm text-gray-400 mt-2">
+              Khi một hóa đơn đã tải ở lô trước có nội dung giống hệt, tránh để thư mục lưu trữ phình to vì các bản sao
+            </p>
+          </div>
+
+          {/* File and folder names */}
+          <div className="p-6 space-y-3">
+            <label className="block text-sm font-medium text-gray-700">Đặt tên file và thư mục</label>
+            <div className="grid grid-cols-2 gap-4">
+              <input
+                type="text"
+                value={settings.folder_template}
+                onChange={(e) => setSettings({ folder_template: e.target.value })}
+                placeholder="Thư mục con, vd. {seller}"
+                className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              <input
+                type="text"
+                value={settings.file_name_template}
+                onChange={(e) => setSettings({ file_name_template: e.target.value })}
+                placeholder="Tên file, vd. {number}_{code}"
+                className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+            </div>
+            <select
+              value={settings.file_name_transliteration}
+              onChange={(e) => setSettings({ file_name_transliteration: e.target.value as Transliteration })}
+              className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              <option value="keep">Giữ nguyên tiếng Việt có dấu</option>
+              <option value="ascii">Bỏ dấu (chỉ ký tự ASCII)</option>
+              <option value="ascii-underscore">Bỏ dấu, thay khoảng trắng bằng _</option>
+            </select>
+            <p className="text-sm text-gray-400">
+              Dùng {'{code}'} (mã tra cứu), {'{seller}'} (tên người bán), {'{number}'} (số hóa đơn) và {'{tax_code}'} (MST
+              người bán); dấu / tạo thư mục lồng nhau. Tên người bán và số hóa đơn được đọc từ trang kết quả tra cứu.
+              Để trống tên file thì dùng mã tra cứu. Bỏ dấu khi ổ đĩa mạng hoặc phần mềm chỉ nhận tên file ASCII
+            </p>
+          </div>
+
+          {/* CSV export */}
+          <div className="p-6 space-y-3">
+            <div className="flex items-center justify-between">
+              <label className="block text-sm font-medium text-gray-700">Định dạng file CSV xuất ra</label>
+              <button
+                type="button"
+                onClick={() =>
+                  setSettings({
+                    csv_delimiter: 'semicolon',
+                    csv_decimal_comma: true,
+                    csv_bom: true,
+                    csv_day_first_dates: true,
+                  })
+                }
+                className="text-sm text-blue-600 hover:underline"
+              >
+                Dùng cho Excel tiếng Việt
+              </button>
+            </div>
+            <select
+              value={settings.csv_delimiter}
+              onChange={(e) => setSettings({ csv_delimiter: e.target.value as CsvDelimiter })}
+              className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              <option value="comma">Phân cách bằng dấu phẩy (,)</option>
+              <option value="semicolon">Phân cách bằng dấu chấm phẩy (;)</option>
+            </select>
+            <label className="flex items-center gap-3 text-sm text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.csv_decimal_comma}
+                onChange={(e) => setSettings({ csv_decimal_comma: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Dùng dấu phẩy thập phân cho số tiền (1234567,5)
+            </label>
+            <label className="flex items-center gap-3 text-sm text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.csv_bom}
+                onChange={(e) => setSettings({ csv_bom: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Thêm UTF-8 BOM để Excel hiển thị đúng tiếng Việt
+            </label>
+            <label className="flex items-center gap-3 text-sm text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.csv_day_first_dates}
+                onChange={(e) => setSettings({ csv_day_first_dates: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Ngày dạng dd/MM/yyyy
+            </label>
+          </div>
+
+          {/* PDF optimization */}
+          <div className="p-6 space-y-3">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.optimize_pdfs}
+                onChange={(e) => setSettings({ optimize_pdfs: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Nén PDF sau khi tải (không giảm chất lượng)
+            </label>
+            <p className="text-sm text-gray-400">
+              Giúp lưu trữ nhiều năm tốn ít dung lượng hơn. PDF có chữ ký số được giữ nguyên để chữ ký vẫn hợp lệ
+            </p>
+            {settings.optimize_pdfs && (
+              <>
+                <label className="flex items-center gap-3 text-sm text-gray-700">
+                  <input
+                    type="checkbox"
+                    checked={settings.keep_original_pdfs}
+                    onChange={(e) => setSettings({ keep_original_pdfs: e.target.checked })}
+                    className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+                  />
+                  Giữ bản gốc trong thư mục originals
+                </label>
+                <label className="flex items-center gap-3 text-sm text-gray-700">
+                  <input
+                    type="checkbox"
+                    checked={settings.linearize_pdfs}
+                    onChange={(e) => setSettings({ linearize_pdfs: e.target.checked })}
+                    className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+                  />
+                  Tối ưu hiển thị web (linearize) bằng qpdf
+                </label>
+                {settings.linearize_pdfs && (
+                  <input
+                    type="text"
+                    value={settings.qpdf_path}
+                    onChange={(e) => setSettings({ qpdf_path: e.target.value })}
+                    placeholder="qpdf (để trống nếu đã có trong PATH)"
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                )}
+              </>
+            )}
+          </div>
+
+          {/* PDF stamp */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.stamp_pdfs}
+                onChange={(e) => setSettings({ stamp_pdfs: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Đóng dấu "Processed &lt;ngày&gt; – batch &lt;tên lô&gt;" lên trang đầu
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Theo yêu cầu kiểm soát nội bộ trước khi lưu hồ sơ. Dấu được ghi thêm vào cuối file nên nội dung gốc và chữ ký số không bị thay đổi
+            </p>
+          </div>
+
+          {/* Demo mode */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.demo_mode}
+                onChange={(e) => setSettings({ demo_mode: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Chế độ demo (đào tạo nhân viên mới)
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Tải hóa đơn mẫu từ một cổng giả lập để làm quen với quy trình nhập file, tải và xem lịch sử. Không truy cập cổng thật,
+              không tốn lượt gọi API
+            </p>
+          </div>
+
+          {/* Browser window and rendering */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">Cửa sổ trình duyệt</label>
+            <div className="flex flex-wrap items-center gap-3 text-sm text-gray-600">
+              <input
+                type="number"
+                min={800}
+                max={3840}
+                value={settings.browser_window_width}
+                onChange={(e) => setSettings({ browser_window_width: Number(e.target.value) || 0 })}
+                className="w-24 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              ×
+              <input
+                type="number"
+                min={600}
+                max={2160}
+                value={settings.browser_window_height}
+                onChange={(e) => setSettings({ browser_window_height: Number(e.target.value) || 0 })}
+                className="w-24 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              <span className="ml-3">Tỉ lệ điểm ảnh</span>
+              <input
+                type="number"
+                min={0.5}
+                max={4}
+                step={0.25}
+                value={settings.browser_device_scale_factor}
+                onChange={(e) => setSettings({ browser_device_scale_factor: Number(e.target.value) || 1 })}
+                className="w-20 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              <span className="ml-3">Thu phóng (%)</span>
+              <input
+                type="number"
+                min={25}
+                max={400}
+                value={settings.browser_zoom_percent}
+                onChange={(e) => setSettings({ browser_zoom_percent: Number(e.target.value) || 100 })}
+                className="w-20 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              <span className="ml-3">Lề ảnh captcha (px)</span>
+              <input
+                type="number"
+                min={0}
+                max={50}
+                value={settings.captcha_padding_px}
+                onChange={(e) => setSettings({ captcha_padding_px: Number(e.target.value) || 0 })}
+                className="w-20 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+            </div>
+            <p className="text-sm text-gray-400 mt-2">
+              Cố định kích thước và độ phân giải trang để ảnh captcha không bị cắt hoặc mờ. Ảnh captcha được chụp ở độ phân giải gấp đôi, kèm phần lề xung quanh
+            </p>
+          </div>
+
+          {/* Not-found verification */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.verify_not_found}
+                onChange={(e) => setSettings({ verify_not_found: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Kiểm tra lại hóa đơn không tìm thấy sau
+              <input
+                type="number"
+                min={0}
+                max={600}
+                value={settings.not_found_verify_delay_secs}
+                onChange={(e) =>
+                  setSettings({ not_found_verify_delay_secs: Math.max(0, Number(e.target.value) || 0) })
+                }
+                disabled={!settings.verify_not_found}
+                className="w-20 px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:opacity-50"
+              />
+              giây
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Hóa đơn xuất trong ngày thường cần một lúc mới tra cứu được trên cổng
+            </p>
+          </div>
+
+          {/* Retry-later queue */}
+          <RetryLaterSettings />
+
+          {/* Tax-authority portal fallback */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.fallback_to_tax_portal}
+                onChange={(e) => setSettings({ fallback_to_tax_portal: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Thử lại trên cổng hóa đơn của Tổng cục Thuế
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Khi không tìm thấy hóa đơn trên VNPT, tra cứu lại bằng mã tra cứu và MST người bán
+            </p>
+          </div>
+
+          {/* Invoice XML */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.download_xml}
+                onChange={(e) => setSettings({ download_xml: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Tải kèm file XML của hóa đơn
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              File XML theo Thông tư 78 được lưu cạnh file PDF, đọc và kiểm tra người bán, người mua, hàng hóa và tiền thuế
+            </p>
+          </div>
+
+          {/* Converted invoice */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">Bản PDF cần tải</label>
+            <select
+              value={settings.pdf_variant}
+              onChange={(e) => setSettings({ pdf_variant: e.target.value as PdfVariant })}
+              className="px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              <option value="original">Hóa đơn gốc</option>
+              <option value="converted">Hóa đơn chuyển đổi (bản in)</option>
+              <option value="both">Cả hai</option>
+            </select>
+            <p className="text-sm text-gray-400 mt-2">
+              Bản chuyển đổi là bản in ra giấy để lưu hồ sơ. Khi cổng không có bản chuyển đổi, hóa đơn gốc được giữ lại; tải cả hai thì bản chuyển đổi được lưu thành file _chuyen-doi.pdf
+            </p>
+          </div>
+
+          {/* OCR of scanned PDFs */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.ocr_scanned_pdfs}
+                onChange={(e) => setSettings({ ocr_scanned_pdfs: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Nhận dạng chữ (OCR) cho PDF dạng ảnh quét
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Đọc số, ngày và tiền hóa đơn bằng Tesseract khi PDF không có lớp chữ. Cần cài dữ liệu tiếng Việt (vie)
+            </p>
+            {settings.ocr_scanned_pdfs && (
+              <div className="flex gap-2 mt-3">
+                <input
+                  type="text"
+                  value={settings.tesseract_path}
+                  onChange={(e) => setSettings({ tesseract_path: e.target.value })}
+                  placeholder="tesseract"
+                  className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                />
+                <button
+                  onClick={handleBrowseTesseract}
+                  className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors"
+                >
+                  Duyệt
+                </button>
+              </div>
+            )}
+          </div>
+
+          {/* Offline queue */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.auto_start_queued}
+                onChange={(e) => setSettings({ auto_start_queued: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Tự động tải các lô đang chờ khi có mạng
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Các lô được lưu bằng nút &quot;Tải sau&quot; sẽ bắt đầu ngay khi kết nối mạng trở lại
+            </p>
+          </div>
+
+          {/* Post-download hook */}
+          <div className="p-6">
+            <label className="block text-sm font-medium text-gray-700 mb-2">
+              Script chạy sau khi tải
+            </label>
+            <div className="flex gap-2">
+              <input
+                type="text"
+                value={settings.post_download_hook}
+                onChange={(e) => setSettings({ post_download_hook: e.target.value })}
+                placeholder="/usr/local/bin/archive-invoice.sh"
+                className="flex-1 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+              />
+              <button
+                onClick={handleBrowseHook}
+                className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors"
+              >
+                Duyệt
+              </button>
+            </div>
+            <select
+              value={settings.post_download_hook_scope}
+              onChange={(e) => setSettings({ post_download_hook_scope: e.target.value as HookScope })}
+              className="mt-2 px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+            >
+              <option value="invoice">Sau mỗi hóa đơn</option>
+              <option value="batch">Sau cả lô</option>
+            </select>
+            <p className="text-sm text-gray-400 mt-2">
+              Nhận thông tin hóa đơn (mã, đường dẫn file, trạng thái) dạng JSON qua stdin, dùng để lưu trữ hoặc tải lên nơi khác. Để trống để tắt.
+            </p>
+          </div>
+
+          {/* S3-compatible storage */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.upload_to_s3}
+                onChange={(e) => setSettings({ upload_to_s3: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Tải hóa đơn lên S3 / MinIO
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Mỗi file PDF được tải lên ngay sau khi tải về, đường dẫn được lưu trong lịch sử
+            </p>
+            {settings.upload_to_s3 && (
+              <div className="mt-4 grid grid-cols-2 gap-3">
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Endpoint</label>
+                  <input
+                    type="url"
+                    value={settings.s3_endpoint}
+                    onChange={(e) => setSettings({ s3_endpoint: e.target.value })}
+                    placeholder="https://s3.ap-southeast-1.amazonaws.com"
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Region</label>
+                  <input
+                    type="text"
+                    value={settings.s3_region}
+                    onChange={(e) => setSettings({ s3_region: e.target.value })}
+                    placeholder="us-east-1"
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Bucket</label>
+                  <input
+                    type="text"
+                    value={settings.s3_bucket}
+                    onChange={(e) => setSettings({ s3_bucket: e.target.value })}
+                    placeholder="invoices"
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Thư mục (prefix)</label>
+                  <input
+                    type="text"
+                    value={settings.s3_prefix}
+                    onChange={(e) => setSettings({ s3_prefix: e.target.value })}
+                    placeholder="autoinvoice"
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Access key</label>
+                  <input
+                    type="text"
+                    value={settings.s3_access_key}
+                    onChange={(e) => setSettings({ s3_access_key: e.target.value })}
+                    placeholder="AKIA..."
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Secret key</label>
+                  <input
+                    type="password"
+                    value={settings.s3_secret_key}
+                    onChange={(e) => setSettings({ s3_secret_key: e.target.value })}
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+              </div>
+            )}
+          </div>
+
+          {/* WebDAV / Nextcloud */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.upload_to_webdav}
+                onChange={(e) => setSettings({ upload_to_webdav: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Tải lô đã hoàn tất lên Nextcloud / WebDAV
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Mỗi lô được tải lên một thư mục riêng. Dùng mật khẩu ứng dụng, không dùng mật khẩu tài khoản.
+            </p>
+            {settings.upload_to_webdav && (
+              <div className="mt-4 grid grid-cols-2 gap-3">
+                <div className="col-span-2">
+                  <label className="block text-sm text-gray-600 mb-1">URL thư mục</label>
+                  <input
+                    type="url"
+                    value={settings.webdav_url}
+                    onChange={(e) => setSettings({ webdav_url: e.target.value })}
+                    placeholder="https://cloud.example.com/remote.php/dav/files/ketoan/HoaDon"
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Tên đăng nhập</label>
+                  <input
+                    type="text"
+                    value={settings.webdav_username}
+                    onChange={(e) => setSettings({ webdav_username: e.target.value })}
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+                <div>
+                  <label className="block text-sm text-gray-600 mb-1">Mật khẩu ứng dụng</label>
+                  <input
+                    type="password"
+                    value={settings.webdav_password}
+                    onChange={(e) => setSettings({ webdav_password: e.target.value })}
+                    className="w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+                  />
+                </div>
+              </div>
+            )}
+          </div>
+
+          {/* SFTP delivery */}
+          <SftpSettings />
+
+          {/* Cloud drive mirror */}
+          <CloudDriveSettings />
+
+          {/* Local performance metrics */}
+          <div className="p-6">
+            <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings.collect_metrics}
+                onChange={(e) => setSettings({ collect_metrics: e.target.checked })}
+                className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+              />
+              Ghi lại thời gian từng bước tải
+            </label>
+            <p className="text-sm text-gray-400 mt-2">
+              Giúp điều chỉnh thời gian chờ và giới hạn. Số liệu chỉ lưu trên máy này.
+            </p>
+            {settings.collect_metrics && <PerformanceMetrics />}
+          </div>
         </div>
 
         <div className="mt-6 flex items-center justify-between">
@@ -197,6 +1190,11 @@ export function SettingsPage() {
             )}
           </button>
         </div>
+
+        <PasscodeSettings />
+
+        {/* Provisioning other machines */}
+        <ConfigTransfer />
       </div>
     </div>
   );
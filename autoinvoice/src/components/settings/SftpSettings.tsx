@@ -0,0 +1,137 @@
+import { useCallback } from 'react';
+import { open } from '@tauri-apps/plugin-dialog';
+import { useSettings } from '../../store';
+import type { SftpMode } from '../../bindings';
+
+const inputClass =
+  'w-full px-4 py-2.5 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500';
+
+export function SftpSettings() {
+  const { settings, setSettings } = useSettings();
+
+  const handleBrowseKey = useCallback(async () => {
+    try {
+      const selected = await open({ multiple: false, title: 'Chọn khóa SSH' });
+      if (selected && typeof selected === 'string') {
+        setSettings({ sftp_private_key_path: selected });
+      }
+    } catch (err) {
+      console.error('Failed to select SSH key:', err);
+    }
+  }, [setSettings]);
+
+  return (
+    <div className="p-6">
+      <label className="flex items-center gap-3 text-sm font-medium text-gray-700">
+        <input
+          type="checkbox"
+          checked={settings.upload_to_sftp}
+          onChange={(e) => setSettings({ upload_to_sftp: e.target.checked })}
+          className="w-4 h-4 rounded border-gray-300 text-blue-600 focus:ring-blue-500"
+        />
+        Gửi lô đã hoàn tất qua SFTP
+      </label>
+      <p className="text-sm text-gray-400 mt-2">
+        Đẩy thư mục hoặc file ZIP của lô lên máy chủ, ví dụ để giao cho công ty kế toán
+      </p>
+
+      {settings.upload_to_sftp && (
+        <div className="mt-4 grid grid-cols-2 gap-3">
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Máy chủ</label>
+            <input
+              type="text"
+              value={settings.sftp_host}
+              onChange={(e) => setSettings({ sftp_host: e.target.value })}
+              placeholder="sftp.ketoan.vn"
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Cổng</label>
+            <input
+              type="number"
+              min={1}
+              max={65535}
+              value={settings.sftp_port}
+              onChange={(e) => setSettings({ sftp_port: Math.min(65535, Math.max(1, Number(e.target.value) || 22)) })}
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Tên đăng nhập</label>
+            <input
+              type="text"
+              value={settings.sftp_username}
+              onChange={(e) => setSettings({ sftp_username: e.target.value })}
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">
+              {settings.sftp_private_key_path ? 'Mật khẩu khóa SSH' : 'Mật khẩu'}
+            </label>
+            <input
+              type="password"
+              value={settings.sftp_password}
+              onChange={(e) => setSettings({ sftp_password: e.target.value })}
+              className={inputClass}
+            />
+          </div>
+          <div className="col-span-2">
+            <label className="block text-sm text-gray-600 mb-1">Khóa SSH (không bắt buộc)</label>
+            <div className="flex gap-2">
+              <input
+                type="text"
+                value={settings.sftp_private_key_path}
+                onChange={(e) => setSettings({ sftp_private_key_path: e.target.value })}
+                placeholder="~/.ssh/id_ed25519"
+                className={inputClass}
+              />
+              <button
+                onClick={handleBrowseKey}
+                className="px-4 py-2.5 border border-gray-300 rounded-lg text-gray-700 hover:bg-gray-50 transition-colors"
+              >
+                Duyệt
+              </button>
+            </div>
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Thư mục trên máy chủ</label>
+            <input
+              type="text"
+              value={settings.sftp_remote_dir}
+              onChange={(e) => setSettings({ sftp_remote_dir: e.target.value })}
+              placeholder="/upload/hoadon"
+              className={inputClass}
+            />
+          </div>
+          <div>
+            <label className="block text-sm text-gray-600 mb-1">Gửi dưới dạng</label>
+            <select
+              value={settings.sftp_mode}
+              onChange={(e) => setSettings({ sftp_mode: e.target.value as SftpMode })}
+              className={inputClass}
+            >
+              <option value="folder">Thư mục</option>
+              <option value="zip">File ZIP</option>
+            </select>
+          </div>
+          <div className="col-span-2">
+            <label className="block text-sm text-gray-600 mb-1">Vân tay khóa máy chủ</label>
+            <input
+              type="text"
+              value={settings.sftp_host_key_fingerprint}
+              onChange={(e) => setSettings({ sftp_host_key_fingerprint: e.target.value })}
+              placeholder="SHA256:..."
+              className={inputClass}
+            />
+            <p className="text-sm text-gray-400 mt-1">
+              Để trống thì chấp nhận mọi khóa; vân tay máy chủ được ghi vào nhật ký sau lần gửi đầu tiên
+            </p>
+          </div>
+        </div>
+      )}
+    </div>
+  );
+}
@@ -1,7 +1,6 @@
 import { useState, useRef, useCallback } from 'react';
-import { invoke } from '@tauri-apps/api/core';
-import { open } from '@tauri-apps/plugin-dialog';
-import type { ExcelParseResult } from '../../types';
+import { commands, type ExcelParseResult, type SelectedFile } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
 
 interface ExcelUploaderProps {
   onParseComplete: (result: ExcelParseResult) => void;
@@ -15,27 +14,30 @@ export function ExcelUploader({ onParseComplete, onError }: ExcelUploaderProps)
   const fileInputRef = useRef<HTMLInputElement>(null);
 
   const handleFile = useCallback(
-    async (filePath: string) => {
-      if (!filePath.toLowerCase().endsWith('.xlsx')) {
-        onError('Vui lòng chọn file Excel (.xlsx)');
+    async (file: SelectedFile) => {
+      const lowerName = file.file_name.toLowerCase();
+      const isPdf = lowerName.endsWith('.pdf');
+      if (!lowerName.endsWith('.xlsx') && !isPdf) {
+        onError('Vui lòng chọn file Excel (.xlsx) hoặc PDF');
         return;
       }
 
       setIsLoading(true);
-      setFileName(filePath.split('/').pop() || filePath);
+      setFileName(file.file_name);
 
       try {
-        const result = await invoke<ExcelParseResult>('parse_excel', {
-          filePath,
-        });
+        // Codes found in a PDF's text go through the same preview as a sheet
+        const result = unwrap(
+          await (isPdf ? commands.parsePdfForCodes(file.token) : commands.parseExcel(file.token))
+        );
 
         if (result.invoices.length === 0) {
-          onError('Không tìm thấy mã hóa đơn trong file Excel');
+          onError(isPdf ? 'Không tìm thấy mã tra cứu trong file PDF' : 'Không tìm thấy mã hóa đơn trong file Excel');
         } else {
           onParseComplete(result);
         }
       } catch (err) {
-        onError(String(err));
+        onError(errorMessage(err));
       } finally {
         setIsLoading(false);
       }
@@ -70,21 +72,13 @@ export function ExcelUploader({ onParseComplete, onError }: ExcelUploaderProps)
 
   const handleClick = useCallback(async () => {
     try {
-      const selected = await open({
-        multiple: false,
-        filters: [
-          {
-            name: 'Excel Files',
-            extensions: ['xlsx'],
-          },
-        ],
-      });
-
-      if (selected && typeof selected === 'string') {
+      // The backend opens the dialog and only reads the file picked in it
+      const selected = unwrap(await commands.chooseSourceFile());
+      if (selected) {
         await handleFile(selected);
       }
     } catch (err) {
-      onError(String(err));
+      onError(errorMessage(err));
     }
   }, [handleFile, onError]);
 
@@ -155,11 +149,11 @@ export function ExcelUploader({ onParseComplete, onError }: ExcelUploaderProps)
           </>
         )}
       </div>
-      <p className="text-center text-sm text-gray-400 mt-4">Định dạng hỗ trợ: .xlsx</p>
+      <p className="text-center text-sm text-gray-400 mt-4">Định dạng hỗ trợ: .xlsx, .pdf</p>
       <input
         ref={fileInputRef}
         type="file"
-        accept=".xlsx"
+        accept=".xlsx,.pdf"
         className="hidden"
         onChange={() => {}}
       />
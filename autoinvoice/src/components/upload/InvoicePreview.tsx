@@ -1,8 +1,10 @@
-import type { InvoiceCode } from '../../types';
+import type { DetectedUrl, InvoiceCode } from '../../bindings';
 
 interface InvoicePreviewProps {
   invoices: InvoiceCode[];
-  detected_url: string | null;
+  detected_urls: DetectedUrl[];
+  selected_url: string | null;
+  onSelectUrl: (url: string) => void;
   sheet_name: string;
   total_rows: number;
   onProceed: () => void;
@@ -11,7 +13,9 @@ interface InvoicePreviewProps {
 
 export function InvoicePreview({
   invoices,
-  detected_url,
+  detected_urls,
+  selected_url,
+  onSelectUrl,
   sheet_name,
   total_rows,
   onProceed,
@@ -46,10 +50,40 @@ export function InvoicePreview({
           </div>
         </div>
 
-        {detected_url && (
+        {detected_urls.length === 1 && (
           <div className="bg-blue-50 rounded-lg p-4">
             <p className="text-sm text-blue-600 mb-1">URL VNPT được phát hiện</p>
-            <p className="text-sm font-mono text-blue-800 truncate">{detected_url}</p>
+            <p className="text-sm font-mono text-blue-800 truncate">{detected_urls[0].url}</p>
+          </div>
+        )}
+
+        {detected_urls.length > 1 && (
+          <div className="bg-amber-50 rounded-lg p-4">
+            <p className="text-sm text-amber-700 mb-2">
+              Phát hiện {detected_urls.length} URL VNPT khác nhau. Hóa đơn sẽ được chia thành
+              các lô con theo URL trên từng dòng. Chọn URL dùng cho các dòng không có URL:
+            </p>
+            <div className="space-y-2">
+              {detected_urls.map((detected) => (
+                <label key={detected.url} className="flex items-start gap-2 cursor-pointer">
+                  <input
+                    type="radio"
+                    name="detected-url"
+                    checked={selected_url === detected.url}
+                    onChange={() => onSelectUrl(detected.url)}
+                    className="mt-1"
+                  />
+                  <span className="min-w-0">
+                    <span className="block text-sm font-mono text-amber-900 truncate">
+                      {detected.url}
+                    </span>
+                    <span className="block text-xs text-amber-600">
+                      {detected.count} lần, dòng {detected.rows.join(', ')}
+                    </span>
+                  </span>
+                </label>
+              ))}
+            </div>
           </div>
         )}
       </div>
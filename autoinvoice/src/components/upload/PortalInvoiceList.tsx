@@ -0,0 +1,114 @@
+import { useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { commands, EVENT_NAMES, type BatchCompletedEvent, type ExcelParseResult } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
+
+interface PortalInvoiceListProps {
+  onParseComplete: (result: ExcelParseResult) => void;
+  onError: (error: string) => void;
+}
+
+/** First and last day of the previous month, as YYYY-MM-DD */
+function previousMonth(): [string, string] {
+  const now = new Date();
+  const first = new Date(now.getFullYear(), now.getMonth() - 1, 1);
+  const last = new Date(now.getFullYear(), now.getMonth(), 0);
+  const format = (date: Date) =>
+    `${date.getFullYear()}-${String(date.getMonth() + 1).padStart(2, '0')}-${String(date.getDate()).padStart(2, '0')}`;
+  return [format(first), format(last)];
+}
+
+/** Invoices listed by the portal account for a period, instead of an Excel file */
+export function PortalInvoiceList({ onParseComplete, onError }: PortalInvoiceListProps) {
+  const [[fromDate, toDate], setPeriod] = useState(previousMonth);
+  const [isLoading, setIsLoading] = useState(false);
+  const [isDownloading, setIsDownloading] = useState(false);
+  const [summary, setSummary] = useState<string | null>(null);
+
+  const handleList = async () => {
+    setIsLoading(true);
+    try {
+      const result = unwrap(await commands.listPortalInvoices(fromDate, toDate));
+      if (result.invoices.length === 0) {
+        onError('Không có hóa đơn nào trong kỳ này');
+      } else {
+        onParseComplete(result);
+      }
+    } catch (err) {
+      onError(errorMessage(err));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  // List every page and download the whole period as a batch in one go
+  const handleDownloadAll = async () => {
+    setIsDownloading(true);
+    setSummary(null);
+    const batchId = crypto.randomUUID();
+    // Returns at once; listing and downloading end with batch:completed
+    const unlisten = await listen<BatchCompletedEvent>(EVENT_NAMES.batchCompleted, (event) => {
+      const result = event.payload;
+      if (result.batch_id !== batchId) {
+        return;
+      }
+      unlisten();
+      setIsDownloading(false);
+      if (result.error) {
+        onError(result.error);
+        return;
+      }
+      setSummary(
+        `Đã tải ${result.success_count}/${result.total} hóa đơn` +
+          (result.failed_count > 0 ? `, ${result.failed_count} lỗi. Xem chi tiết trong Lịch sử.` : '.')
+      );
+    });
+    try {
+      unwrap(await commands.discoverInvoices(batchId, fromDate, toDate));
+    } catch (err) {
+      unlisten();
+      setIsDownloading(false);
+      onError(errorMessage(err));
+    }
+  };
+
+  const isBusy = isLoading || isDownloading;
+
+  return (
+    <div className="w-full max-w-xl mt-6 border border-gray-200 rounded-xl p-6">
+      <p className="text-sm font-medium text-gray-700 mb-3">Hoặc lấy danh sách từ tài khoản cổng</p>
+      <div className="flex flex-wrap items-center gap-3 text-sm text-gray-600">
+        <input
+          type="date"
+          value={fromDate}
+          onChange={(e) => setPeriod([e.target.value, toDate])}
+          className="px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        <span>đến</span>
+        <input
+          type="date"
+          value={toDate}
+          onChange={(e) => setPeriod([fromDate, e.target.value])}
+          className="px-3 py-2 border border-gray-300 rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+        />
+        <button
+          type="button"
+          onClick={handleList}
+          disabled={isBusy || !fromDate || !toDate}
+          className="ml-auto px-4 py-2 border border-blue-600 text-blue-600 rounded-lg hover:bg-blue-50 transition-colors disabled:opacity-50"
+        >
+          {isLoading ? 'Đang đăng nhập...' : 'Xem danh sách'}
+        </button>
+        <button
+          type="button"
+          onClick={handleDownloadAll}
+          disabled={isBusy || !fromDate || !toDate}
+          className="px-4 py-2 bg-blue-600 text-white rounded-lg hover:bg-blue-700 transition-colors disabled:opacity-50"
+        >
+          {isDownloading ? 'Đang tải...' : 'Tải tất cả'}
+        </button>
+      </div>
+      {summary && <p className="text-sm text-gray-600 mt-3">{summary}</p>}
+    </div>
+  );
+}
@@ -1,8 +1,10 @@
 import { useState, useEffect } from 'react';
 import { ExcelUploader } from './ExcelUploader';
 import { InvoicePreview } from './InvoicePreview';
+import { PortalInvoiceList } from './PortalInvoiceList';
 import { useDownload, useSettings } from '../../store';
-import type { ExcelParseResult } from '../../types';
+import { commands, type DownloadBatch, type ExcelParseResult } from '../../bindings';
+import { errorMessage, unwrap } from '../../utils/errors';
 
 interface UploadPageProps {
   onNavigateToDownload: () => void;
@@ -10,7 +12,10 @@ interface UploadPageProps {
 
 export function UploadPage({ onNavigateToDownload }: UploadPageProps) {
   const [parseResult, setParseResult] = useState<ExcelParseResult | null>(null);
+  const [selectedUrl, setSelectedUrl] = useState<string | null>(null);
   const [error, setError] = useState<string | null>(null);
+  // Completed batches already downloaded from the same file
+  const [previousBatches, setPreviousBatches] = useState<DownloadBatch[]>([]);
   const { setInvoices } = useDownload();
   const { settings, loadSettings } = useSettings();
 
@@ -21,7 +26,12 @@ export function UploadPage({ onNavigateToDownload }: UploadPageProps) {
 
   const handleParseComplete = (result: ExcelParseResult) => {
     setParseResult(result);
+    setSelectedUrl(result.detected_url);
     setError(null);
+    commands
+      .getBatchesForSource(result.file_hash)
+      .then((batches) => setPreviousBatches(unwrap(batches)))
+      .catch((err) => console.error('Failed to look up earlier batches:', err));
   };
 
   const handleError = (errorMessage: string) => {
@@ -29,6 +39,13 @@ export function UploadPage({ onNavigateToDownload }: UploadPageProps) {
     setParseResult(null);
   };
 
+  const handleLoadDemo = () => {
+    commands
+      .loadDemoInvoices()
+      .then((result) => handleParseComplete(unwrap(result)))
+      .catch((err) => handleError(errorMessage(err)));
+  };
+
   const handleProceed = () => {
     if (parseResult) {
       // Set invoices in store with pending status and default download directory
@@ -36,14 +53,20 @@ export function UploadPage({ onNavigateToDownload }: UploadPageProps) {
         ...inv,
         status: 'pending' as const,
       }));
-      setInvoices(invoicesWithStatus, parseResult.detected_url, settings.downloadDirectory);
+      setInvoices(invoicesWithStatus, selectedUrl, settings.download_directory, {
+        name: parseResult.file_name,
+        path: parseResult.file_path,
+        hash: parseResult.file_hash,
+      });
       onNavigateToDownload();
     }
   };
 
   const handleReset = () => {
     setParseResult(null);
+    setSelectedUrl(null);
     setError(null);
+    setPreviousBatches([]);
   };
 
   return (
@@ -90,18 +113,43 @@ export function UploadPage({ onNavigateToDownload }: UploadPageProps) {
         </div>
       )}
 
+      {parseResult && previousBatches.length > 0 && (
+        <div className="mb-6 bg-amber-50 border border-amber-200 text-amber-800 rounded-lg p-4 text-sm">
+          File này đã được tải xong trong lô{' '}
+          <span className="font-medium">
+            {previousBatches[0].name || new Date(previousBatches[0].created_at).toLocaleString('vi-VN')}
+          </span>
+          {previousBatches.length > 1 && ` và ${previousBatches.length - 1} lô khác`}. Tải lại có thể tạo hóa đơn trùng.
+        </div>
+      )}
+
       <div className="flex-1 flex items-center justify-center">
         {parseResult ? (
           <InvoicePreview
             invoices={parseResult.invoices}
-            detected_url={parseResult.detected_url}
+            detected_urls={parseResult.detected_urls}
+            selected_url={selectedUrl}
+            onSelectUrl={setSelectedUrl}
             sheet_name={parseResult.sheet_name}
             total_rows={parseResult.total_rows}
             onProceed={handleProceed}
             onReset={handleReset}
           />
         ) : (
-          <ExcelUploader onParseComplete={handleParseComplete} onError={handleError} />
+          <div className="flex flex-col items-center w-full">
+            <ExcelUploader onParseComplete={handleParseComplete} onError={handleError} />
+            {settings.portal_username && (
+              <PortalInvoiceList onParseComplete={handleParseComplete} onError={handleError} />
+            )}
+            {settings.demo_mode && (
+              <button
+                onClick={handleLoadDemo}
+                className="mt-4 px-4 py-2 border border-blue-300 text-blue-700 rounded-lg text-sm hover:bg-blue-50 transition-colors"
+              >
+                Dùng dữ liệu mẫu
+              </button>
+            )}
+          </div>
         )}
       </div>
     </div>
@@ -1 +1,2 @@
 export { useTauriEvents } from './useTauriEvents';
+export { useActiveDownloads } from './useActiveDownloads';
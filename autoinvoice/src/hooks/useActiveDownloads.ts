@@ -0,0 +1,42 @@
+import { useEffect } from 'react';
+import { commands } from '../bindings';
+import { useAppStore } from '../store';
+import { unwrap } from '../utils/errors';
+
+// After a reload the store is empty while the backend may still be downloading;
+// pick the newest running batch back up so the screen does not look idle
+export function useActiveDownloads() {
+  useEffect(() => {
+    const attach = async () => {
+      try {
+        const active = unwrap(await commands.getActiveDownloads());
+        const batch = active[active.length - 1];
+        if (!batch || useAppStore.getState().status !== 'idle') {
+          return;
+        }
+
+        const invoices = unwrap(await commands.getBatchInvoices(batch.batch_id));
+        useAppStore.getState().attachToBatch(
+          batch,
+          invoices.map((invoice, idx) => ({
+            id: invoice.id,
+            code: invoice.code,
+            row_number: idx + 1,
+            seller_tax_code: null,
+            lookup_mode: 'code' as const,
+            invoice_series: null,
+            invoice_number: null,
+            portal_url: null,
+            status: invoice.status,
+            error: invoice.error ?? undefined,
+            filePath: invoice.file_path ?? undefined,
+          }))
+        );
+      } catch (err) {
+        console.error('Failed to re-attach to active downloads:', err);
+      }
+    };
+
+    attach();
+  }, []);
+}
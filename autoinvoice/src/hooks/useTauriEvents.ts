@@ -1,19 +1,44 @@
 import { useEffect } from 'react';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
-import { useDownload } from '../store';
-import type { DownloadProgress, LogEntry, CaptchaRequest } from '../types';
+import { useAppStore, useDownload, useSettings } from '../store';
+import type { DownloadStatus } from '../store/downloadSlice';
+import {
+  EVENT_NAMES,
+  type BatchCompletedEvent,
+  type BatchStatus,
+  type BatchStatusEvent,
+  type BudgetExceededEvent,
+  type CaptchaRequiredEvent,
+  type ConnectivityEvent,
+  type DownloadsCancelledEvent,
+  type InvoiceStatusBatchEvent,
+  type InvoiceStatusEvent,
+  type InvoicesAvailableEvent,
+  type LogBatchEvent,
+  type LogEvent,
+  type ProgressEvent,
+  type SelectorsOutdatedEvent,
+  type SettingsChangedEvent,
+  type UploadProgressEvent,
+} from '../bindings';
+import type { LogEntry } from '../types';
 
-interface InvoiceStatusPayload {
-  invoice_id: string;
-  status: 'pending' | 'downloading' | 'success' | 'failed';
-  error?: string;
-}
+// Backend log levels are the ones the log viewer shows
+const toLogEntry = ({ timestamp, level, message, request_id }: LogEvent): LogEntry => ({
+  timestamp,
+  level: level as LogEntry['level'],
+  message,
+  request_id,
+});
 
-interface DownloadCompletePayload {
-  batch_id: string;
-  success_count: number;
-  failed_count: number;
-}
+// Download screen state for each batch lifecycle status (queued keeps the current state)
+const downloadStatusForBatch: Partial<Record<BatchStatus, DownloadStatus>> = {
+  running: 'downloading',
+  paused: 'paused',
+  completed: 'completed',
+  cancelled: 'cancelled',
+  failed: 'cancelled',
+};
 
 export function useTauriEvents() {
   const {
@@ -22,50 +47,144 @@ export function useTauriEvents() {
     updateInvoiceStatus,
     setCaptchaRequest,
     setStatus,
+    setUploadProgress,
+    setBudgetExceeded,
   } = useDownload();
+  const { loadSettings } = useSettings();
 
   useEffect(() => {
     const listeners: UnlistenFn[] = [];
 
     // Listen for download progress updates
-    listen<DownloadProgress>('download:progress', (event) => {
+    listen<ProgressEvent>(EVENT_NAMES.downloadProgress, (event) => {
       setProgress(event.payload);
     }).then((unlisten) => listeners.push(unlisten));
 
     // Listen for log messages
-    listen<LogEntry>('download:log', (event) => {
-      addLog(event.payload);
+    listen<LogEvent>(EVENT_NAMES.downloadLog, (event) => {
+      addLog(toLogEntry(event.payload));
+    }).then((unlisten) => listeners.push(unlisten));
+
+    listen<LogBatchEvent>(EVENT_NAMES.downloadLogBatch, (event) => {
+      event.payload.entries.forEach((entry) => addLog(toLogEntry(entry)));
     }).then((unlisten) => listeners.push(unlisten));
 
     // Listen for invoice status updates
-    listen<InvoiceStatusPayload>('invoice:status', (event) => {
+    listen<InvoiceStatusEvent>(EVENT_NAMES.invoiceStatus, (event) => {
       const { invoice_id, status, error } = event.payload;
-      updateInvoiceStatus(invoice_id, status, error);
+      updateInvoiceStatus(invoice_id, status, error ?? undefined);
+    }).then((unlisten) => listeners.push(unlisten));
+
+    listen<InvoiceStatusBatchEvent>(EVENT_NAMES.invoiceStatusBatch, (event) => {
+      event.payload.statuses.forEach(({ invoice_id, status, error }) =>
+        updateInvoiceStatus(invoice_id, status, error ?? undefined)
+      );
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for batch lifecycle changes
+    listen<BatchStatusEvent>(EVENT_NAMES.batchStatus, (event) => {
+      // Sub-batches of a split batch report their own lifecycle; only the batch on screen counts
+      if (event.payload.batch_id !== useAppStore.getState().batchId) {
+        return;
+      }
+      const status = downloadStatusForBatch[event.payload.status];
+      if (status) {
+        setStatus(status);
+      }
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for every batch being cancelled at once, from the button or the app menu
+    listen<DownloadsCancelledEvent>(EVENT_NAMES.downloadsCancelled, (event) => {
+      const { batch_ids } = event.payload;
+      if (batch_ids.includes(useAppStore.getState().batchId ?? '')) {
+        setStatus('cancelled');
+      }
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: 'warn',
+        message: `Đã hủy tất cả (${batch_ids.length} lô)`,
+      });
     }).then((unlisten) => listeners.push(unlisten));
 
     // Listen for captcha requests (auto-solve failed)
-    listen<CaptchaRequest>('captcha:required', (event) => {
+    listen<CaptchaRequiredEvent>(EVENT_NAMES.captchaRequired, (event) => {
       setCaptchaRequest(event.payload);
     }).then((unlisten) => listeners.push(unlisten));
 
-    // Listen for download completion
-    listen<DownloadCompletePayload>('download:complete', (event) => {
-      const { success_count, failed_count } = event.payload;
-      setStatus('completed');
+    // Listen for a finished batch being uploaded to WebDAV or a cloud drive
+    listen<UploadProgressEvent>(EVENT_NAMES.uploadProgress, (event) => {
+      if (event.payload.batch_id === useAppStore.getState().batchId) {
+        setUploadProgress(event.payload);
+      }
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for network loss and recovery
+    listen<ConnectivityEvent>(EVENT_NAMES.connectivityChanged, (event) => {
+      const { online, queued_batches } = event.payload;
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: online ? 'info' : 'warn',
+        message: online
+          ? `Đã có kết nối mạng (${queued_batches} lô đang chờ)`
+          : 'Mất kết nối mạng',
+      });
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for the startup check finding selectors older than the portal HTML
+    listen<SelectorsOutdatedEvent>(EVENT_NAMES.selectorsOutdated, (event) => {
+      const { local_version, min_version, message } = event.payload;
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: 'warn',
+        message: `Bộ chọn trang VNPT đã cũ (phiên bản ${local_version}, cần ${min_version}). ${
+          message ?? 'Vui lòng cập nhật ứng dụng.'
+        }`,
+      });
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for invoices missing on an earlier run becoming available
+    listen<InvoicesAvailableEvent>(EVENT_NAMES.invoicesAvailable, (event) => {
+      const { codes } = event.payload;
       addLog({
         timestamp: new Date().toISOString(),
         level: 'info',
-        message: `Download complete: ${success_count} succeeded, ${failed_count} failed`,
+        message: `${codes.length} hóa đơn trước đây chưa có đã tải được: ${codes.join(', ')}`,
+      });
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for a batch reaching its AI captcha budget
+    listen<BudgetExceededEvent>(EVENT_NAMES.budgetExceeded, (event) => {
+      const { batch_id, scope, limit_usd, spent_usd, action } = event.payload;
+      if (batch_id === useAppStore.getState().batchId) {
+        setBudgetExceeded(event.payload);
+      }
+      addLog({
+        timestamp: new Date().toISOString(),
+        level: 'warn',
+        message: `Đã hết ngân sách AI ${scope === 'batch' ? 'của lô' : 'của tháng'} ($${limit_usd.toFixed(2)}, đã dùng $${spent_usd.toFixed(4)}). ${
+          action === 'pause' ? 'Lô đã tạm dừng.' : 'Các captcha còn lại cần nhập tay.'
+        }`,
       });
     }).then((unlisten) => listeners.push(unlisten));
 
-    // Listen for download errors
-    listen<string>('download:error', (event) => {
-      setStatus('cancelled');
+    // Settings saved anywhere in the app, e.g. the folder picked on the download page
+    listen<SettingsChangedEvent>(EVENT_NAMES.settingsChanged, () => {
+      loadSettings();
+    }).then((unlisten) => listeners.push(unlisten));
+
+    // Listen for the end of a batch run, including one that could not start
+    listen<BatchCompletedEvent>(EVENT_NAMES.batchCompleted, (event) => {
+      const { batch_id, status, total, success_count, failed_count, error } = event.payload;
+      if (batch_id !== useAppStore.getState().batchId) {
+        return;
+      }
+      setStatus(downloadStatusForBatch[status] ?? 'completed');
       addLog({
         timestamp: new Date().toISOString(),
-        level: 'error',
-        message: `Download error: ${event.payload}`,
+        level: error ? 'error' : 'info',
+        message: error
+          ? `Lô tải bị lỗi: ${error}`
+          : `Hoàn tất: ${success_count}/${total} thành công, ${failed_count} lỗi`,
       });
     }).then((unlisten) => listeners.push(unlisten));
 
@@ -73,5 +192,14 @@ export function useTauriEvents() {
     return () => {
       listeners.forEach((unlisten) => unlisten());
     };
-  }, [setProgress, addLog, updateInvoiceStatus, setCaptchaRequest, setStatus]);
+  }, [
+    setProgress,
+    addLog,
+    updateInvoiceStatus,
+    setCaptchaRequest,
+    setStatus,
+    setUploadProgress,
+    setBudgetExceeded,
+    loadSettings,
+  ]);
 }
@@ -1,52 +1,77 @@
 import type { StateCreator } from 'zustand';
-import type { InvoiceCode, DownloadProgress, LogEntry, CaptchaRequest } from '../types';
+import type {
+  ActiveDownload,
+  BudgetExceededEvent,
+  CaptchaRequiredEvent,
+  ProgressEvent,
+  UploadProgressEvent,
+} from '../bindings';
+import type { InvoiceRow, LogEntry, SourceFile } from '../types';
 
-export type DownloadStatus = 'idle' | 'parsing' | 'ready' | 'downloading' | 'paused' | 'completed' | 'cancelled';
+export type DownloadStatus = 'idle' | 'parsing' | 'ready' | 'queued' | 'downloading' | 'paused' | 'completed' | 'cancelled';
 
 export interface DownloadSlice {
   // State
   status: DownloadStatus;
-  invoices: InvoiceCode[];
+  invoices: InvoiceRow[];
   detectedVnptUrl: string | null;
-  progress: DownloadProgress | null;
+  progress: ProgressEvent | null;
   logs: LogEntry[];
-  captchaRequest: CaptchaRequest | null;
+  captchaRequest: CaptchaRequiredEvent | null;
   downloadDirectory: string;
   batchId: string | null;
+  uploadProgress: UploadProgressEvent | null;
+  // Set while the batch is held back by its AI captcha budget
+  budgetExceeded: BudgetExceededEvent | null;
+  // Excel file the invoices were imported from
+  sourceFile: SourceFile | null;
 
   // Actions
-  setInvoices: (invoices: InvoiceCode[], detectedUrl: string | null, defaultDirectory?: string) => void;
-  updateInvoiceStatus: (id: string, status: InvoiceCode['status'], error?: string) => void;
-  setProgress: (progress: DownloadProgress) => void;
+  setInvoices: (
+    invoices: InvoiceRow[],
+    detectedUrl: string | null,
+    defaultDirectory?: string,
+    sourceFile?: SourceFile
+  ) => void;
+  updateInvoiceStatus: (id: string, status: InvoiceRow['status'], error?: string) => void;
+  setProgress: (progress: ProgressEvent) => void;
   addLog: (log: LogEntry) => void;
   clearLogs: () => void;
-  setCaptchaRequest: (request: CaptchaRequest | null) => void;
+  setCaptchaRequest: (request: CaptchaRequiredEvent | null) => void;
   setDownloadDirectory: (dir: string) => void;
   setStatus: (status: DownloadStatus) => void;
   setBatchId: (id: string) => void;
+  setUploadProgress: (progress: UploadProgressEvent | null) => void;
+  setBudgetExceeded: (budget: BudgetExceededEvent | null) => void;
+  // Show a batch that is already running in the backend
+  attachToBatch: (batch: ActiveDownload, invoices: InvoiceRow[]) => void;
   reset: () => void;
 }
 
 const initialState = {
   status: 'idle' as DownloadStatus,
-  invoices: [] as InvoiceCode[],
+  invoices: [] as InvoiceRow[],
   detectedVnptUrl: null as string | null,
-  progress: null as DownloadProgress | null,
+  progress: null as ProgressEvent | null,
   logs: [] as LogEntry[],
-  captchaRequest: null as CaptchaRequest | null,
+  captchaRequest: null as CaptchaRequiredEvent | null,
   downloadDirectory: '',
   batchId: null as string | null,
+  uploadProgress: null as UploadProgressEvent | null,
+  budgetExceeded: null as BudgetExceededEvent | null,
+  sourceFile: null as SourceFile | null,
 };
 
 export const createDownloadSlice: StateCreator<DownloadSlice> = (set) => ({
   ...initialState,
 
-  setInvoices: (invoices, detectedUrl, defaultDirectory) => {
+  setInvoices: (invoices, detectedUrl, defaultDirectory, sourceFile) => {
     set((state) => ({
       invoices,
       detectedVnptUrl: detectedUrl,
       status: 'ready',
       downloadDirectory: defaultDirectory || state.downloadDirectory,
+      sourceFile: sourceFile ?? null,
     }));
   },
 
@@ -85,7 +110,31 @@ export const createDownloadSlice: StateCreator<DownloadSlice> = (set) => ({
   },
 
   setBatchId: (id) => {
-    set({ batchId: id });
+    set({ batchId: id, uploadProgress: null, budgetExceeded: null });
+  },
+
+  setUploadProgress: (progress) => {
+    set({ uploadProgress: progress });
+  },
+
+  setBudgetExceeded: (budget) => {
+    set({ budgetExceeded: budget });
+  },
+
+  attachToBatch: (batch, invoices) => {
+    set({
+      batchId: batch.batch_id,
+      invoices,
+      status: batch.status === 'paused' ? 'paused' : 'downloading',
+      progress: {
+        batch_id: batch.batch_id,
+        current: batch.current,
+        total: batch.total,
+        percentage: batch.percentage,
+      },
+      uploadProgress: null,
+      budgetExceeded: null,
+    });
   },
 
   reset: () => {
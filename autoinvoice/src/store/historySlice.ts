@@ -1,6 +1,6 @@
-import { invoke } from '@tauri-apps/api/core';
 import type { StateCreator } from 'zustand';
-import type { DownloadBatch, HistoryInvoice } from '../types';
+import { commands, type DownloadBatch, type HistoryInvoice } from '../bindings';
+import { unwrap } from '../utils/errors';
 
 export interface HistorySlice {
   // State
@@ -44,7 +44,7 @@ export const createHistorySlice: StateCreator<HistorySlice> = (set, get) => ({
   loadBatches: async () => {
     set({ isLoading: true });
     try {
-      const batches = await invoke<DownloadBatch[]>('get_batches');
+      const batches = unwrap(await commands.getBatches());
       set({ batches, isLoading: false });
     } catch (err) {
       console.error('Failed to load batches:', err);
@@ -55,7 +55,7 @@ export const createHistorySlice: StateCreator<HistorySlice> = (set, get) => ({
   loadBatchInvoices: async (batchId) => {
     set({ isLoading: true });
     try {
-      const invoices = await invoke<HistoryInvoice[]>('get_batch_invoices', { batchId });
+      const invoices = unwrap(await commands.getBatchInvoices(batchId));
       set({ batchInvoices: invoices, isLoading: false });
     } catch (err) {
       console.error('Failed to load batch invoices:', err);
@@ -65,7 +65,7 @@ export const createHistorySlice: StateCreator<HistorySlice> = (set, get) => ({
 
   deleteBatch: async (batchId) => {
     try {
-      await invoke('delete_batch', { batchId });
+      unwrap(await commands.deleteBatch(batchId));
       // Refresh batches after deletion
       const batches = get().batches.filter((b) => b.id !== batchId);
       set({ batches });
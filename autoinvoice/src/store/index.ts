@@ -36,6 +36,9 @@ export const useDownload = () =>
       captchaRequest: state.captchaRequest,
       downloadDirectory: state.downloadDirectory,
       batchId: state.batchId,
+      uploadProgress: state.uploadProgress,
+      budgetExceeded: state.budgetExceeded,
+      sourceFile: state.sourceFile,
       setInvoices: state.setInvoices,
       updateInvoiceStatus: state.updateInvoiceStatus,
       setProgress: state.setProgress,
@@ -45,6 +48,9 @@ export const useDownload = () =>
       setDownloadDirectory: state.setDownloadDirectory,
       setStatus: state.setStatus,
       setBatchId: state.setBatchId,
+      setUploadProgress: state.setUploadProgress,
+      setBudgetExceeded: state.setBudgetExceeded,
+      attachToBatch: state.attachToBatch,
       reset: state.reset,
     }))
   );
@@ -1,13 +1,6 @@
-import { invoke } from '@tauri-apps/api/core';
 import type { StateCreator } from 'zustand';
-import type { Settings } from '../types';
-
-// Backend uses snake_case
-interface BackendSettings {
-  openai_api_key: string;
-  vnpt_url: string;
-  download_directory: string;
-}
+import { commands, type Settings } from '../bindings';
+import { unwrap } from '../utils/errors';
 
 export interface SettingsSlice {
   settings: Settings;
@@ -19,9 +12,92 @@ export interface SettingsSlice {
 
 export const createSettingsSlice: StateCreator<SettingsSlice> = (set, get) => ({
   settings: {
-    openaiApiKey: '',
-    vnptUrl: '',
-    downloadDirectory: '',
+    openai_api_key: '',
+    openai_backup_api_keys: [],
+    use_azure_openai: false,
+    azure_openai_endpoint: '',
+    azure_openai_deployment: '',
+    azure_openai_api_version: '2024-06-01',
+    vnpt_url: '',
+    download_directory: '',
+    invoice_delay_secs: 2,
+    quiet_hours_start: '',
+    quiet_hours_end: '',
+    fallback_to_tax_portal: false,
+    verify_not_found: false,
+    not_found_verify_delay_secs: 60,
+    retry_not_found_hours: 24,
+    auto_start_queued: false,
+    collect_metrics: false,
+    manual_captcha_timeout_secs: 120,
+    post_download_hook: '',
+    post_download_hook_scope: 'invoice',
+    upload_to_s3: false,
+    s3_endpoint: '',
+    s3_region: '',
+    s3_bucket: '',
+    s3_access_key: '',
+    s3_secret_key: '',
+    s3_prefix: '',
+    cloud_provider: 'off',
+    cloud_client_id: '',
+    cloud_client_secret: '',
+    cloud_folder: '',
+    upload_to_webdav: false,
+    webdav_url: '',
+    webdav_username: '',
+    webdav_password: '',
+    upload_to_sftp: false,
+    sftp_host: '',
+    sftp_port: 22,
+    sftp_username: '',
+    sftp_password: '',
+    sftp_private_key_path: '',
+    sftp_remote_dir: '',
+    sftp_mode: 'folder',
+    sftp_host_key_fingerprint: '',
+    download_xml: false,
+    pdf_variant: 'original',
+    ocr_scanned_pdfs: false,
+    tesseract_path: '',
+    captcha_lookahead: false,
+    local_captcha_solver: false,
+    local_captcha_min_confidence: 0.9,
+    collect_captcha_dataset: false,
+    captcha_dataset_dir: '',
+    persistent_browser_profiles: false,
+    demo_mode: false,
+    duplicate_files: 'keep',
+    file_name_template: '',
+    folder_template: '',
+    file_name_transliteration: 'keep',
+    csv_delimiter: 'comma',
+    csv_decimal_comma: false,
+    csv_bom: false,
+    csv_day_first_dates: false,
+    optimize_pdfs: false,
+    keep_original_pdfs: false,
+    linearize_pdfs: false,
+    qpdf_path: '',
+    stamp_pdfs: false,
+    ai_budget_per_batch_usd: 0,
+    ai_budget_per_month_usd: 0,
+    ai_budget_action: 'manual-captcha',
+    browser_window_width: 1920,
+    browser_window_height: 1080,
+    browser_device_scale_factor: 1,
+    browser_zoom_percent: 100,
+    captcha_padding_px: 4,
+    portal_login_url: '',
+    portal_invoice_list_url: '',
+    portal_username: '',
+    portal_password: '',
+    inbox_enabled: false,
+    imap_host: '',
+    imap_port: 993,
+    imap_username: '',
+    imap_password: '',
+    imap_folder: 'INBOX',
   },
   settingsLoading: false,
 
@@ -34,15 +110,8 @@ export const createSettingsSlice: StateCreator<SettingsSlice> = (set, get) => ({
   loadSettings: async () => {
     set({ settingsLoading: true });
     try {
-      const backendSettings = await invoke<BackendSettings>('get_settings');
-      set({
-        settings: {
-          openaiApiKey: backendSettings.openai_api_key,
-          vnptUrl: backendSettings.vnpt_url,
-          downloadDirectory: backendSettings.download_directory,
-        },
-        settingsLoading: false,
-      });
+      const settings = unwrap(await commands.getSettings());
+      set({ settings, settingsLoading: false });
     } catch (err) {
       console.error('Failed to load settings:', err);
       set({ settingsLoading: false });
@@ -52,13 +121,7 @@ export const createSettingsSlice: StateCreator<SettingsSlice> = (set, get) => ({
   saveSettings: async () => {
     const { settings } = get();
     try {
-      await invoke('save_settings', {
-        settings: {
-          openai_api_key: settings.openaiApiKey,
-          vnpt_url: settings.vnptUrl,
-          download_directory: settings.downloadDirectory,
-        },
-      });
+      unwrap(await commands.saveSettings(settings));
     } catch (err) {
       console.error('Failed to save settings:', err);
       throw err;
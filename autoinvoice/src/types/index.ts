@@ -1,68 +1,27 @@
+// Payloads of commands and events are the generated types of `../bindings`;
+// only state of the UI itself is declared here
+import type { InvoiceCode, InvoiceStatus, LogEvent } from '../bindings';
+
 // Navigation types
 export type NavItem = 'upload' | 'download' | 'history' | 'settings';
 
-// Invoice types from Excel parsing
-export interface InvoiceCode {
-  id: string;
-  code: string;
-  row_number: number;
-  status: 'pending' | 'downloading' | 'success' | 'failed';
+// Invoice on the upload and download screens, with its download outcome
+export interface InvoiceRow extends InvoiceCode {
+  status: InvoiceStatus;
   error?: string;
   filePath?: string;
 }
 
-// Excel parse result from Tauri backend
-export interface ExcelParseResult {
-  invoices: InvoiceCode[];
-  detected_url: string | null;
-  total_rows: number;
-  sheet_name: string;
-}
-
-// Download state
-export interface DownloadProgress {
-  batchId: string;
-  current: number;
-  total: number;
-  percentage: number;
-}
-
-export interface LogEntry {
-  timestamp: string;
-  level: 'info' | 'warn' | 'error';
-  message: string;
-}
-
-// Settings
-export interface Settings {
-  openaiApiKey: string;
-  vnptUrl: string;
-  downloadDirectory: string;
-}
-
-// History - snake_case to match Rust backend
-export interface DownloadBatch {
-  id: string;
-  created_at: string;
-  total_count: number;
-  success_count: number;
-  failed_count: number;
-  download_directory: string;
-}
-
-export interface HistoryInvoice {
-  id: string;
-  batch_id: string;
-  code: string;
-  status: string;
-  error: string | null;
-  file_path: string | null;
-  downloaded_at: string | null;
+// Excel file a batch is imported from
+export interface SourceFile {
+  name: string;
+  path: string;
+  hash: string;
 }
 
-// Captcha - snake_case to match Rust backend
-export interface CaptchaRequest {
-  invoice_id: string;
-  invoice_code: string;
-  image_base64: string;
+// Line of the log viewer, from the backend or written by the UI itself
+export interface LogEntry extends Omit<LogEvent, 'batch_id' | 'level' | 'request_id'> {
+  level: 'debug' | 'info' | 'warn' | 'error';
+  // Finds the entry in the backend log
+  request_id?: string | null;
 }
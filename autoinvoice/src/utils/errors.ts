@@ -0,0 +1,18 @@
+import type { Result, StructuredError } from '../bindings';
+
+// Text to show for an error a command rejected with
+export function errorMessage(err: unknown): string {
+  if (typeof err === 'object' && err !== null && (err as StructuredError).kind === 'invalid-argument') {
+    const { argument, reason } = err as StructuredError;
+    return `Invalid argument '${argument}': ${reason}`;
+  }
+  return String(err);
+}
+
+// Data of a command's result, throwing its error so callers keep their try/catch
+export function unwrap<T, E>(result: Result<T, E>): T {
+  if (result.status === 'error') {
+    throw result.error;
+  }
+  return result.data;
+}